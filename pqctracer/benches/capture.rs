@@ -0,0 +1,53 @@
+//! Requests per second through one client at growing concurrency, against a
+//! loopback server, to see what capturing each request's handshake costs
+//! when many run at once. Run with `cargo bench --bench capture`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_util::future::join_all;
+use pqctracer::testutil::TestTlsServer;
+
+/// Requests per round; each round runs them `concurrency` at a time.
+const REQUESTS: usize = 512;
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("build runtime");
+    runtime.block_on(async {
+        let server = common::start(TestTlsServer::builder()).await;
+        let url = server.url();
+        println!("{:>12} {:>16} {:>16}", "concurrency", "pooled req/s", "fresh req/s");
+        for concurrency in [1, 8, 64, 256] {
+            let client = Arc::new(common::insecure_client());
+            let pooled = rate(concurrency, || client.clone(), &url).await;
+            let fresh = rate(concurrency, || Arc::new(common::insecure_client()), &url).await;
+            println!("{:>12} {:>16.0} {:>16.0}", concurrency, pooled, fresh);
+        }
+    });
+}
+
+/// Requests per second for `REQUESTS` requests to `url`, `concurrency` at a
+/// time, each through the client `client` gives it.
+async fn rate(
+    concurrency: usize,
+    client: impl Fn() -> Arc<pqctracer::TlsAwareClient>,
+    url: &str,
+) -> f64 {
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..REQUESTS / concurrency {
+        let batch: Vec<_> = (0..concurrency).map(|_| client()).collect();
+        let start = Instant::now();
+        join_all(batch.iter().map(|client| async move {
+            client.get(url).send().await.expect("request");
+        }))
+        .await;
+        elapsed += start.elapsed();
+    }
+    REQUESTS as f64 / elapsed.as_secs_f64()
+}
@@ -0,0 +1,60 @@
+/*
+ * C interface of pqctracer, built as a shared library by the
+ * pqctracer-ffi crate:
+ *
+ *     cargo build --release -p pqctracer-ffi
+ *
+ * All functions may be called from several threads at once.
+ */
+
+#ifndef PQCTRACER_H
+#define PQCTRACER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Request `url` and return the negotiated TLS parameters as a JSON object,
+ * to be freed with pqc_free_string(). `options_json` may be NULL, or a JSON
+ * object with any of "head" (bool), "timeout_ms" (number), "kx"
+ * ("hybrid-preferred" or "classical-only") and "insecure" (bool). Returns
+ * NULL on failure; pqc_last_error() then says why.
+ */
+char *pqc_trace_url(const char *url, const char *options_json);
+
+/*
+ * Trace every target of `targets_json`, a JSON array of URLs and addresses,
+ * and return a JSON array with a result object for each, to be freed with
+ * pqc_free_string(). Targets that fail have their error in their result.
+ * `options_json` may be NULL, or a JSON object with any of the options of
+ * pqc_trace_url() ("head" defaults to true here), "concurrency" (number),
+ * "max_per_host" (number or null), "retries" (number) and "rate" (requests
+ * per second). Returns NULL on invalid arguments; pqc_last_error() then
+ * says why.
+ */
+char *pqc_scan(const char *targets_json, const char *options_json);
+
+/* Free a string returned by pqc_trace_url() or pqc_scan(); NULL is ignored. */
+void pqc_free_string(char *s);
+
+/*
+ * Why the calling thread's last pqc_trace_url() or pqc_scan() failed, or
+ * NULL if it succeeded. Owned by the library and valid until the thread's
+ * next call; do not free it.
+ */
+const char *pqc_last_error(void);
+
+/*
+ * The category of the calling thread's last failed request as a JSON
+ * object, e.g. {"kind":"connect-refused"}, or NULL if the last call
+ * succeeded or rejected its arguments. Owned by the library like the
+ * string of pqc_last_error().
+ */
+const char *pqc_last_error_category(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PQCTRACER_H */
@@ -0,0 +1,7 @@
+//! The shared library of the C interface: `libpqctracer.so`, exporting the
+//! functions `include/pqctracer.h` declares. They are the tracer's own,
+//! compiled in by its `ffi` feature; building the shared library in a
+//! crate of its own keeps crates that depend on the tracer to an rlib.
+
+// Linked for its `#[no_mangle]` functions, which the library exports.
+use tracer as _;
@@ -0,0 +1,71 @@
+//! Compiles `tests/smoke.c` against the shared library and runs it
+//! against a loopback server. Needs a C compiler, `cc` or `$CC`.
+
+#![cfg(unix)]
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use tracer::testutil::TestTlsServer;
+
+/// The directory the shared library was built into, next to the tests.
+fn library_dir() -> PathBuf {
+    let deps = std::env::current_exe().expect("test executable");
+    let deps = deps.parent().expect("deps directory");
+    let name = format!(
+        "{}pqctracer{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    [deps, deps.parent().expect("profile directory")]
+        .into_iter()
+        .find(|dir| dir.join(&name).exists())
+        .unwrap_or_else(|| panic!("{} not found next to {}", name, deps.display()))
+        .to_path_buf()
+}
+
+#[test]
+fn the_c_smoke_test_passes() {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let library = library_dir();
+    let program = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ffi-smoke");
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let compiled = Command::new(&compiler)
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest.join("include"))
+        .arg(manifest.join("tests/smoke.c"))
+        .arg("-L")
+        .arg(&library)
+        .arg("-lpqctracer")
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .unwrap_or_else(|e| panic!("failed to run {}: {}", compiler, e));
+    assert!(compiled.success(), "compiling smoke.c failed");
+
+    let runtime = tokio::runtime::Runtime::new().expect("runtime");
+    let server = runtime
+        .block_on(TestTlsServer::builder().bind("127.0.0.1:0"))
+        .expect("bind test server");
+    let output = Command::new(&program)
+        .arg(server.url())
+        .env("LD_LIBRARY_PATH", &library)
+        .env("DYLD_LIBRARY_PATH", &library)
+        .output()
+        .expect("run smoke test");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        output.status.success(),
+        "smoke test failed:\n{}\n{}",
+        stdout,
+        stderr
+    );
+    assert!(
+        stdout.contains("\"group\":\"X25519MLKEM768\""),
+        "{}",
+        stdout
+    );
+}
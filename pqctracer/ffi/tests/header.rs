@@ -0,0 +1,88 @@
+//! Checks `include/pqctracer.h` against the `extern "C"` functions of the
+//! tracer's `src/ffi.rs`, so that the hand-written header cannot drift
+//! from what the library exports.
+
+use std::path::Path;
+
+/// The source file at `path` relative to this crate.
+fn read(path: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+/// `text` with every run of whitespace turned into one space.
+fn squeeze(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The C spelling of a Rust parameter or return type, ending where the
+/// name would follow.
+fn c_type(rust: &str) -> &'static str {
+    match rust {
+        "*const c_char" => "const char *",
+        "*mut c_char" => "char *",
+        "" => "void ",
+        other => panic!("no C spelling for {}", other),
+    }
+}
+
+/// The prototypes of the `extern "C"` functions in `source`, as the header
+/// would spell them.
+fn rust_prototypes(source: &str) -> Vec<String> {
+    source
+        .split("extern \"C\" fn ")
+        .skip(1)
+        .map(|function| {
+            let signature = squeeze(&function[..function.find('{').expect("function body")]);
+            let (name, rest) = signature.split_once('(').expect("parameters");
+            let (parameters, returned) = rest.rsplit_once(')').expect("parameters");
+            let returned = returned.trim().trim_start_matches("->").trim();
+            let parameters: Vec<String> = parameters
+                .split(',')
+                .map(str::trim)
+                .filter(|parameter| !parameter.is_empty())
+                .map(|parameter| {
+                    let (name, ty) = parameter.split_once(':').expect("typed parameter");
+                    format!("{}{}", c_type(ty.trim()), name.trim())
+                })
+                .collect();
+            let parameters = match parameters.is_empty() {
+                true => "void".to_string(),
+                false => parameters.join(", "),
+            };
+            format!("{}{}({})", c_type(returned), name.trim(), parameters)
+        })
+        .collect()
+}
+
+/// The prototypes `header` declares, comments removed.
+fn header_prototypes(header: &str) -> Vec<String> {
+    let mut code = String::new();
+    let mut rest = header;
+    while let Some(start) = rest.find("/*") {
+        code.push_str(&rest[..start]);
+        let end = rest[start..].find("*/").expect("closed comment");
+        rest = &rest[start + end + 2..];
+    }
+    code.push_str(rest);
+    // What is left besides the declarations is the preprocessor's and the
+    // C++ guard's.
+    let declarations: Vec<&str> = code
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#') && *line != "extern \"C\" {" && *line != "}")
+        .collect();
+    declarations
+        .join(" ")
+        .split(';')
+        .map(squeeze)
+        .filter(|declaration| !declaration.is_empty())
+        .collect()
+}
+
+#[test]
+fn the_header_declares_every_exported_function() {
+    let exported = rust_prototypes(&read("../src/ffi.rs"));
+    assert_eq!(exported.len(), 5, "{:?}", exported);
+    assert_eq!(header_prototypes(&read("include/pqctracer.h")), exported);
+}
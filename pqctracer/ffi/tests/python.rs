@@ -0,0 +1,47 @@
+//! Runs the unittest suite of the Python bindings in `python/tests` against
+//! a loopback server. Needs Python 3, `python3` or `$PYTHON`.
+
+#![cfg(unix)]
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use tracer::testutil::TestTlsServer;
+
+/// The shared library that was built next to the tests.
+fn library() -> PathBuf {
+    let deps = std::env::current_exe().expect("test executable");
+    let deps = deps.parent().expect("deps directory");
+    let name = format!(
+        "{}pqctracer{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    [deps, deps.parent().expect("profile directory")]
+        .into_iter()
+        .map(|dir| dir.join(&name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| panic!("{} not found next to {}", name, deps.display()))
+}
+
+#[test]
+fn the_python_tests_pass() {
+    let python = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../python");
+    let runtime = tokio::runtime::Runtime::new().expect("runtime");
+    let server = runtime
+        .block_on(TestTlsServer::builder().bind("127.0.0.1:0"))
+        .expect("bind test server");
+    let interpreter = std::env::var("PYTHON").unwrap_or_else(|_| "python3".to_string());
+    let output = Command::new(&interpreter)
+        .args(["-m", "unittest", "discover", "-v", "-s", "tests"])
+        .current_dir(&python)
+        .env("PYTHONPATH", &python)
+        .env("PQCTRACER_LIBRARY", library())
+        .env("PQCTRACER_TEST_URL", server.url())
+        .output()
+        .unwrap_or_else(|e| panic!("failed to run {}: {}", interpreter, e));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "Python tests failed:\n{}", stderr);
+    // unittest reports on stderr; make sure nothing was skipped.
+    assert!(!stderr.contains("skipped"), "{}", stderr);
+}
@@ -0,0 +1,89 @@
+/*
+ * Calls every function of the C interface: traces the URL given as the
+ * first argument, which serves a self-signed certificate, and checks the
+ * failures a caller can run into. Exits with 0 when everything behaved.
+ *
+ *     cc -Iinclude tests/smoke.c -L../target/debug -lpqctracer
+ *     ./a.out https://localhost:8443
+ */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "pqctracer.h"
+
+static int failures = 0;
+
+static void check(int ok, const char *what)
+{
+    if (!ok) {
+        fprintf(stderr, "FAILED: %s\n", what);
+        failures++;
+    }
+}
+
+/*
+ * Expect pqc_trace_url(url, options) to fail with an error containing
+ * `reason` and a category containing `category`, or none if it is NULL.
+ */
+static void check_fails(const char *url, const char *options, const char *reason,
+                        const char *category)
+{
+    char *json = pqc_trace_url(url, options);
+    const char *error = pqc_last_error();
+    const char *error_category = pqc_last_error_category();
+    check(json == NULL, reason);
+    check(error != NULL && strstr(error, reason) != NULL, reason);
+    if (category == NULL) {
+        check(error_category == NULL, "no category for rejected arguments");
+    } else {
+        check(error_category != NULL && strstr(error_category, category) != NULL, category);
+    }
+    if (error != NULL) {
+        printf("expected error: %s (%s)\n", error, error_category ? error_category : "-");
+    }
+    pqc_free_string(json);
+}
+
+int main(int argc, char **argv)
+{
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <https-url>\n", argv[0]);
+        return 2;
+    }
+
+    char *json = pqc_trace_url(argv[1], "{\"insecure\": true, \"timeout_ms\": 5000}");
+    check(json != NULL, "trace with insecure=true");
+    check(pqc_last_error() == NULL, "no error after a successful trace");
+    if (json != NULL) {
+        puts(json);
+        check(strstr(json, "\"group\":\"") != NULL, "the metadata has a group");
+        check(strstr(json, "\"cipher\":\"") != NULL, "the metadata has a cipher");
+    }
+    pqc_free_string(json);
+
+    /* The certificate is self-signed, so verification fails by default. */
+    check_fails(argv[1], NULL, "certificate", "\"kind\":\"certificate-invalid\"");
+    check_fails(argv[1], "{\"insecure\": 1}", "invalid options", NULL);
+    check_fails(argv[1], "{\"color\": \"blue\"}", "unknown field", NULL);
+    check_fails(NULL, NULL, "url is null", NULL);
+
+    /* A scan reports each target's failure in its result instead. */
+    char targets[512];
+    snprintf(targets, sizeof targets, "[\"%s\", \"https://127.0.0.1:1/\"]", argv[1]);
+    json = pqc_scan(targets, "{\"insecure\": true, \"retries\": 0}");
+    check(json != NULL, "scan with insecure=true");
+    if (json != NULL) {
+        puts(json);
+        check(strstr(json, "\"group\":\"") != NULL, "the scan reached the server");
+        check(strstr(json, "\"kind\":\"connect-refused\"") != NULL,
+              "the closed port is in the scan");
+    }
+    pqc_free_string(json);
+    json = pqc_scan("\"https://example.com\"", NULL);
+    check(json == NULL && strstr(pqc_last_error(), "invalid targets") != NULL,
+          "targets must be an array");
+
+    pqc_free_string(NULL);
+    return failures == 0 ? 0 : 1;
+}
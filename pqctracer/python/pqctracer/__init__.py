@@ -0,0 +1,106 @@
+"""Python bindings of pqctracer, over its C interface with :mod:`ctypes`
+rather than as a PyO3 extension module, so that one shared library serves
+every Python 3 version and there is nothing to build but the library.
+
+Build the shared library with ``cargo build --release -p pqctracer-ffi`` and
+point ``PQCTRACER_LIBRARY`` at it, or put it where the dynamic loader finds
+``libpqctracer``. Every call runs on a Tokio runtime of its own inside the
+library, and ctypes releases the GIL for the whole call, so other Python
+threads keep running while the handshakes are in flight.
+
+    >>> import pqctracer
+    >>> pqctracer.trace("https://www.google.com", timeout_ms=5000)["group"]
+    'X25519MLKEM768'
+"""
+
+import ctypes
+import ctypes.util
+import json
+import os
+import threading
+
+__all__ = ["TracerError", "trace", "scan"]
+
+
+class TracerError(Exception):
+    """A request that failed, with its error category.
+
+    ``category`` is the category as the JSON reports carry it, e.g.
+    ``{"kind": "certificate-invalid", "reason": "untrusted"}``, and ``kind``
+    its ``kind``.
+    """
+
+    def __init__(self, message, category):
+        super().__init__(message)
+        self.category = category
+        self.kind = category["kind"]
+
+
+_library = None
+_lock = threading.Lock()
+
+
+def _load():
+    global _library
+    with _lock:
+        if _library is None:
+            path = os.environ.get("PQCTRACER_LIBRARY") or ctypes.util.find_library("pqctracer")
+            if path is None:
+                raise OSError(
+                    "libpqctracer not found: build it with `cargo build --release "
+                    "-p pqctracer-ffi` and set PQCTRACER_LIBRARY to its path"
+                )
+            library = ctypes.CDLL(path)
+            # The JSON strings are freed by the library, so they are taken
+            # as plain pointers rather than converted to bytes right away.
+            for name in ("pqc_trace_url", "pqc_scan"):
+                function = getattr(library, name)
+                function.argtypes = [ctypes.c_char_p, ctypes.c_char_p]
+                function.restype = ctypes.c_void_p
+            library.pqc_free_string.argtypes = [ctypes.c_void_p]
+            library.pqc_free_string.restype = None
+            for name in ("pqc_last_error", "pqc_last_error_category"):
+                getattr(library, name).restype = ctypes.c_char_p
+            _library = library
+    return _library
+
+
+def _call(name, argument, options):
+    library = _load()
+    options = json.dumps(options).encode() if options else None
+    pointer = getattr(library, name)(argument.encode(), options)
+    if not pointer:
+        # The last error is the calling thread's, so no other call can
+        # have replaced it in between.
+        message = library.pqc_last_error().decode()
+        category = library.pqc_last_error_category()
+        if category is None:
+            raise ValueError(message)
+        raise TracerError(message, json.loads(category))
+    try:
+        return json.loads(ctypes.string_at(pointer).decode())
+    finally:
+        library.pqc_free_string(pointer)
+
+
+def trace(url, **options):
+    """Request ``url`` and return the negotiated TLS parameters as a dict.
+
+    The options are those of ``pqc_trace_url``: ``head``, ``timeout_ms``,
+    ``kx`` (``"hybrid-preferred"`` or ``"classical-only"``) and
+    ``insecure``. A failed request raises :class:`TracerError`, and invalid
+    arguments raise :class:`ValueError`.
+    """
+    return _call("pqc_trace_url", url, options)
+
+
+def scan(targets, **options):
+    """Trace every target, URLs or addresses, and return a list of result dicts.
+
+    Takes the options of :func:`trace`, with ``head`` defaulting to true,
+    plus ``concurrency``, ``max_per_host``, ``retries`` and ``rate``. A
+    target that fails has its error in its result, e.g.
+    ``result["error"]["category"]["kind"]``; only invalid arguments raise,
+    as :class:`ValueError`.
+    """
+    return _call("pqc_scan", json.dumps(list(targets)), options)
@@ -0,0 +1,76 @@
+"""Tests of the Python bindings against a loopback server.
+
+``cargo test -p pqctracer-ffi --test python`` starts the server and runs
+them. Run by hand, they need ``PQCTRACER_LIBRARY`` and the server's URL in
+``PQCTRACER_TEST_URL``, e.g. from ``cargo run -- serve-test``:
+
+    PQCTRACER_LIBRARY=target/debug/libpqctracer.so \\
+    PQCTRACER_TEST_URL=https://localhost:8443/ python3 -m unittest discover python/tests
+"""
+
+import os
+import threading
+import unittest
+
+import pqctracer
+
+URL = os.environ.get("PQCTRACER_TEST_URL")
+# Nothing listens on port 1 of the loopback address.
+CLOSED = "https://127.0.0.1:1/"
+
+
+@unittest.skipUnless(URL, "PQCTRACER_TEST_URL is not set")
+class TraceTest(unittest.TestCase):
+    def test_trace_returns_the_metadata(self):
+        metadata = pqctracer.trace(URL, insecure=True, timeout_ms=5000)
+        self.assertEqual(metadata["group"], "X25519MLKEM768")
+        self.assertTrue(metadata["cipher"].startswith("TLS13_"))
+        self.assertEqual(metadata["origin"], "fresh")
+
+    def test_classical_only_is_classical(self):
+        metadata = pqctracer.trace(URL, insecure=True, kx="classical-only")
+        self.assertEqual(metadata["group"], "X25519")
+
+    def test_failures_carry_their_category(self):
+        # The server's certificate is self-signed.
+        with self.assertRaises(pqctracer.TracerError) as raised:
+            pqctracer.trace(URL)
+        self.assertEqual(raised.exception.kind, "certificate-invalid")
+        self.assertEqual(raised.exception.category["reason"], "untrusted")
+        self.assertIn("certificate", str(raised.exception))
+
+        with self.assertRaises(pqctracer.TracerError) as raised:
+            pqctracer.trace(CLOSED, timeout_ms=5000)
+        self.assertEqual(raised.exception.kind, "connect-refused")
+
+    def test_invalid_arguments_raise_value_error(self):
+        with self.assertRaisesRegex(ValueError, "unknown field `colour`"):
+            pqctracer.trace(URL, colour="blue")
+        with self.assertRaisesRegex(ValueError, "invalid options"):
+            pqctracer.trace(URL, insecure="yes")
+
+    def test_scan_reports_each_target(self):
+        results = pqctracer.scan([URL, CLOSED], insecure=True, retries=0)
+        by_target = {result["target"]: result for result in results}
+        self.assertEqual(set(by_target), {URL, CLOSED})
+        self.assertEqual(by_target[URL]["metadata"]["group"], "X25519MLKEM768")
+        self.assertIsNone(by_target[URL]["error"])
+        category = by_target[CLOSED]["error"]["category"]
+        self.assertEqual(category, {"kind": "connect-refused"})
+
+    def test_calls_from_several_threads(self):
+        groups = []
+
+        def trace():
+            groups.append(pqctracer.trace(URL, insecure=True)["group"])
+
+        threads = [threading.Thread(target=trace) for _ in range(4)]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+        self.assertEqual(groups, ["X25519MLKEM768"] * 4)
+
+
+if __name__ == "__main__":
+    unittest.main()
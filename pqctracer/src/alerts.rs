@@ -0,0 +1,118 @@
+//! TLS alerts the server sent during a handshake the tracer drove itself.
+//!
+//! rustls ignores warning alerts outside TLS 1.3 and reports no others
+//! except by failing the handshake, so the alerts are read off the wire
+//! instead: the received bytes are followed record by record and every
+//! alert still sent in plaintext is kept. Once the handshake is encrypted,
+//! alerts are too, and can no longer be told apart from other records.
+
+use std::fmt;
+
+use rustls::AlertDescription;
+
+/// The record-layer content type of alerts.
+const CONTENT_TYPE_ALERT: u8 = 21;
+/// Record header: content type, legacy version and payload length.
+const HEADER_LEN: usize = 5;
+
+/// One alert received from the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlertInfo {
+    pub level: AlertLevel,
+    /// The alert's name as rustls spells it, e.g. `UnrecognisedName`.
+    pub description: String,
+}
+
+/// `warning UnrecognisedName`.
+impl fmt::Display for AlertInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.level, self.description)
+    }
+}
+
+/// How serious the sender considers an alert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum AlertLevel {
+    /// The connection may continue; TLS 1.3 allows no warnings other than
+    /// `user_canceled`.
+    Warning,
+    /// The connection ends; the handshake fails with
+    /// `AlertReceived(..)`.
+    Fatal,
+}
+
+impl fmt::Display for AlertLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AlertLevel::Warning => "warning",
+            AlertLevel::Fatal => "fatal",
+        })
+    }
+}
+
+/// Follows the records in a stream of received bytes and collects the
+/// plaintext alerts among them. An encrypted alert carries an
+/// authentication tag and so is never exactly two bytes long like a
+/// plaintext one.
+#[derive(Default)]
+pub(crate) struct AlertScanner {
+    header: [u8; HEADER_LEN],
+    header_len: usize,
+    /// Payload bytes left in the current record.
+    remaining: usize,
+    /// The payload read so far, if the current record is a plaintext alert.
+    alert: Option<Vec<u8>>,
+    alerts: Vec<AlertInfo>,
+}
+
+impl AlertScanner {
+    /// Follow `bytes`, the next bytes received.
+    pub(crate) fn feed(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.remaining == 0 {
+                let n = (HEADER_LEN - self.header_len).min(bytes.len());
+                self.header[self.header_len..self.header_len + n].copy_from_slice(&bytes[..n]);
+                self.header_len += n;
+                bytes = &bytes[n..];
+                if self.header_len == HEADER_LEN {
+                    self.header_len = 0;
+                    self.remaining = u16::from_be_bytes([self.header[3], self.header[4]]) as usize;
+                    self.alert = (self.header[0] == CONTENT_TYPE_ALERT && self.remaining == 2)
+                        .then(Vec::new);
+                }
+                continue;
+            }
+
+            let n = self.remaining.min(bytes.len());
+            if let Some(alert) = &mut self.alert {
+                alert.extend_from_slice(&bytes[..n]);
+            }
+            self.remaining -= n;
+            bytes = &bytes[n..];
+            if self.remaining == 0
+                && let Some(alert) = self.alert.take()
+            {
+                self.record(alert[0], alert[1]);
+            }
+        }
+    }
+
+    fn record(&mut self, level: u8, description: u8) {
+        let level = match level {
+            1 => AlertLevel::Warning,
+            2 => AlertLevel::Fatal,
+            _ => return,
+        };
+        self.alerts.push(AlertInfo {
+            level,
+            description: format!("{:?}", AlertDescription::from(description)),
+        });
+    }
+
+    pub(crate) fn alerts(&self) -> &[AlertInfo] {
+        &self.alerts
+    }
+}
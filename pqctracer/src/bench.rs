@@ -0,0 +1,191 @@
+//! Handshake latency benchmarks comparing hybrid and classical key exchange.
+//!
+//! Every sample is a fresh TCP connection and a full TLS handshake (no
+//! pooling, no resumption). Only the handshake itself is timed; DNS
+//! resolution and the TCP connect are excluded so the numbers isolate the
+//! cost of the key exchange and certificate flight.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rustls_pki_types::ServerName;
+use tokio_rustls::TlsConnector;
+
+use crate::bind::LocalBind;
+use crate::counting::CountingStream;
+use crate::key_shares;
+use crate::stats::{DurationStats, percentile};
+use crate::{TlsAwareClient, TlsMetadata, TraceError};
+
+pub use crate::KxPreference;
+
+/// Controls how a benchmark is run.
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// Measured handshakes per configuration.
+    pub iterations: usize,
+    /// Unmeasured handshakes per configuration before sampling starts.
+    pub warmup: usize,
+    /// Pause between consecutive handshakes, to stay below rate limits.
+    pub delay: Duration,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            iterations: 10,
+            warmup: 1,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+/// One measured handshake.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HandshakeSample {
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "duration_ms", with = "crate::serde_util::millis")
+    )]
+    pub duration: Duration,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub metadata: TlsMetadata,
+}
+
+/// The results for one [`KxPreference`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BenchRun {
+    pub preference: KxPreference,
+    /// The groups offered in the ClientHello, in preference order.
+    pub offered_groups: Vec<String>,
+    pub durations: DurationStats,
+    /// Median handshake bytes written by the client.
+    pub bytes_sent: u64,
+    /// Median handshake bytes read by the client.
+    pub bytes_received: u64,
+    /// Samples whose handshake needed a HelloRetryRequest.
+    pub hello_retries: usize,
+    /// The [`hrr_penalty`](TlsMetadata::hrr_penalty) of those samples.
+    pub hrr_penalties: Option<DurationStats>,
+    pub samples: Vec<HandshakeSample>,
+}
+
+/// Results of [`TlsAwareClient::bench`] for one target.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BenchReport {
+    pub host: String,
+    pub port: u16,
+    pub runs: Vec<BenchRun>,
+}
+
+impl TlsAwareClient {
+    /// Perform `options.iterations` fresh handshakes with `host:port` once
+    /// with hybrid groups preferred and once with classical groups only.
+    ///
+    /// Any failed handshake aborts the benchmark with its error.
+    pub async fn bench(
+        &self,
+        host: &str,
+        port: u16,
+        options: &BenchOptions,
+    ) -> Result<BenchReport, TraceError> {
+        if options.iterations == 0 {
+            return Err(TraceError::Config(
+                "at least one iteration is required".to_string(),
+            ));
+        }
+        let server_name = ServerName::try_from(host.to_string())
+            .map_err(|_| TraceError::InvalidServerName(host.to_string()))?;
+
+        let mut runs = Vec::new();
+        for preference in [KxPreference::HybridPreferred, KxPreference::ClassicalOnly] {
+            let kx_groups = preference.apply(self.kx_groups());
+            let offered_groups = kx_groups
+                .iter()
+                .map(|g| format!("{:?}", g.name()))
+                .collect();
+            let connector = TlsConnector::from(Arc::new(self.direct_tls_config(Some(kx_groups))?));
+
+            let mut samples = Vec::with_capacity(options.iterations);
+            for i in 0..options.warmup + options.iterations {
+                if i > 0 && !options.delay.is_zero() {
+                    tokio::time::sleep(options.delay).await;
+                }
+                let sample =
+                    handshake(&connector, &self.bind, host, port, server_name.clone()).await?;
+                if i >= options.warmup {
+                    samples.push(sample);
+                }
+            }
+
+            let durations: Vec<Duration> = samples.iter().map(|s| s.duration).collect();
+            let hrr_penalties: Vec<Duration> = samples
+                .iter()
+                .filter_map(|s| s.metadata.hrr_penalty)
+                .collect();
+            let mut sent: Vec<u64> = samples.iter().map(|s| s.bytes_sent).collect();
+            let mut received: Vec<u64> = samples.iter().map(|s| s.bytes_received).collect();
+            sent.sort();
+            received.sort();
+
+            runs.push(BenchRun {
+                preference,
+                offered_groups,
+                durations: DurationStats::from_samples(&durations).expect("at least one sample"),
+                bytes_sent: percentile(&sent, 50.0),
+                bytes_received: percentile(&received, 50.0),
+                hello_retries: hrr_penalties.len(),
+                hrr_penalties: DurationStats::from_samples(&hrr_penalties),
+                samples,
+            });
+        }
+
+        Ok(BenchReport {
+            host: host.to_string(),
+            port,
+            runs,
+        })
+    }
+}
+
+async fn handshake(
+    connector: &TlsConnector,
+    bind: &LocalBind,
+    host: &str,
+    port: u16,
+    server_name: ServerName<'static>,
+) -> Result<HandshakeSample, TraceError> {
+    let tcp = bind.connect_host(host, port).await?;
+    tcp.set_nodelay(true)?;
+
+    let start = Instant::now();
+    let (tls, key_shares) = key_shares::observe(async {
+        connector
+            .connect(server_name, CountingStream::new(tcp))
+            .await
+    })
+    .await;
+    let tls = tls?;
+    let duration = start.elapsed();
+
+    let (stream, conn) = tls.get_ref();
+    Ok(HandshakeSample {
+        duration,
+        bytes_sent: stream.bytes_sent(),
+        bytes_received: stream.bytes_received(),
+        metadata: TlsMetadata {
+            handshake_duration: Some(duration),
+            hrr_penalty: key_shares.hrr_penalty(),
+            client_cert_requested: Some(key_shares.client_cert_requested()),
+            client_cert_authorities: key_shares.client_cert_authorities(),
+            handshake_bytes_sent: Some(stream.bytes_sent()),
+            handshake_bytes_received: Some(stream.bytes_received()),
+            alerts_received: stream.alerts_received(),
+            ..TlsMetadata::from_connection(conn)
+        },
+    })
+}
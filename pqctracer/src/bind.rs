@@ -0,0 +1,141 @@
+//! Connecting from a chosen local address or network interface, for
+//! clients built with
+//! [`local_address`](crate::TlsAwareClientBuilder::local_address) or
+//! `interface`.
+//!
+//! reqwest binds its sockets itself, but connects from an unbound one when
+//! the server's address is of the other family than the local address, so
+//! the resolver here hands it addresses of the local address's family only.
+//! The handshakes the tracer drives itself connect through
+//! [`LocalBind::connect`].
+//!
+//! Hosts given addresses with
+//! [`resolve`](crate::TlsAwareClientBuilder::resolve) are looked up here
+//! too, so that those handshakes reach the same addresses as requests do.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use tokio::net::{TcpSocket, TcpStream, lookup_host};
+
+/// Where connections are made from; connections are left unbound when
+/// nothing is set.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LocalBind {
+    pub(crate) address: Option<IpAddr>,
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    pub(crate) interface: Option<String>,
+    /// Lowercase host names and the addresses they are reached at instead
+    /// of what they resolve to.
+    pub(crate) resolved: Arc<HashMap<String, Vec<IpAddr>>>,
+}
+
+impl LocalBind {
+    fn is_set(&self) -> bool {
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        if self.interface.is_some() {
+            return true;
+        }
+        self.address.is_some()
+    }
+
+    /// Fail unless `addr`, an address of `host`, is of the local address's
+    /// family.
+    pub(crate) fn check(&self, host: &str, addr: IpAddr) -> io::Result<()> {
+        match self.address {
+            Some(local) if local.is_ipv4() != addr.is_ipv4() => Err(family_mismatch(host, local)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Resolve `host` and keep the addresses of the local address's family,
+    /// failing when it has addresses but none of them are.
+    pub(crate) async fn lookup(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let addrs: Vec<SocketAddr> = match self.resolved.get(host) {
+            Some(ips) => ips.iter().map(|ip| SocketAddr::new(*ip, port)).collect(),
+            None => lookup_host((host, port)).await?.collect(),
+        };
+        let Some(local) = self.address else {
+            return Ok(addrs);
+        };
+        let reachable: Vec<SocketAddr> = addrs
+            .iter()
+            .copied()
+            .filter(|addr| addr.is_ipv4() == local.is_ipv4())
+            .collect();
+        if reachable.is_empty() && !addrs.is_empty() {
+            return Err(family_mismatch(host, local));
+        }
+        Ok(reachable)
+    }
+
+    /// Resolve `host` and connect to the first of its addresses that
+    /// accepts the connection.
+    pub(crate) async fn connect_host(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        if !self.is_set() && !self.resolved.contains_key(host) {
+            return TcpStream::connect((host, port)).await;
+        }
+        let mut last_error = None;
+        for addr in self.lookup(host, port).await? {
+            match self.connect(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} did not resolve to any address", host),
+            )
+        }))
+    }
+
+    /// Connect to `addr` from the local address and interface.
+    pub(crate) async fn connect(&self, addr: SocketAddr) -> io::Result<TcpStream> {
+        if !self.is_set() {
+            return TcpStream::connect(addr).await;
+        }
+        self.check(&addr.ip().to_string(), addr.ip())?;
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        if let Some(interface) = &self.interface {
+            socket.bind_device(Some(interface.as_bytes()))?;
+        }
+        if let Some(local) = self.address {
+            socket.bind(SocketAddr::new(local, 0))?;
+        }
+        socket.connect(addr).await
+    }
+}
+
+/// reqwest's resolver for clients with a local address but no timings;
+/// the timing resolver filters the same way.
+impl Resolve for LocalBind {
+    fn resolve(&self, name: Name) -> Resolving {
+        let bind = self.clone();
+        Box::pin(async move {
+            let addrs = bind.lookup(name.as_str(), 0).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn family_mismatch(host: &str, local: IpAddr) -> io::Error {
+    let family = match local {
+        IpAddr::V4(_) => "IPv4",
+        IpAddr::V6(_) => "IPv6",
+    };
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "{} has no {} address to reach from the local address {}",
+            host, family, local
+        ),
+    )
+}
@@ -0,0 +1,211 @@
+//! What the tracer itself offers in its ClientHello, so that a classical
+//! handshake can be told apart from a client that never offered a hybrid.
+
+use std::fmt;
+use std::time::SystemTime;
+
+use rustls::crypto::CryptoProvider;
+
+use crate::fingerprint::Fingerprints;
+use crate::{KeyExchangeKind, KxPreference};
+
+/// The TLS configuration a [`TlsAwareClient`](crate::TlsAwareClient) was
+/// built with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClientCapabilities {
+    /// The crypto provider, `aws-lc-rs` or `ring`.
+    pub provider: String,
+    /// Whether the provider runs in FIPS mode.
+    pub fips: bool,
+    pub kx_preference: KxPreference,
+    /// The key-exchange groups offered, most preferred first.
+    pub kx_groups: Vec<String>,
+    /// The cipher suites offered, most preferred first.
+    pub cipher_suites: Vec<String>,
+    /// The TLS versions offered, e.g. `TLS1.3`.
+    pub protocol_versions: Vec<String>,
+    /// Whether the most preferred group is hybrid or post-quantum, which is
+    /// what rustls' `prefer-post-quantum` feature arranges.
+    pub post_quantum_preferred: bool,
+    /// Whether the provider has any hybrid or post-quantum group at all,
+    /// whatever the key-exchange preference. `false` for providers such as
+    /// ring, with which no server can negotiate a post-quantum key exchange.
+    pub pqc_capable: bool,
+    /// The JA3N fingerprint of the ClientHello: JA3 with the extensions
+    /// sorted, since rustls sends them in a different order every time.
+    pub ja3n: String,
+    /// The JA4 fingerprint of the ClientHello, e.g.
+    /// `t13d101000_61a7ad8aa9b6_f9531d972513`. It covers the cipher suites
+    /// but not the groups, which only JA3N includes.
+    pub ja4: String,
+}
+
+impl ClientCapabilities {
+    pub(crate) fn new(
+        provider: &CryptoProvider,
+        kx_preference: KxPreference,
+        fingerprints: Fingerprints,
+    ) -> Self {
+        let groups = kx_preference.apply(&provider.kx_groups);
+        Self {
+            provider: provider_name(provider),
+            fips: provider.fips(),
+            kx_preference,
+            kx_groups: groups.iter().map(|g| format!("{:?}", g.name())).collect(),
+            cipher_suites: provider
+                .cipher_suites
+                .iter()
+                .map(|s| format!("{:?}", s.suite()))
+                .collect(),
+            protocol_versions: rustls::DEFAULT_VERSIONS
+                .iter()
+                .map(|v| match v.version {
+                    rustls::ProtocolVersion::TLSv1_3 => "TLS1.3".to_string(),
+                    rustls::ProtocolVersion::TLSv1_2 => "TLS1.2".to_string(),
+                    other => format!("{:?}", other),
+                })
+                .collect(),
+            post_quantum_preferred: groups
+                .first()
+                .is_some_and(|g| KeyExchangeKind::of(g.name()).is_quantum_resistant()),
+            pqc_capable: provider
+                .kx_groups
+                .iter()
+                .any(|g| KeyExchangeKind::of(g.name()).is_quantum_resistant()),
+            ja3n: fingerprints.ja3n,
+            ja4: fingerprints.ja4,
+        }
+    }
+
+    /// Whether any offered group is hybrid or post-quantum.
+    pub fn offers_post_quantum(&self) -> bool {
+        self.kx_groups
+            .iter()
+            .any(|g| KeyExchangeKind::of_name(g).is_quantum_resistant())
+    }
+}
+
+/// What produced a set of results: this crate and how it was built, the
+/// crypto provider and groups of the client, the platform and the time.
+///
+/// The command line includes it as `meta` in its JSON reports. Fields are
+/// only ever added, never renamed or removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReportMeta {
+    /// The version of this crate.
+    pub pqctracer_version: String,
+    /// The rustls release series this crate is built against, e.g. `0.23`.
+    pub rustls_version: String,
+    /// The client's crypto provider, `aws-lc-rs` or `ring`.
+    pub provider: String,
+    /// Whether the provider runs in FIPS mode.
+    pub fips: bool,
+    /// Whether rustls was built with its `prefer-post-quantum` feature,
+    /// which puts the hybrid group first among its default groups.
+    pub prefer_post_quantum: bool,
+    /// The optional features of this crate that are enabled, e.g. `dns`.
+    pub features: Vec<String>,
+    pub kx_preference: KxPreference,
+    /// The key-exchange groups the client offers, most preferred first.
+    pub kx_groups: Vec<String>,
+    /// The operating system, e.g. `linux`.
+    pub os: String,
+    /// The CPU architecture, e.g. `x86_64`.
+    pub arch: String,
+    /// When the block was assembled.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_util::rfc3339"))]
+    pub generated_at: SystemTime,
+}
+
+/// The rustls release series of the `rustls` dependency in `Cargo.toml`.
+const RUSTLS_VERSION: &str = "0.23";
+
+impl ReportMeta {
+    /// The block for a client offering `capabilities`, stamped with the
+    /// current time.
+    pub fn new(capabilities: &ClientCapabilities) -> Self {
+        let features = [
+            ("dns", cfg!(feature = "dns")),
+            ("json", cfg!(feature = "json")),
+            ("log", cfg!(feature = "log")),
+            ("serde", cfg!(feature = "serde")),
+            ("test-util", cfg!(feature = "test-util")),
+            ("tower", cfg!(feature = "tower")),
+            ("websocket", cfg!(feature = "websocket")),
+        ];
+        Self {
+            pqctracer_version: env!("CARGO_PKG_VERSION").to_string(),
+            rustls_version: RUSTLS_VERSION.to_string(),
+            provider: capabilities.provider.clone(),
+            fips: capabilities.fips,
+            prefer_post_quantum: rustls::crypto::aws_lc_rs::default_provider()
+                .kx_groups
+                .first()
+                .is_some_and(|g| KeyExchangeKind::of(g.name()).is_quantum_resistant()),
+            features: features
+                .into_iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            kx_preference: capabilities.kx_preference,
+            kx_groups: capabilities.kx_groups.clone(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            generated_at: SystemTime::now(),
+        }
+    }
+}
+
+/// One `name: value` line per field, as printed by `--version --verbose`.
+impl fmt::Display for ReportMeta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fips = if self.fips { " (FIPS)" } else { "" };
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+        writeln!(f, "pqctracer: {}", self.pqctracer_version)?;
+        writeln!(f, "rustls: {}", self.rustls_version)?;
+        writeln!(f, "Provider: {}{}", self.provider, fips)?;
+        writeln!(
+            f,
+            "prefer-post-quantum: {}",
+            yes_no(self.prefer_post_quantum)
+        )?;
+        writeln!(f, "Features: {}", self.features.join(", "))?;
+        writeln!(f, "Key-exchange preference: {}", self.kx_preference)?;
+        writeln!(f, "Key-exchange groups: {}", self.kx_groups.join(", "))?;
+        writeln!(f, "Platform: {}-{}", self.os, self.arch)?;
+        write!(f, "Generated: {}", crate::cert::rfc3339(self.generated_at))
+    }
+}
+
+/// One `name: value` line per field, as printed by `--capabilities`.
+impl fmt::Display for ClientCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fips = if self.fips { " (FIPS)" } else { "" };
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+        writeln!(f, "Provider: {}{}", self.provider, fips)?;
+        writeln!(f, "Key-exchange preference: {}", self.kx_preference)?;
+        writeln!(f, "Key-exchange groups: {}", self.kx_groups.join(", "))?;
+        writeln!(f, "Cipher suites: {}", self.cipher_suites.join(", "))?;
+        writeln!(f, "Versions: {}", self.protocol_versions.join(", "))?;
+        writeln!(f, "Post-quantum capable: {}", yes_no(self.pqc_capable))?;
+        writeln!(
+            f,
+            "Post-quantum preferred: {}",
+            yes_no(self.post_quantum_preferred)
+        )?;
+        writeln!(f, "JA3N: {}", self.ja3n)?;
+        write!(f, "JA4: {}", self.ja4)
+    }
+}
+
+/// rustls does not name its providers; their random number generators'
+/// `Debug` output does.
+fn provider_name(provider: &CryptoProvider) -> String {
+    match format!("{:?}", provider.secure_random).as_str() {
+        "AwsLcRs" => "aws-lc-rs".to_string(),
+        "Ring" => "ring".to_string(),
+        other => other.to_string(),
+    }
+}
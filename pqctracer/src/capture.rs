@@ -0,0 +1,174 @@
+//! Tying what the TLS callbacks report to the request whose handshake they
+//! come from, for [`execute`](crate::TlsAwareClient::execute).
+//!
+//! rustls reports a handshake through the session store and the
+//! certificate verifier, which every connection of a client shares and
+//! which only learn the server's name. Like the timing marks and the
+//! [key-share log](crate::key_shares), each request installs a capture of
+//! its own for the duration of its future, and the callbacks fill in the
+//! one installed where they run: a request's handshakes run inside its
+//! future, so concurrent requests to the same host never see each other's.
+//!
+//! The cipher suite comes from rustls installing the key that decrypts the
+//! server's handshake, which every TLS 1.3 suite is wrapped to note. Session
+//! tickets are different: a TLS 1.3 server sends them after the handshake,
+//! and hyper reads them on a connection task of its own, outside every
+//! request, so they are only counted where the tracer reads them itself.
+
+use std::future::Future;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use rustls::crypto::CryptoProvider;
+use rustls::crypto::cipher::{
+    AeadKey, Iv, MessageDecrypter, MessageEncrypter, Tls13AeadAlgorithm, UnsupportedOperationError,
+};
+use rustls::{
+    CipherSuite, CipherSuiteCommon, ConnectionTrafficSecrets, SupportedCipherSuite,
+    Tls13CipherSuite,
+};
+use rustls_pki_types::ServerName;
+
+use crate::{Captured, lock, logging};
+
+tokio::task_local! {
+    static CAPTURE: Capture;
+}
+
+/// The capture a request installed, and the server it is for.
+struct Capture {
+    /// As [`ServerName::to_str`] gives it: lowercase, and IPv6 literals
+    /// without their brackets.
+    server: String,
+    /// Whether the handshake under way is with `server`.
+    on_server: AtomicBool,
+    captured: Arc<Captured>,
+}
+
+/// Run `future`, a request to `host`, with `captured` installed.
+pub(crate) async fn scope<F: Future>(host: &str, captured: Arc<Captured>, future: F) -> F::Output {
+    let capture = Capture {
+        server: host.trim_matches(['[', ']']).to_ascii_lowercase(),
+        on_server: AtomicBool::new(false),
+        captured,
+    };
+    CAPTURE.scope(capture, future).await
+}
+
+/// The server and capture of the request whose future the caller runs in.
+pub(crate) fn current() -> Option<(String, Arc<Captured>)> {
+    CAPTURE
+        .try_with(|capture| (capture.server.clone(), capture.captured.clone()))
+        .ok()
+}
+
+/// Run `f` on the capture installed where the TLS callback `callback` for
+/// `server_name` runs. Handshakes with other servers, e.g. after a
+/// cross-host redirect, are left out, and so are callbacks outside every
+/// request.
+pub(crate) fn with(callback: &str, server_name: &ServerName<'_>, f: impl FnOnce(&Captured)) {
+    let _ = CAPTURE.try_with(|capture| {
+        if capture.server == server_name.to_str() {
+            f(&capture.captured);
+        } else {
+            logging::capture_missed(callback, server_name);
+        }
+    });
+}
+
+/// Note that a handshake with `server_name` starts; called from the session
+/// store, which rustls asks for a group hint while building the ClientHello.
+pub(crate) fn hello_started(server_name: &ServerName<'_>) {
+    let _ = CAPTURE.try_with(|capture| {
+        let on_server = capture.server == server_name.to_str();
+        capture.on_server.store(on_server, Ordering::Relaxed);
+    });
+}
+
+/// `provider` with each of its TLS 1.3 cipher suites wrapped so that the
+/// capture notes which one a handshake negotiated.
+pub(crate) fn observed(provider: &CryptoProvider) -> Arc<CryptoProvider> {
+    Arc::new(CryptoProvider {
+        cipher_suites: provider
+            .cipher_suites
+            .iter()
+            .map(|suite| match suite {
+                SupportedCipherSuite::Tls13(suite) => {
+                    SupportedCipherSuite::Tls13(observed_suite(suite))
+                }
+                suite => *suite,
+            })
+            .collect(),
+        ..provider.clone()
+    })
+}
+
+/// Providers need `'static` suites, so each suite is wrapped once for the
+/// life of the process and the wrappers are shared by every client.
+fn observed_suite(suite: &'static Tls13CipherSuite) -> &'static Tls13CipherSuite {
+    static WRAPPERS: OnceLock<Mutex<Vec<(&'static Tls13CipherSuite, &'static Tls13CipherSuite)>>> =
+        OnceLock::new();
+    let mut wrappers = lock(WRAPPERS.get_or_init(Mutex::default), "suite-wrappers");
+    let found = wrappers
+        .iter()
+        .find(|(inner, wrapper)| ptr::eq(*inner, suite) || ptr::eq(*wrapper, suite));
+    if let Some((_, wrapper)) = found {
+        return wrapper;
+    }
+    let aead_alg: &'static ObservedAead = Box::leak(Box::new(ObservedAead {
+        suite: suite.common.suite,
+        inner: suite.aead_alg,
+    }));
+    let wrapper: &'static Tls13CipherSuite = Box::leak(Box::new(Tls13CipherSuite {
+        common: CipherSuiteCommon {
+            suite: suite.common.suite,
+            hash_provider: suite.common.hash_provider,
+            confidentiality_limit: suite.common.confidentiality_limit,
+        },
+        hkdf_provider: suite.hkdf_provider,
+        aead_alg,
+        quic: suite.quic,
+    }));
+    wrappers.push((suite, wrapper));
+    wrapper
+}
+
+/// Notes `suite` when rustls installs a decryption key, which a TLS 1.3
+/// client first does for the server's handshake messages, and otherwise
+/// leaves it to `inner`.
+struct ObservedAead {
+    suite: CipherSuite,
+    inner: &'static dyn Tls13AeadAlgorithm,
+}
+
+impl Tls13AeadAlgorithm for ObservedAead {
+    fn encrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageEncrypter> {
+        self.inner.encrypter(key, iv)
+    }
+
+    fn decrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageDecrypter> {
+        let _ = CAPTURE.try_with(|capture| {
+            if capture.on_server.load(Ordering::Relaxed) {
+                capture.captured.cipher.get_or_init(|| self.suite);
+            }
+        });
+        self.inner.decrypter(key, iv)
+    }
+
+    fn key_len(&self) -> usize {
+        self.inner.key_len()
+    }
+
+    fn extract_keys(
+        &self,
+        key: AeadKey,
+        iv: Iv,
+    ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        self.inner.extract_keys(key, iv)
+    }
+
+    fn fips(&self) -> bool {
+        self.inner.fips()
+    }
+}
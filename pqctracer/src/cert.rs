@@ -0,0 +1,217 @@
+//! Details of the certificates servers present.
+
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use rustls::crypto::CryptoProvider;
+use rustls_pki_types::CertificateDer;
+use x509_parser::der_parser::oid::Oid;
+use x509_parser::prelude::{FromDer, SubjectPublicKeyInfo, X509Certificate};
+use x509_parser::public_key::PublicKey;
+
+use crate::Sha256Digest;
+
+/// The validity period of a certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CertificateValidity {
+    pub not_before: SystemTime,
+    pub not_after: SystemTime,
+}
+
+impl CertificateValidity {
+    /// Read the validity period of a DER-encoded certificate, or `None` if it
+    /// does not parse.
+    pub fn of(cert: &CertificateDer<'_>) -> Option<Self> {
+        let (_, cert) = X509Certificate::from_der(cert.as_ref()).ok()?;
+        let validity = cert.validity();
+        Some(Self {
+            not_before: from_unix(validity.not_before.timestamp()),
+            not_after: from_unix(validity.not_after.timestamp()),
+        })
+    }
+
+    /// Whole days from `now` until the certificate expires; negative once it
+    /// has expired.
+    pub fn days_remaining(&self, now: SystemTime) -> i64 {
+        let seconds = match self.not_after.duration_since(now) {
+            Ok(left) => left.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs_f64().ceil() as i64),
+        };
+        seconds.div_euclid(86_400)
+    }
+}
+
+/// Who a certificate was issued to and by, for a quick look at a chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateSummary {
+    /// E.g. `CN=example.com`.
+    pub subject: String,
+    pub issuer: String,
+    /// The public key's algorithm and size, e.g. `ECDSA P-256`, `RSA 2048`
+    /// or `ML-DSA-65`; the OID for algorithms without a name here.
+    pub key_algorithm: String,
+    pub not_after: SystemTime,
+}
+
+impl CertificateSummary {
+    /// Summarize a DER-encoded certificate, or `None` if it does not parse.
+    pub fn of(cert: &CertificateDer<'_>) -> Option<Self> {
+        let (_, cert) = X509Certificate::from_der(cert.as_ref()).ok()?;
+        Some(Self {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            key_algorithm: key_algorithm(cert.public_key()),
+            not_after: from_unix(cert.validity().not_after.timestamp()),
+        })
+    }
+}
+
+/// `subject=CN=example.com issuer=CN=Example CA key=ECDSA P-256
+/// not_after=2027-03-01T12:00:00Z`.
+impl fmt::Display for CertificateSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "subject={} issuer={} key={} not_after={}",
+            self.subject,
+            self.issuer,
+            self.key_algorithm,
+            rfc3339(self.not_after)
+        )
+    }
+}
+
+fn key_algorithm(spki: &SubjectPublicKeyInfo<'_>) -> String {
+    let curve = || {
+        let parameters = spki.algorithm.parameters.clone()?;
+        let name = match Oid::try_from(parameters).ok()?.to_id_string().as_str() {
+            "1.2.840.10045.3.1.7" => "P-256",
+            "1.3.132.0.34" => "P-384",
+            "1.3.132.0.35" => "P-521",
+            other => return Some(other.to_string()),
+        };
+        Some(name.to_string())
+    };
+    match spki.algorithm.algorithm.to_id_string().as_str() {
+        "1.2.840.113549.1.1.1" => match spki.parsed() {
+            Ok(PublicKey::RSA(rsa)) => format!("RSA {}", rsa.key_size()),
+            _ => "RSA".to_string(),
+        },
+        "1.2.840.10045.2.1" => match curve() {
+            Some(curve) => format!("ECDSA {}", curve),
+            None => "ECDSA".to_string(),
+        },
+        "1.3.101.112" => "Ed25519".to_string(),
+        "1.3.101.113" => "Ed448".to_string(),
+        "2.16.840.1.101.3.4.3.17" => "ML-DSA-44".to_string(),
+        "2.16.840.1.101.3.4.3.18" => "ML-DSA-65".to_string(),
+        "2.16.840.1.101.3.4.3.19" => "ML-DSA-87".to_string(),
+        // SLH-DSA parameter sets, RFC 9909.
+        other if slh_dsa(other) => format!("SLH-DSA ({})", other),
+        // Composite ML-DSA with a classical algorithm, draft OIDs.
+        other if other.starts_with("2.16.840.1.114027.80.8.1.") => {
+            format!("composite ML-DSA ({})", other)
+        }
+        other => other.to_string(),
+    }
+}
+
+fn slh_dsa(oid: &str) -> bool {
+    oid.strip_prefix("2.16.840.1.101.3.4.3.")
+        .and_then(|arc| arc.parse::<u32>().ok())
+        .is_some_and(|arc| (20..=31).contains(&arc))
+}
+
+/// Encode `cert` as PEM, the way `openssl x509` prints it.
+pub fn to_pem(cert: &CertificateDer<'_>) -> String {
+    let encoded = BASE64.encode(cert.as_ref());
+    let mut pem = String::from("-----BEGIN CERTIFICATE-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        pem.push('\n');
+    }
+    pem.push_str("-----END CERTIFICATE-----\n");
+    pem
+}
+
+/// The SHA-256 fingerprint of `cert`, computed with the process-wide
+/// default crypto provider; `None` when none is installed.
+pub fn fingerprint(cert: &CertificateDer<'_>) -> Option<Sha256Digest> {
+    let sha256 = crate::fingerprint::sha256(CryptoProvider::get_default()?)?;
+    let mut digest = [0; 32];
+    digest.copy_from_slice(sha256.hash(cert.as_ref()).as_ref());
+    Some(Sha256Digest(digest))
+}
+
+fn from_unix(seconds: i64) -> SystemTime {
+    if seconds >= 0 {
+        UNIX_EPOCH + Duration::from_secs(seconds as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs())
+    }
+}
+
+/// Format `time` as an RFC 3339 UTC timestamp with second precision, e.g.
+/// `2026-03-01T12:00:00Z`.
+pub fn rfc3339(time: SystemTime) -> String {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs_f64().ceil() as i64),
+    };
+    let (days, secs) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Parse a timestamp written by [`rfc3339`].
+#[cfg(feature = "serde")]
+pub(crate) fn parse_rfc3339(s: &str) -> Option<SystemTime> {
+    let s = s.strip_suffix('Z')?;
+    let (date, time) = s.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let mut time = time.splitn(3, ':').map(str::parse::<i64>);
+    let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    Some(from_unix(days * 86_400 + hour * 3600 + minute * 60 + second))
+}
+
+// Howard Hinnant's `civil_from_days` / `days_from_civil` algorithms for the
+// proleptic Gregorian calendar.
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(feature = "serde")]
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
@@ -0,0 +1,59 @@
+//! Telling the servers that ask for a client certificate from those that do
+//! not, for [`TlsMetadata::client_cert_requested`](crate::TlsMetadata::client_cert_requested).
+//!
+//! A server asks with a CertificateRequest, which TLS 1.3 encrypts, so it
+//! cannot be read off the wire like the [ServerHello](crate::server_hello).
+//! Instead every client configuration gets a certificate resolver: rustls
+//! asks it for a certificate when, and only when, the server sent a
+//! CertificateRequest, and it notes the question and the certificate
+//! authorities the server named in the handshake's
+//! [key-share log](crate::key_shares) before passing it on to the client's
+//! own [resolver](crate::TlsAwareClientBuilder::client_cert_resolver).
+//! Without one, the handshake goes on without a certificate, which servers
+//! that merely ask for one accept.
+
+use std::sync::Arc;
+
+use rustls::SignatureScheme;
+use rustls::client::ResolvesClientCert;
+use rustls::sign::CertifiedKey;
+use x509_parser::prelude::{FromDer, X509Name};
+
+use crate::key_shares;
+
+/// Notes every request for a client certificate, then answers it with
+/// `inner` if there is one.
+#[derive(Debug)]
+pub(crate) struct ObservingCertResolver {
+    pub(crate) inner: Option<Arc<dyn ResolvesClientCert>>,
+}
+
+impl ResolvesClientCert for ObservingCertResolver {
+    fn resolve(
+        &self,
+        root_hint_subjects: &[&[u8]],
+        sigschemes: &[SignatureScheme],
+    ) -> Option<Arc<CertifiedKey>> {
+        key_shares::client_cert_requested(root_hint_subjects);
+        self.inner.as_ref()?.resolve(root_hint_subjects, sigschemes)
+    }
+
+    fn only_raw_public_keys(&self) -> bool {
+        self.inner
+            .as_ref()
+            .is_some_and(|inner| inner.only_raw_public_keys())
+    }
+
+    fn has_certs(&self) -> bool {
+        self.inner.as_ref().is_some_and(|inner| inner.has_certs())
+    }
+}
+
+/// A DER-encoded distinguished name from a CertificateRequest, e.g.
+/// `CN=Example Client CA, O=Example`.
+pub(crate) fn authority_name(der: &[u8]) -> String {
+    match X509Name::from_der(der) {
+        Ok((_, name)) => name.to_string(),
+        Err(_) => format!("unparsable name ({} bytes)", der.len()),
+    }
+}
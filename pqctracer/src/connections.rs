@@ -0,0 +1,247 @@
+//! Handshake metadata per established connection, so that requests served
+//! over a pooled or multiplexed connection report that connection's
+//! handshake instead of nothing.
+//!
+//! A new connection captures its handshake in a [capture](crate::capture)
+//! and [key-share log](crate::key_shares) of its own, installed by the
+//! connector layer around the connection's future. hyper may finish a
+//! connection in the background after the request that started it took a
+//! pooled one instead, outside every request; the connection's own capture
+//! still sees that handshake. The first request served over the connection
+//! takes the capture and reports it as its handshake, and later ones
+//! report the connection's handshake as shared.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use hyper_util::client::legacy::connect::{Connection, HttpInfo};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::key_shares::{self, KeyShareLog};
+use crate::{Captured, TlsMetadata, capture, lock, tcp_stats};
+
+/// A TCP connection, identified by its local and remote address as reported
+/// in the response's `HttpInfo`.
+pub(crate) type ConnectionKey = (SocketAddr, SocketAddr);
+
+/// What a new connection's handshake captured, until the first request
+/// served over it takes it.
+pub(crate) struct Handshake {
+    pub(crate) captured: Arc<Captured>,
+    pub(crate) key_shares: Arc<KeyShareLog>,
+    /// The bytes written and read until the handshake was done, where the
+    /// kernel tells, see [`tcp_stats`](crate::tcp_stats).
+    pub(crate) bytes: Option<(u64, u64)>,
+}
+
+enum Entry {
+    /// Connected, and no request has been served over it yet.
+    Pending(Handshake),
+    /// The metadata of the connection's handshake, as its first request
+    /// reported it.
+    Recorded(Box<TlsMetadata>),
+}
+
+/// The handshake of each connection.
+///
+/// Closed connections are never reported, so the table is bounded instead:
+/// once full, the connection whose handshake is oldest is forgotten. A new
+/// connection reusing a forgotten one's addresses performs a handshake of
+/// its own and overwrites the entry.
+#[derive(Default)]
+pub(crate) struct Connections {
+    table: Mutex<Table>,
+}
+
+#[derive(Default)]
+struct Table {
+    /// Handshakes recorded so far; orders the entries by age.
+    generation: u64,
+    entries: HashMap<ConnectionKey, (u64, Entry)>,
+}
+
+impl Table {
+    fn insert(&mut self, connection: ConnectionKey, entry: Entry) {
+        self.generation += 1;
+        let generation = self.generation;
+        self.entries.insert(connection, (generation, entry));
+        if self.entries.len() > Connections::MAX_ENTRIES {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (generation, _))| *generation)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+impl Connections {
+    /// Connections remembered at most.
+    const MAX_ENTRIES: usize = 256;
+
+    /// Keep what the handshake of the new `connection` captured for the
+    /// first request served over it.
+    fn connected(&self, connection: ConnectionKey, handshake: Handshake) {
+        lock(&self.table, "connections").insert(connection, Entry::Pending(handshake));
+    }
+
+    /// The handshake of `connection`, for the first request served over
+    /// it; `None` once a request took it.
+    pub(crate) fn take_handshake(&self, connection: &ConnectionKey) -> Option<Handshake> {
+        let mut table = lock(&self.table, "connections");
+        match table.entries.get(connection) {
+            Some((_, Entry::Pending(_))) => match table.entries.remove(connection) {
+                Some((_, Entry::Pending(handshake))) => Some(handshake),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Remember `metadata` as the handshake of `connection`.
+    pub(crate) fn record(&self, connection: ConnectionKey, metadata: &TlsMetadata) {
+        lock(&self.table, "connections").insert(connection, Entry::Recorded(Box::new(metadata.clone())));
+    }
+
+    /// The handshake recorded for `connection`, if it is still remembered.
+    pub(crate) fn get(&self, connection: &ConnectionKey) -> Option<TlsMetadata> {
+        match lock(&self.table, "connections").entries.get(connection) {
+            Some((_, Entry::Recorded(metadata))) => Some(TlsMetadata::clone(metadata)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        lock(&self.table, "connections").entries.len()
+    }
+}
+
+/// Wraps reqwest's connector to give every connection a request starts a
+/// capture of its own, see the [module docs](self).
+#[derive(Clone)]
+pub(crate) struct ConnectionLayer {
+    pub(crate) connections: Arc<Connections>,
+}
+
+impl<S> Layer<S> for ConnectionLayer {
+    type Service = CapturingConnector<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CapturingConnector {
+            inner,
+            connections: self.connections.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct CapturingConnector<S> {
+    inner: S,
+    connections: Arc<Connections>,
+}
+
+impl<S, R> Service<R> for CapturingConnector<S>
+where
+    S: Service<R>,
+    S::Response: Connection,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let connecting = self.inner.call(request);
+        // Connections hyper starts outside every request are left alone.
+        let (Some((server, request_capture)), Some(request_log)) =
+            (capture::current(), key_shares::current())
+        else {
+            return Box::pin(connecting);
+        };
+        let connections = self.connections.clone();
+        Box::pin(async move {
+            let captured = Arc::<Captured>::default();
+            let (result, log) =
+                key_shares::observe(capture::scope(&server, captured.clone(), connecting)).await;
+            match &result {
+                Ok(connection) => {
+                    let mut extensions = http::Extensions::new();
+                    connection.connected().get_extras(&mut extensions);
+                    if let Some(info) = extensions.get::<HttpInfo>() {
+                        let key = (info.local_addr(), info.remote_addr());
+                        request_capture.connection.get_or_init(|| key);
+                        let handshake = Handshake {
+                            captured,
+                            key_shares: log,
+                            bytes: tcp_stats::bytes_transferred(key.0, key.1),
+                        };
+                        connections.connected(key, handshake);
+                    }
+                }
+                // The request that started the connection gets the error,
+                // and what the failed handshake captured goes with it.
+                Err(_) => {
+                    request_capture.absorb(&captured);
+                    request_log.absorb(&log);
+                }
+            }
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TlsAwareClient;
+    use crate::testutil::TestTlsServer;
+
+    #[tokio::test]
+    async fn a_poisoned_table_does_not_brick_the_client() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let server = TestTlsServer::builder()
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let client = TlsAwareClient::builder()
+            .add_root_certificate(server.cert_der().clone())
+            .build()
+            .expect("build client");
+
+        std::thread::scope(|scope| {
+            let poisoner = scope.spawn(|| {
+                let _table = client.connections.table.lock().unwrap();
+                panic!("poisoning the connection table");
+            });
+            assert!(poisoner.join().is_err());
+        });
+        assert!(client.connections.table.is_poisoned());
+
+        for _ in 0..2 {
+            let traced = client
+                .get(server.url().as_str())
+                .send()
+                .await
+                .expect("request after the poisoning");
+            assert_eq!(traced.response.status(), 200);
+            assert_eq!(traced.metadata.group.as_deref(), Some("X25519MLKEM768"));
+            assert_eq!(
+                traced.metadata.cipher.as_deref(),
+                Some("TLS13_AES_256_GCM_SHA384")
+            );
+        }
+        assert!(!client.connections.table.is_poisoned());
+        assert!(client.connections.len() >= 1);
+    }
+}
@@ -0,0 +1,505 @@
+//! A cookie jar for [`cookie_store`](crate::TlsAwareClientBuilder::cookie_store),
+//! which reqwest is built here without, and the redirects it takes over.
+//!
+//! A client with a jar follows redirects itself rather than leaving them to
+//! reqwest, which could not hand a hop the cookies the hop before it set:
+//! every hop is sent as a request of its own, with the cookies that match
+//! its URL, so its handshake is captured like that of any other request.
+//!
+//! Cookies are kept as [RFC 6265](https://www.rfc-editor.org/rfc/rfc6265)
+//! asks, short of the public suffix list: a `Domain` attribute without a dot
+//! is refused, but one naming a public suffix such as `co.uk` is not.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::{Method, StatusCode, Url};
+
+use crate::lock;
+
+/// Redirects followed for one request, as reqwest's default policy does.
+pub(crate) const MAX_REDIRECTS: usize = 10;
+
+/// The cookies a client received, shared by all of its requests.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: Mutex<Vec<Cookie>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cookie {
+    name: String,
+    value: String,
+    /// Lowercase, without a leading dot.
+    domain: String,
+    /// Whether the cookie goes to `domain` only, and not to its subdomains:
+    /// it was set without a `Domain` attribute.
+    host_only: bool,
+    path: String,
+    secure: bool,
+    http_only: bool,
+    /// `None` for a session cookie.
+    expires: Option<SystemTime>,
+}
+
+impl Cookie {
+    fn expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let host = host(url);
+        let domain = match self.host_only {
+            true => host == self.domain,
+            false => domain_match(&host, &self.domain),
+        };
+        domain && path_match(url.path(), &self.path) && (!self.secure || url.scheme() == "https")
+    }
+}
+
+impl CookieJar {
+    /// An empty jar.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a jar saved with [`save`](Self::save), or another file in the
+    /// Netscape format curl and browsers' export tools write. A file that
+    /// does not exist yet is an empty jar; cookies that have expired are
+    /// left out.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match File::open(path) {
+            Ok(file) => Self::read_netscape(BufReader::new(file)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read a jar in the Netscape format.
+    pub fn read_netscape(reader: impl BufRead) -> io::Result<Self> {
+        let now = SystemTime::now();
+        let mut cookies = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+                Some(line) => (line, true),
+                None => (line.as_str(), false),
+            };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cookie = parse_netscape(line, http_only).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: expected 7 tab-separated fields", i + 1),
+                )
+            })?;
+            if !cookie.expired(now) {
+                cookies.push(cookie);
+            }
+        }
+        Ok(Self {
+            cookies: Mutex::new(cookies),
+        })
+    }
+
+    /// Write the jar to `path` in the Netscape format, session cookies
+    /// included, with an expiry of 0. On Unix the file is only readable by
+    /// its owner, since the cookies in it are as good as the logins they
+    /// stand for.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(0o600);
+            let file = options.open(path)?;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            self.write_netscape(BufWriter::new(file))
+        }
+        #[cfg(not(unix))]
+        self.write_netscape(BufWriter::new(options.open(path)?))
+    }
+
+    /// Write the jar in the Netscape format.
+    pub fn write_netscape(&self, mut writer: impl Write) -> io::Result<()> {
+        let now = SystemTime::now();
+        writeln!(writer, "# Netscape HTTP Cookie File")?;
+        writeln!(
+            writer,
+            "# Written by pqctracer. It holds credentials: keep it private."
+        )?;
+        writeln!(writer)?;
+        for cookie in lock(&self.cookies, "cookies").iter() {
+            if cookie.expired(now) {
+                continue;
+            }
+            let expires = cookie
+                .expires
+                .and_then(|expires| expires.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs().max(1));
+            let flag = |set: bool| if set { "TRUE" } else { "FALSE" };
+            writeln!(
+                writer,
+                "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                if cookie.http_only { "#HttpOnly_" } else { "" },
+                if cookie.host_only { "" } else { "." },
+                cookie.domain,
+                flag(!cookie.host_only),
+                cookie.path,
+                flag(cookie.secure),
+                expires,
+                cookie.name,
+                cookie.value
+            )?;
+        }
+        writer.flush()
+    }
+
+    /// The number of cookies in the jar, expired ones included until they
+    /// are next looked at.
+    pub fn len(&self) -> usize {
+        lock(&self.cookies, "cookies").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Take in a `Set-Cookie` header value the server of `url` sent; a
+    /// cookie the server was not allowed to set is ignored, and one that
+    /// has expired removes the cookie it replaces.
+    pub fn set_cookie(&self, url: &Url, set_cookie: &str) {
+        let now = SystemTime::now();
+        let Some(cookie) = parse_set_cookie(url, set_cookie, now) else {
+            return;
+        };
+        let mut cookies = lock(&self.cookies, "cookies");
+        cookies.retain(|c| {
+            (&c.name, &c.domain, &c.path) != (&cookie.name, &cookie.domain, &cookie.path)
+        });
+        if !cookie.expired(now) {
+            cookies.push(cookie);
+        }
+    }
+
+    /// The `Cookie` header a request to `url` carries, `None` when no
+    /// cookie matches; cookies with longer paths come first.
+    pub fn cookie_header(&self, url: &Url) -> Option<String> {
+        let now = SystemTime::now();
+        let mut cookies = lock(&self.cookies, "cookies");
+        cookies.retain(|cookie| !cookie.expired(now));
+        let mut matching: Vec<&Cookie> = cookies.iter().filter(|c| c.matches(url)).collect();
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        let pairs: Vec<String> = matching
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        (!pairs.is_empty()).then(|| pairs.join("; "))
+    }
+
+    /// Take in every `Set-Cookie` header of a response from `url`.
+    pub(crate) fn store(&self, url: &Url, headers: &HeaderMap) {
+        for value in headers.get_all(header::SET_COOKIE) {
+            if let Ok(value) = value.to_str() {
+                self.set_cookie(url, value);
+            }
+        }
+    }
+
+    /// Give `request` the cookies that match its URL, replacing any
+    /// `Cookie` header it had.
+    pub(crate) fn add_to(&self, request: &mut reqwest::Request) {
+        let cookies = self.cookie_header(request.url());
+        let headers = request.headers_mut();
+        headers.remove(header::COOKIE);
+        if let Some(value) = cookies.and_then(|c| HeaderValue::from_str(&c).ok()) {
+            headers.insert(header::COOKIE, value);
+        }
+    }
+}
+
+/// The request a redirect from `url` asks for, `None` when `status` is no
+/// redirect or the next request cannot be made: `next` is a copy of the
+/// request that was redirected, if its body could be copied.
+///
+/// As with reqwest, 301, 302 and 303 turn into a bodiless GET, keeping HEAD,
+/// 307 and 308 repeat the request as it was, and credentials are dropped
+/// when the redirect leaves the host. The caller's own `Cookie` header goes
+/// to the first hop only.
+pub(crate) fn next_hop(
+    url: &Url,
+    status: StatusCode,
+    headers: &HeaderMap,
+    method: &Method,
+    request_headers: &HeaderMap,
+    next: Option<reqwest::Request>,
+) -> Option<reqwest::Request> {
+    let location = headers.get(header::LOCATION)?.to_str().ok()?;
+    let target = url.join(location).ok()?;
+    if !matches!(target.scheme(), "http" | "https") {
+        return None;
+    }
+    let mut request = match status {
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER => {
+            let method = match *method {
+                Method::HEAD => Method::HEAD,
+                _ => Method::GET,
+            };
+            let mut request = reqwest::Request::new(method, target.clone());
+            *request.timeout_mut() = next.as_ref().and_then(|next| next.timeout().copied());
+            *request.headers_mut() = request_headers.clone();
+            for name in [
+                header::CONTENT_TYPE,
+                header::CONTENT_LENGTH,
+                header::CONTENT_ENCODING,
+                header::TRANSFER_ENCODING,
+            ] {
+                request.headers_mut().remove(name);
+            }
+            request
+        }
+        StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => {
+            let mut request = next?;
+            *request.url_mut() = target.clone();
+            request
+        }
+        _ => return None,
+    };
+    let headers = request.headers_mut();
+    headers.remove(header::COOKIE);
+    if (host(url), url.port_or_known_default()) != (host(&target), target.port_or_known_default()) {
+        headers.remove(header::AUTHORIZATION);
+        headers.remove(header::PROXY_AUTHORIZATION);
+        headers.remove(header::WWW_AUTHENTICATE);
+    }
+    Some(request)
+}
+
+fn host(url: &Url) -> String {
+    url.host_str().unwrap_or_default().to_ascii_lowercase()
+}
+
+fn is_ip(host: &str) -> bool {
+    host.starts_with('[') || host.parse::<std::net::Ipv4Addr>().is_ok()
+}
+
+/// Whether the cookie domain `domain` covers `host`: it is the host, or a
+/// domain the host is in.
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (!is_ip(host)
+            && host
+                .strip_suffix(domain)
+                .is_some_and(|prefix| prefix.ends_with('.')))
+}
+
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || path
+            .strip_prefix(cookie_path)
+            .is_some_and(|rest| cookie_path.ends_with('/') || rest.starts_with('/'))
+}
+
+/// The path a cookie set without a `Path` attribute goes to: the request
+/// path up to, not including, its last `/`.
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => path[..end].to_string(),
+    }
+}
+
+fn parse_set_cookie(url: &Url, set_cookie: &str, now: SystemTime) -> Option<Cookie> {
+    let mut parts = set_cookie.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let (name, value) = (name.trim(), value.trim());
+    if name.is_empty() {
+        return None;
+    }
+    let host = host(url);
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.to_string(),
+        domain: host.clone(),
+        host_only: true,
+        path: default_path(url.path()),
+        secure: false,
+        http_only: false,
+        expires: None,
+    };
+    let mut max_age = None;
+    for attribute in parts {
+        let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "expires" => cookie.expires = cookie.expires.or(parse_date(value)),
+            "max-age" => {
+                if let Ok(seconds) = value.parse::<i64>() {
+                    max_age = Some(match u64::try_from(seconds) {
+                        Ok(seconds) if seconds > 0 => now + Duration::from_secs(seconds),
+                        _ => UNIX_EPOCH,
+                    });
+                }
+            }
+            "domain" if !value.is_empty() => {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                // Refuse cookies for other sites and for whole top-level
+                // domains.
+                if !domain_match(&host, &domain) || (!domain.contains('.') && domain != host) {
+                    return None;
+                }
+                cookie.host_only = false;
+                cookie.domain = domain;
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_string(),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            _ => {}
+        }
+    }
+    if cookie.secure && url.scheme() != "https" {
+        return None;
+    }
+    cookie.expires = max_age.or(cookie.expires);
+    Some(cookie)
+}
+
+fn parse_netscape(line: &str, http_only: bool) -> Option<Cookie> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [domain, subdomains, path, secure, expires, name, value] = fields[..] else {
+        return None;
+    };
+    let expires: u64 = expires.trim().parse().ok()?;
+    Some(Cookie {
+        name: name.to_string(),
+        value: value.to_string(),
+        domain: domain.trim_start_matches('.').to_ascii_lowercase(),
+        host_only: !subdomains.eq_ignore_ascii_case("TRUE"),
+        path: path.to_string(),
+        secure: secure.eq_ignore_ascii_case("TRUE"),
+        http_only,
+        expires: (expires > 0).then(|| UNIX_EPOCH + Duration::from_secs(expires)),
+    })
+}
+
+/// Parse an `Expires` date the way RFC 6265 section 5.1.1 does, which
+/// copes with the several formats servers send, e.g.
+/// `Wed, 21 Oct 2026 07:28:00 GMT` and `Wednesday, 21-Oct-26 07:28:00 GMT`.
+fn parse_date(date: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let (mut time, mut day, mut month, mut year) = (None, None, None, None);
+    let tokens = date
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == ':'))
+        .filter(|token| !token.is_empty());
+    for token in tokens {
+        let numeric = token.bytes().all(|b| b.is_ascii_digit());
+        if time.is_none() && token.contains(':') {
+            let parts: Vec<u64> = token.split(':').filter_map(|p| p.parse().ok()).collect();
+            if let [h, m, s] = parts[..] {
+                time = Some((h, m, s));
+                continue;
+            }
+        }
+        if day.is_none() && numeric && token.len() <= 2 {
+            day = token.parse::<u64>().ok();
+            continue;
+        }
+        if month.is_none()
+            && let Some(prefix) = token.get(..3)
+            && let Some(i) = MONTHS.iter().position(|m| prefix.eq_ignore_ascii_case(m))
+        {
+            month = Some(i as u64 + 1);
+            continue;
+        }
+        if year.is_none() && numeric && (2..=4).contains(&token.len()) {
+            year = token.parse::<i64>().ok();
+        }
+    }
+    let year = match year? {
+        year @ 70..=99 => year + 1900,
+        year @ 0..=69 => year + 2000,
+        year => year,
+    };
+    let ((hour, minute, second), day, month) = (time?, day?, month?);
+    if !(1..=31).contains(&day) || year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    let seconds = days * 86_400 + (hour * 3600 + minute * 60 + second) as i64;
+    Some(match u64::try_from(seconds) {
+        Ok(seconds) => UNIX_EPOCH + Duration::from_secs(seconds),
+        Err(_) => UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs()),
+    })
+}
+
+/// Days from 1970-01-01 to the given date of the proleptic Gregorian
+/// calendar.
+fn days_from_civil(year: i64, month: u64, day: u64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_parse_in_every_format() {
+        let expected = Some(UNIX_EPOCH + Duration::from_secs(1_792_567_680));
+        for date in [
+            "Wed, 21 Oct 2026 07:28:00 GMT",
+            "Wednesday, 21-Oct-26 07:28:00 GMT",
+            "Wed Oct 21 07:28:00 2026",
+            "21 oct 2026 7:28:0",
+        ] {
+            assert_eq!(parse_date(date), expected, "{}", date);
+        }
+        assert_eq!(
+            parse_date("Thu, 01 Jan 1970 00:00:00 GMT"),
+            Some(UNIX_EPOCH)
+        );
+        assert_eq!(
+            parse_date("Mon, 01 Jan 1960 00:00:00 GMT"),
+            Some(UNIX_EPOCH - Duration::from_secs(315_619_200))
+        );
+        for date in [
+            "",
+            "Wed, 21 Oct 2026",
+            "32 Oct 2026 07:28:00",
+            "21 Foo 2026 07:28:00",
+        ] {
+            assert_eq!(parse_date(date), None, "{}", date);
+        }
+    }
+
+    #[test]
+    fn a_poisoned_jar_keeps_its_cookies() {
+        let jar = CookieJar::new();
+        let site: Url = "https://example.com/".parse().unwrap();
+        jar.set_cookie(&site, "a=1");
+        std::thread::scope(|scope| {
+            let poisoner = scope.spawn(|| {
+                let _cookies = jar.cookies.lock().unwrap();
+                panic!("poisoning the cookie jar");
+            });
+            assert!(poisoner.join().is_err());
+        });
+        jar.set_cookie(&site, "b=2");
+        assert_eq!(jar.cookie_header(&site).as_deref(), Some("a=1; b=2"));
+        assert!(!jar.cookies.is_poisoned());
+    }
+}
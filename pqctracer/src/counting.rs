@@ -0,0 +1,93 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::alerts::{AlertInfo, AlertScanner};
+use crate::server_hello::{ExtensionInfo, ServerHelloScanner};
+
+/// Wraps a stream and counts the bytes flowing in each direction, noting
+/// the plaintext alerts among the received ones and, if asked to, the
+/// ServerHello's extensions.
+pub(crate) struct CountingStream<S> {
+    inner: S,
+    sent: u64,
+    received: u64,
+    alerts: AlertScanner,
+    server_hello: Option<ServerHelloScanner>,
+}
+
+impl<S> CountingStream<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self {
+            inner,
+            sent: 0,
+            received: 0,
+            alerts: AlertScanner::default(),
+            server_hello: None,
+        }
+    }
+
+    /// Also parse the ServerHello's extensions, if `enable`.
+    pub(crate) fn deep_inspect(mut self, enable: bool) -> Self {
+        self.server_hello = enable.then(ServerHelloScanner::default);
+        self
+    }
+
+    pub(crate) fn bytes_sent(&self) -> u64 {
+        self.sent
+    }
+
+    pub(crate) fn bytes_received(&self) -> u64 {
+        self.received
+    }
+
+    pub(crate) fn alerts_received(&self) -> Vec<AlertInfo> {
+        self.alerts.alerts().to_vec()
+    }
+
+    pub(crate) fn server_extensions(&self) -> Option<Vec<ExtensionInfo>> {
+        self.server_hello.as_ref()?.extensions()
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for CountingStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let this = &mut *self;
+        this.received += (buf.filled().len() - before) as u64;
+        this.alerts.feed(&buf.filled()[before..]);
+        if let Some(server_hello) = &mut this.server_hello {
+            server_hello.feed(&buf.filled()[before..]);
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for CountingStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = result {
+            self.sent += n as u64;
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,222 @@
+//! DANE (RFC 6698, 7671): the TLSA records a host publishes for a service,
+//! checked against the certificate chain it presented.
+//!
+//! The TLSA lookup is only as trustworthy as its DNSSEC validation, which
+//! the tracer leaves to the resolver: [`DaneResult::authenticated`] reports
+//! the resolver's AD bit, not a validation of its own.
+
+use std::fmt;
+use std::io;
+
+use aws_lc_rs::digest;
+use rustls_pki_types::CertificateDer;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::TlsMetadata;
+use crate::dns::{TlsaAnswer, TlsaRecord};
+
+/// How a host's TLSA records compare with the chain it presented.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DaneResult {
+    /// How many TLSA records the host publishes; 0 means DANE is not
+    /// published for the service, which is no error.
+    pub records_found: usize,
+    /// Whether at least one record matched the chain.
+    pub matched: bool,
+    /// Whether the resolver vouched for the records with the AD bit.
+    pub authenticated: bool,
+    /// The outcome for each record, in the order the resolver gave them.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub details: Vec<TlsaMatch>,
+    /// Why the records could not be checked: the lookup failed, or there
+    /// was no chain to check them against, e.g. after a resumed handshake.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub error: Option<String>,
+}
+
+impl DaneResult {
+    /// Whether the host publishes TLSA records for the service.
+    pub fn is_published(&self) -> bool {
+        self.records_found > 0
+    }
+}
+
+/// `matched, 1 of 2 records (DNSSEC-authenticated)`, `not published` or
+/// `not checked (lookup failed: timed out)`.
+impl fmt::Display for DaneResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(error) = &self.error {
+            return write!(f, "not checked ({})", error);
+        }
+        if !self.is_published() {
+            return f.write_str("not published");
+        }
+        let matches = self
+            .details
+            .iter()
+            .filter(|detail| matches!(detail.outcome, TlsaOutcome::Matched { .. }))
+            .count();
+        write!(
+            f,
+            "{}, {} of {} records ({})",
+            if self.matched { "matched" } else { "NO MATCH" },
+            matches,
+            self.records_found,
+            if self.authenticated {
+                "DNSSEC-authenticated"
+            } else {
+                "not DNSSEC-authenticated"
+            }
+        )
+    }
+}
+
+/// One TLSA record and how it compares with the chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TlsaMatch {
+    pub record: TlsaRecord,
+    pub outcome: TlsaOutcome,
+}
+
+/// `3 1 1 0c72ac70…: matches certificate 0`.
+impl fmt::Display for TlsaMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.record, self.outcome)
+    }
+}
+
+/// How one TLSA record compares with the chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "outcome", rename_all = "kebab-case"))]
+pub enum TlsaOutcome {
+    /// The record matches the certificate at this position in the chain,
+    /// 0 being the server's own.
+    Matched { certificate: usize },
+    /// The record matches, but names a PKIX usage (0 or 1) and the chain
+    /// failed the usual validation, or was not validated at all.
+    PkixUnverified { certificate: usize },
+    /// No certificate the record may name matches it.
+    NoMatch,
+    /// The record uses a usage, selector or matching type this tracer does
+    /// not know, and is ignored as RFC 6698 requires.
+    Unusable { reason: String },
+}
+
+/// `matches certificate 0`, `no match` or `unusable (unknown selector 7)`.
+impl fmt::Display for TlsaOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsaOutcome::Matched { certificate } => {
+                write!(f, "matches certificate {}", certificate)
+            }
+            TlsaOutcome::PkixUnverified { certificate } => write!(
+                f,
+                "matches certificate {}, but the chain is not PKIX-valid",
+                certificate
+            ),
+            TlsaOutcome::NoMatch => f.write_str("no match"),
+            TlsaOutcome::Unusable { reason } => write!(f, "unusable ({})", reason),
+        }
+    }
+}
+
+/// Check the outcome of a TLSA lookup against the chain in `metadata`.
+pub(crate) fn check(tlsa: io::Result<TlsaAnswer>, metadata: &TlsMetadata) -> DaneResult {
+    let answer = match tlsa {
+        Ok(answer) => answer,
+        Err(e) => {
+            return DaneResult {
+                records_found: 0,
+                matched: false,
+                authenticated: false,
+                details: Vec::new(),
+                error: Some(format!("lookup failed: {}", e)),
+            };
+        }
+    };
+    let chain = &metadata.peer_certificates;
+    let records_found = answer.records.len();
+    let error = (records_found > 0 && chain.is_empty())
+        .then(|| "no certificate chain captured".to_string());
+    let pkix_valid = matches!(metadata.cert_validation, Some(Ok(())));
+    let details: Vec<TlsaMatch> = answer
+        .records
+        .into_iter()
+        .filter(|_| error.is_none())
+        .map(|record| TlsaMatch {
+            outcome: outcome(&record, chain, pkix_valid),
+            record,
+        })
+        .collect();
+    DaneResult {
+        records_found,
+        matched: details
+            .iter()
+            .any(|detail| matches!(detail.outcome, TlsaOutcome::Matched { .. })),
+        authenticated: answer.authenticated,
+        details,
+        error,
+    }
+}
+
+fn outcome(record: &TlsaRecord, chain: &[CertificateDer<'_>], pkix_valid: bool) -> TlsaOutcome {
+    // End-entity usages name the server's own certificate, trust-anchor
+    // usages one of the issuers it sent along.
+    let candidates = match record.usage {
+        1 | 3 => 0..chain.len().min(1),
+        0 | 2 => 1.min(chain.len())..chain.len(),
+        usage => {
+            return TlsaOutcome::Unusable {
+                reason: format!("unknown usage {}", usage),
+            };
+        }
+    };
+    if record.selector > 1 {
+        return TlsaOutcome::Unusable {
+            reason: format!("unknown selector {}", record.selector),
+        };
+    }
+    let algorithm = match record.matching_type {
+        0 => None,
+        1 => Some(&digest::SHA256),
+        2 => Some(&digest::SHA512),
+        matching_type => {
+            return TlsaOutcome::Unusable {
+                reason: format!("unknown matching type {}", matching_type),
+            };
+        }
+    };
+
+    let matching = candidates.into_iter().find(|&index| {
+        let Some(selected) = selected(&chain[index], record.selector) else {
+            return false;
+        };
+        match algorithm {
+            Some(algorithm) => digest::digest(algorithm, selected).as_ref() == record.data,
+            None => selected == record.data,
+        }
+    });
+    match matching {
+        None => TlsaOutcome::NoMatch,
+        Some(certificate) if record.usage < 2 && !pkix_valid => {
+            TlsaOutcome::PkixUnverified { certificate }
+        }
+        Some(certificate) => TlsaOutcome::Matched { certificate },
+    }
+}
+
+/// The part of `cert` a record with `selector` describes: the whole
+/// certificate, or its SubjectPublicKeyInfo.
+fn selected<'a>(cert: &'a CertificateDer<'_>, selector: u8) -> Option<&'a [u8]> {
+    if selector == 0 {
+        return Some(cert.as_ref());
+    }
+    let (_, parsed) = X509Certificate::from_der(cert.as_ref()).ok()?;
+    Some(parsed.tbs_certificate.subject_pki.raw)
+}
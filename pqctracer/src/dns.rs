@@ -0,0 +1,673 @@
+//! HTTPS (SVCB-compatible, type 65) DNS records, looked up before a request
+//! to report what the target advertises: ALPN protocols, ECH, a port
+//! override and address hints. TLSA records (type 52) for DANE are looked
+//! up the same way when asked for.
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rustls::client::danger::ServerCertVerifier;
+use rustls::client::{ClientSessionStore, EchConfig, EchMode, ResolvesClientCert, Resumption};
+use rustls::crypto::CryptoProvider;
+use rustls::crypto::aws_lc_rs::hpke::ALL_SUPPORTED_SUITES;
+use rustls_pki_types::EchConfigListBytes;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::dane::{self, DaneResult};
+use crate::{capture, key_shares};
+use crate::{HttpSettings, TlsMetadata};
+
+const TYPE_TLSA: u16 = 52;
+const TYPE_HTTPS: u16 = 65;
+const TYPE_OPT: u16 = 41;
+const CLASS_IN: u16 = 1;
+
+/// UDP payload size advertised with EDNS(0); ECH configs rarely fit into
+/// the classic 512 bytes.
+const UDP_PAYLOAD_SIZE: u16 = 1232;
+
+/// Upper bound for each query to one name server.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Upper bound for a whole lookup, whichever resolver does it.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One HTTPS resource record.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HttpsRecord {
+    /// 0 for an alias record, otherwise lower values are preferred.
+    pub priority: u16,
+    /// The target name; `.` stands for the queried name itself.
+    pub target: String,
+    /// Protocols offered beyond the default `http/1.1`, e.g. `h2`, `h3`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub alpn: Vec<String>,
+    /// Whether `http/1.1` is left out of the offered protocols.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub no_default_alpn: bool,
+    /// Port the service listens on instead of 443.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub port: Option<u16>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ipv4_hints: Vec<Ipv4Addr>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ipv6_hints: Vec<Ipv6Addr>,
+    /// Whether the record carries an ECH configuration.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ech: bool,
+    /// The encoded `ECHConfigList`, if present.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub ech_config_list: Option<Vec<u8>>,
+}
+
+impl HttpsRecord {
+    /// Whether this is an alias record, pointing at another name rather than
+    /// describing the service.
+    pub fn is_alias(&self) -> bool {
+        self.priority == 0
+    }
+}
+
+/// Zone-file presentation form, e.g. `1 . alpn=h2,h3 ipv4hint=192.0.2.1 ech`;
+/// the ECH configuration itself is left out.
+impl fmt::Display for HttpsRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.priority, self.target)?;
+        if !self.alpn.is_empty() {
+            write!(f, " alpn={}", self.alpn.join(","))?;
+        }
+        if self.no_default_alpn {
+            f.write_str(" no-default-alpn")?;
+        }
+        if let Some(port) = self.port {
+            write!(f, " port={}", port)?;
+        }
+        if !self.ipv4_hints.is_empty() {
+            write!(f, " ipv4hint={}", comma_separated(&self.ipv4_hints))?;
+        }
+        if self.ech {
+            f.write_str(" ech")?;
+        }
+        if !self.ipv6_hints.is_empty() {
+            write!(f, " ipv6hint={}", comma_separated(&self.ipv6_hints))?;
+        }
+        Ok(())
+    }
+}
+
+fn comma_separated<T: fmt::Display>(items: &[T]) -> String {
+    items.iter().map(T::to_string).collect::<Vec<_>>().join(",")
+}
+
+/// One TLSA resource record (RFC 6698), which says what certificate or key
+/// a service presents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TlsaRecord {
+    /// 0 PKIX-TA, 1 PKIX-EE, 2 DANE-TA or 3 DANE-EE: whether the record
+    /// names a trust anchor or the server's own certificate, and whether
+    /// the chain must pass the usual validation too.
+    pub usage: u8,
+    /// 0 for the whole certificate, 1 for its SubjectPublicKeyInfo.
+    pub selector: u8,
+    /// 0 for the selected data itself, 1 for its SHA-256, 2 for its
+    /// SHA-512.
+    pub matching_type: u8,
+    /// The certificate association data, in hex when serialized.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_util::hex"))]
+    pub data: Vec<u8>,
+}
+
+/// Zone-file presentation form, e.g. `3 1 1 0c72ac70…`.
+impl fmt::Display for TlsaRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.usage,
+            self.selector,
+            self.matching_type,
+            hex(&self.data)
+        )
+    }
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The TLSA records of a name, as the resolver answered.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsaAnswer {
+    pub records: Vec<TlsaRecord>,
+    /// Whether the resolver set the AD bit, vouching that it validated the
+    /// answer with DNSSEC. The tracer does not validate DNSSEC itself, so
+    /// this is only as trustworthy as the resolver and the path to it.
+    pub authenticated: bool,
+}
+
+/// The future returned by [`HttpsResolver::lookup_https`].
+pub type LookupFuture<'a> = Pin<Box<dyn Future<Output = io::Result<Vec<HttpsRecord>>> + Send + 'a>>;
+
+/// The future returned by [`HttpsResolver::lookup_tlsa`].
+pub type TlsaLookupFuture<'a> = Pin<Box<dyn Future<Output = io::Result<TlsaAnswer>> + Send + 'a>>;
+
+/// Looks up the HTTPS records of a name, and its TLSA records for
+/// [DANE](crate::TlsAwareClientBuilder::dane).
+///
+/// [`SystemResolver`] asks the system's name servers; tests can plug in a
+/// resolver returning crafted records.
+pub trait HttpsResolver: Send + Sync {
+    /// The HTTPS records of `name`, empty when it has none.
+    fn lookup_https<'a>(&'a self, name: &'a str) -> LookupFuture<'a>;
+
+    /// The TLSA records of `name`, empty when it has none. Resolvers that
+    /// cannot look them up keep this default, which fails with
+    /// `ErrorKind::Unsupported`.
+    fn lookup_tlsa<'a>(&'a self, name: &'a str) -> TlsaLookupFuture<'a> {
+        let _ = name;
+        Box::pin(async { Err(io::ErrorKind::Unsupported.into()) })
+    }
+}
+
+/// Queries the name servers from `/etc/resolv.conf` directly, over UDP with
+/// a fallback to TCP for truncated answers.
+#[derive(Debug, Clone)]
+pub struct SystemResolver {
+    name_servers: Vec<SocketAddr>,
+}
+
+impl SystemResolver {
+    /// The name servers listed in `/etc/resolv.conf`, or the local host if
+    /// there are none.
+    pub fn new() -> Self {
+        let mut name_servers: Vec<SocketAddr> = std::fs::read_to_string("/etc/resolv.conf")
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("nameserver"))
+            // Drop IPv6 zone ids such as `%eth0`.
+            .filter_map(|addr| addr.trim().split('%').next()?.parse::<IpAddr>().ok())
+            .map(|ip| SocketAddr::new(ip, 53))
+            .collect();
+        if name_servers.is_empty() {
+            name_servers.push(SocketAddr::from(([127, 0, 0, 1], 53)));
+        }
+        Self { name_servers }
+    }
+
+    /// Query `name_servers` in order instead.
+    pub fn with_name_servers(name_servers: Vec<SocketAddr>) -> Self {
+        Self { name_servers }
+    }
+
+    /// Ask each name server in turn for the `record_type` records of
+    /// `name`, until one answers.
+    async fn lookup<T>(
+        &self,
+        name: &str,
+        record_type: u16,
+        parse: RecordParser<T>,
+    ) -> io::Result<Answer<T>> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no name servers");
+        for server in &self.name_servers {
+            let query = self.query(*server, name, record_type, parse);
+            match tokio::time::timeout(QUERY_TIMEOUT, query).await {
+                Ok(Ok(answer)) => return Ok(answer),
+                Ok(Err(e)) => last_error = e,
+                Err(_) => last_error = io::ErrorKind::TimedOut.into(),
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn query<T>(
+        &self,
+        server: SocketAddr,
+        name: &str,
+        record_type: u16,
+        parse: RecordParser<T>,
+    ) -> io::Result<Answer<T>> {
+        let id = query_id();
+        let query = encode_query(id, name, record_type)?;
+
+        let socket = UdpSocket::bind(if server.is_ipv4() {
+            SocketAddr::from(([0, 0, 0, 0], 0))
+        } else {
+            SocketAddr::from(([0u16; 8], 0))
+        })
+        .await?;
+        socket.connect(server).await?;
+        socket.send(&query).await?;
+        let mut buf = vec![0; usize::from(UDP_PAYLOAD_SIZE)];
+        let response = loop {
+            let len = socket.recv(&mut buf).await?;
+            // Ignore stray datagrams that do not answer this query.
+            if buf[..len].starts_with(&id.to_be_bytes()) {
+                break &buf[..len];
+            }
+        };
+        match decode_response(id, response, record_type, parse) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            result => return result,
+        }
+
+        // Truncated: repeat the query over TCP.
+        let mut stream = TcpStream::connect(server).await?;
+        let len = u16::try_from(query.len()).expect("queries are short");
+        stream.write_all(&len.to_be_bytes()).await?;
+        stream.write_all(&query).await?;
+        let len = stream.read_u16().await?;
+        let mut response = vec![0; usize::from(len)];
+        stream.read_exact(&mut response).await?;
+        decode_response(id, &response, record_type, parse)
+    }
+}
+
+impl Default for SystemResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpsResolver for SystemResolver {
+    fn lookup_https<'a>(&'a self, name: &'a str) -> LookupFuture<'a> {
+        Box::pin(async move {
+            let mut answer = self
+                .lookup(name, TYPE_HTTPS, |reader, end| reader.https_record(end))
+                .await?;
+            answer.records.sort_by_key(|record| record.priority);
+            Ok(answer.records)
+        })
+    }
+
+    fn lookup_tlsa<'a>(&'a self, name: &'a str) -> TlsaLookupFuture<'a> {
+        Box::pin(async move {
+            let answer = self
+                .lookup(name, TYPE_TLSA, |reader, end| reader.tlsa_record(end))
+                .await?;
+            Ok(TlsaAnswer {
+                records: answer.records,
+                authenticated: answer.authenticated,
+            })
+        })
+    }
+}
+
+/// A client's HTTPS record lookups, set up by
+/// [`TlsAwareClientBuilder::https_resolver`](crate::TlsAwareClientBuilder::https_resolver).
+pub(crate) struct DnsLookup {
+    pub(crate) resolver: Arc<dyn HttpsResolver>,
+    /// Present when ECH is enabled.
+    pub(crate) ech: Option<EchSettings>,
+    /// Whether to look up TLSA records too.
+    pub(crate) dane: bool,
+}
+
+/// What [`DnsLookup::prepare`] found for a request.
+pub(crate) struct Prepared {
+    pub(crate) https_records: Vec<HttpsRecord>,
+    pub(crate) ech_client: Option<reqwest::Client>,
+    /// The TLSA lookup, when DANE is enabled and the URL has a domain.
+    pub(crate) tlsa: Option<io::Result<TlsaAnswer>>,
+}
+
+/// What a client needs to build an ECH-enabled `reqwest` client for a host
+/// that matches its shared one in everything but ECH.
+pub(crate) struct EchSettings {
+    pub(crate) provider: Arc<CryptoProvider>,
+    pub(crate) verifier: Arc<dyn ServerCertVerifier>,
+    pub(crate) session_store: Arc<dyn ClientSessionStore>,
+    pub(crate) cert_resolver: Arc<dyn ResolvesClientCert>,
+    pub(crate) http: HttpSettings,
+}
+
+impl DnsLookup {
+    /// Look up the HTTPS records for `url`'s host and, if ECH is enabled and
+    /// one of them has a usable ECH configuration, build a client that uses
+    /// it. Nothing here fails the request: lookup errors, IP hosts and
+    /// unusable configurations all just yield less. TLSA records are looked
+    /// up alongside, and their errors kept for the DANE result.
+    pub(crate) async fn prepare(&self, url: &reqwest::Url) -> Prepared {
+        let (Some(host), "https") = (url.domain(), url.scheme()) else {
+            return Prepared {
+                https_records: Vec::new(),
+                ech_client: None,
+                tlsa: None,
+            };
+        };
+        let port = url.port_or_known_default().unwrap_or(443);
+        let name = query_name(host, port);
+        let https = tokio::time::timeout(LOOKUP_TIMEOUT, self.resolver.lookup_https(&name));
+        let (records, tlsa) = futures_util::future::join(https, self.tlsa(host, port)).await;
+        let records = records.ok().and_then(Result::ok).unwrap_or_default();
+
+        let ech_client = self.ech.as_ref().and_then(|ech| {
+            records
+                .iter()
+                .filter(|record| !record.is_alias())
+                .find_map(|record| record.ech_config_list.clone())
+                .and_then(|list| ech.client(list))
+        });
+        Prepared {
+            https_records: records,
+            ech_client,
+            tlsa,
+        }
+    }
+
+    /// Look up the TLSA records of the service at `host:port`, if DANE is
+    /// enabled.
+    pub(crate) async fn tlsa(&self, host: &str, port: u16) -> Option<io::Result<TlsaAnswer>> {
+        if !self.dane {
+            return None;
+        }
+        let name = format!("_{}._tcp.{}", port, host);
+        let lookup = tokio::time::timeout(LOOKUP_TIMEOUT, self.resolver.lookup_tlsa(&name));
+        Some(
+            lookup
+                .await
+                .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into())),
+        )
+    }
+
+    /// Look up the TLSA records of the service at `host:port`, if DANE is
+    /// enabled, and check them against the chain in `metadata`.
+    pub(crate) async fn dane(
+        &self,
+        host: &str,
+        port: u16,
+        metadata: &TlsMetadata,
+    ) -> Option<DaneResult> {
+        let tlsa = self.tlsa(host, port).await?;
+        Some(dane::check(tlsa, metadata))
+    }
+}
+
+impl EchSettings {
+    fn client(&self, ech_config_list: Vec<u8>) -> Option<reqwest::Client> {
+        let config = EchConfig::new(
+            EchConfigListBytes::from(ech_config_list),
+            ALL_SUPPORTED_SUITES,
+        )
+        .ok()?;
+        let provider = capture::observed(&key_shares::observed(&self.provider));
+        let mut tls_config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_ech(EchMode::Enable(config))
+            .ok()?
+            .dangerous()
+            .with_custom_certificate_verifier(self.verifier.clone())
+            .with_client_cert_resolver(self.cert_resolver.clone());
+        tls_config.resumption = Resumption::store(self.session_store.clone());
+        tls_config.alpn_protocols = self.http.alpn.clone();
+        self.http.client(tls_config).ok()
+    }
+}
+
+/// The name whose HTTPS records describe the service at `host:port`
+/// (RFC 9460, section 9.1).
+pub(crate) fn query_name(host: &str, port: u16) -> String {
+    if port == 443 {
+        host.to_string()
+    } else {
+        format!("_{}._https.{}", port, host)
+    }
+}
+
+fn query_id() -> u16 {
+    static COUNTER: AtomicU16 = AtomicU16::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    (nanos as u16) ^ COUNTER.fetch_add(0x9e37, Ordering::Relaxed)
+}
+
+fn encode_query(id: u16, name: &str, record_type: u16) -> io::Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(64);
+    query.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired and AD, asking for the AD bit in the response
+    // (RFC 6840, section 5.7); one question and one additional record (OPT).
+    query.extend_from_slice(&[0x01, 0x20, 0, 1, 0, 0, 0, 0, 0, 1]);
+    for label in name.trim_end_matches('.').split('.') {
+        let len = u8::try_from(label.len())
+            .ok()
+            .filter(|len| (1..64).contains(len))
+            .ok_or_else(|| invalid(format!("invalid DNS name: {}", name)))?;
+        query.push(len);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&record_type.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    // EDNS(0): root name, type OPT, UDP payload size as class, TTL 0, no data.
+    query.push(0);
+    query.extend_from_slice(&TYPE_OPT.to_be_bytes());
+    query.extend_from_slice(&UDP_PAYLOAD_SIZE.to_be_bytes());
+    query.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    Ok(query)
+}
+
+/// Reads the data of one resource record, which ends at the given offset.
+type RecordParser<T> = fn(&mut Reader<'_>, usize) -> io::Result<T>;
+
+/// The records of one type in a response.
+struct Answer<T> {
+    records: Vec<T>,
+    /// Whether the AD bit was set.
+    authenticated: bool,
+}
+
+/// Parse the `record_type` records out of a response. A truncated response
+/// yields an `Interrupted` error so that the caller retries over TCP.
+fn decode_response<T>(
+    id: u16,
+    message: &[u8],
+    record_type: u16,
+    parse: RecordParser<T>,
+) -> io::Result<Answer<T>> {
+    let mut reader = Reader { message, offset: 0 };
+    if reader.u16()? != id {
+        return Err(invalid("response does not match the query"));
+    }
+    let flags = reader.u16()?;
+    if flags & 0x8000 == 0 {
+        return Err(invalid("not a DNS response"));
+    }
+    if flags & 0x0200 != 0 {
+        return Err(io::ErrorKind::Interrupted.into());
+    }
+    let authenticated = flags & 0x0020 != 0;
+    match flags & 0x000f {
+        0 => {}
+        // NXDOMAIN: no records, not a failure.
+        3 => {
+            return Ok(Answer {
+                records: Vec::new(),
+                authenticated,
+            });
+        }
+        rcode => return Err(io::Error::other(format!("DNS error, rcode {}", rcode))),
+    }
+    let questions = reader.u16()?;
+    let answers = reader.u16()?;
+    reader.skip(4)?;
+
+    for _ in 0..questions {
+        reader.name()?;
+        reader.skip(4)?;
+    }
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        reader.name()?;
+        let answer_type = reader.u16()?;
+        reader.skip(6)?;
+        let len = usize::from(reader.u16()?);
+        let end = reader.offset + len;
+        if end > message.len() {
+            return Err(invalid("truncated record"));
+        }
+        // CNAMEs leading to the records are skipped along with anything
+        // else that is not a record of the type asked for.
+        if answer_type == record_type {
+            records.push(parse(&mut reader, end)?);
+        }
+        reader.offset = end;
+    }
+    Ok(Answer {
+        records,
+        authenticated,
+    })
+}
+
+struct Reader<'a> {
+    message: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> io::Result<&[u8]> {
+        let bytes = self
+            .message
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| invalid("truncated DNS message"))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> io::Result<()> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Read a possibly compressed name, returning it in dotted form.
+    fn name(&mut self) -> io::Result<String> {
+        let mut labels: Vec<String> = Vec::new();
+        let mut offset = self.offset;
+        let mut resume = None;
+        // Every pointer must go backwards, which rules out loops.
+        let mut limit = offset;
+        loop {
+            let len = *self
+                .message
+                .get(offset)
+                .ok_or_else(|| invalid("truncated name"))?;
+            match len {
+                0 => {
+                    offset += 1;
+                    break;
+                }
+                len if len & 0xc0 == 0xc0 => {
+                    let low = *self
+                        .message
+                        .get(offset + 1)
+                        .ok_or_else(|| invalid("truncated name"))?;
+                    let target = usize::from(len & 0x3f) << 8 | usize::from(low);
+                    if target >= limit {
+                        return Err(invalid("invalid name compression"));
+                    }
+                    resume.get_or_insert(offset + 2);
+                    offset = target;
+                    limit = target;
+                }
+                len if len < 64 => {
+                    let label = self
+                        .message
+                        .get(offset + 1..offset + 1 + usize::from(len))
+                        .ok_or_else(|| invalid("truncated name"))?;
+                    labels.push(String::from_utf8_lossy(label).into_owned());
+                    offset += 1 + usize::from(len);
+                }
+                _ => return Err(invalid("invalid label")),
+            }
+        }
+        self.offset = resume.unwrap_or(offset);
+        Ok(if labels.is_empty() {
+            ".".to_string()
+        } else {
+            labels.join(".")
+        })
+    }
+
+    fn https_record(&mut self, end: usize) -> io::Result<HttpsRecord> {
+        let mut record = HttpsRecord {
+            priority: self.u16()?,
+            target: self.name()?,
+            ..Default::default()
+        };
+        while self.offset < end {
+            let key = self.u16()?;
+            let len = usize::from(self.u16()?);
+            let value = self.bytes(len)?;
+            match key {
+                1 => record.alpn = alpn_ids(value)?,
+                2 => record.no_default_alpn = true,
+                3 => {
+                    let port: [u8; 2] = value.try_into().map_err(|_| invalid("invalid port"))?;
+                    record.port = Some(u16::from_be_bytes(port));
+                }
+                4 => {
+                    record.ipv4_hints = value
+                        .chunks_exact(4)
+                        .map(|ip| Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]))
+                        .collect()
+                }
+                5 => {
+                    record.ech = true;
+                    record.ech_config_list = Some(value.to_vec());
+                }
+                6 => {
+                    record.ipv6_hints = value
+                        .chunks_exact(16)
+                        .map(|ip| Ipv6Addr::from(<[u8; 16]>::try_from(ip).expect("16 bytes")))
+                        .collect()
+                }
+                // `mandatory` and keys defined later are of no interest.
+                _ => {}
+            }
+        }
+        Ok(record)
+    }
+
+    fn tlsa_record(&mut self, end: usize) -> io::Result<TlsaRecord> {
+        let header = self.bytes(3)?;
+        let (usage, selector, matching_type) = (header[0], header[1], header[2]);
+        let len = end
+            .checked_sub(self.offset)
+            .ok_or_else(|| invalid("truncated record"))?;
+        Ok(TlsaRecord {
+            usage,
+            selector,
+            matching_type,
+            data: self.bytes(len)?.to_vec(),
+        })
+    }
+}
+
+fn alpn_ids(mut value: &[u8]) -> io::Result<Vec<String>> {
+    let mut ids = Vec::new();
+    while let Some((&len, rest)) = value.split_first() {
+        let len = usize::from(len);
+        if rest.len() < len {
+            return Err(invalid("invalid alpn list"));
+        }
+        ids.push(String::from_utf8_lossy(&rest[..len]).into_owned());
+        value = &rest[len..];
+    }
+    Ok(ids)
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
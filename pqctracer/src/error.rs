@@ -0,0 +1,742 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::TlsMetadata;
+use crate::pinning::PinMismatch;
+use crate::starttls::Protocol;
+
+/// Errors produced while building a client or driving a connection.
+///
+/// [`TlsAwareClient::execute`](crate::TlsAwareClient::execute) keeps returning
+/// `reqwest::Error` directly.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TraceError {
+    /// An HTTP request sent through `reqwest` failed.
+    Http(reqwest::Error),
+    /// The client configuration is invalid.
+    Config(String),
+    /// Connecting to or talking to the server failed.
+    Io(io::Error),
+    /// The server did not answer within the allowed time.
+    Timeout,
+    /// The host is not a valid DNS name or IP address for SNI.
+    InvalidServerName(String),
+    /// The TLS handshake failed.
+    Tls(rustls::Error),
+    /// The server does not advertise STARTTLS support.
+    StartTlsNotSupported { protocol: Protocol },
+    /// The server sent an unexpected reply during the plaintext preamble.
+    StartTlsRejected { protocol: Protocol, reply: String },
+    /// The URL cannot be parsed or is not usable for the request.
+    InvalidUrl(String),
+    /// The server answered the WebSocket upgrade request with a redirect.
+    WebSocketRedirected { status: u16, location: Option<String> },
+    /// The server answered the WebSocket upgrade request with a status other
+    /// than `101 Switching Protocols`.
+    WebSocketRejected { status: u16, reason: String },
+    /// The server's WebSocket upgrade response is malformed or does not
+    /// match the request.
+    WebSocketProtocol(String),
+    /// The request succeeded but broke a requirement it was sent with, e.g.
+    /// [`KxOverride::PqcRequired`](crate::KxOverride::PqcRequired) and a
+    /// classical group; `metadata` is what the handshake negotiated.
+    PolicyViolation { reason: String, metadata: Box<TlsMetadata> },
+    /// The request failed after its handshake completed, e.g. on a
+    /// connection reset while the body was uploaded; `metadata` is what the
+    /// handshake negotiated.
+    RequestFailed { error: reqwest::Error, metadata: Box<TlsMetadata> },
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Http(e) => write!(f, "HTTP request failed: {}", e),
+            TraceError::Config(msg) => write!(f, "invalid client configuration: {}", msg),
+            TraceError::Io(e) => write!(f, "I/O error: {}", e),
+            TraceError::Timeout => write!(f, "timed out waiting for the server"),
+            TraceError::InvalidServerName(name) => write!(f, "invalid server name: {}", name),
+            TraceError::Tls(e) => write!(f, "TLS handshake failed: {}", e),
+            TraceError::StartTlsNotSupported { protocol } => {
+                write!(f, "{} server does not advertise STARTTLS", protocol)
+            }
+            TraceError::StartTlsRejected { protocol, reply } => {
+                write!(f, "unexpected {} reply: {}", protocol, reply)
+            }
+            TraceError::InvalidUrl(msg) => write!(f, "invalid URL: {}", msg),
+            TraceError::WebSocketRedirected { status, location } => match location {
+                Some(location) => write!(
+                    f,
+                    "WebSocket upgrade redirected with HTTP {} to {}",
+                    status, location
+                ),
+                None => write!(f, "WebSocket upgrade redirected with HTTP {}", status),
+            },
+            TraceError::WebSocketRejected { status, reason } => {
+                write!(f, "WebSocket upgrade rejected with HTTP {} {}", status, reason)
+            }
+            TraceError::WebSocketProtocol(msg) => write!(f, "invalid WebSocket upgrade: {}", msg),
+            TraceError::PolicyViolation { reason, .. } => write!(f, "policy violation: {}", reason),
+            TraceError::RequestFailed { error, .. } => {
+                write!(f, "HTTP request failed after the handshake: {}", error)
+            }
+        }
+    }
+}
+
+impl TraceError {
+    /// Why the handshake failed, if this error is a handshake failure.
+    pub fn handshake_failure(&self) -> Option<HandshakeFailureKind> {
+        HandshakeFailureKind::diagnose(self)
+    }
+
+    /// Which names the certificate was and was not valid for, if this error
+    /// is a certificate name mismatch.
+    pub fn name_mismatch(&self) -> Option<NameMismatch> {
+        NameMismatch::diagnose(self)
+    }
+}
+
+impl Error for TraceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TraceError::Http(e) => Some(e),
+            TraceError::Io(e) => Some(e),
+            TraceError::Tls(e) => Some(e),
+            TraceError::RequestFailed { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TraceError {
+    fn from(e: io::Error) -> Self {
+        // tokio-rustls reports handshake failures as io::Error wrapping the
+        // rustls error; unwrap those so callers can match on them.
+        if let Some(tls) = e
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+        {
+            return TraceError::Tls(tls.clone());
+        }
+        TraceError::Io(e)
+    }
+}
+
+/// Reading a response body failed after the handshake succeeded; the
+/// metadata captured during that handshake is kept.
+#[derive(Debug)]
+pub struct BodyError {
+    pub error: reqwest::Error,
+    pub metadata: TlsMetadata,
+}
+
+impl fmt::Display for BodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl Error for BodyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Streaming a response body into a writer failed after the handshake
+/// succeeded: reading the body, writing it, or a body shorter or longer
+/// than its `Content-Length`. Reading errors are wrapped in the
+/// `io::Error`; the metadata is kept.
+#[derive(Debug)]
+pub struct StreamError {
+    pub error: io::Error,
+    /// How many bytes of the body had been written when it failed.
+    pub written: u64,
+    pub metadata: TlsMetadata,
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} after {} bytes", error_chain(&self.error), self.written)
+    }
+}
+
+impl Error for StreamError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Render `error` followed by its chain of sources, separated by `: `.
+///
+/// `reqwest::Error`'s own message rarely says more than "error sending
+/// request"; the interesting part (refused connection, TLS alert) is in the
+/// sources.
+pub(crate) fn error_chain(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        let text = e.to_string();
+        if !message.ends_with(&text) {
+            message.push_str(": ");
+            message.push_str(&text);
+        }
+        source = e.source();
+    }
+    message
+}
+
+/// What kind of failure a request ran into, for triaging scan results.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum ErrorCategory {
+    /// The host name could not be resolved.
+    DnsFailure,
+    /// The TCP connection was not established in time.
+    ConnectTimeout,
+    /// The server actively refused the TCP connection.
+    ConnectRefused,
+    /// The handshake failed for a reason other than the certificate, e.g.
+    /// `AlertReceived(HandshakeFailure)` or
+    /// `PeerIncompatible(NoKxGroupsInCommon)`.
+    TlsHandshakeFailure { detail: String },
+    /// The server's certificate was rejected.
+    CertificateInvalid { reason: CertificateProblem },
+    /// The server answered with a 4xx or 5xx status.
+    HttpError { status: u16 },
+    /// Reading or decoding the response body failed.
+    BodyError,
+    /// The request as a whole took longer than the configured timeout.
+    Timeout,
+    /// The target is not a valid URL.
+    InvalidUrl,
+    /// Anything else.
+    Other,
+}
+
+/// Why a certificate was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum CertificateProblem {
+    Expired,
+    NotYetValid,
+    /// The chain does not lead to a trusted root.
+    Untrusted,
+    /// The certificate is not valid for the requested host name.
+    NameMismatch,
+    Revoked,
+    /// The key matches none of the host's
+    /// [pins](crate::TlsAwareClientBuilder::pin_spki).
+    PinMismatch,
+    Other,
+}
+
+impl ErrorCategory {
+    /// Classify a failed request by digging through its source chain for the
+    /// underlying rustls or I/O error.
+    pub fn of(error: &reqwest::Error) -> Self {
+        if error.is_builder() {
+            return ErrorCategory::InvalidUrl;
+        }
+        if error.is_body() || error.is_decode() {
+            return ErrorCategory::BodyError;
+        }
+        if let Some(status) = error.status() {
+            return ErrorCategory::HttpError {
+                status: status.as_u16(),
+            };
+        }
+
+        if let Some(category) = Self::of_source_chain(error, error.is_connect()) {
+            category
+        } else if error.is_timeout() {
+            if error.is_connect() {
+                ErrorCategory::ConnectTimeout
+            } else {
+                ErrorCategory::Timeout
+            }
+        } else {
+            ErrorCategory::Other
+        }
+    }
+
+    /// Classify a failed connection the tracer drove itself; `connecting`
+    /// tells whether it failed before the TCP connection was established.
+    pub(crate) fn of_trace(error: &TraceError, connecting: bool) -> Self {
+        match error {
+            TraceError::Timeout if connecting => ErrorCategory::ConnectTimeout,
+            TraceError::Timeout => ErrorCategory::Timeout,
+            TraceError::InvalidServerName(_) | TraceError::InvalidUrl(_) => {
+                ErrorCategory::InvalidUrl
+            }
+            TraceError::WebSocketRedirected { status, .. }
+            | TraceError::WebSocketRejected { status, .. } => {
+                ErrorCategory::HttpError { status: *status }
+            }
+            e => Self::of_source_chain(e, connecting).unwrap_or(ErrorCategory::Other),
+        }
+    }
+
+    /// Classify by the rustls or I/O error somewhere in `error`'s source
+    /// chain; `connecting` tells whether the failure happened while
+    /// establishing the TCP connection.
+    pub(crate) fn of_source_chain(error: &(dyn Error + 'static), connecting: bool) -> Option<Self> {
+        for e in sources(error) {
+            if let Some(tls) = e.downcast_ref::<rustls::Error>() {
+                return Some(Self::of_tls(tls));
+            }
+            if let Some(io) = e.downcast_ref::<io::Error>() {
+                match io.kind() {
+                    io::ErrorKind::ConnectionRefused => return Some(ErrorCategory::ConnectRefused),
+                    io::ErrorKind::TimedOut if connecting => {
+                        return Some(ErrorCategory::ConnectTimeout);
+                    }
+                    _ => {}
+                }
+            } else if e.to_string().starts_with("dns error") {
+                // hyper-util's connector reports resolver failures only by
+                // message.
+                return Some(ErrorCategory::DnsFailure);
+            }
+        }
+        None
+    }
+
+    fn of_tls(error: &rustls::Error) -> Self {
+        match error {
+            rustls::Error::InvalidCertificate(cert) => ErrorCategory::CertificateInvalid {
+                reason: CertificateProblem::of(cert),
+            },
+            other => ErrorCategory::TlsHandshakeFailure {
+                detail: format!("{:?}", other),
+            },
+        }
+    }
+}
+
+/// Why a server's certificate failed verification, as recorded under
+/// [`CertPolicy::ReportOnly`](crate::CertPolicy::ReportOnly).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CertError {
+    pub problem: CertificateProblem,
+    /// The rustls error, e.g. `certificate expired: verification time …`.
+    pub detail: String,
+    /// The names involved, for a [`NameMismatch`](CertificateProblem::NameMismatch).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub name_mismatch: Option<NameMismatch>,
+}
+
+impl CertError {
+    pub(crate) fn of(error: &rustls::Error) -> Self {
+        match error {
+            rustls::Error::InvalidCertificate(cert) => Self {
+                problem: CertificateProblem::of(cert),
+                detail: cert.to_string(),
+                name_mismatch: NameMismatch::of(cert),
+            },
+            other => Self {
+                problem: CertificateProblem::Other,
+                detail: other.to_string(),
+                name_mismatch: None,
+            },
+        }
+    }
+}
+
+/// A certificate that is not valid for the name it was checked against:
+/// which names it is valid for instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NameMismatch {
+    /// The name the certificate was checked against: the server name sent
+    /// by SNI, or the IP address of a URL without a host name.
+    pub expected: String,
+    /// The names of the certificate's subjectAltName extension, e.g.
+    /// `*.cdn.example` or `192.0.2.1`; empty when it has none. Only these
+    /// count: a name that appears in the subject's common name alone is
+    /// ignored, as RFC 6125 asks.
+    pub presented: Vec<String>,
+    /// A wildcard among `presented` that covers `expected`'s domain but
+    /// not `expected` itself, since a wildcard stands for exactly one
+    /// label: `*.example.com` for `example.com` or for `a.b.example.com`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub wildcard_near_miss: Option<String>,
+}
+
+impl NameMismatch {
+    /// Find a certificate name mismatch somewhere in `error`'s source chain,
+    /// like [`HandshakeFailureKind::diagnose`]. `None` if the chain holds
+    /// none.
+    pub fn diagnose(error: &(dyn Error + 'static)) -> Option<Self> {
+        sources(error).find_map(|e| match e.downcast_ref::<rustls::Error>()? {
+            rustls::Error::InvalidCertificate(cert) => Self::of(cert),
+            _ => None,
+        })
+    }
+
+    /// Read the names off rustls' error, which webpki fills in with the
+    /// subjectAltName entries of the end-entity certificate.
+    fn of(error: &rustls::CertificateError) -> Option<Self> {
+        let rustls::CertificateError::NotValidForNameContext { expected, presented } = error else {
+            return None;
+        };
+        let expected = expected.to_str().into_owned();
+        let presented: Vec<String> = presented.iter().map(|name| presented_name(name)).collect();
+        let wildcard_near_miss = presented
+            .iter()
+            .find(|name| wildcard_nearly_matches(name, &expected))
+            .cloned();
+        Some(Self {
+            expected,
+            presented,
+            wildcard_near_miss,
+        })
+    }
+}
+
+/// The bare name of webpki's `DnsName("example.com")` or
+/// `IpAddress(192.0.2.1)`; other kinds of names, such as URIs, are kept as
+/// webpki wrote them.
+fn presented_name(name: &str) -> String {
+    let dns = name.strip_prefix("DnsName(\"").and_then(|n| n.strip_suffix("\")"));
+    let ip = name.strip_prefix("IpAddress(").and_then(|n| n.strip_suffix(')'));
+    dns.or(ip).unwrap_or(name).to_string()
+}
+
+/// Whether the wildcard `presented` would cover `expected` if a wildcard
+/// stood for any number of labels, or for none.
+fn wildcard_nearly_matches(presented: &str, expected: &str) -> bool {
+    let Some(domain) = presented.strip_prefix("*.") else {
+        return false;
+    };
+    let (domain, expected) = (domain.to_ascii_lowercase(), expected.to_ascii_lowercase());
+    let labels = match expected.strip_suffix(&domain) {
+        Some("") => return true,
+        Some(labels) => labels,
+        None => return false,
+    };
+    labels
+        .strip_suffix('.')
+        .is_some_and(|labels| labels.contains('.'))
+}
+
+impl CertificateProblem {
+    fn of(error: &rustls::CertificateError) -> Self {
+        use rustls::CertificateError as C;
+
+        match error {
+            C::Expired | C::ExpiredContext { .. } => CertificateProblem::Expired,
+            C::NotValidYet | C::NotValidYetContext { .. } => CertificateProblem::NotYetValid,
+            C::UnknownIssuer | C::BadSignature => CertificateProblem::Untrusted,
+            C::NotValidForName | C::NotValidForNameContext { .. } => {
+                CertificateProblem::NameMismatch
+            }
+            C::Revoked => CertificateProblem::Revoked,
+            C::Other(other) if other.0.downcast_ref::<PinMismatch>().is_some() => {
+                CertificateProblem::PinMismatch
+            }
+            _ => CertificateProblem::Other,
+        }
+    }
+}
+
+/// `error` followed by its sources. `io::Error::source` skips the wrapped
+/// error itself, which is where tokio-rustls puts the rustls error, so the
+/// walk steps into it instead.
+fn sources<'a>(
+    error: &'a (dyn Error + 'static),
+) -> impl Iterator<Item = &'a (dyn Error + 'static)> {
+    std::iter::successors(Some(error), |&e| match e.downcast_ref::<io::Error>() {
+        Some(io) => io.get_ref().map(|inner| inner as &(dyn Error + 'static)),
+        None => e.source(),
+    })
+}
+
+/// Why a TLS handshake failed, in terms of what to change rather than which
+/// rustls error came back.
+///
+/// The `Display` form explains the failure; [`hint`](Self::hint) suggests
+/// what to try next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum HandshakeFailureKind {
+    /// The client offered none of the key-exchange groups the server
+    /// accepts. A client sees this when the server's HelloRetryRequest asks
+    /// for a group it never offered; most servers answer with a
+    /// `handshake_failure` alert instead, see
+    /// [`HandshakeRejected`](Self::HandshakeRejected).
+    NoCommonGroup,
+    /// Client and server have no cipher suite in common.
+    NoCommonCipherSuite,
+    /// The server answered the ClientHello with a `handshake_failure` or
+    /// `insufficient_security` alert, which servers send for any parameter
+    /// they cannot agree on: groups, cipher suites or signature schemes.
+    HandshakeRejected,
+    /// Client and server have no TLS version in common.
+    VersionMismatch,
+    /// The server's certificate was rejected.
+    Certificate { problem: CertificateProblem },
+    /// The server requires a client certificate.
+    ClientCertificateRequired,
+    /// The server does not serve the requested host name.
+    ServerNameRejected,
+    /// The server speaks none of the application protocols offered by
+    /// ALPN, or selected one that was not offered.
+    AlpnMismatch,
+    /// The peer did not answer with TLS at all, e.g. a plaintext HTTP port.
+    NotTls,
+    /// The server closed the connection during the handshake without an
+    /// alert.
+    ConnectionClosed,
+    /// The peer sent something the TLS protocol does not allow.
+    ProtocolViolation { detail: String },
+    /// The server sent an alert not covered above, e.g. `internal_error`.
+    Alert { alert: String },
+    /// Any other rustls error.
+    Other { detail: String },
+}
+
+impl HandshakeFailureKind {
+    /// Diagnose the handshake failure somewhere in `error`'s source chain,
+    /// such as the rustls error several levels below a `reqwest::Error`.
+    /// `None` if the chain holds no handshake failure.
+    pub fn diagnose(error: &(dyn Error + 'static)) -> Option<Self> {
+        sources(error).find_map(|e| {
+            if let Some(tls) = e.downcast_ref::<rustls::Error>() {
+                return Some(Self::of_tls(tls));
+            }
+            // tokio-rustls' message when the peer closes the connection
+            // before the handshake finished.
+            e.downcast_ref::<io::Error>()
+                .filter(|io| {
+                    io.kind() == io::ErrorKind::UnexpectedEof
+                        && io.to_string() == "tls handshake eof"
+                })
+                .map(|_| HandshakeFailureKind::ConnectionClosed)
+        })
+    }
+
+    /// Diagnose a rustls error.
+    pub fn of_tls(error: &rustls::Error) -> Self {
+        use rustls::{AlertDescription as A, Error as E, PeerIncompatible as I, PeerMisbehaved as M};
+
+        match error {
+            E::InvalidCertificate(cert) => HandshakeFailureKind::Certificate {
+                problem: CertificateProblem::of(cert),
+            },
+            E::AlertReceived(A::HandshakeFailure | A::InsufficientSecurity) => {
+                HandshakeFailureKind::HandshakeRejected
+            }
+            E::AlertReceived(A::ProtocolVersion) => HandshakeFailureKind::VersionMismatch,
+            E::AlertReceived(A::CertificateRequired) => {
+                HandshakeFailureKind::ClientCertificateRequired
+            }
+            E::AlertReceived(A::UnrecognisedName) => HandshakeFailureKind::ServerNameRejected,
+            E::AlertReceived(A::NoApplicationProtocol)
+            | E::PeerMisbehaved(M::SelectedUnofferedApplicationProtocol) => {
+                HandshakeFailureKind::AlpnMismatch
+            }
+            E::AlertReceived(alert) => HandshakeFailureKind::Alert {
+                alert: format!("{:?}", alert),
+            },
+            E::PeerIncompatible(I::NoKxGroupsInCommon)
+            | E::PeerMisbehaved(M::IllegalHelloRetryRequestWithUnofferedNamedGroup) => {
+                HandshakeFailureKind::NoCommonGroup
+            }
+            E::PeerIncompatible(I::NoCipherSuitesInCommon) => {
+                HandshakeFailureKind::NoCommonCipherSuite
+            }
+            E::PeerIncompatible(
+                I::ServerDoesNotSupportTls12Or13
+                | I::ServerTlsVersionIsDisabledByOurConfig
+                | I::SupportedVersionsExtensionRequired
+                | I::Tls12NotOffered
+                | I::Tls12NotOfferedOrEnabled
+                | I::Tls13RequiredForQuic,
+            ) => HandshakeFailureKind::VersionMismatch,
+            E::InvalidMessage(rustls::InvalidMessage::InvalidContentType) => {
+                HandshakeFailureKind::NotTls
+            }
+            E::InvalidMessage(_)
+            | E::PeerMisbehaved(_)
+            | E::PeerIncompatible(_)
+            | E::InappropriateMessage { .. }
+            | E::InappropriateHandshakeMessage { .. }
+            | E::PeerSentOversizedRecord => HandshakeFailureKind::ProtocolViolation {
+                detail: format!("{:?}", error),
+            },
+            other => HandshakeFailureKind::Other {
+                detail: format!("{:?}", other),
+            },
+        }
+    }
+
+    /// What to try next, e.g. another key-exchange preference; `None` when
+    /// the fix lies with the server alone.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            HandshakeFailureKind::NoCommonGroup | HandshakeFailureKind::HandshakeRejected => Some(
+                "try the other key-exchange preference (--kx classical-only or --kx \
+                 hybrid-preferred) to see whether the server rejects the groups offered",
+            ),
+            HandshakeFailureKind::NoCommonCipherSuite => {
+                Some("the server may only offer cipher suites rustls does not implement")
+            }
+            HandshakeFailureKind::VersionMismatch => Some(
+                "rustls speaks TLS 1.2 and 1.3 only; a server limited to older versions \
+                 cannot negotiate post-quantum key exchange anyway",
+            ),
+            HandshakeFailureKind::Certificate {
+                problem: CertificateProblem::Untrusted,
+            } => Some("for a private CA, pass its certificate with --cacert"),
+            HandshakeFailureKind::Certificate {
+                problem: CertificateProblem::NameMismatch,
+            } => Some("check the host name; the certificate was issued for other names"),
+            HandshakeFailureKind::Certificate {
+                problem: CertificateProblem::PinMismatch,
+            } => Some(
+                "the server presented an unpinned key: a TLS-intercepting middlebox may be in \
+                 the path, or the key was rotated and the --pin needs updating",
+            ),
+            HandshakeFailureKind::ServerNameRejected => {
+                Some("check the host name; the server has no site configured for it")
+            }
+            HandshakeFailureKind::AlpnMismatch => Some(
+                "offer a protocol the server speaks with --alpn, such as http/1.1, or leave --alpn out",
+            ),
+            HandshakeFailureKind::NotTls => {
+                Some("check the port, or use an http:// URL for plaintext servers")
+            }
+            HandshakeFailureKind::ConnectionClosed => Some(
+                "some servers and middleboxes drop ClientHellos too large for one packet, \
+                 as a post-quantum key share makes it; try --kx classical-only",
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// One sentence explaining the failure.
+impl fmt::Display for HandshakeFailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandshakeFailureKind::NoCommonGroup => {
+                f.write_str("the server accepts none of the key-exchange groups offered")
+            }
+            HandshakeFailureKind::NoCommonCipherSuite => {
+                f.write_str("the server accepts none of the cipher suites offered")
+            }
+            HandshakeFailureKind::HandshakeRejected => f.write_str(
+                "the server rejected the handshake, usually because it accepts none of the \
+                 key-exchange groups or cipher suites offered",
+            ),
+            HandshakeFailureKind::VersionMismatch => {
+                f.write_str("client and server have no TLS version in common")
+            }
+            HandshakeFailureKind::Certificate { problem } => {
+                write!(f, "the server's certificate is invalid ({})", problem)
+            }
+            HandshakeFailureKind::ClientCertificateRequired => {
+                f.write_str("the server requires a client certificate")
+            }
+            HandshakeFailureKind::ServerNameRejected => {
+                f.write_str("the server does not recognise the requested host name")
+            }
+            HandshakeFailureKind::AlpnMismatch => {
+                f.write_str("the server speaks none of the application protocols offered")
+            }
+            HandshakeFailureKind::NotTls => f.write_str("the server did not answer with TLS"),
+            HandshakeFailureKind::ConnectionClosed => {
+                f.write_str("the server closed the connection during the handshake")
+            }
+            HandshakeFailureKind::ProtocolViolation { detail } => {
+                write!(f, "the server violated the TLS protocol ({})", detail)
+            }
+            HandshakeFailureKind::Alert { alert } => {
+                write!(f, "the server aborted the handshake with alert {}", alert)
+            }
+            HandshakeFailureKind::Other { detail } => {
+                write!(f, "the handshake failed ({})", detail)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorCategory::DnsFailure => f.write_str("DNS failure"),
+            ErrorCategory::ConnectTimeout => f.write_str("connect timeout"),
+            ErrorCategory::ConnectRefused => f.write_str("connection refused"),
+            ErrorCategory::TlsHandshakeFailure { detail } => {
+                write!(f, "TLS handshake failure ({})", detail)
+            }
+            ErrorCategory::CertificateInvalid { reason } => {
+                write!(f, "invalid certificate ({})", reason)
+            }
+            ErrorCategory::HttpError { status } => write!(f, "HTTP {}", status),
+            ErrorCategory::BodyError => f.write_str("body error"),
+            ErrorCategory::Timeout => f.write_str("timeout"),
+            ErrorCategory::InvalidUrl => f.write_str("invalid URL"),
+            ErrorCategory::Other => f.write_str("other"),
+        }
+    }
+}
+
+/// `api.example.com is not among the certificate's names *.cdn.example,
+/// cdn.example (subjectAltName only; the common name is not considered)`.
+impl fmt::Display for NameMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.presented.as_slice() {
+            [] => write!(f, "{}: the certificate has no subjectAltName names", self.expected)?,
+            names => write!(
+                f,
+                "{} is not among the certificate's names {}",
+                self.expected,
+                names.join(", ")
+            )?,
+        }
+        f.write_str(" (subjectAltName only; the common name is not considered)")?;
+        if let Some(wildcard) = &self.wildcard_near_miss {
+            write!(f, "; {} covers exactly one label in place of the *", wildcard)?;
+        }
+        Ok(())
+    }
+}
+
+/// `expired (certificate expired: …)`.
+impl fmt::Display for CertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.problem, self.detail)
+    }
+}
+
+impl Error for CertError {}
+
+impl fmt::Display for CertificateProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CertificateProblem::Expired => "expired",
+            CertificateProblem::NotYetValid => "not yet valid",
+            CertificateProblem::Untrusted => "untrusted",
+            CertificateProblem::NameMismatch => "name mismatch",
+            CertificateProblem::Revoked => "revoked",
+            CertificateProblem::PinMismatch => "pin mismatch",
+            CertificateProblem::Other => "other",
+        })
+    }
+}
@@ -0,0 +1,59 @@
+//! Retrying with classical key exchange when a handshake offering hybrid
+//! groups fails, for clients built with
+//! [`fallback_to_classical`](crate::TlsAwareClientBuilder::fallback_to_classical).
+//!
+//! Some servers and middleboxes abort the handshake on an unknown hybrid
+//! group or a ClientHello too large for one packet. Whether a classical-only
+//! ClientHello gets through tells them apart from servers that are down or
+//! misconfigured altogether.
+
+use std::fmt;
+
+use crate::HandshakeFailureKind;
+
+/// How a request fared with and without post-quantum key exchange.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "outcome", rename_all = "kebab-case"))]
+pub enum FallbackOutcome {
+    /// The handshake offering hybrid groups succeeded, whichever group the
+    /// server then chose; no retry was needed.
+    PqcOk,
+    /// The handshake offering hybrid groups failed, a classical-only one
+    /// on a new connection succeeded.
+    ClassicalOnlyAfterFailure {
+        /// Why the first handshake failed.
+        original_error: HandshakeFailureKind,
+    },
+    /// The classical-only retry failed too, so key exchange is not what
+    /// the server objects to.
+    BothFailed,
+}
+
+/// `classical-only after failure (the server rejected the handshake, …)`.
+impl fmt::Display for FallbackOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FallbackOutcome::PqcOk => f.write_str("post-quantum handshake ok"),
+            FallbackOutcome::ClassicalOnlyAfterFailure { original_error } => {
+                write!(f, "classical-only after failure ({})", original_error)
+            }
+            FallbackOutcome::BothFailed => f.write_str("classical-only retry failed too"),
+        }
+    }
+}
+
+/// Whether a failure may be down to the groups offered or the size of the
+/// ClientHello, and so worth a classical-only retry. Certificate, name and
+/// protocol problems are not.
+pub(crate) fn worth_retrying(kind: &HandshakeFailureKind) -> bool {
+    matches!(
+        kind,
+        HandshakeFailureKind::NoCommonGroup
+            | HandshakeFailureKind::HandshakeRejected
+            | HandshakeFailureKind::ConnectionClosed
+            | HandshakeFailureKind::ProtocolViolation { .. }
+            | HandshakeFailureKind::Alert { .. }
+            | HandshakeFailureKind::Other { .. }
+    )
+}
@@ -0,0 +1,314 @@
+//! A C interface for agents written in other languages, compiled in by the
+//! `ffi` feature and built as a shared library by the `pqctracer-ffi` crate
+//! in `ffi/`: `cargo build --release -p pqctracer-ffi`. The declarations
+//! are in `ffi/include/pqctracer.h`, `ffi/tests/smoke.c` calls each of
+//! them, and `python/pqctracer` wraps them for Python.
+//!
+//! Every call runs on a runtime of its own and leaves no state behind but
+//! the calling thread's last error, so calls from several threads at once
+//! are fine. Panics are caught at the boundary and reported like errors.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::error::error_chain;
+use crate::scan::{ScanMethod, ScanOptions};
+use crate::trace::{TraceOptions, ensure_crypto_provider, trace_url_with};
+use crate::{ErrorCategory, KxPreference, TlsAwareClient, TraceError};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<Failure>> = const { RefCell::new(None) };
+}
+
+/// Why a call failed: the message and, for failed requests, the
+/// [`ErrorCategory`] as JSON. Arguments the call rejected have none.
+struct Failure {
+    message: CString,
+    category: Option<CString>,
+}
+
+/// A failed call before it is stored as the thread's [`Failure`].
+struct FfiError {
+    message: String,
+    category: Option<ErrorCategory>,
+}
+
+impl From<String> for FfiError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            category: None,
+        }
+    }
+}
+
+impl From<&str> for FfiError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl From<TraceError> for FfiError {
+    fn from(error: TraceError) -> Self {
+        let category = match &error {
+            TraceError::Http(e) | TraceError::RequestFailed { error: e, .. } => {
+                ErrorCategory::of(e)
+            }
+            e => ErrorCategory::of_trace(e, false),
+        };
+        Self {
+            message: error_chain(&error),
+            category: Some(category),
+        }
+    }
+}
+
+/// The options of [`pqc_trace_url`], all optional, e.g.
+/// `{"timeout_ms": 5000, "kx": "classical-only"}`.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FfiOptions {
+    head: bool,
+    timeout_ms: Option<u64>,
+    kx: KxPreference,
+    insecure: bool,
+}
+
+/// The options of [`pqc_scan`]: those of [`FfiOptions`], with `head`
+/// defaulting to true as in scans, and those of [`ScanOptions`].
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FfiScanOptions {
+    head: bool,
+    timeout_ms: Option<u64>,
+    kx: KxPreference,
+    insecure: bool,
+    concurrency: usize,
+    max_per_host: Option<usize>,
+    retries: u32,
+    rate: Option<f64>,
+}
+
+impl Default for FfiScanOptions {
+    fn default() -> Self {
+        let scan = ScanOptions::default();
+        Self {
+            head: true,
+            timeout_ms: None,
+            kx: KxPreference::default(),
+            insecure: false,
+            concurrency: scan.concurrency,
+            max_per_host: scan.max_per_host,
+            retries: scan.retries,
+            rate: scan.rate_limit,
+        }
+    }
+}
+
+/// Request `url` and return the negotiated TLS parameters as a JSON object
+/// in the shape [`TlsMetadata`](crate::TlsMetadata) serializes to, to be
+/// freed with [`pqc_free_string`]. `options_json` may be null for the
+/// defaults. On failure, returns null and leaves the reason to
+/// [`pqc_last_error`].
+///
+/// # Safety
+///
+/// `url` must point to a NUL-terminated string, and so must `options_json`
+/// unless it is null.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pqc_trace_url(
+    url: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    call(|| {
+        // SAFETY: the caller passes NUL-terminated strings or null.
+        let url = unsafe { string_arg(url, "url") }?.ok_or("url is null")?;
+        let options = unsafe { options_arg(options_json) }?;
+        trace(url, options)
+    })
+}
+
+/// Trace every target of `targets_json`, a JSON array of URLs and
+/// addresses, as [`TlsAwareClient::scan`] does, and return the JSON array of
+/// their [`ScanResult`](crate::scan::ScanResult)s, to be freed with
+/// [`pqc_free_string`]. A target that fails has its error in its result;
+/// the call itself fails only on invalid arguments.
+///
+/// # Safety
+///
+/// `targets_json` must point to a NUL-terminated string, and so must
+/// `options_json` unless it is null.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pqc_scan(
+    targets_json: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    call(|| {
+        // SAFETY: the caller passes NUL-terminated strings or null.
+        let targets = unsafe { string_arg(targets_json, "targets_json") }?
+            .ok_or("targets_json is null")?;
+        let targets: Vec<String> =
+            serde_json::from_str(targets).map_err(|e| format!("invalid targets: {}", e))?;
+        let options = unsafe { options_arg(options_json) }?;
+        scan(targets, options)
+    })
+}
+
+/// Run `f`, catching a panic, and hand its JSON to the caller or store its
+/// failure as the thread's last error.
+fn call(f: impl FnOnce() -> Result<String, FfiError>) -> *mut c_char {
+    let result = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Err(format!("panic: {}", message).into())
+    });
+    match result.and_then(|json| CString::new(json).map_err(|e| e.to_string().into())) {
+        Ok(json) => {
+            set_last_error(None);
+            json.into_raw()
+        }
+        Err(error) => {
+            set_last_error(Some(error));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a string returned by [`pqc_trace_url`] or [`pqc_scan`]; null is
+/// ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by [`pqc_trace_url`] or
+/// [`pqc_scan`] that was not freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pqc_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: `s` came from `CString::into_raw` in `call`.
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// Why the calling thread's last [`pqc_trace_url`] or [`pqc_scan`] failed,
+/// or null if it succeeded. The string belongs to the library and stays
+/// valid until the thread's next call; it must not be freed.
+#[unsafe(no_mangle)]
+pub extern "C" fn pqc_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |failure| failure.message.as_ptr())
+    })
+}
+
+/// The [`ErrorCategory`] of the calling thread's last failed request as a
+/// JSON object, e.g. `{"kind":"connect-refused"}`, or null if the last
+/// call succeeded or rejected its arguments. Owned by the library like
+/// [`pqc_last_error`]'s string.
+#[unsafe(no_mangle)]
+pub extern "C" fn pqc_last_error_category() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .and_then(|failure| failure.category.as_ref())
+            .map_or(ptr::null(), |category| category.as_ptr())
+    })
+}
+
+fn trace(url: &str, options: FfiOptions) -> Result<String, FfiError> {
+    let mut trace_options = TraceOptions::default()
+        .head(options.head)
+        .kx_preference(options.kx)
+        .danger_accept_invalid_certs(options.insecure);
+    if let Some(timeout) = options.timeout_ms {
+        trace_options = trace_options.timeout(Duration::from_millis(timeout));
+    }
+    let metadata = runtime()?.block_on(trace_url_with(url, trace_options))?;
+    Ok(serde_json::to_string(&metadata).map_err(|e| e.to_string())?)
+}
+
+fn scan(targets: Vec<String>, options: FfiScanOptions) -> Result<String, FfiError> {
+    ensure_crypto_provider();
+    let mut builder = TlsAwareClient::builder()
+        .kx_preference(options.kx)
+        .danger_accept_invalid_certs(options.insecure);
+    if let Some(timeout) = options.timeout_ms {
+        builder = builder.timeout(Duration::from_millis(timeout));
+    }
+    let client = builder.build().map_err(|e| error_chain(&e))?;
+    let scan_options = ScanOptions {
+        concurrency: options.concurrency.max(1),
+        max_per_host: options.max_per_host,
+        retries: options.retries,
+        rate_limit: options.rate,
+        method: match options.head {
+            true => ScanMethod::Head,
+            false => ScanMethod::Get,
+        },
+        ..ScanOptions::default()
+    };
+    let results = runtime()?.block_on(client.scan(targets, &scan_options));
+    Ok(serde_json::to_string(&results).map_err(|e| e.to_string())?)
+}
+
+/// The runtime of a single call.
+fn runtime() -> Result<tokio::runtime::Runtime, String> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| format!("failed to start a runtime: {}", e))
+}
+
+/// Read the options argument, the defaults when it is null.
+///
+/// # Safety
+///
+/// `s` must be null or point to a NUL-terminated string.
+unsafe fn options_arg<T: Default + for<'de> Deserialize<'de>>(
+    s: *const c_char,
+) -> Result<T, String> {
+    // SAFETY: guaranteed by the caller.
+    match unsafe { string_arg(s, "options_json") }? {
+        Some(json) => serde_json::from_str(json).map_err(|e| format!("invalid options: {}", e)),
+        None => Ok(T::default()),
+    }
+}
+
+/// Read the string argument `name`, `None` when it is null.
+///
+/// # Safety
+///
+/// `s` must be null or point to a NUL-terminated string.
+unsafe fn string_arg<'a>(s: *const c_char, name: &str) -> Result<Option<&'a str>, String> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    // SAFETY: guaranteed by the caller.
+    let s = unsafe { CStr::from_ptr(s) };
+    s.to_str()
+        .map(Some)
+        .map_err(|_| format!("{} is not valid UTF-8", name))
+}
+
+fn set_last_error(error: Option<FfiError>) {
+    // A message with a NUL in it is cut short there.
+    let failure = error.map(|error| {
+        let end = error.message.find('\0').unwrap_or(error.message.len());
+        Failure {
+            message: CString::new(&error.message[..end]).unwrap_or_default(),
+            category: error
+                .category
+                .and_then(|category| serde_json::to_string(&category).ok())
+                .and_then(|json| CString::new(json).ok()),
+        }
+    });
+    LAST_ERROR.with(|last| *last.borrow_mut() = failure);
+}
@@ -0,0 +1,385 @@
+//! JA3N and JA4 fingerprints of the ClientHello a client configuration
+//! sends, so that server operators can find the tracer's handshakes in their
+//! logs.
+//!
+//! The ClientHello is produced by rustls itself, for a throwaway connection
+//! that is never sent anywhere, and read back with the ClientHello parser;
+//! the fingerprints then follow whatever rustls puts on the wire. rustls
+//! shuffles the order of its extensions for every connection, so plain JA3,
+//! which hashes them in the order sent, would differ every time; JA3N sorts
+//! them first, as JA4 does.
+
+use std::sync::Arc;
+
+use rustls::client::{ClientSessionMemoryCache, Resumption};
+use rustls::crypto::CryptoProvider;
+use rustls::crypto::hash::{Hash, HashAlgorithm};
+use rustls::{ClientConfig, ClientConnection, SupportedCipherSuite};
+use rustls_pki_types::ServerName;
+
+use crate::TraceError;
+use crate::hello::{self, ClientHelloSummary};
+
+/// Sent as SNI in the generated ClientHello; JA4 only records that a domain
+/// name was sent, not which.
+const PLACEHOLDER_SERVER_NAME: &str = "fingerprint.invalid";
+
+/// The fingerprints of one ClientHello.
+pub(crate) struct Fingerprints {
+    pub(crate) ja3n: String,
+    pub(crate) ja4: String,
+}
+
+impl Fingerprints {
+    /// Fingerprint the ClientHello that `config` sends to a host name.
+    ///
+    /// Resumption goes to an empty store of its own, so the ClientHello
+    /// carries the same extensions as a first connection without touching
+    /// any caller-provided session store.
+    pub(crate) fn of_config(config: &ClientConfig) -> Result<Self, TraceError> {
+        let sha256 = sha256(config.crypto_provider())
+            .ok_or_else(|| TraceError::Config("no SHA-256 in the crypto provider".to_string()))?;
+        let mut config = config.clone();
+        config.resumption = Resumption::store(Arc::new(ClientSessionMemoryCache::new(1)));
+        let server_name = ServerName::try_from(PLACEHOLDER_SERVER_NAME)
+            .expect("placeholder is a valid server name");
+        let records = client_hello(config, server_name)?;
+        let hello = hello::parse(&records)
+            .ok_or_else(|| TraceError::Config("unreadable ClientHello".to_string()))?;
+        Ok(Self {
+            ja3n: ja3n(&hello),
+            ja4: ja4(&hello, sha256),
+        })
+    }
+}
+
+/// The records holding the ClientHello that `config` sends to
+/// `server_name`, as they would go on the wire.
+pub(crate) fn client_hello(
+    config: ClientConfig,
+    server_name: ServerName<'static>,
+) -> Result<Vec<u8>, TraceError> {
+    let mut connection =
+        ClientConnection::new(Arc::new(config), server_name).map_err(TraceError::Tls)?;
+    let mut records = Vec::new();
+    while connection.wants_write() {
+        connection.write_tls(&mut records)?;
+    }
+    Ok(records)
+}
+
+/// The provider's SHA-256, borrowed from one of its cipher suites.
+pub(crate) fn sha256(provider: &CryptoProvider) -> Option<&'static dyn Hash> {
+    provider
+        .cipher_suites
+        .iter()
+        .map(|suite| match suite {
+            SupportedCipherSuite::Tls13(suite) => suite.common.hash_provider,
+            SupportedCipherSuite::Tls12(suite) => suite.common.hash_provider,
+        })
+        .find(|hash| hash.algorithm() == HashAlgorithm::SHA256)
+}
+
+/// GREASE values (RFC 8701) are left out of both fingerprints.
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+fn without_grease(values: &[u16]) -> impl Iterator<Item = u16> + '_ {
+    values.iter().copied().filter(|value| !is_grease(*value))
+}
+
+/// The MD5 of `version,ciphers,extensions,groups,point_formats`, each list
+/// in decimal and dash-separated, the extensions sorted and everything else
+/// in the order sent.
+fn ja3n(hello: &ClientHelloSummary) -> String {
+    let join = |values: &mut dyn Iterator<Item = u16>| {
+        values
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join("-")
+    };
+    let groups: Vec<u16> = hello
+        .supported_groups
+        .iter()
+        .map(|group| u16::from(*group))
+        .collect();
+    let mut extensions: Vec<u16> = without_grease(&hello.extensions).collect();
+    extensions.sort_unstable();
+    let full = format!(
+        "{},{},{},{},{}",
+        hello.legacy_version,
+        join(&mut without_grease(&hello.cipher_suites)),
+        join(&mut extensions.into_iter()),
+        join(&mut without_grease(&groups)),
+        join(
+            &mut hello
+                .ec_point_formats
+                .iter()
+                .map(|format| u16::from(*format))
+        ),
+    );
+    hex(&md5(full.as_bytes()))
+}
+
+/// `t13d1516h2_8daaf6152771_e5627efa2ab1`: protocol, version, SNI, counts
+/// and ALPN, then truncated SHA-256 hashes of the sorted cipher suites and of
+/// the sorted extensions followed by the signature algorithms.
+fn ja4(hello: &ClientHelloSummary, sha256: &dyn Hash) -> String {
+    let version = without_grease(&hello.supported_versions)
+        .max()
+        .unwrap_or(hello.legacy_version);
+    let version = match version {
+        0x0304 => "13",
+        0x0303 => "12",
+        0x0302 => "11",
+        0x0301 => "10",
+        0x0300 => "s3",
+        _ => "00",
+    };
+    let sni = if hello.extensions.contains(&hello::EXT_SERVER_NAME) {
+        'd'
+    } else {
+        'i'
+    };
+    let mut suites: Vec<u16> = without_grease(&hello.cipher_suites).collect();
+    let mut extensions: Vec<u16> = without_grease(&hello.extensions).collect();
+    let count = |n: usize| n.min(99);
+    let a = format!(
+        "t{}{}{:02}{:02}{}",
+        version,
+        sni,
+        count(suites.len()),
+        count(extensions.len()),
+        alpn_chars(hello.alpn.first().map(Vec::as_slice).unwrap_or_default())
+    );
+
+    let hash = |input: String| match input.is_empty() {
+        true => "000000000000".to_string(),
+        false => hex(sha256.hash(input.as_bytes()).as_ref())[..12].to_string(),
+    };
+    let list = |values: &[u16]| {
+        values
+            .iter()
+            .map(|value| format!("{:04x}", value))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    suites.sort_unstable();
+    extensions.retain(|ext| *ext != hello::EXT_SERVER_NAME && *ext != hello::EXT_ALPN);
+    extensions.sort_unstable();
+    let mut c = list(&extensions);
+    if !hello.signature_algorithms.is_empty() {
+        c.push('_');
+        c.push_str(&list(&hello.signature_algorithms));
+    }
+    format!("{}_{}_{}", a, hash(list(&suites)), hash(c))
+}
+
+/// The first and last character of the first ALPN protocol, `h2` for `h2`
+/// and `h1` for `http/1.1`; `00` without ALPN. Protocols that do not start
+/// and end alphanumerically are represented by their hex form's first and
+/// last digit.
+fn alpn_chars(protocol: &[u8]) -> String {
+    match (protocol.first(), protocol.last()) {
+        (Some(first), Some(last))
+            if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() =>
+        {
+            format!("{}{}", *first as char, *last as char)
+        }
+        (Some(first), Some(last)) => {
+            let (first, last) = (format!("{:02x}", first), format!("{:02x}", last));
+            format!("{}{}", &first[..1], &last[1..])
+        }
+        _ => "00".to_string(),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// MD5 (RFC 1321), which JA3N is defined with and no crypto provider offers.
+fn md5(input: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+    let constants: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4_294_967_296.0) as u32)
+        .collect();
+
+    let mut message = input.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((input.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for chunk in message.chunks_exact(64) {
+        let words: Vec<u32> = chunk
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g])
+                .rotate_left(SHIFTS[i]);
+            (a, b, c, d) = (d, b.wrapping_add(rotated), b, c);
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 16];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use rustls::crypto::aws_lc_rs;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::{ClientCapabilities, KxPreference, TlsAwareClient, TlsAwareClientBuilder};
+
+    fn capabilities(builder: TlsAwareClientBuilder) -> ClientCapabilities {
+        let _ = aws_lc_rs::default_provider().install_default();
+        builder.build().expect("build client").client_capabilities()
+    }
+
+    #[test]
+    fn md5_test_vectors() {
+        // RFC 1321, appendix A.5.
+        for (input, digest) in [
+            ("", "d41d8cd98f00b204e9800998ecf8427e"),
+            ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+            ("message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            (
+                "12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
+        ] {
+            assert_eq!(hex(&md5(input.as_bytes())), digest, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn grease_and_alpn_characters() {
+        assert!(is_grease(0x0a0a) && is_grease(0xfafa));
+        assert!(!is_grease(0x0a1a) && !is_grease(0x1301));
+        for (protocol, chars) in [("h2", "h2"), ("http/1.1", "h1"), ("", "00"), ("/x", "28")] {
+            assert_eq!(alpn_chars(protocol.as_bytes()), chars, "{:?}", protocol);
+        }
+    }
+
+    #[test]
+    fn fingerprints_of_fixed_configurations() {
+        let single_suite = CryptoProvider {
+            cipher_suites: vec![aws_lc_rs::cipher_suite::TLS13_AES_128_GCM_SHA256],
+            ..aws_lc_rs::default_provider()
+        };
+        let cases = [
+            (
+                TlsAwareClient::builder(),
+                "54294ea073f85fb8975a09107e5dd489",
+                "t13d101000_61a7ad8aa9b6_f9531d972513",
+            ),
+            // Groups only show in JA3N.
+            (
+                TlsAwareClient::builder().kx_preference(KxPreference::ClassicalOnly),
+                "f81ecb4047f8a1cd5bad262f3c97a040",
+                "t13d101000_61a7ad8aa9b6_f9531d972513",
+            ),
+            (
+                TlsAwareClient::builder().alpn_protocols(&["http/1.1"]),
+                "082240ccb778985f253b420029540a34",
+                "t13d1011h1_61a7ad8aa9b6_f9531d972513",
+            ),
+            // Without a TLS 1.2 suite, the TLS 1.2 extensions go as well.
+            (
+                TlsAwareClient::builder().crypto_provider(Arc::new(single_suite)),
+                "e58a01bef85439be24d67f98e5f39a7e",
+                "t13d010900_0f2cb44170f4_d268365384ff",
+            ),
+            // ring signs with fewer algorithms, and has no hybrid group.
+            (
+                TlsAwareClient::builder()
+                    .crypto_provider(Arc::new(rustls::crypto::ring::default_provider())),
+                "f81ecb4047f8a1cd5bad262f3c97a040",
+                "t13d101000_61a7ad8aa9b6_3fcd1a44f3e3",
+            ),
+        ];
+        for (builder, ja3n, ja4) in cases {
+            let capabilities = capabilities(builder);
+            assert_eq!(
+                (capabilities.ja3n.as_str(), capabilities.ja4.as_str()),
+                (ja3n, ja4),
+                "{:?}",
+                capabilities
+            );
+        }
+    }
+
+    /// The ClientHello of a request by `client`, read by a listener that
+    /// never answers.
+    async fn sent_hello(client: &TlsAwareClient) -> ClientHelloSummary {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let url = format!(
+            "https://localhost:{}/",
+            listener.local_addr().unwrap().port()
+        );
+        let read = async {
+            let (mut stream, _) = listener.accept().await.expect("accept");
+            let mut records = Vec::new();
+            while let Some(needed) = hello::bytes_needed(&records) {
+                let start = records.len();
+                records.resize(start + needed, 0);
+                stream
+                    .read_exact(&mut records[start..])
+                    .await
+                    .expect("read ClientHello");
+            }
+            records
+        };
+        let (records, _) = tokio::join!(read, client.get(url.as_str()).send());
+        hello::parse(&records).expect("ClientHello")
+    }
+
+    #[tokio::test]
+    async fn fingerprints_are_those_of_the_hello_sent() {
+        for builder in [
+            TlsAwareClient::builder(),
+            TlsAwareClient::builder().kx_preference(KxPreference::ClassicalOnly),
+            TlsAwareClient::builder().alpn_protocols(&["http/1.1"]),
+        ] {
+            let _ = aws_lc_rs::default_provider().install_default();
+            let client = builder
+                .timeout(std::time::Duration::from_millis(300))
+                .build()
+                .expect("build client");
+            let capabilities = client.client_capabilities();
+            let hello = sent_hello(&client).await;
+            let sha256 = sha256(&aws_lc_rs::default_provider()).unwrap();
+            assert_eq!(ja3n(&hello), capabilities.ja3n);
+            assert_eq!(ja4(&hello, sha256), capabilities.ja4);
+        }
+    }
+}
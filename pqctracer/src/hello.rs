@@ -0,0 +1,211 @@
+//! A small parser for the plaintext ClientHello that opens every TLS connection.
+//!
+//! rustls exposes the offered `supported_groups` on the server side, but not
+//! which of those groups the client actually sent key shares for – and that is
+//! exactly what decides whether a HelloRetryRequest is needed. The remaining
+//! fields feed the JA3 and JA4 fingerprints of the tracer's own ClientHello.
+
+use rustls::NamedGroup;
+
+const CONTENT_TYPE_HANDSHAKE: u8 = 22;
+const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+pub(crate) const EXT_SERVER_NAME: u16 = 0;
+const EXT_SUPPORTED_GROUPS: u16 = 10;
+const EXT_EC_POINT_FORMATS: u16 = 11;
+const EXT_SIGNATURE_ALGORITHMS: u16 = 13;
+pub(crate) const EXT_ALPN: u16 = 16;
+const EXT_SUPPORTED_VERSIONS: u16 = 43;
+const EXT_KEY_SHARE: u16 = 51;
+
+/// The parts of a ClientHello the tracer cares about.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ClientHelloSummary {
+    pub(crate) legacy_version: u16,
+    pub(crate) cipher_suites: Vec<u16>,
+    /// Extension types in the order they were sent.
+    pub(crate) extensions: Vec<u16>,
+    pub(crate) supported_groups: Vec<NamedGroup>,
+    pub(crate) key_shares: Vec<NamedGroup>,
+    pub(crate) ec_point_formats: Vec<u8>,
+    pub(crate) signature_algorithms: Vec<u16>,
+    pub(crate) alpn: Vec<Vec<u8>>,
+    pub(crate) supported_versions: Vec<u16>,
+}
+
+/// Number of bytes still needed before `records` holds the complete
+/// ClientHello handshake message, or `None` once it is complete.
+///
+/// Input that is not a handshake record is reported as complete so the caller
+/// hands it to rustls, which produces the proper alert.
+#[cfg(feature = "test-util")]
+pub(crate) fn bytes_needed(records: &[u8]) -> Option<usize> {
+    let mut handshake_len = 0usize;
+    let mut pos = 0usize;
+
+    loop {
+        let Some(header) = records.get(pos..pos + 5) else {
+            return Some(pos + 5 - records.len());
+        };
+        if header[0] != CONTENT_TYPE_HANDSHAKE {
+            return None;
+        }
+        let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let end = pos + 5 + len;
+        if records.len() < end {
+            return Some(end - records.len());
+        }
+        handshake_len += len;
+        pos = end;
+
+        // The 4-byte handshake header tells us how long the message is.
+        let payload = handshake_payload(records, pos);
+        if payload.len() >= 4 {
+            let msg_len = u32::from_be_bytes([0, payload[1], payload[2], payload[3]]) as usize;
+            if handshake_len >= msg_len + 4 {
+                return None;
+            }
+        }
+    }
+}
+
+/// Parse a ClientHello from one or more raw TLS records.
+pub(crate) fn parse(records: &[u8]) -> Option<ClientHelloSummary> {
+    let payload = handshake_payload(records, records.len());
+    let mut r = Reader::new(&payload);
+
+    if r.u8()? != HANDSHAKE_CLIENT_HELLO {
+        return None;
+    }
+    let body_len = r.u24()?;
+    let mut body = Reader::new(r.take(body_len)?);
+
+    let mut summary = ClientHelloSummary {
+        legacy_version: body.u16()?,
+        ..Default::default()
+    };
+    body.take(32)?; // random
+    let session_id_len = body.u8()? as usize;
+    body.take(session_id_len)?;
+    let suites_len = body.u16()? as usize;
+    let mut suites = Reader::new(body.take(suites_len)?);
+    while !suites.is_empty() {
+        summary.cipher_suites.push(suites.u16()?);
+    }
+    let compression_len = body.u8()? as usize;
+    body.take(compression_len)?;
+
+    if body.is_empty() {
+        return Some(summary);
+    }
+
+    let extensions_len = body.u16()? as usize;
+    let mut extensions = Reader::new(body.take(extensions_len)?);
+    while !extensions.is_empty() {
+        let ext_type = extensions.u16()?;
+        let ext_len = extensions.u16()? as usize;
+        let mut ext = Reader::new(extensions.take(ext_len)?);
+        summary.extensions.push(ext_type);
+
+        match ext_type {
+            EXT_SUPPORTED_GROUPS => {
+                let list_len = ext.u16()? as usize;
+                let mut list = Reader::new(ext.take(list_len)?);
+                while !list.is_empty() {
+                    summary.supported_groups.push(NamedGroup::from(list.u16()?));
+                }
+            }
+            EXT_KEY_SHARE => {
+                let list_len = ext.u16()? as usize;
+                let mut list = Reader::new(ext.take(list_len)?);
+                while !list.is_empty() {
+                    let group = NamedGroup::from(list.u16()?);
+                    let key_len = list.u16()? as usize;
+                    list.take(key_len)?;
+                    summary.key_shares.push(group);
+                }
+            }
+            EXT_EC_POINT_FORMATS => {
+                let list_len = ext.u8()? as usize;
+                summary.ec_point_formats = ext.take(list_len)?.to_vec();
+            }
+            EXT_SIGNATURE_ALGORITHMS => {
+                let list_len = ext.u16()? as usize;
+                let mut list = Reader::new(ext.take(list_len)?);
+                while !list.is_empty() {
+                    summary.signature_algorithms.push(list.u16()?);
+                }
+            }
+            EXT_ALPN => {
+                let list_len = ext.u16()? as usize;
+                let mut list = Reader::new(ext.take(list_len)?);
+                while !list.is_empty() {
+                    let protocol_len = list.u8()? as usize;
+                    summary.alpn.push(list.take(protocol_len)?.to_vec());
+                }
+            }
+            EXT_SUPPORTED_VERSIONS => {
+                let list_len = ext.u8()? as usize;
+                let mut list = Reader::new(ext.take(list_len)?);
+                while !list.is_empty() {
+                    summary.supported_versions.push(list.u16()?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(summary)
+}
+
+/// Concatenate the payloads of the handshake records in `records[..limit]`.
+fn handshake_payload(records: &[u8], limit: usize) -> Vec<u8> {
+    let mut payload = Vec::new();
+    let mut pos = 0usize;
+    while let Some(header) = records.get(pos..pos + 5) {
+        let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let end = (pos + 5 + len).min(limit);
+        if header[0] != CONTENT_TYPE_HANDSHAKE || end <= pos + 5 {
+            break;
+        }
+        payload.extend_from_slice(&records[pos + 5..end]);
+        pos += 5 + len;
+    }
+    payload
+}
+
+/// Reads big-endian fields off the front of a byte slice.
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < n {
+            return None;
+        }
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+        Some(head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        self.take(3)
+            .map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]) as usize)
+    }
+}
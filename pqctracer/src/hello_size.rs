@@ -0,0 +1,285 @@
+//! Whether a server copes with the size of a post-quantum ClientHello.
+//!
+//! An ML-KEM key share takes the ClientHello past 1,200 bytes, more than
+//! some servers and middleboxes read from a single TCP segment. A second
+//! handshake offering classical groups only, padded with filler ALPN
+//! protocols to the same size, tells a server that objects to the groups
+//! apart from one that breaks on any large ClientHello.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use rustls::ClientConfig;
+use rustls_pki_types::ServerName;
+use tokio_rustls::TlsConnector;
+
+use crate::fingerprint;
+use crate::{ErrorCategory, KxPreference, TlsAwareClient, TlsMetadata, TraceError};
+
+/// Offered ahead of the filler when the client offers no protocols of its
+/// own, so that servers insisting on ALPN still find one they speak.
+const DEFAULT_PROTOCOL: &[u8] = b"http/1.1";
+
+/// ALPN protocol names are at most 255 bytes long.
+const MAX_PROTOCOL_LEN: usize = 255;
+
+/// What the size probe concluded about a server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum HelloSizeVerdict {
+    /// The handshake with the client's usual groups completed.
+    Fine,
+    /// Only the handshake offering hybrid groups failed; a classical one
+    /// of the same size completed, so the groups are the problem.
+    GroupProblem,
+    /// Both large handshakes failed but a classical one of the usual size
+    /// completed, so the size is the problem.
+    SizeProblem,
+    /// Every handshake failed, e.g. because the server is down or its
+    /// certificate is invalid.
+    Inconclusive,
+}
+
+impl fmt::Display for HelloSizeVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HelloSizeVerdict::Fine => "fine",
+            HelloSizeVerdict::GroupProblem => "group problem",
+            HelloSizeVerdict::SizeProblem => "size problem",
+            HelloSizeVerdict::Inconclusive => "inconclusive",
+        })
+    }
+}
+
+/// How a server answered ClientHellos of different sizes and groups.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HelloSizeReport {
+    pub verdict: HelloSizeVerdict,
+    /// Bytes on the wire of the ClientHello with the client's usual groups.
+    pub hybrid_hello_len: usize,
+    /// Bytes on the wire of the classical-only ClientHello, unpadded.
+    pub classical_hello_len: usize,
+    /// Bytes on the wire of the classical-only ClientHello once padded,
+    /// at least `hybrid_hello_len`.
+    pub padded_hello_len: usize,
+    /// The handshake with the client's usual groups.
+    pub hybrid: HelloProbe,
+    /// The handshake with classical groups only, padded.
+    pub padded: HelloProbe,
+    /// The handshake with classical groups only, unpadded; only tried when
+    /// the other two failed.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub classical: Option<HelloProbe>,
+}
+
+impl HelloSizeReport {
+    /// Report `error` for every probe, for probes that cannot start.
+    fn failed(error: ErrorCategory) -> Self {
+        Self {
+            verdict: HelloSizeVerdict::Inconclusive,
+            hybrid_hello_len: 0,
+            classical_hello_len: 0,
+            padded_hello_len: 0,
+            hybrid: HelloProbe::Failed {
+                error: error.clone(),
+            },
+            padded: HelloProbe::Failed { error },
+            classical: None,
+        }
+    }
+}
+
+/// `size problem (hybrid 1310 bytes: failed (…); classical 1312 bytes:
+/// failed (…); classical 250 bytes: completed (secp256r1))`.
+impl fmt::Display for HelloSizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (hybrid {} bytes: {}; classical {} bytes: {}",
+            self.verdict, self.hybrid_hello_len, self.hybrid, self.padded_hello_len, self.padded
+        )?;
+        if let Some(classical) = &self.classical {
+            write!(
+                f,
+                "; classical {} bytes: {}",
+                self.classical_hello_len, classical
+            )?;
+        }
+        f.write_str(")")
+    }
+}
+
+/// The outcome of one handshake of the size probe.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "outcome", rename_all = "kebab-case"))]
+pub enum HelloProbe {
+    /// The handshake completed with this group.
+    Completed {
+        group: Option<String>,
+    },
+    Failed {
+        error: ErrorCategory,
+    },
+}
+
+impl HelloProbe {
+    pub fn is_completed(&self) -> bool {
+        matches!(self, HelloProbe::Completed { .. })
+    }
+
+    fn of(result: Result<TlsMetadata, (TraceError, bool)>) -> Self {
+        match result {
+            Ok(metadata) => HelloProbe::Completed {
+                group: metadata.group,
+            },
+            Err((e, connecting)) => HelloProbe::Failed {
+                error: ErrorCategory::of_trace(&e, connecting),
+            },
+        }
+    }
+}
+
+/// `completed (X25519MLKEM768)` or `failed (connection closed)`.
+impl fmt::Display for HelloProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HelloProbe::Completed { group } => {
+                write!(f, "completed ({})", group.as_deref().unwrap_or("n/a"))
+            }
+            HelloProbe::Failed { error } => write!(f, "failed ({})", error),
+        }
+    }
+}
+
+/// Probe whether `host:port` copes with large ClientHellos, using a
+/// default [`TlsAwareClient`].
+pub async fn probe_hello_size_tolerance(host: &str, port: u16) -> HelloSizeReport {
+    TlsAwareClient::new()
+        .probe_hello_size_tolerance(host, port)
+        .await
+}
+
+impl TlsAwareClient {
+    /// Attempt one handshake with `host:port` offering the client's usual
+    /// groups and one offering classical groups only but padded to the same
+    /// size, and tell from the two whether the groups or the size of the
+    /// ClientHello are what the server objects to.
+    pub async fn probe_hello_size_tolerance(&self, host: &str, port: u16) -> HelloSizeReport {
+        let Ok(server_name) = ServerName::try_from(host.to_string()) else {
+            return HelloSizeReport::failed(ErrorCategory::InvalidUrl);
+        };
+        let addr = match self.bind.lookup(host, port).await {
+            Ok(addrs) => addrs.into_iter().next(),
+            Err(_) => None,
+        };
+        match addr {
+            Some(addr) => self.probe_hello_size_tolerance_at(addr, server_name).await,
+            None => HelloSizeReport::failed(ErrorCategory::DnsFailure),
+        }
+    }
+
+    /// Like [`probe_hello_size_tolerance`](Self::probe_hello_size_tolerance)
+    /// for an address already resolved.
+    pub(crate) async fn probe_hello_size_tolerance_at(
+        &self,
+        addr: SocketAddr,
+        server_name: ServerName<'static>,
+    ) -> HelloSizeReport {
+        let configs = self.hello_size_configs(&server_name);
+        let [hybrid, classical, padded] = match configs {
+            Ok(configs) => configs,
+            Err(e) => return HelloSizeReport::failed(ErrorCategory::of_trace(&e, false)),
+        };
+
+        let mut report = HelloSizeReport {
+            verdict: HelloSizeVerdict::Inconclusive,
+            hybrid_hello_len: hybrid.1,
+            classical_hello_len: classical.1,
+            padded_hello_len: padded.1,
+            hybrid: self.hello_probe(addr, server_name.clone(), hybrid.0).await,
+            padded: self.hello_probe(addr, server_name.clone(), padded.0).await,
+            classical: None,
+        };
+        report.verdict = if report.hybrid.is_completed() {
+            HelloSizeVerdict::Fine
+        } else if report.padded.is_completed() {
+            HelloSizeVerdict::GroupProblem
+        } else {
+            let classical = self.hello_probe(addr, server_name, classical.0).await;
+            let verdict = if classical.is_completed() {
+                HelloSizeVerdict::SizeProblem
+            } else {
+                HelloSizeVerdict::Inconclusive
+            };
+            report.classical = Some(classical);
+            verdict
+        };
+        report
+    }
+
+    /// The configurations of the three handshakes, hybrid, classical and
+    /// classical padded, each with the length of its ClientHello.
+    fn hello_size_configs(
+        &self,
+        server_name: &ServerName<'static>,
+    ) -> Result<[(ClientConfig, usize); 3], TraceError> {
+        let hello_len = |config: &ClientConfig| {
+            fingerprint::client_hello(config.clone(), server_name.clone()).map(|hello| hello.len())
+        };
+        let hybrid = self.direct_tls_config_with(None, rustls::DEFAULT_VERSIONS)?;
+        let hybrid_len = hello_len(&hybrid)?;
+        let classical_groups = KxPreference::ClassicalOnly.apply(&self.provider.kx_groups);
+        let classical =
+            self.direct_tls_config_with(Some(classical_groups), rustls::DEFAULT_VERSIONS)?;
+        let classical_len = hello_len(&classical)?;
+
+        // The ALPN extension and list headers count too when the client
+        // offers no protocols, so pad, measure and top up until there.
+        let mut padded = classical.clone();
+        if padded.alpn_protocols.is_empty() {
+            padded.alpn_protocols.push(DEFAULT_PROTOCOL.to_vec());
+        }
+        let mut padded_len = hello_len(&padded)?;
+        while padded_len < hybrid_len {
+            // Each protocol costs its length plus one byte.
+            let len = (hybrid_len - padded_len)
+                .saturating_sub(1)
+                .clamp(1, MAX_PROTOCOL_LEN);
+            padded.alpn_protocols.push(filler(len));
+            padded_len = hello_len(&padded)?;
+        }
+        Ok([
+            (hybrid, hybrid_len),
+            (classical, classical_len),
+            (padded, padded_len),
+        ])
+    }
+
+    async fn hello_probe(
+        &self,
+        addr: SocketAddr,
+        server_name: ServerName<'static>,
+        config: ClientConfig,
+    ) -> HelloProbe {
+        let connector = TlsConnector::from(Arc::new(config));
+        HelloProbe::of(self.handshake_at(addr, server_name, &connector).await)
+    }
+}
+
+/// A made-up ALPN protocol `len` bytes long, `padding---…`, which no server
+/// will select.
+fn filler(len: usize) -> Vec<u8> {
+    b"padding"
+        .iter()
+        .copied()
+        .chain(std::iter::repeat(b'-'))
+        .take(len)
+        .collect()
+}
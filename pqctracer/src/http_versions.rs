@@ -0,0 +1,206 @@
+//! Whether a server negotiates alike for HTTP/1.1 and HTTP/2, probed with
+//! one handshake offering each by ALPN.
+//!
+//! Some origins hand HTTP/2 connections to other software than HTTP/1.1
+//! ones, e.g. a proxy that routes on ALPN, and the two may support other
+//! groups or present another certificate. Only the handshakes are
+//! compared; no request is sent over either connection.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use rustls::{AlertDescription, PeerMisbehaved};
+use rustls_pki_types::ServerName;
+use tokio_rustls::TlsConnector;
+
+use crate::{ErrorCategory, TlsAwareClient, TlsMetadata, TraceError};
+
+const HTTP1: &str = "http/1.1";
+const H2: &str = "h2";
+
+/// How a server answered a handshake offering `http/1.1` and one offering
+/// `h2`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HttpVersionReport {
+    pub http1: HttpVersionSupport,
+    pub h2: HttpVersionSupport,
+    /// Whether the two handshakes agree on the group, the TLS version and
+    /// the certificate. Only `false` when both completed; a server without
+    /// HTTP/2 or a failed probe has nothing to disagree with.
+    pub versions_consistent: bool,
+}
+
+impl HttpVersionReport {
+    fn of(http1: HttpVersionSupport, h2: HttpVersionSupport) -> Self {
+        let versions_consistent = match (&http1, &h2) {
+            (
+                HttpVersionSupport::Negotiated { metadata: a },
+                HttpVersionSupport::Negotiated { metadata: b },
+            ) => {
+                a.group == b.group
+                    && a.protocol_version() == b.protocol_version()
+                    && a.peer_certificates.first() == b.peer_certificates.first()
+            }
+            _ => true,
+        };
+        Self {
+            http1,
+            h2,
+            versions_consistent,
+        }
+    }
+
+    /// Report `error` for both protocols, for probes that cannot start.
+    fn failed(error: ErrorCategory) -> Self {
+        Self::of(
+            HttpVersionSupport::Failed {
+                error: error.clone(),
+            },
+            HttpVersionSupport::Failed { error },
+        )
+    }
+}
+
+/// `http/1.1 X25519MLKEM768, h2 X25519 (INCONSISTENT)` or `http/1.1
+/// X25519MLKEM768, h2 unsupported (no protocol selected)`.
+impl fmt::Display for HttpVersionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}, {} {}", HTTP1, self.http1, H2, self.h2)?;
+        if !self.versions_consistent {
+            f.write_str(" (INCONSISTENT)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of a handshake offering a single application protocol.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "outcome", rename_all = "kebab-case"))]
+pub enum HttpVersionSupport {
+    /// The handshake completed and the server can speak the protocol; for
+    /// HTTP/1.1 that includes servers that select no protocol at all.
+    Negotiated { metadata: Box<TlsMetadata> },
+    /// The server does not speak HTTP/2: it selected no protocol, or
+    /// refused the handshake with a `no_application_protocol` alert.
+    Unsupported { reason: String },
+    /// The probe failed for another reason, e.g. a network error or an
+    /// invalid certificate, so it says nothing about the protocol.
+    Failed { error: ErrorCategory },
+}
+
+impl HttpVersionSupport {
+    pub fn is_negotiated(&self) -> bool {
+        matches!(self, HttpVersionSupport::Negotiated { .. })
+    }
+
+    fn of(protocol: &str, result: Result<TlsMetadata, (TraceError, bool)>) -> Self {
+        match result {
+            Ok(metadata) if protocol == H2 && metadata.alpn.as_deref() != Some(H2) => {
+                HttpVersionSupport::Unsupported {
+                    reason: match metadata.alpn {
+                        Some(selected) => format!("{} selected", selected),
+                        None => "no protocol selected".to_string(),
+                    },
+                }
+            }
+            Ok(metadata) => HttpVersionSupport::Negotiated {
+                metadata: Box::new(metadata),
+            },
+            Err((TraceError::Tls(e), false)) if refuses_protocol(&e) => {
+                HttpVersionSupport::Unsupported {
+                    reason: format!("{:?}", e),
+                }
+            }
+            Err((e, connecting)) => HttpVersionSupport::Failed {
+                error: ErrorCategory::of_trace(&e, connecting),
+            },
+        }
+    }
+}
+
+/// `X25519MLKEM768`, `unsupported (no protocol selected)` or `failed
+/// (connect timeout)`.
+impl fmt::Display for HttpVersionSupport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpVersionSupport::Negotiated { metadata } => {
+                f.write_str(metadata.group.as_deref().unwrap_or("n/a"))
+            }
+            HttpVersionSupport::Unsupported { reason } => write!(f, "unsupported ({})", reason),
+            HttpVersionSupport::Failed { error } => write!(f, "failed ({})", error),
+        }
+    }
+}
+
+/// Whether the handshake failed because the server speaks none of the
+/// offered protocols, as RFC 7301 has it answer.
+fn refuses_protocol(error: &rustls::Error) -> bool {
+    matches!(
+        error,
+        rustls::Error::AlertReceived(AlertDescription::NoApplicationProtocol)
+            | rustls::Error::PeerMisbehaved(PeerMisbehaved::SelectedUnofferedApplicationProtocol)
+    )
+}
+
+/// Probe whether `host:port` negotiates alike for HTTP/1.1 and HTTP/2,
+/// using a default [`TlsAwareClient`].
+pub async fn probe_http_versions(host: &str, port: u16) -> HttpVersionReport {
+    TlsAwareClient::new().probe_http_versions(host, port).await
+}
+
+impl TlsAwareClient {
+    /// Attempt one handshake offering only `http/1.1` and one offering only
+    /// `h2` by ALPN with `host:port`, and compare what they negotiated.
+    pub async fn probe_http_versions(&self, host: &str, port: u16) -> HttpVersionReport {
+        let Ok(server_name) = ServerName::try_from(host.to_string()) else {
+            return HttpVersionReport::failed(ErrorCategory::InvalidUrl);
+        };
+        let addr = match self.bind.lookup(host, port).await {
+            Ok(addrs) => addrs.into_iter().next(),
+            Err(_) => None,
+        };
+        match addr {
+            Some(addr) => self.probe_http_versions_at(addr, server_name).await,
+            None => HttpVersionReport::failed(ErrorCategory::DnsFailure),
+        }
+    }
+
+    /// Like [`probe_http_versions`](Self::probe_http_versions) for an
+    /// address already resolved.
+    pub(crate) async fn probe_http_versions_at(
+        &self,
+        addr: SocketAddr,
+        server_name: ServerName<'static>,
+    ) -> HttpVersionReport {
+        HttpVersionReport::of(
+            self.probe_http_version(addr, server_name.clone(), HTTP1)
+                .await,
+            self.probe_http_version(addr, server_name, H2).await,
+        )
+    }
+
+    async fn probe_http_version(
+        &self,
+        addr: SocketAddr,
+        server_name: ServerName<'static>,
+        protocol: &str,
+    ) -> HttpVersionSupport {
+        let mut config = match self.direct_tls_config(None) {
+            Ok(config) => config,
+            Err(e) => {
+                return HttpVersionSupport::Failed {
+                    error: ErrorCategory::of_trace(&e, false),
+                };
+            }
+        };
+        config.alpn_protocols = vec![protocol.as_bytes().to_vec()];
+        let connector = TlsConnector::from(Arc::new(config));
+        HttpVersionSupport::of(
+            protocol,
+            self.handshake_at(addr, server_name, &connector).await,
+        )
+    }
+}
@@ -0,0 +1,176 @@
+//! Internationalized domain names: hosts such as `münchen.example` are
+//! converted to their A-label form, `xn--mnchen-3ya.example`, before they
+//! are resolved, sent as SNI and matched against the certificate, which
+//! only ever carries A-labels.
+
+use std::fmt;
+
+use crate::TraceError;
+
+/// A host name in both forms, for hosts with at least one non-ASCII label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdnName {
+    /// The name as people write it, e.g. `münchen.example`.
+    pub unicode: String,
+    /// The name on the wire and in the certificate, e.g.
+    /// `xn--mnchen-3ya.example`.
+    pub ascii: String,
+}
+
+impl IdnName {
+    /// Both forms of `host`, given in either; `None` for plain ASCII names
+    /// and for names that are not valid IDNs.
+    pub fn of(host: &str) -> Option<Self> {
+        if !is_idn(host) {
+            return None;
+        }
+        let ascii = to_ascii(host).ok()?;
+        let (unicode, result) = idna::domain_to_unicode(&ascii);
+        result.ok()?;
+        Some(Self { unicode, ascii })
+    }
+}
+
+/// `münchen.example (xn--mnchen-3ya.example)`.
+impl fmt::Display for IdnName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.unicode, self.ascii)
+    }
+}
+
+/// The A-label form of `host`. Plain ASCII names are returned unchanged;
+/// an invalid IDN fails with an error naming the offending label.
+pub fn to_ascii(host: &str) -> Result<String, TraceError> {
+    if !is_idn(host) {
+        return Ok(host.to_string());
+    }
+    idna::domain_to_ascii(host).map_err(|_| {
+        let label = host
+            .split('.')
+            .find(|label| idna::domain_to_ascii(label).is_err())
+            .unwrap_or(host);
+        TraceError::InvalidServerName(format!(
+            "{}: the label {:?} is not a valid internationalized domain name",
+            host, label
+        ))
+    })
+}
+
+/// `url` with its host in A-label form, so that an invalid IDN fails with
+/// [`to_ascii`]'s error rather than a URL parser's.
+pub fn normalize_url(url: &str) -> Result<String, TraceError> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return Ok(url.to_string());
+    };
+    let (authority, path) = rest.split_at(rest.find(['/', '?', '#']).unwrap_or(rest.len()));
+    let (userinfo, host_port) = authority.split_at(authority.rfind('@').map_or(0, |at| at + 1));
+    // IPv6 literals are never IDNs.
+    if host_port.starts_with('[') {
+        return Ok(url.to_string());
+    }
+    let (host, port) = host_port.split_at(host_port.rfind(':').unwrap_or(host_port.len()));
+    if !is_idn(host) {
+        return Ok(url.to_string());
+    }
+    Ok(format!(
+        "{}://{}{}{}{}",
+        scheme,
+        userinfo,
+        to_ascii(host)?,
+        port,
+        path
+    ))
+}
+
+/// Whether `host` has a non-ASCII label or an A-label.
+fn is_idn(host: &str) -> bool {
+    !host.is_ascii()
+        || host.split('.').any(|label| {
+            label
+                .get(..4)
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case("xn--"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip_between_their_forms() {
+        let expected = IdnName {
+            unicode: "münchen.example".to_string(),
+            ascii: "xn--mnchen-3ya.example".to_string(),
+        };
+        assert_eq!(IdnName::of("münchen.example"), Some(expected.clone()));
+        assert_eq!(IdnName::of("xn--mnchen-3ya.example"), Some(expected.clone()));
+        // Upper case is folded as the name is mapped.
+        assert_eq!(IdnName::of("MÜNCHEN.example"), Some(expected.clone()));
+        assert_eq!(
+            expected.to_string(),
+            "münchen.example (xn--mnchen-3ya.example)"
+        );
+        let bücher = IdnName::of("bücher.münchen.example").expect("an IDN");
+        assert_eq!(bücher.ascii, "xn--bcher-kva.xn--mnchen-3ya.example");
+        assert_eq!(IdnName::of(&bücher.ascii), Some(bücher));
+    }
+
+    #[test]
+    fn plain_names_are_left_alone() {
+        for host in ["example.com", "localhost", "127.0.0.1", "Mixed.Case.example"] {
+            assert_eq!(IdnName::of(host), None, "{}", host);
+            assert_eq!(to_ascii(host).unwrap(), host);
+        }
+        assert_eq!(
+            to_ascii("münchen.example").unwrap(),
+            "xn--mnchen-3ya.example"
+        );
+        assert_eq!(
+            to_ascii("xn--mnchen-3ya.example").unwrap(),
+            "xn--mnchen-3ya.example"
+        );
+    }
+
+    #[test]
+    fn invalid_names_are_refused_naming_the_label() {
+        for (host, label) in [
+            ("xn--mnchen-3ya.xn--zz", "xn--zz"),
+            ("xn--.example", "xn--"),
+            ("a\u{200D}b.example", "a\u{200D}b"),
+        ] {
+            let Err(TraceError::InvalidServerName(message)) = to_ascii(host) else {
+                panic!("{:?} is not a valid IDN", host);
+            };
+            assert_eq!(
+                message,
+                format!(
+                    "{}: the label {:?} is not a valid internationalized domain name",
+                    host, label
+                )
+            );
+            assert_eq!(IdnName::of(host), None, "{}", host);
+        }
+    }
+
+    #[test]
+    fn urls_keep_everything_but_the_host() {
+        for (url, expected) in [
+            ("https://münchen.example/", "https://xn--mnchen-3ya.example/"),
+            (
+                "https://user:pw@münchen.example:8443/a/ö?q=ü#f",
+                "https://user:pw@xn--mnchen-3ya.example:8443/a/ö?q=ü#f",
+            ),
+            ("https://münchen.example", "https://xn--mnchen-3ya.example"),
+            ("https://example.com:443/ü", "https://example.com:443/ü"),
+            ("https://[::1]:8443/", "https://[::1]:8443/"),
+            ("münchen.example", "münchen.example"),
+        ] {
+            assert_eq!(normalize_url(url).unwrap(), expected, "{}", url);
+        }
+        assert!(matches!(
+            normalize_url("https://xn--zz.example/"),
+            Err(TraceError::InvalidServerName(_))
+        ));
+    }
+}
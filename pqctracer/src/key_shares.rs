@@ -0,0 +1,283 @@
+//! Watching rustls start key exchanges, to tell when a server answered the
+//! ClientHello with a HelloRetryRequest and what the extra round trip cost.
+//!
+//! Every group the tracer offers is wrapped so that starting a key exchange
+//! is noted in a log that [`observe`] installs for the duration of a
+//! handshake or request. rustls starts one key exchange for the first
+//! ClientHello and another for the group a HelloRetryRequest asks for, so
+//! the time between the two is the round trip the retry added. Like the
+//! timing marks, the notes are made inside the request's future and need
+//! no keying by server name.
+//!
+//! Completing a key exchange is noted too, with the group completed, which
+//! tells a resumed handshake that exchanged a fresh key share from one that
+//! resumed on the pre-shared key alone, see [`ResumptionKx`].
+//!
+//! The log also notes the server asking for a client certificate, which
+//! [`client_auth`](crate::client_auth) learns the same way.
+
+use std::future::Future;
+use std::ptr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use rustls::crypto::{
+    ActiveKeyExchange, CompletedKeyExchange, CryptoProvider, SharedSecret, SupportedKxGroup,
+};
+use rustls::ffdhe_groups::FfdheGroup;
+use rustls::{NamedGroup, ProtocolVersion, SupportedProtocolVersion};
+
+use crate::{ResumptionKx, client_auth, lock};
+
+tokio::task_local! {
+    static LOG: Arc<KeyShareLog>;
+}
+
+/// What happened during one handshake or request, in order.
+#[derive(Default)]
+pub(crate) struct KeyShareLog {
+    events: Mutex<Vec<Event>>,
+}
+
+enum Event {
+    /// A first ClientHello is being built, for a new connection.
+    Hello,
+    /// A key exchange for a group was started.
+    Share(Instant),
+    /// A key exchange was completed with the server's share for a group.
+    Completed(NamedGroup),
+    /// That key exchange was a TLS 1.2 one, which is only started after
+    /// the ServerHello.
+    Tls12,
+    /// The server asked for a client certificate, naming these certificate
+    /// authorities.
+    CertificateRequest(Vec<String>),
+}
+
+impl KeyShareLog {
+    /// How long after starting the first ClientHello's key exchange rustls
+    /// had to start another one for a HelloRetryRequest; `None` when the
+    /// first connection the log saw needed no retry. Later connections,
+    /// e.g. after a redirect, are left out. A TLS 1.2 server's key exchange
+    /// starts after the first ClientHello's too, but is no retry.
+    pub(crate) fn hrr_penalty(&self) -> Option<Duration> {
+        let events = lock(&self.events, "key-share-log");
+        let first_connection = events
+            .iter()
+            .skip_while(|event| matches!(event, Event::Hello))
+            .take_while(|event| !matches!(event, Event::Hello));
+        if first_connection
+            .clone()
+            .any(|event| matches!(event, Event::Tls12))
+        {
+            return None;
+        }
+        let mut shares = first_connection.map_while(|event| match event {
+            Event::Share(at) => Some(*at),
+            _ => None,
+        });
+        let first = shares.next()?;
+        Some(shares.next()?.duration_since(first))
+    }
+
+    /// How a handshake that `resumed` a session exchanged keys: with the
+    /// group of the first key exchange the log saw completed, or with none
+    /// at all. `None` for handshakes that did not resume.
+    pub(crate) fn resumption_kx(&self, resumed: bool) -> Option<ResumptionKx> {
+        if !resumed {
+            return None;
+        }
+        let events = lock(&self.events, "key-share-log");
+        let group = events.iter().find_map(|event| match event {
+            Event::Completed(group) => Some(*group),
+            _ => None,
+        });
+        Some(match group {
+            Some(group) => ResumptionKx::PskWithGroup(group),
+            None => ResumptionKx::PskOnly,
+        })
+    }
+
+    /// Whether a server the log saw asked for a client certificate.
+    pub(crate) fn client_cert_requested(&self) -> bool {
+        let events = lock(&self.events, "key-share-log");
+        events
+            .iter()
+            .any(|event| matches!(event, Event::CertificateRequest(_)))
+    }
+
+    /// The certificate authorities the first server to ask for a client
+    /// certificate named, if any.
+    pub(crate) fn client_cert_authorities(&self) -> Vec<String> {
+        let events = lock(&self.events, "key-share-log");
+        events
+            .iter()
+            .find_map(|event| match event {
+                Event::CertificateRequest(authorities) => Some(authorities.clone()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Append the events of `other`, a log of the connection the request
+    /// this log is for started, leaving `other` empty.
+    pub(crate) fn absorb(&self, other: &KeyShareLog) {
+        let events = std::mem::take(&mut *lock(&other.events, "key-share-log"));
+        lock(&self.events, "key-share-log").extend(events);
+    }
+
+    fn note(event: Event) {
+        let _ = LOG.try_with(|log| lock(&log.events, "key-share-log").push(event));
+    }
+}
+
+/// Run `future` with a fresh log of the key exchanges it starts.
+pub(crate) async fn observe<F: Future>(future: F) -> (F::Output, Arc<KeyShareLog>) {
+    let log = Arc::new(KeyShareLog::default());
+    let output = LOG.scope(log.clone(), future).await;
+    (output, log)
+}
+
+/// The log installed where the caller runs, if any.
+pub(crate) fn current() -> Option<Arc<KeyShareLog>> {
+    LOG.try_with(Arc::clone).ok()
+}
+
+/// Note that a connection's first ClientHello is being built; called from
+/// the session store, which rustls asks for a group hint at that point.
+pub(crate) fn hello_started() {
+    KeyShareLog::note(Event::Hello);
+}
+
+/// Note that the server asked for a client certificate from one of the
+/// certificate authorities with the DER-encoded names `authorities`; called
+/// from the client's certificate resolver.
+pub(crate) fn client_cert_requested(authorities: &[&[u8]]) {
+    KeyShareLog::note(Event::CertificateRequest(
+        authorities
+            .iter()
+            .map(|name| client_auth::authority_name(name))
+            .collect(),
+    ));
+}
+
+/// `provider` with each of its groups wrapped so that starting a key
+/// exchange is noted.
+pub(crate) fn observed(provider: &CryptoProvider) -> Arc<CryptoProvider> {
+    Arc::new(CryptoProvider {
+        kx_groups: provider
+            .kx_groups
+            .iter()
+            .map(|group| observed_group(*group))
+            .collect(),
+        ..provider.clone()
+    })
+}
+
+/// Providers need `'static` groups, so each group is wrapped once for the
+/// life of the process and the wrappers are shared by every client.
+fn observed_group(group: &'static dyn SupportedKxGroup) -> &'static dyn SupportedKxGroup {
+    static WRAPPERS: OnceLock<Mutex<Vec<&'static ObservedKxGroup>>> = OnceLock::new();
+    let mut wrappers = lock(WRAPPERS.get_or_init(Mutex::default), "kx-wrappers");
+    let found = wrappers.iter().copied().find(|wrapper| {
+        ptr::addr_eq(wrapper.inner, group) || ptr::addr_eq(*wrapper as &dyn SupportedKxGroup, group)
+    });
+    if let Some(wrapper) = found {
+        return wrapper;
+    }
+    let wrapper: &'static ObservedKxGroup = Box::leak(Box::new(ObservedKxGroup { inner: group }));
+    wrappers.push(wrapper);
+    wrapper
+}
+
+/// Notes every key exchange started, and completed, and otherwise leaves it
+/// to `inner`.
+#[derive(Debug)]
+struct ObservedKxGroup {
+    inner: &'static dyn SupportedKxGroup,
+}
+
+impl SupportedKxGroup for ObservedKxGroup {
+    fn start(&self) -> Result<Box<dyn ActiveKeyExchange>, rustls::Error> {
+        KeyShareLog::note(Event::Share(Instant::now()));
+        let inner = self.inner.start()?;
+        Ok(Box::new(ObservedKeyExchange { inner }))
+    }
+
+    fn start_and_complete(
+        &self,
+        peer_pub_key: &[u8],
+    ) -> Result<CompletedKeyExchange, rustls::Error> {
+        KeyShareLog::note(Event::Completed(self.inner.name()));
+        self.inner.start_and_complete(peer_pub_key)
+    }
+
+    fn ffdhe_group(&self) -> Option<FfdheGroup<'static>> {
+        self.inner.ffdhe_group()
+    }
+
+    fn name(&self) -> NamedGroup {
+        self.inner.name()
+    }
+
+    fn fips(&self) -> bool {
+        self.inner.fips()
+    }
+
+    fn usable_for_version(&self, version: ProtocolVersion) -> bool {
+        self.inner.usable_for_version(version)
+    }
+}
+
+/// A key exchange that notes being completed and otherwise leaves it to
+/// `inner`.
+struct ObservedKeyExchange {
+    inner: Box<dyn ActiveKeyExchange>,
+}
+
+impl ActiveKeyExchange for ObservedKeyExchange {
+    fn complete(self: Box<Self>, peer_pub_key: &[u8]) -> Result<SharedSecret, rustls::Error> {
+        KeyShareLog::note(Event::Completed(self.inner.group()));
+        self.inner.complete(peer_pub_key)
+    }
+
+    fn complete_for_tls_version(
+        self: Box<Self>,
+        peer_pub_key: &[u8],
+        tls_version: &SupportedProtocolVersion,
+    ) -> Result<SharedSecret, rustls::Error> {
+        KeyShareLog::note(Event::Completed(self.inner.group()));
+        if tls_version.version == ProtocolVersion::TLSv1_2 {
+            KeyShareLog::note(Event::Tls12);
+        }
+        self.inner
+            .complete_for_tls_version(peer_pub_key, tls_version)
+    }
+
+    fn hybrid_component(&self) -> Option<(NamedGroup, &[u8])> {
+        self.inner.hybrid_component()
+    }
+
+    // The server picked the classical half of a hybrid share.
+    fn complete_hybrid_component(
+        self: Box<Self>,
+        peer_pub_key: &[u8],
+    ) -> Result<SharedSecret, rustls::Error> {
+        if let Some((group, _)) = self.inner.hybrid_component() {
+            KeyShareLog::note(Event::Completed(group));
+        }
+        self.inner.complete_hybrid_component(peer_pub_key)
+    }
+
+    fn pub_key(&self) -> &[u8] {
+        self.inner.pub_key()
+    }
+
+    fn ffdhe_group(&self) -> Option<FfdheGroup<'static>> {
+        self.inner.ffdhe_group()
+    }
+
+    fn group(&self) -> NamedGroup {
+        self.inner.group()
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rustls::NamedGroup;
+use rustls::crypto::SupportedKxGroup;
+
+/// How quantum-resistant a key-exchange group is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum KeyExchangeKind {
+    /// Elliptic-curve or finite-field Diffie-Hellman only.
+    Classical,
+    /// A classical group combined with ML-KEM, e.g. `X25519MLKEM768`.
+    Hybrid,
+    /// ML-KEM on its own.
+    PostQuantum,
+}
+
+impl KeyExchangeKind {
+    pub fn of(group: NamedGroup) -> Self {
+        match group {
+            NamedGroup::X25519MLKEM768 | NamedGroup::secp256r1MLKEM768 => KeyExchangeKind::Hybrid,
+            NamedGroup::MLKEM512 | NamedGroup::MLKEM768 | NamedGroup::MLKEM1024 => {
+                KeyExchangeKind::PostQuantum
+            }
+            _ => KeyExchangeKind::Classical,
+        }
+    }
+
+    /// Classify a group by the name the tracer reports for it, e.g.
+    /// `X25519MLKEM768`.
+    pub fn of_name(name: &str) -> Self {
+        let name = name.to_ascii_uppercase();
+        if name.starts_with("MLKEM") {
+            KeyExchangeKind::PostQuantum
+        } else if name.contains("MLKEM") {
+            KeyExchangeKind::Hybrid
+        } else {
+            KeyExchangeKind::Classical
+        }
+    }
+
+    pub fn is_quantum_resistant(self) -> bool {
+        self != KeyExchangeKind::Classical
+    }
+}
+
+impl fmt::Display for KeyExchangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KeyExchangeKind::Classical => "classical",
+            KeyExchangeKind::Hybrid => "hybrid",
+            KeyExchangeKind::PostQuantum => "post-quantum",
+        })
+    }
+}
+
+/// Which key-exchange groups a client offers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum KxPreference {
+    /// The provider's default list, hybrid groups first.
+    #[default]
+    HybridPreferred,
+    /// The provider's list with every ML-KEM group removed.
+    ClassicalOnly,
+}
+
+impl KxPreference {
+    /// Filter `groups` down to the ones this preference offers, keeping
+    /// their order.
+    pub(crate) fn apply(
+        self,
+        groups: &[&'static dyn SupportedKxGroup],
+    ) -> Vec<&'static dyn SupportedKxGroup> {
+        match self {
+            KxPreference::HybridPreferred => groups.to_vec(),
+            KxPreference::ClassicalOnly => groups
+                .iter()
+                .copied()
+                .filter(|g| !KeyExchangeKind::of(g.name()).is_quantum_resistant())
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for KxPreference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KxPreference::HybridPreferred => "hybrid-preferred",
+            KxPreference::ClassicalOnly => "classical-only",
+        })
+    }
+}
+
+impl FromStr for KxPreference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hybrid-preferred" | "hybrid" => Ok(KxPreference::HybridPreferred),
+            "classical-only" | "classical" => Ok(KxPreference::ClassicalOnly),
+            other => Err(format!("unknown key-exchange preference: {}", other)),
+        }
+    }
+}
+
+/// Which key exchange a single request offers, whatever the client's
+/// [`KxPreference`]; see
+/// [`TracedRequestBuilder::key_exchange`](crate::TracedRequestBuilder::key_exchange).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum KxOverride {
+    /// The client's own preference.
+    #[default]
+    Default,
+    /// Classical groups only.
+    ClassicalOnly,
+    /// The provider's full list, hybrid groups first, failing the request
+    /// when the server still picks a classical group.
+    PqcRequired,
+}
+
+impl fmt::Display for KxOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KxOverride::Default => "default",
+            KxOverride::ClassicalOnly => "classical-only",
+            KxOverride::PqcRequired => "pqc-required",
+        })
+    }
+}
+
+impl FromStr for KxOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "default" => Ok(KxOverride::Default),
+            "classical-only" | "classical" => Ok(KxOverride::ClassicalOnly),
+            "pqc-required" | "pqc" => Ok(KxOverride::PqcRequired),
+            other => Err(format!("unknown key-exchange override: {}", other)),
+        }
+    }
+}
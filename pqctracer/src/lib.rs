@@ -1,75 +1,1551 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
-use std::sync::{Arc, Mutex};
-use rustls::client::{ClientSessionStore, Resumption};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+use hyper_util::client::legacy::connect::HttpInfo;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::{ClientSessionStore, ResolvesClientCert, Resumption, WebPkiServerVerifier};
+use rustls::crypto::{CryptoProvider, SupportedKxGroup};
 use rustls::client::{Tls12ClientSessionValue, Tls13ClientSessionValue};
-use rustls::NamedGroup;
-use rustls_pki_types::ServerName;
+use rustls::{CipherSuite, DigitallySignedStruct, DistinguishedName, HandshakeKind, NamedGroup, SignatureScheme};
+use rustls::SupportedProtocolVersion;
+use rustls_pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
-/// TLS metadata captured during a single request's handshake.
+use crate::connections::Connections;
+use crate::pinning::Pins;
+use crate::validation::ChainValidator;
+
+pub mod bench;
+mod alerts;
+mod bind;
+mod capabilities;
+mod capture;
+pub mod cert;
+mod client_auth;
+mod connections;
+pub mod cookies;
+mod counting;
+#[cfg(feature = "dns")]
+pub mod dane;
+#[cfg(feature = "dns")]
+pub mod dns;
+mod error;
+mod fallback;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod fingerprint;
+mod hello;
+pub mod hello_size;
+pub mod http_versions;
+pub mod idn;
+mod key_shares;
+mod kx;
+mod logging;
+pub mod multipart;
+mod pinning;
+mod ratelimit;
+pub mod readiness;
+pub mod repeat;
+mod request;
+pub mod resumption;
+pub mod scan;
+#[cfg(feature = "serde")]
+mod serde_util;
+pub mod server;
+mod server_hello;
+pub mod starttls;
+pub mod stats;
+mod tcp_stats;
+#[cfg(feature = "test-util")]
+pub mod testutil;
+mod timings;
+mod trace;
+mod validation;
+mod verify;
+pub mod versions;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+pub use alerts::{AlertInfo, AlertLevel};
+pub use capabilities::{ClientCapabilities, ReportMeta};
+pub use cookies::CookieJar;
+pub use fallback::FallbackOutcome;
+pub use hello_size::probe_hello_size_tolerance;
+pub use http_versions::probe_http_versions;
+pub use error::{
+    BodyError, CertError, CertificateProblem, ErrorCategory, HandshakeFailureKind, NameMismatch,
+    StreamError, TraceError,
+};
+pub use kx::{KeyExchangeKind, KxOverride, KxPreference};
+pub use server_hello::ExtensionInfo;
+pub use pinning::{PinMismatch, PinPolicy, Sha256Digest};
+pub use repeat::trace_repeated;
+pub use request::TracedRequestBuilder;
+pub use resumption::{ResumptionKx, probe_resumption};
+pub use starttls::probe_starttls;
+pub use timings::Timings;
+pub use trace::{TraceOptions, trace_url, trace_url_with};
+pub use validation::{CertPolicy, ValidationReport};
+pub use versions::probe_tls_versions;
+
+/// TLS metadata captured during a single handshake.
+///
+/// Fields are added in minor releases, so code outside this crate starts
+/// from [`TlsMetadata::new`] or `Default` and sets the fields it needs.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct TlsMetadata {
+    /// The negotiated key-exchange group, e.g. `X25519MLKEM768`.
+    pub group: Option<String>,
+    /// The negotiated cipher suite, e.g. `TLS13_AES_256_GCM_SHA384`.
+    pub cipher: Option<String>,
+    /// The certificate chain the server presented, end-entity first.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub peer_certificates: Vec<CertificateDer<'static>>,
+    /// How the connection the request went over was set up; `None` when it
+    /// is not known.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub origin: Option<HandshakeOrigin>,
+    /// The local end of the connection, e.g. the address picked with
+    /// [`local_address`](TlsAwareClientBuilder::local_address); `None` when
+    /// it is not known.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub local_addr: Option<SocketAddr>,
+    /// The server's end of the connection; `None` when it is not known.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub remote_addr: Option<SocketAddr>,
+    /// The server name in both forms when it is an internationalized domain
+    /// name: SNI and certificate validation use the A-label form.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub idn: Option<idn::IdnName>,
+    /// Whether `group` and `cipher` were captured, and if not, why.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub capture_status: CaptureStatus,
+    /// How long the TLS handshake took, for handshakes the tracer drove
+    /// itself.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "handshake_ms",
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "crate::serde_util::millis_option"
+        )
+    )]
+    pub handshake_duration: Option<Duration>,
+    /// How much later than planned the key exchange started because the
+    /// server answered with a HelloRetryRequest: the time from the first
+    /// ClientHello's key share to the one the server asked for, about one
+    /// round trip. `None` for handshakes without a retry and for requests
+    /// that reused a connection.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "hrr_penalty_ms",
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "crate::serde_util::millis_option"
+        )
+    )]
+    pub hrr_penalty: Option<Duration>,
+    /// Bytes the client wrote during the handshake. HTTP requests leave the
+    /// TCP connection to reqwest, so for them the kernel's counters of the
+    /// connection are read once the handshake is done; only Linux has
+    /// those, and through a proxy they also count the `CONNECT` exchange.
+    /// `None` for requests whose connection an earlier one set up.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub handshake_bytes_sent: Option<u64>,
+    /// Bytes the client read during the handshake; see
+    /// [`handshake_bytes_sent`](Self::handshake_bytes_sent).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub handshake_bytes_received: Option<u64>,
+    /// Alerts the server sent during the handshake without failing it,
+    /// such as a TLS 1.2 server's `unrecognized_name` warning. Only
+    /// recorded for handshakes the tracer drove itself – scans of
+    /// [`ScanTarget::Address`](scan::ScanTarget::Address), the `probe_*`
+    /// methods such as [`probe_tls_versions`](TlsAwareClient::probe_tls_versions),
+    /// and [`bench`](TlsAwareClient::bench) – and only for alerts sent before
+    /// encryption started. HTTP requests hand the connection to reqwest, so
+    /// their list is always empty. A fatal alert fails the handshake and
+    /// shows up in [`HandshakeFailureKind`] instead.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub alerts_received: Vec<AlertInfo>,
+    /// The extensions of the server's ServerHello, e.g. `key_share` with
+    /// the group and `supported_versions`, when the client was built with
+    /// [`deep_inspect`](TlsAwareClientBuilder::deep_inspect). Only recorded
+    /// for handshakes the tracer drove itself, like
+    /// [`alerts_received`](Self::alerts_received); HTTP requests always
+    /// report `None`. The extensions TLS 1.3 sends encrypted, such as
+    /// `early_data`, cannot be seen.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub server_extensions: Option<Vec<ExtensionInfo>>,
+    /// The host's HTTPS DNS records, best first, when the client was given
+    /// an [`https_resolver`](TlsAwareClientBuilder::https_resolver).
+    #[cfg(feature = "dns")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub https_records: Vec<dns::HttpsRecord>,
+    /// Whether the ClientHello was encrypted with an ECH configuration from
+    /// the host's HTTPS record.
+    #[cfg(feature = "dns")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    pub ech_offered: bool,
+    /// How the host's TLSA records compare with its certificate chain, for
+    /// clients built with [`dane`](TlsAwareClientBuilder::dane).
+    #[cfg(feature = "dns")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub dane: Option<dane::DaneResult>,
+    /// How long each phase of the request took, for clients built with
+    /// [`collect_timings`](TlsAwareClientBuilder::collect_timings).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub timings: Option<Timings>,
+    /// How the server's certificate chain was validated, for requests that
+    /// performed a full handshake.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub validation: Option<ValidationReport>,
+    /// Whether the server's certificate passed verification, for full
+    /// handshakes by a verifying client; `None` otherwise. Under
+    /// [`CertPolicy::ReportOnly`] a failure is recorded here instead of
+    /// failing the handshake, and serialized as `"outcome": "unverified"`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "crate::serde_util::cert_validation"
+        )
+    )]
+    pub cert_validation: Option<Result<(), CertError>>,
+    /// Whether the server's key matched one of its
+    /// [pins](TlsAwareClientBuilder::pin_spki); `None` for hosts without
+    /// pins and handshakes that presented no certificate.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub pin_matched: Option<bool>,
+    /// The application protocol the server selected by ALPN, e.g.
+    /// `http/1.1`. Only known for handshakes the tracer drove itself, since
+    /// reqwest does not hand it back; `None` there when the server selected
+    /// none.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub alpn: Option<String>,
+    /// How many session tickets the server issued while the request was in
+    /// flight: TLS 1.3 tickets, or one for a TLS 1.2 session the server
+    /// made resumable. `Some(0)` means the handshake completed without any;
+    /// `None` that it is not known, e.g. for a reused connection, a
+    /// handshake the tracer drove itself, which stops reading before
+    /// tickets arrive, or a TLS 1.3 handshake whose tickets hyper read on a
+    /// connection task of its own, outside the request.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub tickets_issued: Option<u32>,
+    /// How a resumed handshake set up its keys: with a fresh key exchange in
+    /// some group, or from the pre-shared key alone, which gives up the
+    /// forward secrecy and post-quantum protection a key exchange would
+    /// bring. `None` for full handshakes and when no handshake was seen.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "crate::serde_util::resumption_kx"
+        )
+    )]
+    pub resumption_kx: Option<ResumptionKx>,
+    /// Whether the server asked for a client certificate in the handshake,
+    /// with or without a [resolver](TlsAwareClientBuilder::client_cert_resolver)
+    /// to answer it; `None` when the request saw no handshake, e.g. on a
+    /// reused connection.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub client_cert_requested: Option<bool>,
+    /// The certificate authorities the server named when it asked for a
+    /// client certificate, e.g. `CN=Example Client CA`; empty when it
+    /// named none or did not ask.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub client_cert_authorities: Vec<String>,
+    /// Whether the request needed a classical-only retry; `None` unless
+    /// the client [falls back](TlsAwareClientBuilder::fallback_to_classical).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub fallback: Option<FallbackOutcome>,
+    /// The labels the request was sent with, see
+    /// [`TlsAwareClient::execute_with_labels`]; empty for handshakes the
+    /// tracer drove itself, except in scans, whose results all carry the
+    /// [scan's labels](scan::ScanOptions::labels).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl TlsMetadata {
+    /// Metadata with just the negotiated group and cipher suite, everything
+    /// else unknown, e.g. for tests of code that consumes metadata.
+    pub fn new(group: Option<String>, cipher: Option<String>) -> Self {
+        Self {
+            group,
+            cipher,
+            ..Self::default()
+        }
+    }
+
+    /// Read the negotiated parameters from a connection the tracer drove itself.
+    pub(crate) fn from_connection(conn: &rustls::ClientConnection) -> Self {
+        Self {
+            group: conn
+                .negotiated_key_exchange_group()
+                .map(|g| format!("{:?}", g.name())),
+            cipher: conn
+                .negotiated_cipher_suite()
+                .map(|s| format!("{:?}", s.suite())),
+            peer_certificates: conn
+                .peer_certificates()
+                .map(|certs| certs.iter().map(|c| c.clone().into_owned()).collect())
+                .unwrap_or_default(),
+            origin: conn.handshake_kind().map(|kind| match kind {
+                HandshakeKind::Resumed => HandshakeOrigin::Resumed,
+                _ => HandshakeOrigin::Fresh,
+            }),
+            local_addr: None,
+            remote_addr: None,
+            idn: None,
+            capture_status: CaptureStatus::Captured,
+            handshake_duration: None,
+            hrr_penalty: None,
+            handshake_bytes_sent: None,
+            handshake_bytes_received: None,
+            alerts_received: Vec::new(),
+            server_extensions: None,
+            #[cfg(feature = "dns")]
+            https_records: Vec::new(),
+            #[cfg(feature = "dns")]
+            ech_offered: false,
+            #[cfg(feature = "dns")]
+            dane: None,
+            timings: None,
+            validation: None,
+            cert_validation: None,
+            pin_matched: None,
+            alpn: conn
+                .alpn_protocol()
+                .map(|protocol| String::from_utf8_lossy(protocol).into_owned()),
+            tickets_issued: None,
+            resumption_kx: None,
+            client_cert_requested: None,
+            client_cert_authorities: Vec::new(),
+            fallback: None,
+            labels: BTreeMap::new(),
+        }
+    }
+
+    /// Whether the handshake went ahead although the server's certificate
+    /// failed verification, see [`CertPolicy::ReportOnly`].
+    pub fn is_unverified(&self) -> bool {
+        matches!(self.cert_validation, Some(Err(_)))
+    }
+
+    /// The validity period of the server's end-entity certificate, if one
+    /// was captured.
+    pub fn certificate_validity(&self) -> Option<cert::CertificateValidity> {
+        self.peer_certificates
+            .first()
+            .and_then(cert::CertificateValidity::of)
+    }
+
+    /// How quantum-resistant the negotiated group is, if one was captured.
+    pub fn key_exchange_kind(&self) -> Option<KeyExchangeKind> {
+        self.group.as_deref().map(KeyExchangeKind::of_name)
+    }
+
+    /// The TLS version, `TLS1.3` or `TLS1.2`, as implied by the cipher suite.
+    pub fn protocol_version(&self) -> Option<&'static str> {
+        match self.cipher.as_deref()? {
+            cipher if cipher.starts_with("TLS13_") => Some("TLS1.3"),
+            _ => Some("TLS1.2"),
+        }
+    }
+}
+
+/// The stable single-line form used by the command line and meant for logs:
+/// `TLS1.3 X25519MLKEM768 / TLS13_AES_256_GCM_SHA384 (12ms, fresh)`, with
+/// `n/a` for each part that was not captured. The duration is the
+/// handshake's, or the TLS phase of the [`timings`](Self::timings).
+impl fmt::Display for TlsMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} / {} (",
+            self.protocol_version().unwrap_or("n/a"),
+            self.group.as_deref().unwrap_or("n/a"),
+            self.cipher.as_deref().unwrap_or("n/a"),
+        )?;
+        match self
+            .handshake_duration
+            .or(self.timings.and_then(|timings| timings.tls))
+        {
+            Some(duration) => write!(f, "{}ms", duration.as_millis())?,
+            None => f.write_str("n/a")?,
+        }
+        match self.origin {
+            Some(origin) => write!(f, ", {})", origin),
+            None => f.write_str(", n/a)"),
+        }
+    }
+}
+
+/// How the connection behind a [`TlsMetadata`] came about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum HandshakeOrigin {
+    /// A full handshake was performed for this request.
+    Fresh,
+    /// An earlier session was resumed.
+    Resumed,
+    /// The request went over a pooled connection set up by an earlier
+    /// request, so no handshake was observed.
+    Reused,
+    /// The request went over a keep-alive connection set up by an earlier
+    /// request and reports that connection's handshake. Requests speak
+    /// HTTP/1.1 only, so no two share a connection at the same time.
+    #[cfg_attr(feature = "serde", serde(rename = "shared"))]
+    SharedConnection,
+}
+
+impl fmt::Display for HandshakeOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HandshakeOrigin::Fresh => "fresh",
+            HandshakeOrigin::Resumed => "resumed",
+            HandshakeOrigin::Reused => "reused",
+            HandshakeOrigin::SharedConnection => "shared",
+        })
+    }
+}
+
+/// Why a [`TlsMetadata`] has, or lacks, its group and cipher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CaptureStatus {
+    /// Both the group and the cipher were captured.
+    Captured,
+    /// A TLS 1.3 handshake was observed, but not its cipher, which a
+    /// configuration the tracer did not build only reveals in a session
+    /// ticket, and the server issued none.
+    NoSessionTicket,
+    /// The server's certificate, or a resumed TLS 1.2 session, was seen
+    /// but no key-exchange group: rustls reports neither group nor cipher
+    /// for TLS 1.2 handshakes it does not drive directly.
+    Tls12NoData,
+    /// The request went over a pooled connection set up by an earlier
+    /// request, so there was no handshake to observe.
+    ReusedConnection,
+    /// The URL was plain HTTP.
+    Plaintext,
+    /// A handshake happened for a server the capture was not registered
+    /// for, e.g. the target of a cross-host redirect.
+    CallbackMissed,
+    /// Nothing was captured, and the tracer cannot tell why; also the
+    /// status of metadata that was never filled in.
+    #[default]
+    Unknown,
+}
+
+/// Human-readable reasons, as printed by the command line.
+impl fmt::Display for CaptureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CaptureStatus::Captured => "captured",
+            CaptureStatus::NoSessionTicket => "cipher unknown, the server issued no session ticket",
+            CaptureStatus::Tls12NoData => "TLS 1.2 handshake, group and cipher not reported",
+            CaptureStatus::ReusedConnection => "connection reused, no handshake observed",
+            CaptureStatus::Plaintext => "plain HTTP, no TLS",
+            CaptureStatus::CallbackMissed => "handshake not attributed to this request",
+            CaptureStatus::Unknown => "nothing captured",
+        })
+    }
+}
+
+/// A response together with the TLS metadata captured during its handshake.
+///
+/// Like [`TlsMetadata`], it may gain fields in minor releases; code outside
+/// this crate builds one with [`TlsResponse::new`].
+#[non_exhaustive]
 pub struct TlsResponse {
     pub response: reqwest::Response,
+    /// The same as `metadata.group`, kept for code written against 1.0.
+    #[deprecated(since = "2.0.0", note = "use `metadata.group`")]
     pub group: Option<String>,
+    /// The same as `metadata.cipher`, kept for code written against 1.0.
+    #[deprecated(since = "2.0.0", note = "use `metadata.cipher`")]
     pub cipher: Option<String>,
+    pub metadata: TlsMetadata,
 }
 
-/// Per-request TLS metadata populated by the session store callbacks.
+impl TlsResponse {
+    /// Pair `response` with the `metadata` of its handshake, e.g. to stand
+    /// in for [`TlsAwareClient::execute`] in tests.
+    pub fn new(response: reqwest::Response, metadata: TlsMetadata) -> Self {
+        #[allow(deprecated)]
+        Self {
+            response,
+            group: metadata.group.clone(),
+            cipher: metadata.cipher.clone(),
+            metadata,
+        }
+    }
+
+    /// The metadata's [`Display`](TlsMetadata#impl-Display-for-TlsMetadata)
+    /// form prefixed with the host, e.g.
+    /// `www.example.com: TLS1.3 X25519MLKEM768 / TLS13_AES_256_GCM_SHA384 (n/a, fresh)`.
+    pub fn summary(&self) -> String {
+        let host = self.response.url().host_str().unwrap_or("n/a");
+        format!("{}: {}", host, self.metadata)
+    }
+
+    /// How long each phase of the request took, if the client was built
+    /// with [`collect_timings`](TlsAwareClientBuilder::collect_timings).
+    /// Reading the body with [`text`](Self::text), [`bytes`](Self::bytes),
+    /// [`json`](Self::json) or [`stream_to`](Self::stream_to) fills in
+    /// [`Timings::total`] of the metadata they return.
+    pub fn timings(&self) -> Option<Timings> {
+        self.metadata.timings
+    }
+
+    /// Grade the handshake's quantum readiness with the default weights;
+    /// see [`TlsMetadata::readiness`] for others.
+    pub fn readiness(&self) -> readiness::QuantumReadiness {
+        self.metadata.readiness(&readiness::ReadinessWeights::default())
+    }
+
+    /// Which root anchored the server's chain, how long the chain was and
+    /// how long validating it took. Requests on a pooled connection repeat
+    /// the report of the handshake that opened it; `None` when no
+    /// certificate was verified, e.g. for a resumed session.
+    pub fn validation_report(&self) -> Option<&ValidationReport> {
+        self.metadata.validation.as_ref()
+    }
+
+    /// Read the body as text, like `reqwest::Response::text`, keeping the
+    /// metadata.
+    pub async fn text(self) -> Result<(String, TlsMetadata), BodyError> {
+        let Self { response, mut metadata, .. } = self;
+        let start = Instant::now();
+        let body = response.text().await;
+        if let Some(timings) = &mut metadata.timings {
+            timings.body_read(start.elapsed());
+        }
+        match body {
+            Ok(text) => Ok((text, metadata)),
+            Err(error) => Err(BodyError { error, metadata }),
+        }
+    }
+
+    /// Read the body as raw bytes, keeping the metadata.
+    pub async fn bytes(self) -> Result<(bytes::Bytes, TlsMetadata), BodyError> {
+        let Self { response, mut metadata, .. } = self;
+        let start = Instant::now();
+        let body = response.bytes().await;
+        if let Some(timings) = &mut metadata.timings {
+            timings.body_read(start.elapsed());
+        }
+        match body {
+            Ok(bytes) => Ok((bytes, metadata)),
+            Err(error) => Err(BodyError { error, metadata }),
+        }
+    }
+
+    /// Write the body into `writer` chunk by chunk as it arrives, without
+    /// holding it in memory, and return how many bytes were written along
+    /// with the metadata. A body that ends before, or runs past, the
+    /// response's `Content-Length` is an error, with the bytes written so
+    /// far left in `writer`.
+    pub async fn stream_to<W>(self, writer: &mut W) -> Result<(u64, TlsMetadata), StreamError>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let Self { mut response, mut metadata, .. } = self;
+        let expected = response.content_length();
+        let start = Instant::now();
+        let mut written = 0;
+        let streamed = async {
+            while let Some(chunk) = response.chunk().await.map_err(io::Error::other)? {
+                writer.write_all(&chunk).await?;
+                written += chunk.len() as u64;
+            }
+            writer.flush().await?;
+            match expected {
+                Some(expected) if expected != written => Err(io::Error::new(
+                    if written < expected {
+                        io::ErrorKind::UnexpectedEof
+                    } else {
+                        io::ErrorKind::InvalidData
+                    },
+                    format!("body of {} bytes, Content-Length {}", written, expected),
+                )),
+                _ => Ok(()),
+            }
+        }
+        .await;
+        if let Some(timings) = &mut metadata.timings {
+            timings.body_read(start.elapsed());
+        }
+        match streamed {
+            Ok(()) => Ok((written, metadata)),
+            Err(error) => Err(StreamError { error, written, metadata }),
+        }
+    }
+
+    /// Deserialize the body as JSON, like `reqwest::Response::json`, keeping
+    /// the metadata; invalid JSON is an error that still carries it.
+    #[cfg(feature = "json")]
+    pub async fn json<T: serde::de::DeserializeOwned>(self) -> Result<(T, TlsMetadata), BodyError> {
+        let Self { response, mut metadata, .. } = self;
+        let start = Instant::now();
+        let body = response.json().await;
+        if let Some(timings) = &mut metadata.timings {
+            timings.body_read(start.elapsed());
+        }
+        match body {
+            Ok(value) => Ok((value, metadata)),
+            Err(error) => Err(BodyError { error, metadata }),
+        }
+    }
+}
+
+/// Per-request TLS metadata populated by the session store callbacks, for
+/// the request that [installed](capture::scope) it.
+///
+/// Each value is written at most once – the first callback wins – so that
+/// callbacks fill it in without taking a lock. Values are kept raw and only
+/// formatted into the [`TlsMetadata`] strings once the request is done.
+///
+/// Every request allocates a cell of its own. Since the cell is installed
+/// as a task-local, no callback can reach the cell of another request, so
+/// the generation-checked pool cells were once reused from is gone: it
+/// took a lock per checkout and measured no faster, see
+/// `benches/capture.rs`.
 #[derive(Default, Debug)]
 struct Captured {
-    group: Option<String>,
-    cipher: Option<String>,
+    group: OnceLock<NamedGroup>,
+    cipher: OnceLock<CipherSuite>,
+    peer_certificates: OnceLock<Vec<CertificateDer<'static>>>,
+    validation: OnceLock<ValidationReport>,
+    cert_validation: OnceLock<Result<(), CertError>>,
+    tickets: AtomicU32,
+    /// Whether the session store handed out a session to resume.
+    resumption_offered: AtomicBool,
+    /// The connection a request started, if it started one.
+    connection: OnceLock<connections::ConnectionKey>,
+}
+
+/// The values taken out of a [`Captured`] cell.
+#[derive(Default)]
+struct CapturedValues {
+    group: Option<NamedGroup>,
+    cipher: Option<CipherSuite>,
+    peer_certificates: Vec<CertificateDer<'static>>,
+    validation: Option<ValidationReport>,
+    cert_validation: Option<Result<(), CertError>>,
+    tickets: u32,
+    resumption_offered: bool,
+}
+
+impl CapturedValues {
+    /// Whether any handshake callback fired for this capture; silence
+    /// means a pooled connection.
+    fn handshake_seen(&self) -> bool {
+        self.group.is_some() || !self.peer_certificates.is_empty() || self.resumption_offered
+    }
+
+    /// A full handshake always verifies the server's certificate; one that
+    /// went without it resumed a session from a
+    /// [`session_store`](TlsAwareClientBuilder::session_store), reporting
+    /// a group if it was a TLS 1.3 one.
+    fn origin(&self) -> HandshakeOrigin {
+        match (self.handshake_seen(), self.peer_certificates.is_empty()) {
+            (false, _) => HandshakeOrigin::Reused,
+            (true, true) => HandshakeOrigin::Resumed,
+            (true, false) => HandshakeOrigin::Fresh,
+        }
+    }
+
+    /// What the callbacks that fired say about the capture. Silence is
+    /// taken for a pooled connection.
+    fn status(&self) -> CaptureStatus {
+        match (self.group, self.cipher) {
+            (Some(_), Some(_)) => CaptureStatus::Captured,
+            (Some(_), None) => CaptureStatus::NoSessionTicket,
+            (None, _) if !self.peer_certificates.is_empty() || self.resumption_offered => {
+                CaptureStatus::Tls12NoData
+            }
+            (None, _) => CaptureStatus::ReusedConnection,
+        }
+    }
+
+    /// How many session tickets the request saw, if that is all of them. A
+    /// TLS 1.2 server makes its session resumable within the handshake, a
+    /// TLS 1.3 one sends its tickets afterwards; unless some arrived inside
+    /// the request, hyper read them, if there were any, on its connection
+    /// task. Only TLS 1.3 handshakes reveal their cipher here.
+    fn tickets_issued(&self) -> Option<u32> {
+        let tls13 = self.cipher.is_some();
+        (self.handshake_seen() && (self.tickets > 0 || !tls13)).then_some(self.tickets)
+    }
+
+    /// How many session tickets a handshake saw whose connection was read
+    /// up to the server's `close_notify`, which comes after all of them.
+    #[cfg(feature = "test-util")]
+    fn tickets_read_to_close(&self) -> Option<u32> {
+        self.handshake_seen().then_some(self.tickets)
+    }
+
+    fn group_name(&self) -> Option<String> {
+        self.group.map(|group| format!("{:?}", group))
+    }
+
+    fn cipher_name(&self) -> Option<String> {
+        self.cipher.map(|suite| format!("{:?}", suite))
+    }
+}
+
+impl Captured {
+    /// Copy in what was captured for a connection the request started.
+    /// Anything the request's own cell already holds is kept.
+    fn absorb(&self, other: &Captured) {
+        fn copy<T: Clone>(into: &OnceLock<T>, from: &OnceLock<T>) {
+            if let Some(value) = from.get() {
+                let _ = into.set(value.clone());
+            }
+        }
+        copy(&self.group, &other.group);
+        copy(&self.cipher, &other.cipher);
+        copy(&self.peer_certificates, &other.peer_certificates);
+        copy(&self.validation, &other.validation);
+        copy(&self.cert_validation, &other.cert_validation);
+        self.tickets
+            .fetch_add(other.tickets.load(Ordering::Relaxed), Ordering::Relaxed);
+        if other.resumption_offered.load(Ordering::Relaxed) {
+            self.resumption_offered.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Take the values out of a cell whose request is done.
+    ///
+    /// Only a request whose future was leaked can still share the cell then;
+    /// it is copied from and left to that request.
+    fn into_values(mut cell: Arc<Captured>) -> CapturedValues {
+        let Some(unique) = Arc::get_mut(&mut cell) else {
+            return CapturedValues {
+                group: cell.group.get().copied(),
+                cipher: cell.cipher.get().copied(),
+                peer_certificates: cell.peer_certificates.get().cloned().unwrap_or_default(),
+                validation: cell.validation.get().cloned(),
+                cert_validation: cell.cert_validation.get().cloned(),
+                tickets: cell.tickets.load(Ordering::Relaxed),
+                resumption_offered: cell.resumption_offered.load(Ordering::Relaxed),
+            };
+        };
+        CapturedValues {
+            group: unique.group.take(),
+            cipher: unique.cipher.take(),
+            peer_certificates: unique.peer_certificates.take().unwrap_or_default(),
+            validation: unique.validation.take(),
+            cert_validation: unique.cert_validation.take(),
+            tickets: std::mem::take(unique.tickets.get_mut()),
+            resumption_offered: *unique.resumption_offered.get_mut(),
+        }
+    }
 }
 
-/// A `ClientSessionStore` that routes TLS handshake callbacks into whatever
-/// per-request `Captured` is currently active.
+/// Lock `mutex`, recovering it if a panic poisoned it: one panicking caller
+/// must not brick the client for everyone else. `what` names the lock for
+/// the log.
+pub(crate) fn lock<'a, T>(mutex: &'a Mutex<T>, what: &str) -> MutexGuard<'a, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        logging::poisoned(what);
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// A `ClientSessionStore` that routes TLS handshake callbacks into the
+/// `Captured` of the request whose handshake they come from, see
+/// [`capture`].
 ///
-/// The shared `active` pointer is set just before a request is sent and
-/// cleared immediately after – always outside any `.await` point.
+/// With an `inner` store it is a transparent decorator: every callback is
+/// recorded, then forwarded, and lookups are answered by the inner store.
+/// Without one, no session is ever handed out for resumption.
+#[derive(Debug)]
 struct CapturingSessionStore {
-    active: Arc<Mutex<Option<Arc<Mutex<Captured>>>>>,
+    inner: Option<Arc<dyn ClientSessionStore>>,
 }
 
-impl fmt::Debug for CapturingSessionStore {
+impl ClientSessionStore for CapturingSessionStore {
+    // Called after every successful handshake with the group that was used.
+    fn set_kx_hint(&self, server_name: ServerName<'static>, group: NamedGroup) {
+        capture::with("set_kx_hint", &server_name, |c| {
+            c.group.get_or_init(|| group);
+        });
+        if let Some(inner) = &self.inner {
+            inner.set_kx_hint(server_name, group);
+        }
+    }
+
+    // Called while building the ClientHello, so the handshake starts now.
+    fn kx_hint(&self, server_name: &ServerName<'_>) -> Option<NamedGroup> {
+        timings::handshake_started();
+        key_shares::hello_started();
+        capture::hello_started(server_name);
+        self.inner.as_ref()?.kx_hint(server_name)
+    }
+
+    // TLS 1.2 session – suite() is not public, so only count it.
+    fn set_tls12_session(&self, server_name: ServerName<'static>, value: Tls12ClientSessionValue) {
+        capture::with("set_tls12_session", &server_name, |c| {
+            c.tickets.fetch_add(1, Ordering::Relaxed);
+        });
+        if let Some(inner) = &self.inner {
+            inner.set_tls12_session(server_name, value);
+        }
+    }
+
+    // Resumed TLS 1.2 handshakes report neither group nor certificate, so
+    // note that one may be under way.
+    fn tls12_session(&self, server_name: &ServerName<'_>) -> Option<Tls12ClientSessionValue> {
+        let session = self.inner.as_ref()?.tls12_session(server_name)?;
+        capture::with("tls12_session", server_name, |c| {
+            c.resumption_offered.store(true, Ordering::Relaxed);
+        });
+        Some(session)
+    }
+
+    fn remove_tls12_session(&self, server_name: &ServerName<'static>) {
+        if let Some(inner) = &self.inner {
+            inner.remove_tls12_session(server_name);
+        }
+    }
+
+    // TLS 1.3 ticket – record cipher suite from the session value and
+    // count it. Only tickets read inside the request get here; hyper reads
+    // those of its connections on a task of its own.
+    fn insert_tls13_ticket(&self, server_name: ServerName<'static>, value: Tls13ClientSessionValue) {
+        let suite = value.suite().common.suite;
+        capture::with("insert_tls13_ticket", &server_name, |c| {
+            c.cipher.get_or_init(|| suite);
+            c.tickets.fetch_add(1, Ordering::Relaxed);
+        });
+        if let Some(inner) = &self.inner {
+            inner.insert_tls13_ticket(server_name, value);
+        }
+    }
+
+    fn take_tls13_ticket(&self, server_name: &ServerName<'static>) -> Option<Tls13ClientSessionValue> {
+        self.inner.as_ref()?.take_tls13_ticket(server_name)
+    }
+}
+
+/// Wraps the client's certificate verifier to record the chain each server
+/// presents, how it validates and the verifier's verdict.
+struct CapturingVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    validator: ChainValidator,
+    /// `None` when certificates are not verified at all.
+    policy: Option<CertPolicy>,
+}
+
+impl fmt::Debug for CapturingVerifier {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "CapturingSessionStore")
+        f.debug_struct("CapturingVerifier")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
     }
 }
 
-impl ClientSessionStore for CapturingSessionStore {
-    // Called after every successful handshake with the group that was used.
-    fn set_kx_hint(&self, _server_name: ServerName<'static>, group: NamedGroup) {
-        let slot = self.active.lock().unwrap().clone();
-        if let Some(captured) = slot {
-            captured.lock().unwrap().group = Some(format!("{:?}", group));
+impl ServerCertVerifier for CapturingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let result = self
+            .inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now);
+        let verdict = result.as_ref().map(|_| ()).map_err(CertError::of);
+        capture::with("verify_server_cert", server_name, |c| {
+            c.peer_certificates.get_or_init(|| {
+                std::iter::once(end_entity)
+                    .chain(intermediates)
+                    .map(|cert| cert.clone().into_owned())
+                    .collect()
+            });
+            c.validation.get_or_init(|| {
+                self.validator
+                    .validate(end_entity, intermediates, server_name, now)
+            });
+            if self.policy.is_some() {
+                c.cert_validation.get_or_init(|| verdict.clone());
+            }
+        });
+        match (result, self.policy) {
+            (Err(e), Some(CertPolicy::ReportOnly)) => verify::unenforced(server_name, &e),
+            (result, _) => result,
         }
     }
 
-    fn kx_hint(&self, _server_name: &ServerName<'_>) -> Option<NamedGroup> {
-        None
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+
+    fn requires_raw_public_keys(&self) -> bool {
+        self.inner.requires_raw_public_keys()
+    }
+
+    fn root_hint_subjects(&self) -> Option<&[DistinguishedName]> {
+        self.inner.root_hint_subjects()
+    }
+}
+
+/// Configures a [`TlsAwareClient`].
+#[derive(Default)]
+pub struct TlsAwareClientBuilder {
+    accept_invalid_certs: bool,
+    root_certificates: Vec<CertificateDer<'static>>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    proxy: Option<reqwest::Proxy>,
+    no_proxy: bool,
+    bind: bind::LocalBind,
+    kx_preference: KxPreference,
+    cert_policy: CertPolicy,
+    pins: HashMap<String, Vec<Sha256Digest>>,
+    pin_policy: PinPolicy,
+    alpn_protocols: Option<Vec<String>>,
+    session_store: Option<Arc<dyn ClientSessionStore>>,
+    client_cert_resolver: Option<Arc<dyn ResolvesClientCert>>,
+    #[cfg(feature = "dns")]
+    https_resolver: Option<Arc<dyn dns::HttpsResolver>>,
+    #[cfg(feature = "dns")]
+    ech: bool,
+    #[cfg(feature = "dns")]
+    dane: bool,
+    collect_timings: bool,
+    deep_inspect: bool,
+    require_pqc_capability: bool,
+    fallback_to_classical: bool,
+    provider: Option<Arc<CryptoProvider>>,
+    ignore_redirects: bool,
+    cookies: Option<Arc<CookieJar>>,
+}
+
+impl TlsAwareClientBuilder {
+    /// Accept any server certificate, including self-signed and expired ones.
+    ///
+    /// Handshake signatures are still verified, so the negotiated group and
+    /// cipher are unaffected. Only use this against servers you control.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Trust `cert` as a root in addition to the bundled web PKI roots.
+    pub fn add_root_certificate(mut self, cert: CertificateDer<'static>) -> Self {
+        self.root_certificates.push(cert);
+        self
+    }
+
+    /// Give up on HTTP requests that take longer than `timeout` in total.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Give up on establishing a TCP connection after `timeout`, for HTTP
+    /// requests and the connections the tracer drives itself alike.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Send HTTP requests through `proxy`. TLS is still negotiated end to end
+    /// with the target, so the captured metadata is the target's.
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self.no_proxy = false;
+        self
+    }
+
+    /// Connect to every host directly, dropping a [`proxy`](Self::proxy)
+    /// set before and ignoring the `HTTP_PROXY`, `HTTPS_PROXY` and
+    /// `ALL_PROXY` environment variables reqwest honours otherwise, e.g. so
+    /// that requests only reach the addresses a host was checked against.
+    pub fn no_proxy(mut self) -> Self {
+        self.proxy = None;
+        self.no_proxy = true;
+        self
+    }
+
+    /// Connect from `address`, e.g. to pick the uplink of a multi-homed
+    /// host or to trace over IPv6 only, for HTTP requests and the
+    /// connections the tracer drives itself alike. Hosts are then reached
+    /// over their addresses of the same family only, and fail to connect
+    /// without one; see [`TlsAwareClient::check_local_address`] for URLs
+    /// with an IP address. Both ends of a connection are reported in
+    /// [`TlsMetadata::local_addr`] and [`TlsMetadata::remote_addr`].
+    pub fn local_address(mut self, address: IpAddr) -> Self {
+        self.bind.address = Some(address);
+        self
+    }
+
+    /// Connect through the network interface `name`, e.g. `eth1`, with
+    /// `SO_BINDTODEVICE`, which usually needs `CAP_NET_RAW`.
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    pub fn interface(mut self, name: impl Into<String>) -> Self {
+        self.bind.interface = Some(name.into());
+        self
+    }
+
+    /// Reach `host` at `addrs` instead of the addresses it resolves to, for
+    /// HTTP requests and the connections the tracer drives itself alike,
+    /// e.g. to trace one server behind a load balancer, or to connect to
+    /// the addresses a host was checked against. Ports come from the URL.
+    pub fn resolve(mut self, host: &str, addrs: &[IpAddr]) -> Self {
+        Arc::make_mut(&mut self.bind.resolved).insert(host.to_ascii_lowercase(), addrs.to_vec());
+        self
+    }
+
+    /// Follow redirects, up to ten of them; enabled by default. Without,
+    /// a redirect is returned as the response, and only the host of the
+    /// URL requested is ever connected to.
+    pub fn follow_redirects(mut self, follow: bool) -> Self {
+        self.ignore_redirects = !follow;
+        self
+    }
+
+    /// Keep the cookies servers set and send them back, across the hops of
+    /// a redirect too, e.g. through a single sign-on that bounces to the
+    /// real host; disabled by default. The redirects are then followed by
+    /// the client itself, one request per hop, so that each hop's handshake
+    /// is captured on its own, and the metadata of the response is the
+    /// first hop's, as without cookies.
+    pub fn cookie_store(mut self, enable: bool) -> Self {
+        self.cookies = match enable {
+            true => Some(self.cookies.unwrap_or_default()),
+            false => None,
+        };
+        self
+    }
+
+    /// Keep cookies in `jar`, e.g. one [loaded](CookieJar::load) from a
+    /// file, to be saved once the client is done; implies
+    /// [`cookie_store`](Self::cookie_store).
+    pub fn cookie_jar(mut self, jar: Arc<CookieJar>) -> Self {
+        self.cookies = Some(jar);
+        self
+    }
+
+    /// Choose which key-exchange groups are offered. Defaults to
+    /// [`KxPreference::HybridPreferred`].
+    pub fn kx_preference(mut self, preference: KxPreference) -> Self {
+        self.kx_preference = preference;
+        self
+    }
+
+    /// Choose what a certificate that fails verification does to the
+    /// handshake. Defaults to [`CertPolicy::Enforce`]; ignored when
+    /// [`danger_accept_invalid_certs`](Self::danger_accept_invalid_certs)
+    /// skips verification altogether.
+    pub fn certificate_policy(mut self, policy: CertPolicy) -> Self {
+        self.cert_policy = policy;
+        self
+    }
+
+    /// Pin `host`'s public key: once the chain has been verified, the
+    /// SHA-256 of the end-entity certificate's SubjectPublicKeyInfo must be
+    /// one of `pins`. Calling it again for the same host adds pins, e.g. for
+    /// the next key of a planned rotation. The outcome is reported in
+    /// [`TlsMetadata::pin_matched`].
+    pub fn pin_spki(mut self, host: &str, pins: &[Sha256Digest]) -> Self {
+        self.pins
+            .entry(host.to_ascii_lowercase())
+            .or_default()
+            .extend_from_slice(pins);
+        self
+    }
+
+    /// Choose what a key that matches none of its host's pins does to the
+    /// handshake. Defaults to [`PinPolicy::Enforce`].
+    pub fn pin_policy(mut self, policy: PinPolicy) -> Self {
+        self.pin_policy = policy;
+        self
     }
 
-    // TLS 1.2 session – suite() is not public, so nothing to capture.
-    fn set_tls12_session(&self, _server_name: ServerName<'static>, _value: Tls12ClientSessionValue) {}
+    /// Offer `protocols` by ALPN, most preferred first, instead of no ALPN
+    /// extension at all, e.g. to see how a server treats protocols it does
+    /// not speak. Strict servers fail the handshake with
+    /// [`HandshakeFailureKind::AlpnMismatch`].
+    ///
+    /// Requests speak HTTP/1.1 only, since reqwest is built without HTTP/2
+    /// here, so [`build`](Self::build) rejects lists offering `h2` or `h3`
+    /// as well as names that are empty or longer than 255 bytes.
+    pub fn alpn_protocols(mut self, protocols: &[&str]) -> Self {
+        self.alpn_protocols = Some(protocols.iter().map(|p| p.to_string()).collect());
+        self
+    }
+
+    /// Keep using `store` for session resumption: the capturing store
+    /// records each callback and then forwards it, and resumption lookups
+    /// are answered by `store`. Without one, sessions are never resumed.
+    ///
+    /// Resumed handshakes show up as [`HandshakeOrigin::Resumed`], without
+    /// peer certificates. Handshakes the tracer drives itself never resume.
+    pub fn session_store(mut self, store: Arc<dyn ClientSessionStore>) -> Self {
+        self.session_store = Some(store);
+        self
+    }
 
-    fn tls12_session(&self, _server_name: &ServerName<'_>) -> Option<Tls12ClientSessionValue> {
-        None
+    /// Answer servers that ask for a client certificate with `resolver`.
+    /// Either way, whether a server asked is reported in
+    /// [`TlsMetadata::client_cert_requested`]; without a resolver no
+    /// certificate is presented, and servers that require one fail the
+    /// handshake.
+    pub fn client_cert_resolver(mut self, resolver: Arc<dyn ResolvesClientCert>) -> Self {
+        self.client_cert_resolver = Some(resolver);
+        self
     }
 
-    fn remove_tls12_session(&self, _server_name: &ServerName<'static>) {}
+    /// Look up the HTTPS DNS records of every request's host with
+    /// `resolver` before sending it, and report them in
+    /// [`TlsMetadata::https_records`]. Failed lookups are ignored.
+    #[cfg(feature = "dns")]
+    pub fn https_resolver(mut self, resolver: impl dns::HttpsResolver + 'static) -> Self {
+        self.https_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Encrypt the ClientHello for hosts whose HTTPS record carries an ECH
+    /// configuration. Only has an effect together with
+    /// [`https_resolver`](Self::https_resolver); such requests use a
+    /// connection of their own, configured for that host only.
+    #[cfg(feature = "dns")]
+    pub fn enable_ech(mut self, enable: bool) -> Self {
+        self.ech = enable;
+        self
+    }
+
+    /// Look up the TLSA records of every request's service, e.g.
+    /// `_443._tcp.example.com`, and check them against the certificate
+    /// chain the server presented, in [`TlsMetadata::dane`]. Also applies to
+    /// [STARTTLS probes](TlsAwareClient::probe_starttls). Only has an effect together with
+    /// [`https_resolver`](Self::https_resolver).
+    ///
+    /// DNSSEC is left to the resolver, whose AD bit is reported as
+    /// [`DaneResult::authenticated`](dane::DaneResult::authenticated).
+    /// Hosts that rely on DANE alone usually present certificates the web
+    /// PKI does not trust, so those need [`CertPolicy::ReportOnly`] or an
+    /// insecure client to get as far as the check.
+    #[cfg(feature = "dns")]
+    pub fn dane(mut self, enable: bool) -> Self {
+        self.dane = enable;
+        self
+    }
+
+    /// Measure how long each phase of every request takes – DNS, TCP
+    /// connect, TLS handshake and time to the response head – and report
+    /// it in [`TlsMetadata::timings`]. Host names are then resolved by the
+    /// tracer's own resolver, which works like reqwest's default one.
+    pub fn collect_timings(mut self, collect: bool) -> Self {
+        self.collect_timings = collect;
+        self
+    }
+
+    /// Parse the ServerHello of every handshake the tracer drives itself,
+    /// e.g. in scans of addresses or STARTTLS probes, and report its
+    /// extensions in [`TlsMetadata::server_extensions`]. Off by default,
+    /// since it keeps the handshake messages until the ServerHello is
+    /// complete.
+    pub fn deep_inspect(mut self, enable: bool) -> Self {
+        self.deep_inspect = enable;
+        self
+    }
+
+    /// Fail [`build`](Self::build) when the crypto provider has no hybrid or
+    /// post-quantum key-exchange group, instead of building a client that
+    /// can only ever negotiate classical key exchange.
+    /// [`ClientCapabilities::pqc_capable`] reports this either way.
+    pub fn require_pqc_capability(mut self, require: bool) -> Self {
+        self.require_pqc_capability = require;
+        self
+    }
+
+    /// Retry requests whose handshake failed while offering hybrid groups
+    /// with classical groups only, on a connection and rustls configuration
+    /// of their own, and report how that went in
+    /// [`TlsMetadata::fallback`]. Only failures that the groups offered or
+    /// the size of the ClientHello may explain are retried, and only
+    /// requests whose body can be cloned. Has no effect with
+    /// [`KxPreference::ClassicalOnly`].
+    pub fn fallback_to_classical(mut self, fallback: bool) -> Self {
+        self.fallback_to_classical = fallback;
+        self
+    }
+
+    /// Use `provider`, e.g. `rustls::crypto::ring::default_provider()`,
+    /// with its own groups and cipher suites, instead of the process-level
+    /// default. Clients with different providers can be used side by side,
+    /// and no default needs to be installed for a client with its own.
+    pub fn crypto_provider(mut self, provider: Arc<CryptoProvider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
 
-    // TLS 1.3 ticket – record cipher suite from the session value.
-    fn insert_tls13_ticket(&self, _server_name: ServerName<'static>, value: Tls13ClientSessionValue) {
-        let slot = self.active.lock().unwrap().clone();
-        if let Some(captured) = slot {
-            let mut c = captured.lock().unwrap();
-            if c.cipher.is_none() {
-                c.cipher = Some(format!("{:?}", value.suite().common.suite));
+    /// Build the client, configuring rustls once.
+    pub fn build(self) -> Result<TlsAwareClient, TraceError> {
+        let resumes_sessions = self.session_store.is_some();
+        let session_store = Arc::new(CapturingSessionStore {
+            inner: self.session_store,
+        });
+
+        let cert_resolver = Arc::new(client_auth::ObservingCertResolver {
+            inner: self.client_cert_resolver,
+        });
+
+        let provider = match self.provider {
+            Some(provider) => provider,
+            None => rustls::ClientConfig::builder().crypto_provider().clone(),
+        };
+        // The roots are also needed without verification, for the
+        // validation reports.
+        let mut root_store = rustls::RootCertStore::empty();
+        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let added = self.root_certificates.len();
+        for cert in self.root_certificates {
+            root_store
+                .add(cert)
+                .map_err(|e| TraceError::Config(format!("invalid root certificate: {}", e)))?;
+        }
+        let root_store = Arc::new(root_store);
+        let roots;
+        let cert_policy = (!self.accept_invalid_certs).then_some(self.cert_policy);
+        let verifier: Arc<dyn ServerCertVerifier> = if self.accept_invalid_certs {
+            roots = "none, certificates are not verified".to_string();
+            Arc::new(verify::AcceptAnyCertificate::new(provider.clone()))
+        } else {
+            roots = format!(
+                "webpki-roots ({}) + {} added",
+                webpki_roots::TLS_SERVER_ROOTS.len(),
+                added
+            );
+            WebPkiServerVerifier::builder_with_provider(root_store.clone(), provider.clone())
+                .build()
+                .map_err(|e| TraceError::Config(e.to_string()))?
+        };
+        let sha256 = fingerprint::sha256(&provider)
+            .ok_or_else(|| TraceError::Config("no SHA-256 in the crypto provider".to_string()))?;
+        let pins = Arc::new(Pins::new(self.pins, self.pin_policy, sha256));
+        let direct_verifier: Arc<dyn ServerCertVerifier> = match cert_policy {
+            Some(CertPolicy::ReportOnly) => Arc::new(verify::ReportOnly::new(verifier.clone())),
+            _ => verifier.clone(),
+        };
+        let direct_verifier = pins.wrap(direct_verifier);
+
+        let preferred = Arc::new(CryptoProvider {
+            kx_groups: self.kx_preference.apply(&provider.kx_groups),
+            ..(*provider).clone()
+        });
+        let capturing = pins.wrap(Arc::new(CapturingVerifier {
+            inner: verifier.clone(),
+            validator: ChainValidator::new(root_store, &provider),
+            policy: cert_policy,
+        }));
+        let alpn = match self.alpn_protocols {
+            Some(protocols) => alpn_protocols(protocols)?,
+            None => Vec::new(),
+        };
+        let proxy = self.proxy.is_some();
+        let http = HttpSettings {
+            timeout: self.timeout,
+            connect_timeout: self.connect_timeout,
+            proxy: self.proxy,
+            no_proxy: self.no_proxy,
+            bind: self.bind.clone(),
+            timings: self.collect_timings,
+            follow_redirects: !self.ignore_redirects && self.cookies.is_none(),
+            alpn,
+            connections: Arc::default(),
+        };
+        #[cfg(feature = "dns")]
+        let dns = self.https_resolver.map(|resolver| dns::DnsLookup {
+            resolver,
+            ech: self.ech.then(|| dns::EchSettings {
+                provider: preferred.clone(),
+                verifier: capturing.clone(),
+                session_store: session_store.clone(),
+                cert_resolver: cert_resolver.clone(),
+                http: http.clone(),
+            }),
+            dane: self.dane,
+        });
+
+        // Requests that override the key exchange go through a client
+        // offering the other preference's groups, as do classical-only
+        // retries, whose connections must not come from the pool of the
+        // attempt that failed, nor resume its sessions.
+        let alternate_preference = match self.kx_preference {
+            KxPreference::HybridPreferred => KxPreference::ClassicalOnly,
+            KxPreference::ClassicalOnly => KxPreference::HybridPreferred,
+        };
+        let alternate_provider = Arc::new(CryptoProvider {
+            kx_groups: alternate_preference.apply(&provider.kx_groups),
+            ..(*provider).clone()
+        });
+        let mut config = tls_config(
+            alternate_provider,
+            capturing.clone(),
+            cert_resolver.clone(),
+            rustls::DEFAULT_VERSIONS,
+        )?;
+        config.resumption = Resumption::store(Arc::new(CapturingSessionStore { inner: None }));
+        config.alpn_protocols = http.alpn.clone();
+        let alternate = http.client(config).map_err(TraceError::Http)?;
+
+        let mut tls_config = tls_config(
+            preferred,
+            capturing,
+            cert_resolver.clone(),
+            rustls::DEFAULT_VERSIONS,
+        )?;
+        tls_config.resumption = Resumption::store(session_store);
+        tls_config.alpn_protocols = http.alpn.clone();
+        let fingerprints = fingerprint::Fingerprints::of_config(&tls_config)?;
+        let capabilities = ClientCapabilities::new(&provider, self.kx_preference, fingerprints);
+        // Servers cannot be blamed for classical handshakes when the client
+        // never could have offered anything else, e.g. with ring.
+        if !capabilities.pqc_capable {
+            if self.require_pqc_capability {
+                return Err(TraceError::Config(format!(
+                    "the {} crypto provider has no post-quantum key-exchange group",
+                    capabilities.provider
+                )));
             }
+            logging::provider_without_pqc(&capabilities.provider);
         }
+        let client = http.client(tls_config).map_err(TraceError::Http)?;
+
+        Ok(TlsAwareClient {
+            client,
+            alternate,
+            fallback_to_classical: self.fallback_to_classical
+                && self.kx_preference != KxPreference::ClassicalOnly,
+            capabilities,
+            provider,
+            verifier,
+            direct_verifier,
+            cert_policy,
+            pins,
+            alpn: http.alpn.clone(),
+            kx_preference: self.kx_preference,
+            timeout: self.timeout,
+            connect_timeout: self.connect_timeout,
+            bind: self.bind,
+            cert_resolver,
+            roots,
+            proxy,
+            resumes_sessions,
+            collect_timings: self.collect_timings,
+            deep_inspect: self.deep_inspect,
+            connections: http.connections.clone(),
+            counters: Counters::default(),
+            cookies: self.cookies,
+            follow_redirects: !self.ignore_redirects,
+            #[cfg(feature = "dns")]
+            dns,
+        })
     }
+}
+
+/// The `reqwest` settings of a client, kept for the per-host clients that
+/// ECH needs.
+#[derive(Clone)]
+struct HttpSettings {
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    proxy: Option<reqwest::Proxy>,
+    no_proxy: bool,
+    bind: bind::LocalBind,
+    timings: bool,
+    follow_redirects: bool,
+    /// The ALPN protocols offered; empty for none.
+    alpn: Vec<Vec<u8>>,
+    /// Shared by all of a client's `reqwest` clients.
+    connections: Arc<Connections>,
+}
 
-    fn take_tls13_ticket(&self, _server_name: &ServerName<'static>) -> Option<Tls13ClientSessionValue> {
-        None
+impl HttpSettings {
+    fn client(&self, tls_config: rustls::ClientConfig) -> reqwest::Result<reqwest::Client> {
+        let mut client = reqwest::Client::builder().use_preconfigured_tls(tls_config);
+        if let Some(timeout) = self.timeout {
+            client = client.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+        if let Some(proxy) = self.proxy.clone() {
+            client = client.proxy(proxy);
+        }
+        if self.no_proxy {
+            client = client.no_proxy();
+        }
+        if !self.follow_redirects {
+            client = client.redirect(reqwest::redirect::Policy::none());
+        }
+        // reqwest's overrides bypass the resolver, which looks the hosts up
+        // where it is installed, keeping the addresses of the local
+        // address's family.
+        if !self.timings && self.bind.address.is_none() {
+            for (host, ips) in self.bind.resolved.iter() {
+                let addrs: Vec<SocketAddr> =
+                    ips.iter().map(|ip| SocketAddr::new(*ip, 0)).collect();
+                client = client.resolve_to_addrs(host, &addrs);
+            }
+        }
+        if let Some(address) = self.bind.address {
+            client = client.local_address(address);
+        }
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        if let Some(interface) = &self.bind.interface {
+            client = client.interface(interface);
+        }
+        if self.timings {
+            client = client
+                .dns_resolver(Arc::new(timings::TimingResolver {
+                    bind: self.bind.clone(),
+                }))
+                .connector_layer(timings::TimingLayer);
+        } else if self.bind.address.is_some() {
+            client = client.dns_resolver(Arc::new(self.bind.clone()));
+        }
+        client
+            .connector_layer(connections::ConnectionLayer {
+                connections: self.connections.clone(),
+            })
+            .build()
+    }
+}
+
+/// Check an ALPN list against what ALPN allows and what the client's
+/// requests can speak.
+fn alpn_protocols(protocols: Vec<String>) -> Result<Vec<Vec<u8>>, TraceError> {
+    for protocol in &protocols {
+        if protocol.is_empty() || protocol.len() > 255 {
+            return Err(TraceError::Config(format!(
+                "invalid ALPN protocol name {:?}: must be 1 to 255 bytes",
+                protocol
+            )));
+        }
+        if protocol == "h2" || protocol == "h3" {
+            return Err(TraceError::Config(format!(
+                "ALPN protocol {} offered, but requests speak HTTP/1.1 only",
+                protocol
+            )));
+        }
     }
+    Ok(protocols.into_iter().map(String::into_bytes).collect())
+}
+
+fn tls_config(
+    provider: Arc<CryptoProvider>,
+    verifier: Arc<dyn ServerCertVerifier>,
+    cert_resolver: Arc<dyn ResolvesClientCert>,
+    versions: &[&'static SupportedProtocolVersion],
+) -> Result<rustls::ClientConfig, TraceError> {
+    let provider = capture::observed(&key_shares::observed(&provider));
+    Ok(rustls::ClientConfig::builder_with_provider(provider)
+        .with_protocol_versions(versions)
+        .map_err(TraceError::Tls)?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier)
+        .with_client_cert_resolver(cert_resolver))
 }
 
 /// A reusable HTTP client that captures TLS handshake metadata for every request.
@@ -80,65 +1556,611 @@ impl ClientSessionStore for CapturingSessionStore {
 /// across an await point.
 pub struct TlsAwareClient {
     client: reqwest::Client,
-    active_capture: Arc<Mutex<Option<Arc<Mutex<Captured>>>>>,
+    /// The client offering the groups of the other [`KxPreference`], for
+    /// requests that [override](TracedRequestBuilder::key_exchange) the key
+    /// exchange and for classical-only retries.
+    alternate: reqwest::Client,
+    /// Whether failed requests are retried with classical groups only.
+    fallback_to_classical: bool,
+    capabilities: ClientCapabilities,
+    provider: Arc<CryptoProvider>,
+    verifier: Arc<dyn ServerCertVerifier>,
+    /// `verifier`, or under [`CertPolicy::ReportOnly`] a wrapper that
+    /// accepts what it rejects, for the handshakes the tracer drives itself.
+    direct_verifier: Arc<dyn ServerCertVerifier>,
+    /// `None` when certificates are not verified.
+    cert_policy: Option<CertPolicy>,
+    pins: Arc<Pins>,
+    /// The ALPN protocols offered; empty for none.
+    alpn: Vec<Vec<u8>>,
+    kx_preference: KxPreference,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    /// Where connections are made from.
+    bind: bind::LocalBind,
+    /// Answers requests for a client certificate and notes them.
+    cert_resolver: Arc<client_auth::ObservingCertResolver>,
+    /// Where the trusted roots come from, for `Debug`.
+    roots: String,
+    /// Whether a proxy is configured; its URL may carry credentials.
+    proxy: bool,
+    /// Whether a caller's session store backs resumption.
+    resumes_sessions: bool,
+    collect_timings: bool,
+    deep_inspect: bool,
+    connections: Arc<Connections>,
+    counters: Counters,
+    /// With a jar, redirects are followed here rather than by reqwest.
+    cookies: Option<Arc<CookieJar>>,
+    follow_redirects: bool,
+    #[cfg(feature = "dns")]
+    dns: Option<dns::DnsLookup>,
+}
+
+/// Running totals of [`TlsAwareClient::execute`] calls, shown by `Debug`.
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    in_flight: AtomicU64,
+    handshakes: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Counters {
+    fn bump(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A redacted summary of the configuration plus the request counters; proxy
+/// URLs and other credentials never appear.
+impl fmt::Debug for TlsAwareClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let versions: Vec<_> = rustls::DEFAULT_VERSIONS.iter().map(|v| v.version).collect();
+        let groups: Vec<_> = self
+            .kx_preference
+            .apply(&self.provider.kx_groups)
+            .iter()
+            .map(|g| g.name())
+            .collect();
+        let alpn: Vec<_> = self
+            .alpn
+            .iter()
+            .map(|p| String::from_utf8_lossy(p))
+            .collect();
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let mut debug = f.debug_struct("TlsAwareClient");
+        debug
+            .field("roots", &self.roots)
+            .field("cert_policy", &self.cert_policy)
+            .field("pins", &self.pins)
+            .field("alpn", &alpn)
+            .field("fallback_to_classical", &self.fallback_to_classical)
+            .field("kx_preference", &self.kx_preference)
+            .field("kx_groups", &groups)
+            .field("protocol_versions", &versions)
+            .field("timeout", &self.timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("proxy", &self.proxy)
+            .field("local_address", &self.bind.address)
+            .field(
+                "resumption",
+                &match self.resumes_sessions {
+                    true => "capture, then the given session store",
+                    false => "capture only, tickets are never reused",
+                },
+            )
+            .field("timings", &self.collect_timings)
+            .field("deep_inspect", &self.deep_inspect)
+            .field("cookies", &self.cookies.as_ref().map(|jar| jar.len()));
+        #[cfg(feature = "dns")]
+        debug
+            .field("https_records", &self.dns.is_some())
+            .field("ech", &self.dns.as_ref().is_some_and(|dns| dns.ech.is_some()));
+        debug
+            .field("requests", &load(&self.counters.requests))
+            .field("handshakes", &load(&self.counters.handshakes))
+            .field("failures", &load(&self.counters.failures))
+            .field("in_flight", &load(&self.counters.in_flight))
+            .field("known_connections", &self.connections.len())
+            .finish()
+    }
 }
 
 impl TlsAwareClient {
-    /// Build the client, configuring rustls once.
+    /// Build a client with the default configuration.
     pub fn new() -> Self {
-        let active_capture: Arc<Mutex<Option<Arc<Mutex<Captured>>>>> =
-            Arc::new(Mutex::new(None));
+        Self::builder().build().expect("failed to build TLS-aware client")
+    }
 
-        let session_store = Arc::new(CapturingSessionStore {
-            active: active_capture.clone(),
-        });
+    /// Start configuring a client.
+    pub fn builder() -> TlsAwareClientBuilder {
+        TlsAwareClientBuilder::default()
+    }
 
-        let mut root_store = rustls::RootCertStore::empty();
-        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    /// The crypto provider, groups, cipher suites and versions this client
+    /// offers, as assembled when it was built.
+    pub fn client_capabilities(&self) -> ClientCapabilities {
+        self.capabilities.clone()
+    }
 
-        let mut tls_config = rustls::ClientConfig::builder()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
+    /// The [`ReportMeta`] block for results produced with this client.
+    pub fn report_meta(&self) -> ReportMeta {
+        ReportMeta::new(&self.capabilities)
+    }
 
-        tls_config.resumption = Resumption::store(session_store);
+    /// The jar the client keeps cookies in, if it was built with one, e.g.
+    /// to [save](CookieJar::save) it after a run.
+    pub fn cookie_jar(&self) -> Option<&Arc<CookieJar>> {
+        self.cookies.as_ref()
+    }
 
-        let client = reqwest::Client::builder()
-            .use_preconfigured_tls(tls_config)
-            .build()
-            .expect("failed to build reqwest client");
+    /// The client's TLS configuration for connections the tracer drives
+    /// itself, where the metadata is read straight from the connection.
+    ///
+    /// `kx_groups` replaces the groups selected by the client's
+    /// [`KxPreference`] when given. Resumption is disabled so that these handshakes are always full ones
+    /// and never feed the capturing store used by [`execute`](Self::execute).
+    pub(crate) fn direct_tls_config(
+        &self,
+        kx_groups: Option<Vec<&'static dyn SupportedKxGroup>>,
+    ) -> Result<rustls::ClientConfig, TraceError> {
+        self.direct_tls_config_with(kx_groups, rustls::DEFAULT_VERSIONS)
+    }
 
-        Self { client, active_capture }
+    /// Like [`direct_tls_config`](Self::direct_tls_config), offering only
+    /// the TLS `versions` given.
+    pub(crate) fn direct_tls_config_with(
+        &self,
+        kx_groups: Option<Vec<&'static dyn SupportedKxGroup>>,
+        versions: &[&'static SupportedProtocolVersion],
+    ) -> Result<rustls::ClientConfig, TraceError> {
+        let provider = match kx_groups {
+            Some(kx_groups) => Arc::new(CryptoProvider {
+                kx_groups,
+                ..(*self.provider).clone()
+            }),
+            None => Arc::new(CryptoProvider {
+                kx_groups: self.kx_preference.apply(&self.provider.kx_groups),
+                ..(*self.provider).clone()
+            }),
+        };
+        let mut config = tls_config(
+            provider,
+            self.direct_verifier.clone(),
+            self.cert_resolver.clone(),
+            versions,
+        )?;
+        config.resumption = Resumption::disabled();
+        config.alpn_protocols = self.alpn.clone();
+        Ok(config)
+    }
+
+    /// The verdict on the chain the server presented in a handshake the
+    /// tracer drove itself. rustls does not hand it back, so under
+    /// [`CertPolicy::ReportOnly`] the chain is verified once more; under
+    /// [`CertPolicy::Enforce`] a completed handshake has passed.
+    pub(crate) fn cert_validation(
+        &self,
+        server_name: &ServerName<'_>,
+        conn: &rustls::ClientConnection,
+    ) -> Option<Result<(), CertError>> {
+        let (end_entity, intermediates) = conn.peer_certificates()?.split_first()?;
+        match self.cert_policy? {
+            CertPolicy::Enforce => Some(Ok(())),
+            CertPolicy::ReportOnly => Some(
+                self.verifier
+                    .verify_server_cert(
+                        end_entity,
+                        intermediates,
+                        server_name,
+                        &[],
+                        UnixTime::now(),
+                    )
+                    .map(|_| ())
+                    .map_err(|e| CertError::of(&e)),
+            ),
+        }
+    }
+
+    /// Whether the key presented in a handshake the tracer drove itself
+    /// matched the host's pins.
+    pub(crate) fn pin_matched(
+        &self,
+        server_name: &ServerName<'_>,
+        conn: &rustls::ClientConnection,
+    ) -> Option<bool> {
+        self.pins
+            .matched(&server_name.to_str(), conn.peer_certificates()?)
+    }
+
+    /// Every key-exchange group of the client's crypto provider, in order,
+    /// regardless of its [`KxPreference`].
+    pub(crate) fn kx_groups(&self) -> &[&'static dyn SupportedKxGroup] {
+        &self.provider.kx_groups
+    }
+
+    /// Fail when `url`'s host is an IP address of the other family than the
+    /// [local address](TlsAwareClientBuilder::local_address). reqwest
+    /// connects to such an address from an unbound socket instead, so
+    /// [`execute`](Self::execute) does not catch it; a
+    /// [request](Self::request) checks before it is sent. Host names are
+    /// checked when they are resolved.
+    pub fn check_local_address(&self, url: &reqwest::Url) -> Result<(), TraceError> {
+        let host = url.host_str().unwrap_or_default().trim_matches(['[', ']']);
+        match host.parse::<IpAddr>() {
+            Ok(addr) => Ok(self.bind.check(host, addr)?),
+            Err(_) => Ok(()),
+        }
     }
 
     /// Execute any `reqwest::Request` and return the response together with
     /// the negotiated TLS key-exchange group and cipher suite.
     ///
     /// Accepts GET / POST / PUT / PATCH / DELETE / … without special handling.
+    ///
+    /// The metadata is also inserted into the response's extensions, so
+    /// that code handed only the inner `reqwest::Response` can recover it
+    /// with `response.extensions().get::<TlsMetadata>()`. Only responses
+    /// produced by this client carry it.
+    ///
+    /// A client that [falls back](TlsAwareClientBuilder::fallback_to_classical)
+    /// returns the classical-only retry's error when that fails too.
     pub async fn execute(&self, request: reqwest::Request) -> Result<TlsResponse, reqwest::Error> {
-        // 1. Create a fresh capture context for this request.
-        let captured = Arc::new(Mutex::new(Captured::default()));
+        self.execute_with_fallback(request).await.0
+    }
 
-        // 2. Activate it (lock scope ends before .await).
-        {
-            let mut active = self.active_capture.lock().unwrap();
-            *active = Some(captured.clone());
+    /// Like [`execute`](Self::execute), also returning the
+    /// [`FallbackOutcome`] of a failed request, which has no metadata to
+    /// carry it. `None` for clients that do not fall back, and for failures
+    /// that were not retried.
+    pub async fn execute_with_fallback(
+        &self,
+        request: reqwest::Request,
+    ) -> (Result<TlsResponse, reqwest::Error>, Option<FallbackOutcome>) {
+        let (result, outcome) = self.execute_labelled(request, &BTreeMap::new()).await;
+        (result.map_err(|e| e.error), outcome)
+    }
+
+    /// Like [`execute`](Self::execute), also carrying `labels` into the
+    /// metadata and the `log` records of the request, so that a handshake
+    /// can be tied back to the operation that caused it. A classical-only
+    /// retry carries them too, and redirects are part of the one request.
+    pub async fn execute_with_labels(
+        &self,
+        request: reqwest::Request,
+        labels: BTreeMap<String, String>,
+    ) -> Result<TlsResponse, reqwest::Error> {
+        self.execute_labelled(request, &labels).await.0.map_err(|e| e.error)
+    }
+
+    /// [`execute_with_fallback`](Self::execute_with_fallback) with `labels`.
+    pub(crate) async fn execute_labelled(
+        &self,
+        request: reqwest::Request,
+        labels: &BTreeMap<String, String>,
+    ) -> (Result<TlsResponse, SendError>, Option<FallbackOutcome>) {
+        if !self.fallback_to_classical {
+            return (self.send(request, self.kx_preference, None, labels).await, None);
         }
+        let retry = request.try_clone();
+        let server = request.url().host_str().unwrap_or_default().to_string();
+        let first = self.send(request, self.kx_preference, None, labels).await;
+        let (original_error, retry) = match first {
+            Ok(response) => return (Ok(response), Some(FallbackOutcome::PqcOk)),
+            Err(e) => match (HandshakeFailureKind::diagnose(&e.error), retry) {
+                (Some(kind), Some(retry)) if fallback::worth_retrying(&kind) => (kind, retry),
+                _ => return (Err(e), None),
+            },
+        };
+        logging::classical_fallback(&server, &original_error);
+        match self.send(retry, KxPreference::ClassicalOnly, Some(original_error), labels).await {
+            Ok(response) => {
+                let outcome = response.metadata.fallback.clone();
+                (Ok(response), outcome)
+            }
+            Err(e) => (Err(e), Some(FallbackOutcome::BothFailed)),
+        }
+    }
 
-        // 3. Send the request through the shared, pooled client.
-        let response = self.client.execute(request).await?;
+    /// Send `request` offering the groups of `preference`; for a
+    /// classical-only retry, `original_error` is why the first attempt
+    /// failed. With a [cookie jar](TlsAwareClientBuilder::cookie_store),
+    /// redirects are followed here, each hop with the cookies that match it.
+    pub(crate) async fn send(
+        &self,
+        request: reqwest::Request,
+        preference: KxPreference,
+        original_error: Option<HandshakeFailureKind>,
+        labels: &BTreeMap<String, String>,
+    ) -> Result<TlsResponse, SendError> {
+        let Some(jar) = &self.cookies else {
+            return self.send_once(request, preference, original_error, labels).await;
+        };
+        let mut request = request;
+        let mut first: Option<TlsMetadata> = None;
+        for hop in 0.. {
+            if hop > 0 {
+                jar.add_to(&mut request);
+            } else if let Some(cookies) = jar.cookie_header(request.url()) {
+                // The caller's own cookies go along on the first hop.
+                let own = request.headers().get(reqwest::header::COOKIE);
+                let cookies = match own.and_then(|own| own.to_str().ok()) {
+                    Some(own) => format!("{}; {}", own, cookies),
+                    None => cookies,
+                };
+                if let Ok(value) = reqwest::header::HeaderValue::from_str(&cookies) {
+                    request.headers_mut().insert(reqwest::header::COOKIE, value);
+                }
+            }
+            let url = request.url().clone();
+            let method = request.method().clone();
+            let headers = request.headers().clone();
+            let copy = request.try_clone();
+            let mut response =
+                self.send_once(request, preference, original_error.clone(), labels).await?;
+            jar.store(&url, response.response.headers());
+            let first = first.get_or_insert_with(|| response.metadata.clone());
+            let next = (self.follow_redirects && hop < cookies::MAX_REDIRECTS)
+                .then(|| {
+                    let status = response.response.status();
+                    let location = response.response.headers();
+                    cookies::next_hop(&url, status, location, &method, &headers, copy)
+                })
+                .flatten();
+            match next {
+                Some(next) => request = next,
+                None => {
+                    if hop > 0 {
+                        response.response.extensions_mut().insert(first.clone());
+                        #[allow(deprecated)]
+                        {
+                            response.group = first.group.clone();
+                            response.cipher = first.cipher.clone();
+                        }
+                        response.metadata = first.clone();
+                    }
+                    return Ok(response);
+                }
+            }
+        }
+        unreachable!("the hops end with a response or an error")
+    }
+
+    /// Send `request` once, as [`send`](Self::send) does every hop.
+    async fn send_once(
+        &self,
+        request: reqwest::Request,
+        preference: KxPreference,
+        original_error: Option<HandshakeFailureKind>,
+        labels: &BTreeMap<String, String>,
+    ) -> Result<TlsResponse, SendError> {
+        let alternate = (preference != self.kx_preference).then_some(&self.alternate);
 
-        // 4. Deactivate capture (lock scope ends immediately).
+        // 0. Look up the host's HTTPS records, which may bring an ECH config;
+        // requests through the alternate client do without.
+        #[cfg(feature = "dns")]
+        let (https_records, ech_client, tlsa) = match &self.dns {
+            Some(dns) => {
+                let prepared = dns.prepare(request.url()).await;
+                (prepared.https_records, prepared.ech_client, prepared.tlsa)
+            }
+            None => (Vec::new(), None, None),
+        };
+        #[cfg(feature = "dns")]
+        let ech_client = ech_client.filter(|_| alternate.is_none());
+        #[cfg(feature = "dns")]
+        let client = alternate.or(ech_client.as_ref()).unwrap_or(&self.client);
+        #[cfg(not(feature = "dns"))]
+        let client = alternate.unwrap_or(&self.client);
+
+        Counters::bump(&self.counters.requests);
+        let in_flight = InFlight::enter(&self.counters.in_flight);
+
+        // 1. An empty capture cell for this request.
+        let captured = Arc::<Captured>::default();
+
+        // 2. Send the request through the shared, pooled client, with the
+        // cell installed for the handshakes it drives.
+        // Match `ServerName::to_str()`: IPv6 literals come without brackets.
+        let host = request.url().host_str().unwrap_or_default();
+        let server = host.trim_matches(['[', ']']).to_ascii_lowercase();
+        let sending = capture::scope(&server, captured.clone(), async {
+            if self.collect_timings {
+                let (result, timings) = timings::timed(client.execute(request)).await;
+                (result, Some(timings))
+            } else {
+                (client.execute(request).await, None)
+            }
+        });
+        let ((result, timings), key_shares) = key_shares::observe(sending).await;
+
+        // 3. The request is done with the cell, also on error. A connection
+        // it started, or found in the pool fresh from another request that
+        // started it, brings the handshake's own capture.
+        drop(in_flight);
+        let connection = match &result {
+            Ok(response) => response
+                .extensions()
+                .get::<HttpInfo>()
+                .map(|info| (info.local_addr(), info.remote_addr())),
+            Err(_) => captured.connection.get().copied(),
+        };
+        let mut state = Captured::into_values(captured);
+        let mut key_shares = key_shares;
+        let mut handshake_bytes = None;
+        if !state.handshake_seen()
+            && let Some(handshake) =
+                connection.and_then(|connection| self.connections.take_handshake(&connection))
         {
-            let mut active = self.active_capture.lock().unwrap();
-            *active = None;
+            state = Captured::into_values(handshake.captured);
+            key_shares = handshake.key_shares;
+            handshake_bytes = handshake.bytes;
         }
 
-        // 5. Read captured values.
-        let state = captured.lock().unwrap();
-        Ok(TlsResponse {
-            response,
-            group: state.group.clone(),
-            cipher: state.cipher.clone(),
-        })
+        // 4. Turn the captured values into metadata. Without any callback,
+        // the final URL tells a plaintext or redirected request from a
+        // pooled connection.
+        let handshake_seen = state.handshake_seen();
+        let mut capture_status = state.status();
+        if !handshake_seen && let Ok(response) = &result {
+            let url = response.url();
+            let host = url.host_str().unwrap_or_default().trim_matches(['[', ']']);
+            if url.scheme() == "http" {
+                capture_status = CaptureStatus::Plaintext;
+            } else if !host.eq_ignore_ascii_case(&server) {
+                capture_status = CaptureStatus::CallbackMissed;
+            }
+        }
+        let pin_matched = self.pins.matched(&server, &state.peer_certificates);
+        let resumed = state.origin() == HandshakeOrigin::Resumed;
+        let tickets_issued = state.tickets_issued();
+        let mut metadata = TlsMetadata {
+            group: state.group_name(),
+            cipher: state.cipher_name(),
+            origin: match capture_status {
+                CaptureStatus::Plaintext | CaptureStatus::CallbackMissed => None,
+                _ => Some(state.origin()),
+            },
+            local_addr: None,
+            remote_addr: None,
+            idn: idn::IdnName::of(&server),
+            capture_status,
+            peer_certificates: state.peer_certificates,
+            handshake_duration: None,
+            hrr_penalty: key_shares.hrr_penalty(),
+            handshake_bytes_sent: handshake_bytes.map(|(sent, _)| sent),
+            handshake_bytes_received: handshake_bytes.map(|(_, received)| received),
+            alerts_received: Vec::new(),
+            server_extensions: None,
+            #[cfg(feature = "dns")]
+            https_records,
+            #[cfg(feature = "dns")]
+            ech_offered: ech_client.is_some(),
+            #[cfg(feature = "dns")]
+            dane: None,
+            timings,
+            validation: state.validation,
+            cert_validation: state.cert_validation,
+            pin_matched,
+            alpn: None,
+            tickets_issued,
+            resumption_kx: key_shares.resumption_kx(resumed),
+            client_cert_requested: handshake_seen.then(|| key_shares.client_cert_requested()),
+            client_cert_authorities: key_shares.client_cert_authorities(),
+            fallback: match original_error {
+                Some(original_error) => {
+                    Some(FallbackOutcome::ClassicalOnlyAfterFailure { original_error })
+                }
+                None if self.fallback_to_classical && alternate.is_none() => {
+                    Some(FallbackOutcome::PqcOk)
+                }
+                None => None,
+            },
+            labels: labels.clone(),
+        };
+        if handshake_seen {
+            Counters::bump(&self.counters.handshakes);
+            let offered_pqc = preference == KxPreference::HybridPreferred;
+            logging::handshake(&server, &metadata, offered_pqc);
+        }
+        let mut response = match result {
+            Ok(response) => response,
+            Err(error) => {
+                Counters::bump(&self.counters.failures);
+                logging::failure_labelled(&server, labels, &error::error_chain(&error));
+                // A request that failed after its handshake, e.g. on a
+                // connection reset while the body was uploaded, keeps it.
+                let metadata = handshake_seen.then(|| Box::new(metadata));
+                return Err(SendError { error, metadata });
+            }
+        };
+
+        // 5. Remember the handshake for the connection, or report the
+        // connection's handshake for a request that had none of its own.
+        if let Some(connection) = connection {
+            metadata.local_addr = Some(connection.0);
+            metadata.remote_addr = Some(connection.1);
+            if handshake_seen {
+                self.connections.record(connection, &metadata);
+            } else if capture_status == CaptureStatus::ReusedConnection
+                && let Some(shared) = self.connections.get(&connection)
+            {
+                metadata = TlsMetadata {
+                    origin: Some(HandshakeOrigin::SharedConnection),
+                    handshake_duration: None,
+                    hrr_penalty: None,
+                    handshake_bytes_sent: None,
+                    handshake_bytes_received: None,
+                    tickets_issued: None,
+                    #[cfg(feature = "dns")]
+                    https_records: metadata.https_records,
+                    #[cfg(feature = "dns")]
+                    ech_offered: metadata.ech_offered,
+                    timings: metadata.timings,
+                    fallback: metadata.fallback,
+                    labels: metadata.labels,
+                    ..shared
+                };
+            }
+        }
+        // The chain to check the TLSA records against may be the shared
+        // connection's.
+        #[cfg(feature = "dns")]
+        if let Some(tlsa) = tlsa {
+            metadata.dane = Some(dane::check(tlsa, &metadata));
+        }
+
+        // 6. Let code that only sees the `reqwest::Response` find it too.
+        response.extensions_mut().insert(metadata.clone());
+        Ok(TlsResponse::new(response, metadata))
+    }
+
+    /// Log a completed handshake, flagging a classical group negotiated
+    /// despite hybrids being offered.
+    pub(crate) fn log_handshake(&self, server: &str, metadata: &TlsMetadata) {
+        logging::handshake(server, metadata, self.kx_preference == KxPreference::HybridPreferred);
+    }
+}
+
+/// A request that failed in [`TlsAwareClient::send`], with the metadata of
+/// its handshake if one completed before the failure.
+pub(crate) struct SendError {
+    pub(crate) error: reqwest::Error,
+    pub(crate) metadata: Option<Box<TlsMetadata>>,
+}
+
+impl From<SendError> for TraceError {
+    fn from(e: SendError) -> Self {
+        match e.metadata {
+            Some(metadata) => TraceError::RequestFailed {
+                error: e.error,
+                metadata,
+            },
+            None => TraceError::Http(e.error),
+        }
+    }
+}
+
+/// Counts a request as in flight until it is dropped, which also covers
+/// requests whose future is cancelled.
+struct InFlight<'a>(&'a AtomicU64);
+
+impl<'a> InFlight<'a> {
+    fn enter(counter: &'a AtomicU64) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Default for TlsAwareClient {
+    fn default() -> Self {
+        Self::new()
     }
 }
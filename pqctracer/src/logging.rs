@@ -0,0 +1,140 @@
+//! `log` records for handshake outcomes, behind the `log` feature.
+//!
+//! Every message is an event name followed by `key=value` pairs, e.g.
+//! `handshake server=example.com version=TLS1.3 group=X25519MLKEM768 ...`,
+//! and is logged with the `pqctracer` target. Without the feature these are
+//! no-ops.
+#![cfg_attr(not(feature = "log"), allow(unused_variables))]
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use rustls_pki_types::ServerName;
+
+use crate::TlsMetadata;
+#[cfg(feature = "log")]
+use crate::{HandshakeOrigin, KeyExchangeKind};
+
+/// A handshake completed; warns as well when a client that offered
+/// quantum-resistant groups ended up with a classical one.
+pub(crate) fn handshake(server: &str, metadata: &TlsMetadata, offered_pqc: bool) {
+    #[cfg(feature = "log")]
+    {
+        let duration = match metadata.handshake_duration {
+            Some(duration) => format!("{}ms", duration.as_millis()),
+            None => "n/a".to_string(),
+        };
+        log::info!(
+            target: "pqctracer",
+            "handshake server={} version={} group={} cipher={} duration={} origin={}{}",
+            server,
+            metadata.protocol_version().unwrap_or("n/a"),
+            metadata.group.as_deref().unwrap_or("n/a"),
+            metadata.cipher.as_deref().unwrap_or("n/a"),
+            duration,
+            metadata.origin.unwrap_or(HandshakeOrigin::Fresh),
+            label_fields(&metadata.labels),
+        );
+        if offered_pqc && metadata.key_exchange_kind() == Some(KeyExchangeKind::Classical) {
+            log::warn!(
+                target: "pqctracer",
+                "downgrade server={} group={}{}",
+                server,
+                metadata.group.as_deref().unwrap_or("n/a"),
+                label_fields(&metadata.labels),
+            );
+        }
+    }
+}
+
+/// A request's labels as ` label.tenant=acme` pairs, prefixed so that they
+/// cannot be mistaken for the event's own keys.
+#[cfg(feature = "log")]
+fn label_fields(labels: &BTreeMap<String, String>) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| format!(" label.{}={}", key, value))
+        .collect()
+}
+
+/// The crypto provider offers no hybrid or post-quantum group; warned once
+/// per process rather than for every client built.
+pub(crate) fn provider_without_pqc(provider: &str) {
+    #[cfg(feature = "log")]
+    {
+        static WARNED: std::sync::Once = std::sync::Once::new();
+        WARNED.call_once(|| {
+            log::warn!(target: "pqctracer", "provider-without-pqc provider={}", provider);
+        });
+    }
+}
+
+/// A request or handshake failed.
+pub(crate) fn failure(server: &str, error: &dyn Display) {
+    failure_labelled(server, &BTreeMap::new(), error);
+}
+
+/// A request sent with `labels` failed.
+pub(crate) fn failure_labelled(
+    server: &str,
+    labels: &BTreeMap<String, String>,
+    error: &dyn Display,
+) {
+    #[cfg(feature = "log")]
+    log::warn!(
+        target: "pqctracer",
+        "failure server={}{} error={}",
+        server,
+        label_fields(labels),
+        error,
+    );
+}
+
+/// A TLS callback inside a request arrived for another server than the
+/// request's.
+pub(crate) fn capture_missed(callback: &str, server: &ServerName<'_>) {
+    #[cfg(feature = "log")]
+    log::warn!(
+        target: "pqctracer",
+        "capture-missed callback={} server={}",
+        callback,
+        server.to_str(),
+    );
+}
+
+/// A certificate was accepted without verification.
+pub(crate) fn verification_skipped(server: &ServerName<'_>) {
+    #[cfg(feature = "log")]
+    log::warn!(target: "pqctracer", "verification-skipped server={}", server.to_str());
+}
+
+/// A certificate failed verification and was accepted anyway, as
+/// [`CertPolicy::ReportOnly`](crate::CertPolicy::ReportOnly) asks.
+pub(crate) fn verification_unenforced(server: &ServerName<'_>, error: &rustls::Error) {
+    #[cfg(feature = "log")]
+    log::warn!(
+        target: "pqctracer",
+        "verification-unenforced server={} error={}",
+        server.to_str(),
+        error,
+    );
+}
+
+/// A handshake offering hybrid groups failed and is retried with classical
+/// ones only.
+pub(crate) fn classical_fallback(server: &str, error: &dyn Display) {
+    #[cfg(feature = "log")]
+    log::warn!(target: "pqctracer", "classical-fallback server={} error={}", server, error);
+}
+
+/// A server's key matched none of the pins for its host.
+pub(crate) fn pin_mismatch(server: &str, actual: &dyn Display) {
+    #[cfg(feature = "log")]
+    log::warn!(target: "pqctracer", "pin-mismatch server={} actual=sha256/{}", server, actual);
+}
+
+/// A panic poisoned one of the client's locks; its state was recovered.
+pub(crate) fn poisoned(lock: &str) {
+    #[cfg(feature = "log")]
+    log::warn!(target: "pqctracer", "poisoned-lock lock={}", lock);
+}
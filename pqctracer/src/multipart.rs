@@ -0,0 +1,120 @@
+//! `multipart/form-data` request bodies (RFC 7578), for
+//! [`TracedRequestBuilder::multipart`](crate::TracedRequestBuilder::multipart).
+//!
+//! Shaped like `reqwest::multipart`, which needs reqwest's `multipart`
+//! feature; the parts are kept in memory and the body is encoded when the
+//! request is built.
+
+use std::fmt::Write as _;
+
+use reqwest::header::HeaderValue;
+
+use crate::TraceError;
+
+/// The fields of a form, in the order they are sent.
+#[derive(Debug, Default)]
+pub struct Form {
+    parts: Vec<(String, Part)>,
+}
+
+impl Form {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a text field.
+    pub fn text(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.part(name, Part::text(value))
+    }
+
+    /// Add a field.
+    pub fn part(mut self, name: impl Into<String>, part: Part) -> Self {
+        self.parts.push((name.into(), part));
+        self
+    }
+
+    /// The body with `boundary` between the parts.
+    pub(crate) fn encode(&self, boundary: &str) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, part) in &self.parts {
+            let mut head = format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"",
+                boundary,
+                escape(name)
+            );
+            if let Some(file_name) = &part.file_name {
+                let _ = write!(head, "; filename=\"{}\"", escape(file_name));
+            }
+            if let Some(mime) = &part.mime {
+                let _ = write!(head, "\r\nContent-Type: {}", mime);
+            }
+            head.push_str("\r\n\r\n");
+            body.extend_from_slice(head.as_bytes());
+            body.extend_from_slice(&part.data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+        body
+    }
+}
+
+/// One field of a [`Form`]: its contents, and for files a file name and a
+/// media type.
+#[derive(Debug)]
+pub struct Part {
+    data: Vec<u8>,
+    file_name: Option<String>,
+    mime: Option<String>,
+}
+
+impl Part {
+    /// A text field, sent without a `Content-Type`.
+    pub fn text(value: impl Into<String>) -> Self {
+        Self {
+            data: value.into().into_bytes(),
+            file_name: None,
+            mime: None,
+        }
+    }
+
+    /// A field with the contents `data`, e.g. of a file.
+    pub fn bytes(data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            data: data.into(),
+            file_name: None,
+            mime: None,
+        }
+    }
+
+    /// Send the part as a file upload named `file_name`. Unless a media type
+    /// is set, it goes out as `application/octet-stream`.
+    pub fn file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        if self.mime.is_none() {
+            self.mime = Some("application/octet-stream".to_string());
+        }
+        self
+    }
+
+    /// Set the part's `Content-Type`, e.g. `image/png`. Fails unless it
+    /// looks like `type/subtype`, optionally with parameters.
+    pub fn mime_str(mut self, mime: &str) -> Result<Self, TraceError> {
+        let essence = mime.split(';').next().unwrap_or_default().trim();
+        let valid = HeaderValue::from_str(mime).is_ok()
+            && essence
+                .split_once('/')
+                .is_some_and(|(kind, subtype)| !kind.is_empty() && !subtype.is_empty());
+        if !valid {
+            return Err(TraceError::Config(format!("invalid media type {:?}", mime)));
+        }
+        self.mime = Some(mime.to_string());
+        Ok(self)
+    }
+}
+
+/// Escape a field or file name for its quoted string, the way browsers do.
+fn escape(name: &str) -> String {
+    name.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
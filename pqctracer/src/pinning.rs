@@ -0,0 +1,265 @@
+//! Public-key pinning: the SHA-256 of the end-entity certificate's
+//! SubjectPublicKeyInfo is compared with the pins configured for its host
+//! once the chain has been verified as usual.
+//!
+//! A key that matches none of the pins may mean a TLS-intercepting
+//! middlebox, which tends to strip post-quantum key exchange as well.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use rustls::SignatureScheme;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::hash::Hash;
+use rustls::{CertificateError, DigitallySignedStruct, DistinguishedName, OtherError};
+use rustls_pki_types::{CertificateDer, ServerName, UnixTime};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::logging;
+
+/// A SHA-256 digest, here of a certificate's SubjectPublicKeyInfo.
+///
+/// Parsed from and displayed as standard base64, the form HPKP and
+/// `openssl ... | openssl dgst -sha256 -binary | base64` produce; a
+/// `sha256/` prefix is accepted too.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sha256Digest(pub [u8; 32]);
+
+impl FromStr for Sha256Digest {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let encoded = s.strip_prefix("sha256/").unwrap_or(s);
+        let bytes = BASE64
+            .decode(encoded)
+            .map_err(|e| format!("invalid base64 digest {}: {}", s, e))?;
+        let digest = <[u8; 32]>::try_from(bytes.as_slice())
+            .map_err(|_| format!("invalid digest {}: {} bytes instead of 32", s, bytes.len()))?;
+        Ok(Self(digest))
+    }
+}
+
+impl Sha256Digest {
+    /// The digest in lowercase hex, e.g. for file names, which base64's `/`
+    /// does not suit.
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+impl fmt::Display for Sha256Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&BASE64.encode(self.0))
+    }
+}
+
+/// `Sha256Digest(47DEQpj8…)`, base64 like `Display`, since the digest ends up
+/// in the `Debug` form of the handshake error.
+impl fmt::Debug for Sha256Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sha256Digest({})", self)
+    }
+}
+
+/// What a key that matches none of its host's pins does to the handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum PinPolicy {
+    /// Fail the handshake with
+    /// [`CertificateProblem::PinMismatch`](crate::CertificateProblem::PinMismatch).
+    #[default]
+    Enforce,
+    /// Carry on, with
+    /// [`TlsMetadata::pin_matched`](crate::TlsMetadata::pin_matched) set to
+    /// `false`.
+    Report,
+}
+
+impl fmt::Display for PinPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PinPolicy::Enforce => "enforce",
+            PinPolicy::Report => "report",
+        })
+    }
+}
+
+impl FromStr for PinPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "enforce" => Ok(PinPolicy::Enforce),
+            "report" | "report-only" => Ok(PinPolicy::Report),
+            other => Err(format!("unknown pin policy: {}", other)),
+        }
+    }
+}
+
+/// The key a server presented matched none of the pins for its host.
+#[derive(Debug)]
+pub struct PinMismatch {
+    pub host: String,
+    /// The SHA-256 of the key the server presented.
+    pub actual: Sha256Digest,
+}
+
+impl fmt::Display for PinMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "public key sha256/{} of {} matches none of its pins",
+            self.actual, self.host
+        )
+    }
+}
+
+impl Error for PinMismatch {}
+
+/// The pins of every pinned host, keyed by lowercase host name.
+pub(crate) struct Pins {
+    pins: HashMap<String, Vec<Sha256Digest>>,
+    policy: PinPolicy,
+    sha256: &'static dyn Hash,
+}
+
+impl fmt::Debug for Pins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pins")
+            .field("hosts", &self.pins.keys().collect::<Vec<_>>())
+            .field("policy", &self.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Pins {
+    pub(crate) fn new(
+        pins: HashMap<String, Vec<Sha256Digest>>,
+        policy: PinPolicy,
+        sha256: &'static dyn Hash,
+    ) -> Self {
+        Self {
+            pins,
+            policy,
+            sha256,
+        }
+    }
+
+    /// Whether `chain`'s end-entity key matches a pin for `host`; `None`
+    /// when the host is not pinned or there is no chain to check.
+    pub(crate) fn matched(&self, host: &str, chain: &[CertificateDer<'_>]) -> Option<bool> {
+        self.check(host, chain.first()?)
+            .map(|result| result.is_ok())
+    }
+
+    fn check(
+        &self,
+        host: &str,
+        end_entity: &CertificateDer<'_>,
+    ) -> Option<Result<(), PinMismatch>> {
+        let pins = self.pins.get(&host.to_ascii_lowercase())?;
+        let actual = match X509Certificate::from_der(end_entity.as_ref()) {
+            Ok((_, cert)) => self.digest(cert.tbs_certificate.subject_pki.raw),
+            // webpki has already rejected such a certificate unless
+            // verification is off; an unreadable key matches no pin.
+            Err(_) => Sha256Digest([0; 32]),
+        };
+        if pins.contains(&actual) {
+            return Some(Ok(()));
+        }
+        logging::pin_mismatch(host, &actual);
+        Some(Err(PinMismatch {
+            host: host.to_string(),
+            actual,
+        }))
+    }
+
+    fn digest(&self, bytes: &[u8]) -> Sha256Digest {
+        let mut digest = [0; 32];
+        digest.copy_from_slice(self.sha256.hash(bytes).as_ref());
+        Sha256Digest(digest)
+    }
+
+    /// `verifier`, or under [`PinPolicy::Enforce`] with any pins a wrapper
+    /// that also fails chains whose key is not pinned.
+    pub(crate) fn wrap(
+        self: &Arc<Self>,
+        verifier: Arc<dyn ServerCertVerifier>,
+    ) -> Arc<dyn ServerCertVerifier> {
+        match (self.policy, self.pins.is_empty()) {
+            (PinPolicy::Enforce, false) => Arc::new(PinningVerifier {
+                inner: verifier,
+                pins: self.clone(),
+            }),
+            _ => verifier,
+        }
+    }
+}
+
+/// Runs the pin check after `inner` accepted the chain.
+#[derive(Debug)]
+struct PinningVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    pins: Arc<Pins>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        match self.pins.check(&server_name.to_str(), end_entity) {
+            Some(Err(mismatch)) => Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                OtherError(Arc::new(mismatch)),
+            ))),
+            _ => Ok(verified),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+
+    fn requires_raw_public_keys(&self) -> bool {
+        self.inner.requires_raw_public_keys()
+    }
+
+    fn root_hint_subjects(&self) -> Option<&[DistinguishedName]> {
+        self.inner.root_hint_subjects()
+    }
+}
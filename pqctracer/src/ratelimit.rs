@@ -0,0 +1,168 @@
+//! Request pacing shared by concurrent scan workers.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{Instant, sleep_until};
+
+/// A token bucket holding at most one token, refilled at `rate` tokens per
+/// second.
+///
+/// Callers reserve a token up front – letting the balance go negative – and
+/// then sleep until their reservation is covered, so waiting never spins and
+/// concurrent callers are served in the order they asked.
+pub(crate) struct TokenBucket {
+    rate: f64,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            state: Mutex::new(BucketState {
+                tokens: 1.0,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait until a token is available and consume it.
+    pub(crate) async fn acquire(&self) {
+        let ready_at = {
+            let mut state = crate::lock(&self.state, "token-bucket");
+            let now = Instant::now();
+            let refill = now.duration_since(state.updated).as_secs_f64() * self.rate;
+            state.tokens = (state.tokens + refill).min(1.0) - 1.0;
+            state.updated = now;
+            if state.tokens >= 0.0 {
+                return;
+            }
+            now + Duration::from_secs_f64(-state.tokens / self.rate)
+        };
+        sleep_until(ready_at).await;
+    }
+}
+
+/// Enforces a minimum interval between requests to the same host.
+pub(crate) struct HostSpacing {
+    interval: Duration,
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl HostSpacing {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reserve the next free slot for `host` and wait for it.
+    pub(crate) async fn acquire(&self, host: &str) {
+        let slot = {
+            let mut next = crate::lock(&self.next, "host-spacing");
+            let now = Instant::now();
+            let slot = next.get(host).map_or(now, |&n| n.max(now));
+            next.insert(host.to_string(), slot + self.interval);
+            slot
+        };
+        sleep_until(slot).await;
+    }
+}
+
+/// Caps the number of targets of the same host traced at the same time.
+pub(crate) struct HostSlots {
+    max: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostSlots {
+    pub(crate) fn new(max: usize) -> Self {
+        Self {
+            max,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait for one of `host`'s slots, which is free again once the permit
+    /// is dropped.
+    pub(crate) async fn acquire(&self, host: &str) -> OwnedSemaphorePermit {
+        let semaphore = {
+            let mut hosts = crate::lock(&self.hosts, "host-slots");
+            hosts
+                .entry(host.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(self.max)))
+                .clone()
+        };
+        semaphore
+            .acquire_owned()
+            .await
+            .expect("host semaphores are never closed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::future::join_all;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn token_bucket_spreads_a_burst_over_the_rate() {
+        let bucket = TokenBucket::new(10.0);
+        let start = Instant::now();
+        join_all((0..20).map(|_| bucket.acquire())).await;
+        // The first token is there up front, the other 19 take 0.1 s each.
+        assert_eq!(start.elapsed(), Duration::from_millis(1900));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn token_bucket_serves_callers_in_order() {
+        let bucket = TokenBucket::new(10.0);
+        let start = Instant::now();
+        let done = join_all((0..5).map(|_| async {
+            bucket.acquire().await;
+            start.elapsed()
+        }))
+        .await;
+        let expected: Vec<_> = (0..5).map(|i| Duration::from_millis(100 * i)).collect();
+        assert_eq!(done, expected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn token_bucket_refills_at_most_one_token() {
+        let bucket = TokenBucket::new(10.0);
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        let start = Instant::now();
+        join_all((0..3).map(|_| bucket.acquire())).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn host_spacing_paces_each_host_on_its_own() {
+        let spacing = HostSpacing::new(Duration::from_millis(100));
+        let start = Instant::now();
+        let done = join_all((0..20).map(|i| {
+            let host = if i % 2 == 0 { "a.example" } else { "b.example" };
+            let spacing = &spacing;
+            async move {
+                spacing.acquire(host).await;
+                start.elapsed()
+            }
+        }))
+        .await;
+        // Ten requests per host, 0.1 s apart, both hosts at the same time.
+        assert_eq!(done.iter().max(), Some(&Duration::from_millis(900)));
+        assert_eq!(done[0], Duration::ZERO);
+        assert_eq!(done[1], Duration::ZERO);
+        assert_eq!(done[18], Duration::from_millis(900));
+    }
+}
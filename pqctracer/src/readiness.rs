@@ -0,0 +1,568 @@
+//! A single quantum-readiness grade per endpoint, rolled up from what a
+//! handshake revealed: the key exchange, the certificate's key, the
+//! cipher's key size and the TLS version.
+//!
+//! Each factor earns 0 to 100 points, and the score is their average
+//! weighted by [`ReadinessWeights`]. Factors that were not captured, e.g.
+//! the certificate of a resumed handshake, are left out of the average
+//! instead of counting as 0, and show up as unknown.
+
+use std::fmt;
+
+use crate::cert::CertificateSummary;
+use crate::{KeyExchangeKind, TlsMetadata};
+
+/// How much each factor counts towards the score. Only the ratios matter;
+/// a weight of 0 leaves a factor out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ReadinessWeights {
+    pub key_exchange: u32,
+    pub certificate: u32,
+    pub cipher: u32,
+    pub version: u32,
+}
+
+/// Key exchange first, since recorded traffic can be decrypted once a
+/// quantum computer breaks it, while a classical certificate only matters
+/// from that day on.
+impl Default for ReadinessWeights {
+    fn default() -> Self {
+        Self {
+            key_exchange: 50,
+            certificate: 20,
+            cipher: 15,
+            version: 15,
+        }
+    }
+}
+
+/// What the score is computed from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadinessFacts {
+    pub key_exchange: Option<KeyExchangeKind>,
+    /// The end-entity certificate's key algorithm, as in
+    /// [`CertificateSummary::key_algorithm`].
+    pub certificate_key: Option<String>,
+    /// The negotiated cipher suite, e.g. `TLS13_AES_256_GCM_SHA384`.
+    pub cipher: Option<String>,
+    /// `TLS1.3` or `TLS1.2`.
+    pub version: Option<String>,
+}
+
+impl ReadinessFacts {
+    /// The facts `metadata` captured.
+    pub fn of(metadata: &TlsMetadata) -> Self {
+        Self {
+            key_exchange: metadata.key_exchange_kind(),
+            certificate_key: metadata
+                .peer_certificates
+                .first()
+                .and_then(CertificateSummary::of)
+                .map(|summary| summary.key_algorithm),
+            cipher: metadata.cipher.clone(),
+            version: metadata.protocol_version().map(str::to_string),
+        }
+    }
+}
+
+/// One of the factors the score is made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum ReadinessFactor {
+    KeyExchange,
+    Certificate,
+    Cipher,
+    Version,
+}
+
+impl fmt::Display for ReadinessFactor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ReadinessFactor::KeyExchange => "key exchange",
+            ReadinessFactor::Certificate => "certificate",
+            ReadinessFactor::Cipher => "cipher",
+            ReadinessFactor::Version => "version",
+        })
+    }
+}
+
+/// How one factor scored, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReadinessComponent {
+    pub factor: ReadinessFactor,
+    /// 0 to 100; `None` when the fact was not captured or not recognized.
+    pub points: Option<u8>,
+    pub weight: u32,
+    pub reason: String,
+}
+
+/// `key exchange 100/100 (hybrid)` or `certificate unknown (not captured)`.
+impl fmt::Display for ReadinessComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.points {
+            Some(points) => write!(f, "{} {}/100 ({})", self.factor, points, self.reason),
+            None => write!(f, "{} unknown ({})", self.factor, self.reason),
+        }
+    }
+}
+
+/// A letter grade for a score: A from 90, B from 75, C from 50, D from 25.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReadinessGrade {
+    A,
+    B,
+    C,
+    D,
+    F,
+}
+
+impl ReadinessGrade {
+    pub fn of_score(score: u8) -> Self {
+        match score {
+            90.. => ReadinessGrade::A,
+            75.. => ReadinessGrade::B,
+            50.. => ReadinessGrade::C,
+            25.. => ReadinessGrade::D,
+            _ => ReadinessGrade::F,
+        }
+    }
+}
+
+impl fmt::Display for ReadinessGrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// An endpoint's grade, with the components it was computed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuantumReadiness {
+    /// 0 to 100.
+    pub score: u8,
+    pub grade: ReadinessGrade,
+    /// Key exchange, certificate, cipher and version, in that order.
+    pub components: Vec<ReadinessComponent>,
+}
+
+/// `B (80/100): key exchange 100/100 (hybrid), certificate 0/100 (ECDSA
+/// P-256, classical signature), …`.
+impl fmt::Display for QuantumReadiness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}/100)", self.grade, self.score)?;
+        for (index, component) in self.components.iter().enumerate() {
+            let separator = if index == 0 { ": " } else { ", " };
+            write!(f, "{}{}", separator, component)?;
+        }
+        Ok(())
+    }
+}
+
+/// Grade `facts` with `weights`.
+pub fn assess(facts: &ReadinessFacts, weights: &ReadinessWeights) -> QuantumReadiness {
+    let components = vec![
+        component(
+            ReadinessFactor::KeyExchange,
+            weights.key_exchange,
+            key_exchange(facts.key_exchange),
+        ),
+        component(
+            ReadinessFactor::Certificate,
+            weights.certificate,
+            certificate(facts.certificate_key.as_deref()),
+        ),
+        component(
+            ReadinessFactor::Cipher,
+            weights.cipher,
+            cipher(facts.cipher.as_deref()),
+        ),
+        component(
+            ReadinessFactor::Version,
+            weights.version,
+            version(facts.version.as_deref()),
+        ),
+    ];
+
+    let (earned, possible) = components
+        .iter()
+        .filter_map(|component| Some((component.points?, component.weight)))
+        .fold((0u64, 0u64), |(earned, possible), (points, weight)| {
+            (
+                earned + u64::from(points) * u64::from(weight),
+                possible + u64::from(weight),
+            )
+        });
+    let score = match possible {
+        0 => 0,
+        possible => {
+            u8::try_from((earned + possible / 2) / possible).expect("at most 100 points each")
+        }
+    };
+    QuantumReadiness {
+        score,
+        grade: ReadinessGrade::of_score(score),
+        components,
+    }
+}
+
+fn component(
+    factor: ReadinessFactor,
+    weight: u32,
+    (points, reason): (Option<u8>, String),
+) -> ReadinessComponent {
+    ReadinessComponent {
+        factor,
+        points,
+        weight,
+        reason,
+    }
+}
+
+fn key_exchange(kind: Option<KeyExchangeKind>) -> (Option<u8>, String) {
+    match kind {
+        Some(KeyExchangeKind::Hybrid) => (Some(100), "hybrid".to_string()),
+        Some(KeyExchangeKind::PostQuantum) => (Some(100), "post-quantum".to_string()),
+        Some(KeyExchangeKind::Classical) => (Some(0), "classical".to_string()),
+        None => (None, "not captured".to_string()),
+    }
+}
+
+fn certificate(key: Option<&str>) -> (Option<u8>, String) {
+    let Some(key) = key else {
+        return (None, "not captured".to_string());
+    };
+    let quantum_resistant = ["ML-DSA", "SLH-DSA", "composite"];
+    let classical = ["RSA", "ECDSA", "Ed25519", "Ed448"];
+    if quantum_resistant.iter().any(|name| key.starts_with(name)) {
+        (Some(100), format!("{}, post-quantum signature", key))
+    } else if classical.iter().any(|name| key.starts_with(name)) {
+        (Some(0), format!("{}, classical signature", key))
+    } else {
+        (None, format!("unrecognized key algorithm {}", key))
+    }
+}
+
+/// Grover's algorithm halves the strength of a symmetric key, which leaves
+/// 128 bits of a 256-bit key and 64 of a 128-bit one. ChaCha20 has a
+/// 256-bit key.
+fn cipher(cipher: Option<&str>) -> (Option<u8>, String) {
+    let Some(cipher) = cipher else {
+        return (None, "not captured".to_string());
+    };
+    if cipher.contains("AES_256") || cipher.contains("CHACHA20") {
+        (Some(100), "256-bit key".to_string())
+    } else if cipher.contains("AES_128") {
+        (Some(50), "128-bit key".to_string())
+    } else {
+        (None, format!("unrecognized cipher {}", cipher))
+    }
+}
+
+/// Hybrid key exchange needs TLS 1.3.
+fn version(version: Option<&str>) -> (Option<u8>, String) {
+    match version {
+        Some("TLS1.3") => (Some(100), "TLS1.3".to_string()),
+        Some("TLS1.2") => (Some(0), "TLS1.2, no hybrid key exchange".to_string()),
+        Some(other) => (None, format!("unrecognized version {}", other)),
+        None => (None, "not captured".to_string()),
+    }
+}
+
+impl TlsMetadata {
+    /// Grade the handshake's quantum readiness with `weights`.
+    pub fn readiness(&self, weights: &ReadinessWeights) -> QuantumReadiness {
+        assess(&ReadinessFacts::of(self), weights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_factor_scores_each_of_its_cases() {
+        let kinds = [
+            (Some(KeyExchangeKind::Hybrid), Some(100), "hybrid"),
+            (
+                Some(KeyExchangeKind::PostQuantum),
+                Some(100),
+                "post-quantum",
+            ),
+            (Some(KeyExchangeKind::Classical), Some(0), "classical"),
+            (None, None, "not captured"),
+        ];
+        for (kind, points, reason) in kinds {
+            assert_eq!(
+                key_exchange(kind),
+                (points, reason.to_string()),
+                "{:?}",
+                kind
+            );
+        }
+
+        let keys = [
+            (
+                Some("ML-DSA-65"),
+                Some(100),
+                "ML-DSA-65, post-quantum signature",
+            ),
+            (
+                Some("SLH-DSA-SHA2-128s"),
+                Some(100),
+                "SLH-DSA-SHA2-128s, post-quantum signature",
+            ),
+            (
+                Some("composite ML-DSA-65 + ECDSA P-256"),
+                Some(100),
+                "composite ML-DSA-65 + ECDSA P-256, post-quantum signature",
+            ),
+            (Some("RSA 2048"), Some(0), "RSA 2048, classical signature"),
+            (
+                Some("ECDSA P-256"),
+                Some(0),
+                "ECDSA P-256, classical signature",
+            ),
+            (Some("Ed25519"), Some(0), "Ed25519, classical signature"),
+            (Some("Ed448"), Some(0), "Ed448, classical signature"),
+            (
+                Some("DSA 1024"),
+                None,
+                "unrecognized key algorithm DSA 1024",
+            ),
+            (None, None, "not captured"),
+        ];
+        for (key, points, reason) in keys {
+            assert_eq!(certificate(key), (points, reason.to_string()), "{:?}", key);
+        }
+
+        let ciphers = [
+            (Some("TLS13_AES_256_GCM_SHA384"), Some(100), "256-bit key"),
+            (
+                Some("TLS13_CHACHA20_POLY1305_SHA256"),
+                Some(100),
+                "256-bit key",
+            ),
+            (
+                Some("TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384"),
+                Some(100),
+                "256-bit key",
+            ),
+            (Some("TLS13_AES_128_GCM_SHA256"), Some(50), "128-bit key"),
+            (
+                Some("TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256"),
+                Some(50),
+                "128-bit key",
+            ),
+            (
+                Some("TLS_RSA_WITH_3DES_EDE_CBC_SHA"),
+                None,
+                "unrecognized cipher TLS_RSA_WITH_3DES_EDE_CBC_SHA",
+            ),
+            (None, None, "not captured"),
+        ];
+        for (suite, points, reason) in ciphers {
+            assert_eq!(cipher(suite), (points, reason.to_string()), "{:?}", suite);
+        }
+
+        let versions = [
+            (Some("TLS1.3"), Some(100), "TLS1.3"),
+            (Some("TLS1.2"), Some(0), "TLS1.2, no hybrid key exchange"),
+            (Some("TLS1.1"), None, "unrecognized version TLS1.1"),
+            (None, None, "not captured"),
+        ];
+        for (tls, points, reason) in versions {
+            assert_eq!(version(tls), (points, reason.to_string()), "{:?}", tls);
+        }
+    }
+
+    fn facts(
+        key_exchange: Option<KeyExchangeKind>,
+        certificate_key: Option<&str>,
+        cipher: Option<&str>,
+        version: Option<&str>,
+    ) -> ReadinessFacts {
+        ReadinessFacts {
+            key_exchange,
+            certificate_key: certificate_key.map(str::to_string),
+            cipher: cipher.map(str::to_string),
+            version: version.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn scores_are_weighted_averages_of_what_was_captured() {
+        use KeyExchangeKind::{Classical, Hybrid, PostQuantum};
+
+        let defaults = ReadinessWeights::default();
+        let only_key_exchange = ReadinessWeights {
+            key_exchange: 1,
+            certificate: 0,
+            cipher: 0,
+            version: 0,
+        };
+        let equal = ReadinessWeights {
+            key_exchange: 1,
+            certificate: 1,
+            cipher: 1,
+            version: 1,
+        };
+        let nothing = ReadinessWeights {
+            key_exchange: 0,
+            certificate: 0,
+            cipher: 0,
+            version: 0,
+        };
+        let aes256 = Some("TLS13_AES_256_GCM_SHA384");
+        let aes128 = Some("TLS13_AES_128_GCM_SHA256");
+        let cases = [
+            // Everything quantum-resistant.
+            (
+                facts(Some(PostQuantum), Some("ML-DSA-65"), aes256, Some("TLS1.3")),
+                defaults,
+                100,
+                ReadinessGrade::A,
+            ),
+            // A hybrid key exchange behind a classical certificate.
+            (
+                facts(Some(Hybrid), Some("ECDSA P-256"), aes256, Some("TLS1.3")),
+                defaults,
+                80,
+                ReadinessGrade::B,
+            ),
+            (
+                facts(Some(Hybrid), Some("ECDSA P-256"), aes128, Some("TLS1.3")),
+                defaults,
+                73,
+                ReadinessGrade::C,
+            ),
+            (
+                facts(Some(Classical), Some("RSA 2048"), aes256, Some("TLS1.3")),
+                defaults,
+                30,
+                ReadinessGrade::D,
+            ),
+            (
+                facts(Some(Classical), Some("RSA 2048"), aes128, Some("TLS1.2")),
+                defaults,
+                8,
+                ReadinessGrade::F,
+            ),
+            // Unknown facts are left out rather than counted as 0.
+            (
+                facts(Some(Hybrid), None, None, None),
+                defaults,
+                100,
+                ReadinessGrade::A,
+            ),
+            (
+                facts(Some(Hybrid), None, aes128, Some("TLS1.3")),
+                defaults,
+                91,
+                ReadinessGrade::A,
+            ),
+            (
+                facts(None, Some("ECDSA P-256"), None, None),
+                defaults,
+                0,
+                ReadinessGrade::F,
+            ),
+            (
+                facts(None, None, None, None),
+                defaults,
+                0,
+                ReadinessGrade::F,
+            ),
+            // Weights of 0 leave factors out.
+            (
+                facts(Some(Hybrid), Some("RSA 2048"), aes128, Some("TLS1.2")),
+                only_key_exchange,
+                100,
+                ReadinessGrade::A,
+            ),
+            // Halves round up: 150 points of 400 are 37.5.
+            (
+                facts(Some(Hybrid), Some("RSA 2048"), aes128, Some("TLS1.2")),
+                equal,
+                38,
+                ReadinessGrade::D,
+            ),
+            (
+                facts(Some(Hybrid), Some("RSA 2048"), aes256, Some("TLS1.3")),
+                nothing,
+                0,
+                ReadinessGrade::F,
+            ),
+            (
+                facts(Some(Classical), None, aes128, Some("TLS1.3")),
+                equal,
+                50,
+                ReadinessGrade::C,
+            ),
+        ];
+        for (facts, weights, score, grade) in cases {
+            let readiness = assess(&facts, &weights);
+            assert_eq!(
+                (readiness.score, readiness.grade),
+                (score, grade),
+                "{:?} {:?}",
+                facts,
+                weights
+            );
+            let factors: Vec<_> = readiness
+                .components
+                .iter()
+                .map(|c| (c.factor, c.weight))
+                .collect();
+            assert_eq!(
+                factors,
+                [
+                    (ReadinessFactor::KeyExchange, weights.key_exchange),
+                    (ReadinessFactor::Certificate, weights.certificate),
+                    (ReadinessFactor::Cipher, weights.cipher),
+                    (ReadinessFactor::Version, weights.version),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn grade_boundaries() {
+        for (score, grade) in [
+            (100, ReadinessGrade::A),
+            (90, ReadinessGrade::A),
+            (89, ReadinessGrade::B),
+            (75, ReadinessGrade::B),
+            (74, ReadinessGrade::C),
+            (50, ReadinessGrade::C),
+            (49, ReadinessGrade::D),
+            (25, ReadinessGrade::D),
+            (24, ReadinessGrade::F),
+            (0, ReadinessGrade::F),
+        ] {
+            assert_eq!(ReadinessGrade::of_score(score), grade, "{}", score);
+        }
+    }
+
+    #[test]
+    fn display_lists_every_component() {
+        let readiness = assess(
+            &facts(
+                Some(KeyExchangeKind::Hybrid),
+                Some("ECDSA P-256"),
+                None,
+                Some("TLS1.3"),
+            ),
+            &ReadinessWeights::default(),
+        );
+        assert_eq!(
+            readiness.to_string(),
+            "B (76/100): key exchange 100/100 (hybrid), certificate 0/100 (ECDSA P-256, \
+             classical signature), cipher unknown (not captured), version 100/100 (TLS1.3)"
+        );
+    }
+}
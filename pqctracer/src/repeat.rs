@@ -0,0 +1,228 @@
+//! Tracing one target again and again, to catch load-balanced hostnames
+//! whose backends disagree: one negotiates a hybrid group, the next
+//! X25519, or they present different certificates.
+//!
+//! Every attempt is a handshake of its own on a new connection, neither
+//! pooled nor resumed, so that each one can land on another backend and
+//! reports that backend's group and certificate.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_util::stream::{self, StreamExt};
+use rustls_pki_types::{CertificateDer, ServerName};
+use tokio_rustls::TlsConnector;
+
+use crate::ratelimit::HostSpacing;
+use crate::scan::ScanError;
+use crate::{ErrorCategory, Sha256Digest, TlsAwareClient, TlsMetadata, TraceError, idn};
+
+/// How [`TlsAwareClient::trace_repeated`] paces its attempts.
+#[derive(Debug, Clone)]
+pub struct RepeatOptions {
+    /// Minimum time between the starts of two attempts.
+    pub delay: Duration,
+    /// Number of attempts in flight at the same time.
+    pub concurrency: usize,
+}
+
+impl Default for RepeatOptions {
+    fn default() -> Self {
+        Self {
+            delay: Duration::ZERO,
+            concurrency: 1,
+        }
+    }
+}
+
+/// One attempt of a repeated trace.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RepeatAttempt {
+    /// The address connected to, once the name was resolved.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub remote_addr: Option<SocketAddr>,
+    pub metadata: TlsMetadata,
+    /// The SHA-256 fingerprint of the end-entity certificate, in hex.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub certificate: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub error: Option<ScanError>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "duration_ms", with = "crate::serde_util::millis")
+    )]
+    pub duration: Duration,
+}
+
+/// How often one value was observed across the attempts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValueCount {
+    pub value: String,
+    pub count: usize,
+}
+
+/// The attempts of a repeated trace and what they had in common.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RepeatReport {
+    pub url: String,
+    /// In the order they were started.
+    pub attempts: Vec<RepeatAttempt>,
+    /// Negotiated groups of the completed attempts, most common first.
+    pub groups: Vec<ValueCount>,
+    /// Addresses connected to, most common first.
+    pub remote_addrs: Vec<ValueCount>,
+    /// End-entity certificate fingerprints, most common first.
+    pub certificates: Vec<ValueCount>,
+    pub failed: usize,
+    /// Whether the completed attempts negotiated more than one group or
+    /// were presented more than one certificate.
+    pub inconsistent: bool,
+}
+
+impl RepeatReport {
+    fn of(url: String, attempts: Vec<RepeatAttempt>) -> Self {
+        let groups = tally(attempts.iter().filter_map(|a| a.metadata.group.clone()));
+        let remote_addrs = tally(
+            attempts
+                .iter()
+                .filter_map(|a| a.remote_addr.map(|addr| addr.to_string())),
+        );
+        let certificates = tally(attempts.iter().filter_map(|a| a.certificate.clone()));
+        Self {
+            url,
+            failed: attempts.iter().filter(|a| a.error.is_some()).count(),
+            inconsistent: groups.len() > 1 || certificates.len() > 1,
+            attempts,
+            groups,
+            remote_addrs,
+            certificates,
+        }
+    }
+}
+
+fn tally(values: impl Iterator<Item = String>) -> Vec<ValueCount> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for value in values {
+        *counts.entry(value).or_default() += 1;
+    }
+    let mut counts: Vec<ValueCount> = counts
+        .into_iter()
+        .map(|(value, count)| ValueCount { value, count })
+        .collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+    counts
+}
+
+/// Trace `url` `attempts` times with a default [`TlsAwareClient`].
+pub async fn trace_repeated(
+    url: &str,
+    attempts: usize,
+    options: &RepeatOptions,
+) -> Result<RepeatReport, TraceError> {
+    TlsAwareClient::new()
+        .trace_repeated(url, attempts, options)
+        .await
+}
+
+impl TlsAwareClient {
+    /// Perform `attempts` fresh handshakes with the host of `url`, paced by
+    /// `options`, and report the groups, addresses and certificates seen.
+    ///
+    /// The host name is resolved again for every attempt, and the first
+    /// address it resolves to is connected to. Failed attempts are recorded
+    /// in the report; only an unusable URL or zero attempts fail the call.
+    pub async fn trace_repeated(
+        &self,
+        url: &str,
+        attempts: usize,
+        options: &RepeatOptions,
+    ) -> Result<RepeatReport, TraceError> {
+        if attempts == 0 {
+            return Err(TraceError::Config(
+                "at least one attempt is required".to_string(),
+            ));
+        }
+        let parsed = reqwest::Url::parse(&idn::normalize_url(url)?)
+            .map_err(|e| TraceError::InvalidUrl(format!("{}: {}", url, e)))?;
+        let (Some(host), "https") = (parsed.host_str(), parsed.scheme()) else {
+            return Err(TraceError::InvalidUrl(format!(
+                "{}: not an https URL with a host",
+                url
+            )));
+        };
+        let host = host.trim_matches(['[', ']']).to_string();
+        let port = parsed.port_or_known_default().unwrap_or(443);
+        let server_name = ServerName::try_from(host.clone())
+            .map_err(|_| TraceError::InvalidServerName(host.clone()))?;
+        let connector = TlsConnector::from(Arc::new(self.direct_tls_config(None)?));
+
+        let spacing = HostSpacing::new(options.delay);
+        let results = stream::iter(0..attempts)
+            .map(|_| async {
+                spacing.acquire(&host).await;
+                self.repeat_attempt(&host, port, server_name.clone(), &connector)
+                    .await
+            })
+            .buffered(options.concurrency.max(1))
+            .collect()
+            .await;
+        Ok(RepeatReport::of(url.to_string(), results))
+    }
+
+    async fn repeat_attempt(
+        &self,
+        host: &str,
+        port: u16,
+        server_name: ServerName<'static>,
+        connector: &TlsConnector,
+    ) -> RepeatAttempt {
+        let start = Instant::now();
+        let mut attempt = RepeatAttempt {
+            remote_addr: None,
+            metadata: TlsMetadata::default(),
+            certificate: None,
+            error: None,
+            duration: Duration::ZERO,
+        };
+        let addr = match self.bind.lookup(host, port).await {
+            Ok(addrs) => addrs.into_iter().next(),
+            Err(_) => None,
+        };
+        let Some(addr) = addr else {
+            attempt.error = Some(ScanError {
+                category: ErrorCategory::DnsFailure,
+                message: format!("{} did not resolve", host),
+                name_mismatch: None,
+            });
+            attempt.duration = start.elapsed();
+            return attempt;
+        };
+        attempt.remote_addr = Some(addr);
+        match self.handshake_at(addr, server_name, connector).await {
+            Ok(metadata) => {
+                attempt.certificate = metadata
+                    .peer_certificates
+                    .first()
+                    .and_then(|cert| self.fingerprint(cert))
+                    .map(|digest| digest.to_hex());
+                attempt.metadata = metadata;
+            }
+            Err((e, connecting)) => attempt.error = Some(ScanError::direct(e, connecting)),
+        }
+        attempt.duration = start.elapsed();
+        attempt
+    }
+
+    /// The SHA-256 fingerprint of `cert`, with this client's provider.
+    fn fingerprint(&self, cert: &CertificateDer<'_>) -> Option<Sha256Digest> {
+        let sha256 = crate::fingerprint::sha256(&self.provider)?;
+        let mut digest = [0; 32];
+        digest.copy_from_slice(sha256.hash(cert.as_ref()).as_ref());
+        Some(Sha256Digest(digest))
+    }
+}
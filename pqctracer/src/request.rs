@@ -0,0 +1,239 @@
+//! Building a request and sending it through a [`TlsAwareClient`] in one
+//! go, with options that apply to that request alone.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http_body::{Frame, SizeHint};
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Body, IntoUrl, Method};
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::multipart::Form;
+use crate::{KxOverride, KxPreference, TlsAwareClient, TlsResponse, TraceError};
+
+/// A request being built by [`TlsAwareClient::request`]; wraps
+/// `reqwest::RequestBuilder` and sends through the tracing client.
+pub struct TracedRequestBuilder<'a> {
+    client: &'a TlsAwareClient,
+    request: reqwest::RequestBuilder,
+    key_exchange: KxOverride,
+    labels: BTreeMap<String, String>,
+}
+
+impl TlsAwareClient {
+    /// Start building a `method` request to `url`.
+    pub fn request(&self, method: Method, url: impl IntoUrl) -> TracedRequestBuilder<'_> {
+        TracedRequestBuilder {
+            client: self,
+            request: self.client.request(method, url),
+            key_exchange: KxOverride::Default,
+            labels: BTreeMap::new(),
+        }
+    }
+
+    /// Start building a `GET` request to `url`.
+    pub fn get(&self, url: impl IntoUrl) -> TracedRequestBuilder<'_> {
+        self.request(Method::GET, url)
+    }
+}
+
+impl TracedRequestBuilder<'_> {
+    /// Add a header to the request.
+    pub fn header(mut self, key: HeaderName, value: HeaderValue) -> Self {
+        self.request = self.request.header(key, value);
+        self
+    }
+
+    /// Add several headers to the request.
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.request = self.request.headers(headers);
+        self
+    }
+
+    /// Set the request body.
+    pub fn body(mut self, body: impl Into<Body>) -> Self {
+        self.request = self.request.body(body);
+        self
+    }
+
+    /// Send HTTP Basic credentials with the request. Like a
+    /// [`header`](Self::header) marked sensitive, they are left out of
+    /// `Debug` output and dropped when a redirect leads to another host,
+    /// port or scheme.
+    pub fn basic_auth(
+        mut self,
+        username: impl fmt::Display,
+        password: Option<impl fmt::Display>,
+    ) -> Self {
+        self.request = self.request.basic_auth(username, password);
+        self
+    }
+
+    /// Send `token` as a bearer token with the request; kept out of
+    /// `Debug` output and redirects like [`basic_auth`](Self::basic_auth).
+    pub fn bearer_auth(mut self, token: impl fmt::Display) -> Self {
+        self.request = self.request.bearer_auth(token);
+        self
+    }
+
+    /// Stream the request body from `reader` as the request is sent,
+    /// instead of holding it in memory, e.g. from a `tokio::fs::File`.
+    ///
+    /// With a `length` the body is sent with that `Content-Length` and ends
+    /// after `length` bytes; a reader that ends earlier fails the request.
+    /// Without one it is sent chunked until the reader ends. The handshake
+    /// is captured before the first byte of the body is read, so a slow
+    /// upload does not delay it, and a connection reset mid-upload fails
+    /// with [`TraceError::RequestFailed`] and the handshake's metadata.
+    pub fn body_reader<R>(self, reader: R, length: Option<u64>) -> Self
+    where
+        R: AsyncRead + Unpin + Send + Sync + 'static,
+    {
+        self.body(Body::wrap(ReaderBody {
+            reader,
+            remaining: length,
+            buf: vec![0; CHUNK_SIZE].into_boxed_slice(),
+        }))
+    }
+
+    /// Send `form` as a `multipart/form-data` body, setting the
+    /// `Content-Type` with a random boundary.
+    pub fn multipart(mut self, form: Form) -> Self {
+        let mut nonce = [0u8; 16];
+        // Should the random source fail, the zeroed boundary is still
+        // valid, just predictable.
+        let _ = self.client.provider.secure_random.fill(&mut nonce);
+        let boundary: String = nonce.iter().map(|b| format!("{:02x}", b)).collect();
+        let boundary = format!("pqctracer-{}", boundary);
+        self.request = self
+            .request
+            .header(
+                CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(form.encode(&boundary));
+        self
+    }
+
+    /// Give up on this request after `timeout`, instead of the client's
+    /// timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.request = self.request.timeout(timeout);
+        self
+    }
+
+    /// Offer other key-exchange groups than the client's for this request.
+    ///
+    /// The client keeps a second connection pool for the groups of the
+    /// other [`KxPreference`], so overridden requests neither need a client
+    /// of their own nor disturb the pooled connections of the rest. Under
+    /// [`KxOverride::PqcRequired`] a handshake that still lands on a
+    /// classical group, or a connection without one, fails the request with
+    /// [`TraceError::PolicyViolation`]. Overridden requests are not
+    /// [retried](crate::TlsAwareClientBuilder::fallback_to_classical) with
+    /// classical groups.
+    pub fn key_exchange(mut self, key_exchange: KxOverride) -> Self {
+        self.key_exchange = key_exchange;
+        self
+    }
+
+    /// Attach a label to the request, e.g. `.label("tenant", "acme")`; it
+    /// ends up in [`TlsMetadata::labels`](crate::TlsMetadata::labels) and
+    /// the request's `log` records. A second label with the same key
+    /// replaces the first.
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Send the request, like [`TlsAwareClient::execute`].
+    pub async fn send(self) -> Result<TlsResponse, TraceError> {
+        let request = self.request.build().map_err(TraceError::Http)?;
+        let client = self.client;
+        client.check_local_address(request.url())?;
+        let preference = match self.key_exchange {
+            KxOverride::Default => {
+                let (result, _) = client.execute_labelled(request, &self.labels).await;
+                return result.map_err(TraceError::from);
+            }
+            KxOverride::ClassicalOnly => KxPreference::ClassicalOnly,
+            KxOverride::PqcRequired => KxPreference::HybridPreferred,
+        };
+        let response = client.send(request, preference, None, &self.labels).await?;
+        let quantum_resistant = response
+            .metadata
+            .key_exchange_kind()
+            .is_some_and(|kind| kind.is_quantum_resistant());
+        if self.key_exchange == KxOverride::PqcRequired && !quantum_resistant {
+            return Err(TraceError::PolicyViolation {
+                reason: format!(
+                    "post-quantum key exchange required, {} negotiated",
+                    response.metadata.group.as_deref().unwrap_or("no group")
+                ),
+                metadata: Box::new(response.metadata),
+            });
+        }
+        Ok(response)
+    }
+}
+
+/// The most read from a body reader at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A request body read from an `AsyncRead` as it is sent.
+struct ReaderBody<R> {
+    reader: R,
+    /// Bytes still to send, when the length is known.
+    remaining: Option<u64>,
+    buf: Box<[u8]>,
+}
+
+impl<R: AsyncRead + Unpin> http_body::Body for ReaderBody<R> {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, io::Error>>> {
+        let this = &mut *self;
+        let limit = match this.remaining {
+            Some(0) => return Poll::Ready(None),
+            Some(remaining) => this
+                .buf
+                .len()
+                .min(remaining.try_into().unwrap_or(usize::MAX)),
+            None => this.buf.len(),
+        };
+        let mut read = ReadBuf::new(&mut this.buf[..limit]);
+        ready!(Pin::new(&mut this.reader).poll_read(cx, &mut read))?;
+        let filled = read.filled();
+        if filled.is_empty() {
+            return Poll::Ready(this.remaining.map(|remaining| {
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("request body ended {} bytes short of its length", remaining),
+                ))
+            }));
+        }
+        if let Some(remaining) = &mut this.remaining {
+            *remaining -= filled.len() as u64;
+        }
+        Poll::Ready(Some(Ok(Frame::data(Bytes::copy_from_slice(filled)))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == Some(0)
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.remaining
+            .map_or_else(SizeHint::default, SizeHint::with_exact)
+    }
+}
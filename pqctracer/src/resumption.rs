@@ -0,0 +1,200 @@
+//! Whether a server's resumed handshakes exchange a fresh key share, probed
+//! with a second handshake that resumes the session of a first one.
+//!
+//! A resumed TLS 1.3 handshake with `psk_dhe_ke` runs a key exchange of its
+//! own, so it is as quantum-resistant as the group it negotiates. One with
+//! `psk_ke`, like every resumed TLS 1.2 session, derives its keys from the
+//! earlier session's alone: it drops forward secrecy and inherits whatever
+//! the original key exchange was. rustls offers only `psk_dhe_ke`, so a
+//! TLS 1.3 server that resumes with `psk_ke` only answers with a full
+//! handshake instead; pure-PSK resumptions show up over TLS 1.2.
+//!
+//! Which of the two a resumed handshake did shows in the key exchanges the
+//! [key-share log](crate::key_shares) saw completed.
+
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use rustls::NamedGroup;
+use rustls::client::{ClientSessionMemoryCache, Resumption};
+use rustls_pki_types::ServerName;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_rustls::TlsConnector;
+
+use crate::scan::DIRECT_TIMEOUT;
+use crate::{ErrorCategory, HandshakeOrigin, TlsAwareClient, TlsMetadata, TraceError};
+
+/// How a resumed handshake set up its keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResumptionKx {
+    /// From the pre-shared key alone, without a fresh key exchange.
+    PskOnly,
+    /// From the pre-shared key and a fresh key exchange in this group.
+    PskWithGroup(NamedGroup),
+}
+
+impl ResumptionKx {
+    /// Whether the resumed handshake ran no key exchange of its own.
+    pub fn is_psk_only(self) -> bool {
+        self == ResumptionKx::PskOnly
+    }
+}
+
+/// `PSK only` or `PSK with X25519MLKEM768`.
+impl fmt::Display for ResumptionKx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResumptionKx::PskOnly => f.write_str("PSK only"),
+            ResumptionKx::PskWithGroup(group) => write!(f, "PSK with {:?}", group),
+        }
+    }
+}
+
+/// How a server answered a handshake resuming the session of an earlier
+/// one.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "outcome", rename_all = "kebab-case"))]
+pub enum ResumptionReport {
+    /// The server resumed the session; the metadata is the resumed
+    /// handshake's, with its [`resumption_kx`](TlsMetadata::resumption_kx).
+    Resumed { metadata: Box<TlsMetadata> },
+    /// The server performed a full handshake again, e.g. because it issued
+    /// no session ticket or only resumes with `psk_ke`.
+    NotResumed { metadata: Box<TlsMetadata> },
+    /// A handshake failed, so the probe says nothing about resumption.
+    Failed { error: ErrorCategory },
+}
+
+impl ResumptionReport {
+    /// How the resumed handshake set up its keys, if the server resumed.
+    pub fn kx(&self) -> Option<ResumptionKx> {
+        match self {
+            ResumptionReport::Resumed { metadata } => metadata.resumption_kx,
+            _ => None,
+        }
+    }
+
+    fn failed(error: TraceError, connecting: bool) -> Self {
+        ResumptionReport::Failed {
+            error: ErrorCategory::of_trace(&error, connecting),
+        }
+    }
+}
+
+/// `resumed, PSK with X25519MLKEM768`, `not resumed` or `failed (connect
+/// timeout)`.
+impl fmt::Display for ResumptionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResumptionReport::Resumed { metadata } => match metadata.resumption_kx {
+                Some(kx) => write!(f, "resumed, {}", kx),
+                None => f.write_str("resumed"),
+            },
+            ResumptionReport::NotResumed { .. } => f.write_str("not resumed"),
+            ResumptionReport::Failed { error } => write!(f, "failed ({})", error),
+        }
+    }
+}
+
+/// Probe how `host:port` resumes sessions, using a default
+/// [`TlsAwareClient`].
+pub async fn probe_resumption(host: &str, port: u16) -> ResumptionReport {
+    TlsAwareClient::new().probe_resumption(host, port).await
+}
+
+impl TlsAwareClient {
+    /// Complete a handshake with `host:port` and send a `HEAD /` request to
+    /// receive its session tickets, then resume the session in a second
+    /// handshake and report whether it exchanged a fresh key share.
+    pub async fn probe_resumption(&self, host: &str, port: u16) -> ResumptionReport {
+        let Ok(server_name) = ServerName::try_from(host.to_string()) else {
+            return ResumptionReport::Failed {
+                error: ErrorCategory::InvalidUrl,
+            };
+        };
+        let addr = match self.bind.lookup(host, port).await {
+            Ok(addrs) => addrs.into_iter().next(),
+            Err(_) => None,
+        };
+        match addr {
+            Some(addr) => self.probe_resumption_at(addr, server_name).await,
+            None => ResumptionReport::Failed {
+                error: ErrorCategory::DnsFailure,
+            },
+        }
+    }
+
+    /// Like [`probe_resumption`](Self::probe_resumption) for an address
+    /// already resolved.
+    pub(crate) async fn probe_resumption_at(
+        &self,
+        addr: SocketAddr,
+        server_name: ServerName<'static>,
+    ) -> ResumptionReport {
+        // A store of the probe's own, so that the second handshake can only
+        // resume the first. The cache evicts a server as soon as it is full,
+        // so it needs room for more than the one server.
+        let mut config = match self.direct_tls_config(None) {
+            Ok(config) => config,
+            Err(e) => return ResumptionReport::failed(e, false),
+        };
+        config.resumption = Resumption::store(Arc::new(ClientSessionMemoryCache::new(32)));
+        let connector = TlsConnector::from(Arc::new(config));
+
+        if let Err((e, connecting)) = self.receive_tickets(addr, &server_name, &connector).await {
+            return ResumptionReport::failed(e, connecting);
+        }
+        match self.handshake_at(addr, server_name, &connector).await {
+            Ok(metadata) if metadata.origin == Some(HandshakeOrigin::Resumed) => {
+                ResumptionReport::Resumed {
+                    metadata: Box::new(metadata),
+                }
+            }
+            Ok(metadata) => ResumptionReport::NotResumed {
+                metadata: Box::new(metadata),
+            },
+            Err((e, connecting)) => ResumptionReport::failed(e, connecting),
+        }
+    }
+
+    /// Complete a handshake and wait for the start of the response to a
+    /// request, which TLS 1.3 servers send their session tickets ahead of.
+    /// Whether the response comes does not matter; the tickets have been
+    /// read by then, if there are any.
+    async fn receive_tickets(
+        &self,
+        addr: SocketAddr,
+        server_name: &ServerName<'static>,
+        connector: &TlsConnector,
+    ) -> Result<(), (TraceError, bool)> {
+        let connect_timeout = self
+            .connect_timeout
+            .or(self.timeout)
+            .unwrap_or(DIRECT_TIMEOUT);
+        let stream = tokio::time::timeout(connect_timeout, self.bind.connect(addr))
+            .await
+            .map_err(|_| (TraceError::Timeout, true))?
+            .map_err(|e| (TraceError::from(e), true))?;
+
+        let timeout = self.timeout.unwrap_or(DIRECT_TIMEOUT);
+        let request = format!(
+            "HEAD / HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            server_name.to_str()
+        );
+        let exchange = async {
+            let mut tls = connector.connect(server_name.clone(), stream).await?;
+            tls.write_all(request.as_bytes()).await?;
+            Ok::<_, io::Error>(tls)
+        };
+        let mut tls = tokio::time::timeout(timeout, exchange)
+            .await
+            .map_err(|_| (TraceError::Timeout, false))?
+            .map_err(|e| (TraceError::from(e), false))?;
+        let mut buf = [0; 1024];
+        let _ = tokio::time::timeout(timeout, tls.read(&mut buf)).await;
+        Ok(())
+    }
+}
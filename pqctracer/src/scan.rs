@@ -0,0 +1,996 @@
+//! Tracing many targets concurrently with one shared client.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use futures_util::stream::{self, StreamExt};
+use reqwest::header::{CONNECTION, HeaderValue};
+use reqwest::{Method, StatusCode};
+use rustls::HandshakeKind;
+use rustls_pki_types::ServerName;
+use tokio::sync::Semaphore;
+use tokio_rustls::TlsConnector;
+
+use crate::counting::CountingStream;
+use crate::error::error_chain;
+use crate::hello_size::HelloSizeReport;
+use crate::http_versions::HttpVersionReport;
+use crate::idn;
+use crate::key_shares;
+use crate::ratelimit::{HostSlots, HostSpacing, TokenBucket};
+use crate::readiness::{QuantumReadiness, ReadinessGrade, ReadinessWeights};
+use crate::resumption::{ResumptionKx, ResumptionReport};
+use crate::stats::{DurationStats, LatencyHistogram};
+use crate::versions::VersionSupportReport;
+use crate::{
+    ErrorCategory, FallbackOutcome, KeyExchangeKind, NameMismatch, TlsAwareClient, TlsMetadata,
+    TlsResponse, TraceError, logging,
+};
+
+/// Upper bound for connecting to and for handshaking with an address target
+/// when the client has no timeouts of its own.
+pub(crate) const DIRECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something to trace in a scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanTarget {
+    /// A URL, host name or `host:port`, traced with an HTTP request; see
+    /// [`ScanOptions::method`].
+    Url(String),
+    /// A bare TLS handshake with `addr`, sending `server_name` as SNI and
+    /// validating the certificate against it. No HTTP request is made.
+    Address {
+        addr: SocketAddr,
+        server_name: String,
+    },
+}
+
+impl ScanTarget {
+    /// How the target appears in [`ScanResult::target`].
+    pub fn label(&self) -> String {
+        match self {
+            ScanTarget::Url(target) => target.clone(),
+            ScanTarget::Address { addr, .. } => addr.to_string(),
+        }
+    }
+}
+
+impl From<String> for ScanTarget {
+    fn from(target: String) -> Self {
+        ScanTarget::Url(target)
+    }
+}
+
+impl From<&str> for ScanTarget {
+    fn from(target: &str) -> Self {
+        ScanTarget::Url(target.to_string())
+    }
+}
+
+/// The HTTP method a scan requests [`ScanTarget::Url`] targets with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "UPPERCASE"))]
+pub enum ScanMethod {
+    /// Only the response head is sent, so hardly anything but the handshake
+    /// is transferred.
+    #[default]
+    Head,
+    Get,
+}
+
+impl ScanMethod {
+    fn method(self) -> Method {
+        match self {
+            ScanMethod::Head => Method::HEAD,
+            ScanMethod::Get => Method::GET,
+        }
+    }
+}
+
+impl fmt::Display for ScanMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.method().as_str())
+    }
+}
+
+impl FromStr for ScanMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "HEAD" => Ok(ScanMethod::Head),
+            "GET" => Ok(ScanMethod::Get),
+            other => Err(format!("unsupported scan method: {}", other)),
+        }
+    }
+}
+
+/// Controls how [`TlsAwareClient::scan`] works through its targets.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Number of targets traced at the same time.
+    pub concurrency: usize,
+    /// Number of targets of the same host traced at the same time, within
+    /// `concurrency`; `None` for no cap. Targets are taken in turns by
+    /// host, so that a host with many targets waits for its own slots
+    /// without holding up the others.
+    pub max_per_host: Option<usize>,
+    /// Additional attempts for a target whose request failed.
+    pub retries: u32,
+    /// Maximum requests per second across all workers, retries included.
+    pub rate_limit: Option<f64>,
+    /// Minimum time between two requests to the same host.
+    pub per_host_interval: Option<Duration>,
+    /// The method of the request to every URL target. The handshake is the
+    /// same whatever the method; the connection is closed after the
+    /// response head either way, without reading the body.
+    pub method: ScanMethod,
+    /// Repeat a HEAD request that was answered with `405 Method Not
+    /// Allowed` or `501 Not Implemented` as a GET request.
+    pub fallback_get: bool,
+    /// Also probe which TLS versions every reachable target accepts, see
+    /// [`TlsAwareClient::probe_tls_versions`].
+    pub probe_versions: bool,
+    /// Also probe whether every target copes with the size of a
+    /// post-quantum ClientHello, see
+    /// [`TlsAwareClient::probe_hello_size_tolerance`]. Failed targets are
+    /// probed too, since they are the ones the verdict explains.
+    pub diagnose_hello_size: bool,
+    /// Also compare a handshake offering HTTP/1.1 with one offering HTTP/2
+    /// for every reachable target, see
+    /// [`TlsAwareClient::probe_http_versions`].
+    pub compare_http_versions: bool,
+    /// Also resume a session with every reachable target and tell whether
+    /// the resumed handshake exchanged a fresh key share, see
+    /// [`TlsAwareClient::probe_resumption`].
+    pub check_resumption: bool,
+    /// How the factors of [`ScanResult::readiness`] are weighted.
+    pub readiness_weights: ReadinessWeights,
+    /// Labels carried by every target's request, as with
+    /// [`TracedRequestBuilder::label`](crate::TracedRequestBuilder::label),
+    /// and by every result's metadata, failed and address targets
+    /// included.
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            max_per_host: Some(2),
+            retries: 1,
+            rate_limit: None,
+            per_host_interval: None,
+            method: ScanMethod::default(),
+            fallback_get: false,
+            probe_versions: false,
+            diagnose_hello_size: false,
+            compare_http_versions: false,
+            check_resumption: false,
+            readiness_weights: ReadinessWeights::default(),
+            labels: BTreeMap::new(),
+        }
+    }
+}
+
+/// The outcome of tracing one scan target.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScanResult {
+    /// The target as given.
+    pub target: String,
+    /// The URL that was requested; `https://` plus the address for address
+    /// targets.
+    pub url: String,
+    /// The inputs that were [normalized](normalize_target) into this
+    /// target, in input order, e.g. `EXAMPLE.COM:443` and
+    /// `https://example.com/`; empty when the target was scanned as given.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub sources: Vec<String>,
+    /// The server name sent to an address target.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sni: Option<String>,
+    /// HTTP status code of the final attempt, if it got a response. Always
+    /// `None` for address targets.
+    pub status: Option<u16>,
+    /// The method of the request that got the response, after any
+    /// [fallback to GET](ScanOptions::fallback_get).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub method: Option<ScanMethod>,
+    pub metadata: TlsMetadata,
+    /// Error of the final attempt, if it failed, including HTTP error
+    /// statuses.
+    pub error: Option<ScanError>,
+    pub attempts: u32,
+    /// Duration of the final attempt.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "duration_ms", with = "crate::serde_util::millis")
+    )]
+    pub duration: Duration,
+    /// Start of the end-entity certificate's validity period.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "crate::serde_util::rfc3339_option")
+    )]
+    pub cert_not_before: Option<SystemTime>,
+    /// End of the end-entity certificate's validity period.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "crate::serde_util::rfc3339_option")
+    )]
+    pub cert_not_after: Option<SystemTime>,
+    /// Whole days until `cert_not_after` at the time of the scan; negative if
+    /// the certificate had already expired.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cert_days_remaining: Option<i64>,
+    /// The TLS versions the target accepts, when
+    /// [`ScanOptions::probe_versions`] is set and the target was reached.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub versions: Option<VersionSupportReport>,
+    /// Whether the target copes with a large ClientHello, when
+    /// [`ScanOptions::diagnose_hello_size`] is set.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub hello_size: Option<HelloSizeReport>,
+    /// Whether the target negotiates alike for HTTP/1.1 and HTTP/2, when
+    /// [`ScanOptions::compare_http_versions`] is set and the target was
+    /// reached.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub http_versions: Option<HttpVersionReport>,
+    /// How the target resumes sessions, when
+    /// [`ScanOptions::check_resumption`] is set and the target was reached.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub resumption: Option<ResumptionReport>,
+    /// The quantum readiness of a reached target, graded with
+    /// [`ScanOptions::readiness_weights`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub readiness: Option<QuantumReadiness>,
+}
+
+/// Why a scan target failed.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScanError {
+    pub category: ErrorCategory,
+    /// The full error with its sources. The URL is left out, since it is in
+    /// [`ScanResult::url`] already.
+    pub message: String,
+    /// Which names the certificate was valid for, when it was not valid
+    /// for the target's.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub name_mismatch: Option<NameMismatch>,
+}
+
+impl ScanError {
+    fn new(error: reqwest::Error) -> Self {
+        Self {
+            category: ErrorCategory::of(&error),
+            name_mismatch: NameMismatch::diagnose(&error),
+            message: error_chain(&error.without_url()),
+        }
+    }
+
+    pub(crate) fn direct(error: TraceError, connecting: bool) -> Self {
+        Self {
+            category: ErrorCategory::of_trace(&error, connecting),
+            message: error_chain(&error),
+            name_mismatch: error.name_mismatch(),
+        }
+    }
+}
+
+impl ScanResult {
+    /// Whether the target was reached: it answered with an HTTP response or,
+    /// for address targets, completed the handshake.
+    pub fn reached(&self) -> bool {
+        self.status.is_some() || self.error.is_none()
+    }
+
+    /// Record the handshake's metadata along with the certificate's validity
+    /// and the readiness grade.
+    fn set_metadata(&mut self, metadata: TlsMetadata, weights: &ReadinessWeights) {
+        if let Some(validity) = metadata.certificate_validity() {
+            self.cert_not_before = Some(validity.not_before);
+            self.cert_not_after = Some(validity.not_after);
+            self.cert_days_remaining = Some(validity.days_remaining(SystemTime::now()));
+        }
+        self.readiness = Some(metadata.readiness(weights));
+        self.metadata = metadata;
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.category, self.message)
+    }
+}
+
+/// Headline numbers for a set of [`ScanResult`]s.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ScanSummary {
+    pub total: usize,
+    /// Targets that answered with an HTTP response, error statuses included,
+    /// or completed the handshake.
+    pub reachable: usize,
+    /// Targets with an error, HTTP error statuses included.
+    pub failed: usize,
+    /// Reachable targets per kind of negotiated key exchange.
+    pub key_exchange: BTreeMap<KeyExchangeKind, usize>,
+    /// Reachable targets whose group was not captured, e.g. because the
+    /// request reused a pooled connection.
+    pub key_exchange_unknown: usize,
+    /// Negotiated groups, most common first.
+    pub groups: Vec<GroupCount>,
+    /// Failed targets per error category, most common first.
+    pub errors: Vec<ErrorCount>,
+    /// Duration of the reachable targets' final attempts; attempts without
+    /// a response are left out so that timeouts do not skew them.
+    pub durations: Option<DurationStats>,
+    /// Reachable targets whose handshake needed a HelloRetryRequest.
+    pub hello_retries: usize,
+    /// The [`hrr_penalty`](TlsMetadata::hrr_penalty) of those targets.
+    pub hrr_penalties: Option<DurationStats>,
+    /// Targets whose resumed handshake ran no key exchange of its own, see
+    /// [`ResumptionKx::PskOnly`].
+    pub psk_only_resumptions: usize,
+    /// Reachable targets per [readiness](ScanResult::readiness) grade.
+    pub grades: BTreeMap<ReadinessGrade, usize>,
+    /// Handshake and request durations per kind of negotiated key
+    /// exchange.
+    pub latency: BTreeMap<KeyExchangeKind, KindLatency>,
+}
+
+/// The durations of the targets that negotiated one kind of key exchange.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct KindLatency {
+    /// Targets that negotiated the kind but failed, e.g. with an HTTP error
+    /// status; their durations are left out.
+    pub failed: usize,
+    /// Handshake durations, where known: for requests only with
+    /// [timings](crate::TlsAwareClientBuilder::collect_timings) collected.
+    pub handshake: Option<LatencyHistogram>,
+    /// Durations of the final attempts.
+    pub total: Option<LatencyHistogram>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GroupCount {
+    pub group: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ErrorCount {
+    pub category: ErrorCategory,
+    pub count: usize,
+}
+
+impl ScanSummary {
+    pub fn from_results(results: &[ScanResult]) -> Self {
+        let mut key_exchange = BTreeMap::new();
+        let mut key_exchange_unknown = 0;
+        let mut groups: HashMap<&str, usize> = HashMap::new();
+        let mut errors: HashMap<&ErrorCategory, usize> = HashMap::new();
+        let mut durations = Vec::new();
+        let mut hrr_penalties = Vec::new();
+        let mut psk_only_resumptions = 0;
+        let mut grades = BTreeMap::new();
+        let mut latency: BTreeMap<KeyExchangeKind, (usize, Vec<Duration>, Vec<Duration>)> =
+            BTreeMap::new();
+
+        for result in results {
+            if let Some(error) = &result.error {
+                *errors.entry(&error.category).or_default() += 1;
+            }
+            if !result.reached() {
+                continue;
+            }
+            durations.push(result.duration);
+            let kind = result.metadata.key_exchange_kind();
+            match kind {
+                Some(kind) => *key_exchange.entry(kind).or_default() += 1,
+                None => key_exchange_unknown += 1,
+            }
+            if let Some(kind) = kind {
+                let (failed, handshakes, totals) = latency.entry(kind).or_default();
+                if result.error.is_some() {
+                    *failed += 1;
+                } else {
+                    let metadata = &result.metadata;
+                    let tls = metadata.timings.and_then(|timings| timings.tls);
+                    handshakes.extend(tls.or(metadata.handshake_duration));
+                    totals.push(result.duration);
+                }
+            }
+            if let Some(group) = &result.metadata.group {
+                *groups.entry(group).or_default() += 1;
+            }
+            hrr_penalties.extend(result.metadata.hrr_penalty);
+            let resumption_kx = result.resumption.as_ref().and_then(ResumptionReport::kx);
+            if resumption_kx.is_some_and(ResumptionKx::is_psk_only) {
+                psk_only_resumptions += 1;
+            }
+            if let Some(readiness) = &result.readiness {
+                *grades.entry(readiness.grade).or_default() += 1;
+            }
+        }
+
+        Self {
+            total: results.len(),
+            reachable: durations.len(),
+            failed: results.iter().filter(|r| r.error.is_some()).count(),
+            key_exchange,
+            key_exchange_unknown,
+            groups: by_count(groups)
+                .map(|(group, count)| GroupCount {
+                    group: group.to_string(),
+                    count,
+                })
+                .collect(),
+            errors: by_count(errors)
+                .map(|(category, count)| ErrorCount {
+                    category: category.clone(),
+                    count,
+                })
+                .collect(),
+            durations: DurationStats::from_samples(&durations),
+            hello_retries: hrr_penalties.len(),
+            hrr_penalties: DurationStats::from_samples(&hrr_penalties),
+            psk_only_resumptions,
+            grades,
+            latency: latency
+                .into_iter()
+                .map(|(kind, (failed, handshakes, totals))| {
+                    let latency = KindLatency {
+                        failed,
+                        handshake: LatencyHistogram::from_samples(&handshakes),
+                        total: LatencyHistogram::from_samples(&totals),
+                    };
+                    (kind, latency)
+                })
+                .collect(),
+        }
+    }
+
+    /// The fraction of reachable targets that negotiated `kind`.
+    pub fn share(&self, kind: KeyExchangeKind) -> f64 {
+        if self.reachable == 0 {
+            return 0.0;
+        }
+        self.key_exchange.get(&kind).copied().unwrap_or_default() as f64 / self.reachable as f64
+    }
+}
+
+/// Most common first, ties in key order so that reports are stable.
+fn by_count<K: Ord>(counts: HashMap<K, usize>) -> impl Iterator<Item = (K, usize)> {
+    let mut counts: Vec<(K, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts.into_iter()
+}
+
+/// `https://` URL for a bare host name (or `host:port`); URLs pass through.
+/// The host `target` is capped by in [`ScanOptions::max_per_host`].
+fn host_key(target: &ScanTarget) -> String {
+    match target {
+        ScanTarget::Url(target) => {
+            let url = idn::normalize_url(&target_url(target)).ok();
+            url.and_then(|url| reqwest::Url::parse(&url).ok())
+                .and_then(|url| url.host_str().map(str::to_string))
+                .unwrap_or_else(|| target.clone())
+        }
+        ScanTarget::Address { addr, .. } => addr.ip().to_string(),
+    }
+}
+
+/// `targets` reordered to take one of every host's in turn, keeping the
+/// order within each host.
+fn in_turns_by_host(
+    targets: impl Iterator<Item = (usize, ScanTarget)>,
+) -> Vec<(usize, ScanTarget)> {
+    let mut hosts: Vec<VecDeque<(usize, ScanTarget)>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for target in targets {
+        let host = host_key(&target.1);
+        let queue = *index.entry(host).or_insert_with(|| {
+            hosts.push(VecDeque::new());
+            hosts.len() - 1
+        });
+        hosts[queue].push_back(target);
+    }
+    let mut ordered = Vec::new();
+    while !hosts.is_empty() {
+        hosts.retain_mut(|queue| {
+            ordered.extend(queue.pop_front());
+            !queue.is_empty()
+        });
+    }
+    ordered
+}
+
+/// `target` in the form every way of writing the same handshake target
+/// shares: `https://` added, the host lowercased and in A-label form, the
+/// default port, user info and fragment dropped, and the path and query
+/// dropped too unless `keep_path` is set, since redirects may differ by
+/// path. Targets that do not parse are returned as given, to fail in the
+/// scan.
+pub fn normalize_target(target: &str, keep_path: bool) -> String {
+    let url = idn::normalize_url(&target_url(target.trim())).ok();
+    let Some(url) = url.and_then(|url| reqwest::Url::parse(&url).ok()) else {
+        return target.to_string();
+    };
+    let Some(host) = url.host_str() else {
+        return target.to_string();
+    };
+    let mut normalized = format!("{}://{}", url.scheme(), host);
+    if let Some(port) = url.port() {
+        normalized.push_str(&format!(":{}", port));
+    }
+    if keep_path && (url.path() != "/" || url.query().is_some()) {
+        normalized.push_str(url.path());
+        if let Some(query) = url.query() {
+            normalized.push('?');
+            normalized.push_str(query);
+        }
+    }
+    normalized
+}
+
+/// Normalize `targets` and merge the ones that are the same target, keeping
+/// the order of first appearance. Every unique target comes with the inputs
+/// it was normalized from.
+pub fn dedupe_targets<I, S>(targets: I, keep_paths: bool) -> Vec<(String, Vec<String>)>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let mut unique: Vec<(String, Vec<String>)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for target in targets {
+        let target = target.into();
+        let normalized = normalize_target(&target, keep_paths);
+        match index.get(&normalized) {
+            Some(&i) => unique[i].1.push(target),
+            None => {
+                index.insert(normalized.clone(), unique.len());
+                unique.push((normalized, vec![target]));
+            }
+        }
+    }
+    unique
+}
+
+/// A request for a URL target that asks the server to close the connection
+/// after the response, since its body is never read.
+fn scan_request(method: ScanMethod, url: reqwest::Url) -> reqwest::Request {
+    let mut request = reqwest::Request::new(method.method(), url);
+    request
+        .headers_mut()
+        .insert(CONNECTION, HeaderValue::from_static("close"));
+    request
+}
+
+/// `https://` URL for a bare host name (or `host:port`); URLs pass through.
+pub fn target_url(target: &str) -> String {
+    if target.contains("://") {
+        target.to_string()
+    } else {
+        format!("https://{}", target)
+    }
+}
+
+struct Limits {
+    rate: Option<TokenBucket>,
+    per_host: Option<HostSpacing>,
+}
+
+impl Limits {
+    async fn acquire(&self, host: &str) {
+        if let Some(per_host) = &self.per_host {
+            per_host.acquire(host).await;
+        }
+        if let Some(rate) = &self.rate {
+            rate.acquire().await;
+        }
+    }
+}
+
+impl TlsAwareClient {
+    /// Trace every target – with a HEAD or GET request, or a bare handshake for
+    /// [`ScanTarget::Address`] – and return the results in input order.
+    ///
+    /// Failed requests are retried up to `options.retries` times; every
+    /// attempt, retries included, passes through the rate limits.
+    pub async fn scan<I, S>(&self, targets: I, options: &ScanOptions) -> Vec<ScanResult>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<ScanTarget>,
+    {
+        self.scan_with(targets, options, |_| {}).await
+    }
+
+    /// Like [`scan`](Self::scan), but calls `on_result` with every result as
+    /// soon as its target is finished, in completion order.
+    pub async fn scan_with<I, S, F>(
+        &self,
+        targets: I,
+        options: &ScanOptions,
+        mut on_result: F,
+    ) -> Vec<ScanResult>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<ScanTarget>,
+        F: FnMut(&ScanResult),
+    {
+        let limits = Limits {
+            rate: options
+                .rate_limit
+                .filter(|rate| *rate > 0.0)
+                .map(TokenBucket::new),
+            per_host: options.per_host_interval.map(HostSpacing::new),
+        };
+        let limits = &limits;
+        let concurrency = options.concurrency.max(1);
+        let per_host = options.max_per_host.map(|max| HostSlots::new(max.max(1)));
+        let per_host = per_host.as_ref();
+        let slots = &Semaphore::new(concurrency);
+
+        let targets = targets.into_iter().map(Into::into).enumerate();
+        // Targets waiting for a slot of their host are kept off the
+        // workers, with some more of them in flight to keep the other
+        // hosts busy meanwhile.
+        let (targets, in_flight) = match per_host {
+            Some(_) => (in_turns_by_host(targets), concurrency.saturating_mul(4)),
+            None => (targets.collect(), concurrency),
+        };
+        let mut results: Vec<(usize, ScanResult)> = stream::iter(targets)
+            .map(|(index, target)| async move {
+                let _host = match per_host {
+                    Some(per_host) => Some(per_host.acquire(&host_key(&target)).await),
+                    None => None,
+                };
+                let _slot = slots
+                    .acquire()
+                    .await
+                    .expect("the semaphore is never closed");
+                let mut result = self.scan_target(target, options, limits).await;
+                result.metadata.labels.clone_from(&options.labels);
+                (index, result)
+            })
+            .buffer_unordered(in_flight)
+            .inspect(|(_, result)| on_result(result))
+            .collect()
+            .await;
+
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    async fn scan_target(
+        &self,
+        target: ScanTarget,
+        options: &ScanOptions,
+        limits: &Limits,
+    ) -> ScanResult {
+        let target = match target {
+            ScanTarget::Url(target) => target,
+            ScanTarget::Address { addr, server_name } => {
+                return self.scan_address(addr, server_name, options, limits).await;
+            }
+        };
+        let url = target_url(&target);
+        let mut result = ScanResult {
+            target,
+            url,
+            sources: Vec::new(),
+            sni: None,
+            status: None,
+            method: None,
+            metadata: TlsMetadata::default(),
+            error: None,
+            attempts: 0,
+            duration: Duration::ZERO,
+            cert_not_before: None,
+            cert_not_after: None,
+            cert_days_remaining: None,
+            versions: None,
+            hello_size: None,
+            http_versions: None,
+            resumption: None,
+            readiness: None,
+        };
+
+        let url = idn::normalize_url(&result.url).map_err(|e| e.to_string());
+        let request = match url.and_then(|url| reqwest::Url::parse(&url).map_err(|e| e.to_string()))
+        {
+            Ok(url) => scan_request(options.method, url),
+            Err(e) => {
+                result.error = Some(ScanError {
+                    category: ErrorCategory::InvalidUrl,
+                    message: e,
+                    name_mismatch: None,
+                });
+                return result;
+            }
+        };
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        // Known before connecting, so that failed scans name the host in
+        // both forms too.
+        result.metadata.idn = idn::IdnName::of(&host);
+
+        while result.attempts <= options.retries {
+            limits.acquire(&host).await;
+            result.attempts += 1;
+
+            let mut method = options.method;
+            let mut start = Instant::now();
+            let (mut attempt, mut fallback) = self
+                .execute_scan_request(
+                    request
+                        .try_clone()
+                        .expect("scan requests have no streaming body"),
+                    &options.labels,
+                )
+                .await;
+            let rejected = attempt.as_ref().is_ok_and(|response| {
+                matches!(
+                    response.response.status(),
+                    StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+                )
+            });
+            if rejected && method == ScanMethod::Head && options.fallback_get {
+                limits.acquire(&host).await;
+                result.attempts += 1;
+                method = ScanMethod::Get;
+                start = Instant::now();
+                (attempt, fallback) = self
+                    .execute_scan_request(
+                        scan_request(method, request.url().clone()),
+                        &options.labels,
+                    )
+                    .await;
+            }
+            result.duration = start.elapsed();
+
+            match attempt {
+                Ok(response) => {
+                    let status = response.response.status();
+                    result.status = Some(status.as_u16());
+                    result.method = Some(method);
+                    result.set_metadata(response.metadata, &options.readiness_weights);
+                    // The handshake worked, so error statuses are recorded
+                    // but not retried.
+                    result.error = response
+                        .response
+                        .error_for_status()
+                        .err()
+                        .map(ScanError::new);
+                    break;
+                }
+                Err(e) => {
+                    result.error = Some(ScanError::new(e));
+                    result.metadata.fallback = fallback;
+                }
+            }
+        }
+
+        let port = request.url().port_or_known_default().unwrap_or(443);
+        let host = host.trim_matches(['[', ']']);
+        if options.probe_versions && result.reached() {
+            limits.acquire(host).await;
+            result.versions = Some(self.probe_tls_versions(host, port).await);
+        }
+        if options.diagnose_hello_size {
+            limits.acquire(host).await;
+            result.hello_size = Some(self.probe_hello_size_tolerance(host, port).await);
+        }
+        if options.compare_http_versions && result.reached() {
+            limits.acquire(host).await;
+            result.http_versions = Some(self.probe_http_versions(host, port).await);
+        }
+        if options.check_resumption && result.reached() {
+            limits.acquire(host).await;
+            result.resumption = Some(self.probe_resumption(host, port).await);
+        }
+        result
+    }
+    /// [`execute_with_fallback`](Self::execute_with_fallback) with the
+    /// scan's labels.
+    async fn execute_scan_request(
+        &self,
+        request: reqwest::Request,
+        labels: &BTreeMap<String, String>,
+    ) -> (Result<TlsResponse, reqwest::Error>, Option<FallbackOutcome>) {
+        let (result, fallback) = self.execute_labelled(request, labels).await;
+        (result.map_err(|e| e.error), fallback)
+    }
+
+    async fn scan_address(
+        &self,
+        addr: SocketAddr,
+        server_name: String,
+        options: &ScanOptions,
+        limits: &Limits,
+    ) -> ScanResult {
+        let mut result = ScanResult {
+            target: addr.to_string(),
+            url: format!("https://{}", addr),
+            sources: Vec::new(),
+            sni: Some(server_name.clone()),
+            status: None,
+            method: None,
+            metadata: TlsMetadata::default(),
+            error: None,
+            attempts: 0,
+            duration: Duration::ZERO,
+            cert_not_before: None,
+            cert_not_after: None,
+            cert_days_remaining: None,
+            versions: None,
+            hello_size: None,
+            http_versions: None,
+            resumption: None,
+            readiness: None,
+        };
+
+        let server_name = idn::to_ascii(&server_name).and_then(|name| {
+            ServerName::try_from(name).map_err(|e| TraceError::InvalidServerName(e.to_string()))
+        });
+        let server_name = match server_name {
+            Ok(name) => name,
+            Err(e) => {
+                result.error = Some(ScanError {
+                    category: ErrorCategory::InvalidUrl,
+                    message: e.to_string(),
+                    name_mismatch: None,
+                });
+                return result;
+            }
+        };
+        let connector = match self.direct_tls_config(None) {
+            Ok(config) => TlsConnector::from(Arc::new(config)),
+            Err(e) => {
+                result.error = Some(ScanError::direct(e, false));
+                return result;
+            }
+        };
+        result.metadata.idn = idn::IdnName::of(&server_name.to_str());
+        let host = addr.ip().to_string();
+
+        while result.attempts <= options.retries {
+            limits.acquire(&host).await;
+            result.attempts += 1;
+
+            let start = Instant::now();
+            let attempt = self
+                .handshake_at(addr, server_name.clone(), &connector)
+                .await;
+            result.duration = start.elapsed();
+
+            match attempt {
+                Ok(metadata) => {
+                    result.set_metadata(metadata, &options.readiness_weights);
+                    result.error = None;
+                    break;
+                }
+                Err((e, connecting)) => result.error = Some(ScanError::direct(e, connecting)),
+            }
+        }
+
+        if options.probe_versions && result.reached() {
+            limits.acquire(&host).await;
+            result.versions = Some(self.probe_tls_versions_at(addr, server_name.clone()).await);
+        }
+        if options.diagnose_hello_size {
+            limits.acquire(&host).await;
+            result.hello_size = Some(
+                self.probe_hello_size_tolerance_at(addr, server_name.clone())
+                    .await,
+            );
+        }
+        if options.compare_http_versions && result.reached() {
+            limits.acquire(&host).await;
+            result.http_versions =
+                Some(self.probe_http_versions_at(addr, server_name.clone()).await);
+        }
+        if options.check_resumption && result.reached() {
+            limits.acquire(&host).await;
+            result.resumption = Some(self.probe_resumption_at(addr, server_name).await);
+        }
+        result
+    }
+
+    /// Connect to `addr` and complete a handshake for `server_name`. Errors
+    /// come with whether they happened before the TCP connection was up.
+    pub(crate) async fn handshake_at(
+        &self,
+        addr: SocketAddr,
+        server_name: ServerName<'static>,
+        connector: &TlsConnector,
+    ) -> Result<TlsMetadata, (TraceError, bool)> {
+        let connect_timeout = self
+            .connect_timeout
+            .or(self.timeout)
+            .unwrap_or(DIRECT_TIMEOUT);
+        let stream = match tokio::time::timeout(connect_timeout, self.bind.connect(addr)).await {
+            Ok(stream) => stream.map_err(|e| (TraceError::from(e), true))?,
+            Err(_) => return Err((TraceError::Timeout, true)),
+        };
+        let local_addr = stream.local_addr().ok();
+
+        let timeout = self.timeout.unwrap_or(DIRECT_TIMEOUT);
+        let start = Instant::now();
+        // Inside the future, so that the ClientHello's key share, started
+        // as soon as the connection is set up, is observed too.
+        let handshake = key_shares::observe(async {
+            connector
+                .connect(
+                    server_name.clone(),
+                    CountingStream::new(stream).deep_inspect(self.deep_inspect),
+                )
+                .await
+        });
+        match tokio::time::timeout(timeout, handshake).await {
+            Ok((tls, key_shares)) => {
+                let tls = tls.map_err(|e| {
+                    logging::failure(&addr.to_string(), &e);
+                    (TraceError::from(e), false)
+                })?;
+                let (stream, conn) = tls.get_ref();
+                let metadata = TlsMetadata {
+                    local_addr,
+                    remote_addr: Some(addr),
+                    idn: idn::IdnName::of(&server_name.to_str()),
+                    handshake_duration: Some(start.elapsed()),
+                    hrr_penalty: key_shares.hrr_penalty(),
+                    resumption_kx: key_shares
+                        .resumption_kx(conn.handshake_kind() == Some(HandshakeKind::Resumed)),
+                    client_cert_requested: Some(key_shares.client_cert_requested()),
+                    client_cert_authorities: key_shares.client_cert_authorities(),
+                    handshake_bytes_sent: Some(stream.bytes_sent()),
+                    handshake_bytes_received: Some(stream.bytes_received()),
+                    alerts_received: stream.alerts_received(),
+                    server_extensions: stream.server_extensions(),
+                    cert_validation: self.cert_validation(&server_name, conn),
+                    pin_matched: self.pin_matched(&server_name, conn),
+                    ..TlsMetadata::from_connection(conn)
+                };
+                self.log_handshake(&addr.to_string(), &metadata);
+                Ok(metadata)
+            }
+            Err(_) => Err((TraceError::Timeout, false)),
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Serialization helpers shared by the `serde` derives.
+
+/// (De)serialize a `Duration` as fractional milliseconds.
+pub(crate) mod millis {
+    use std::time::Duration;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(value.as_secs_f64() * 1000.0)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        let millis = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(millis / 1000.0).map_err(D::Error::custom)
+    }
+}
+
+/// (De)serialize a `SystemTime` as an RFC 3339 UTC timestamp.
+pub(crate) mod rfc3339 {
+    use std::time::SystemTime;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &SystemTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&crate::cert::rfc3339(*value))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<SystemTime, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        crate::cert::parse_rfc3339(&s)
+            .ok_or_else(|| D::Error::custom(format!("invalid timestamp: {}", s)))
+    }
+}
+
+/// (De)serialize an optional `SystemTime` as an RFC 3339 UTC timestamp.
+pub(crate) mod rfc3339_option {
+    use std::time::SystemTime;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &Option<SystemTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(time) => serializer.serialize_str(&crate::cert::rfc3339(*time)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<SystemTime>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(s) => crate::cert::parse_rfc3339(&s)
+                .map(Some)
+                .ok_or_else(|| D::Error::custom(format!("invalid timestamp: {}", s))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// (De)serialize an optional `Duration` as fractional milliseconds.
+pub(crate) mod millis_option {
+    use std::time::Duration;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_f64(value.as_secs_f64() * 1000.0),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        match Option::<f64>::deserialize(deserializer)? {
+            Some(millis) => Duration::try_from_secs_f64(millis / 1000.0)
+                .map(Some)
+                .map_err(D::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+/// (De)serialize a certificate verdict as `{"outcome": "verified"}` or
+/// `{"outcome": "unverified", "problem": …, "detail": …}`, so that results
+/// whose certificate failed verification stand out.
+pub(crate) mod cert_validation {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::{CertError, CertificateProblem, NameMismatch};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(tag = "outcome", rename_all = "kebab-case")]
+    enum Verdict {
+        Verified,
+        Unverified {
+            problem: CertificateProblem,
+            detail: String,
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            name_mismatch: Option<NameMismatch>,
+        },
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &Option<Result<(), CertError>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let verdict = value.as_ref().map(|result| match result {
+            Ok(()) => Verdict::Verified,
+            Err(e) => Verdict::Unverified {
+                problem: e.problem,
+                detail: e.detail.clone(),
+                name_mismatch: e.name_mismatch.clone(),
+            },
+        });
+        verdict.serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Result<(), CertError>>, D::Error> {
+        let verdict = Option::<Verdict>::deserialize(deserializer)?;
+        Ok(verdict.map(|verdict| match verdict {
+            Verdict::Verified => Ok(()),
+            Verdict::Unverified {
+                problem,
+                detail,
+                name_mismatch,
+            } => Err(CertError {
+                problem,
+                detail,
+                name_mismatch,
+            }),
+        }))
+    }
+}
+
+/// (De)serialize how a resumed handshake set up its keys as `{"mode":
+/// "psk-only"}` or `{"mode": "psk-with-group", "group": "X25519MLKEM768",
+/// "group_id": 4588}`; the group is read back from its code point.
+pub(crate) mod resumption_kx {
+    use rustls::NamedGroup;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::ResumptionKx;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(tag = "mode", rename_all = "kebab-case")]
+    enum Mode {
+        PskOnly,
+        PskWithGroup {
+            #[serde(default)]
+            group: String,
+            group_id: u16,
+        },
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &Option<ResumptionKx>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mode = value.map(|kx| match kx {
+            ResumptionKx::PskOnly => Mode::PskOnly,
+            ResumptionKx::PskWithGroup(group) => Mode::PskWithGroup {
+                group: format!("{:?}", group),
+                group_id: u16::from(group),
+            },
+        });
+        mode.serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<ResumptionKx>, D::Error> {
+        let mode = Option::<Mode>::deserialize(deserializer)?;
+        Ok(mode.map(|mode| match mode {
+            Mode::PskOnly => ResumptionKx::PskOnly,
+            Mode::PskWithGroup { group_id, .. } => {
+                ResumptionKx::PskWithGroup(NamedGroup::from(group_id))
+            }
+        }))
+    }
+}
+
+/// (De)serialize bytes as a lowercase hex string.
+#[cfg(feature = "dns")]
+pub(crate) mod hex {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&crate::dns::hex(value))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 {
+            return Err(D::Error::custom("odd number of hex digits"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(D::Error::custom))
+            .collect()
+    }
+}
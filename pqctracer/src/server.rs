@@ -0,0 +1,326 @@
+//! The server side: what inbound clients offer and negotiate against a
+//! rustls server.
+//!
+//! [`TracingAcceptor`] wraps an existing `rustls::ServerConfig` and slots into
+//! a tokio accept loop in place of `tokio_rustls::TlsAcceptor`. With the
+//! `tower` feature, [`InboundTlsLayer`] hands the outcome of each handshake to
+//! the HTTP service serving that connection.
+
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+#[cfg(feature = "tower")]
+use std::task::{Context, Poll};
+
+use rustls::server::{Acceptor, ClientHello, ServerConfig};
+use rustls::{CipherSuite, HandshakeKind, NamedGroup, ProtocolVersion};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::LazyConfigAcceptor;
+use tokio_rustls::server::TlsStream;
+
+use crate::KeyExchangeKind;
+
+type HandshakeCallback = Arc<dyn Fn(&InboundHandshakeInfo) + Send + Sync>;
+
+/// What one inbound client offered and what the server selected.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct InboundHandshakeInfo {
+    /// The client's address, when accepted with
+    /// [`accept_tcp`](TracingAcceptor::accept_tcp).
+    pub peer: Option<SocketAddr>,
+    /// The SNI the client sent.
+    pub server_name: Option<String>,
+    /// Groups listed in the client's `supported_groups` extension, in the
+    /// client's preference order.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "debug_names"))]
+    pub offered_groups: Vec<NamedGroup>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "debug_names"))]
+    pub offered_cipher_suites: Vec<CipherSuite>,
+    /// ALPN protocols offered by the client.
+    pub alpn: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "debug_name"))]
+    pub selected_group: Option<NamedGroup>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "debug_name"))]
+    pub cipher: Option<CipherSuite>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "debug_name"))]
+    pub version: Option<ProtocolVersion>,
+    /// Whether the client resumed an earlier session.
+    pub resumed: bool,
+    /// Set when the handshake did not complete.
+    pub error: Option<String>,
+}
+
+impl InboundHandshakeInfo {
+    fn from_client_hello(hello: &ClientHello<'_>, peer: Option<SocketAddr>) -> Self {
+        Self {
+            peer,
+            server_name: hello.server_name().map(str::to_string),
+            offered_groups: hello.named_groups().unwrap_or_default().to_vec(),
+            offered_cipher_suites: hello.cipher_suites().to_vec(),
+            alpn: hello
+                .alpn()
+                .map(|protocols| {
+                    protocols
+                        .map(|p| String::from_utf8_lossy(p).into_owned())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    /// How quantum-resistant the selected group is, if the handshake got
+    /// that far.
+    pub fn key_exchange_kind(&self) -> Option<KeyExchangeKind> {
+        self.selected_group.map(KeyExchangeKind::of)
+    }
+
+    /// Whether the client offered any hybrid or post-quantum group.
+    pub fn offered_pqc(&self) -> bool {
+        self.offered_groups
+            .iter()
+            .any(|group| KeyExchangeKind::of(*group).is_quantum_resistant())
+    }
+
+    /// The negotiated parameters, for handing to request handlers.
+    pub fn tls_info(&self) -> InboundTlsInfo {
+        InboundTlsInfo {
+            group: self.selected_group,
+            cipher: self.cipher,
+            version: self.version,
+            resumed: self.resumed,
+        }
+    }
+}
+
+impl fmt::Display for InboundHandshakeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.peer {
+            Some(peer) => write!(f, "{}", peer)?,
+            None => f.write_str("-")?,
+        }
+        if let Some(name) = &self.server_name {
+            write!(f, " sni={}", name)?;
+        }
+        write!(f, " offered={:?} selected=", self.offered_groups)?;
+        match self.selected_group {
+            Some(group) => write!(f, "{:?}", group)?,
+            None => f.write_str("none")?,
+        }
+        if let Some(cipher) = self.cipher {
+            write!(f, " cipher={:?}", cipher)?;
+        }
+        if let Some(version) = self.version {
+            write!(f, " version={:?}", version)?;
+        }
+        if self.resumed {
+            f.write_str(" resumed")?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " error={}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// What one inbound connection negotiated: the per-request view of an
+/// [`InboundHandshakeInfo`], shared by every request on the connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct InboundTlsInfo {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "debug_name"))]
+    pub group: Option<NamedGroup>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "debug_name"))]
+    pub cipher: Option<CipherSuite>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "debug_name"))]
+    pub version: Option<ProtocolVersion>,
+    pub resumed: bool,
+}
+
+impl InboundTlsInfo {
+    /// How quantum-resistant the negotiated group is.
+    pub fn key_exchange_kind(&self) -> Option<KeyExchangeKind> {
+        self.group.map(KeyExchangeKind::of)
+    }
+
+    /// The negotiated group's IANA name, e.g. `X25519MLKEM768`, as used
+    /// in [`TlsMetadata::group`](crate::TlsMetadata::group).
+    pub fn group_name(&self) -> Option<String> {
+        self.group.map(|group| format!("{:?}", group))
+    }
+}
+
+#[cfg(feature = "serde")]
+fn debug_names<T: fmt::Debug, S: serde::Serializer>(
+    items: &[T],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(items.iter().map(|item| format!("{:?}", item)))
+}
+
+#[cfg(feature = "serde")]
+fn debug_name<T: fmt::Debug, S: serde::Serializer>(
+    item: &Option<T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match item {
+        Some(item) => serializer.serialize_some(&format!("{:?}", item)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Accepts TLS connections with a fixed `ServerConfig`, recording an
+/// [`InboundHandshakeInfo`] for every one of them.
+///
+/// Cheap to clone; clones share the config and the callback.
+#[derive(Clone)]
+pub struct TracingAcceptor {
+    config: Arc<ServerConfig>,
+    on_handshake: Option<HandshakeCallback>,
+}
+
+impl TracingAcceptor {
+    pub fn new(config: Arc<ServerConfig>) -> Self {
+        Self {
+            config,
+            on_handshake: None,
+        }
+    }
+
+    /// Invoke `callback` for every connection as soon as its handshake has
+    /// completed or failed.
+    pub fn on_handshake(
+        mut self,
+        callback: impl Fn(&InboundHandshakeInfo) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_handshake = Some(Arc::new(callback));
+        self
+    }
+
+    /// Perform the server side of the handshake on `stream`.
+    pub async fn accept<IO>(&self, stream: IO) -> io::Result<(TlsStream<IO>, InboundHandshakeInfo)>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.accept_from(stream, None).await
+    }
+
+    /// Like [`accept`](Self::accept), recording the peer address of `stream`.
+    pub async fn accept_tcp(
+        &self,
+        stream: TcpStream,
+    ) -> io::Result<(TlsStream<TcpStream>, InboundHandshakeInfo)> {
+        let peer = stream.peer_addr().ok();
+        self.accept_from(stream, peer).await
+    }
+
+    async fn accept_from<IO>(
+        &self,
+        stream: IO,
+        peer: Option<SocketAddr>,
+    ) -> io::Result<(TlsStream<IO>, InboundHandshakeInfo)>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        let start = match LazyConfigAcceptor::new(Acceptor::default(), stream).await {
+            Ok(start) => start,
+            Err(e) => {
+                // Not even a ClientHello: there is nothing to report but the
+                // error.
+                let info = InboundHandshakeInfo {
+                    peer,
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                };
+                self.report(&info);
+                return Err(e);
+            }
+        };
+        let mut info = InboundHandshakeInfo::from_client_hello(&start.client_hello(), peer);
+
+        match start.into_stream(self.config.clone()).await {
+            Ok(tls) => {
+                let (_, conn) = tls.get_ref();
+                info.selected_group = conn.negotiated_key_exchange_group().map(|g| g.name());
+                info.cipher = conn.negotiated_cipher_suite().map(|s| s.suite());
+                info.version = conn.protocol_version();
+                info.resumed = conn.handshake_kind() == Some(HandshakeKind::Resumed);
+                self.report(&info);
+                Ok((tls, info))
+            }
+            Err(e) => {
+                info.error = Some(e.to_string());
+                self.report(&info);
+                Err(e)
+            }
+        }
+    }
+
+    fn report(&self, info: &InboundHandshakeInfo) {
+        if let Some(callback) = &self.on_handshake {
+            callback(info);
+        }
+    }
+}
+
+/// A tower layer that inserts one connection's [`InboundTlsInfo`] into the
+/// extensions of every request served on it.
+///
+/// Build one per accepted connection, around the service handed to hyper's
+/// `serve_connection`; HTTP/2 requests multiplexed on the connection all see
+/// the same info. Handlers read it with `req.extensions().get()`, or
+/// `Extension<InboundTlsInfo>` in axum.
+#[cfg(feature = "tower")]
+#[derive(Debug, Clone)]
+pub struct InboundTlsLayer {
+    info: InboundTlsInfo,
+}
+
+#[cfg(feature = "tower")]
+impl InboundTlsLayer {
+    pub fn new(info: InboundTlsInfo) -> Self {
+        Self { info }
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<S> tower_layer::Layer<S> for InboundTlsLayer {
+    type Service = InboundTlsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        InboundTlsService {
+            inner,
+            info: self.info,
+        }
+    }
+}
+
+/// The service produced by [`InboundTlsLayer`].
+#[cfg(feature = "tower")]
+#[derive(Debug, Clone)]
+pub struct InboundTlsService<S> {
+    inner: S,
+    info: InboundTlsInfo,
+}
+
+#[cfg(feature = "tower")]
+impl<S, B> tower_service::Service<http::Request<B>> for InboundTlsService<S>
+where
+    S: tower_service::Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        request.extensions_mut().insert(self.info);
+        self.inner.call(request)
+    }
+}
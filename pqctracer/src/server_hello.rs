@@ -0,0 +1,219 @@
+//! The extensions of the ServerHello of a handshake the tracer drove
+//! itself, read off the wire like the [alerts](crate::alerts).
+//!
+//! The received bytes are followed record by record, and the plaintext
+//! handshake messages among them are put back together until the
+//! ServerHello is complete. In TLS 1.3 everything after it is encrypted, so
+//! the extensions of EncryptedExtensions, such as `early_data` or ALPN,
+//! cannot be seen; a TLS 1.2 server sends all of its extensions in the
+//! ServerHello.
+
+use std::fmt;
+
+use rustls::NamedGroup;
+
+use crate::hello::Reader;
+
+const CONTENT_TYPE_CHANGE_CIPHER_SPEC: u8 = 20;
+const CONTENT_TYPE_HANDSHAKE: u8 = 22;
+const HANDSHAKE_SERVER_HELLO: u8 = 2;
+/// Record header: content type, legacy version and payload length.
+const RECORD_HEADER_LEN: usize = 5;
+/// Handshake header: message type and a 24-bit length.
+const HANDSHAKE_HEADER_LEN: usize = 4;
+/// The `random` of a ServerHello that is a HelloRetryRequest (RFC 8446,
+/// section 4.1.3).
+const HELLO_RETRY_REQUEST_RANDOM: [u8; 32] = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
+
+const EXT_ALPN: u16 = 16;
+const EXT_PRE_SHARED_KEY: u16 = 41;
+const EXT_SUPPORTED_VERSIONS: u16 = 43;
+const EXT_KEY_SHARE: u16 = 51;
+
+/// One extension of the server's ServerHello.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtensionInfo {
+    /// The extension's code point, e.g. `51` for `key_share`.
+    pub id: u16,
+    /// The extension's name as RFC 8446 and its predecessors spell it, e.g.
+    /// `key_share`, or `unknown` for code points the tracer does not know.
+    pub name: String,
+    /// What the server selected, for the extensions that say: the group of
+    /// `key_share`, the version of `supported_versions`, the identity of
+    /// `pre_shared_key` and the protocol of a TLS 1.2 ALPN extension.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub detail: Option<String>,
+}
+
+/// `key_share (X25519MLKEM768)` or `extended_master_secret`.
+impl fmt::Display for ExtensionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.detail {
+            Some(detail) => write!(f, "{} ({})", self.name, detail),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// Follows the records in a stream of received bytes and parses the
+/// extensions of the ServerHello among them. After a HelloRetryRequest it
+/// keeps going, so that the ServerHello that follows replaces it.
+#[derive(Default)]
+pub(crate) struct ServerHelloScanner {
+    header: [u8; RECORD_HEADER_LEN],
+    header_len: usize,
+    /// Payload bytes left in the current record.
+    remaining: usize,
+    /// Whether the current record carries plaintext handshake messages.
+    handshake: bool,
+    /// Handshake messages received so far, not yet parsed.
+    messages: Vec<u8>,
+    /// Set once encryption started or a ServerHello was parsed.
+    done: bool,
+    extensions: Option<Vec<ExtensionInfo>>,
+}
+
+impl ServerHelloScanner {
+    /// Follow `bytes`, the next bytes received.
+    pub(crate) fn feed(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() && !self.done {
+            if self.remaining == 0 {
+                let n = (RECORD_HEADER_LEN - self.header_len).min(bytes.len());
+                self.header[self.header_len..self.header_len + n].copy_from_slice(&bytes[..n]);
+                self.header_len += n;
+                bytes = &bytes[n..];
+                if self.header_len == RECORD_HEADER_LEN {
+                    self.header_len = 0;
+                    self.remaining = u16::from_be_bytes([self.header[3], self.header[4]]) as usize;
+                    self.handshake = self.header[0] == CONTENT_TYPE_HANDSHAKE;
+                    // A TLS 1.3 server sends a ChangeCipherSpec for
+                    // middlebox compatibility only after a HelloRetryRequest
+                    // or its ServerHello, and encrypts everything else.
+                    self.done =
+                        !self.handshake && self.header[0] != CONTENT_TYPE_CHANGE_CIPHER_SPEC;
+                }
+                continue;
+            }
+
+            let n = self.remaining.min(bytes.len());
+            if self.handshake {
+                self.messages.extend_from_slice(&bytes[..n]);
+                self.parse_messages();
+            }
+            self.remaining -= n;
+            bytes = &bytes[n..];
+        }
+    }
+
+    /// Parse the complete handshake messages received so far.
+    fn parse_messages(&mut self) {
+        while self.messages.len() >= HANDSHAKE_HEADER_LEN && !self.done {
+            let len = u32::from_be_bytes([0, self.messages[1], self.messages[2], self.messages[3]])
+                as usize;
+            let end = HANDSHAKE_HEADER_LEN + len;
+            if self.messages.len() < end {
+                return;
+            }
+            if self.messages[0] == HANDSHAKE_SERVER_HELLO {
+                let body = &self.messages[HANDSHAKE_HEADER_LEN..end];
+                if let Some((retry, extensions)) = parse_server_hello(body) {
+                    self.extensions = Some(extensions);
+                    self.done = !retry;
+                }
+            }
+            self.messages.drain(..end);
+        }
+    }
+
+    /// The extensions of the last ServerHello, if one was received.
+    pub(crate) fn extensions(&self) -> Option<Vec<ExtensionInfo>> {
+        self.extensions.clone()
+    }
+}
+
+/// Whether `body` is a HelloRetryRequest, and its extensions.
+fn parse_server_hello(body: &[u8]) -> Option<(bool, Vec<ExtensionInfo>)> {
+    let mut reader = Reader::new(body);
+    reader.take(2)?; // legacy_version
+    let retry = reader.take(32)? == HELLO_RETRY_REQUEST_RANDOM;
+    let session_id_len = reader.u8()?;
+    reader.take(session_id_len as usize)?;
+    reader.take(2)?; // cipher_suite
+    reader.take(1)?; // legacy_compression_method
+    let mut extensions = Vec::new();
+    // Servers answering a ClientHello without extensions may send none.
+    if reader.is_empty() {
+        return Some((retry, extensions));
+    }
+    let len = reader.u16()?;
+    let mut list = Reader::new(reader.take(len as usize)?);
+    while !list.is_empty() {
+        let id = list.u16()?;
+        let len = list.u16()?;
+        let data = list.take(len as usize)?;
+        extensions.push(ExtensionInfo {
+            id,
+            name: extension_name(id).to_string(),
+            detail: extension_detail(id, data, retry),
+        });
+    }
+    Some((retry, extensions))
+}
+
+/// What the server selected in the extension `id` with `data`.
+fn extension_detail(id: u16, data: &[u8], retry: bool) -> Option<String> {
+    let mut reader = Reader::new(data);
+    match id {
+        // A HelloRetryRequest names only the group it wants a share for.
+        EXT_KEY_SHARE => {
+            let group = NamedGroup::from(reader.u16()?);
+            Some(match retry {
+                true => format!("{:?} requested", group),
+                false => format!("{:?}", group),
+            })
+        }
+        EXT_SUPPORTED_VERSIONS => Some(match reader.u16()? {
+            0x0304 => "TLS1.3".to_string(),
+            0x0303 => "TLS1.2".to_string(),
+            other => format!("{:#06x}", other),
+        }),
+        EXT_PRE_SHARED_KEY => Some(format!("identity {}", reader.u16()?)),
+        EXT_ALPN => {
+            reader.u16()?; // the list's length
+            let len = reader.u8()?;
+            let protocol = reader.take(len as usize)?;
+            Some(String::from_utf8_lossy(protocol).into_owned())
+        }
+        _ => None,
+    }
+}
+
+/// The name of an extension code point from the IANA registry.
+fn extension_name(id: u16) -> &'static str {
+    match id {
+        0 => "server_name",
+        5 => "status_request",
+        10 => "supported_groups",
+        11 => "ec_point_formats",
+        16 => "application_layer_protocol_negotiation",
+        18 => "signed_certificate_timestamp",
+        22 => "encrypt_then_mac",
+        23 => "extended_master_secret",
+        35 => "session_ticket",
+        41 => "pre_shared_key",
+        42 => "early_data",
+        43 => "supported_versions",
+        44 => "cookie",
+        51 => "key_share",
+        65037 => "encrypted_client_hello",
+        65281 => "renegotiation_info",
+        _ => "unknown",
+    }
+}
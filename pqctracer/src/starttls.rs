@@ -0,0 +1,288 @@
+//! TLS probing for mail protocols that upgrade a plaintext connection with
+//! STARTTLS (SMTP, IMAP) or STLS (POP3).
+
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rustls_pki_types::ServerName;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+use crate::counting::CountingStream;
+use crate::{TlsAwareClient, TlsMetadata, TraceError, idn, key_shares, logging};
+
+/// Upper bound for each network step of a probe (connect, every reply,
+/// the TLS handshake).
+const STEP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Mail protocols that negotiate TLS in-band.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Smtp,
+    Imap,
+    Pop3,
+}
+
+impl Protocol {
+    /// The port the protocol's STARTTLS variant conventionally listens on.
+    pub fn default_port(self) -> u16 {
+        match self {
+            Protocol::Smtp => 25,
+            Protocol::Imap => 143,
+            Protocol::Pop3 => 110,
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Protocol::Smtp => "SMTP",
+            Protocol::Imap => "IMAP",
+            Protocol::Pop3 => "POP3",
+        })
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "smtp" => Ok(Protocol::Smtp),
+            "imap" => Ok(Protocol::Imap),
+            "pop3" | "pop" => Ok(Protocol::Pop3),
+            other => Err(format!("unknown STARTTLS protocol: {}", other)),
+        }
+    }
+}
+
+/// Perform the plaintext preamble for `protocol`, upgrade the connection and
+/// report the negotiated TLS parameters, using a default [`TlsAwareClient`].
+pub async fn probe_starttls(
+    host: &str,
+    port: u16,
+    protocol: Protocol,
+) -> Result<TlsMetadata, TraceError> {
+    TlsAwareClient::new()
+        .probe_starttls(host, port, protocol)
+        .await
+}
+
+impl TlsAwareClient {
+    /// Perform the plaintext preamble for `protocol`, upgrade the connection
+    /// with this client's TLS configuration and report the negotiated TLS
+    /// parameters.
+    pub async fn probe_starttls(
+        &self,
+        host: &str,
+        port: u16,
+        protocol: Protocol,
+    ) -> Result<TlsMetadata, TraceError> {
+        let host = &idn::to_ascii(host)?;
+        let server_name = ServerName::try_from(host.to_string())
+            .map_err(|_| TraceError::InvalidServerName(host.to_string()))?;
+
+        let stream = step(self.bind.connect_host(host, port)).await??;
+        let (local_addr, remote_addr) = (stream.local_addr().ok(), stream.peer_addr().ok());
+        let mut conn = PlainConnection {
+            reader: BufReader::new(stream),
+            protocol,
+        };
+
+        match protocol {
+            Protocol::Smtp => conn.smtp_preamble().await?,
+            Protocol::Imap => conn.imap_preamble().await?,
+            Protocol::Pop3 => conn.pop3_preamble().await?,
+        }
+
+        // Anything the server sent after accepting STARTTLS would be read as
+        // plaintext ahead of the handshake – refuse rather than mix them.
+        if !conn.reader.buffer().is_empty() {
+            return Err(TraceError::StartTlsRejected {
+                protocol,
+                reply: "unexpected data after STARTTLS response".to_string(),
+            });
+        }
+        let stream = conn.reader.into_inner();
+
+        let connector = TlsConnector::from(Arc::new(self.direct_tls_config(None)?));
+        let start = Instant::now();
+        let (tls, key_shares) = step(key_shares::observe(async {
+            connector
+                .connect(
+                    server_name.clone(),
+                    CountingStream::new(stream).deep_inspect(self.deep_inspect),
+                )
+                .await
+        }))
+        .await?;
+        let tls = tls.inspect_err(|e| logging::failure(host, e))?;
+        let (stream, conn) = tls.get_ref();
+        let metadata = TlsMetadata {
+            local_addr,
+            remote_addr,
+            idn: idn::IdnName::of(host),
+            handshake_duration: Some(start.elapsed()),
+            hrr_penalty: key_shares.hrr_penalty(),
+            client_cert_requested: Some(key_shares.client_cert_requested()),
+            client_cert_authorities: key_shares.client_cert_authorities(),
+            handshake_bytes_sent: Some(stream.bytes_sent()),
+            handshake_bytes_received: Some(stream.bytes_received()),
+            alerts_received: stream.alerts_received(),
+            server_extensions: stream.server_extensions(),
+            cert_validation: self.cert_validation(&server_name, conn),
+            pin_matched: self.pin_matched(&server_name, conn),
+            ..TlsMetadata::from_connection(conn)
+        };
+        self.log_handshake(host, &metadata);
+        #[cfg(feature = "dns")]
+        let metadata = match &self.dns {
+            Some(dns) => TlsMetadata {
+                dane: dns.dane(host, port, &metadata).await,
+                ..metadata
+            },
+            None => metadata,
+        };
+        Ok(metadata)
+    }
+}
+
+async fn step<F: Future>(future: F) -> Result<F::Output, TraceError> {
+    tokio::time::timeout(STEP_TIMEOUT, future)
+        .await
+        .map_err(|_| TraceError::Timeout)
+}
+
+struct PlainConnection {
+    reader: BufReader<TcpStream>,
+    protocol: Protocol,
+}
+
+impl PlainConnection {
+    async fn smtp_preamble(&mut self) -> Result<(), TraceError> {
+        self.expect_smtp_reply("220").await?;
+        self.send("EHLO pqctracer.invalid\r\n").await?;
+        let capabilities = self.expect_smtp_reply("250").await?;
+        // Reply lines look like "250-STARTTLS" / "250 STARTTLS".
+        let advertised = capabilities.iter().any(|line| {
+            line.get(4..)
+                .is_some_and(|cap| cap.trim().eq_ignore_ascii_case("STARTTLS"))
+        });
+        if !advertised {
+            return Err(self.not_supported());
+        }
+        self.send("STARTTLS\r\n").await?;
+        self.expect_smtp_reply("220").await?;
+        Ok(())
+    }
+
+    async fn imap_preamble(&mut self) -> Result<(), TraceError> {
+        let greeting = self.read_line().await?;
+        if !greeting.starts_with("* OK") {
+            return Err(self.rejected(greeting));
+        }
+        self.send("a1 CAPABILITY\r\n").await?;
+        let mut advertised = false;
+        loop {
+            let line = self.read_line().await?;
+            if line.starts_with("* CAPABILITY") {
+                advertised |= line
+                    .split_ascii_whitespace()
+                    .any(|cap| cap.eq_ignore_ascii_case("STARTTLS"));
+            } else if line.starts_with("a1 ") {
+                if !line.starts_with("a1 OK") {
+                    return Err(self.rejected(line));
+                }
+                break;
+            }
+        }
+        if !advertised {
+            return Err(self.not_supported());
+        }
+        self.send("a2 STARTTLS\r\n").await?;
+        let reply = self.read_line().await?;
+        if !reply.starts_with("a2 OK") {
+            return Err(self.rejected(reply));
+        }
+        Ok(())
+    }
+
+    async fn pop3_preamble(&mut self) -> Result<(), TraceError> {
+        let greeting = self.read_line().await?;
+        if !greeting.starts_with("+OK") {
+            return Err(self.rejected(greeting));
+        }
+        self.send("CAPA\r\n").await?;
+        let reply = self.read_line().await?;
+        if !reply.starts_with("+OK") {
+            // Servers without CAPA (RFC 2449) cannot advertise STLS either.
+            return Err(self.not_supported());
+        }
+        let mut advertised = false;
+        loop {
+            let line = self.read_line().await?;
+            if line == "." {
+                break;
+            }
+            advertised |= line.eq_ignore_ascii_case("STLS");
+        }
+        if !advertised {
+            return Err(self.not_supported());
+        }
+        self.send("STLS\r\n").await?;
+        let reply = self.read_line().await?;
+        if !reply.starts_with("+OK") {
+            return Err(self.rejected(reply));
+        }
+        Ok(())
+    }
+
+    /// Read a (possibly multi-line) SMTP reply and check its status code.
+    async fn expect_smtp_reply(&mut self, code: &str) -> Result<Vec<String>, TraceError> {
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_line().await?;
+            let last = line.as_bytes().get(3) != Some(&b'-');
+            if !line.starts_with(code) {
+                return Err(self.rejected(line));
+            }
+            lines.push(line);
+            if last {
+                return Ok(lines);
+            }
+        }
+    }
+
+    async fn read_line(&mut self) -> Result<String, TraceError> {
+        let mut line = Vec::new();
+        let n = step(self.reader.read_until(b'\n', &mut line)).await??;
+        if n == 0 {
+            return Err(self.rejected("connection closed".to_string()));
+        }
+        let line = String::from_utf8_lossy(&line);
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    async fn send(&mut self, command: &str) -> Result<(), TraceError> {
+        step(self.reader.get_mut().write_all(command.as_bytes())).await??;
+        Ok(())
+    }
+
+    fn not_supported(&self) -> TraceError {
+        TraceError::StartTlsNotSupported {
+            protocol: self.protocol,
+        }
+    }
+
+    fn rejected(&self, reply: String) -> TraceError {
+        TraceError::StartTlsRejected {
+            protocol: self.protocol,
+            reply,
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Summary statistics over handshake and request durations.
+
+use std::time::Duration;
+
+/// Order statistics of a set of durations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DurationStats {
+    pub count: usize,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "min_ms", with = "crate::serde_util::millis")
+    )]
+    pub min: Duration,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "median_ms", with = "crate::serde_util::millis")
+    )]
+    pub median: Duration,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "p95_ms", with = "crate::serde_util::millis")
+    )]
+    pub p95: Duration,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "max_ms", with = "crate::serde_util::millis")
+    )]
+    pub max: Duration,
+}
+
+impl DurationStats {
+    /// Compute the statistics of `samples`, or `None` if there are none.
+    pub fn from_samples(samples: &[Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        Some(Self {
+            count: sorted.len(),
+            min: sorted[0],
+            median: percentile(&sorted, 50.0),
+            p95: percentile(&sorted, 95.0),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+/// Inclusive upper bounds of the [`LatencyHistogram`] buckets, in
+/// milliseconds; one more bucket takes everything slower.
+const BUCKET_BOUNDS_MS: [u64; 13] = [1, 2, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// Percentiles of a set of durations, with how many fall into each of a
+/// fixed set of buckets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LatencyHistogram {
+    pub count: usize,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "p50_ms", with = "crate::serde_util::millis")
+    )]
+    pub p50: Duration,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "p90_ms", with = "crate::serde_util::millis")
+    )]
+    pub p90: Duration,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "p95_ms", with = "crate::serde_util::millis")
+    )]
+    pub p95: Duration,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "p99_ms", with = "crate::serde_util::millis")
+    )]
+    pub p99: Duration,
+    /// Every bucket, empty ones included, fastest first.
+    pub buckets: Vec<HistogramBucket>,
+}
+
+/// The durations of a [`LatencyHistogram`] up to `le`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HistogramBucket {
+    /// Inclusive upper bound, above the previous bucket's; `None` for the
+    /// last bucket, which takes everything slower.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "le_ms", with = "crate::serde_util::millis_option")
+    )]
+    pub le: Option<Duration>,
+    pub count: usize,
+}
+
+impl LatencyHistogram {
+    /// Compute the histogram of `samples`, or `None` if there are none.
+    pub fn from_samples(samples: &[Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let mut buckets: Vec<HistogramBucket> = BUCKET_BOUNDS_MS
+            .iter()
+            .map(|&ms| Some(Duration::from_millis(ms)))
+            .chain([None])
+            .map(|le| HistogramBucket { le, count: 0 })
+            .collect();
+        for sample in &sorted {
+            let bucket = buckets
+                .iter_mut()
+                .find(|bucket| bucket.le.is_none_or(|le| *sample <= le))
+                .expect("the last bucket is unbounded");
+            bucket.count += 1;
+        }
+        Some(Self {
+            count: sorted.len(),
+            p50: percentile(&sorted, 50.0),
+            p90: percentile(&sorted, 90.0),
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+            buckets,
+        })
+    }
+}
+
+/// The nearest-rank `p`th percentile of an ascending, non-empty slice.
+pub(crate) fn percentile<T: Copy>(sorted: &[T], p: f64) -> T {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(values: &[u64]) -> Vec<Duration> {
+        values.iter().copied().map(Duration::from_millis).collect()
+    }
+
+    #[test]
+    fn percentiles_take_the_nearest_rank() {
+        let sorted: Vec<u32> = (1..=10).collect();
+        let cases = [
+            (0.0, 1),
+            (1.0, 1),
+            (10.0, 1),
+            (11.0, 2),
+            (50.0, 5),
+            (51.0, 6),
+            (90.0, 9),
+            (95.0, 10),
+            (99.0, 10),
+            (100.0, 10),
+        ];
+        for (p, expected) in cases {
+            assert_eq!(percentile(&sorted, p), expected, "p{}", p);
+        }
+        assert_eq!(percentile(&[7], 0.0), 7);
+        assert_eq!(percentile(&[7], 99.0), 7);
+        // Of the 20 values 1..=20, p95 is the 19th and p99 the 20th.
+        let twenty: Vec<u32> = (1..=20).collect();
+        assert_eq!(percentile(&twenty, 95.0), 19);
+        assert_eq!(percentile(&twenty, 99.0), 20);
+    }
+
+    #[test]
+    fn duration_stats_of_unsorted_samples() {
+        assert_eq!(DurationStats::from_samples(&[]), None);
+        let stats = DurationStats::from_samples(&ms(&[30, 10, 50, 20, 40])).expect("stats");
+        assert_eq!(
+            stats,
+            DurationStats {
+                count: 5,
+                min: Duration::from_millis(10),
+                median: Duration::from_millis(30),
+                p95: Duration::from_millis(50),
+                max: Duration::from_millis(50),
+            }
+        );
+        // An even count takes the lower middle value, not an average.
+        let stats = DurationStats::from_samples(&ms(&[4, 1, 3, 2])).expect("stats");
+        assert_eq!(stats.median, Duration::from_millis(2));
+    }
+
+    #[test]
+    fn histograms_bucket_by_inclusive_upper_bound() {
+        assert_eq!(LatencyHistogram::from_samples(&[]), None);
+        let samples = ms(&[1, 2, 3, 5, 6, 10_000, 10_001, 60_000]);
+        let histogram = LatencyHistogram::from_samples(&samples).expect("histogram");
+        assert_eq!(histogram.count, 8);
+        assert_eq!(histogram.buckets.len(), BUCKET_BOUNDS_MS.len() + 1);
+        let counts: Vec<(Option<u128>, usize)> = histogram
+            .buckets
+            .iter()
+            .filter(|bucket| bucket.count > 0)
+            .map(|bucket| (bucket.le.map(|le| le.as_millis()), bucket.count))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                (Some(1), 1),
+                (Some(2), 1),
+                (Some(5), 2),
+                (Some(10), 1),
+                (Some(10_000), 1),
+                (None, 2)
+            ]
+        );
+        let total: usize = histogram.buckets.iter().map(|bucket| bucket.count).sum();
+        assert_eq!(total, samples.len());
+        assert_eq!(histogram.p50, Duration::from_millis(5));
+        assert_eq!(histogram.p90, Duration::from_millis(60_000));
+        assert_eq!(histogram.p99, Duration::from_millis(60_000));
+    }
+
+    #[test]
+    fn a_hundred_samples_give_the_textbook_percentiles() {
+        let samples: Vec<Duration> = (1..=100).rev().map(Duration::from_millis).collect();
+        let histogram = LatencyHistogram::from_samples(&samples).expect("histogram");
+        assert_eq!(
+            [histogram.p50, histogram.p90, histogram.p95, histogram.p99],
+            [50, 90, 95, 99].map(Duration::from_millis)
+        );
+        let stats = DurationStats::from_samples(&samples).expect("stats");
+        assert_eq!(
+            (stats.min, stats.median, stats.p95, stats.max),
+            (
+                Duration::from_millis(1),
+                Duration::from_millis(50),
+                Duration::from_millis(95),
+                Duration::from_millis(100)
+            )
+        );
+    }
+}
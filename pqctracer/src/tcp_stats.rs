@@ -0,0 +1,114 @@
+//! The kernel's byte counters of an established TCP connection, so that
+//! HTTP requests, whose sockets reqwest keeps to itself, report the bytes
+//! their handshake took like the handshakes the tracer drives itself.
+//!
+//! The [connector layer](crate::connections) only learns a new
+//! connection's addresses, once its handshake is done and before hyper
+//! writes the request. The socket is found among the process's open file
+//! descriptors by those addresses and asked for its `TCP_INFO`, which costs
+//! a few system calls per open descriptor and new connection. Only Linux
+//! has both; elsewhere the counts stay unknown.
+
+use std::net::SocketAddr;
+
+/// Bytes written to and read from the TCP connection between `local` and
+/// `remote` so far, if it is one of this process's sockets.
+#[cfg(target_os = "linux")]
+pub(crate) fn bytes_transferred(local: SocketAddr, remote: SocketAddr) -> Option<(u64, u64)> {
+    std::fs::read_dir("/proc/self/fd")
+        .ok()?
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+        .find(|fd| linux::addresses(*fd) == Some((local, remote)))
+        .and_then(linux::bytes_transferred)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn bytes_transferred(_local: SocketAddr, _remote: SocketAddr) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::mem;
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+    use std::os::fd::RawFd;
+
+    /// Offsets into `struct tcp_info` of the counters used, all there
+    /// since Linux 4.19.
+    const NOTSENT_BYTES: usize = 144;
+    const BYTES_RECEIVED: usize = 128;
+    const BYTES_SENT: usize = 200;
+    const BYTES_RETRANS: usize = 208;
+
+    /// The local and remote address of `fd`, if it is a connected IP
+    /// socket. A descriptor closed or reused meanwhile only makes the calls
+    /// fail or answer for another socket, which then does not match.
+    pub(super) fn addresses(fd: RawFd) -> Option<(SocketAddr, SocketAddr)> {
+        let local = address(fd, libc::getsockname)?;
+        let remote = address(fd, libc::getpeername)?;
+        Some((local, remote))
+    }
+
+    fn address(
+        fd: RawFd,
+        get: unsafe extern "C" fn(
+            libc::c_int,
+            *mut libc::sockaddr,
+            *mut libc::socklen_t,
+        ) -> libc::c_int,
+    ) -> Option<SocketAddr> {
+        // SAFETY: a zeroed `sockaddr_storage` is valid, and `get` writes at
+        // most `len` bytes into it.
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        let done = unsafe { get(fd, (&raw mut storage).cast(), &mut len) };
+        if done != 0 {
+            return None;
+        }
+        match libc::c_int::from(storage.ss_family) {
+            libc::AF_INET => {
+                // SAFETY: the family says the storage holds a `sockaddr_in`.
+                let addr: libc::sockaddr_in = unsafe { *(&raw const storage).cast() };
+                let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+                Some(SocketAddr::new(ip.into(), u16::from_be(addr.sin_port)))
+            }
+            libc::AF_INET6 => {
+                // SAFETY: the family says the storage holds a `sockaddr_in6`.
+                let addr: libc::sockaddr_in6 = unsafe { *(&raw const storage).cast() };
+                Some(SocketAddr::V6(SocketAddrV6::new(
+                    Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                    u16::from_be(addr.sin6_port),
+                    addr.sin6_flowinfo,
+                    addr.sin6_scope_id,
+                )))
+            }
+            _ => None,
+        }
+    }
+
+    /// The bytes the application wrote to – sent once, or still queued –
+    /// and the bytes the peer sent on `fd`.
+    pub(super) fn bytes_transferred(fd: RawFd) -> Option<(u64, u64)> {
+        let mut info = [0u8; 256];
+        let mut len = info.len() as libc::socklen_t;
+        // SAFETY: the kernel writes at most `len` bytes into `info`.
+        let done = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::IPPROTO_TCP,
+                libc::TCP_INFO,
+                info.as_mut_ptr().cast(),
+                &mut len,
+            )
+        };
+        if done != 0 || (len as usize) < BYTES_RETRANS + 8 {
+            return None;
+        }
+        let u64_at = |at: usize| u64::from_ne_bytes(info[at..at + 8].try_into().unwrap());
+        let u32_at = |at: usize| u32::from_ne_bytes(info[at..at + 4].try_into().unwrap());
+        let sent = u64_at(BYTES_SENT).saturating_sub(u64_at(BYTES_RETRANS))
+            + u64::from(u32_at(NOTSENT_BYTES));
+        Some((sent, u64_at(BYTES_RECEIVED)))
+    }
+}
@@ -0,0 +1,923 @@
+//! A local rustls server for verifying what the client actually offers.
+//!
+//! `TestTlsServer` accepts TLS connections with a freshly generated self-signed
+//! certificate and a configurable set of key-exchange groups, records every
+//! inbound ClientHello together with the group the server selected, and
+//! answers each HTTP/1.1 request with a short plain-text summary. Protocol
+//! versions, certificate names and validity, ticket issuance, a warning
+//! alert ahead of the handshake, a STARTTLS mail preamble and the HTTP
+//! responder can all be configured. It backs the `serve-test` CLI subcommand
+//! and is equally usable from integration tests, here and downstream.
+
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use rustls::client::Resumption;
+use rustls::crypto::{CryptoProvider, SupportedKxGroup, aws_lc_rs};
+use rustls::server::{Acceptor, ServerConfig};
+use rustls::{AlertDescription, CipherSuite, HandshakeKind, NamedGroup, ProtocolVersion};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName};
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadBuf,
+};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_rustls::{LazyConfigAcceptor, TlsAcceptor, TlsConnector};
+
+use crate::starttls::Protocol;
+use crate::{Captured, CapturingSessionStore, TlsMetadata, TraceError, capture, hello};
+
+type HandshakeCallback = Arc<dyn Fn(&ObservedHandshake) + Send + Sync>;
+type HttpHandler = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// The only application protocol the server speaks.
+const HTTP_1_1: &[u8] = b"http/1.1";
+
+/// Look up a key-exchange group supported by the aws-lc-rs provider by its
+/// name, ignoring case (`x25519mlkem768`, `x25519`, `secp256r1`, …).
+pub fn kx_group_by_name(name: &str) -> Option<NamedGroup> {
+    aws_lc_rs::ALL_KX_GROUPS
+        .iter()
+        .map(|group| group.name())
+        .find(|group| format!("{:?}", group).eq_ignore_ascii_case(name.trim()))
+}
+
+/// What the server saw and decided for one inbound connection.
+#[derive(Debug, Clone)]
+pub struct ObservedHandshake {
+    pub peer: SocketAddr,
+    /// Groups listed in the client's `supported_groups` extension.
+    pub offered_groups: Vec<NamedGroup>,
+    /// Groups the client sent a key share for in its first ClientHello.
+    pub key_shares: Vec<NamedGroup>,
+    pub selected_group: Option<NamedGroup>,
+    pub hello_retry: bool,
+    pub version: Option<ProtocolVersion>,
+    pub cipher: Option<CipherSuite>,
+    /// Set when the handshake did not complete.
+    pub error: Option<String>,
+}
+
+impl fmt::Display for ObservedHandshake {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} offered={:?} key_shares={:?} selected=",
+            self.peer, self.offered_groups, self.key_shares
+        )?;
+        match self.selected_group {
+            Some(group) => write!(f, "{:?}", group)?,
+            None => write!(f, "none")?,
+        }
+        if self.hello_retry {
+            write!(f, " (HelloRetryRequest)")?;
+        }
+        if let Some(version) = self.version {
+            write!(f, " version={:?}", version)?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " error={}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Configures and starts a [`TestTlsServer`].
+pub struct TestTlsServerBuilder {
+    groups: Vec<NamedGroup>,
+    force_hello_retry: bool,
+    reject_unknown_groups: bool,
+    max_hello_len: Option<usize>,
+    versions: Option<Vec<ProtocolVersion>>,
+    names: Vec<String>,
+    validity: Option<(SystemTime, SystemTime)>,
+    intermediates: usize,
+    tls13_tickets: Option<usize>,
+    warning_alert: Option<AlertDescription>,
+    starttls: Option<Protocol>,
+    http: bool,
+    http_handler: Option<HttpHandler>,
+    keep_alive: bool,
+    on_handshake: Option<HandshakeCallback>,
+}
+
+impl TestTlsServerBuilder {
+    /// Restrict the key-exchange groups the server accepts, in preference
+    /// order. Defaults to every group the aws-lc-rs provider supports.
+    pub fn groups(mut self, groups: impl IntoIterator<Item = NamedGroup>) -> Self {
+        self.groups = groups.into_iter().collect();
+        self
+    }
+
+    /// Refuse every group the client sent a key share for, so that the
+    /// handshake needs a HelloRetryRequest whenever the client lists another
+    /// accepted group in `supported_groups`.
+    pub fn force_hello_retry(mut self, force: bool) -> Self {
+        self.force_hello_retry = force;
+        self
+    }
+
+    /// Abort with a fatal `handshake_failure` alert when the ClientHello
+    /// lists any group the server does not accept, like servers and
+    /// middleboxes that choke on unknown hybrid groups.
+    pub fn reject_unknown_groups(mut self, reject: bool) -> Self {
+        self.reject_unknown_groups = reject;
+        self
+    }
+
+    /// Close the connection without a word when the ClientHello records
+    /// take more than `len` bytes, like middleboxes that drop ClientHellos
+    /// spanning more than one TCP segment.
+    pub fn max_hello_len(mut self, len: usize) -> Self {
+        self.max_hello_len = Some(len);
+        self
+    }
+
+    /// Only accept TLS 1.2 handshakes.
+    pub fn tls12_only(mut self, tls12_only: bool) -> Self {
+        self.versions = tls12_only.then(|| vec![ProtocolVersion::TLSv1_2]);
+        self
+    }
+
+    /// Accept only these protocol versions, `TLSv1_2` and/or `TLSv1_3`.
+    /// Defaults to every version rustls enables.
+    pub fn protocol_versions(
+        mut self,
+        versions: impl IntoIterator<Item = ProtocolVersion>,
+    ) -> Self {
+        self.versions = Some(versions.into_iter().collect());
+        self
+    }
+
+    /// Issue the certificate for these DNS names and IP addresses instead
+    /// of `localhost`, `127.0.0.1` and `::1`.
+    pub fn subject_alt_names(
+        mut self,
+        names: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.names = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Issue the certificate for the given validity period instead of
+    /// rcgen's default, e.g. to test expiry reporting with a short-lived or
+    /// already expired certificate.
+    pub fn cert_validity(mut self, not_before: SystemTime, not_after: SystemTime) -> Self {
+        self.validity = Some((not_before, not_after));
+        self
+    }
+
+    /// Issue the certificate from a generated root through this many
+    /// intermediate CAs, all sent in the handshake, instead of self-signing
+    /// it. [`TestTlsServer::cert_der`] is then the root.
+    pub fn intermediates(mut self, count: usize) -> Self {
+        self.intermediates = count;
+        self
+    }
+
+    /// Send this many TLS 1.3 session tickets after each handshake instead
+    /// of rustls' default; `0` disables ticket issuance.
+    pub fn tls13_tickets(mut self, tickets: usize) -> Self {
+        self.tls13_tickets = Some(tickets);
+        self
+    }
+
+    /// Send a warning-level alert in plaintext, as some TLS 1.2 servers do
+    /// with `unrecognized_name`, after reading each ClientHello and before
+    /// answering it. Clients accept it since no version is negotiated yet.
+    pub fn warning_alert(mut self, alert: AlertDescription) -> Self {
+        self.warning_alert = Some(alert);
+        self
+    }
+
+    /// Greet every connection as a `protocol` mail server that advertises
+    /// STARTTLS (STLS for POP3), and start the handshake once the client
+    /// asked for it, as [`probe_starttls`](crate::starttls::probe_starttls)
+    /// does. Connections that end the preamble otherwise are not recorded.
+    pub fn starttls(mut self, protocol: Protocol) -> Self {
+        self.starttls = Some(protocol);
+        self
+    }
+
+    /// Answer one HTTP/1.1 request per connection with the handshake
+    /// summary (the default). When disabled the server only completes the
+    /// handshake and keeps the connection open until the client closes it.
+    pub fn http_responder(mut self, enabled: bool) -> Self {
+        self.http = enabled;
+        self
+    }
+
+    /// Answer HTTP requests with the status and extra headers `handler`
+    /// makes of the request line, headers and body, e.g.
+    /// `"302 Found\r\nLocation: /next"`, instead of `200 OK`. The body is
+    /// the handshake summary, unless the handler ends its headers with an
+    /// empty line and gives a body of its own after it.
+    pub fn http_handler(
+        mut self,
+        handler: impl Fn(&str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.http_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Keep each connection open for further HTTP/1.1 requests, answered
+    /// with `Connection: keep-alive`, until the client closes it, instead of
+    /// closing it after the first response.
+    pub fn keep_alive(mut self, keep_alive: bool) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Invoke `callback` for every connection as soon as its handshake has
+    /// completed or failed.
+    pub fn on_handshake(
+        mut self,
+        callback: impl Fn(&ObservedHandshake) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_handshake = Some(Arc::new(callback));
+        self
+    }
+
+    /// Bind the listener and start accepting connections on the current
+    /// tokio runtime.
+    pub async fn bind(self, addr: impl ToSocketAddrs) -> io::Result<TestTlsServer> {
+        let mut kx_groups: Vec<&'static dyn SupportedKxGroup> = Vec::new();
+        for name in &self.groups {
+            let group = aws_lc_rs::ALL_KX_GROUPS
+                .iter()
+                .find(|g| g.name() == *name)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("unsupported key-exchange group {:?}", name),
+                    )
+                })?;
+            kx_groups.push(*group);
+        }
+
+        let mut versions: Vec<&'static rustls::SupportedProtocolVersion> = Vec::new();
+        for version in self.versions.as_deref().unwrap_or_default() {
+            let supported = rustls::ALL_VERSIONS
+                .iter()
+                .find(|v| v.version == *version)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("unsupported protocol version {:?}", version),
+                    )
+                })?;
+            versions.push(*supported);
+        }
+
+        let mut params = rcgen::CertificateParams::new(self.names).map_err(io::Error::other)?;
+        if let Some((not_before, not_after)) = self.validity {
+            params.not_before = not_before.into();
+            params.not_after = not_after.into();
+        }
+        let signing_key = rcgen::KeyPair::generate().map_err(io::Error::other)?;
+        let (cert_chain, cert, cert_pem) = match self.intermediates {
+            0 => {
+                let generated = params
+                    .self_signed(&signing_key)
+                    .map_err(io::Error::other)?;
+                (vec![generated.der().clone()], generated.der().clone(), generated.pem())
+            }
+            count => issue_through_intermediates(&params, &signing_key, count)
+                .map_err(io::Error::other)?,
+        };
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(signing_key.serialize_der()));
+
+        let setup = Arc::new(ServerSetup {
+            kx_groups,
+            versions,
+            cert_chain,
+            key,
+            tls13_tickets: self.tls13_tickets,
+        });
+        let base_config = setup.config(&[])?;
+
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let observed = Arc::new(Mutex::new(Vec::new()));
+
+        let state = Arc::new(ConnectionState {
+            setup,
+            base_config,
+            force_hello_retry: self.force_hello_retry,
+            reject_unknown_groups: self.reject_unknown_groups,
+            max_hello_len: self.max_hello_len,
+            warning_alert: self.warning_alert,
+            starttls: self.starttls,
+            http: self.http,
+            http_handler: self.http_handler,
+            keep_alive: self.keep_alive,
+            observed: observed.clone(),
+            on_handshake: self.on_handshake,
+        });
+
+        // Connections live in a JoinSet owned by the accept task, so that
+        // aborting it on drop also ends every open connection.
+        let task = tokio::spawn(async move {
+            let mut connections = JoinSet::new();
+            while let Ok((stream, peer)) = listener.accept().await {
+                while connections.try_join_next().is_some() {}
+                connections.spawn(state.clone().handle(stream, peer));
+            }
+        });
+
+        Ok(TestTlsServer {
+            local_addr,
+            cert,
+            cert_pem,
+            observed,
+            task,
+        })
+    }
+}
+
+/// Issue a certificate for `params` from a fresh root through `count`
+/// intermediates; returns the chain to send, end-entity first, and the
+/// root's DER and PEM forms.
+fn issue_through_intermediates(
+    params: &rcgen::CertificateParams,
+    key: &rcgen::KeyPair,
+    count: usize,
+) -> Result<(Vec<CertificateDer<'static>>, CertificateDer<'static>, String), rcgen::Error> {
+    let ca = |name: String| {
+        let mut params = rcgen::CertificateParams::default();
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, name);
+        params
+    };
+    let root = rcgen::CertifiedIssuer::self_signed(
+        ca("pqctracer test root".to_string()),
+        rcgen::KeyPair::generate()?,
+    )?;
+    let (root_der, root_pem) = (root.der().clone(), root.pem());
+
+    let mut chain = Vec::new();
+    let mut issuer = root;
+    for i in 1..=count {
+        issuer = rcgen::CertifiedIssuer::signed_by(
+            ca(format!("pqctracer test intermediate {}", i)),
+            rcgen::KeyPair::generate()?,
+            &issuer,
+        )?;
+        chain.push(issuer.der().clone());
+    }
+    chain.push(params.signed_by(key, &issuer)?.der().clone());
+    chain.reverse();
+    Ok((chain, root_der, root_pem))
+}
+
+/// A running local TLS server; stops accepting connections and closes the
+/// open ones when dropped.
+pub struct TestTlsServer {
+    local_addr: SocketAddr,
+    cert: CertificateDer<'static>,
+    cert_pem: String,
+    observed: Arc<Mutex<Vec<ObservedHandshake>>>,
+    task: JoinHandle<()>,
+}
+
+impl TestTlsServer {
+    pub fn builder() -> TestTlsServerBuilder {
+        TestTlsServerBuilder {
+            groups: aws_lc_rs::ALL_KX_GROUPS.iter().map(|g| g.name()).collect(),
+            force_hello_retry: false,
+            reject_unknown_groups: false,
+            max_hello_len: None,
+            versions: None,
+            names: vec![
+                "localhost".to_string(),
+                "127.0.0.1".to_string(),
+                "::1".to_string(),
+            ],
+            validity: None,
+            intermediates: 0,
+            tls13_tickets: None,
+            warning_alert: None,
+            starttls: None,
+            http: true,
+            http_handler: None,
+            keep_alive: false,
+            on_handshake: None,
+        }
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// `https://localhost:<port>` for the bound port.
+    pub fn url(&self) -> String {
+        format!("https://localhost:{}", self.local_addr.port())
+    }
+
+    /// The certificate to add to a root store: the self-signed server
+    /// certificate, which is its own CA, or the root it was issued from with
+    /// [`intermediates`](TestTlsServerBuilder::intermediates).
+    pub fn cert_der(&self) -> &CertificateDer<'static> {
+        &self.cert
+    }
+
+    pub fn cert_pem(&self) -> &str {
+        &self.cert_pem
+    }
+
+    /// Every handshake observed so far, in completion order.
+    pub fn observed(&self) -> Vec<ObservedHandshake> {
+        crate::lock(&self.observed, "observed-handshakes").clone()
+    }
+}
+
+impl Drop for TestTlsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Everything needed to build a `ServerConfig`, kept around so that forced
+/// HelloRetryRequests can build a per-connection config.
+struct ServerSetup {
+    kx_groups: Vec<&'static dyn SupportedKxGroup>,
+    /// Empty for rustls' defaults.
+    versions: Vec<&'static rustls::SupportedProtocolVersion>,
+    cert_chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    tls13_tickets: Option<usize>,
+}
+
+impl ServerSetup {
+    /// Build a config accepting every configured group except `excluded`.
+    fn config(&self, excluded: &[NamedGroup]) -> io::Result<Arc<ServerConfig>> {
+        let provider = CryptoProvider {
+            kx_groups: self
+                .kx_groups
+                .iter()
+                .copied()
+                .filter(|g| !excluded.contains(&g.name()))
+                .collect(),
+            ..aws_lc_rs::default_provider()
+        };
+
+        let versions = match self.versions.as_slice() {
+            [] => rustls::DEFAULT_VERSIONS,
+            versions => versions,
+        };
+
+        let mut config = ServerConfig::builder_with_provider(Arc::new(provider))
+            .with_protocol_versions(versions)
+            .map_err(io::Error::other)?
+            .with_no_client_auth()
+            .with_single_cert(self.cert_chain.clone(), self.key.clone_key())
+            .map_err(io::Error::other)?;
+        config.alpn_protocols = vec![HTTP_1_1.to_vec()];
+        if let Some(tickets) = self.tls13_tickets {
+            config.send_tls13_tickets = tickets;
+        }
+        Ok(Arc::new(config))
+    }
+}
+
+struct ConnectionState {
+    setup: Arc<ServerSetup>,
+    base_config: Arc<ServerConfig>,
+    force_hello_retry: bool,
+    reject_unknown_groups: bool,
+    max_hello_len: Option<usize>,
+    warning_alert: Option<AlertDescription>,
+    starttls: Option<Protocol>,
+    http: bool,
+    http_handler: Option<HttpHandler>,
+    keep_alive: bool,
+    observed: Arc<Mutex<Vec<ObservedHandshake>>>,
+    on_handshake: Option<HandshakeCallback>,
+}
+
+impl ConnectionState {
+    async fn handle(self: Arc<Self>, mut stream: TcpStream, peer: SocketAddr) {
+        let _ = stream.set_nodelay(true);
+        if let Some(protocol) = self.starttls
+            && !starttls_preamble(&mut stream, protocol).await.unwrap_or(false)
+        {
+            return;
+        }
+        // Read the ClientHello ourselves so the offered key shares are known
+        // before rustls picks a group, then replay it into the acceptor.
+        let mut hello_bytes = Vec::new();
+        while let Some(needed) = hello::bytes_needed(&hello_bytes) {
+            let start = hello_bytes.len();
+            hello_bytes.resize(start + needed, 0);
+            if stream.read_exact(&mut hello_bytes[start..]).await.is_err() {
+                return;
+            }
+        }
+        let summary = hello::parse(&hello_bytes).unwrap_or_default();
+
+        let mut record = ObservedHandshake {
+            peer,
+            offered_groups: summary.supported_groups.clone(),
+            key_shares: summary.key_shares.clone(),
+            selected_group: None,
+            hello_retry: false,
+            version: None,
+            cipher: None,
+            error: None,
+        };
+
+        if self.max_hello_len.is_some_and(|max| hello_bytes.len() > max) {
+            record.error = Some(format!("dropped {}-byte ClientHello", hello_bytes.len()));
+            self.record(record);
+            return;
+        }
+
+        let config = match self.config_for(&summary) {
+            Ok(config) => config,
+            Err(e) => {
+                record.error = Some(e.to_string());
+                self.record(record);
+                return;
+            }
+        };
+
+        let unknown_group = summary
+            .supported_groups
+            .iter()
+            .any(|group| !self.setup.kx_groups.iter().any(|g| g.name() == *group));
+        if self.reject_unknown_groups && unknown_group {
+            let alert = [21, 3, 3, 0, 2, 2, u8::from(AlertDescription::HandshakeFailure)];
+            let _ = stream.write_all(&alert).await;
+            record.error = Some("rejected unknown key-exchange group".to_string());
+            self.record(record);
+            return;
+        }
+
+        // rustls notices an ALPN mismatch only after its ServerHello, the
+        // last record tokio-rustls gets out before giving up; reject it up
+        // front instead, as servers checking ALPN first do.
+        if !summary.alpn.is_empty() && !summary.alpn.iter().any(|p| p == HTTP_1_1) {
+            let alert = [21, 3, 3, 0, 2, 2, u8::from(AlertDescription::NoApplicationProtocol)];
+            let _ = stream.write_all(&alert).await;
+            record.error = Some(rustls::Error::NoApplicationProtocol.to_string());
+            self.record(record);
+            return;
+        }
+
+        if let Some(alert) = self.warning_alert {
+            // Content type alert, TLS 1.2 record version, two bytes of
+            // payload: level warning and the description.
+            let record = [21, 3, 3, 0, 2, 1, u8::from(alert)];
+            if stream.write_all(&record).await.is_err() {
+                return;
+            }
+        }
+
+        let replay = Replay {
+            prefix: hello_bytes,
+            pos: 0,
+            inner: stream,
+        };
+        let mut tls = match TlsAcceptor::from(config).accept(replay).await {
+            Ok(tls) => tls,
+            Err(e) => {
+                record.error = Some(e.to_string());
+                self.record(record);
+                return;
+            }
+        };
+
+        let (_, conn) = tls.get_ref();
+        record.selected_group = conn.negotiated_key_exchange_group().map(|g| g.name());
+        record.hello_retry =
+            conn.handshake_kind() == Some(HandshakeKind::FullWithHelloRetryRequest);
+        record.version = conn.protocol_version();
+        record.cipher = conn.negotiated_cipher_suite().map(|s| s.suite());
+        let body = format!("{}\n", record);
+        self.record(record);
+
+        let mut buf = [0u8; 1024];
+        if !self.http {
+            while let Ok(n) = tls.read(&mut buf).await {
+                if n == 0 {
+                    break;
+                }
+            }
+            let _ = tls.shutdown().await;
+            return;
+        }
+
+        // Minimal HTTP/1.1 responder: one request per connection, or every
+        // request the client sends on it with keep-alive.
+        let mut buffered = Vec::new();
+        while let Some(request) = read_request(&mut tls, &mut buffered).await {
+            let answer = match &self.http_handler {
+                Some(handler) => handler(&String::from_utf8_lossy(&request)),
+                None => "200 OK".to_string(),
+            };
+            let (status, body) = match answer.split_once("\r\n\r\n") {
+                Some((status, own)) => (status.to_string(), own.to_string()),
+                None => (answer, body.clone()),
+            };
+            let connection = if self.keep_alive { "keep-alive" } else { "close" };
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: {}\r\n\r\n",
+                status,
+                body.len(),
+                connection
+            );
+            let mut response = head.into_bytes();
+            if !request.starts_with(b"HEAD ") {
+                response.extend_from_slice(body.as_bytes());
+            }
+            if tls.write_all(&response).await.is_err() || !self.keep_alive {
+                break;
+            }
+        }
+        let _ = tls.shutdown().await;
+    }
+
+    fn config_for(&self, summary: &hello::ClientHelloSummary) -> io::Result<Arc<ServerConfig>> {
+        if !self.force_hello_retry {
+            return Ok(self.base_config.clone());
+        }
+        let retry_possible = self.setup.kx_groups.iter().any(|g| {
+            summary.supported_groups.contains(&g.name()) && !summary.key_shares.contains(&g.name())
+        });
+        if retry_possible {
+            self.setup.config(&summary.key_shares)
+        } else {
+            Ok(self.base_config.clone())
+        }
+    }
+
+    fn record(&self, record: ObservedHandshake) {
+        if let Some(callback) = &self.on_handshake {
+            callback(&record);
+        }
+        crate::lock(&self.observed, "observed-handshakes").push(record);
+    }
+}
+
+/// Read the next HTTP/1.1 request from `tls`, its body included, by
+/// `Content-Length` or decoded from chunks; `buffered` keeps what was read
+/// beyond it. `None` once the client closes the connection.
+async fn read_request(
+    tls: &mut (impl AsyncRead + Unpin),
+    buffered: &mut Vec<u8>,
+) -> Option<Vec<u8>> {
+    async fn fill(tls: &mut (impl AsyncRead + Unpin), buffered: &mut Vec<u8>) -> Option<()> {
+        let mut buf = [0u8; 16 * 1024];
+        match tls.read(&mut buf).await {
+            Ok(0) | Err(_) => None,
+            Ok(n) => {
+                buffered.extend_from_slice(&buf[..n]);
+                Some(())
+            }
+        }
+    }
+    let find = |haystack: &[u8], needle: &[u8]| {
+        haystack.windows(needle.len()).position(|w| w == needle)
+    };
+
+    let head_len = loop {
+        match find(buffered, b"\r\n\r\n") {
+            Some(at) => break at + 4,
+            None => fill(tls, buffered).await?,
+        }
+    };
+    let head = String::from_utf8_lossy(&buffered[..head_len]).to_ascii_lowercase();
+    let header = |name: &str| {
+        head.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .map(str::trim)
+    };
+    let mut request: Vec<u8> = buffered.drain(..head_len).collect();
+    if header("transfer-encoding") == Some("chunked") {
+        loop {
+            let line_len = loop {
+                match find(buffered, b"\r\n") {
+                    Some(at) => break at,
+                    None => fill(tls, buffered).await?,
+                }
+            };
+            let size = String::from_utf8_lossy(&buffered[..line_len]);
+            let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+            while buffered.len() < line_len + 2 + size + 2 {
+                fill(tls, buffered).await?;
+            }
+            request.extend_from_slice(&buffered[line_len + 2..line_len + 2 + size]);
+            buffered.drain(..line_len + 2 + size + 2);
+            if size == 0 {
+                return Some(request);
+            }
+        }
+    }
+    let length: usize = header("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+    while buffered.len() < length {
+        fill(tls, buffered).await?;
+    }
+    request.extend(buffered.drain(..length));
+    Some(request)
+}
+
+/// Play the server side of a mail protocol's plaintext preamble until the
+/// client asks for TLS; `false` when it ended the preamble otherwise. The
+/// client waits for each reply, so nothing it sends after the upgrade
+/// command is buffered here.
+async fn starttls_preamble(stream: &mut TcpStream, protocol: Protocol) -> io::Result<bool> {
+    let mut reader = BufReader::new(stream);
+    let greeting = match protocol {
+        Protocol::Smtp => "220 localhost ESMTP pqctracer test server\r\n",
+        Protocol::Imap => "* OK pqctracer test server ready\r\n",
+        Protocol::Pop3 => "+OK pqctracer test server ready\r\n",
+    };
+    reader.get_mut().write_all(greeting.as_bytes()).await?;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(false);
+        }
+        let mut words = line.split_ascii_whitespace();
+        let (tag, command) = match protocol {
+            Protocol::Imap => (words.next().unwrap_or("*"), words.next()),
+            Protocol::Smtp | Protocol::Pop3 => ("", words.next()),
+        };
+        let command = command.unwrap_or_default().to_ascii_uppercase();
+        let reply = match (protocol, command.as_str()) {
+            (Protocol::Smtp, "EHLO") => "250-localhost\r\n250 STARTTLS\r\n".to_string(),
+            (Protocol::Smtp, "STARTTLS") => "220 Ready to start TLS\r\n".to_string(),
+            (Protocol::Smtp, _) => "502 Command not recognized\r\n".to_string(),
+            (Protocol::Imap, "CAPABILITY") => {
+                format!("* CAPABILITY IMAP4rev1 STARTTLS\r\n{} OK done\r\n", tag)
+            }
+            (Protocol::Imap, "STARTTLS") => format!("{} OK Begin TLS\r\n", tag),
+            (Protocol::Imap, _) => format!("{} BAD unknown command\r\n", tag),
+            (Protocol::Pop3, "CAPA") => "+OK\r\nUSER\r\nSTLS\r\n.\r\n".to_string(),
+            (Protocol::Pop3, "STLS") => "+OK Begin TLS\r\n".to_string(),
+            (Protocol::Pop3, _) => "-ERR unknown command\r\n".to_string(),
+        };
+        let upgrade = match protocol {
+            Protocol::Smtp | Protocol::Imap => command == "STARTTLS",
+            Protocol::Pop3 => command == "STLS",
+        };
+        reader.get_mut().write_all(reply.as_bytes()).await?;
+        if upgrade {
+            return Ok(true);
+        }
+    }
+}
+
+/// Replays already-consumed bytes before reading from the inner stream.
+struct Replay {
+    prefix: Vec<u8>,
+    pos: usize,
+    inner: TcpStream,
+}
+
+impl AsyncRead for Replay {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.pos < self.prefix.len() {
+            let n = (self.prefix.len() - self.pos).min(buf.remaining());
+            let start = self.pos;
+            buf.put_slice(&self.prefix[start..start + n]);
+            self.pos += n;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Replay {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// What the server side of [`handshake_in_memory`] saw and negotiated.
+#[derive(Debug, Clone, Default)]
+pub struct ServerObservations {
+    pub server_name: Option<String>,
+    /// Groups listed in the client's `supported_groups` extension.
+    pub offered_groups: Vec<NamedGroup>,
+    pub selected_group: Option<NamedGroup>,
+    pub hello_retry: bool,
+    pub version: Option<ProtocolVersion>,
+    pub cipher: Option<CipherSuite>,
+}
+
+/// `provider` with its TLS 1.3 cipher suites wrapped as the tracer's own
+/// clients wrap them, so that [`handshake_in_memory`] captures the cipher of
+/// a client config built on it the way
+/// [`TlsAwareClient::execute`](crate::TlsAwareClient::execute) does.
+pub fn capturing_provider(provider: &CryptoProvider) -> Arc<CryptoProvider> {
+    capture::observed(provider)
+}
+
+/// Run a complete handshake between `client_config` and `server_config`
+/// over an in-memory `tokio::io::duplex` pipe, with the server name
+/// `localhost` and no sockets involved.
+///
+/// The client's session store is replaced by the same capturing store
+/// [`TlsAwareClient::execute`](crate::TlsAwareClient::execute) uses, and the
+/// returned metadata's group, cipher and ticket count come from the capture,
+/// so capture behaviour (groups, tickets, HelloRetryRequests) can be checked
+/// deterministically. The cipher is captured as the key decrypting the
+/// server's handshake is installed when `client_config` was built on a
+/// [`capturing_provider`], and from the session tickets otherwise; a config
+/// on any other provider, talking to a server that sends no tickets, gets
+/// the cipher the client connection negotiated, with the capture none the
+/// wiser. The certificate chain of a full handshake is taken from the client
+/// connection, which `client_config`'s own verifier checked, so that the
+/// capture tells fresh handshakes from resumed ones as it does for the
+/// tracer. The server sends its session tickets and closes the connection
+/// before the metadata is read, so the ticket count is complete: `Some(0)`
+/// when the server sent none.
+pub async fn handshake_in_memory(
+    mut client_config: rustls::ClientConfig,
+    server_config: Arc<ServerConfig>,
+) -> Result<(TlsMetadata, ServerObservations), TraceError> {
+    const SERVER_NAME: &str = "localhost";
+
+    client_config.resumption = Resumption::store(Arc::new(CapturingSessionStore { inner: None }));
+    let captured = Arc::<Captured>::default();
+
+    let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+    let server = async move {
+        let start = LazyConfigAcceptor::new(Acceptor::default(), server_io).await?;
+        let hello = start.client_hello();
+        let mut observations = ServerObservations {
+            server_name: hello.server_name().map(str::to_string),
+            offered_groups: hello.named_groups().unwrap_or_default().to_vec(),
+            ..Default::default()
+        };
+        let mut tls = start.into_stream(server_config).await?;
+        let (_, conn) = tls.get_ref();
+        observations.selected_group = conn.negotiated_key_exchange_group().map(|g| g.name());
+        observations.hello_retry =
+            conn.handshake_kind() == Some(HandshakeKind::FullWithHelloRetryRequest);
+        observations.version = conn.protocol_version();
+        observations.cipher = conn.negotiated_cipher_suite().map(|s| s.suite());
+        tls.shutdown().await?;
+        Ok::<_, io::Error>(observations)
+    };
+    let cell = captured.clone();
+    let client = capture::scope(SERVER_NAME, captured.clone(), async move {
+        let server_name = ServerName::try_from(SERVER_NAME).expect("valid server name");
+        let mut tls = TlsConnector::from(Arc::new(client_config))
+            .connect(server_name, client_io)
+            .await?;
+        // The caller's verifier is not the tracer's capturing one; the
+        // chain it checked is what the capture would have seen.
+        let (_, conn) = tls.get_ref();
+        if conn.handshake_kind() != Some(HandshakeKind::Resumed)
+            && let Some(chain) = conn.peer_certificates()
+        {
+            let _ = cell
+                .peer_certificates
+                .set(chain.iter().map(|cert| cert.clone().into_owned()).collect());
+        }
+        // Reading to the server's close_notify processes its tickets.
+        let mut rest = Vec::new();
+        tls.read_to_end(&mut rest).await?;
+        Ok::<_, io::Error>(TlsMetadata::from_connection(tls.get_ref().1))
+    });
+    let (server, client) = futures_util::future::join(server, client).await;
+    let connection = client?;
+    let observations = server?;
+
+    let state = Captured::into_values(captured);
+    let metadata = TlsMetadata {
+        group: state.group_name(),
+        cipher: state.cipher_name().or(connection.cipher.clone()),
+        tickets_issued: state.tickets_read_to_close(),
+        origin: Some(state.origin()),
+        capture_status: state.status(),
+        ..connection
+    };
+    Ok((metadata, observations))
+}
@@ -0,0 +1,236 @@
+//! Where the time of a request goes, for clients built with
+//! [`collect_timings`](crate::TlsAwareClientBuilder::collect_timings).
+//!
+//! The connector layer, the DNS resolver and the session store's ClientHello
+//! callback mark the start of each phase on a clock that
+//! [`execute`](crate::TlsAwareClient::execute) installs for the duration of
+//! the request. They all run inside the request's future, so – like the
+//! [capture](crate::capture) callbacks – the marks need no keying by
+//! server name. A connection that hyper finishes in the background, after
+//! the request took a pooled one instead, marks nothing.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::bind::LocalBind;
+
+tokio::task_local! {
+    static CLOCK: Arc<PhaseClock>;
+}
+
+/// How long each phase of a request took.
+///
+/// `dns + connect + tls <= ttfb <= total`. The connection phases are `None`
+/// for a request that went over a pooled connection; `dns` is also `None`
+/// for hosts given as IP addresses, and `tls` for `http://` URLs. After a
+/// redirect, the phases are those of the first connection the request
+/// opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timings {
+    /// Resolving the host name.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "dns_ms", default, with = "crate::serde_util::millis_option")
+    )]
+    pub dns: Option<Duration>,
+    /// Establishing the TCP connection, through the proxy if there is one.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "connect_ms",
+            default,
+            with = "crate::serde_util::millis_option"
+        )
+    )]
+    pub connect: Option<Duration>,
+    /// The TLS handshake, from the ClientHello to the finished connection.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "tls_ms", default, with = "crate::serde_util::millis_option")
+    )]
+    pub tls: Option<Duration>,
+    /// From sending the request, connection phases included, to the
+    /// response head.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "ttfb_ms", with = "crate::serde_util::millis")
+    )]
+    pub ttfb: Duration,
+    /// `ttfb` plus reading the body; only known once the body was read with
+    /// [`TlsResponse::text`](crate::TlsResponse::text),
+    /// [`bytes`](crate::TlsResponse::bytes) or
+    /// [`json`](crate::TlsResponse::json).
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "total_ms",
+            default,
+            with = "crate::serde_util::millis_option"
+        )
+    )]
+    pub total: Option<Duration>,
+}
+
+impl Timings {
+    /// Whether the request opened a connection of its own.
+    pub fn new_connection(&self) -> bool {
+        self.connect.is_some()
+    }
+
+    /// Record that reading the body, started after the response head, took
+    /// `reading`.
+    pub(crate) fn body_read(&mut self, reading: Duration) {
+        self.total = Some(self.ttfb + reading);
+    }
+}
+
+/// `dns 2.1ms, connect 0.4ms, tls 3.0ms, ttfb 12.5ms, total 13.0ms`, with
+/// `n/a` for phases that did not happen.
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Option<Duration>| match d {
+            Some(d) => format!("{:.1}ms", d.as_secs_f64() * 1000.0),
+            None => "n/a".to_string(),
+        };
+        write!(
+            f,
+            "dns {}, connect {}, tls {}, ttfb {}, total {}",
+            ms(self.dns),
+            ms(self.connect),
+            ms(self.tls),
+            ms(Some(self.ttfb)),
+            ms(self.total)
+        )
+    }
+}
+
+/// The moments a request's connection reached each phase; the first mark
+/// of each kind wins.
+#[derive(Default)]
+struct PhaseClock {
+    connecting: OnceLock<Instant>,
+    resolving: OnceLock<Instant>,
+    resolved: OnceLock<Instant>,
+    handshaking: OnceLock<Instant>,
+    connected: OnceLock<Instant>,
+}
+
+impl PhaseClock {
+    fn timings(&self, ttfb: Duration) -> Timings {
+        let (Some(connecting), Some(connected)) = (self.connecting.get(), self.connected.get())
+        else {
+            return Timings {
+                ttfb,
+                ..Timings::default()
+            };
+        };
+        let resolved = self.resolved.get();
+        let handshaking = self.handshaking.get();
+        Timings {
+            dns: self
+                .resolving
+                .get()
+                .zip(resolved)
+                .map(|(start, end)| end.duration_since(*start)),
+            connect: Some(
+                handshaking
+                    .unwrap_or(connected)
+                    .duration_since(*resolved.unwrap_or(connecting)),
+            ),
+            tls: handshaking.map(|start| connected.duration_since(*start)),
+            ttfb,
+            total: None,
+        }
+    }
+}
+
+/// Mark the moment `phase` started on the current request's clock, if the
+/// caller runs inside a timed request.
+fn mark(phase: impl FnOnce(&PhaseClock) -> &OnceLock<Instant>) {
+    let _ = CLOCK.try_with(|clock| {
+        phase(clock).get_or_init(Instant::now);
+    });
+}
+
+/// Called when rustls builds the ClientHello.
+pub(crate) fn handshake_started() {
+    mark(|clock| &clock.handshaking);
+}
+
+/// Run `request` with a fresh clock and report its phases.
+pub(crate) async fn timed<F: Future>(request: F) -> (F::Output, Timings) {
+    let clock = Arc::new(PhaseClock::default());
+    let start = Instant::now();
+    let output = CLOCK.scope(clock.clone(), request).await;
+    (output, clock.timings(start.elapsed()))
+}
+
+/// Resolves host names like reqwest's default resolver, marking when the
+/// lookup starts and ends. Addresses the local address cannot reach are
+/// left out.
+pub(crate) struct TimingResolver {
+    pub(crate) bind: LocalBind,
+}
+
+impl Resolve for TimingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let bind = self.bind.clone();
+        Box::pin(async move {
+            mark(|clock| &clock.resolving);
+            let addrs = bind.lookup(name.as_str(), 0).await?;
+            mark(|clock| &clock.resolved);
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Wraps reqwest's connector to mark when a connection is started and when
+/// it is ready, TLS handshake included.
+#[derive(Clone)]
+pub(crate) struct TimingLayer;
+
+impl<S> Layer<S> for TimingLayer {
+    type Service = TimingConnector<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimingConnector { inner }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct TimingConnector<S> {
+    inner: S,
+}
+
+impl<S, R> Service<R> for TimingConnector<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        mark(|clock| &clock.connecting);
+        let connecting = self.inner.call(request);
+        Box::pin(async move {
+            let connection = connecting.await?;
+            mark(|clock| &clock.connected);
+            Ok(connection)
+        })
+    }
+}
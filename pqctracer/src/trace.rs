@@ -0,0 +1,112 @@
+//! One-shot tracing of a single URL, for scripts and examples that do not
+//! need a long-lived [`TlsAwareClient`].
+
+use std::time::Duration;
+
+use rustls::crypto::CryptoProvider;
+use rustls_pki_types::CertificateDer;
+
+use crate::{KxPreference, TlsAwareClient, TlsMetadata, TraceError, idn};
+
+/// Timeout of a [`trace_url`] request unless [`TraceOptions::timeout`] says
+/// otherwise.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How [`trace_url_with`] builds its client and sends its request.
+#[derive(Debug, Clone)]
+pub struct TraceOptions {
+    head: bool,
+    timeout: Duration,
+    kx_preference: KxPreference,
+    accept_invalid_certs: bool,
+    root_certificates: Vec<CertificateDer<'static>>,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        Self {
+            head: false,
+            timeout: DEFAULT_TIMEOUT,
+            kx_preference: KxPreference::default(),
+            accept_invalid_certs: false,
+            root_certificates: Vec::new(),
+        }
+    }
+}
+
+impl TraceOptions {
+    /// Send a `HEAD` request instead of a `GET`.
+    pub fn head(mut self, head: bool) -> Self {
+        self.head = head;
+        self
+    }
+
+    /// Give up on the request after `timeout`. Defaults to 30 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Choose which key-exchange groups are offered.
+    pub fn kx_preference(mut self, preference: KxPreference) -> Self {
+        self.kx_preference = preference;
+        self
+    }
+
+    /// Skip certificate verification – see
+    /// [`TlsAwareClientBuilder::danger_accept_invalid_certs`](crate::TlsAwareClientBuilder::danger_accept_invalid_certs).
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Trust `cert` in addition to the bundled web PKI roots.
+    pub fn add_root_certificate(mut self, cert: CertificateDer<'static>) -> Self {
+        self.root_certificates.push(cert);
+        self
+    }
+}
+
+/// Request `url` with a throwaway client and return the negotiated TLS
+/// parameters, discarding the response.
+pub async fn trace_url(url: &str) -> Result<TlsMetadata, TraceError> {
+    trace_url_with(url, TraceOptions::default()).await
+}
+
+/// Like [`trace_url`], with the client and request configured by `options`.
+///
+/// Installs aws-lc-rs as the process-level crypto provider if none is
+/// installed yet; a provider the caller installed is left in place.
+pub async fn trace_url_with(url: &str, options: TraceOptions) -> Result<TlsMetadata, TraceError> {
+    let url = reqwest::Url::parse(&idn::normalize_url(url)?)
+        .map_err(|e| TraceError::InvalidUrl(format!("{}: {}", url, e)))?;
+    ensure_crypto_provider();
+
+    let mut builder = TlsAwareClient::builder()
+        .timeout(options.timeout)
+        .kx_preference(options.kx_preference)
+        .danger_accept_invalid_certs(options.accept_invalid_certs);
+    for cert in options.root_certificates {
+        builder = builder.add_root_certificate(cert);
+    }
+    let client = builder.build()?;
+
+    let method = if options.head {
+        reqwest::Method::HEAD
+    } else {
+        reqwest::Method::GET
+    };
+    let response = client
+        .execute(reqwest::Request::new(method, url))
+        .await
+        .map_err(TraceError::Http)?;
+    Ok(response.metadata)
+}
+
+pub(crate) fn ensure_crypto_provider() {
+    if CryptoProvider::get_default().is_none() {
+        // Losing a race against another installer is fine: either way a
+        // provider is in place afterwards.
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+    }
+}
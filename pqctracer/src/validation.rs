@@ -0,0 +1,208 @@
+//! How a server's certificate chain was validated: which root anchored it
+//! and through which intermediates, for audits that need more than "the
+//! handshake succeeded".
+//!
+//! rustls' verifier only answers yes or no, so the capturing verifier builds
+//! the path once more with webpki, against the same roots and signature
+//! algorithms. That also works for clients that accept invalid
+//! certificates, whose report then says why validation would have failed.
+//!
+//! [`CertPolicy`] decides what a failed verification does to the handshake.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rustls::RootCertStore;
+use rustls::crypto::CryptoProvider;
+use rustls_pki_types::{CertificateDer, ServerName, SignatureVerificationAlgorithm, UnixTime};
+use webpki::{EndEntityCert, KeyUsage};
+use x509_parser::prelude::{FromDer, X509Name};
+
+/// The outcome of validating the chain a server presented.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValidationReport {
+    /// The subject of the root the chain leads to, e.g.
+    /// `C=US, O=Internet Security Research Group, CN=ISRG Root X1`; `None`
+    /// when no path to a trusted root was found.
+    pub trust_anchor: Option<String>,
+    /// Certificates the server presented, end-entity included.
+    pub chain_length: usize,
+    /// Intermediates on the path from the end-entity certificate to the
+    /// root; fewer than presented when the server sent certificates the
+    /// path does not need.
+    pub path_intermediates: Option<usize>,
+    /// Whether the path goes through a cross-signed root: an intermediate
+    /// with the same subject as a trusted root.
+    pub cross_signed: bool,
+    /// How long building and checking the path took.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "validation_ms", with = "crate::serde_util::millis")
+    )]
+    pub duration: Duration,
+    /// Why validation failed, as webpki reports it, e.g. `UnknownIssuer`.
+    /// Only seen on successful requests for clients that
+    /// [accept invalid certificates](crate::TlsAwareClientBuilder::danger_accept_invalid_certs)
+    /// or only [report failures](CertPolicy::ReportOnly).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub error: Option<String>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// `anchored at C=US, O=Example, CN=Example Root via 2 of 2 intermediates
+/// (0.3ms)`, or `failed: UnknownIssuer (0.1ms)`.
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = self.duration.as_secs_f64() * 1000.0;
+        if let Some(error) = &self.error {
+            return write!(f, "failed: {} ({:.1}ms)", error, ms);
+        }
+        write!(
+            f,
+            "anchored at {} via {} of {} intermediates",
+            self.trust_anchor.as_deref().unwrap_or("n/a"),
+            self.path_intermediates.unwrap_or_default(),
+            self.chain_length.saturating_sub(1)
+        )?;
+        if self.cross_signed {
+            f.write_str(", cross-signed")?;
+        }
+        write!(f, " ({:.1}ms)", ms)
+    }
+}
+
+/// What a verifying client does when a server's certificate fails
+/// verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum CertPolicy {
+    /// Fail the handshake.
+    #[default]
+    Enforce,
+    /// Record the failure in
+    /// [`TlsMetadata::cert_validation`](crate::TlsMetadata::cert_validation)
+    /// and carry on, so that misconfigured hosts still report their group,
+    /// cipher and certificates. Unlike
+    /// [`danger_accept_invalid_certs`](crate::TlsAwareClientBuilder::danger_accept_invalid_certs),
+    /// every chain is still verified.
+    ReportOnly,
+}
+
+impl fmt::Display for CertPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CertPolicy::Enforce => "enforce",
+            CertPolicy::ReportOnly => "report-only",
+        })
+    }
+}
+
+impl FromStr for CertPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "enforce" => Ok(CertPolicy::Enforce),
+            "report-only" | "report" => Ok(CertPolicy::ReportOnly),
+            other => Err(format!("unknown certificate policy: {}", other)),
+        }
+    }
+}
+
+/// Validates chains against a client's roots to report how they were
+/// anchored.
+pub(crate) struct ChainValidator {
+    roots: Arc<RootCertStore>,
+    algorithms: &'static [&'static dyn SignatureVerificationAlgorithm],
+}
+
+impl ChainValidator {
+    pub(crate) fn new(roots: Arc<RootCertStore>, provider: &CryptoProvider) -> Self {
+        Self {
+            roots,
+            algorithms: provider.signature_verification_algorithms.all,
+        }
+    }
+
+    pub(crate) fn validate(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        now: UnixTime,
+    ) -> ValidationReport {
+        let start = Instant::now();
+        let mut report = ValidationReport {
+            trust_anchor: None,
+            chain_length: 1 + intermediates.len(),
+            path_intermediates: None,
+            cross_signed: false,
+            duration: Duration::ZERO,
+            error: None,
+        };
+        if let Err(e) = self.check(end_entity, intermediates, server_name, now, &mut report) {
+            report.error = Some(format!("{:?}", e));
+        }
+        report.duration = start.elapsed();
+        report
+    }
+
+    /// Build the path to a root, then check the name, filling in `report`
+    /// as far as it gets.
+    fn check(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        now: UnixTime,
+        report: &mut ValidationReport,
+    ) -> Result<(), webpki::Error> {
+        let cert = EndEntityCert::try_from(end_entity)?;
+        let path = cert.verify_for_usage(
+            self.algorithms,
+            &self.roots.roots,
+            intermediates,
+            now,
+            KeyUsage::server_auth(),
+            None,
+            None,
+        )?;
+        report.trust_anchor = Some(name(path.anchor().subject.as_ref()));
+        report.path_intermediates = Some(path.intermediate_certificates().count());
+        report.cross_signed = path.intermediate_certificates().any(|intermediate| {
+            self.roots
+                .roots
+                .iter()
+                .any(|root| root.subject.as_ref() == intermediate.subject())
+        });
+        cert.verify_is_valid_for_subject_name(server_name)
+    }
+}
+
+/// Render a distinguished name given, as webpki keeps it, without its
+/// outer `SEQUENCE` header.
+fn name(contents: &[u8]) -> String {
+    let mut der = vec![0x30];
+    match contents.len() {
+        len @ 0..0x80 => der.push(len as u8),
+        len @ 0x80..0x100 => der.extend_from_slice(&[0x81, len as u8]),
+        len => der.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]),
+    }
+    der.extend_from_slice(contents);
+    match X509Name::from_der(&der) {
+        Ok((_, name)) => name.to_string(),
+        Err(_) => "unparsable name".to_string(),
+    }
+}
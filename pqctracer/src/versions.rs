@@ -0,0 +1,164 @@
+//! Which TLS versions a server accepts, probed with one handshake restricted
+//! to each version. Post-quantum key exchange needs TLS 1.3.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use rustls::{AlertDescription, PeerIncompatible, SupportedProtocolVersion};
+use rustls_pki_types::ServerName;
+use tokio_rustls::TlsConnector;
+
+use crate::{ErrorCategory, TlsAwareClient, TlsMetadata, TraceError};
+
+/// How a server answered handshakes restricted to each TLS version.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VersionSupportReport {
+    pub tls13: VersionSupport,
+    pub tls12: VersionSupport,
+}
+
+impl VersionSupportReport {
+    /// Report `error` for both versions, for probes that cannot start.
+    fn failed(error: ErrorCategory) -> Self {
+        Self {
+            tls13: VersionSupport::Failed {
+                error: error.clone(),
+            },
+            tls12: VersionSupport::Failed { error },
+        }
+    }
+}
+
+/// The outcome of a handshake offering a single TLS version.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "outcome", rename_all = "kebab-case"))]
+pub enum VersionSupport {
+    /// The handshake completed.
+    Accepted { metadata: Box<TlsMetadata> },
+    /// The server refused the version, e.g. with a `protocol_version`
+    /// alert; `reason` is the rustls error.
+    Rejected { reason: String },
+    /// The probe failed for another reason, e.g. a network error or an
+    /// invalid certificate, so it says nothing about the version.
+    Failed { error: ErrorCategory },
+}
+
+impl VersionSupport {
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, VersionSupport::Accepted { .. })
+    }
+
+    fn of(result: Result<TlsMetadata, (TraceError, bool)>) -> Self {
+        match result {
+            Ok(metadata) => VersionSupport::Accepted {
+                metadata: Box::new(metadata),
+            },
+            Err((TraceError::Tls(e), false)) if refuses_version(&e) => VersionSupport::Rejected {
+                reason: format!("{:?}", e),
+            },
+            Err((e, connecting)) => VersionSupport::Failed {
+                error: ErrorCategory::of_trace(&e, connecting),
+            },
+        }
+    }
+}
+
+/// `accepted (X25519MLKEM768)`, `rejected (AlertReceived(ProtocolVersion))`
+/// or `failed (connect timeout)`.
+impl fmt::Display for VersionSupport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VersionSupport::Accepted { metadata } => {
+                write!(
+                    f,
+                    "accepted ({})",
+                    metadata.group.as_deref().unwrap_or("n/a")
+                )
+            }
+            VersionSupport::Rejected { reason } => write!(f, "rejected ({})", reason),
+            VersionSupport::Failed { error } => write!(f, "failed ({})", error),
+        }
+    }
+}
+
+/// Whether the handshake failed because the server does not speak the
+/// offered version. Servers answer with a `protocol_version` alert, or just
+/// `handshake_failure`, or pick a version the client did not offer.
+fn refuses_version(error: &rustls::Error) -> bool {
+    match error {
+        rustls::Error::AlertReceived(alert) => matches!(
+            alert,
+            AlertDescription::ProtocolVersion | AlertDescription::HandshakeFailure
+        ),
+        rustls::Error::PeerIncompatible(incompatible) => matches!(
+            incompatible,
+            PeerIncompatible::ServerDoesNotSupportTls12Or13
+                | PeerIncompatible::ServerTlsVersionIsDisabledByOurConfig
+                | PeerIncompatible::SupportedVersionsExtensionRequired
+                | PeerIncompatible::Tls12NotOffered
+                | PeerIncompatible::Tls12NotOfferedOrEnabled
+        ),
+        _ => false,
+    }
+}
+
+/// Probe which TLS versions `host:port` accepts, using a default
+/// [`TlsAwareClient`].
+pub async fn probe_tls_versions(host: &str, port: u16) -> VersionSupportReport {
+    TlsAwareClient::new().probe_tls_versions(host, port).await
+}
+
+impl TlsAwareClient {
+    /// Attempt one handshake offering only TLS 1.3 and one offering only
+    /// TLS 1.2 with `host:port`, and report how the server answered each.
+    pub async fn probe_tls_versions(&self, host: &str, port: u16) -> VersionSupportReport {
+        let Ok(server_name) = ServerName::try_from(host.to_string()) else {
+            return VersionSupportReport::failed(ErrorCategory::InvalidUrl);
+        };
+        let addr = match self.bind.lookup(host, port).await {
+            Ok(addrs) => addrs.into_iter().next(),
+            Err(_) => None,
+        };
+        match addr {
+            Some(addr) => self.probe_tls_versions_at(addr, server_name).await,
+            None => VersionSupportReport::failed(ErrorCategory::DnsFailure),
+        }
+    }
+
+    /// Like [`probe_tls_versions`](Self::probe_tls_versions) for an address
+    /// already resolved.
+    pub(crate) async fn probe_tls_versions_at(
+        &self,
+        addr: SocketAddr,
+        server_name: ServerName<'static>,
+    ) -> VersionSupportReport {
+        VersionSupportReport {
+            tls13: self
+                .probe_version(addr, server_name.clone(), &rustls::version::TLS13)
+                .await,
+            tls12: self
+                .probe_version(addr, server_name, &rustls::version::TLS12)
+                .await,
+        }
+    }
+
+    async fn probe_version(
+        &self,
+        addr: SocketAddr,
+        server_name: ServerName<'static>,
+        version: &'static SupportedProtocolVersion,
+    ) -> VersionSupport {
+        let connector = match self.direct_tls_config_with(None, &[version]) {
+            Ok(config) => TlsConnector::from(Arc::new(config)),
+            Err(e) => {
+                return VersionSupport::Failed {
+                    error: ErrorCategory::of_trace(&e, false),
+                };
+            }
+        };
+        VersionSupport::of(self.handshake_at(addr, server_name, &connector).await)
+    }
+}
@@ -0,0 +1,210 @@
+//! TLS probing for `wss://` endpoints: the HTTP/1.1 upgrade (RFC 6455) runs
+//! over a connection set up with the client's TLS configuration, so the
+//! negotiated parameters are reported alongside the upgraded stream.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use aws_lc_rs::digest;
+use aws_lc_rs::rand::{SecureRandom, SystemRandom};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use reqwest::Url;
+use rustls_pki_types::ServerName;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+use crate::{TlsAwareClient, TlsMetadata, TraceError, idn, key_shares, logging};
+
+/// The TLS connection the upgrade was made on, positioned right after the
+/// server's `101` response. It carries raw WebSocket frames but does not
+/// frame them itself: hand it to a WebSocket implementation that accepts an
+/// already upgraded stream, e.g.
+/// `tokio_tungstenite::WebSocketStream::from_raw_socket`.
+pub type UpgradedStream = tokio_rustls::client::TlsStream<TcpStream>;
+
+/// Used when the client has no timeout of its own.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest response head accepted before the upgrade is considered failed.
+const MAX_RESPONSE_HEAD: usize = 16 * 1024;
+
+/// Appended to the key to compute `Sec-WebSocket-Accept` (RFC 6455, 1.3).
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+impl TlsAwareClient {
+    /// Open a WebSocket connection to a `wss://` URL and report the TLS
+    /// parameters negotiated for it.
+    ///
+    /// The connection is made directly, without the client's proxy, and
+    /// redirects are not followed: a `3xx` answer to the upgrade request is
+    /// [`TraceError::WebSocketRedirected`], any other status but `101` is
+    /// [`TraceError::WebSocketRejected`].
+    pub async fn websocket(&self, url: &str) -> Result<(UpgradedStream, TlsMetadata), TraceError> {
+        let url = Url::parse(&idn::normalize_url(url)?)
+            .map_err(|e| TraceError::InvalidUrl(format!("{}: {}", url, e)))?;
+        if url.scheme() != "wss" {
+            return Err(TraceError::InvalidUrl(format!(
+                "{}: scheme must be wss",
+                url
+            )));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| TraceError::InvalidUrl(format!("{}: missing host", url)))?
+            .trim_matches(['[', ']'])
+            .to_string();
+        let port = url.port_or_known_default().unwrap_or(443);
+        let server_name = ServerName::try_from(host.clone())
+            .map_err(|_| TraceError::InvalidServerName(host.clone()))?;
+
+        let connect_timeout = self
+            .connect_timeout
+            .or(self.timeout)
+            .unwrap_or(DEFAULT_TIMEOUT);
+        let timeout = self.timeout.unwrap_or(DEFAULT_TIMEOUT);
+        let stream = tokio::time::timeout(connect_timeout, self.bind.connect_host(&host, port))
+            .await
+            .map_err(|_| TraceError::Timeout)??;
+        let (local_addr, remote_addr) = (stream.local_addr().ok(), stream.peer_addr().ok());
+
+        let mut config = self.direct_tls_config(None)?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        let connector = TlsConnector::from(Arc::new(config));
+        let start = Instant::now();
+        let handshake =
+            key_shares::observe(async { connector.connect(server_name.clone(), stream).await });
+        let (tls, key_shares) = tokio::time::timeout(timeout, handshake)
+            .await
+            .map_err(|_| TraceError::Timeout)?;
+        let mut tls = tls.inspect_err(|e| logging::failure(&host, e))?;
+        let metadata = TlsMetadata {
+            local_addr,
+            remote_addr,
+            idn: idn::IdnName::of(&host),
+            handshake_duration: Some(start.elapsed()),
+            hrr_penalty: key_shares.hrr_penalty(),
+            client_cert_requested: Some(key_shares.client_cert_requested()),
+            client_cert_authorities: key_shares.client_cert_authorities(),
+            cert_validation: self.cert_validation(&server_name, tls.get_ref().1),
+            pin_matched: self.pin_matched(&server_name, tls.get_ref().1),
+            ..TlsMetadata::from_connection(tls.get_ref().1)
+        };
+        self.log_handshake(&host, &metadata);
+
+        let key = websocket_key()?;
+        tokio::time::timeout(timeout, upgrade(&mut tls, &url, &key))
+            .await
+            .map_err(|_| TraceError::Timeout)??;
+        Ok((tls, metadata))
+    }
+}
+
+/// A fresh `Sec-WebSocket-Key`: 16 random bytes, base64-encoded.
+fn websocket_key() -> Result<String, TraceError> {
+    let mut nonce = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| TraceError::Config("no system random number generator".to_string()))?;
+    Ok(BASE64.encode(nonce))
+}
+
+fn expected_accept(key: &str) -> String {
+    let mut context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+    context.update(key.as_bytes());
+    context.update(ACCEPT_GUID.as_bytes());
+    BASE64.encode(context.finish())
+}
+
+/// Send the upgrade request and check the server's answer.
+async fn upgrade(tls: &mut UpgradedStream, url: &Url, key: &str) -> Result<(), TraceError> {
+    let mut target = url.path().to_string();
+    if let Some(query) = url.query() {
+        target.push('?');
+        target.push_str(query);
+    }
+    let authority = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        target, authority, key
+    );
+    tls.write_all(request.as_bytes()).await?;
+    tls.flush().await?;
+
+    let head = read_response_head(tls).await?;
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next().unwrap_or_default();
+    let mut parts = status_line.splitn(3, ' ');
+    let status = match (
+        parts.next(),
+        parts.next().and_then(|s| s.parse::<u16>().ok()),
+    ) {
+        (Some(version), Some(status)) if version.starts_with("HTTP/") => status,
+        _ => {
+            return Err(TraceError::WebSocketProtocol(format!(
+                "malformed status line: {}",
+                status_line
+            )));
+        }
+    };
+    let reason = parts.next().unwrap_or_default().to_string();
+    let headers: Vec<(&str, &str)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .collect();
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    };
+
+    match status {
+        101 => {}
+        300..=399 => {
+            return Err(TraceError::WebSocketRedirected {
+                status,
+                location: header("Location").map(str::to_string),
+            });
+        }
+        _ => return Err(TraceError::WebSocketRejected { status, reason }),
+    }
+    if !header("Upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket")) {
+        return Err(TraceError::WebSocketProtocol(
+            "101 response without Upgrade: websocket".to_string(),
+        ));
+    }
+    if header("Sec-WebSocket-Accept") != Some(expected_accept(key).as_str()) {
+        return Err(TraceError::WebSocketProtocol(
+            "Sec-WebSocket-Accept does not match the key".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Read up to and including the blank line ending the response head, one
+/// byte at a time so that no WebSocket frame bytes are consumed.
+async fn read_response_head(tls: &mut UpgradedStream) -> Result<String, TraceError> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_RESPONSE_HEAD {
+            return Err(TraceError::WebSocketProtocol(
+                "response head too large".to_string(),
+            ));
+        }
+        let mut byte = [0u8; 1];
+        if tls.read(&mut byte).await? == 0 {
+            return Err(TraceError::WebSocketProtocol(
+                "connection closed before the upgrade response".to_string(),
+            ));
+        }
+        head.push(byte[0]);
+    }
+    head.truncate(head.len() - 4);
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
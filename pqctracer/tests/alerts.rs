@@ -0,0 +1,84 @@
+//! Warning alerts injected by the test server before it answers the
+//! ClientHello, as some TLS 1.2 servers send `unrecognized_name`. Only the
+//! handshakes the tracer drives itself see them.
+
+mod common;
+
+use pqctracer::scan::{ScanOptions, ScanTarget};
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::versions::VersionSupport;
+use pqctracer::{AlertInfo, AlertLevel, TlsAwareClient, TlsMetadata};
+use rustls::AlertDescription;
+
+async fn warning_server() -> TestTlsServer {
+    common::start(
+        TestTlsServer::builder()
+            .tls12_only(true)
+            .warning_alert(AlertDescription::UnrecognisedName),
+    )
+    .await
+}
+
+fn unrecognised_name() -> AlertInfo {
+    AlertInfo {
+        level: AlertLevel::Warning,
+        description: "UnrecognisedName".to_string(),
+    }
+}
+
+/// A scan of `server`'s address, a handshake the tracer drives itself.
+async fn handshake(client: &TlsAwareClient, server: &TestTlsServer) -> TlsMetadata {
+    let target = ScanTarget::Address {
+        addr: server.local_addr(),
+        server_name: "localhost".to_string(),
+    };
+    let mut results = client.scan([target], &ScanOptions::default()).await;
+    let result = results.remove(0);
+    assert!(result.error.is_none(), "{:?}", result.error);
+    result.metadata
+}
+
+#[tokio::test]
+async fn a_warning_is_attached_to_the_handshake() {
+    let server = warning_server().await;
+    let client = common::client_for(&server).build().expect("build client");
+    let metadata = handshake(&client, &server).await;
+    assert_eq!(metadata.alerts_received, [unrecognised_name()]);
+    assert_eq!(
+        metadata.alerts_received[0].to_string(),
+        "warning UnrecognisedName"
+    );
+    // The handshake carried on regardless.
+    assert_eq!(metadata.group.as_deref(), Some("X25519"));
+    assert!(metadata.cipher.is_some());
+}
+
+#[tokio::test]
+async fn version_probes_see_the_warning() {
+    let server = warning_server().await;
+    let client = common::client_for(&server).build().expect("build client");
+    let report = client
+        .probe_tls_versions("localhost", server.local_addr().port())
+        .await;
+    let VersionSupport::Accepted { metadata } = &report.tls12 else {
+        panic!("{}", report.tls12);
+    };
+    assert_eq!(metadata.alerts_received, [unrecognised_name()]);
+}
+
+#[tokio::test]
+async fn quiet_servers_send_none() {
+    let server = common::start(TestTlsServer::builder().tls12_only(true)).await;
+    let client = common::client_for(&server).build().expect("build client");
+    assert!(handshake(&client, &server).await.alerts_received.is_empty());
+}
+
+#[tokio::test]
+async fn requests_do_not_see_alerts() {
+    // reqwest reads the connection, so the alert goes unnoticed.
+    let server = warning_server().await;
+    let client = common::client_for(&server).build().expect("build client");
+    let response = client.get(server.url()).send().await.expect("request");
+    assert!(response.metadata.alerts_received.is_empty());
+    assert!(!response.metadata.peer_certificates.is_empty());
+}
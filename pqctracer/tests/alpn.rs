@@ -0,0 +1,110 @@
+//! `alpn_protocols` against the test server, which like strict servers
+//! refuses a ClientHello offering only protocols it does not speak.
+
+mod common;
+
+use pqctracer::scan::{ScanOptions, ScanTarget};
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{ErrorCategory, HandshakeFailureKind, TlsAwareClient, TlsMetadata, TraceError};
+
+fn client(server: &TestTlsServer, protocols: &[&str]) -> TlsAwareClient {
+    common::client_for(server)
+        .alpn_protocols(protocols)
+        .build()
+        .expect("build client")
+}
+
+/// A scan of `server`'s address, whose ALPN outcome the metadata reports.
+async fn handshake(client: &TlsAwareClient, server: &TestTlsServer) -> TlsMetadata {
+    let target = ScanTarget::Address {
+        addr: server.local_addr(),
+        server_name: "localhost".to_string(),
+    };
+    let options = ScanOptions {
+        retries: 0,
+        ..ScanOptions::default()
+    };
+    let mut results = client.scan([target], &options).await;
+    let result = results.remove(0);
+    assert!(result.error.is_none(), "{:?}", result.error);
+    result.metadata
+}
+
+#[tokio::test]
+async fn a_bogus_protocol_alone_is_an_alpn_mismatch() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = client(&server, &["bogus/1"]);
+    let Err(error) = client.get(server.url()).send().await else {
+        panic!("the server speaks no bogus/1");
+    };
+    assert_eq!(
+        error.handshake_failure(),
+        Some(HandshakeFailureKind::AlpnMismatch),
+        "{:?}",
+        error
+    );
+    assert!(error.handshake_failure().unwrap().hint().is_some());
+
+    // Scans file it among the handshake failures.
+    let options = ScanOptions {
+        retries: 0,
+        ..ScanOptions::default()
+    };
+    let mut results = client.scan([server.url()], &options).await;
+    let error = results
+        .remove(0)
+        .error
+        .expect("the server speaks no bogus/1");
+    let ErrorCategory::TlsHandshakeFailure { detail } = &error.category else {
+        panic!("{:?}", error);
+    };
+    assert!(detail.contains("NoApplicationProtocol"), "{}", detail);
+    let observed = common::observed(&server, 2).await;
+    assert_eq!(
+        observed[0].error.as_deref(),
+        Some(rustls::Error::NoApplicationProtocol.to_string().as_str())
+    );
+}
+
+#[tokio::test]
+async fn a_bogus_protocol_with_a_known_one_gets_through() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = client(&server, &["bogus/1", "http/1.1"]);
+    client
+        .get(server.url())
+        .send()
+        .await
+        .expect("http/1.1 is offered too");
+    let metadata = handshake(&client, &server).await;
+    assert_eq!(metadata.alpn.as_deref(), Some("http/1.1"));
+}
+
+#[tokio::test]
+async fn without_alpn_none_is_selected() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    assert_eq!(handshake(&client, &server).await.alpn, None);
+}
+
+#[test]
+fn lists_requests_cannot_speak_are_refused() {
+    let long = "x".repeat(256);
+    for protocols in [&["h2"][..], &["http/1.1", "h3"], &[""], &[long.as_str()]] {
+        let Err(error) = TlsAwareClient::builder().alpn_protocols(protocols).build() else {
+            panic!("{:?} accepted", protocols);
+        };
+        assert!(matches!(error, TraceError::Config(_)), "{:?}", error);
+    }
+    assert_eq!(
+        TlsAwareClient::builder()
+            .alpn_protocols(&["h2"])
+            .build()
+            .err()
+            .map(|e| e.to_string()),
+        Some(
+            "invalid client configuration: ALPN protocol h2 offered, but requests speak \
+             HTTP/1.1 only"
+                .to_string()
+        )
+    );
+}
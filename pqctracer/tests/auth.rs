@@ -0,0 +1,143 @@
+//! Credentials across redirects between two local servers: kept for the
+//! same origin, dropped for another, whether reqwest or the cookie jar
+//! follows the redirect.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use pqctracer::TlsAwareClient;
+use pqctracer::testutil::TestTlsServer;
+
+/// The path and `Authorization` header of each request a server
+/// was sent.
+type Seen = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+/// A server noting what it is sent, answering `/cross` with `status` and
+/// a redirect to `cross`, `/same` with one to its own `/landed`, and
+/// anything else with `200 OK`.
+async fn server(status: &'static str, cross: Option<String>) -> (TestTlsServer, Seen) {
+    let seen = Seen::default();
+    let noted = seen.clone();
+    let server = common::start(TestTlsServer::builder().http_handler(move |request| {
+        let mut lines = request.lines();
+        let path = lines
+            .next()
+            .and_then(|line| line.split(' ').nth(1))
+            .unwrap_or_default()
+            .to_string();
+        let authorization = lines
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("authorization"))
+            .map(|(_, value)| value.trim().to_string());
+        noted.lock().unwrap().push((path.clone(), authorization));
+        match (path.as_str(), &cross) {
+            ("/cross", Some(cross)) => format!("{}\r\nLocation: {}", status, cross),
+            ("/same", _) => format!("{}\r\nLocation: /landed", status),
+            _ => "200 OK".to_string(),
+        }
+    }))
+    .await;
+    (server, seen)
+}
+
+/// An origin redirecting to a `landing` server on another port, and a
+/// client trusting both.
+async fn servers(status: &'static str) -> (TestTlsServer, Seen, TestTlsServer, Seen) {
+    let (landing, landing_seen) = server(status, None).await;
+    let target = format!("{}/landed", landing.url());
+    let (origin, origin_seen) = server(status, Some(target)).await;
+    (origin, origin_seen, landing, landing_seen)
+}
+
+fn client(origin: &TestTlsServer, landing: &TestTlsServer, cookies: bool) -> TlsAwareClient {
+    common::client_for(origin)
+        .add_root_certificate(landing.cert_der().clone())
+        .cookie_store(cookies)
+        .build()
+        .expect("build client")
+}
+
+fn seen(seen: &Seen) -> Vec<(String, Option<String>)> {
+    seen.lock().unwrap().clone()
+}
+
+/// One request to `/landed`, without credentials.
+fn landed_bare() -> Vec<(String, Option<String>)> {
+    vec![("/landed".to_string(), None)]
+}
+
+#[tokio::test]
+async fn basic_credentials_stay_behind_on_a_cross_origin_redirect() {
+    for cookies in [false, true] {
+        let (origin, origin_seen, landing, landing_seen) = servers("302 Found").await;
+        let response = client(&origin, &landing, cookies)
+            .get(format!("{}/cross", origin.url()))
+            .basic_auth("alice", Some("s3cret"))
+            .send()
+            .await
+            .expect("request");
+        assert_eq!(response.response.status(), 200);
+        assert_eq!(
+            response.response.url().port(),
+            Some(landing.local_addr().port())
+        );
+
+        // `alice:s3cret`, for the origin only.
+        let basic = "Basic YWxpY2U6czNjcmV0";
+        assert_eq!(
+            seen(&origin_seen),
+            [("/cross".to_string(), Some(basic.to_string()))],
+            "cookies: {}",
+            cookies
+        );
+        assert_eq!(seen(&landing_seen), landed_bare(), "cookies: {}", cookies);
+    }
+}
+
+#[tokio::test]
+async fn bearer_tokens_stay_behind_on_a_cross_origin_redirect() {
+    for cookies in [false, true] {
+        // A 307 repeats the request as it was, but without the token.
+        let (origin, origin_seen, landing, landing_seen) = servers("307 Temporary Redirect").await;
+        let response = client(&origin, &landing, cookies)
+            .get(format!("{}/cross", origin.url()))
+            .bearer_auth("t0ken")
+            .send()
+            .await
+            .expect("request");
+        assert_eq!(response.response.status(), 200);
+        assert_eq!(
+            seen(&origin_seen)[0].1.as_deref(),
+            Some("Bearer t0ken"),
+            "cookies: {}",
+            cookies
+        );
+        assert_eq!(seen(&landing_seen), landed_bare(), "cookies: {}", cookies);
+    }
+}
+
+#[tokio::test]
+async fn credentials_follow_a_same_origin_redirect() {
+    for cookies in [false, true] {
+        let (origin, origin_seen, landing, landing_seen) = servers("302 Found").await;
+        let response = client(&origin, &landing, cookies)
+            .get(format!("{}/same", origin.url()))
+            .bearer_auth("t0ken")
+            .send()
+            .await
+            .expect("request");
+        assert_eq!(response.response.status(), 200);
+        assert_eq!(
+            seen(&origin_seen),
+            [
+                ("/same".to_string(), Some("Bearer t0ken".to_string())),
+                ("/landed".to_string(), Some("Bearer t0ken".to_string())),
+            ],
+            "cookies: {}",
+            cookies
+        );
+        assert!(seen(&landing_seen).is_empty());
+    }
+}
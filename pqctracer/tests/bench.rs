@@ -0,0 +1,99 @@
+//! `TlsAwareClient::bench` against a loopback server: a hybrid and a
+//! classical run, each with its own samples.
+
+mod common;
+
+use std::time::Duration;
+
+use pqctracer::TraceError;
+use pqctracer::bench::{BenchOptions, KxPreference};
+use pqctracer::testutil::TestTlsServer;
+use rustls::NamedGroup;
+
+#[tokio::test]
+async fn hybrid_and_classical_runs_both_sample() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("client");
+    let options = BenchOptions {
+        iterations: 3,
+        warmup: 1,
+        delay: Duration::from_millis(1),
+    };
+    let report = client
+        .bench("localhost", server.local_addr().port(), &options)
+        .await
+        .expect("bench");
+    assert_eq!(report.host, "localhost");
+    assert_eq!(report.port, server.local_addr().port());
+
+    let [hybrid, classical] = &report.runs[..] else {
+        panic!("{} runs", report.runs.len());
+    };
+    assert_eq!(hybrid.preference, KxPreference::HybridPreferred);
+    assert_eq!(classical.preference, KxPreference::ClassicalOnly);
+    assert_eq!(hybrid.offered_groups[0], "X25519MLKEM768");
+    assert!(
+        classical
+            .offered_groups
+            .iter()
+            .all(|g| !g.contains("MLKEM"))
+    );
+    assert!(classical.offered_groups.contains(&"X25519".to_string()));
+
+    for (run, group) in [(hybrid, "X25519MLKEM768"), (classical, "X25519")] {
+        assert_eq!(run.samples.len(), 3);
+        assert_eq!(run.durations.count, 3);
+        assert!(run.durations.min <= run.durations.median);
+        assert!(run.durations.median <= run.durations.max);
+        assert_eq!(run.hello_retries, 0);
+        assert!(run.hrr_penalties.is_none());
+        for sample in &run.samples {
+            assert_eq!(sample.metadata.group.as_deref(), Some(group));
+            assert_eq!(sample.metadata.handshake_duration, Some(sample.duration));
+            assert!(sample.bytes_sent > 0 && sample.bytes_received > 0);
+        }
+    }
+    // An ML-KEM-768 share and ciphertext are each over a kilobyte.
+    assert!(hybrid.bytes_sent > classical.bytes_sent + 1000);
+    assert!(hybrid.bytes_received > classical.bytes_received + 1000);
+
+    // One warmup and three samples a run, each on a fresh connection.
+    let observed = common::observed(&server, 8).await;
+    let groups: Vec<_> = observed.iter().map(|o| o.selected_group).collect();
+    let expected: Vec<_> = [NamedGroup::X25519MLKEM768; 4]
+        .into_iter()
+        .chain([NamedGroup::X25519; 4])
+        .map(Some)
+        .collect();
+    assert_eq!(groups, expected);
+}
+
+#[tokio::test]
+async fn retries_are_counted_per_run() {
+    let server = common::start(TestTlsServer::builder().groups([NamedGroup::secp256r1])).await;
+    let client = common::client_for(&server).build().expect("client");
+    let options = BenchOptions {
+        iterations: 2,
+        warmup: 0,
+        ..BenchOptions::default()
+    };
+    let report = client
+        .bench("localhost", server.local_addr().port(), &options)
+        .await
+        .expect("bench");
+    for run in &report.runs {
+        assert_eq!(run.hello_retries, 2, "{}", run.preference);
+        assert_eq!(run.hrr_penalties.expect("penalties").count, 2);
+    }
+}
+
+#[tokio::test]
+async fn zero_iterations_are_refused() {
+    let client = common::insecure_client();
+    let options = BenchOptions {
+        iterations: 0,
+        ..BenchOptions::default()
+    };
+    let e = client.bench("localhost", 443, &options).await.err();
+    assert!(matches!(e, Some(TraceError::Config(_))), "{:?}", e);
+}
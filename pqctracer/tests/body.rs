@@ -0,0 +1,106 @@
+//! `TlsResponse::text`, `bytes` and `json` keep the handshake's metadata,
+//! on success and when the body cannot be read.
+
+mod common;
+
+use pqctracer::TlsResponse;
+use pqctracer::testutil::TestTlsServer;
+
+/// The response of a timed request to a server answering it with `answer`,
+/// as `http_handler` takes it.
+async fn respond(answer: &'static str) -> (TlsResponse, TestTlsServer) {
+    let server = common::start(TestTlsServer::builder().http_handler(|_| answer.to_string())).await;
+    let client = common::client_for(&server)
+        .collect_timings(true)
+        .build()
+        .expect("build client");
+    let response = client.get(server.url()).send().await.expect("request");
+    (response, server)
+}
+
+/// An invalid chunked body: the head arrives, the body does not.
+const BROKEN_BODY: &str = "200 OK\r\nTransfer-Encoding: chunked";
+
+#[tokio::test]
+async fn text_keeps_the_metadata() {
+    let (response, _server) = respond("200 OK\r\n\r\nhello, world").await;
+    let expected = response.metadata.clone();
+    assert_eq!(expected.timings.unwrap().total, None);
+    let (text, metadata) = response.text().await.expect("text");
+    assert_eq!(text, "hello, world");
+    assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(metadata.group, expected.group);
+    assert_eq!(metadata.cipher, expected.cipher);
+    assert_eq!(metadata.peer_certificates, expected.peer_certificates);
+    // Reading the body is timed along with the rest of the request.
+    assert!(metadata.timings.unwrap().total.is_some());
+}
+
+#[tokio::test]
+async fn bytes_keeps_the_metadata() {
+    let (response, _server) = respond("200 OK\r\n\r\n\u{1}\u{2}\u{3}").await;
+    let (bytes, metadata) = response.bytes().await.expect("bytes");
+    assert_eq!(&bytes[..], [1, 2, 3]);
+    assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert!(metadata.cipher.is_some());
+}
+
+#[tokio::test]
+async fn unreadable_bodies_keep_the_metadata() {
+    let (response, _server) = respond(BROKEN_BODY).await;
+    let error = response.text().await.expect_err("invalid chunk");
+    assert!(
+        error.error.is_body() || error.error.is_decode(),
+        "{:?}",
+        error
+    );
+    assert_eq!(error.metadata.group.as_deref(), Some("X25519MLKEM768"));
+
+    let (response, _server) = respond(BROKEN_BODY).await;
+    let error = response.bytes().await.expect_err("invalid chunk");
+    assert_eq!(error.metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert!(error.metadata.cipher.is_some());
+}
+
+#[cfg(feature = "json")]
+mod json {
+    use super::*;
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Greeting {
+        greeting: String,
+        count: u32,
+    }
+
+    #[tokio::test]
+    async fn json_keeps_the_metadata() {
+        let (response, _server) =
+            respond("200 OK\r\n\r\n{\"greeting\": \"hello\", \"count\": 2}").await;
+        let (greeting, metadata) = response.json::<Greeting>().await.expect("json");
+        assert_eq!(
+            greeting,
+            Greeting {
+                greeting: "hello".to_string(),
+                count: 2
+            }
+        );
+        assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+        assert!(metadata.timings.unwrap().total.is_some());
+    }
+
+    #[tokio::test]
+    async fn invalid_json_keeps_the_metadata() {
+        let (response, _server) = respond("200 OK\r\n\r\n{\"greeting\": 1}").await;
+        let error = response.json::<Greeting>().await.expect_err("invalid JSON");
+        assert!(error.error.is_decode(), "{:?}", error);
+        assert_eq!(error.metadata.group.as_deref(), Some("X25519MLKEM768"));
+        assert!(error.metadata.cipher.is_some());
+
+        let (response, _server) = respond(BROKEN_BODY).await;
+        let error = response
+            .json::<Greeting>()
+            .await
+            .expect_err("invalid chunk");
+        assert_eq!(error.metadata.group.as_deref(), Some("X25519MLKEM768"));
+    }
+}
@@ -0,0 +1,212 @@
+//! `client_capabilities` and `report_meta` of clients on aws-lc-rs and on
+//! ring, checked against the build and what a server sees them offer.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{ClientCapabilities, KxPreference, TlsAwareClient};
+use rustls::crypto::{CryptoProvider, aws_lc_rs, ring};
+
+fn client(provider: CryptoProvider, preference: KxPreference) -> TlsAwareClient {
+    TlsAwareClient::builder()
+        .crypto_provider(Arc::new(provider))
+        .kx_preference(preference)
+        .danger_accept_invalid_certs(true)
+        .build()
+        .expect("build client")
+}
+
+/// The groups a server saw `client` offer, by the names the capabilities
+/// use.
+async fn offered(client: &TlsAwareClient) -> Vec<String> {
+    let server = common::start(TestTlsServer::builder()).await;
+    client.get(server.url()).send().await.expect("request");
+    let observed = common::observed(&server, 1).await;
+    observed[0]
+        .offered_groups
+        .iter()
+        .map(|group| format!("{:?}", group))
+        .collect()
+}
+
+fn has_ml_kem(capabilities: &ClientCapabilities) -> bool {
+    capabilities.kx_groups.iter().any(|g| g.contains("MLKEM"))
+}
+
+#[tokio::test]
+async fn aws_lc_rs_offers_ml_kem_first() {
+    let client = client(aws_lc_rs::default_provider(), KxPreference::HybridPreferred);
+    let capabilities = client.client_capabilities();
+    assert_eq!(capabilities.provider, "aws-lc-rs");
+    assert_eq!(capabilities.kx_groups[0], "X25519MLKEM768");
+    assert!(capabilities.pqc_capable);
+    assert!(capabilities.post_quantum_preferred);
+    assert!(capabilities.offers_post_quantum());
+    assert_eq!(capabilities.kx_preference, KxPreference::HybridPreferred);
+    assert!(
+        capabilities
+            .protocol_versions
+            .contains(&"TLS1.3".to_string())
+    );
+    assert!(!capabilities.cipher_suites.is_empty());
+    assert_eq!(offered(&client).await, capabilities.kx_groups);
+}
+
+#[tokio::test]
+async fn ring_offers_no_ml_kem() {
+    let client = client(ring::default_provider(), KxPreference::HybridPreferred);
+    let capabilities = client.client_capabilities();
+    assert_eq!(capabilities.provider, "ring");
+    assert!(!has_ml_kem(&capabilities), "{:?}", capabilities.kx_groups);
+    assert!(!capabilities.pqc_capable);
+    assert!(!capabilities.post_quantum_preferred);
+    assert!(!capabilities.offers_post_quantum());
+    assert!(!capabilities.fips);
+    assert_eq!(offered(&client).await, capabilities.kx_groups);
+}
+
+#[tokio::test]
+async fn classical_only_aws_lc_rs_stays_capable() {
+    let client = client(aws_lc_rs::default_provider(), KxPreference::ClassicalOnly);
+    let capabilities = client.client_capabilities();
+    assert_eq!(capabilities.provider, "aws-lc-rs");
+    assert!(!has_ml_kem(&capabilities), "{:?}", capabilities.kx_groups);
+    // The provider could, the preference keeps it from offering.
+    assert!(capabilities.pqc_capable);
+    assert!(!capabilities.post_quantum_preferred);
+    assert_eq!(offered(&client).await, capabilities.kx_groups);
+}
+
+#[test]
+fn the_providers_differ_in_groups_and_fingerprint() {
+    let aws_lc_rs =
+        client(aws_lc_rs::default_provider(), KxPreference::HybridPreferred).client_capabilities();
+    let ring =
+        client(ring::default_provider(), KxPreference::HybridPreferred).client_capabilities();
+    assert_ne!(aws_lc_rs.kx_groups, ring.kx_groups);
+    assert_ne!(aws_lc_rs.ja3n, ring.ja3n);
+
+    let printed = ring.to_string();
+    assert!(printed.starts_with("Provider: ring\n"), "{}", printed);
+    assert!(
+        printed.contains("\nPost-quantum capable: no\n"),
+        "{}",
+        printed
+    );
+    let printed = aws_lc_rs.to_string();
+    assert!(
+        printed.contains("\nPost-quantum capable: yes\n"),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains("\nPost-quantum preferred: yes\n"),
+        "{}",
+        printed
+    );
+}
+
+#[tokio::test]
+async fn the_meta_block_describes_the_build_and_client() {
+    let hybrid = client(aws_lc_rs::default_provider(), KxPreference::HybridPreferred);
+    let meta = hybrid.report_meta();
+    let capabilities = hybrid.client_capabilities();
+    assert_eq!(meta.pqctracer_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(meta.rustls_version, "0.23");
+    assert_eq!(meta.provider, "aws-lc-rs");
+    assert!(!meta.fips);
+    // This crate builds rustls with `prefer-post-quantum`.
+    assert!(meta.prefer_post_quantum);
+    assert_eq!(meta.kx_preference, KxPreference::HybridPreferred);
+    assert_eq!(meta.kx_groups, capabilities.kx_groups);
+    assert_eq!(meta.kx_groups, offered(&hybrid).await);
+    assert_eq!(meta.os, std::env::consts::OS);
+    assert_eq!(meta.arch, std::env::consts::ARCH);
+    // The integration tests always build the test server.
+    assert!(meta.features.contains(&"test-util".to_string()));
+    assert_eq!(
+        meta.features.contains(&"dns".to_string()),
+        cfg!(feature = "dns")
+    );
+    let age = SystemTime::now()
+        .duration_since(meta.generated_at)
+        .expect("generated in the past");
+    assert!(age < Duration::from_secs(60), "{:?}", age);
+
+    let printed = meta.to_string();
+    assert!(
+        printed.starts_with(&format!("pqctracer: {}\n", env!("CARGO_PKG_VERSION"))),
+        "{}",
+        printed
+    );
+    assert!(printed.contains("\nProvider: aws-lc-rs\n"), "{}", printed);
+    assert!(
+        printed.contains(&format!(
+            "\nKey-exchange groups: {}\n",
+            meta.kx_groups.join(", ")
+        )),
+        "{}",
+        printed
+    );
+
+    // A ring client has a block of its own.
+    let ring = client(ring::default_provider(), KxPreference::ClassicalOnly).report_meta();
+    assert_eq!(ring.provider, "ring");
+    assert_eq!(ring.kx_preference, KxPreference::ClassicalOnly);
+    assert_ne!(ring.kx_groups, meta.kx_groups);
+}
+
+#[cfg(all(feature = "serde", feature = "json"))]
+#[test]
+fn the_meta_block_round_trips_through_json() {
+    let meta = client(aws_lc_rs::default_provider(), KxPreference::HybridPreferred).report_meta();
+    let json = serde_json::to_value(&meta).expect("serialize");
+    let fields: Vec<&str> = json
+        .as_object()
+        .expect("object")
+        .keys()
+        .map(String::as_str)
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            "arch",
+            "features",
+            "fips",
+            "generated_at",
+            "kx_groups",
+            "kx_preference",
+            "os",
+            "pqctracer_version",
+            "prefer_post_quantum",
+            "provider",
+            "rustls_version",
+        ]
+    );
+    assert_eq!(json["provider"], "aws-lc-rs");
+    assert_eq!(json["kx_groups"][0], "X25519MLKEM768");
+    let generated_at = json["generated_at"].as_str().expect("timestamp");
+    assert!(generated_at.ends_with('Z'), "{}", generated_at);
+
+    let read: pqctracer::ReportMeta = serde_json::from_value(json).expect("deserialize");
+    // The timestamp keeps whole seconds.
+    let whole_seconds = |time: SystemTime| {
+        time.duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    };
+    assert_eq!(
+        whole_seconds(read.generated_at),
+        whole_seconds(meta.generated_at)
+    );
+    assert_eq!(
+        pqctracer::ReportMeta {
+            generated_at: meta.generated_at,
+            ..read
+        },
+        meta
+    );
+}
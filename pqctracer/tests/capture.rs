@@ -0,0 +1,74 @@
+//! Concurrent requests through one client must each report their own
+//! handshake, also when they go to the same host.
+
+mod common;
+
+use std::sync::Arc;
+
+use futures_util::future::join_all;
+use pqctracer::HandshakeOrigin;
+use pqctracer::testutil::TestTlsServer;
+use rustls::NamedGroup;
+
+/// Servers on one host name that differ in their group and certificate.
+async fn servers() -> Vec<(TestTlsServer, NamedGroup)> {
+    let mut servers = Vec::new();
+    for group in [NamedGroup::X25519, NamedGroup::secp256r1, NamedGroup::secp384r1] {
+        let server = common::start(TestTlsServer::builder().groups([group])).await;
+        servers.push((server, group));
+    }
+    servers
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_requests_to_one_host_keep_their_own_handshake() {
+    let servers = servers().await;
+    let client = Arc::new(common::insecure_client());
+
+    let requests = (0..120).map(|i| {
+        let client = client.clone();
+        let (server, group) = &servers[i % servers.len()];
+        let url = format!("{}/{}", server.url(), i);
+        let expected_cert = server.cert_der().clone();
+        let expected_group = format!("{:?}", group);
+        async move {
+            let metadata = client.get(url.as_str()).send().await.expect("request").metadata;
+            match metadata.origin {
+                Some(HandshakeOrigin::Fresh | HandshakeOrigin::SharedConnection) => {
+                    assert_eq!(metadata.group.as_deref(), Some(expected_group.as_str()), "{}", url);
+                    assert_eq!(metadata.peer_certificates.first(), Some(&expected_cert), "{}", url);
+                    metadata.origin == Some(HandshakeOrigin::Fresh)
+                }
+                // A pooled connection the client no longer knows reports
+                // nothing, which is never another request's handshake.
+                _ => {
+                    assert_eq!(metadata.group, None, "{}", url);
+                    assert!(metadata.peer_certificates.is_empty(), "{}", url);
+                    false
+                }
+            }
+        }
+    });
+    let fresh = join_all(requests).await.into_iter().filter(|fresh| *fresh).count();
+    assert!(fresh >= servers.len(), "only {} fresh handshakes", fresh);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn every_fresh_handshake_reports_its_own_group_and_cipher() {
+    let servers = servers().await;
+    let requests = (0..30).map(|i| {
+        let (server, group) = &servers[i % servers.len()];
+        let url = server.url();
+        let expected_group = format!("{:?}", group);
+        async move {
+            // A client per request, so that every request makes a fresh
+            // handshake, all of them at once.
+            let client = common::insecure_client();
+            let metadata = client.get(url.as_str()).send().await.expect("request").metadata;
+            assert_eq!(metadata.origin, Some(HandshakeOrigin::Fresh));
+            assert_eq!(metadata.group.as_deref(), Some(expected_group.as_str()));
+            assert_eq!(metadata.cipher.as_deref(), Some("TLS13_AES_256_GCM_SHA384"));
+        }
+    });
+    join_all(requests).await;
+}
@@ -0,0 +1,152 @@
+//! One request per `CaptureStatus` reason, each against a server set up to
+//! bring it about.
+
+mod common;
+
+use std::sync::Arc;
+
+use pqctracer::testutil::{TestTlsServer, handshake_in_memory};
+use pqctracer::{CaptureStatus, HandshakeOrigin, TlsAwareClient, TlsMetadata};
+use rustls::crypto::aws_lc_rs;
+use rustls::{ClientConfig, RootCertStore};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// A plain HTTP server answering every request with `response`.
+async fn plain_server(response: String) -> u16 {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind");
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let response = response.clone();
+            tokio::spawn(async move {
+                let mut request = vec![0; 4096];
+                let _ = stream.read(&mut request).await;
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    port
+}
+
+/// A plain HTTP server redirecting every request to `server`, under the
+/// name `localhost`.
+async fn redirect_to(server: &TestTlsServer) -> String {
+    let port = plain_server(format!(
+        "HTTP/1.1 302 Found\r\nLocation: {}/\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        server.url()
+    ))
+    .await;
+    format!("http://127.0.0.1:{}/", port)
+}
+
+async fn get(client: &TlsAwareClient, url: &str) -> TlsMetadata {
+    client.get(url).send().await.expect("request").metadata
+}
+
+#[tokio::test]
+async fn captured() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let metadata = get(&common::insecure_client(), &server.url()).await;
+    assert_eq!(metadata.capture_status, CaptureStatus::Captured);
+    assert!(metadata.group.is_some() && metadata.cipher.is_some());
+}
+
+#[tokio::test]
+async fn no_session_ticket() {
+    // Only configurations the tracer did not build hide the cipher.
+    let (mut server_config, cert) = common::server_config(&[]);
+    server_config.send_tls13_tickets = 0;
+    let mut roots = RootCertStore::empty();
+    roots.add(cert).expect("root");
+    let client_config =
+        ClientConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+            .with_safe_default_protocol_versions()
+            .expect("protocol versions")
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    let (metadata, _) = handshake_in_memory(client_config, Arc::new(server_config))
+        .await
+        .expect("handshake");
+    assert_eq!(metadata.capture_status, CaptureStatus::NoSessionTicket);
+    assert!(metadata.group.is_some());
+    assert_eq!(metadata.tickets_issued, Some(0));
+}
+
+#[tokio::test]
+async fn tls12_no_data() {
+    let server = common::start(TestTlsServer::builder().tls12_only(true)).await;
+    let metadata = get(&common::insecure_client(), &server.url()).await;
+    assert_eq!(metadata.capture_status, CaptureStatus::Tls12NoData);
+    assert_eq!(metadata.group, None);
+    assert!(!metadata.peer_certificates.is_empty());
+}
+
+#[tokio::test]
+async fn plaintext() {
+    let port = plain_server(
+        "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok".to_string(),
+    )
+    .await;
+    let metadata = get(
+        &common::insecure_client(),
+        &format!("http://127.0.0.1:{}/", port),
+    )
+    .await;
+    assert_eq!(metadata.capture_status, CaptureStatus::Plaintext);
+    assert_eq!(metadata.origin, None);
+}
+
+#[tokio::test]
+async fn callback_missed() {
+    // The request is registered for 127.0.0.1; the handshake after the
+    // redirect is with localhost.
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::insecure_client();
+    let metadata = get(&client, &redirect_to(&server).await).await;
+    assert_eq!(metadata.capture_status, CaptureStatus::CallbackMissed);
+    assert_eq!(metadata.group, None);
+    assert_eq!(metadata.origin, None);
+    assert_eq!(common::observed(&server, 1).await.len(), 1);
+}
+
+#[tokio::test]
+async fn reused_connection() {
+    // The redirect's connection goes back into the pool without a
+    // handshake on record, and the next request to localhost takes it.
+    let server = common::start(TestTlsServer::builder().keep_alive(true)).await;
+    let client = common::insecure_client();
+    get(&client, &redirect_to(&server).await).await;
+    let metadata = get(&client, &server.url()).await;
+    assert_eq!(metadata.capture_status, CaptureStatus::ReusedConnection);
+    assert_eq!(metadata.origin, Some(HandshakeOrigin::Reused));
+    assert_eq!(metadata.group, None);
+    assert_eq!(common::observed(&server, 1).await.len(), 1);
+}
+
+#[test]
+fn unknown() {
+    assert_eq!(
+        TlsMetadata::default().capture_status,
+        CaptureStatus::Unknown
+    );
+}
+
+#[test]
+fn every_reason_has_its_own_text() {
+    let reasons = [
+        CaptureStatus::Captured,
+        CaptureStatus::NoSessionTicket,
+        CaptureStatus::Tls12NoData,
+        CaptureStatus::ReusedConnection,
+        CaptureStatus::Plaintext,
+        CaptureStatus::CallbackMissed,
+        CaptureStatus::Unknown,
+    ];
+    let mut texts: Vec<String> = reasons.iter().map(ToString::to_string).collect();
+    assert_eq!(texts[3], "connection reused, no handshake observed");
+    texts.sort();
+    texts.dedup();
+    assert_eq!(texts.len(), reasons.len());
+}
@@ -0,0 +1,228 @@
+//! Servers asking for a client certificate, through `WebPkiClientVerifier`
+//! in optional mode: the request is reported with the certificate
+//! authorities the server named, over TLS 1.3 and 1.2, with and without a
+//! certificate of the client's own to answer it with.
+
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use pqctracer::TlsAwareClientBuilder;
+use pqctracer::scan::{ScanOptions, ScanTarget};
+use pqctracer::testutil::TestTlsServer;
+use rcgen::{
+    BasicConstraints, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose, IsCa,
+    Issuer, KeyPair,
+};
+use rustls::client::ResolvesClientCert;
+use rustls::crypto::aws_lc_rs;
+use rustls::server::WebPkiClientVerifier;
+use rustls::sign::CertifiedKey;
+use rustls::{RootCertStore, ServerConfig, SignatureScheme, SupportedProtocolVersion};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+/// The client CA's name, as the metadata reports it.
+const CA_NAME: &str = "CN=pqctracer Test Client CA, O=pqctracer";
+
+/// A certificate authority for client certificates.
+struct ClientCa {
+    cert: CertificateDer<'static>,
+    issuer: Issuer<'static, KeyPair>,
+}
+
+impl ClientCa {
+    fn new() -> Self {
+        let mut params = CertificateParams::new(Vec::<String>::new()).expect("CA params");
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name = DistinguishedName::new();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "pqctracer Test Client CA");
+        params
+            .distinguished_name
+            .push(DnType::OrganizationName, "pqctracer");
+        let key = KeyPair::generate().expect("CA key");
+        let cert = params.self_signed(&key).expect("CA cert").der().clone();
+        Self {
+            cert,
+            issuer: Issuer::new(params, key),
+        }
+    }
+
+    /// A client certificate issued by the CA, with its key.
+    fn client_identity(&self) -> Arc<CertifiedKey> {
+        let mut params = CertificateParams::new(Vec::<String>::new()).expect("client params");
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "pqctracer test client");
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let key = KeyPair::generate().expect("client key");
+        let cert = params.signed_by(&key, &self.issuer).expect("client cert");
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der()));
+        let signing_key = aws_lc_rs::sign::any_supported_type(&key).expect("signing key");
+        Arc::new(CertifiedKey::new(vec![cert.der().clone()], signing_key))
+    }
+}
+
+/// Answers every request for a client certificate with the same one.
+#[derive(Debug)]
+struct Identity(Arc<CertifiedKey>);
+
+impl ResolvesClientCert for Identity {
+    fn resolve(&self, _: &[&[u8]], _: &[SignatureScheme]) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.clone())
+    }
+
+    fn has_certs(&self) -> bool {
+        true
+    }
+}
+
+/// Whether each client a server accepted presented a certificate.
+type Presented = Arc<Mutex<Vec<bool>>>;
+
+/// A server for `localhost` speaking `version`, asking for, but not
+/// requiring, a certificate from `ca`, and answering HTTP requests with
+/// `200 OK`.
+async fn server(
+    ca: &ClientCa,
+    version: &'static SupportedProtocolVersion,
+) -> (SocketAddr, CertificateDer<'static>, Presented) {
+    let certified = rcgen::generate_simple_self_signed(["localhost".to_string()]).expect("cert");
+    let cert = certified.cert.der().clone();
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+        certified.signing_key.serialize_der(),
+    ));
+    let provider = Arc::new(aws_lc_rs::default_provider());
+    let mut roots = RootCertStore::empty();
+    roots.add(ca.cert.clone()).expect("client CA");
+    let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .allow_unauthenticated()
+        .build()
+        .expect("client verifier");
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[version])
+        .expect("protocol versions")
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(vec![cert.clone()], key)
+        .expect("server config");
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr = listener.local_addr().unwrap();
+    let presented = Presented::default();
+    let noted = presented.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let Ok(mut tls) = acceptor.accept(stream).await else {
+                continue;
+            };
+            let client_cert = tls.get_ref().1.peer_certificates().is_some();
+            noted.lock().unwrap().push(client_cert);
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match tls.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(read) => request.extend_from_slice(&buf[..read]),
+                    }
+                }
+                let response =
+                    "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+                let _ = tls.write_all(response.as_bytes()).await;
+                let _ = tls.shutdown().await;
+            });
+        }
+    });
+    (addr, cert, presented)
+}
+
+fn client(cert: CertificateDer<'static>) -> TlsAwareClientBuilder {
+    common::client_builder().add_root_certificate(cert)
+}
+
+/// What the clients presented, once the server has accepted `count` of
+/// them; it finishes its side of the handshake after the client.
+async fn presented(presented: &Presented, count: usize) -> Vec<bool> {
+    for _ in 0..200 {
+        let seen = presented.lock().unwrap().clone();
+        if seen.len() >= count {
+            return seen;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("{} clients accepted", presented.lock().unwrap().len());
+}
+
+#[tokio::test]
+async fn servers_that_do_not_ask_are_reported_as_such() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let response = client.get(server.url()).send().await.expect("request");
+    assert_eq!(response.metadata.client_cert_requested, Some(false));
+    assert!(response.metadata.client_cert_authorities.is_empty());
+}
+
+#[tokio::test]
+async fn a_request_for_a_certificate_is_reported_without_an_identity() {
+    let ca = ClientCa::new();
+    for version in [&rustls::version::TLS13, &rustls::version::TLS12] {
+        let (addr, cert, seen) = server(&ca, version).await;
+        let client = client(cert).build().expect("build client");
+        let response = client
+            .get(format!("https://localhost:{}/", addr.port()))
+            .send()
+            .await
+            .expect("request");
+        assert_eq!(response.response.status(), 200);
+        let metadata = &response.metadata;
+        assert_eq!(metadata.client_cert_requested, Some(true), "{:?}", version);
+        assert_eq!(metadata.client_cert_authorities, [CA_NAME], "{:?}", version);
+        assert_eq!(metadata.peer_certificates.len(), 1);
+        // The handshake went on without a certificate.
+        assert_eq!(presented(&seen, 1).await, [false], "{:?}", version);
+    }
+}
+
+#[tokio::test]
+async fn a_request_for_a_certificate_is_reported_with_an_identity() {
+    let ca = ClientCa::new();
+    let (addr, cert, seen) = server(&ca, &rustls::version::TLS13).await;
+    let client = client(cert)
+        .client_cert_resolver(Arc::new(Identity(ca.client_identity())))
+        .build()
+        .expect("build client");
+    let response = client
+        .get(format!("https://localhost:{}/", addr.port()))
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(response.response.status(), 200);
+    assert_eq!(response.metadata.client_cert_requested, Some(true));
+    assert_eq!(response.metadata.client_cert_authorities, [CA_NAME]);
+    // The client's own resolver answered the request.
+    assert_eq!(presented(&seen, 1).await, [true]);
+}
+
+#[tokio::test]
+async fn the_tracers_own_handshakes_report_the_request_too() {
+    let ca = ClientCa::new();
+    let (addr, cert, seen) = server(&ca, &rustls::version::TLS13).await;
+    let client = client(cert).build().expect("build client");
+    let target = ScanTarget::Address {
+        addr,
+        server_name: "localhost".to_string(),
+    };
+    let results = client.scan([target], &ScanOptions::default()).await;
+    assert!(results[0].error.is_none(), "{:?}", results[0].error);
+    assert_eq!(results[0].metadata.client_cert_requested, Some(true));
+    assert_eq!(results[0].metadata.client_cert_authorities, [CA_NAME]);
+    assert_eq!(presented(&seen, 1).await, [false]);
+}
@@ -0,0 +1,88 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use pqctracer::testutil::{ObservedHandshake, TestTlsServer, TestTlsServerBuilder};
+use pqctracer::{TlsAwareClient, TlsAwareClientBuilder};
+use rustls::crypto::aws_lc_rs;
+use rustls::{NamedGroup, ServerConfig};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+
+/// A builder for a client trusting `server`'s certificate, with aws-lc-rs
+/// as the process-level provider; the dev-dependencies also compile ring.
+pub fn client_for(server: &TestTlsServer) -> TlsAwareClientBuilder {
+    install_provider();
+    TlsAwareClient::builder().add_root_certificate(server.cert_der().clone())
+}
+
+/// A builder for a client with the bundled roots only.
+pub fn client_builder() -> TlsAwareClientBuilder {
+    install_provider();
+    TlsAwareClient::builder()
+}
+
+/// A client that accepts any certificate.
+pub fn insecure_client() -> TlsAwareClient {
+    install_provider();
+    TlsAwareClient::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .expect("build client")
+}
+
+pub fn install_provider() {
+    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+}
+
+/// Start `builder`'s server on a loopback port.
+pub async fn start(builder: TestTlsServerBuilder) -> TestTlsServer {
+    builder.bind("127.0.0.1:0").await.expect("bind test server")
+}
+
+/// The handshakes `server` observed once there are `count` of them. The
+/// server records a handshake after finishing its side, which may be after
+/// the client has finished its own.
+pub async fn observed(server: &TestTlsServer, count: usize) -> Vec<ObservedHandshake> {
+    for _ in 0..200 {
+        let observed = server.observed();
+        if observed.len() >= count {
+            assert_eq!(observed.len(), count, "{:?}", observed);
+            return observed;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!(
+        "{} of {} handshakes observed",
+        server.observed().len(),
+        count
+    );
+}
+
+/// A server config for `localhost` accepting `groups`, or aws-lc-rs'
+/// defaults if there are none, and its self-signed certificate.
+pub fn server_config(groups: &[NamedGroup]) -> (ServerConfig, CertificateDer<'static>) {
+    let certified = rcgen::generate_simple_self_signed(["localhost".to_string()]).expect("cert");
+    let cert = certified.cert.der().clone();
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+        certified.signing_key.serialize_der(),
+    ));
+    let mut provider = aws_lc_rs::default_provider();
+    if !groups.is_empty() {
+        provider.kx_groups = aws_lc_rs::ALL_KX_GROUPS
+            .iter()
+            .copied()
+            .filter(|group| groups.contains(&group.name()))
+            .collect();
+    }
+    let mut config = ServerConfig::builder_with_provider(Arc::new(provider))
+        .with_safe_default_protocol_versions()
+        .expect("protocol versions")
+        .with_no_client_auth()
+        .with_single_cert(vec![cert.clone()], key)
+        .expect("server config");
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    (config, cert)
+}
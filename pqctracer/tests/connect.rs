@@ -0,0 +1,62 @@
+//! Where a client connects to: pinned addresses and redirects.
+
+mod common;
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use pqctracer::testutil::TestTlsServer;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[tokio::test]
+async fn resolve_connects_to_the_given_addresses() {
+    let server =
+        common::start(TestTlsServer::builder().subject_alt_names(["pinned.invalid"])).await;
+    let client = common::client_for(&server)
+        .resolve("Pinned.invalid", &[IpAddr::V4(Ipv4Addr::LOCALHOST)])
+        .build()
+        .expect("build client");
+    let url = format!("https://pinned.invalid:{}/", server.local_addr().port());
+    let traced = client.get(url.as_str()).send().await.expect("request");
+    assert_eq!(traced.response.status(), 200);
+    assert_eq!(traced.metadata.remote_addr, Some(server.local_addr()));
+}
+
+/// A plain HTTP server that answers every request with a redirect to
+/// `location`.
+async fn redirecting(location: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let url = format!("http://{}/", listener.local_addr().expect("local address"));
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut head = [0; 1024];
+            let _ = stream.read(&mut head).await;
+            let response = format!(
+                "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n",
+                location
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    });
+    url
+}
+
+#[tokio::test]
+async fn redirects_are_not_followed_when_disabled() {
+    let url = redirecting("http://127.0.0.1:1/elsewhere").await;
+    let client = common::client_builder()
+        .follow_redirects(false)
+        .build()
+        .expect("build client");
+    let traced = client.get(url.as_str()).send().await.expect("request");
+    assert_eq!(traced.response.status(), 302);
+    assert_eq!(traced.response.url().as_str(), url);
+}
+
+#[tokio::test]
+async fn redirects_are_followed_by_default() {
+    let url = redirecting("http://127.0.0.1:1/elsewhere").await;
+    let client = common::client_builder().build().expect("build client");
+    // Port 1 refuses the connection, so following the redirect fails.
+    assert!(client.get(url.as_str()).send().await.is_err());
+}
@@ -0,0 +1,327 @@
+//! Cookies across redirects: a sign-on server that sets a cookie and
+//! bounces to an application server that wants it.
+
+mod common;
+
+use std::sync::Arc;
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{CookieJar, TlsAwareClientBuilder};
+use rustls::NamedGroup;
+
+/// The sign-on server, on its own port and group: `/login` sets `sso` and
+/// redirects to `/app` on the application server, which accepts the
+/// redirect only with the cookie and sets `session` for `/app/home`.
+struct SignOn {
+    login: TestTlsServer,
+    app: TestTlsServer,
+}
+
+impl SignOn {
+    async fn start() -> Self {
+        let app = common::start(
+            TestTlsServer::builder()
+                .subject_alt_names(["localhost"])
+                .groups([NamedGroup::X25519])
+                .http_handler(|request| {
+                    let cookies = request
+                        .lines()
+                        .find_map(|line| line.strip_prefix("cookie: "))
+                        .unwrap_or_default()
+                        .to_string();
+                    if request.starts_with("GET /app/home ") {
+                        match cookies.as_str() {
+                            "session=s1; sso=ticket-1" => "200 OK".to_string(),
+                            _ => format!("401 Unauthorized\r\nX-Cookies: {}", cookies),
+                        }
+                    } else if cookies == "sso=ticket-1" {
+                        "302 Found\r\nSet-Cookie: session=s1; Path=/app; Secure\r\n\
+                         Location: /app/home"
+                            .to_string()
+                    } else {
+                        format!("403 Forbidden\r\nX-Cookies: {}", cookies)
+                    }
+                }),
+        )
+        .await;
+        let location = format!("https://localhost:{}/app", app.local_addr().port());
+        let login = common::start(
+            TestTlsServer::builder()
+                .subject_alt_names(["localhost"])
+                .groups([NamedGroup::secp256r1])
+                .http_handler(move |_| {
+                    format!(
+                        "302 Found\r\nSet-Cookie: sso=ticket-1; Path=/; HttpOnly\r\nLocation: {}",
+                        location
+                    )
+                }),
+        )
+        .await;
+        Self { login, app }
+    }
+
+    fn client(&self) -> TlsAwareClientBuilder {
+        common::client_for(&self.login).add_root_certificate(self.app.cert_der().clone())
+    }
+
+    fn login_url(&self) -> String {
+        format!("https://localhost:{}/login", self.login.local_addr().port())
+    }
+
+    fn home_url(&self) -> String {
+        format!(
+            "https://localhost:{}/app/home",
+            self.app.local_addr().port()
+        )
+    }
+}
+
+#[tokio::test]
+async fn cookies_follow_the_redirects() {
+    let sign_on = SignOn::start().await;
+    let jar = Arc::new(CookieJar::new());
+    let client = sign_on
+        .client()
+        .cookie_jar(jar.clone())
+        .build()
+        .expect("build client");
+
+    let traced = client
+        .get(sign_on.login_url().as_str())
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(traced.response.status(), 200);
+    assert_eq!(traced.response.url().as_str(), sign_on.home_url());
+    assert_eq!(jar.len(), 2);
+
+    // Every hop had a handshake of its own, each captured for its server;
+    // the response carries the first hop's, as redirects reqwest follows do.
+    assert_eq!(traced.metadata.group.as_deref(), Some("secp256r1"));
+    let groups = |server: &TestTlsServer| -> Vec<Option<NamedGroup>> {
+        server.observed().iter().map(|h| h.selected_group).collect()
+    };
+    assert_eq!(groups(&sign_on.login), [Some(NamedGroup::secp256r1)]);
+    assert_eq!(groups(&sign_on.app), [Some(NamedGroup::X25519); 2]);
+    let extension = traced.response.extensions().get::<pqctracer::TlsMetadata>();
+    assert_eq!(
+        extension.and_then(|m| m.group.as_deref()),
+        Some("secp256r1")
+    );
+
+    // A request of its own to the application server is its handshake's.
+    let home = client
+        .get(sign_on.home_url().as_str())
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(home.response.status(), 200);
+    assert_eq!(home.metadata.group.as_deref(), Some("X25519"));
+}
+
+#[tokio::test]
+async fn without_a_jar_the_cookie_is_lost() {
+    let sign_on = SignOn::start().await;
+    let client = sign_on.client().build().expect("build client");
+    let traced = client
+        .get(sign_on.login_url().as_str())
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(traced.response.status(), 403);
+}
+
+#[tokio::test]
+async fn unfollowed_redirects_still_set_cookies() {
+    let sign_on = SignOn::start().await;
+    let jar = Arc::new(CookieJar::new());
+    let client = sign_on
+        .client()
+        .cookie_jar(jar.clone())
+        .follow_redirects(false)
+        .build()
+        .expect("build client");
+    let traced = client
+        .get(sign_on.login_url().as_str())
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(traced.response.status(), 302);
+    assert!(sign_on.app.observed().is_empty());
+    let app = format!("https://localhost:{}/app", sign_on.app.local_addr().port());
+    assert_eq!(
+        jar.cookie_header(&app.parse().unwrap()).as_deref(),
+        Some("sso=ticket-1")
+    );
+}
+
+#[tokio::test]
+async fn saved_jars_log_in_the_next_run() {
+    let sign_on = SignOn::start().await;
+    let path = std::env::temp_dir().join(format!("pqctracer-cookies-{}.txt", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let jar = Arc::new(CookieJar::load(&path).expect("missing jar"));
+    assert!(jar.is_empty());
+    let client = sign_on
+        .client()
+        .cookie_jar(jar.clone())
+        .build()
+        .expect("build client");
+    client
+        .get(sign_on.login_url().as_str())
+        .send()
+        .await
+        .expect("request");
+    jar.save(&path).expect("save");
+
+    let saved = std::fs::read_to_string(&path).expect("read jar");
+    assert!(
+        saved.starts_with("# Netscape HTTP Cookie File\n"),
+        "{}",
+        saved
+    );
+    assert!(
+        saved.contains("#HttpOnly_localhost\tFALSE\t/\tFALSE\t0\tsso\tticket-1\n"),
+        "{}",
+        saved
+    );
+    assert!(
+        saved.contains("localhost\tFALSE\t/app\tTRUE\t0\tsession\ts1\n"),
+        "{}",
+        saved
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path)
+            .expect("metadata")
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let jar = Arc::new(CookieJar::load(&path).expect("load"));
+    let client = sign_on
+        .client()
+        .cookie_jar(jar)
+        .build()
+        .expect("build client");
+    let home = client
+        .get(sign_on.home_url().as_str())
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(home.response.status(), 200);
+    std::fs::remove_file(&path).expect("remove jar");
+}
+
+fn url(url: &str) -> reqwest::Url {
+    url.parse().expect("URL")
+}
+
+#[test]
+fn cookies_go_where_they_were_set_for() {
+    let jar = CookieJar::new();
+    let from = url("https://login.example.com/sso/start");
+    jar.set_cookie(&from, "host=1");
+    jar.set_cookie(&from, "wide=2; Domain=.Example.com; Path=/");
+    jar.set_cookie(&from, "other=3; Domain=example.org");
+    jar.set_cookie(&from, "tld=4; Domain=com");
+    jar.set_cookie(&url("http://login.example.com/"), "plain=5; Secure");
+    jar.set_cookie(&from, "deep=6; Path=/sso/start/step");
+    assert_eq!(jar.len(), 3);
+
+    let header = |to: &str| jar.cookie_header(&url(to));
+    assert_eq!(
+        header("https://login.example.com/sso/x").as_deref(),
+        Some("host=1; wide=2")
+    );
+    assert_eq!(
+        header("https://www.example.com/").as_deref(),
+        Some("wide=2")
+    );
+    assert_eq!(header("https://example.org/"), None);
+    assert_eq!(header("https://notexample.com/"), None);
+    // Without a Path, a cookie is for the directory it was set from.
+    assert_eq!(
+        header("https://login.example.com/"),
+        Some("wide=2".to_string())
+    );
+    assert_eq!(
+        header("https://login.example.com/sso/start/step/2").as_deref(),
+        Some("deep=6; host=1; wide=2")
+    );
+    assert_eq!(
+        header("https://login.example.com/sso/start/steps"),
+        header("https://login.example.com/sso/")
+    );
+}
+
+#[test]
+fn secure_cookies_stay_on_https() {
+    let jar = CookieJar::new();
+    jar.set_cookie(&url("https://example.com/"), "token=1; Secure; HttpOnly");
+    assert_eq!(
+        jar.cookie_header(&url("https://example.com/")).as_deref(),
+        Some("token=1")
+    );
+    assert_eq!(jar.cookie_header(&url("http://example.com/")), None);
+}
+
+#[test]
+fn expired_cookies_are_removed() {
+    let jar = CookieJar::new();
+    let site = url("https://example.com/");
+    jar.set_cookie(&site, "a=1; Expires=Wed, 21 Oct 2099 07:28:00 GMT");
+    jar.set_cookie(&site, "b=2; Max-Age=3600");
+    jar.set_cookie(&site, "c=3");
+    assert_eq!(jar.cookie_header(&site).as_deref(), Some("a=1; b=2; c=3"));
+
+    jar.set_cookie(&site, "a=; Expires=Thu, 01 Jan 1970 00:00:00 GMT");
+    jar.set_cookie(&site, "b=; Max-Age=0");
+    // Max-Age wins over Expires, whatever their order.
+    jar.set_cookie(
+        &site,
+        "c=4; Max-Age=60; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+    );
+    assert_eq!(jar.cookie_header(&site).as_deref(), Some("c=4"));
+    assert_eq!(jar.len(), 1);
+}
+
+#[test]
+fn netscape_files_round_trip() {
+    let file = "# Netscape HTTP Cookie File\n\
+                \n\
+                .example.com\tTRUE\t/\tTRUE\t4102444800\twide\t1\n\
+                #HttpOnly_api.example.com\tFALSE\t/v1\tFALSE\t0\tsession\tabc\n\
+                old.example.com\tFALSE\t/\tFALSE\t1\texpired\tx\n";
+    let jar = CookieJar::read_netscape(file.as_bytes()).expect("read");
+    assert_eq!(jar.len(), 2);
+    assert_eq!(
+        jar.cookie_header(&url("https://api.example.com/v1/x"))
+            .as_deref(),
+        Some("session=abc; wide=1")
+    );
+    assert_eq!(
+        jar.cookie_header(&url("https://www.example.com/"))
+            .as_deref(),
+        Some("wide=1")
+    );
+
+    let mut written = Vec::new();
+    jar.write_netscape(&mut written).expect("write");
+    let written = String::from_utf8(written).expect("UTF-8");
+    assert!(
+        written.contains(".example.com\tTRUE\t/\tTRUE\t4102444800\twide\t1\n"),
+        "{}",
+        written
+    );
+    assert!(
+        written.contains("#HttpOnly_api.example.com\tFALSE\t/v1\tFALSE\t0\tsession\tabc\n"),
+        "{}",
+        written
+    );
+
+    let e = CookieJar::read_netscape("example.com\tTRUE\t/\n".as_bytes()).unwrap_err();
+    assert_eq!(e.to_string(), "line 1: expected 7 tab-separated fields");
+}
@@ -0,0 +1,376 @@
+//! DANE against crafted TLSA records from a mock resolver: records that
+//! match the test server's chain and records that do not, for each usage,
+//! selector and matching type, and hosts publishing none.
+
+#![cfg(feature = "dns")]
+
+mod common;
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use aws_lc_rs::digest;
+use pqctracer::dane::{DaneResult, TlsaOutcome};
+use pqctracer::dns::{HttpsResolver, LookupFuture, TlsaAnswer, TlsaLookupFuture, TlsaRecord};
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{TlsAwareClient, TlsAwareClientBuilder};
+use rustls_pki_types::CertificateDer;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// Answers TLSA lookups with `tlsa`, or fails them when there is none,
+/// noting the names asked for; there are never HTTPS records.
+#[derive(Clone, Default)]
+struct MockResolver {
+    tlsa: Option<TlsaAnswer>,
+    asked: Arc<Mutex<Vec<String>>>,
+}
+
+impl HttpsResolver for MockResolver {
+    fn lookup_https<'a>(&'a self, _name: &'a str) -> LookupFuture<'a> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    fn lookup_tlsa<'a>(&'a self, name: &'a str) -> TlsaLookupFuture<'a> {
+        self.asked.lock().unwrap().push(name.to_string());
+        let tlsa = self.tlsa.clone();
+        Box::pin(async move { tlsa.ok_or_else(|| io::ErrorKind::TimedOut.into()) })
+    }
+}
+
+fn resolver(records: Vec<TlsaRecord>, authenticated: bool) -> MockResolver {
+    MockResolver {
+        tlsa: Some(TlsaAnswer {
+            records,
+            authenticated,
+        }),
+        ..Default::default()
+    }
+}
+
+/// A record for `cert`, with the association data its selector and
+/// matching type call for.
+fn record(usage: u8, selector: u8, matching_type: u8, cert: &CertificateDer<'_>) -> TlsaRecord {
+    let selected = match selector {
+        0 => cert.as_ref(),
+        _ => {
+            let (_, parsed) = X509Certificate::from_der(cert.as_ref()).expect("certificate");
+            parsed.tbs_certificate.subject_pki.raw
+        }
+    };
+    let data = match matching_type {
+        0 => selected.to_vec(),
+        1 => digest::digest(&digest::SHA256, selected).as_ref().to_vec(),
+        _ => digest::digest(&digest::SHA512, selected).as_ref().to_vec(),
+    };
+    TlsaRecord {
+        usage,
+        selector,
+        matching_type,
+        data,
+    }
+}
+
+/// The DANE result of a request to `server` by `builder`'s client, asking
+/// `resolver`.
+async fn dane(
+    builder: TlsAwareClientBuilder,
+    server: &TestTlsServer,
+    resolver: MockResolver,
+) -> Option<DaneResult> {
+    let client = builder
+        .https_resolver(resolver)
+        .dane(true)
+        .build()
+        .expect("build client");
+    let response = client.get(server.url()).send().await.expect("request");
+    response.metadata.dane
+}
+
+fn outcomes(result: &DaneResult) -> Vec<TlsaOutcome> {
+    result
+        .details
+        .iter()
+        .map(|detail| detail.outcome.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn a_record_of_the_servers_key_matches() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let records = vec![record(3, 1, 1, server.cert_der())];
+    let resolver = resolver(records.clone(), true);
+    let asked = resolver.asked.clone();
+    let result = dane(common::client_for(&server), &server, resolver)
+        .await
+        .expect("DANE is enabled");
+
+    assert_eq!(result.records_found, 1);
+    assert!(result.matched);
+    assert!(result.authenticated);
+    assert_eq!(result.error, None);
+    assert_eq!(result.details[0].record, records[0]);
+    assert_eq!(outcomes(&result), [TlsaOutcome::Matched { certificate: 0 }]);
+    assert_eq!(
+        result.to_string(),
+        "matched, 1 of 1 records (DNSSEC-authenticated)"
+    );
+    let name = format!("_{}._tcp.localhost", server.local_addr().port());
+    assert_eq!(*asked.lock().unwrap(), [name]);
+}
+
+#[tokio::test]
+async fn every_selector_and_matching_type_matches() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let cert = server.cert_der();
+    let records = vec![
+        record(3, 0, 0, cert),
+        record(3, 0, 1, cert),
+        record(3, 0, 2, cert),
+        record(3, 1, 0, cert),
+        record(3, 1, 2, cert),
+    ];
+    let result = dane(
+        common::client_for(&server),
+        &server,
+        resolver(records, false),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        outcomes(&result),
+        vec![TlsaOutcome::Matched { certificate: 0 }; 5]
+    );
+    assert_eq!(
+        result.to_string(),
+        "matched, 5 of 5 records (not DNSSEC-authenticated)"
+    );
+}
+
+#[tokio::test]
+async fn a_record_of_another_key_does_not_match() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let other = common::start(TestTlsServer::builder()).await;
+    let mut corrupted = record(3, 0, 1, server.cert_der());
+    corrupted.data[0] ^= 0xff;
+    let records = vec![record(3, 1, 1, other.cert_der()), corrupted];
+    let result = dane(
+        common::client_for(&server),
+        &server,
+        resolver(records, true),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(result.records_found, 2);
+    assert!(!result.matched);
+    assert_eq!(
+        outcomes(&result),
+        [TlsaOutcome::NoMatch, TlsaOutcome::NoMatch]
+    );
+    assert_eq!(
+        result.to_string(),
+        "NO MATCH, 0 of 2 records (DNSSEC-authenticated)"
+    );
+    assert_eq!(
+        result.details[1].to_string(),
+        format!("{}: no match", result.details[1].record)
+    );
+}
+
+#[tokio::test]
+async fn one_matching_record_is_enough() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let other = common::start(TestTlsServer::builder()).await;
+    let records = vec![
+        record(3, 1, 1, other.cert_der()),
+        record(3, 1, 1, server.cert_der()),
+    ];
+    let result = dane(
+        common::client_for(&server),
+        &server,
+        resolver(records, true),
+    )
+    .await
+    .unwrap();
+    assert!(result.matched);
+    assert_eq!(
+        outcomes(&result),
+        [
+            TlsaOutcome::NoMatch,
+            TlsaOutcome::Matched { certificate: 0 }
+        ]
+    );
+    assert_eq!(
+        result.to_string(),
+        "matched, 1 of 2 records (DNSSEC-authenticated)"
+    );
+}
+
+#[tokio::test]
+async fn trust_anchor_records_name_an_issuer() {
+    let server = common::start(TestTlsServer::builder().intermediates(2)).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let chain = client
+        .get(server.url())
+        .send()
+        .await
+        .expect("request")
+        .metadata
+        .peer_certificates;
+    assert_eq!(chain.len(), 3);
+
+    let records = vec![
+        // A trust-anchor usage never names the server's own certificate,
+        record(2, 0, 1, &chain[0]),
+        // but may name any issuer it sent along.
+        record(2, 0, 1, &chain[2]),
+        record(2, 1, 1, &chain[1]),
+        // An end-entity usage only names the server's own.
+        record(3, 0, 1, &chain[1]),
+    ];
+    let result = dane(
+        common::client_for(&server),
+        &server,
+        resolver(records, true),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        outcomes(&result),
+        [
+            TlsaOutcome::NoMatch,
+            TlsaOutcome::Matched { certificate: 2 },
+            TlsaOutcome::Matched { certificate: 1 },
+            TlsaOutcome::NoMatch,
+        ]
+    );
+    assert!(result.matched);
+}
+
+#[tokio::test]
+async fn pkix_usages_need_a_valid_chain() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let records = vec![record(1, 1, 1, server.cert_der())];
+
+    let result = dane(
+        common::client_for(&server),
+        &server,
+        resolver(records.clone(), true),
+    )
+    .await
+    .unwrap();
+    assert_eq!(outcomes(&result), [TlsaOutcome::Matched { certificate: 0 }]);
+
+    // Without validation the record matches, but that is not enough.
+    let insecure = common::client_builder().danger_accept_invalid_certs(true);
+    let result = dane(insecure, &server, resolver(records, true))
+        .await
+        .unwrap();
+    assert_eq!(
+        outcomes(&result),
+        [TlsaOutcome::PkixUnverified { certificate: 0 }]
+    );
+    assert!(!result.matched);
+    assert_eq!(
+        result.details[0].outcome.to_string(),
+        "matches certificate 0, but the chain is not PKIX-valid"
+    );
+
+    // A DANE usage stands on its own.
+    let insecure = common::client_builder().danger_accept_invalid_certs(true);
+    let records = vec![record(3, 1, 1, server.cert_der())];
+    let result = dane(insecure, &server, resolver(records, true))
+        .await
+        .unwrap();
+    assert!(result.matched);
+}
+
+#[tokio::test]
+async fn unknown_parameters_make_a_record_unusable() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let valid = record(3, 1, 1, server.cert_der());
+    let records = vec![
+        TlsaRecord {
+            usage: 4,
+            ..valid.clone()
+        },
+        TlsaRecord {
+            selector: 2,
+            ..valid.clone()
+        },
+        TlsaRecord {
+            matching_type: 3,
+            ..valid
+        },
+    ];
+    let result = dane(
+        common::client_for(&server),
+        &server,
+        resolver(records, true),
+    )
+    .await
+    .unwrap();
+    let reasons: Vec<String> = result
+        .details
+        .iter()
+        .map(|detail| detail.outcome.to_string())
+        .collect();
+    assert_eq!(
+        reasons,
+        [
+            "unusable (unknown usage 4)",
+            "unusable (unknown selector 2)",
+            "unusable (unknown matching type 3)",
+        ]
+    );
+    assert_eq!(result.records_found, 3);
+    assert!(!result.matched);
+}
+
+#[tokio::test]
+async fn missing_records_are_not_published() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let result = dane(
+        common::client_for(&server),
+        &server,
+        resolver(Vec::new(), true),
+    )
+    .await
+    .unwrap();
+    assert!(!result.is_published());
+    assert_eq!(result.records_found, 0);
+    assert!(!result.matched);
+    assert!(result.details.is_empty());
+    assert_eq!(result.error, None);
+    assert_eq!(result.to_string(), "not published");
+}
+
+#[tokio::test]
+async fn a_failed_lookup_is_reported_without_failing_the_request() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let result = dane(
+        common::client_for(&server),
+        &server,
+        MockResolver::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.records_found, 0);
+    let error = result.error.clone().expect("lookup error");
+    assert!(error.starts_with("lookup failed: "), "{}", error);
+    assert_eq!(result.to_string(), format!("not checked ({})", error));
+}
+
+#[tokio::test]
+async fn without_dane_nothing_is_looked_up() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let resolver = resolver(vec![record(3, 1, 1, server.cert_der())], true);
+    let asked = resolver.asked.clone();
+    let client: TlsAwareClient = common::client_for(&server)
+        .https_resolver(resolver)
+        .build()
+        .expect("build client");
+    let response = client.get(server.url()).send().await.expect("request");
+    assert_eq!(response.metadata.dane, None);
+    assert!(asked.lock().unwrap().is_empty());
+}
@@ -0,0 +1,102 @@
+//! The redacted `Debug` of a client: its configuration and counters, but
+//! never the secrets it was configured with.
+
+mod common;
+
+use std::sync::Arc;
+
+use pqctracer::testutil::TestTlsServer;
+use rustls::sign::{CertifiedKey, SingleCertAndKey};
+use rustls_pki_types::PrivateKeyDer;
+
+const PROXY_USER: &str = "alice-proxy-user";
+const PROXY_PASSWORD: &str = "s3cret-proxy-password";
+
+#[tokio::test]
+async fn debug_summarizes_without_secrets() {
+    common::install_provider();
+    let server = common::start(TestTlsServer::builder()).await;
+
+    let key = rcgen::KeyPair::generate().expect("key");
+    let params = rcgen::CertificateParams::new(["client.example".to_string()]).unwrap();
+    let cert = params.self_signed(&key).expect("client certificate");
+    let identity = CertifiedKey::from_der(
+        vec![cert.der().clone()],
+        PrivateKeyDer::try_from(key.serialize_der()).expect("key DER"),
+        &rustls::crypto::aws_lc_rs::default_provider(),
+    )
+    .expect("client identity");
+    let proxy = reqwest::Proxy::all(format!(
+        "http://{}:{}@127.0.0.1:3128",
+        PROXY_USER, PROXY_PASSWORD
+    ))
+    .expect("proxy")
+    // Requests to the local server go around the proxy.
+    .no_proxy(reqwest::NoProxy::from_string("localhost"));
+
+    let client = common::client_for(&server)
+        .proxy(proxy)
+        .client_cert_resolver(Arc::new(SingleCertAndKey::from(identity)))
+        .timeout(std::time::Duration::from_secs(7))
+        .build()
+        .expect("build client");
+    client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request");
+
+    let debug = format!("{:?}", client);
+    for marker in [
+        "TlsAwareClient {",
+        &format!(
+            "roots: \"webpki-roots ({}) + 1 added\"",
+            webpki_roots::TLS_SERVER_ROOTS.len()
+        ),
+        "kx_groups: [X25519MLKEM768,",
+        "protocol_versions: [TLSv1_3, TLSv1_2]",
+        "timeout: Some(7s)",
+        "proxy: true",
+        "resumption: \"capture only, tickets are never reused\"",
+        "requests: 1",
+        "handshakes: 1",
+        "failures: 0",
+        "in_flight: 0",
+    ] {
+        assert!(debug.contains(marker), "{} not in {}", marker, debug);
+    }
+    let key_pem = key.serialize_pem();
+    let key_base64 = key_pem.lines().nth(1).expect("PEM body");
+    for secret in [PROXY_USER, PROXY_PASSWORD, "3128", key_base64] {
+        assert!(!debug.contains(secret), "{} in {}", secret, debug);
+    }
+    let pretty = format!("{:#?}", client);
+    assert!(!pretty.contains(PROXY_PASSWORD), "{}", pretty);
+}
+
+#[test]
+fn debug_shows_a_resuming_session_store() {
+    let client = common::client_builder()
+        .session_store(Arc::new(rustls::client::ClientSessionMemoryCache::new(8)))
+        .build()
+        .expect("build client");
+    let debug = format!("{:?}", client);
+    assert!(
+        debug.contains("resumption: \"capture, then the given session store\""),
+        "{}",
+        debug
+    );
+}
+
+#[test]
+fn debug_of_an_insecure_client() {
+    let debug = format!("{:?}", common::insecure_client());
+    for marker in [
+        "roots: \"none, certificates are not verified\"",
+        "proxy: false",
+        "requests: 0",
+        "known_connections: 0",
+    ] {
+        assert!(debug.contains(marker), "{} not in {}", marker, debug);
+    }
+}
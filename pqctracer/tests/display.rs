@@ -0,0 +1,128 @@
+//! The single-line form of handshake metadata, pinned exactly since log
+//! parsers rely on it.
+
+mod common;
+
+use std::time::Duration;
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{HandshakeOrigin, Timings, TlsMetadata};
+use rustls::NamedGroup;
+
+const HYBRID: &str = "TLS1.3 X25519MLKEM768 / TLS13_AES_256_GCM_SHA384";
+
+fn metadata(group: Option<&str>, cipher: Option<&str>) -> TlsMetadata {
+    TlsMetadata::new(group.map(str::to_string), cipher.map(str::to_string))
+}
+
+/// `metadata` after `change`; the struct is non-exhaustive outside the
+/// crate, so fields are set one by one.
+fn with(mut metadata: TlsMetadata, change: impl FnOnce(&mut TlsMetadata)) -> TlsMetadata {
+    change(&mut metadata);
+    metadata
+}
+
+#[test]
+fn metadata_display_snapshots() {
+    let hybrid = || metadata(Some("X25519MLKEM768"), Some("TLS13_AES_256_GCM_SHA384"));
+    let cases = [
+        (TlsMetadata::default(), "n/a n/a / n/a (n/a, n/a)"),
+        (
+            hybrid(),
+            "TLS1.3 X25519MLKEM768 / TLS13_AES_256_GCM_SHA384 (n/a, n/a)",
+        ),
+        (
+            with(hybrid(), |m| {
+                m.handshake_duration = Some(Duration::from_micros(12_900));
+                m.origin = Some(HandshakeOrigin::Fresh);
+            }),
+            "TLS1.3 X25519MLKEM768 / TLS13_AES_256_GCM_SHA384 (12ms, fresh)",
+        ),
+        (
+            with(hybrid(), |m| {
+                m.handshake_duration = Some(Duration::from_micros(300));
+                m.origin = Some(HandshakeOrigin::Resumed);
+            }),
+            "TLS1.3 X25519MLKEM768 / TLS13_AES_256_GCM_SHA384 (0ms, resumed)",
+        ),
+        (
+            with(metadata(None, None), |m| {
+                m.origin = Some(HandshakeOrigin::Reused);
+            }),
+            "n/a n/a / n/a (n/a, reused)",
+        ),
+        (
+            with(hybrid(), |m| {
+                m.origin = Some(HandshakeOrigin::SharedConnection);
+                m.timings = Some(Timings {
+                    tls: Some(Duration::from_millis(7)),
+                    ..Timings::default()
+                });
+            }),
+            "TLS1.3 X25519MLKEM768 / TLS13_AES_256_GCM_SHA384 (7ms, shared)",
+        ),
+        (
+            with(
+                metadata(
+                    Some("secp256r1"),
+                    Some("TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256"),
+                ),
+                |m| m.origin = Some(HandshakeOrigin::Fresh),
+            ),
+            "TLS1.2 secp256r1 / TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256 (n/a, fresh)",
+        ),
+        (
+            metadata(None, Some("TLS13_CHACHA20_POLY1305_SHA256")),
+            "TLS1.3 n/a / TLS13_CHACHA20_POLY1305_SHA256 (n/a, n/a)",
+        ),
+    ];
+    for (metadata, expected) in cases {
+        assert_eq!(metadata.to_string(), expected);
+    }
+}
+
+async fn summary(server: &TestTlsServer, timings: bool) -> String {
+    let client = common::client_for(server)
+        .collect_timings(timings)
+        .build()
+        .expect("build client");
+    client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request")
+        .summary()
+}
+
+#[tokio::test]
+async fn summaries_of_local_handshakes() {
+    let hybrid = common::start(TestTlsServer::builder()).await;
+    assert_eq!(
+        summary(&hybrid, false).await,
+        format!("localhost: {} (n/a, fresh)", HYBRID)
+    );
+
+    let classical = common::start(TestTlsServer::builder().groups([NamedGroup::X25519])).await;
+    assert_eq!(
+        summary(&classical, false).await,
+        "localhost: TLS1.3 X25519 / TLS13_AES_256_GCM_SHA384 (n/a, fresh)"
+    );
+
+    // rustls reports neither group nor cipher of a TLS 1.2 handshake.
+    let tls12 = common::start(TestTlsServer::builder().tls12_only(true)).await;
+    assert_eq!(
+        summary(&tls12, false).await,
+        "localhost: n/a n/a / n/a (n/a, fresh)"
+    );
+}
+
+#[tokio::test]
+async fn summaries_carry_the_measured_handshake() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let summary = summary(&server, true).await;
+    let millis = summary
+        .strip_prefix(&format!("localhost: {} (", HYBRID))
+        .and_then(|rest| rest.strip_suffix("ms, fresh)"))
+        .unwrap_or_else(|| panic!("{}", summary));
+    assert!(millis.parse::<u128>().is_ok(), "{}", summary);
+}
@@ -0,0 +1,220 @@
+//! HTTPS records from a mock resolver: reported in the metadata, never
+//! failing the request, and their ECH configuration used when ECH is
+//! enabled.
+
+#![cfg(feature = "dns")]
+
+mod common;
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use aws_lc_rs::agreement::{PrivateKey, X25519};
+use pqctracer::dns::{HttpsRecord, HttpsResolver, LookupFuture};
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{TlsAwareClient, TraceError};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Answers every lookup with `records`, or fails when there are none,
+/// noting the names asked for.
+#[derive(Clone, Default)]
+struct MockResolver {
+    records: Option<Vec<HttpsRecord>>,
+    asked: Arc<Mutex<Vec<String>>>,
+}
+
+impl HttpsResolver for MockResolver {
+    fn lookup_https<'a>(&'a self, name: &'a str) -> LookupFuture<'a> {
+        self.asked.lock().unwrap().push(name.to_string());
+        let records = self.records.clone();
+        Box::pin(async move { records.ok_or_else(|| io::ErrorKind::Unsupported.into()) })
+    }
+}
+
+/// An `ECHConfigList` with one X25519, HKDF-SHA256, AES-128-GCM config for
+/// `public_name` (draft-ietf-tls-esni-18, section 4).
+fn ech_config_list(public_name: &str) -> Vec<u8> {
+    let key = PrivateKey::generate(&X25519).expect("X25519 key");
+    let public_key = key.compute_public_key().expect("public key");
+    let public_key = public_key.as_ref();
+
+    let mut contents = vec![7];
+    contents.extend_from_slice(&0x0020u16.to_be_bytes());
+    contents.extend_from_slice(&(public_key.len() as u16).to_be_bytes());
+    contents.extend_from_slice(public_key);
+    contents.extend_from_slice(&4u16.to_be_bytes());
+    contents.extend_from_slice(&0x0001u16.to_be_bytes());
+    contents.extend_from_slice(&0x0001u16.to_be_bytes());
+    contents.push(0);
+    contents.push(public_name.len() as u8);
+    contents.extend_from_slice(public_name.as_bytes());
+    contents.extend_from_slice(&0u16.to_be_bytes());
+
+    let mut config = 0xfe0du16.to_be_bytes().to_vec();
+    config.extend_from_slice(&(contents.len() as u16).to_be_bytes());
+    config.extend_from_slice(&contents);
+    let mut list = (config.len() as u16).to_be_bytes().to_vec();
+    list.extend_from_slice(&config);
+    list
+}
+
+fn ech_record(public_name: &str) -> HttpsRecord {
+    HttpsRecord {
+        priority: 1,
+        target: ".".to_string(),
+        ech: true,
+        ech_config_list: Some(ech_config_list(public_name)),
+        ..Default::default()
+    }
+}
+
+/// Forwards every connection to `server`, keeping the first TLS record
+/// the client sent – its plaintext ClientHello.
+async fn recording_proxy(server: &TestTlsServer) -> (u16, Arc<Mutex<Vec<Vec<u8>>>>) {
+    let upstream = server.local_addr();
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind proxy");
+    let port = listener.local_addr().unwrap().port();
+    let hellos = Arc::new(Mutex::new(Vec::new()));
+    let recorded = hellos.clone();
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let recorded = recorded.clone();
+            tokio::spawn(async move {
+                let mut header = [0; 5];
+                if client.read_exact(&mut header).await.is_err() {
+                    return;
+                }
+                let mut hello = header.to_vec();
+                hello.resize(
+                    5 + usize::from(u16::from_be_bytes([header[3], header[4]])),
+                    0,
+                );
+                if client.read_exact(&mut hello[5..]).await.is_err() {
+                    return;
+                }
+                recorded.lock().unwrap().push(hello.clone());
+                let Ok(mut server) = TcpStream::connect(upstream).await else {
+                    return;
+                };
+                if server.write_all(&hello).await.is_ok() {
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                }
+            });
+        }
+    });
+    (port, hellos)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+fn client(resolver: MockResolver, ech: bool) -> TlsAwareClient {
+    common::install_provider();
+    TlsAwareClient::builder()
+        .danger_accept_invalid_certs(true)
+        .https_resolver(resolver)
+        .enable_ech(ech)
+        .build()
+        .expect("build client")
+}
+
+#[tokio::test]
+async fn records_are_reported_with_the_handshake() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let record = HttpsRecord {
+        priority: 1,
+        target: ".".to_string(),
+        alpn: vec!["h2".to_string(), "h3".to_string()],
+        port: Some(8443),
+        ipv4_hints: vec!["192.0.2.1".parse().unwrap()],
+        ipv6_hints: vec!["2001:db8::1".parse().unwrap()],
+        ..Default::default()
+    };
+    let resolver = MockResolver {
+        records: Some(vec![record.clone()]),
+        ..Default::default()
+    };
+    let asked = resolver.asked.clone();
+    let url = format!("https://localhost:{}/", server.local_addr().port());
+    let response = client(resolver, true).get(&url).send().await.expect("send");
+
+    assert_eq!(response.metadata.https_records, [record]);
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519MLKEM768"));
+    // Without an ECH config there is nothing to encrypt the hello with.
+    assert!(!response.metadata.ech_offered);
+    let name = format!("_{}._https.localhost", server.local_addr().port());
+    assert_eq!(*asked.lock().unwrap(), [name]);
+}
+
+#[tokio::test]
+async fn a_failed_lookup_does_not_fail_the_request() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let response = client(MockResolver::default(), true)
+        .get(server.url())
+        .send()
+        .await
+        .expect("send");
+    assert!(response.metadata.https_records.is_empty());
+    assert!(!response.metadata.ech_offered);
+    assert!(response.metadata.group.is_some());
+}
+
+#[tokio::test]
+async fn an_ech_config_encrypts_the_client_hello() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let (port, hellos) = recording_proxy(&server).await;
+    let resolver = MockResolver {
+        records: Some(vec![ech_record("public.example")]),
+        ..Default::default()
+    };
+    let url = format!("https://localhost:{}/", port);
+    let Err(error) = client(resolver, true).get(&url).send().await else {
+        panic!("the test server does not accept ECH");
+    };
+
+    // The outer hello names the config's public name; the real one is in
+    // the encrypted inner hello.
+    let hellos = hellos.lock().unwrap().clone();
+    assert_eq!(hellos.len(), 1);
+    assert!(contains(&hellos[0], b"public.example"));
+    assert!(!contains(&hellos[0], b"localhost"));
+    let TraceError::RequestFailed { error, metadata } = error else {
+        panic!("{:?}", error);
+    };
+    // Without ECH support the server answered the outer hello, which the
+    // client does not settle for.
+    let error = format!("{:?}", error);
+    assert!(
+        error.contains("ServerRejectedEncryptedClientHello"),
+        "{}",
+        error
+    );
+    assert!(metadata.ech_offered);
+    assert!(metadata.https_records[0].ech);
+}
+
+#[tokio::test]
+async fn ech_configs_are_only_reported_unless_enabled() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let (port, hellos) = recording_proxy(&server).await;
+    let resolver = MockResolver {
+        records: Some(vec![ech_record("public.example")]),
+        ..Default::default()
+    };
+    let url = format!("https://localhost:{}/", port);
+    let response = client(resolver, false)
+        .get(&url)
+        .send()
+        .await
+        .expect("send");
+
+    assert!(!response.metadata.ech_offered);
+    assert!(response.metadata.https_records[0].ech);
+    let hellos = hellos.lock().unwrap().clone();
+    assert!(contains(&hellos[0], b"localhost"));
+    assert!(!contains(&hellos[0], b"public.example"));
+}
@@ -0,0 +1,214 @@
+//! Failures synthesized with local servers, and the category each scan
+//! result reports for them.
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use pqctracer::scan::{ScanError, ScanOptions, ScanTarget};
+use pqctracer::testutil::{TestTlsServer, TestTlsServerBuilder};
+use pqctracer::{CertificateProblem, ErrorCategory, NameMismatch, TlsAwareClient};
+use rustls::NamedGroup;
+use tokio::net::{TcpListener, TcpSocket};
+
+const DAY: Duration = Duration::from_secs(86_400);
+
+/// The error of scanning `target` once with `client`.
+async fn scan_error(client: &TlsAwareClient, target: impl Into<ScanTarget>) -> ScanError {
+    let options = ScanOptions {
+        retries: 0,
+        ..ScanOptions::default()
+    };
+    let mut results = client.scan([target.into()], &options).await;
+    let result = results.pop().expect("one result");
+    result
+        .error
+        .unwrap_or_else(|| panic!("{} did not fail", result.target))
+}
+
+/// The error of scanning a server built from `builder` with a client that
+/// trusts its certificate.
+async fn scan_server(builder: TestTlsServerBuilder) -> ScanError {
+    let server = common::start(builder).await;
+    let client = common::client_for(&server).build().expect("build client");
+    scan_error(&client, server.url()).await
+}
+
+#[tokio::test]
+async fn certificates_fail_for_their_reason() {
+    let now = SystemTime::now();
+    let expired =
+        scan_server(TestTlsServer::builder().cert_validity(now - 10 * DAY, now - DAY)).await;
+    assert_eq!(
+        expired.category,
+        ErrorCategory::CertificateInvalid {
+            reason: CertificateProblem::Expired
+        }
+    );
+    assert!(expired.message.contains("expired"), "{}", expired.message);
+
+    let early =
+        scan_server(TestTlsServer::builder().cert_validity(now + DAY, now + 10 * DAY)).await;
+    assert_eq!(
+        early.category,
+        ErrorCategory::CertificateInvalid {
+            reason: CertificateProblem::NotYetValid
+        }
+    );
+
+    // The bundled roots do not know the self-signed certificate.
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_builder().build().expect("build client");
+    let untrusted = scan_error(&client, server.url()).await;
+    assert_eq!(
+        untrusted.category,
+        ErrorCategory::CertificateInvalid {
+            reason: CertificateProblem::Untrusted
+        }
+    );
+    assert_eq!(untrusted.name_mismatch, None);
+}
+
+#[tokio::test]
+async fn wrong_names_say_which_names_the_certificate_has() {
+    let error = scan_server(
+        TestTlsServer::builder().subject_alt_names(["www.example.com", "*.cdn.example.com"]),
+    )
+    .await;
+    assert_eq!(
+        error.category,
+        ErrorCategory::CertificateInvalid {
+            reason: CertificateProblem::NameMismatch
+        }
+    );
+    assert_eq!(
+        error.name_mismatch,
+        Some(NameMismatch {
+            expected: "localhost".to_string(),
+            presented: vec![
+                "www.example.com".to_string(),
+                "*.cdn.example.com".to_string()
+            ],
+            wildcard_near_miss: None,
+        })
+    );
+}
+
+#[tokio::test]
+async fn handshake_failures_carry_the_rustls_error() {
+    // The client lists groups the server does not accept.
+    let error = scan_server(
+        TestTlsServer::builder()
+            .groups([NamedGroup::X25519])
+            .reject_unknown_groups(true),
+    )
+    .await;
+    match &error.category {
+        ErrorCategory::TlsHandshakeFailure { detail } => {
+            assert_eq!(detail, "AlertReceived(HandshakeFailure)")
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn http_error_statuses_are_categorized_with_the_status() {
+    let error =
+        scan_server(TestTlsServer::builder().http_handler(|_| "404 Not Found".to_string())).await;
+    assert_eq!(error.category, ErrorCategory::HttpError { status: 404 });
+}
+
+#[tokio::test]
+async fn closed_ports_refuse_the_connection() {
+    // Bind a port and free it again, so that nothing listens on it.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind")
+        .local_addr()
+        .expect("address")
+        .port();
+    let error = scan_error(
+        &common::insecure_client(),
+        format!("https://127.0.0.1:{}", port),
+    )
+    .await;
+    assert_eq!(error.category, ErrorCategory::ConnectRefused);
+}
+
+#[tokio::test]
+async fn black_holes_time_out_connecting() {
+    // A listener that never accepts, with its queue taken by the first
+    // connection, drops every further SYN unanswered.
+    let socket = TcpSocket::new_v4().expect("socket");
+    socket.bind("127.0.0.1:0".parse().unwrap()).expect("bind");
+    let listener = socket.listen(0).expect("listen");
+    let addr = listener.local_addr().expect("address");
+    let _queued = tokio::net::TcpStream::connect(addr).await.expect("connect");
+
+    let client = common::client_builder()
+        .danger_accept_invalid_certs(true)
+        .connect_timeout(Duration::from_millis(200))
+        .build()
+        .expect("build client");
+    let error = scan_error(&client, format!("https://{}", addr)).await;
+    assert_eq!(error.category, ErrorCategory::ConnectTimeout);
+
+    // The same for handshakes the tracer drives itself.
+    let error = scan_error(
+        &client,
+        ScanTarget::Address {
+            addr,
+            server_name: "localhost".to_string(),
+        },
+    )
+    .await;
+    assert_eq!(error.category, ErrorCategory::ConnectTimeout);
+}
+
+#[tokio::test]
+async fn silent_servers_time_out_after_connecting() {
+    // Accepts connections and never says a word.
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr: SocketAddr = listener.local_addr().expect("address");
+    tokio::spawn(async move {
+        let mut open = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            open.push(stream);
+        }
+    });
+    let client = common::client_builder()
+        .danger_accept_invalid_certs(true)
+        .timeout(Duration::from_millis(300))
+        .build()
+        .expect("build client");
+    let error = scan_error(&client, format!("https://{}", addr)).await;
+    assert_eq!(error.category, ErrorCategory::Timeout);
+}
+
+#[tokio::test]
+async fn unknown_hosts_and_bad_urls_fail_before_connecting() {
+    let client = common::insecure_client();
+    let error = scan_error(&client, "https://does-not-exist.invalid").await;
+    assert_eq!(error.category, ErrorCategory::DnsFailure);
+    let error = scan_error(&client, "https://exa mple.com/").await;
+    assert_eq!(error.category, ErrorCategory::InvalidUrl);
+}
+
+#[tokio::test]
+async fn unreadable_bodies_are_body_errors() {
+    // A chunked body that is no such thing.
+    let server = common::start(
+        TestTlsServer::builder()
+            .http_handler(|_| "200 OK\r\nTransfer-Encoding: chunked".to_string()),
+    )
+    .await;
+    let client = common::client_for(&server).build().expect("build client");
+    let traced = client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request");
+    let error = traced.response.text().await.expect_err("invalid chunk");
+    assert_eq!(ErrorCategory::of(&error), ErrorCategory::BodyError);
+}
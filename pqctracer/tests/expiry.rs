@@ -0,0 +1,120 @@
+//! Certificate validity in scan results, from fixture certificates with
+//! known dates and from local servers with short-lived ones.
+
+mod common;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use pqctracer::cert::{CertificateValidity, rfc3339};
+use pqctracer::scan::{ScanOptions, ScanResult};
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{CertificateProblem, ErrorCategory, TlsAwareClient};
+use rustls_pki_types::CertificateDer;
+
+const DAY: Duration = Duration::from_secs(86_400);
+
+/// A certificate valid from `not_before` until `not_after`.
+fn fixture(not_before: SystemTime, not_after: SystemTime) -> CertificateDer<'static> {
+    let mut params = rcgen::CertificateParams::new(["fixture.example".to_string()]).unwrap();
+    params.not_before = not_before.into();
+    params.not_after = not_after.into();
+    let key = rcgen::KeyPair::generate().unwrap();
+    params.self_signed(&key).unwrap().der().clone()
+}
+
+fn at(unix: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(unix)
+}
+
+#[test]
+fn validity_is_read_to_the_second() {
+    // 2025-01-01T00:00:00Z to 2025-04-01T12:30:15Z.
+    let (not_before, not_after) = (at(1_735_689_600), at(1_743_510_615));
+    let validity = CertificateValidity::of(&fixture(not_before, not_after)).expect("parses");
+    assert_eq!(
+        validity,
+        CertificateValidity {
+            not_before,
+            not_after
+        }
+    );
+    assert_eq!(rfc3339(validity.not_before), "2025-01-01T00:00:00Z");
+    assert_eq!(rfc3339(validity.not_after), "2025-04-01T12:30:15Z");
+    assert_eq!(
+        CertificateValidity::of(&CertificateDer::from(vec![0x30, 0x03, 0x02, 0x01])),
+        None
+    );
+}
+
+#[test]
+fn days_remaining_count_whole_days_and_go_negative() {
+    let not_after = at(1_743_510_615);
+    let validity = CertificateValidity::of(&fixture(at(1_735_689_600), not_after)).unwrap();
+    let second = Duration::from_secs(1);
+    let cases = [
+        (not_after - 30 * DAY, 30),
+        (not_after - 2 * DAY, 2),
+        (not_after - DAY - second, 1),
+        (not_after - DAY + second, 0),
+        (not_after, 0),
+        // A second past expiry is already a day late.
+        (not_after + second, -1),
+        (not_after + DAY, -1),
+        (not_after + DAY + second, -2),
+        (not_after + 400 * DAY, -400),
+    ];
+    for (now, days) in cases {
+        assert_eq!(validity.days_remaining(now), days, "{}", rfc3339(now));
+    }
+}
+
+async fn scan(client: &TlsAwareClient, server: &TestTlsServer) -> ScanResult {
+    let options = ScanOptions {
+        retries: 0,
+        ..ScanOptions::default()
+    };
+    client
+        .scan([server.url()], &options)
+        .await
+        .pop()
+        .expect("one result")
+}
+
+#[tokio::test]
+async fn short_lived_certificates_report_the_days_they_have_left() {
+    let now = SystemTime::now();
+    let not_after = now + DAY + DAY / 2;
+    let server = common::start(TestTlsServer::builder().cert_validity(now - DAY, not_after)).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let result = scan(&client, &server).await;
+    assert!(result.error.is_none(), "{:?}", result.error);
+    assert_eq!(result.cert_days_remaining, Some(1));
+    assert_eq!(result.cert_not_after.map(rfc3339), Some(rfc3339(not_after)));
+    assert_eq!(
+        result.cert_not_before.map(rfc3339),
+        Some(rfc3339(now - DAY))
+    );
+}
+
+#[tokio::test]
+async fn expired_certificates_are_only_seen_insecure() {
+    let now = SystemTime::now();
+    let server = common::start(
+        TestTlsServer::builder().cert_validity(now - 30 * DAY, now - 2 * DAY - DAY / 2),
+    )
+    .await;
+
+    let validating = common::client_for(&server).build().expect("build client");
+    let result = scan(&validating, &server).await;
+    assert_eq!(
+        result.error.map(|e| e.category),
+        Some(ErrorCategory::CertificateInvalid {
+            reason: CertificateProblem::Expired
+        })
+    );
+    assert_eq!(result.cert_days_remaining, None);
+
+    let result = scan(&common::insecure_client(), &server).await;
+    assert!(result.error.is_none(), "{:?}", result.error);
+    assert_eq!(result.cert_days_remaining, Some(-3));
+}
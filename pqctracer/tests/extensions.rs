@@ -0,0 +1,86 @@
+//! The metadata in the extensions of the inner `reqwest::Response`, for
+//! code handed nothing else.
+
+mod common;
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{HandshakeOrigin, TlsMetadata};
+
+/// Knows nothing about the tracer, but what it put into the response.
+fn metadata_of(response: reqwest::Response) -> Option<TlsMetadata> {
+    response.extensions().get::<TlsMetadata>().cloned()
+}
+
+fn handshake(metadata: &TlsMetadata) -> impl PartialEq + std::fmt::Debug {
+    (
+        metadata.group.clone(),
+        metadata.cipher.clone(),
+        metadata.origin,
+        metadata.capture_status,
+        metadata.peer_certificates.clone(),
+        metadata.remote_addr,
+    )
+}
+
+#[tokio::test]
+async fn the_inner_response_carries_the_metadata() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let traced = client.get(server.url()).send().await.expect("request");
+    let expected = traced.metadata.clone();
+
+    let recovered = metadata_of(traced.response).expect("metadata extension");
+    assert_eq!(recovered.group.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(recovered.origin, Some(HandshakeOrigin::Fresh));
+    assert_eq!(handshake(&recovered), handshake(&expected));
+}
+
+#[tokio::test]
+async fn a_shared_connection_carries_its_handshake() {
+    let server = common::start(TestTlsServer::builder().keep_alive(true)).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let first = client.get(server.url()).send().await.expect("request");
+    let first = metadata_of(first.response).expect("metadata extension");
+    let second = client.get(server.url()).send().await.expect("request");
+    let expected = second.metadata.clone();
+
+    let recovered = metadata_of(second.response).expect("metadata extension");
+    assert_eq!(recovered.origin, Some(HandshakeOrigin::SharedConnection));
+    assert_eq!(handshake(&recovered), handshake(&expected));
+    assert_eq!(recovered.group, first.group);
+}
+
+#[tokio::test]
+async fn a_redirected_request_carries_its_first_handshake() {
+    let server = common::start(TestTlsServer::builder().http_handler(|request| {
+        if request.starts_with("GET /next ") {
+            "200 OK".to_string()
+        } else {
+            "302 Found\r\nLocation: /next".to_string()
+        }
+    }))
+    .await;
+    let client = common::client_for(&server)
+        .cookie_store(true)
+        .build()
+        .expect("build client");
+    let traced = client.get(server.url()).send().await.expect("request");
+    assert!(traced.response.url().path().ends_with("/next"));
+    let expected = traced.metadata.clone();
+
+    let recovered = metadata_of(traced.response).expect("metadata extension");
+    assert_eq!(recovered.origin, Some(HandshakeOrigin::Fresh));
+    assert_eq!(handshake(&recovered), handshake(&expected));
+}
+
+#[tokio::test]
+async fn other_clients_responses_carry_none() {
+    let server = common::start(TestTlsServer::builder()).await;
+    common::install_provider();
+    let plain = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .expect("build client");
+    let response = plain.get(server.url()).send().await.expect("request");
+    assert!(metadata_of(response).is_none());
+}
@@ -0,0 +1,159 @@
+//! `fallback_to_classical` against a server that, like some deployed ones,
+//! rejects key-exchange groups it does not know with a fatal alert instead
+//! of picking one it does.
+
+mod common;
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{FallbackOutcome, HandshakeFailureKind, KxPreference, TlsAwareClient};
+use rustls::NamedGroup;
+
+const CLASSICAL: [NamedGroup; 3] = [
+    NamedGroup::X25519,
+    NamedGroup::secp256r1,
+    NamedGroup::secp384r1,
+];
+
+async fn rejecting_server() -> TestTlsServer {
+    common::start(
+        TestTlsServer::builder()
+            .groups(CLASSICAL)
+            .reject_unknown_groups(true),
+    )
+    .await
+}
+
+fn falling_back(server: &TestTlsServer) -> TlsAwareClient {
+    common::client_for(server)
+        .fallback_to_classical(true)
+        .build()
+        .expect("build client")
+}
+
+async fn outcome(
+    client: &TlsAwareClient,
+    server: &TestTlsServer,
+) -> (bool, Option<FallbackOutcome>) {
+    let url = server.url().parse().expect("URL");
+    let request = reqwest::Request::new(reqwest::Method::GET, url);
+    let (result, outcome) = client.execute_with_fallback(request).await;
+    (result.is_ok(), outcome)
+}
+
+#[tokio::test]
+async fn a_rejected_hybrid_offer_is_retried_classically() {
+    let server = rejecting_server().await;
+    let client = falling_back(&server);
+    let response = client.get(server.url()).send().await.expect("retried");
+    let expected = FallbackOutcome::ClassicalOnlyAfterFailure {
+        original_error: HandshakeFailureKind::HandshakeRejected,
+    };
+    assert_eq!(response.metadata.fallback, Some(expected.clone()));
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519"));
+
+    // The retry came on a connection of its own, offering classical
+    // groups only.
+    let observed = common::observed(&server, 2).await;
+    assert!(observed[0].error.is_some());
+    assert!(
+        observed[0]
+            .offered_groups
+            .contains(&NamedGroup::X25519MLKEM768)
+    );
+    assert_eq!(observed[1].error, None);
+    assert!(
+        observed[1]
+            .offered_groups
+            .iter()
+            .all(|group| CLASSICAL.contains(group)),
+        "{:?}",
+        observed[1].offered_groups
+    );
+    assert_ne!(observed[0].peer, observed[1].peer);
+
+    assert_eq!(outcome(&client, &server).await, (true, Some(expected)));
+}
+
+#[tokio::test]
+async fn without_fallback_the_rejection_stands() {
+    let server = rejecting_server().await;
+    let client = common::client_for(&server).build().expect("build client");
+    let Err(error) = client.get(server.url()).send().await else {
+        panic!("the server rejects hybrid groups");
+    };
+    assert_eq!(
+        error.handshake_failure(),
+        Some(HandshakeFailureKind::HandshakeRejected)
+    );
+    assert_eq!(outcome(&client, &server).await, (false, None));
+    assert_eq!(common::observed(&server, 2).await.len(), 2);
+}
+
+#[tokio::test]
+async fn an_accepted_hybrid_offer_needs_no_retry() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = falling_back(&server);
+    let response = client.get(server.url()).send().await.expect("request");
+    assert_eq!(response.metadata.fallback, Some(FallbackOutcome::PqcOk));
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(common::observed(&server, 1).await.len(), 1);
+}
+
+#[tokio::test]
+async fn a_server_refusing_any_hello_fails_both() {
+    // Every ClientHello is larger than this, classical or not.
+    let server = common::start(TestTlsServer::builder().max_hello_len(64)).await;
+    let client = falling_back(&server);
+    assert_eq!(
+        outcome(&client, &server).await,
+        (false, Some(FallbackOutcome::BothFailed))
+    );
+    assert_eq!(common::observed(&server, 2).await.len(), 2);
+}
+
+#[tokio::test]
+async fn certificate_failures_are_not_retried() {
+    // The bundled roots do not know the test server's certificate.
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_builder()
+        .fallback_to_classical(true)
+        .build()
+        .expect("build client");
+    assert_eq!(outcome(&client, &server).await, (false, None));
+    assert_eq!(common::observed(&server, 1).await.len(), 1);
+}
+
+#[tokio::test]
+async fn classical_only_clients_have_nothing_to_fall_back_from() {
+    let server = rejecting_server().await;
+    let client = common::client_for(&server)
+        .kx_preference(KxPreference::ClassicalOnly)
+        .fallback_to_classical(true)
+        .build()
+        .expect("build client");
+    let response = client.get(server.url()).send().await.expect("request");
+    assert_eq!(response.metadata.fallback, None);
+    assert_eq!(common::observed(&server, 1).await.len(), 1);
+}
+
+#[test]
+fn outcomes_print_their_reason() {
+    let outcome = FallbackOutcome::ClassicalOnlyAfterFailure {
+        original_error: HandshakeFailureKind::HandshakeRejected,
+    };
+    assert!(
+        outcome
+            .to_string()
+            .starts_with("classical-only after failure ("),
+        "{}",
+        outcome
+    );
+    assert_eq!(
+        FallbackOutcome::PqcOk.to_string(),
+        "post-quantum handshake ok"
+    );
+    assert_eq!(
+        FallbackOutcome::BothFailed.to_string(),
+        "classical-only retry failed too"
+    );
+}
@@ -0,0 +1,60 @@
+//! HTTP requests count the bytes of their handshake from the kernel's
+//! counters of the connection, so a hybrid key share shows in the
+//! ClientHello's size.
+
+mod common;
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{KxPreference, TlsMetadata};
+
+async fn request(server: &TestTlsServer, preference: KxPreference) -> TlsMetadata {
+    let client = common::client_for(server)
+        .kx_preference(preference)
+        .build()
+        .expect("build client");
+    client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request")
+        .metadata
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn a_hybrid_key_share_makes_the_client_hello_larger() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let hybrid = request(&server, KxPreference::HybridPreferred).await;
+    let classical = request(&server, KxPreference::ClassicalOnly).await;
+    assert_eq!(hybrid.group.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(classical.group.as_deref(), Some("X25519"));
+
+    let sent = |m: &TlsMetadata| m.handshake_bytes_sent.expect("bytes sent");
+    let received = |m: &TlsMetadata| m.handshake_bytes_received.expect("bytes received");
+    // The X25519MLKEM768 share is 1216 bytes, offered next to the X25519
+    // one, plus its entry in the key_share and supported_groups lists.
+    let extra = sent(&hybrid) - sent(&classical);
+    assert!((1216..1300).contains(&extra), "{} bytes more", extra);
+    // The server's share grows by the 1088-byte ML-KEM ciphertext; ECDSA
+    // signatures vary by a few bytes.
+    let extra = received(&hybrid) - received(&classical);
+    assert!((1080..1200).contains(&extra), "{} bytes more", extra);
+    // Both read the certificate chain, and wrote nothing of the request.
+    assert!(received(&classical) > 300, "{}", received(&classical));
+    assert!(sent(&classical) < 600, "{}", sent(&classical));
+}
+
+#[tokio::test]
+async fn shared_connections_count_no_handshake() {
+    let server = common::start(TestTlsServer::builder().keep_alive(true)).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let url = server.url();
+    let first = client.get(url.as_str()).send().await.expect("request");
+    let second = client.get(url.as_str()).send().await.expect("request");
+    assert_eq!(
+        first.metadata.handshake_bytes_sent.is_some(),
+        cfg!(target_os = "linux")
+    );
+    assert_eq!(second.metadata.handshake_bytes_sent, None);
+    assert_eq!(second.metadata.handshake_bytes_received, None);
+}
@@ -0,0 +1,195 @@
+//! Handshake failures provoked with misconfigured local servers, and how
+//! each is diagnosed.
+
+mod common;
+
+use std::sync::Arc;
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{
+    CertificateProblem, HandshakeFailureKind, KxPreference, TlsAwareClientBuilder, TraceError,
+};
+use rustls::crypto::{CryptoProvider, aws_lc_rs};
+use rustls::{AlertDescription, NamedGroup, PeerIncompatible};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn request(builder: TlsAwareClientBuilder, url: &str) -> Result<(), TraceError> {
+    let client = builder.build().expect("build client");
+    client.get(url).send().await.map(|_| ())
+}
+
+/// The diagnosis of requesting `url` with `builder`'s client, which must
+/// fail.
+async fn diagnosis(builder: TlsAwareClientBuilder, url: &str) -> HandshakeFailureKind {
+    match request(builder, url).await {
+        Ok(()) => panic!("{} did not fail", url),
+        Err(e) => e
+            .handshake_failure()
+            .unwrap_or_else(|| panic!("no handshake failure in {:?}", e)),
+    }
+}
+
+fn classical(server: &TestTlsServer) -> TlsAwareClientBuilder {
+    common::client_for(server).kx_preference(KxPreference::ClassicalOnly)
+}
+
+#[tokio::test]
+async fn rejected_groups() {
+    let server = common::start(
+        TestTlsServer::builder()
+            .groups([
+                NamedGroup::X25519,
+                NamedGroup::secp256r1,
+                NamedGroup::secp384r1,
+            ])
+            .reject_unknown_groups(true),
+    )
+    .await;
+    let kind = diagnosis(common::client_for(&server), &server.url()).await;
+    assert_eq!(kind, HandshakeFailureKind::HandshakeRejected);
+    assert!(kind.hint().unwrap().contains("--kx classical-only"));
+    // Following the hint gets through.
+    request(classical(&server), &server.url())
+        .await
+        .expect("classical groups are accepted");
+}
+
+#[tokio::test]
+async fn hellos_too_large_for_a_middlebox() {
+    // A hybrid key share makes the ClientHello well over 1000 bytes.
+    let server = common::start(TestTlsServer::builder().max_hello_len(1000)).await;
+    let kind = diagnosis(common::client_for(&server), &server.url()).await;
+    assert_eq!(kind, HandshakeFailureKind::ConnectionClosed);
+    assert!(kind.hint().unwrap().contains("--kx classical-only"));
+    request(classical(&server), &server.url())
+        .await
+        .expect("a classical ClientHello fits");
+}
+
+#[tokio::test]
+async fn no_common_version() {
+    let server = common::start(TestTlsServer::builder().tls12_only(true)).await;
+    let tls13_only = CryptoProvider {
+        cipher_suites: vec![aws_lc_rs::cipher_suite::TLS13_AES_128_GCM_SHA256],
+        ..aws_lc_rs::default_provider()
+    };
+    let client = common::client_for(&server).crypto_provider(Arc::new(tls13_only));
+    let kind = diagnosis(client, &server.url()).await;
+    assert_eq!(kind, HandshakeFailureKind::VersionMismatch);
+    assert!(kind.hint().unwrap().contains("TLS 1.2 and 1.3"));
+}
+
+#[tokio::test]
+async fn certificate_problems() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let kind = diagnosis(common::client_builder(), &server.url()).await;
+    assert_eq!(
+        kind,
+        HandshakeFailureKind::Certificate {
+            problem: CertificateProblem::Untrusted
+        }
+    );
+    assert!(kind.hint().unwrap().contains("--cacert"));
+
+    let server = common::start(TestTlsServer::builder().subject_alt_names(["other.example"])).await;
+    let kind = diagnosis(common::client_for(&server), &server.url()).await;
+    assert_eq!(
+        kind,
+        HandshakeFailureKind::Certificate {
+            problem: CertificateProblem::NameMismatch
+        }
+    );
+}
+
+/// Close the sending side of `stream`, then read until the client closes
+/// too, so that it sees an orderly end instead of a reset.
+async fn hang_up(mut stream: TcpStream) {
+    let _ = stream.shutdown().await;
+    let mut buf = [0; 4096];
+    while stream.read(&mut buf).await.is_ok_and(|n| n > 0) {}
+}
+
+#[tokio::test]
+async fn plaintext_and_silent_peers() {
+    let plaintext = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let closing = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let urls = [&plaintext, &closing].map(|listener| {
+        format!(
+            "https://localhost:{}/",
+            listener.local_addr().unwrap().port()
+        )
+    });
+    tokio::spawn(async move {
+        let (mut stream, _) = plaintext.accept().await.expect("accept");
+        let _ = stream
+            .write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")
+            .await;
+        hang_up(stream).await;
+    });
+    tokio::spawn(async move {
+        let (stream, _) = closing.accept().await.expect("accept");
+        hang_up(stream).await;
+    });
+
+    let kind = diagnosis(common::client_builder(), &urls[0]).await;
+    assert_eq!(kind, HandshakeFailureKind::NotTls);
+    assert!(kind.hint().unwrap().contains("http://"));
+    let kind = diagnosis(common::client_builder(), &urls[1]).await;
+    assert_eq!(kind, HandshakeFailureKind::ConnectionClosed);
+}
+
+#[tokio::test]
+async fn other_errors_are_no_handshake_failures() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let url = format!(
+        "https://localhost:{}/",
+        listener.local_addr().unwrap().port()
+    );
+    drop(listener);
+    let e = request(common::client_builder(), &url)
+        .await
+        .expect_err("nothing listens");
+    assert_eq!(e.handshake_failure(), None);
+}
+
+#[test]
+fn rustls_errors_map_to_kinds() {
+    use rustls::Error as E;
+    let cases = [
+        (
+            E::PeerIncompatible(PeerIncompatible::NoKxGroupsInCommon),
+            HandshakeFailureKind::NoCommonGroup,
+        ),
+        (
+            E::PeerIncompatible(PeerIncompatible::NoCipherSuitesInCommon),
+            HandshakeFailureKind::NoCommonCipherSuite,
+        ),
+        (
+            E::AlertReceived(AlertDescription::InsufficientSecurity),
+            HandshakeFailureKind::HandshakeRejected,
+        ),
+        (
+            E::AlertReceived(AlertDescription::CertificateRequired),
+            HandshakeFailureKind::ClientCertificateRequired,
+        ),
+        (
+            E::AlertReceived(AlertDescription::UnrecognisedName),
+            HandshakeFailureKind::ServerNameRejected,
+        ),
+        (
+            E::AlertReceived(AlertDescription::NoApplicationProtocol),
+            HandshakeFailureKind::AlpnMismatch,
+        ),
+        (
+            E::AlertReceived(AlertDescription::InternalError),
+            HandshakeFailureKind::Alert {
+                alert: "InternalError".to_string(),
+            },
+        ),
+    ];
+    for (error, kind) in cases {
+        assert_eq!(HandshakeFailureKind::of_tls(&error), kind, "{:?}", error);
+        assert!(!kind.to_string().is_empty());
+    }
+}
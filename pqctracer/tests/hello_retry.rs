@@ -0,0 +1,45 @@
+//! The round trip a HelloRetryRequest adds, and handshakes that have none.
+
+mod common;
+
+use pqctracer::testutil::{TestTlsServer, TestTlsServerBuilder};
+use pqctracer::{HandshakeOrigin, TlsMetadata};
+use rustls::NamedGroup;
+
+async fn trace(builder: TestTlsServerBuilder) -> TlsMetadata {
+    let server = common::start(builder).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let traced = client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(traced.metadata.origin, Some(HandshakeOrigin::Fresh));
+    traced.metadata
+}
+
+#[tokio::test]
+async fn a_retry_costs_a_round_trip() {
+    let metadata = trace(
+        TestTlsServer::builder()
+            .groups([NamedGroup::X25519MLKEM768, NamedGroup::secp256r1])
+            .force_hello_retry(true),
+    )
+    .await;
+    assert_eq!(metadata.group.as_deref(), Some("secp256r1"));
+    assert!(metadata.hrr_penalty.is_some());
+}
+
+#[tokio::test]
+async fn handshakes_without_a_retry_have_no_penalty() {
+    let metadata = trace(TestTlsServer::builder()).await;
+    assert_eq!(metadata.hrr_penalty, None);
+}
+
+#[tokio::test]
+async fn tls12_key_exchanges_are_no_retry() {
+    // rustls starts the TLS 1.2 key exchange after the ServerHello, once
+    // the first ClientHello's key share has gone unused.
+    let metadata = trace(TestTlsServer::builder().tls12_only(true)).await;
+    assert_eq!(metadata.hrr_penalty, None);
+}
@@ -0,0 +1,118 @@
+//! `probe_hello_size_tolerance` against servers that break on large
+//! ClientHellos, on hybrid groups, and on neither.
+
+mod common;
+
+use pqctracer::hello_size::{HelloProbe, HelloSizeReport, HelloSizeVerdict};
+use pqctracer::testutil::{TestTlsServer, TestTlsServerBuilder};
+use pqctracer::{ErrorCategory, TlsAwareClient};
+use rustls::NamedGroup;
+
+async fn probe(builder: TestTlsServerBuilder) -> (HelloSizeReport, TestTlsServer) {
+    let server = common::start(builder).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let report = client
+        .probe_hello_size_tolerance("localhost", server.local_addr().port())
+        .await;
+    (report, server)
+}
+
+fn completed(probe: &HelloProbe) -> Option<&str> {
+    match probe {
+        HelloProbe::Completed { group } => group.as_deref(),
+        HelloProbe::Failed { error } => panic!("failed: {}", error),
+    }
+}
+
+#[tokio::test]
+async fn a_tolerant_server_is_fine() {
+    let (report, server) = probe(TestTlsServer::builder()).await;
+    assert_eq!(report.verdict, HelloSizeVerdict::Fine, "{}", report);
+    assert_eq!(completed(&report.hybrid), Some("X25519MLKEM768"));
+    assert_eq!(completed(&report.padded), Some("X25519"));
+    assert!(report.classical.is_none());
+    // An ML-KEM key share alone is over a kilobyte.
+    assert!(report.hybrid_hello_len > report.classical_hello_len + 1000);
+    assert!(report.padded_hello_len >= report.hybrid_hello_len);
+    assert!(report.padded_hello_len < report.hybrid_hello_len + 8);
+
+    // The server saw the filler protocols after the one it speaks.
+    let observed = common::observed(&server, 2).await;
+    assert!(observed.iter().all(|handshake| handshake.error.is_none()));
+    assert!(
+        !observed[1]
+            .offered_groups
+            .contains(&NamedGroup::X25519MLKEM768)
+    );
+}
+
+#[tokio::test]
+async fn a_size_sensitive_server_is_a_size_problem() {
+    let (fine, _) = probe(TestTlsServer::builder()).await;
+    // Between the two sizes: classical hellos get through, large ones of
+    // either kind make the server close the connection.
+    let threshold = (fine.classical_hello_len + fine.hybrid_hello_len) / 2;
+    let (report, server) = probe(TestTlsServer::builder().max_hello_len(threshold)).await;
+
+    assert_eq!(report.verdict, HelloSizeVerdict::SizeProblem, "{}", report);
+    assert!(!report.hybrid.is_completed());
+    assert!(!report.padded.is_completed());
+    let classical = report.classical.as_ref().expect("tried unpadded");
+    assert_eq!(completed(classical), Some("X25519"));
+    assert_eq!(
+        report.hybrid_hello_len, fine.hybrid_hello_len,
+        "the hello is the same for every server"
+    );
+    assert!(report.to_string().starts_with("size problem (hybrid "));
+    assert_eq!(common::observed(&server, 3).await.len(), 3);
+}
+
+#[tokio::test]
+async fn a_server_rejecting_hybrid_groups_is_a_group_problem() {
+    let (report, _server) = probe(
+        TestTlsServer::builder()
+            .groups([
+                NamedGroup::X25519,
+                NamedGroup::secp256r1,
+                NamedGroup::secp384r1,
+            ])
+            .reject_unknown_groups(true),
+    )
+    .await;
+    assert_eq!(report.verdict, HelloSizeVerdict::GroupProblem, "{}", report);
+    let HelloProbe::Failed { error } = &report.hybrid else {
+        panic!("{}", report.hybrid);
+    };
+    assert!(
+        matches!(error, ErrorCategory::TlsHandshakeFailure { .. }),
+        "{}",
+        error
+    );
+    assert_eq!(completed(&report.padded), Some("X25519"));
+    assert!(report.classical.is_none());
+}
+
+#[tokio::test]
+async fn an_unreachable_server_is_inconclusive() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+    let report = common::client_builder()
+        .build()
+        .expect("build client")
+        .probe_hello_size_tolerance("localhost", port)
+        .await;
+    assert_eq!(report.verdict, HelloSizeVerdict::Inconclusive);
+    assert!(report.classical.is_some());
+
+    let report = TlsAwareClient::new()
+        .probe_hello_size_tolerance("not a host name", 443)
+        .await;
+    assert_eq!(report.verdict, HelloSizeVerdict::Inconclusive);
+    assert!(matches!(
+        report.hybrid,
+        HelloProbe::Failed {
+            error: ErrorCategory::InvalidUrl
+        }
+    ));
+}
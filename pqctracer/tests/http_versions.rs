@@ -0,0 +1,265 @@
+//! `probe_http_versions` against a pair of servers behind one port, the
+//! ClientHello's ALPN offer picking which answers: agreeing and
+//! disagreeing pairs, servers without HTTP/2, and scans comparing both.
+
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use pqctracer::ErrorCategory;
+use pqctracer::http_versions::{HttpVersionReport, HttpVersionSupport};
+use pqctracer::scan::ScanOptions;
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{TlsAwareClient, TlsMetadata};
+use rustls::NamedGroup;
+use rustls::crypto::aws_lc_rs;
+use rustls::server::{Acceptor, ResolvesServerCert, ServerConfig};
+use rustls_pki_types::CertificateDer;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::LazyConfigAcceptor;
+
+/// A config accepting `groups`, presenting the certificate of `resolver`
+/// and selecting `protocol`.
+fn config(
+    groups: &[NamedGroup],
+    resolver: Arc<dyn ResolvesServerCert>,
+    protocol: &str,
+) -> Arc<ServerConfig> {
+    let mut provider = aws_lc_rs::default_provider();
+    provider
+        .kx_groups
+        .retain(|group| groups.contains(&group.name()));
+    let mut config = ServerConfig::builder_with_provider(Arc::new(provider))
+        .with_safe_default_protocol_versions()
+        .expect("protocol versions")
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+    config.alpn_protocols = vec![protocol.as_bytes().to_vec()];
+    Arc::new(config)
+}
+
+/// A certificate for `localhost` and its resolver.
+fn certificate() -> (Arc<dyn ResolvesServerCert>, CertificateDer<'static>) {
+    let (config, cert) = common::server_config(&[]);
+    (config.cert_resolver.clone(), cert)
+}
+
+/// One port before the HTTP/1.1 server `http1` and the HTTP/2 server
+/// `h2`: a ClientHello offering `h2` goes to the latter, everything else to
+/// the former, which answers a request with `204 No Content`. HTTP/2
+/// connections are kept open until the client closes them.
+async fn alpn_router(http1: Arc<ServerConfig>, h2: Arc<ServerConfig>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let (http1, h2) = (http1.clone(), h2.clone());
+            tokio::spawn(async move {
+                let Ok(start) = LazyConfigAcceptor::new(Acceptor::default(), stream).await else {
+                    return;
+                };
+                let offers_h2 = start
+                    .client_hello()
+                    .alpn()
+                    .is_some_and(|mut protocols| protocols.any(|p| p == b"h2"));
+                let config = if offers_h2 { h2 } else { http1 };
+                let Ok(mut tls) = start.into_stream(config).await else {
+                    return;
+                };
+                if !offers_h2 {
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        match tls.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(read) => request.extend_from_slice(&buf[..read]),
+                        }
+                    }
+                    let answer = b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n";
+                    let _ = tls.write_all(answer).await;
+                    let _ = tls.shutdown().await;
+                    return;
+                }
+                let _ = tokio::io::copy(&mut tls, &mut tokio::io::sink()).await;
+            });
+        }
+    });
+    addr
+}
+
+fn client(certs: &[&CertificateDer<'static>]) -> TlsAwareClient {
+    certs
+        .iter()
+        .fold(common::client_builder(), |builder, cert| {
+            builder.add_root_certificate((*cert).clone())
+        })
+        .build()
+        .expect("build client")
+}
+
+fn negotiated(support: &HttpVersionSupport) -> &TlsMetadata {
+    match support {
+        HttpVersionSupport::Negotiated { metadata } => metadata,
+        other => panic!("{:?}", other),
+    }
+}
+
+async fn probe(client: &TlsAwareClient, addr: SocketAddr) -> HttpVersionReport {
+    client.probe_http_versions("localhost", addr.port()).await
+}
+
+#[tokio::test]
+async fn different_groups_per_protocol_are_inconsistent() {
+    let (resolver, cert) = certificate();
+    let addr = alpn_router(
+        config(
+            &[NamedGroup::X25519MLKEM768, NamedGroup::X25519],
+            resolver.clone(),
+            "http/1.1",
+        ),
+        config(&[NamedGroup::X25519], resolver, "h2"),
+    )
+    .await;
+    let report = probe(&client(&[&cert]), addr).await;
+
+    let http1 = negotiated(&report.http1);
+    let h2 = negotiated(&report.h2);
+    assert_eq!(http1.group.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(http1.alpn.as_deref(), Some("http/1.1"));
+    assert_eq!(h2.group.as_deref(), Some("X25519"));
+    assert_eq!(h2.alpn.as_deref(), Some("h2"));
+    assert_eq!(http1.peer_certificates, h2.peer_certificates);
+    assert!(!report.versions_consistent);
+    assert_eq!(
+        report.to_string(),
+        "http/1.1 X25519MLKEM768, h2 X25519 (INCONSISTENT)"
+    );
+}
+
+#[tokio::test]
+async fn a_different_certificate_is_inconsistent() {
+    let (http1_resolver, http1_cert) = certificate();
+    let (h2_resolver, h2_cert) = certificate();
+    let groups = [NamedGroup::X25519MLKEM768, NamedGroup::X25519];
+    let addr = alpn_router(
+        config(&groups, http1_resolver, "http/1.1"),
+        config(&groups, h2_resolver, "h2"),
+    )
+    .await;
+    let report = probe(&client(&[&http1_cert, &h2_cert]), addr).await;
+    assert_eq!(
+        negotiated(&report.http1).group,
+        negotiated(&report.h2).group
+    );
+    assert_ne!(
+        negotiated(&report.http1).peer_certificates,
+        negotiated(&report.h2).peer_certificates
+    );
+    assert!(!report.versions_consistent);
+}
+
+#[tokio::test]
+async fn an_agreeing_pair_is_consistent() {
+    let (resolver, cert) = certificate();
+    let groups = [NamedGroup::X25519MLKEM768, NamedGroup::X25519];
+    let addr = alpn_router(
+        config(&groups, resolver.clone(), "http/1.1"),
+        config(&groups, resolver, "h2"),
+    )
+    .await;
+    let report = probe(&client(&[&cert]), addr).await;
+    assert!(report.versions_consistent);
+    assert_eq!(
+        report.to_string(),
+        "http/1.1 X25519MLKEM768, h2 X25519MLKEM768"
+    );
+}
+
+#[tokio::test]
+async fn a_server_refusing_h2_does_not_support_it() {
+    // The test server answers an offer without http/1.1 with a
+    // no_application_protocol alert.
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let report = probe(&client, server.local_addr()).await;
+
+    assert_eq!(
+        negotiated(&report.http1).group.as_deref(),
+        Some("X25519MLKEM768")
+    );
+    let HttpVersionSupport::Unsupported { reason } = &report.h2 else {
+        panic!("{:?}", report.h2);
+    };
+    assert!(reason.contains("NoApplicationProtocol"), "{}", reason);
+    // Nothing to disagree with, and no failure either.
+    assert!(report.versions_consistent);
+}
+
+#[tokio::test]
+async fn a_server_ignoring_alpn_does_not_support_h2() {
+    let (resolver, cert) = certificate();
+    let mut ignoring = (*config(&[NamedGroup::X25519], resolver, "http/1.1")).clone();
+    ignoring.alpn_protocols.clear();
+    let ignoring = Arc::new(ignoring);
+    let addr = alpn_router(ignoring.clone(), ignoring).await;
+    let report = probe(&client(&[&cert]), addr).await;
+
+    // HTTP/1.1 is what a server without ALPN speaks.
+    assert_eq!(negotiated(&report.http1).alpn, None);
+    assert_eq!(report.h2.to_string(), "unsupported (no protocol selected)");
+    assert!(report.versions_consistent);
+}
+
+#[tokio::test]
+async fn unreachable_servers_fail_both_probes() {
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+    let addr = closed.local_addr().unwrap();
+    drop(closed);
+    let report = probe(&common::insecure_client(), addr).await;
+    for support in [&report.http1, &report.h2] {
+        let HttpVersionSupport::Failed { error } = support else {
+            panic!("{:?}", support);
+        };
+        assert_eq!(*error, ErrorCategory::ConnectRefused);
+    }
+    assert!(report.versions_consistent);
+}
+
+#[tokio::test]
+async fn scans_compare_both_versions() {
+    let (resolver, cert) = certificate();
+    let addr = alpn_router(
+        config(
+            &[NamedGroup::X25519MLKEM768, NamedGroup::X25519],
+            resolver.clone(),
+            "http/1.1",
+        ),
+        config(&[NamedGroup::X25519], resolver, "h2"),
+    )
+    .await;
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = client(&[&cert, server.cert_der()]);
+    let options = ScanOptions {
+        retries: 0,
+        compare_http_versions: true,
+        ..ScanOptions::default()
+    };
+    let targets = [format!("https://localhost:{}/", addr.port()), server.url()];
+    let results = client.scan(targets, &options).await;
+
+    // The router's HTTP/1.1 server answers the scan's own request, so it
+    // is reached; the probe finds the h2 side disagrees.
+    assert_eq!(results[0].status, Some(204));
+    let router = results[0].http_versions.as_ref().expect("compared");
+    assert!(!router.versions_consistent);
+    let test_server = results[1].http_versions.as_ref().expect("compared");
+    assert!(test_server.versions_consistent);
+    assert!(!test_server.h2.is_negotiated());
+    assert!(results[1].error.is_none(), "{:?}", results[1].error);
+
+    // Without the option there is nothing to compare.
+    let results = client.scan([server.url()], &ScanOptions::default()).await;
+    assert!(results[0].http_versions.is_none());
+}
@@ -0,0 +1,110 @@
+//! Internationalized domain names against a test server whose certificate
+//! carries only the A-label `xn--mnchen-3ya.example`: requests and scans
+//! to the Unicode name succeed and report both forms, a certificate for
+//! another name is checked against the A-label, and invalid names fail
+//! naming their label.
+
+mod common;
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use pqctracer::idn::IdnName;
+use pqctracer::scan::{ScanOptions, ScanTarget};
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{CertificateProblem, ErrorCategory, NameMismatch, TlsAwareClient};
+
+const UNICODE: &str = "münchen.example";
+const ASCII: &str = "xn--mnchen-3ya.example";
+
+fn expected() -> Option<IdnName> {
+    Some(IdnName {
+        unicode: UNICODE.to_string(),
+        ascii: ASCII.to_string(),
+    })
+}
+
+/// A server whose certificate names `name` alone, and a client trusting it
+/// that resolves the A-label to loopback.
+async fn server_for(name: &str) -> (TestTlsServer, TlsAwareClient) {
+    let server = common::start(TestTlsServer::builder().subject_alt_names([name])).await;
+    let client = common::client_for(&server)
+        .resolve(ASCII, &[IpAddr::V4(Ipv4Addr::LOCALHOST)])
+        .build()
+        .expect("build client");
+    (server, client)
+}
+
+#[tokio::test]
+async fn a_unicode_host_is_matched_against_its_a_label() {
+    let (server, client) = server_for(ASCII).await;
+    for host in [UNICODE, ASCII] {
+        let url = format!("https://{}:{}/", host, server.local_addr().port());
+        let response = client.get(&url).send().await.expect("request");
+        assert_eq!(response.response.status(), 200);
+        assert_eq!(response.metadata.idn, expected(), "{}", host);
+        assert_eq!(response.metadata.group.as_deref(), Some("X25519MLKEM768"));
+        assert_eq!(response.metadata.peer_certificates.len(), 1);
+    }
+    common::observed(&server, 2).await;
+}
+
+#[tokio::test]
+async fn scans_report_both_forms() {
+    let (server, client) = server_for(ASCII).await;
+    let url = format!("https://{}:{}/", UNICODE, server.local_addr().port());
+    let address = ScanTarget::Address {
+        addr: server.local_addr(),
+        server_name: UNICODE.to_string(),
+    };
+    let results = client
+        .scan([ScanTarget::from(url), address], &ScanOptions::default())
+        .await;
+    for result in &results {
+        assert!(result.error.is_none(), "{:?}", result.error);
+        assert_eq!(result.metadata.idn, expected());
+        assert_eq!(result.metadata.group.as_deref(), Some("X25519MLKEM768"));
+    }
+    common::observed(&server, 2).await;
+}
+
+#[tokio::test]
+async fn a_certificate_for_another_name_is_checked_in_a_label_form() {
+    let (server, client) = server_for("xn--bcher-kva.example").await;
+    let url = format!("https://{}:{}/", UNICODE, server.local_addr().port());
+    let results = client.scan([url], &ScanOptions::default()).await;
+    let error = results[0]
+        .error
+        .as_ref()
+        .expect("a certificate for bücher.example");
+    assert_eq!(
+        error.category,
+        ErrorCategory::CertificateInvalid {
+            reason: CertificateProblem::NameMismatch
+        }
+    );
+    assert_eq!(
+        error.name_mismatch,
+        Some(NameMismatch {
+            expected: ASCII.to_string(),
+            presented: vec!["xn--bcher-kva.example".to_string()],
+            wildcard_near_miss: None,
+        })
+    );
+    assert_eq!(results[0].metadata.idn, expected());
+}
+
+#[tokio::test]
+async fn an_invalid_idn_is_refused_naming_the_label() {
+    let (server, client) = server_for(ASCII).await;
+    let url = format!("https://xn--zz.example:{}/", server.local_addr().port());
+    let results = client.scan([url], &ScanOptions::default()).await;
+    let error = results[0].error.as_ref().expect("an invalid IDN");
+    assert!(
+        error
+            .message
+            .contains("the label \"xn--zz\" is not a valid"),
+        "{}",
+        error.message
+    );
+    assert!(server.observed().is_empty());
+}
@@ -0,0 +1,101 @@
+//! The group, cipher, ticket and HelloRetryRequest checks of the socket
+//! tests, run through `testutil::handshake_in_memory` without sockets.
+
+mod common;
+
+use std::sync::Arc;
+
+use pqctracer::testutil::{ServerObservations, capturing_provider, handshake_in_memory};
+use pqctracer::{CaptureStatus, HandshakeOrigin, TlsMetadata};
+use rustls::crypto::{CryptoProvider, aws_lc_rs};
+use rustls::{ClientConfig, NamedGroup, RootCertStore, ServerConfig};
+
+/// One handshake between a client on `provider` and a server accepting
+/// `groups` after `configure` had its say.
+async fn handshake(
+    provider: Arc<CryptoProvider>,
+    groups: &[NamedGroup],
+    configure: impl FnOnce(&mut ServerConfig),
+) -> (TlsMetadata, ServerObservations) {
+    let (mut server_config, cert) = common::server_config(groups);
+    configure(&mut server_config);
+    let mut roots = RootCertStore::empty();
+    roots.add(cert).expect("root");
+    let client_config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .expect("protocol versions")
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    handshake_in_memory(client_config, Arc::new(server_config))
+        .await
+        .expect("handshake")
+}
+
+fn capturing() -> Arc<CryptoProvider> {
+    capturing_provider(&aws_lc_rs::default_provider())
+}
+
+#[tokio::test]
+async fn every_handshake_reports_its_own_group_and_cipher() {
+    for group in [
+        NamedGroup::X25519MLKEM768,
+        NamedGroup::X25519,
+        NamedGroup::secp256r1,
+        NamedGroup::secp384r1,
+    ] {
+        let (metadata, server) = handshake(capturing(), &[group], |_| {}).await;
+        assert_eq!(server.selected_group, Some(group));
+        assert_eq!(metadata.group, Some(format!("{:?}", group)));
+        assert_eq!(metadata.cipher.as_deref(), Some("TLS13_AES_256_GCM_SHA384"));
+        assert_eq!(metadata.cipher, server.cipher.map(|c| format!("{:?}", c)));
+        assert_eq!(metadata.origin, Some(HandshakeOrigin::Fresh));
+        assert_eq!(metadata.capture_status, CaptureStatus::Captured);
+        assert_eq!(server.server_name.as_deref(), Some("localhost"));
+    }
+}
+
+#[tokio::test]
+async fn a_group_without_a_key_share_needs_a_retry() {
+    let (metadata, server) = handshake(capturing(), &[NamedGroup::secp256r1], |_| {}).await;
+    assert!(server.hello_retry);
+    assert!(server.offered_groups.contains(&NamedGroup::X25519MLKEM768));
+    assert_eq!(metadata.group.as_deref(), Some("secp256r1"));
+
+    let (_, server) = handshake(capturing(), &[], |_| {}).await;
+    assert!(!server.hello_retry);
+}
+
+#[tokio::test]
+async fn tickets_are_counted() {
+    let (metadata, _) = handshake(capturing(), &[], |config| {
+        config.send_tls13_tickets = 3;
+    })
+    .await;
+    assert_eq!(metadata.tickets_issued, Some(3));
+}
+
+#[tokio::test]
+async fn the_cipher_is_captured_without_tickets() {
+    let (metadata, server) = handshake(capturing(), &[], |config| {
+        config.send_tls13_tickets = 0;
+    })
+    .await;
+    assert_eq!(metadata.cipher, server.cipher.map(|c| format!("{:?}", c)));
+    assert!(metadata.cipher.is_some());
+    // The server closed the connection without sending any.
+    assert_eq!(metadata.tickets_issued, Some(0));
+}
+
+#[tokio::test]
+async fn other_providers_report_the_connections_cipher_without_tickets() {
+    let provider = Arc::new(aws_lc_rs::default_provider());
+    let (metadata, server) = handshake(provider, &[], |config| {
+        config.send_tls13_tickets = 0;
+    })
+    .await;
+    assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(metadata.cipher.as_deref(), Some("TLS13_AES_256_GCM_SHA384"));
+    assert_eq!(metadata.cipher, server.cipher.map(|c| format!("{:?}", c)));
+    // Only the session tickets would have told the capture.
+    assert_eq!(metadata.capture_status, CaptureStatus::NoSessionTicket);
+}
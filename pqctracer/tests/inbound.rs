@@ -0,0 +1,190 @@
+//! `server::TracingAcceptor` accepting the tracer's own handshakes, and with
+//! the `tower` feature `InboundTlsLayer` handing them to a hyper service.
+
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use pqctracer::TlsAwareClient;
+use pqctracer::server::{InboundHandshakeInfo, TracingAcceptor};
+use rustls::{NamedGroup, ProtocolVersion, ServerConfig};
+use rustls_pki_types::CertificateDer;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+
+/// [`common::server_config`], shared by the acceptors.
+fn server_config(groups: &[NamedGroup]) -> (Arc<ServerConfig>, CertificateDer<'static>) {
+    let (config, cert) = common::server_config(groups);
+    (Arc::new(config), cert)
+}
+
+async fn listener() -> (TcpListener, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr = listener.local_addr().expect("local address");
+    (listener, addr)
+}
+
+/// Accept one connection on a server accepting `groups`, answer its first
+/// request with a `204`, and return what the acceptor recorded together
+/// with the client's metadata of the request.
+async fn accept_one(groups: &[NamedGroup]) -> (InboundHandshakeInfo, pqctracer::TlsMetadata) {
+    let (config, cert) = server_config(groups);
+    let (listener, addr) = listener().await;
+    let (recorded, info) = oneshot::channel();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.expect("accept");
+        let accepted = TracingAcceptor::new(config).accept_tcp(stream).await;
+        let (mut tls, info) = accepted.expect("handshake");
+        let _ = recorded.send(info);
+        let _ = tls
+            .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+            .await;
+        let _ = tls.shutdown().await;
+    });
+
+    common::install_provider();
+    let client = TlsAwareClient::builder()
+        .add_root_certificate(cert)
+        .build()
+        .expect("client");
+    let url = format!("https://localhost:{}/", addr.port());
+    let response = client.get(&url).send().await.expect("request");
+    assert_eq!(response.response.status(), 204);
+    (info.await.expect("recorded"), response.metadata)
+}
+
+#[tokio::test]
+async fn the_acceptor_reports_what_the_tracer_offered_and_got() {
+    let (info, metadata) = accept_one(&[]).await;
+    assert_eq!(
+        info.offered_groups.first(),
+        Some(&NamedGroup::X25519MLKEM768)
+    );
+    assert!(info.offered_groups.contains(&NamedGroup::X25519));
+    assert!(info.offered_pqc());
+    assert_eq!(info.selected_group, Some(NamedGroup::X25519MLKEM768));
+    assert_eq!(info.version, Some(ProtocolVersion::TLSv1_3));
+    assert_eq!(info.server_name.as_deref(), Some("localhost"));
+    assert!(info.error.is_none());
+    // Both ends agree on the handshake and on the connection.
+    assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(
+        info.cipher.map(|suite| format!("{:?}", suite)),
+        metadata.cipher
+    );
+    assert_eq!(info.peer, metadata.local_addr);
+}
+
+#[tokio::test]
+async fn a_classical_server_selects_a_classical_group_the_tracer_offered() {
+    let (info, metadata) = accept_one(&[NamedGroup::X25519]).await;
+    assert!(info.offered_pqc());
+    assert!(info.offered_groups.contains(&NamedGroup::X25519MLKEM768));
+    assert_eq!(info.selected_group, Some(NamedGroup::X25519));
+    assert_eq!(metadata.group.as_deref(), Some("X25519"));
+}
+
+#[cfg(feature = "tower")]
+mod tower_layer {
+    use std::convert::Infallible;
+
+    use http::{Request, Response};
+    use pqctracer::server::{InboundTlsInfo, InboundTlsLayer};
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+    use tower::{Layer, Service, ServiceExt};
+
+    use super::*;
+
+    /// Answers with the `X-Key-Exchange` header the README's example sets,
+    /// made of the connection's info in the request's extensions.
+    async fn handler(request: Request<()>) -> Result<Response<String>, Infallible> {
+        let response = match request.extensions().get::<InboundTlsInfo>() {
+            Some(tls) => Response::builder()
+                .header("X-Key-Exchange", tls.group_name().unwrap_or_default())
+                .body(format!(
+                    "{:?} {:?} resumed={}",
+                    tls.cipher, tls.version, tls.resumed
+                )),
+            None => Response::builder()
+                .status(500)
+                .body("no InboundTlsInfo".to_string()),
+        };
+        Ok(response.expect("response"))
+    }
+
+    /// Serve the bodiless HTTP/1.1 requests on `tls` with `service`, as
+    /// hyper's `serve_connection` would, until the client closes it.
+    async fn serve<S>(mut tls: impl AsyncRead + AsyncWrite + Unpin, mut service: S)
+    where
+        S: Service<Request<()>, Response = Response<String>, Error = Infallible>,
+    {
+        let mut buffered = Vec::new();
+        loop {
+            let Some(end) = buffered.windows(4).position(|w| w == b"\r\n\r\n") else {
+                let mut buf = [0u8; 4096];
+                match tls.read(&mut buf).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => buffered.extend_from_slice(&buf[..n]),
+                }
+                continue;
+            };
+            let head = String::from_utf8_lossy(&buffered[..end]).into_owned();
+            buffered.drain(..end + 4);
+            let path = head.split(' ').nth(1).unwrap_or("/");
+            let request = Request::builder().uri(path).body(()).expect("request");
+            let response = service.ready().await.unwrap().call(request).await.unwrap();
+            let mut answer = format!("HTTP/1.1 {}\r\n", response.status());
+            for (name, value) in response.headers() {
+                answer.push_str(&format!("{}: {}\r\n", name, value.to_str().unwrap()));
+            }
+            let body = response.into_body();
+            answer.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+            if tls.write_all(answer.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn handlers_see_their_connections_handshake() {
+        let (config, cert) = server_config(&[]);
+        let (listener, addr) = listener().await;
+        let acceptor = TracingAcceptor::new(config);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok((tls, info)) = acceptor.accept_tcp(stream).await {
+                        let service =
+                            InboundTlsLayer::new(info.tls_info()).layer(tower::service_fn(handler));
+                        serve(tls, service).await;
+                    }
+                });
+            }
+        });
+
+        common::install_provider();
+        let client = TlsAwareClient::builder()
+            .add_root_certificate(cert)
+            .build()
+            .expect("client");
+        let url = format!("https://localhost:{}/", addr.port());
+        // The second request is served on the first one's connection, and
+        // sees the same info.
+        for _ in 0..2 {
+            let response = client.get(&url).send().await.expect("request");
+            let group = response.metadata.group.clone();
+            assert_eq!(group.as_deref(), Some("X25519MLKEM768"));
+            assert_eq!(response.response.status(), 200);
+            let header = response.response.headers()["x-key-exchange"].clone();
+            assert_eq!(header.to_str().ok(), group.as_deref());
+            let body = response.response.text().await.expect("body");
+            assert_eq!(
+                body,
+                "Some(TLS13_AES_256_GCM_SHA384) Some(TLSv1_3) resumed=false"
+            );
+        }
+    }
+}
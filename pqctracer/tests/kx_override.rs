@@ -0,0 +1,164 @@
+//! Per-request key-exchange overrides against the test server: the groups
+//! each offers, the policy violation of a classical group under
+//! `PqcRequired`, and the pools kept apart.
+
+mod common;
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{KxOverride, KxPreference, TraceError};
+use rustls::NamedGroup;
+
+#[tokio::test]
+async fn the_default_keeps_the_clients_preference() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let response = client
+        .get(server.url())
+        .key_exchange(KxOverride::Default)
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519MLKEM768"));
+    let observed = common::observed(&server, 1).await;
+    assert_eq!(observed[0].key_shares[0], NamedGroup::X25519MLKEM768);
+
+    // A classical client stays classical under the default.
+    let client = common::client_for(&server)
+        .kx_preference(KxPreference::ClassicalOnly)
+        .build()
+        .expect("build client");
+    let response = client
+        .get(server.url())
+        .key_exchange(KxOverride::Default)
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519"));
+}
+
+#[tokio::test]
+async fn classical_only_offers_no_hybrid_group() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let response = client
+        .get(server.url())
+        .key_exchange(KxOverride::ClassicalOnly)
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519"));
+
+    let observed = common::observed(&server, 1).await;
+    assert!(
+        !observed[0]
+            .offered_groups
+            .contains(&NamedGroup::X25519MLKEM768),
+        "{}",
+        observed[0]
+    );
+    assert_eq!(observed[0].selected_group, Some(NamedGroup::X25519));
+}
+
+#[tokio::test]
+async fn pqc_required_passes_a_hybrid_handshake() {
+    let server = common::start(TestTlsServer::builder()).await;
+    // Even a classical client offers the hybrid groups when they are
+    // required.
+    let client = common::client_for(&server)
+        .kx_preference(KxPreference::ClassicalOnly)
+        .build()
+        .expect("build client");
+    let response = client
+        .get(server.url())
+        .key_exchange(KxOverride::PqcRequired)
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519MLKEM768"));
+    let observed = common::observed(&server, 1).await;
+    assert!(
+        observed[0]
+            .offered_groups
+            .contains(&NamedGroup::X25519MLKEM768)
+    );
+}
+
+#[tokio::test]
+async fn pqc_required_refuses_a_classical_group() {
+    let server = common::start(TestTlsServer::builder().groups([NamedGroup::X25519])).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let Err(error) = client
+        .get(server.url())
+        .key_exchange(KxOverride::PqcRequired)
+        .send()
+        .await
+    else {
+        panic!("the server only accepts X25519");
+    };
+    let TraceError::PolicyViolation { reason, metadata } = &error else {
+        panic!("{:?}", error);
+    };
+    assert_eq!(
+        reason,
+        "post-quantum key exchange required, X25519 negotiated"
+    );
+    assert_eq!(metadata.group.as_deref(), Some("X25519"));
+    assert!(metadata.cipher.is_some());
+    assert_eq!(
+        error.to_string(),
+        "policy violation: post-quantum key exchange required, X25519 negotiated"
+    );
+
+    // The handshake itself went through; only the policy failed it.
+    let observed = common::observed(&server, 1).await;
+    assert_eq!(observed[0].selected_group, Some(NamedGroup::X25519));
+    assert_eq!(observed[0].error, None);
+}
+
+#[tokio::test]
+async fn overridden_requests_leave_the_pool_alone() {
+    let server = common::start(TestTlsServer::builder().keep_alive(true)).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let send = |key_exchange| {
+        let request = client.get(server.url()).key_exchange(key_exchange);
+        async move {
+            let response = request.send().await.expect("request");
+            let (_, metadata) = response.text().await.expect("body");
+            metadata
+        }
+    };
+
+    let first = send(KxOverride::Default).await;
+    assert_eq!(first.group.as_deref(), Some("X25519MLKEM768"));
+    let classical = send(KxOverride::ClassicalOnly).await;
+    assert_eq!(classical.group.as_deref(), Some("X25519"));
+    let again = send(KxOverride::Default).await;
+    assert_eq!(again.group.as_deref(), Some("X25519MLKEM768"));
+
+    // The third request reused the first connection, its pool untouched by
+    // the classical one in between.
+    let observed = common::observed(&server, 2).await;
+    assert_eq!(observed[0].selected_group, Some(NamedGroup::X25519MLKEM768));
+    assert_eq!(observed[1].selected_group, Some(NamedGroup::X25519));
+}
+
+#[test]
+fn overrides_parse_and_display() {
+    for (text, key_exchange) in [
+        ("default", KxOverride::Default),
+        ("classical-only", KxOverride::ClassicalOnly),
+        ("pqc-required", KxOverride::PqcRequired),
+    ] {
+        assert_eq!(text.parse::<KxOverride>(), Ok(key_exchange));
+        assert_eq!(key_exchange.to_string(), text);
+    }
+    assert_eq!("PQC".parse::<KxOverride>(), Ok(KxOverride::PqcRequired));
+    assert_eq!(
+        "classical".parse::<KxOverride>(),
+        Ok(KxOverride::ClassicalOnly)
+    );
+    assert_eq!(
+        "hybrid".parse::<KxOverride>(),
+        Err("unknown key-exchange override: hybrid".to_string())
+    );
+}
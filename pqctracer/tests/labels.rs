@@ -0,0 +1,156 @@
+//! Request labels carried into the metadata: by the traced request
+//! builder, `execute_with_labels`, a classical-only retry and scans, and
+//! out into the serialized metadata.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use pqctracer::scan::{ScanOptions, ScanTarget};
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{FallbackOutcome, KxOverride};
+use rustls::NamedGroup;
+
+fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn builder_labels_end_up_in_the_metadata() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let response = client
+        .get(server.url())
+        .label("tenant", "acme")
+        .label("operation", "checkout")
+        // A second label with the same key replaces the first.
+        .label("tenant", "globex")
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(
+        response.metadata.labels,
+        labels(&[("operation", "checkout"), ("tenant", "globex")])
+    );
+
+    // An overridden key exchange goes another way, labels and all.
+    let response = client
+        .get(server.url())
+        .key_exchange(KxOverride::ClassicalOnly)
+        .label("tenant", "acme")
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(response.metadata.labels, labels(&[("tenant", "acme")]));
+
+    let response = client.get(server.url()).send().await.expect("request");
+    assert!(response.metadata.labels.is_empty());
+}
+
+#[tokio::test]
+async fn execute_with_labels_carries_them() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let request = reqwest::Request::new(reqwest::Method::GET, server.url().parse().unwrap());
+    let response = client
+        .execute_with_labels(request, labels(&[("job", "nightly")]))
+        .await
+        .expect("request");
+    assert_eq!(response.metadata.labels, labels(&[("job", "nightly")]));
+}
+
+#[tokio::test]
+async fn a_classical_retry_keeps_the_labels() {
+    let server = common::start(
+        TestTlsServer::builder()
+            .groups([
+                NamedGroup::X25519,
+                NamedGroup::secp256r1,
+                NamedGroup::secp384r1,
+            ])
+            .reject_unknown_groups(true),
+    )
+    .await;
+    let client = common::client_for(&server)
+        .fallback_to_classical(true)
+        .build()
+        .expect("build client");
+    let response = client
+        .get(server.url())
+        .label("tenant", "acme")
+        .send()
+        .await
+        .expect("retried");
+    assert!(
+        matches!(
+            response.metadata.fallback,
+            Some(FallbackOutcome::ClassicalOnlyAfterFailure { .. })
+        ),
+        "{:?}",
+        response.metadata.fallback
+    );
+    assert_eq!(response.metadata.labels, labels(&[("tenant", "acme")]));
+}
+
+#[tokio::test]
+async fn every_scan_result_carries_the_scans_labels() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+    let closed_url = format!("https://127.0.0.1:{}/", closed.local_addr().unwrap().port());
+    drop(closed);
+    let targets = [
+        ScanTarget::Url(server.url()),
+        ScanTarget::Url(closed_url),
+        ScanTarget::Address {
+            addr: server.local_addr(),
+            server_name: "localhost".to_string(),
+        },
+    ];
+    let options = ScanOptions {
+        retries: 0,
+        labels: labels(&[("run", "42")]),
+        ..ScanOptions::default()
+    };
+    let results = common::client_for(&server)
+        .build()
+        .expect("build client")
+        .scan(targets, &options)
+        .await;
+
+    assert!(results[0].error.is_none(), "{:?}", results[0].error);
+    assert!(results[1].error.is_some());
+    assert!(results[2].error.is_none(), "{:?}", results[2].error);
+    for result in &results {
+        assert_eq!(result.metadata.labels, options.labels, "{}", result.target);
+    }
+
+    let unlabelled = common::client_for(&server)
+        .build()
+        .expect("build client")
+        .scan([server.url()], &ScanOptions::default())
+        .await;
+    assert!(unlabelled[0].metadata.labels.is_empty());
+}
+
+#[cfg(all(feature = "serde", feature = "json"))]
+#[tokio::test]
+async fn labels_are_serialized_with_the_metadata() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let response = client
+        .get(server.url())
+        .label("tenant", "acme")
+        .send()
+        .await
+        .expect("request");
+    let json = serde_json::to_value(&response.metadata).expect("serialize");
+    assert_eq!(json["labels"], serde_json::json!({ "tenant": "acme" }));
+
+    // And left out when there are none.
+    let response = client.get(server.url()).send().await.expect("request");
+    let json = serde_json::to_value(&response.metadata).expect("serialize");
+    assert!(json.get("labels").is_none(), "{}", json);
+}
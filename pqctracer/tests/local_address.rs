@@ -0,0 +1,125 @@
+//! `local_address` on loopback: connections from 127.0.0.2 instead of
+//! 127.0.0.1, as the server sees them and as the metadata reports them, and
+//! targets of the other address family.
+
+mod common;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use pqctracer::scan::{ScanOptions, ScanTarget};
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{TlsAwareClientBuilder, TlsMetadata, TraceError};
+
+const SECOND_LOOPBACK: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+
+/// The metadata of a request to `server` by `builder`'s client, after
+/// checking that both ends match what the server saw.
+async fn request(builder: TlsAwareClientBuilder, server: &TestTlsServer) -> TlsMetadata {
+    let client = builder.build().expect("build client");
+    let response = client.get(server.url()).send().await.expect("request");
+    let metadata = response.metadata;
+    let observed = common::observed(server, 1).await;
+    assert_eq!(metadata.local_addr, Some(observed[0].peer));
+    assert_eq!(metadata.remote_addr, Some(server.local_addr()));
+    metadata
+}
+
+#[tokio::test]
+async fn requests_come_from_the_default_address_without_one() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let metadata = request(common::client_for(&server), &server).await;
+    assert_eq!(
+        metadata.local_addr.map(|addr| addr.ip()),
+        Some(IpAddr::V4(Ipv4Addr::LOCALHOST))
+    );
+}
+
+#[tokio::test]
+async fn requests_come_from_the_local_address() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let builder = common::client_for(&server).local_address(SECOND_LOOPBACK);
+    let metadata = request(builder, &server).await;
+    assert_eq!(metadata.local_addr.unwrap().ip(), SECOND_LOOPBACK);
+    assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+}
+
+#[tokio::test]
+async fn resolved_hosts_are_reached_from_the_local_address() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let builder = common::client_for(&server)
+        .local_address(SECOND_LOOPBACK)
+        .resolve("localhost", &[IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+    let metadata = request(builder, &server).await;
+    assert_eq!(metadata.local_addr.unwrap().ip(), SECOND_LOOPBACK);
+}
+
+#[tokio::test]
+async fn timed_requests_come_from_the_local_address() {
+    // Timed clients resolve through a resolver of their own.
+    let server = common::start(TestTlsServer::builder()).await;
+    let builder = common::client_for(&server)
+        .local_address(SECOND_LOOPBACK)
+        .collect_timings(true);
+    let metadata = request(builder, &server).await;
+    assert_eq!(metadata.local_addr.unwrap().ip(), SECOND_LOOPBACK);
+    assert!(metadata.timings.is_some());
+}
+
+#[tokio::test]
+async fn the_tracers_own_handshakes_come_from_the_local_address() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server)
+        .local_address(SECOND_LOOPBACK)
+        .build()
+        .expect("build client");
+    let target = ScanTarget::Address {
+        addr: server.local_addr(),
+        server_name: "localhost".to_string(),
+    };
+    let results = client.scan([target], &ScanOptions::default()).await;
+    assert!(results[0].error.is_none(), "{:?}", results[0].error);
+    let observed = common::observed(&server, 1).await;
+    assert_eq!(observed[0].peer.ip(), SECOND_LOOPBACK);
+    assert_eq!(results[0].metadata.local_addr, Some(observed[0].peer));
+    assert_eq!(results[0].metadata.remote_addr, Some(server.local_addr()));
+}
+
+#[tokio::test]
+async fn a_target_of_the_other_family_is_refused() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server)
+        .local_address(IpAddr::V6(Ipv6Addr::LOCALHOST))
+        .build()
+        .expect("build client");
+
+    // An IPv4 literal, checked before the request is sent,
+    let url = format!("https://127.0.0.1:{}/", server.local_addr().port());
+    let Err(error) = client.get(&url).send().await else {
+        panic!("an IPv4 target from an IPv6 address");
+    };
+    assert!(
+        error
+            .to_string()
+            .contains("127.0.0.1 has no IPv6 address to reach from the local address ::1"),
+        "{}",
+        error
+    );
+    assert!(matches!(error, TraceError::Io(_)), "{:?}", error);
+
+    // and a host name with IPv4 addresses only, when it is resolved.
+    let client = common::client_for(&server)
+        .local_address(IpAddr::V6(Ipv6Addr::LOCALHOST))
+        .resolve("localhost", &[IpAddr::V4(Ipv4Addr::LOCALHOST)])
+        .build()
+        .expect("build client");
+    let Err(error) = client.get(server.url()).send().await else {
+        panic!("an IPv4 host from an IPv6 address");
+    };
+    let chain = format!("{:?}", error);
+    assert!(
+        chain.contains("localhost has no IPv6 address to reach from the local address ::1"),
+        "{}",
+        chain
+    );
+    assert!(server.observed().is_empty());
+}
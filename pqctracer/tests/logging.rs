@@ -0,0 +1,173 @@
+//! The `log` records of successful and failed traces, caught by a logger
+//! that keeps them. The logger is process-wide, so every request carries a
+//! label naming its test and each test looks only at its own records.
+
+#![cfg(feature = "log")]
+
+mod common;
+
+use std::sync::{Arc, Mutex, Once};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use pqctracer::testutil::TestTlsServer;
+use rustls::NamedGroup;
+use rustls::crypto::{aws_lc_rs, ring};
+
+/// Every record logged so far: level, target and message.
+static RECORDS: Mutex<Vec<(Level, String, String)>> = Mutex::new(Vec::new());
+
+struct CapturingLogger;
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        RECORDS.lock().unwrap().push((
+            record.level(),
+            record.target().to_string(),
+            record.args().to_string(),
+        ));
+    }
+
+    fn flush(&self) {}
+}
+
+fn install_logger() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        log::set_logger(&CapturingLogger).expect("no other logger");
+        log::set_max_level(LevelFilter::Trace);
+    });
+}
+
+/// The `pqctracer` records whose message has `pattern` in it.
+fn records(pattern: &str) -> Vec<(Level, String)> {
+    RECORDS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, target, message)| target == "pqctracer" && message.contains(pattern))
+        .map(|(level, _, message)| (*level, message.clone()))
+        .collect()
+}
+
+#[tokio::test]
+async fn a_hybrid_handshake_is_logged_at_info() {
+    install_logger();
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    client
+        .get(server.url())
+        .label("test", "hybrid")
+        .send()
+        .await
+        .expect("request");
+
+    let logged = records(" label.test=hybrid");
+    assert_eq!(logged.len(), 1, "{:?}", logged);
+    let (level, message) = &logged[0];
+    assert_eq!(*level, Level::Info);
+    assert!(
+        message.starts_with(
+            "handshake server=localhost version=TLS1.3 group=X25519MLKEM768 cipher=TLS13_"
+        ),
+        "{}",
+        message
+    );
+    assert!(message.contains(" duration="), "{}", message);
+    assert!(message.contains(" origin=fresh "), "{}", message);
+}
+
+#[tokio::test]
+async fn a_classical_group_is_a_downgrade() {
+    install_logger();
+    let server = common::start(TestTlsServer::builder().groups([NamedGroup::X25519])).await;
+    let client = common::client_for(&server).build().expect("build client");
+    client
+        .get(server.url())
+        .label("test", "downgrade")
+        .send()
+        .await
+        .expect("request");
+
+    let logged = records(" label.test=downgrade");
+    assert_eq!(logged.len(), 2, "{:?}", logged);
+    assert_eq!(logged[0].0, Level::Info);
+    assert!(logged[0].1.contains(" group=X25519 "), "{}", logged[0].1);
+    assert_eq!(
+        logged[1],
+        (
+            Level::Warn,
+            "downgrade server=localhost group=X25519 label.test=downgrade".to_string()
+        )
+    );
+}
+
+#[tokio::test]
+async fn a_failed_request_is_logged_at_warn() {
+    install_logger();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+    let url = format!(
+        "https://127.0.0.1:{}/",
+        listener.local_addr().unwrap().port()
+    );
+    drop(listener);
+    let client = common::client_builder().build().expect("build client");
+    let sent = client.get(url).label("test", "failed").send().await;
+    assert!(sent.is_err(), "nothing listens");
+
+    let logged = records(" label.test=failed");
+    assert_eq!(logged.len(), 1, "{:?}", logged);
+    let (level, message) = &logged[0];
+    assert_eq!(*level, Level::Warn);
+    assert!(
+        message.starts_with("failure server=127.0.0.1 label.test=failed error="),
+        "{}",
+        message
+    );
+    assert!(message.contains("onnect"), "{}", message);
+}
+
+#[tokio::test]
+async fn skipped_verification_is_warned_about() {
+    install_logger();
+    let server = common::start(TestTlsServer::builder()).await;
+    let url = format!("https://127.0.0.1:{}/", server.local_addr().port());
+    common::insecure_client()
+        .get(url)
+        .send()
+        .await
+        .expect("request");
+    let logged = records("verification-skipped server=127.0.0.1");
+    assert!(!logged.is_empty());
+    assert!(logged.iter().all(|(level, _)| *level == Level::Warn));
+}
+
+#[test]
+fn a_provider_without_pqc_is_warned_about_once() {
+    install_logger();
+    for provider in [aws_lc_rs::default_provider(), ring::default_provider()] {
+        pqctracer::TlsAwareClient::builder()
+            .crypto_provider(Arc::new(provider))
+            .build()
+            .expect("build client");
+    }
+    let logged = records("provider-without-pqc");
+    assert_eq!(
+        logged,
+        [(
+            Level::Warn,
+            "provider-without-pqc provider=ring".to_string()
+        )]
+    );
+
+    // Every further client without a post-quantum group is just as
+    // classical; once is enough.
+    pqctracer::TlsAwareClient::builder()
+        .crypto_provider(Arc::new(ring::default_provider()))
+        .build()
+        .expect("build client");
+    assert_eq!(records("provider-without-pqc").len(), 1);
+}
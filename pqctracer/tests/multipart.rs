@@ -0,0 +1,131 @@
+//! Multipart forms posted to a test server that checks the boundaries and
+//! reads the fields back, with the handshake captured as for any request.
+
+mod common;
+
+use pqctracer::TraceError;
+use pqctracer::multipart::{Form, Part};
+use pqctracer::testutil::{TestTlsServer, TestTlsServerBuilder};
+use reqwest::Method;
+
+/// The boundary and fields of a `multipart/form-data` request, one line
+/// each, as `headers|contents` with line breaks escaped; `None` unless every
+/// delimiter is where it belongs.
+fn parse_form(request: &str) -> Option<String> {
+    let (head, body) = request.split_once("\r\n\r\n")?;
+    let content_type = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))?
+        .1
+        .trim();
+    let boundary = content_type
+        .strip_prefix("multipart/form-data; boundary=")?
+        .to_string();
+    let delimiter = format!("--{}", boundary);
+    let fields = body.strip_suffix(&format!("{}--\r\n", delimiter))?;
+    let mut lines = Vec::new();
+    // An empty form has the closing delimiter alone.
+    if !fields.is_empty() {
+        let fields = fields
+            .strip_prefix(&format!("{}\r\n", delimiter))?
+            .strip_suffix("\r\n")?;
+        for part in fields.split(&format!("\r\n{}\r\n", delimiter)) {
+            let (headers, contents) = part.split_once("\r\n\r\n")?;
+            let contents = contents.replace("\r\n", "\\r\\n");
+            lines.push(format!("{}|{}", headers.replace("\r\n", "; "), contents));
+        }
+    }
+    Some(format!("{}\n{}", boundary, lines.join("\n")))
+}
+
+/// A server answering a form with its boundary and fields, and anything
+/// else with `400 Bad Request`.
+fn form_server() -> TestTlsServerBuilder {
+    TestTlsServer::builder().http_handler(|request| match parse_form(request) {
+        Some(fields) => format!("200 OK\r\n\r\n{}", fields),
+        None => "400 Bad Request\r\n\r\nnot a form".to_string(),
+    })
+}
+
+/// The boundary and field lines the server read from `form`.
+async fn post(form: Form) -> (String, Vec<String>) {
+    let server = common::start(form_server()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let response = client
+        .request(Method::POST, server.url())
+        .multipart(form)
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(response.response.status(), 200);
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519MLKEM768"));
+    let (text, metadata) = response.text().await.expect("body");
+    assert!(metadata.cipher.is_some());
+    common::observed(&server, 1).await;
+    let mut lines = text.lines().map(str::to_string);
+    let boundary = lines.next().expect("boundary");
+    (boundary, lines.collect())
+}
+
+#[tokio::test]
+async fn fields_and_files_arrive_intact() {
+    let file = Part::bytes("line one\r\n--not a boundary\r\nline three")
+        .file_name("notes.txt")
+        .mime_str("text/plain; charset=utf-8")
+        .expect("media type");
+    let form = Form::new()
+        .text("user", "alice")
+        .part("upload", file)
+        .part("blob", Part::bytes("raw").file_name("data.bin"))
+        .text("empty", "");
+    let (boundary, fields) = post(form).await;
+
+    assert!(boundary.starts_with("pqctracer-"), "{}", boundary);
+    assert_eq!(
+        fields,
+        [
+            "Content-Disposition: form-data; name=\"user\"|alice",
+            "Content-Disposition: form-data; name=\"upload\"; filename=\"notes.txt\"; \
+             Content-Type: text/plain; charset=utf-8|line one\\r\\n--not a boundary\\r\\nline three",
+            "Content-Disposition: form-data; name=\"blob\"; filename=\"data.bin\"; \
+             Content-Type: application/octet-stream|raw",
+            "Content-Disposition: form-data; name=\"empty\"|",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn names_are_escaped_in_their_quotes() {
+    let form = Form::new().part("say \"hi\"", Part::bytes("x").file_name("a\r\nb\".txt"));
+    let (_, fields) = post(form).await;
+    assert_eq!(
+        fields,
+        ["Content-Disposition: form-data; name=\"say %22hi%22\"; \
+          filename=\"a%0D%0Ab%22.txt\"; Content-Type: application/octet-stream|x"]
+    );
+}
+
+#[tokio::test]
+async fn an_empty_form_is_still_a_form() {
+    let (_, fields) = post(Form::new()).await;
+    assert!(fields.is_empty(), "{:?}", fields);
+}
+
+#[tokio::test]
+async fn every_request_has_a_boundary_of_its_own() {
+    let (first, _) = post(Form::new().text("n", "1")).await;
+    let (second, _) = post(Form::new().text("n", "1")).await;
+    assert_ne!(first, second);
+}
+
+#[test]
+fn media_types_need_a_type_and_subtype() {
+    for mime in ["text", "/plain", "text/", "text/plain\n"] {
+        let Err(error) = Part::text("x").mime_str(mime) else {
+            panic!("{:?} is no media type", mime);
+        };
+        assert!(matches!(error, TraceError::Config(_)), "{:?}", error);
+    }
+    assert!(Part::text("x").mime_str("image/svg+xml").is_ok());
+}
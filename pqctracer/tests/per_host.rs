@@ -0,0 +1,127 @@
+//! The per-host cap of scans, measured by proxies in front of two local
+//! servers that count the connections open at once.
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use pqctracer::scan::ScanOptions;
+use pqctracer::testutil::TestTlsServer;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Connections open at once, across every [`Proxy`] sharing it.
+#[derive(Default)]
+struct Gauge {
+    open: AtomicUsize,
+    max: AtomicUsize,
+}
+
+impl Gauge {
+    fn enter(&self) {
+        let open = self.open.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max.fetch_max(open, Ordering::SeqCst);
+    }
+
+    fn leave(&self) {
+        self.open.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn max(&self) -> usize {
+        self.max.load(Ordering::SeqCst)
+    }
+}
+
+/// Forwards connections to a server after a delay, long enough for scan
+/// workers to pile up, counting them in its own gauge and a shared one.
+struct Proxy {
+    port: u16,
+    gauge: Arc<Gauge>,
+    _server: TestTlsServer,
+}
+
+impl Proxy {
+    async fn start(all: Arc<Gauge>, started: Arc<Mutex<Vec<u16>>>) -> Self {
+        let server = common::start(TestTlsServer::builder()).await;
+        let upstream = server.local_addr();
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind proxy");
+        let port = listener.local_addr().unwrap().port();
+        let gauge = Arc::new(Gauge::default());
+        let own = gauge.clone();
+        tokio::spawn(async move {
+            while let Ok((mut client, _)) = listener.accept().await {
+                let (own, all, started) = (own.clone(), all.clone(), started.clone());
+                tokio::spawn(async move {
+                    own.enter();
+                    all.enter();
+                    started.lock().unwrap().push(port);
+                    tokio::time::sleep(Duration::from_millis(30)).await;
+                    if let Ok(mut server) = TcpStream::connect(upstream).await {
+                        let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                    }
+                    own.leave();
+                    all.leave();
+                });
+            }
+        });
+        Self {
+            port,
+            gauge,
+            _server: server,
+        }
+    }
+}
+
+/// Scan sixteen pages of a busy host, `localhost`, and four of a quiet
+/// one, `127.0.0.1`, each behind a proxy, with `max_per_host`. Returns the
+/// proxies, the gauge of both and the port of each connection in the
+/// order they came in.
+async fn scan(max_per_host: Option<usize>) -> (Proxy, Proxy, Arc<Gauge>, Vec<u16>) {
+    let all = Arc::new(Gauge::default());
+    let started = Arc::new(Mutex::new(Vec::new()));
+    let busy = Proxy::start(all.clone(), started.clone()).await;
+    let quiet = Proxy::start(all.clone(), started.clone()).await;
+    let targets: Vec<String> = (0..16)
+        .map(|i| format!("https://localhost:{}/page/{}", busy.port, i))
+        .chain((0..4).map(|i| format!("https://127.0.0.1:{}/page/{}", quiet.port, i)))
+        .collect();
+    let options = ScanOptions {
+        concurrency: 8,
+        max_per_host,
+        retries: 0,
+        ..ScanOptions::default()
+    };
+    let results = common::insecure_client().scan(targets, &options).await;
+    assert!(
+        results.iter().all(|r| r.error.is_none()),
+        "{:?}",
+        results.iter().find_map(|r| r.error.as_ref())
+    );
+    let started = started.lock().unwrap().clone();
+    (busy, quiet, all, started)
+}
+
+#[tokio::test]
+async fn no_host_gets_more_than_its_share() {
+    let (busy, quiet, all, started) = scan(Some(2)).await;
+    assert_eq!(busy.gauge.max(), 2);
+    assert_eq!(quiet.gauge.max(), 2);
+    // Both hosts are worked on at once.
+    assert_eq!(all.max(), 4);
+    // The quiet host is not stuck behind the busy one's sixteen targets.
+    let quiet_done = started.iter().rposition(|port| *port == quiet.port);
+    assert!(quiet_done.unwrap() < 10, "{:?}", started);
+}
+
+#[tokio::test]
+async fn without_a_cap_the_global_limit_applies() {
+    let (busy, _quiet, all, _) = scan(None).await;
+    assert!(busy.gauge.max() > 2, "{}", busy.gauge.max());
+    assert!(all.max() <= 8, "{}", all.max());
+}
+
+#[test]
+fn two_per_host_by_default() {
+    assert_eq!(ScanOptions::default().max_per_host, Some(2));
+}
@@ -0,0 +1,126 @@
+//! `pin_spki` against two local servers with keys of their own: the pinned
+//! key passes, the other one fails the handshake or is only reported,
+//! depending on the policy.
+
+mod common;
+
+use pqctracer::scan::{ScanOptions, ScanTarget};
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{
+    CertificateProblem, ErrorCategory, PinPolicy, Sha256Digest, TlsAwareClient, TlsMetadata,
+};
+use rustls::crypto::aws_lc_rs::cipher_suite::TLS13_AES_128_GCM_SHA256;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// The SHA-256 of the SubjectPublicKeyInfo of `server`'s certificate.
+fn spki_digest(server: &TestTlsServer) -> Sha256Digest {
+    let (_, cert) = X509Certificate::from_der(server.cert_der().as_ref()).expect("certificate");
+    let sha256 = TLS13_AES_128_GCM_SHA256
+        .tls13()
+        .unwrap()
+        .common
+        .hash_provider;
+    let hash = sha256.hash(cert.tbs_certificate.subject_pki.raw);
+    Sha256Digest(hash.as_ref().try_into().unwrap())
+}
+
+/// Two servers with keys of their own, the first of them pinned.
+async fn servers() -> (TestTlsServer, TestTlsServer) {
+    let pinned = common::start(TestTlsServer::builder()).await;
+    let other = common::start(TestTlsServer::builder()).await;
+    assert_ne!(spki_digest(&pinned), spki_digest(&other));
+    (pinned, other)
+}
+
+/// A client trusting both servers, with the first one's key pinned for
+/// `localhost`.
+fn client(pinned: &TestTlsServer, other: &TestTlsServer, policy: PinPolicy) -> TlsAwareClient {
+    common::client_for(pinned)
+        .add_root_certificate(other.cert_der().clone())
+        .pin_spki("localhost", &[spki_digest(pinned)])
+        .pin_policy(policy)
+        .build()
+        .expect("build client")
+}
+
+async fn metadata(client: &TlsAwareClient, url: String) -> TlsMetadata {
+    client.get(url).send().await.expect("request").metadata
+}
+
+#[tokio::test]
+async fn enforce_fails_an_unpinned_key() {
+    let (pinned, other) = servers().await;
+    let client = client(&pinned, &other, PinPolicy::Enforce);
+    assert_eq!(
+        metadata(&client, pinned.url()).await.pin_matched,
+        Some(true)
+    );
+
+    let options = ScanOptions {
+        retries: 0,
+        ..ScanOptions::default()
+    };
+    let mut results = client.scan([ScanTarget::from(other.url())], &options).await;
+    let error = results.remove(0).error.expect("the key is not pinned");
+    assert_eq!(
+        error.category,
+        ErrorCategory::CertificateInvalid {
+            reason: CertificateProblem::PinMismatch
+        }
+    );
+    // The message names the key the server presented.
+    let actual = format!("{:?}", spki_digest(&other));
+    assert!(error.message.contains(&actual), "{}", error.message);
+}
+
+#[tokio::test]
+async fn report_only_records_the_mismatch() {
+    let (pinned, other) = servers().await;
+    let client = client(&pinned, &other, PinPolicy::Report);
+    assert_eq!(
+        metadata(&client, pinned.url()).await.pin_matched,
+        Some(true)
+    );
+
+    let metadata = metadata(&client, other.url()).await;
+    assert_eq!(metadata.pin_matched, Some(false));
+    // The handshake went ahead and was captured as usual.
+    assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(metadata.peer_certificates[0], *other.cert_der());
+}
+
+#[tokio::test]
+async fn further_pins_for_a_host_add_up() {
+    let (pinned, other) = servers().await;
+    let client = common::client_for(&pinned)
+        .add_root_certificate(other.cert_der().clone())
+        .pin_spki("LOCALHOST", &[spki_digest(&pinned)])
+        .pin_spki("localhost", &[spki_digest(&other)])
+        .build()
+        .expect("build client");
+    for server in [&pinned, &other] {
+        assert_eq!(
+            metadata(&client, server.url()).await.pin_matched,
+            Some(true)
+        );
+    }
+}
+
+#[tokio::test]
+async fn other_hosts_are_not_checked() {
+    let (pinned, other) = servers().await;
+    for policy in [PinPolicy::Enforce, PinPolicy::Report] {
+        let client = client(&pinned, &other, policy);
+        let url = format!("https://127.0.0.1:{}/", other.local_addr().port());
+        assert_eq!(metadata(&client, url).await.pin_matched, None);
+    }
+}
+
+#[test]
+fn digests_parse_from_base64() {
+    let digest = Sha256Digest([7; 32]);
+    let encoded = digest.to_string();
+    assert_eq!(encoded.parse::<Sha256Digest>(), Ok(digest));
+    assert_eq!(format!("sha256/{}", encoded).parse(), Ok(digest));
+    assert!("AAAA".parse::<Sha256Digest>().is_err());
+}
@@ -0,0 +1,70 @@
+//! `require_pqc_capability` on a provider that can offer a post-quantum
+//! group and on one that cannot.
+
+mod common;
+
+use std::sync::Arc;
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{KxPreference, TlsAwareClient, TlsAwareClientBuilder, TraceError};
+use rustls::crypto::{CryptoProvider, aws_lc_rs, ring};
+
+fn builder(provider: CryptoProvider) -> TlsAwareClientBuilder {
+    TlsAwareClient::builder()
+        .crypto_provider(Arc::new(provider))
+        .danger_accept_invalid_certs(true)
+}
+
+#[tokio::test]
+async fn aws_lc_rs_meets_the_requirement() {
+    let client = builder(aws_lc_rs::default_provider())
+        .require_pqc_capability(true)
+        .build()
+        .expect("aws-lc-rs has ML-KEM");
+    assert!(client.client_capabilities().pqc_capable);
+
+    let server = common::start(TestTlsServer::builder()).await;
+    let response = client.get(server.url()).send().await.expect("request");
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519MLKEM768"));
+}
+
+#[test]
+fn a_classical_preference_does_not_fail_the_requirement() {
+    // It is the provider that is required to be capable, not the offer.
+    let client = builder(aws_lc_rs::default_provider())
+        .kx_preference(KxPreference::ClassicalOnly)
+        .require_pqc_capability(true)
+        .build()
+        .expect("aws-lc-rs has ML-KEM");
+    assert!(client.client_capabilities().pqc_capable);
+}
+
+#[test]
+fn ring_fails_the_requirement() {
+    let Err(error) = builder(ring::default_provider())
+        .require_pqc_capability(true)
+        .build()
+    else {
+        panic!("ring has no ML-KEM");
+    };
+    let TraceError::Config(message) = &error else {
+        panic!("{:?}", error);
+    };
+    assert_eq!(
+        message,
+        "the ring crypto provider has no post-quantum key-exchange group"
+    );
+}
+
+#[tokio::test]
+async fn ring_is_only_reported_without_the_requirement() {
+    let client = builder(ring::default_provider())
+        .build()
+        .expect("not required");
+    assert!(!client.client_capabilities().pqc_capable);
+
+    // The server offers ML-KEM; the client never could.
+    let server = common::start(TestTlsServer::builder()).await;
+    let response = client.get(server.url()).send().await.expect("request");
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519"));
+}
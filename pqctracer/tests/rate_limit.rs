@@ -0,0 +1,139 @@
+//! Scan pacing on the real clock: the rate limit covers retries, and the
+//! per-host interval spaces the requests each server sees.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use pqctracer::scan::ScanOptions;
+use pqctracer::testutil::TestTlsServer;
+use tokio::net::TcpListener;
+
+/// A listener that notes when every connection comes in and drops it,
+/// failing each attempt before its handshake.
+async fn refusing_listener() -> (u16, Arc<Mutex<Vec<Instant>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let port = listener.local_addr().unwrap().port();
+    let accepted = Arc::new(Mutex::new(Vec::new()));
+    let noted = accepted.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            noted.lock().unwrap().push(Instant::now());
+            drop(stream);
+        }
+    });
+    (port, accepted)
+}
+
+/// A server noting when every request comes in.
+async fn timed_server(requests: Arc<Mutex<Vec<Instant>>>) -> TestTlsServer {
+    common::start(TestTlsServer::builder().http_handler(move |_| {
+        requests.lock().unwrap().push(Instant::now());
+        "200 OK".to_string()
+    }))
+    .await
+}
+
+fn gaps(times: &[Instant]) -> Vec<Duration> {
+    let mut times = times.to_vec();
+    times.sort();
+    times.windows(2).map(|w| w[1] - w[0]).collect()
+}
+
+#[tokio::test]
+async fn retries_take_tokens_of_the_rate_limit() {
+    let (port, accepted) = refusing_listener().await;
+    let targets: Vec<String> = (0..2)
+        .map(|i| format!("https://127.0.0.1:{}/page/{}", port, i))
+        .collect();
+    let options = ScanOptions {
+        retries: 2,
+        rate_limit: Some(5.0),
+        max_per_host: None,
+        ..ScanOptions::default()
+    };
+    let start = Instant::now();
+    let results = common::insecure_client().scan(targets, &options).await;
+    let elapsed = start.elapsed();
+
+    for result in &results {
+        assert!(result.error.is_some(), "{:?}", result);
+        assert_eq!(result.attempts, 3);
+    }
+    let accepted = accepted.lock().unwrap().clone();
+    assert_eq!(accepted.len(), 6);
+    // The first token is there up front, the other five take 0.2 s each.
+    assert!(elapsed >= Duration::from_millis(950), "{:?}", elapsed);
+    for gap in gaps(&accepted) {
+        assert!(gap >= Duration::from_millis(180), "{:?}", gaps(&accepted));
+    }
+}
+
+#[tokio::test]
+async fn without_retries_fewer_tokens_are_taken() {
+    let (port, accepted) = refusing_listener().await;
+    let targets: Vec<String> = (0..2)
+        .map(|i| format!("https://127.0.0.1:{}/page/{}", port, i))
+        .collect();
+    let options = ScanOptions {
+        retries: 0,
+        rate_limit: Some(5.0),
+        max_per_host: None,
+        ..ScanOptions::default()
+    };
+    let start = Instant::now();
+    let results = common::insecure_client().scan(targets, &options).await;
+    assert!(results.iter().all(|r| r.attempts == 1));
+    assert_eq!(accepted.lock().unwrap().len(), 2);
+    assert!(start.elapsed() < Duration::from_millis(600));
+}
+
+#[tokio::test]
+async fn every_host_keeps_its_interval() {
+    let (first, second) = (Arc::default(), Arc::default());
+    let localhost = timed_server(Arc::clone(&first)).await;
+    let loopback = timed_server(Arc::clone(&second)).await;
+    let targets: Vec<String> = (0..4)
+        .flat_map(|i| {
+            [
+                format!(
+                    "https://localhost:{}/page/{}",
+                    localhost.local_addr().port(),
+                    i
+                ),
+                format!(
+                    "https://127.0.0.1:{}/page/{}",
+                    loopback.local_addr().port(),
+                    i
+                ),
+            ]
+        })
+        .collect();
+    let options = ScanOptions {
+        concurrency: 8,
+        max_per_host: None,
+        retries: 0,
+        per_host_interval: Some(Duration::from_millis(200)),
+        ..ScanOptions::default()
+    };
+    let start = Instant::now();
+    let results = common::insecure_client().scan(targets, &options).await;
+    let elapsed = start.elapsed();
+    assert!(
+        results.iter().all(|r| r.error.is_none()),
+        "{:?}",
+        results.iter().find_map(|r| r.error.as_ref())
+    );
+
+    for requests in [&first, &second] {
+        let requests: Vec<Instant> = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 4);
+        for gap in gaps(&requests) {
+            assert!(gap >= Duration::from_millis(180), "{:?}", gaps(&requests));
+        }
+    }
+    // The hosts are spaced each on their own, not one after the other.
+    assert!(elapsed >= Duration::from_millis(600), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(1300), "{:?}", elapsed);
+}
@@ -0,0 +1,75 @@
+//! Quantum-readiness grades of handshakes with local servers.
+
+mod common;
+
+use pqctracer::readiness::{ReadinessFactor, ReadinessGrade, ReadinessWeights};
+use pqctracer::scan::ScanOptions;
+use pqctracer::testutil::{TestTlsServer, TestTlsServerBuilder};
+use rustls::NamedGroup;
+
+/// The score, grade and per-factor points of a request to a server built
+/// from `builder`.
+async fn graded(builder: TestTlsServerBuilder) -> (u8, ReadinessGrade, Vec<Option<u8>>) {
+    let server = common::start(builder).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let readiness = client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request")
+        .readiness();
+    let points = readiness.components.iter().map(|c| c.points).collect();
+    (readiness.score, readiness.grade, points)
+}
+
+#[tokio::test]
+async fn grades_of_local_handshakes() {
+    // The test certificate has a classical ECDSA key.
+    assert_eq!(
+        graded(TestTlsServer::builder()).await,
+        (
+            80,
+            ReadinessGrade::B,
+            vec![Some(100), Some(0), Some(100), Some(100)]
+        )
+    );
+    assert_eq!(
+        graded(TestTlsServer::builder().groups([NamedGroup::X25519])).await,
+        (
+            30,
+            ReadinessGrade::D,
+            vec![Some(0), Some(0), Some(100), Some(100)]
+        )
+    );
+    // Only the certificate of a TLS 1.2 handshake is captured.
+    assert_eq!(
+        graded(TestTlsServer::builder().tls12_only(true)).await,
+        (0, ReadinessGrade::F, vec![None, Some(0), None, None])
+    );
+}
+
+#[tokio::test]
+async fn scans_grade_with_their_weights() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let options = ScanOptions {
+        readiness_weights: ReadinessWeights {
+            key_exchange: 1,
+            certificate: 0,
+            cipher: 0,
+            version: 0,
+        },
+        ..ScanOptions::default()
+    };
+    let result = client.scan([server.url()], &options).await.remove(0);
+    let readiness = result.readiness.expect("graded");
+    assert_eq!((readiness.score, readiness.grade), (100, ReadinessGrade::A));
+    let certificate = &readiness.components[1];
+    assert_eq!(certificate.factor, ReadinessFactor::Certificate);
+    assert_eq!(certificate.weight, 0);
+    assert!(
+        certificate.reason.starts_with("ECDSA"),
+        "{}",
+        certificate.reason
+    );
+}
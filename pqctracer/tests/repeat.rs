@@ -0,0 +1,225 @@
+//! `trace_repeated` behind a round-robin forwarder: two local backends
+//! that disagree on the group and certificate make the target
+//! inconsistent, one backend twice does not.
+
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use pqctracer::repeat::{RepeatOptions, RepeatReport, ValueCount};
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{ErrorCategory, TlsAwareClient, TraceError};
+use rustls::NamedGroup;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Forwards each connection to the next of `backends` in turn, returning
+/// the forwarder's address.
+async fn round_robin(backends: Vec<SocketAddr>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind forwarder");
+    let addr = listener.local_addr().unwrap();
+    let next = Arc::new(AtomicUsize::new(0));
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let backend = backends[next.fetch_add(1, Ordering::SeqCst) % backends.len()];
+            tokio::spawn(async move {
+                if let Ok(mut server) = TcpStream::connect(backend).await {
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// A hybrid backend and a classical one, with certificates of their own.
+async fn backends() -> (TestTlsServer, TestTlsServer) {
+    let hybrid = common::start(TestTlsServer::builder()).await;
+    let classical = common::start(TestTlsServer::builder().groups([NamedGroup::X25519])).await;
+    (hybrid, classical)
+}
+
+fn client(servers: &[&TestTlsServer]) -> TlsAwareClient {
+    servers
+        .iter()
+        .fold(common::client_builder(), |builder, server| {
+            builder.add_root_certificate(server.cert_der().clone())
+        })
+        .build()
+        .expect("build client")
+}
+
+async fn trace(
+    client: &TlsAwareClient,
+    forwarder: SocketAddr,
+    attempts: usize,
+    options: &RepeatOptions,
+) -> RepeatReport {
+    let url = format!("https://localhost:{}/", forwarder.port());
+    client
+        .trace_repeated(&url, attempts, options)
+        .await
+        .expect("trace")
+}
+
+fn count(value: &str, count: usize) -> ValueCount {
+    ValueCount {
+        value: value.to_string(),
+        count,
+    }
+}
+
+#[tokio::test]
+async fn disagreeing_backends_are_inconsistent() {
+    let (hybrid, classical) = backends().await;
+    let forwarder = round_robin(vec![hybrid.local_addr(), classical.local_addr()]).await;
+    let client = client(&[&hybrid, &classical]);
+    let report = trace(&client, forwarder, 4, &RepeatOptions::default()).await;
+
+    assert!(report.inconsistent);
+    assert_eq!(report.failed, 0);
+    assert_eq!(
+        report.groups,
+        [count("X25519", 2), count("X25519MLKEM768", 2)]
+    );
+    assert_eq!(report.certificates.len(), 2);
+    assert!(report.certificates.iter().all(|c| c.count == 2));
+    assert_eq!(report.remote_addrs, [count(&forwarder.to_string(), 4)]);
+
+    // One attempt after the other, each on the next backend.
+    let groups: Vec<_> = report
+        .attempts
+        .iter()
+        .map(|attempt| attempt.metadata.group.as_deref().unwrap())
+        .collect();
+    assert_eq!(
+        groups,
+        ["X25519MLKEM768", "X25519", "X25519MLKEM768", "X25519"]
+    );
+    assert_eq!(
+        report.attempts[0].certificate,
+        report.attempts[2].certificate
+    );
+    assert_ne!(
+        report.attempts[0].certificate,
+        report.attempts[1].certificate
+    );
+    // Every attempt is a handshake of its own.
+    common::observed(&hybrid, 2).await;
+    common::observed(&classical, 2).await;
+}
+
+#[tokio::test]
+async fn another_certificate_alone_is_inconsistent() {
+    let first = common::start(TestTlsServer::builder()).await;
+    let second = common::start(TestTlsServer::builder()).await;
+    let forwarder = round_robin(vec![first.local_addr(), second.local_addr()]).await;
+    let report = trace(
+        &client(&[&first, &second]),
+        forwarder,
+        2,
+        &RepeatOptions::default(),
+    )
+    .await;
+    assert_eq!(report.groups, [count("X25519MLKEM768", 2)]);
+    assert_eq!(report.certificates.len(), 2);
+    assert!(report.inconsistent);
+}
+
+#[tokio::test]
+async fn one_backend_is_consistent() {
+    let (hybrid, _) = backends().await;
+    let forwarder = round_robin(vec![hybrid.local_addr(), hybrid.local_addr()]).await;
+    let report = trace(&client(&[&hybrid]), forwarder, 3, &RepeatOptions::default()).await;
+    assert!(!report.inconsistent);
+    assert_eq!(report.groups, [count("X25519MLKEM768", 3)]);
+    assert_eq!(report.certificates.len(), 1);
+    assert_eq!(report.certificates[0].count, 3);
+    // Neither pooled nor resumed: the server saw three full handshakes.
+    let observed = common::observed(&hybrid, 3).await;
+    assert!(observed.iter().all(|handshake| handshake.error.is_none()));
+    assert!(
+        report
+            .attempts
+            .iter()
+            .all(|attempt| attempt.metadata.peer_certificates.len() == 1)
+    );
+}
+
+#[tokio::test]
+async fn a_failing_backend_is_counted_apart() {
+    let (hybrid, _) = backends().await;
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+    let closed_addr = closed.local_addr().unwrap();
+    drop(closed);
+    let forwarder = round_robin(vec![hybrid.local_addr(), closed_addr]).await;
+    let report = trace(&client(&[&hybrid]), forwarder, 4, &RepeatOptions::default()).await;
+
+    assert_eq!(report.failed, 2);
+    assert!(report.attempts[0].error.is_none());
+    let error = report.attempts[1].error.as_ref().expect("closed");
+    assert_ne!(error.category, ErrorCategory::DnsFailure);
+    // Failed attempts have no group or certificate to disagree with.
+    assert_eq!(report.groups, [count("X25519MLKEM768", 2)]);
+    assert!(!report.inconsistent);
+}
+
+#[tokio::test]
+async fn the_delay_spaces_the_attempts() {
+    let (hybrid, classical) = backends().await;
+    let forwarder = round_robin(vec![hybrid.local_addr(), classical.local_addr()]).await;
+    let options = RepeatOptions {
+        delay: Duration::from_millis(150),
+        concurrency: 1,
+    };
+    let start = Instant::now();
+    let report = trace(&client(&[&hybrid, &classical]), forwarder, 3, &options).await;
+    assert_eq!(report.attempts.len(), 3);
+    assert!(
+        start.elapsed() >= Duration::from_millis(280),
+        "{:?}",
+        start.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn attempts_may_run_concurrently() {
+    let (hybrid, classical) = backends().await;
+    let forwarder = round_robin(vec![hybrid.local_addr(), classical.local_addr()]).await;
+    let options = RepeatOptions {
+        delay: Duration::ZERO,
+        concurrency: 4,
+    };
+    let report = trace(&client(&[&hybrid, &classical]), forwarder, 8, &options).await;
+    assert_eq!(report.attempts.len(), 8);
+    assert_eq!(report.failed, 0);
+    assert_eq!(
+        report.groups,
+        [count("X25519", 4), count("X25519MLKEM768", 4)]
+    );
+    assert!(report.inconsistent);
+}
+
+#[tokio::test]
+async fn unusable_calls_fail() {
+    let client = common::insecure_client();
+    let options = RepeatOptions::default();
+    let Err(error) = client
+        .trace_repeated("https://localhost/", 0, &options)
+        .await
+    else {
+        panic!("zero attempts");
+    };
+    assert!(matches!(error, TraceError::Config(_)), "{:?}", error);
+    let Err(error) = client
+        .trace_repeated("http://localhost/", 1, &options)
+        .await
+    else {
+        panic!("not https");
+    };
+    assert!(matches!(error, TraceError::InvalidUrl(_)), "{:?}", error);
+}
@@ -0,0 +1,121 @@
+//! `CertPolicy::ReportOnly` against certificates that fail verification:
+//! the handshake goes ahead, its details are captured, and the recorded
+//! error names why the certificate would have been refused.
+
+mod common;
+
+use std::time::{Duration, SystemTime};
+
+use pqctracer::testutil::{TestTlsServer, TestTlsServerBuilder};
+use pqctracer::{CertError, CertPolicy, CertificateProblem, NameMismatch, TlsMetadata, TraceError};
+
+const DAY: Duration = Duration::from_secs(86_400);
+
+fn expired() -> TestTlsServerBuilder {
+    let now = SystemTime::now();
+    TestTlsServer::builder().cert_validity(now - 10 * DAY, now - DAY)
+}
+
+fn wrong_name() -> TestTlsServerBuilder {
+    TestTlsServer::builder().subject_alt_names(["www.example.com"])
+}
+
+/// The metadata of a request with `policy` to a server from `builder`,
+/// whose certificate the client trusts.
+async fn request(
+    builder: TestTlsServerBuilder,
+    policy: CertPolicy,
+) -> Result<TlsMetadata, TraceError> {
+    let server = common::start(builder).await;
+    let client = common::client_for(&server)
+        .certificate_policy(policy)
+        .build()
+        .expect("build client");
+    let response = client.get(server.url()).send().await?;
+    Ok(response.metadata)
+}
+
+/// The recorded verification error of a `ReportOnly` request that went
+/// ahead, having captured the handshake.
+fn recorded_error(metadata: &TlsMetadata) -> &CertError {
+    assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert!(metadata.cipher.is_some());
+    assert_eq!(metadata.peer_certificates.len(), 1);
+    assert!(metadata.is_unverified());
+    match &metadata.cert_validation {
+        Some(Err(error)) => error,
+        other => panic!("{:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn an_expired_certificate_is_recorded() {
+    let metadata = request(expired(), CertPolicy::ReportOnly)
+        .await
+        .expect("report only");
+    let error = recorded_error(&metadata);
+    assert_eq!(error.problem, CertificateProblem::Expired);
+    assert!(error.detail.contains("expired"), "{}", error.detail);
+    assert_eq!(error.name_mismatch, None);
+    // The validation report says the same.
+    let report = metadata.validation.as_ref().expect("validation report");
+    assert!(!report.is_valid());
+}
+
+#[tokio::test]
+async fn a_wrong_name_is_recorded_with_the_names() {
+    let metadata = request(wrong_name(), CertPolicy::ReportOnly)
+        .await
+        .expect("report only");
+    let error = recorded_error(&metadata);
+    assert_eq!(error.problem, CertificateProblem::NameMismatch);
+    assert_eq!(
+        error.name_mismatch,
+        Some(NameMismatch {
+            expected: "localhost".to_string(),
+            presented: vec!["www.example.com".to_string()],
+            wildcard_near_miss: None,
+        })
+    );
+}
+
+#[tokio::test]
+async fn a_valid_certificate_is_recorded_as_verified() {
+    let metadata = request(TestTlsServer::builder(), CertPolicy::ReportOnly)
+        .await
+        .expect("valid");
+    assert!(matches!(metadata.cert_validation, Some(Ok(()))));
+    assert!(!metadata.is_unverified());
+}
+
+#[tokio::test]
+async fn enforcing_refuses_both() {
+    for builder in [expired(), wrong_name()] {
+        let Err(error) = request(builder, CertPolicy::Enforce).await else {
+            panic!("an invalid certificate was accepted");
+        };
+        assert!(
+            matches!(error, TraceError::RequestFailed { .. }),
+            "{:?}",
+            error
+        );
+    }
+}
+
+#[cfg(all(feature = "serde", feature = "json"))]
+#[tokio::test]
+async fn json_marks_the_result_unverified() {
+    let metadata = request(expired(), CertPolicy::ReportOnly)
+        .await
+        .expect("report only");
+    let json = serde_json::to_value(&metadata).expect("serialize");
+    let verdict = &json["cert_validation"];
+    assert_eq!(verdict["outcome"], "unverified", "{}", verdict);
+    assert_eq!(verdict["problem"], "expired", "{}", verdict);
+
+    let metadata = request(TestTlsServer::builder(), CertPolicy::ReportOnly)
+        .await
+        .expect("valid");
+    let json = serde_json::to_value(&metadata).expect("serialize");
+    assert_eq!(json["cert_validation"]["outcome"], "verified");
+}
@@ -0,0 +1,40 @@
+//! What a traced response carries.
+
+mod common;
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{HandshakeOrigin, TlsMetadata, TlsResponse};
+use rustls::NamedGroup;
+
+#[tokio::test]
+#[allow(deprecated)]
+async fn the_fields_of_1_0_mirror_the_metadata() {
+    let server = common::start(TestTlsServer::builder().groups([NamedGroup::secp256r1])).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let traced = client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(traced.group.as_deref(), Some("secp256r1"));
+    assert_eq!(traced.group, traced.metadata.group);
+    assert_eq!(traced.cipher, traced.metadata.cipher);
+    assert!(traced.cipher.is_some());
+}
+
+#[test]
+#[allow(deprecated)]
+fn responses_are_built_with_their_metadata() {
+    let mut metadata = TlsMetadata::new(Some("X25519MLKEM768".to_string()), None);
+    metadata.origin = Some(HandshakeOrigin::Fresh);
+    let response = reqwest::Response::from(http::Response::new("body"));
+    let traced = TlsResponse::new(response, metadata);
+    assert_eq!(traced.response.status(), 200);
+    assert_eq!(traced.group.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(traced.cipher, None);
+    assert_eq!(traced.metadata.origin, Some(HandshakeOrigin::Fresh));
+    assert_eq!(
+        traced.metadata.capture_status,
+        TlsMetadata::default().capture_status
+    );
+}
@@ -0,0 +1,146 @@
+//! Resumed handshakes with and without a fresh key exchange, against local
+//! servers that resume over TLS 1.3 with `psk_dhe_ke` and over TLS 1.2.
+
+mod common;
+
+use pqctracer::resumption::ResumptionReport;
+use pqctracer::testutil::{TestTlsServer, TestTlsServerBuilder};
+use pqctracer::{ErrorCategory, HandshakeOrigin, ResumptionKx, TlsAwareClient, TlsMetadata};
+use rustls::NamedGroup;
+use rustls::client::ClientSessionMemoryCache;
+
+/// The resumption probe of a client trusting `builder`'s server.
+async fn probe(builder: TestTlsServerBuilder) -> (TestTlsServer, ResumptionReport) {
+    let server = common::start(builder).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let report = client
+        .probe_resumption("127.0.0.1", server.local_addr().port())
+        .await;
+    (server, report)
+}
+
+#[tokio::test]
+async fn tls13_resumes_with_a_fresh_key_exchange() {
+    let cases = [
+        (None, NamedGroup::X25519MLKEM768),
+        (Some(NamedGroup::secp256r1), NamedGroup::secp256r1),
+    ];
+    for (group, expected) in cases {
+        let mut builder = TestTlsServer::builder()
+            .subject_alt_names(["127.0.0.1"])
+            .tls13_tickets(1);
+        if let Some(group) = group {
+            builder = builder.groups([group]);
+        }
+        let (server, report) = probe(builder).await;
+        let ResumptionReport::Resumed { metadata } = &report else {
+            panic!("{:?}", report);
+        };
+        assert_eq!(metadata.origin, Some(HandshakeOrigin::Resumed));
+        assert_eq!(report.kx(), Some(ResumptionKx::PskWithGroup(expected)));
+        assert!(!ResumptionKx::PskWithGroup(expected).is_psk_only());
+        assert_eq!(
+            report.to_string(),
+            format!("resumed, PSK with {:?}", expected)
+        );
+        // The server agrees that the second handshake ran the key exchange.
+        let observed = common::observed(&server, 2).await;
+        assert_eq!(observed[1].selected_group, Some(expected));
+    }
+}
+
+#[tokio::test]
+async fn tls12_resumes_on_the_pre_shared_key_alone() {
+    let (_server, report) = probe(
+        TestTlsServer::builder()
+            .subject_alt_names(["127.0.0.1"])
+            .tls12_only(true),
+    )
+    .await;
+    assert!(
+        matches!(report, ResumptionReport::Resumed { .. }),
+        "{:?}",
+        report
+    );
+    assert_eq!(report.kx(), Some(ResumptionKx::PskOnly));
+    assert!(ResumptionKx::PskOnly.is_psk_only());
+    assert_eq!(report.to_string(), "resumed, PSK only");
+}
+
+#[tokio::test]
+async fn without_tickets_the_second_handshake_is_full() {
+    let (_server, report) = probe(
+        TestTlsServer::builder()
+            .subject_alt_names(["127.0.0.1"])
+            .tls13_tickets(0),
+    )
+    .await;
+    let ResumptionReport::NotResumed { metadata } = &report else {
+        panic!("{:?}", report);
+    };
+    assert_eq!(metadata.origin, Some(HandshakeOrigin::Fresh));
+    assert_eq!(metadata.resumption_kx, None);
+    assert_eq!(report.kx(), None);
+    assert_eq!(report.to_string(), "not resumed");
+}
+
+#[tokio::test]
+async fn unreachable_servers_fail_the_probe() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+    let port = listener.local_addr().expect("address").port();
+    drop(listener);
+    let report = common::insecure_client()
+        .probe_resumption("127.0.0.1", port)
+        .await;
+    assert!(
+        matches!(
+            report,
+            ResumptionReport::Failed {
+                error: ErrorCategory::ConnectRefused
+            }
+        ),
+        "{:?}",
+        report
+    );
+    assert_eq!(report.kx(), None);
+}
+
+/// The metadata of a request to `server` from `client`.
+async fn request(client: &TlsAwareClient, server: &TestTlsServer) -> TlsMetadata {
+    client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request")
+        .metadata
+}
+
+#[tokio::test]
+async fn requests_classify_the_second_resumed_connection() {
+    let cases = [
+        (
+            TestTlsServer::builder().tls13_tickets(1),
+            ResumptionKx::PskWithGroup(NamedGroup::X25519MLKEM768),
+        ),
+        (
+            TestTlsServer::builder().tls12_only(true),
+            ResumptionKx::PskOnly,
+        ),
+    ];
+    for (builder, expected) in cases {
+        let server = common::start(builder).await;
+        let client = common::client_for(&server)
+            .session_store(std::sync::Arc::new(ClientSessionMemoryCache::new(32)))
+            .build()
+            .expect("build client");
+
+        let first = request(&client, &server).await;
+        assert_eq!(first.origin, Some(HandshakeOrigin::Fresh));
+        assert_eq!(first.resumption_kx, None);
+
+        // The server closes every connection, so the second one resumes.
+        let second = request(&client, &server).await;
+        assert_eq!(second.origin, Some(HandshakeOrigin::Resumed));
+        assert_eq!(second.resumption_kx, Some(expected));
+    }
+}
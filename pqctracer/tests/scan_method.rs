@@ -0,0 +1,153 @@
+//! The scan method against a server with a large body, behind a forwarder
+//! counting what the server sends: HEAD keeps the transfer small, a server
+//! rejecting HEAD is asked again with GET when allowed, and the handshake
+//! is the same either way.
+
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use pqctracer::scan::{ScanMethod, ScanOptions, ScanResult};
+use pqctracer::testutil::{TestTlsServer, TestTlsServerBuilder};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// The size of the servers' bodies.
+const BODY: usize = 1 << 20;
+
+/// Forwards connections to `backend`, counting the bytes it sends back.
+async fn counting_forwarder(backend: SocketAddr) -> (SocketAddr, Arc<AtomicU64>) {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind forwarder");
+    let addr = listener.local_addr().unwrap();
+    let received = Arc::new(AtomicU64::default());
+    let counter = received.clone();
+    tokio::spawn(async move {
+        while let Ok((client, _)) = listener.accept().await {
+            let counter = counter.clone();
+            tokio::spawn(async move {
+                let Ok(server) = TcpStream::connect(backend).await else {
+                    return;
+                };
+                let (mut client_read, mut client_write) = client.into_split();
+                let (mut server_read, mut server_write) = server.into_split();
+                tokio::spawn(async move {
+                    let _ = tokio::io::copy(&mut client_read, &mut server_write).await;
+                    let _ = server_write.shutdown().await;
+                });
+                let mut buf = vec![0; 16 << 10];
+                while let Ok(read) = server_read.read(&mut buf).await {
+                    counter.fetch_add(read as u64, Ordering::SeqCst);
+                    if read == 0 || client_write.write_all(&buf[..read]).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    (addr, received)
+}
+
+/// A server answering every request with a megabyte, or HEAD requests
+/// with `405 Method Not Allowed` when it `rejects_head`.
+fn large_body_server(rejects_head: bool) -> TestTlsServerBuilder {
+    let body = "b".repeat(BODY);
+    TestTlsServer::builder().http_handler(move |request| {
+        if rejects_head && request.starts_with("HEAD ") {
+            "405 Method Not Allowed\r\nAllow: GET".to_string()
+        } else {
+            format!("200 OK\r\n\r\n{}", body)
+        }
+    })
+}
+
+/// Scan `server` through a counting forwarder, returning the result and
+/// how many bytes the server sent.
+async fn scan(server: &TestTlsServer, options: &ScanOptions) -> (ScanResult, u64) {
+    let (forwarder, received) = counting_forwarder(server.local_addr()).await;
+    let client = common::client_for(server).build().expect("build client");
+    let url = format!("https://localhost:{}/", forwarder.port());
+    let mut results = client.scan([url], options).await;
+    (results.remove(0), received.load(Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn head_requests_transfer_little_beyond_the_handshake() {
+    let server = common::start(large_body_server(false)).await;
+    let (result, received) = scan(&server, &ScanOptions::default()).await;
+    assert_eq!(result.status, Some(200));
+    assert_eq!(result.method, Some(ScanMethod::Head));
+    assert_eq!(result.attempts, 1);
+    assert!(result.error.is_none(), "{:?}", result.error);
+    assert!(received < 16 << 10, "{} bytes", received);
+    common::observed(&server, 1).await;
+}
+
+#[tokio::test]
+async fn a_server_rejecting_head_is_asked_with_get() {
+    let server = common::start(large_body_server(true)).await;
+    let options = ScanOptions {
+        fallback_get: true,
+        ..ScanOptions::default()
+    };
+    let (result, _) = scan(&server, &options).await;
+    assert_eq!(result.status, Some(200));
+    assert_eq!(result.method, Some(ScanMethod::Get));
+    assert_eq!(result.attempts, 2);
+    assert!(result.error.is_none(), "{:?}", result.error);
+    assert_eq!(result.metadata.group.as_deref(), Some("X25519MLKEM768"));
+    // Each request came on a connection of its own.
+    common::observed(&server, 2).await;
+}
+
+#[tokio::test]
+async fn without_the_fallback_the_rejection_is_the_result() {
+    let server = common::start(large_body_server(true)).await;
+    let (result, received) = scan(&server, &ScanOptions::default()).await;
+    assert_eq!(result.status, Some(405));
+    assert_eq!(result.method, Some(ScanMethod::Head));
+    assert_eq!(result.attempts, 1);
+    assert!(result.error.is_some());
+    assert!(received < 16 << 10, "{} bytes", received);
+    // The handshake is reported all the same.
+    assert_eq!(result.metadata.group.as_deref(), Some("X25519MLKEM768"));
+}
+
+#[tokio::test]
+async fn the_handshake_is_the_same_whatever_the_method() {
+    let server = common::start(large_body_server(false)).await;
+    let (head, _) = scan(&server, &ScanOptions::default()).await;
+    let get_options = ScanOptions {
+        method: ScanMethod::Get,
+        ..ScanOptions::default()
+    };
+    let (get, _) = scan(&server, &get_options).await;
+    assert_eq!(get.method, Some(ScanMethod::Get));
+    assert_eq!(get.status, Some(200));
+
+    assert_eq!(head.metadata.group, get.metadata.group);
+    assert_eq!(head.metadata.cipher, get.metadata.cipher);
+    assert_eq!(head.metadata.origin, get.metadata.origin);
+    assert_eq!(
+        head.metadata.peer_certificates,
+        get.metadata.peer_certificates
+    );
+    let observed = common::observed(&server, 2).await;
+    assert_eq!(observed[0].offered_groups, observed[1].offered_groups);
+    assert_eq!(observed[0].key_shares, observed[1].key_shares);
+}
+
+#[test]
+fn methods_parse_and_display() {
+    assert_eq!("head".parse::<ScanMethod>(), Ok(ScanMethod::Head));
+    assert_eq!("GET".parse::<ScanMethod>(), Ok(ScanMethod::Get));
+    assert_eq!(ScanMethod::Head.to_string(), "HEAD");
+    assert_eq!(ScanMethod::default(), ScanMethod::Head);
+    assert_eq!(
+        "POST".parse::<ScanMethod>(),
+        Err("unsupported scan method: POST".to_string())
+    );
+}
@@ -0,0 +1,135 @@
+//! The summary of a scan over reachable and failing local targets.
+
+mod common;
+
+use std::time::Duration;
+
+use pqctracer::scan::{ErrorCount, GroupCount, ScanOptions, ScanResult, ScanSummary};
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{ErrorCategory, KeyExchangeKind};
+use rustls::NamedGroup;
+
+#[tokio::test]
+async fn errors_count_but_do_not_drag_the_durations() {
+    let hybrid = common::start(TestTlsServer::builder()).await;
+    let hybrid_too = common::start(TestTlsServer::builder()).await;
+    let x25519 = common::start(TestTlsServer::builder().groups([NamedGroup::X25519])).await;
+    let p256 = common::start(TestTlsServer::builder().groups([NamedGroup::secp256r1])).await;
+    let unavailable = common::start(
+        TestTlsServer::builder().http_handler(|_| "503 Service Unavailable".to_string()),
+    )
+    .await;
+    let targets = vec![
+        hybrid.url(),
+        hybrid_too.url(),
+        x25519.url(),
+        p256.url(),
+        unavailable.url(),
+        // Nothing listens on port 1.
+        "https://127.0.0.1:1".to_string(),
+    ];
+    let options = ScanOptions {
+        retries: 0,
+        ..ScanOptions::default()
+    };
+    // Handshake durations of requests need timings.
+    let client = common::client_builder()
+        .danger_accept_invalid_certs(true)
+        .collect_timings(true)
+        .build()
+        .expect("build client");
+    let results = client.scan(targets, &options).await;
+    let summary = ScanSummary::from_results(&results);
+
+    assert_eq!(
+        (summary.total, summary.reachable, summary.failed),
+        (6, 5, 2)
+    );
+    assert_eq!(
+        summary.key_exchange.into_iter().collect::<Vec<_>>(),
+        [
+            (KeyExchangeKind::Classical, 2),
+            (KeyExchangeKind::Hybrid, 3)
+        ]
+    );
+    assert_eq!(summary.key_exchange_unknown, 0);
+    let group = |group: &str, count| GroupCount {
+        group: group.to_string(),
+        count,
+    };
+    assert_eq!(
+        summary.groups,
+        [
+            group("X25519MLKEM768", 3),
+            group("X25519", 1),
+            group("secp256r1", 1)
+        ]
+    );
+    assert_eq!(
+        summary.errors,
+        [
+            ErrorCount {
+                category: ErrorCategory::ConnectRefused,
+                count: 1
+            },
+            ErrorCount {
+                category: ErrorCategory::HttpError { status: 503 },
+                count: 1
+            },
+        ]
+    );
+
+    // The refused target has no response and is left out; the 503 one
+    // answered and counts.
+    let reached: Vec<Duration> = results
+        .iter()
+        .filter(|r| r.reached())
+        .map(|r| r.duration)
+        .collect();
+    let durations = summary.durations.expect("durations");
+    assert_eq!(durations.count, 5);
+    assert_eq!(durations.min, *reached.iter().min().unwrap());
+    assert_eq!(durations.max, *reached.iter().max().unwrap());
+    assert!(durations.min <= durations.median && durations.median <= durations.p95);
+
+    // Per kind, failed targets are counted apart from the durations.
+    let hybrid = &summary.latency[&KeyExchangeKind::Hybrid];
+    assert_eq!(hybrid.failed, 1);
+    assert_eq!(hybrid.total.as_ref().map(|h| h.count), Some(2));
+    assert_eq!(hybrid.handshake.as_ref().map(|h| h.count), Some(2));
+    let classical = &summary.latency[&KeyExchangeKind::Classical];
+    assert_eq!(classical.failed, 0);
+    assert_eq!(classical.total.as_ref().map(|h| h.count), Some(2));
+    // The client sends no P-256 key share up front.
+    assert_eq!(summary.hello_retries, 1);
+    assert_eq!(summary.hrr_penalties.map(|stats| stats.count), Some(1));
+    assert_eq!(summary.grades.values().sum::<usize>(), 5);
+}
+
+#[tokio::test]
+async fn a_scan_that_reaches_nothing_has_no_durations() {
+    let options = ScanOptions {
+        retries: 0,
+        ..ScanOptions::default()
+    };
+    let targets = ["https://127.0.0.1:1", "https://127.0.0.1:2"];
+    let results: Vec<ScanResult> = common::insecure_client().scan(targets, &options).await;
+    let summary = ScanSummary::from_results(&results);
+    assert_eq!(
+        (summary.total, summary.reachable, summary.failed),
+        (2, 0, 2)
+    );
+    assert_eq!(summary.durations, None);
+    assert!(summary.latency.is_empty());
+    assert!(summary.groups.is_empty());
+    assert_eq!(
+        summary.errors,
+        [ErrorCount {
+            category: ErrorCategory::ConnectRefused,
+            count: 2
+        }]
+    );
+
+    let empty = ScanSummary::from_results(&[]);
+    assert_eq!((empty.total, empty.reachable, empty.failed), (0, 0, 0));
+}
@@ -0,0 +1,171 @@
+//! The test server itself: the loopback self-test of `serve-test`, from
+//! what the client offers to what the server picks.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use pqctracer::testutil::{TestTlsServer, kx_group_by_name};
+use pqctracer::{CaptureStatus, KeyExchangeKind, KxPreference};
+use rustls::{NamedGroup, ProtocolVersion};
+
+#[tokio::test]
+async fn the_client_offers_hybrids_and_the_server_picks_one() {
+    let server = common::start(
+        TestTlsServer::builder().groups([NamedGroup::X25519MLKEM768, NamedGroup::X25519]),
+    )
+    .await;
+    let client = common::client_for(&server).build().expect("build client");
+    let traced = client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(traced.metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(
+        traced.metadata.key_exchange_kind(),
+        Some(KeyExchangeKind::of(NamedGroup::X25519MLKEM768))
+    );
+
+    let observed = server.observed();
+    assert_eq!(observed.len(), 1);
+    let hello = &observed[0];
+    assert_eq!(
+        hello.offered_groups.first(),
+        Some(&NamedGroup::X25519MLKEM768)
+    );
+    assert!(hello.key_shares.contains(&NamedGroup::X25519MLKEM768));
+    assert_eq!(hello.selected_group, Some(NamedGroup::X25519MLKEM768));
+    assert_eq!(hello.version, Some(ProtocolVersion::TLSv1_3));
+    assert!(!hello.hello_retry);
+    assert_eq!(hello.error, None);
+    assert!(
+        hello.to_string().contains("selected=X25519MLKEM768"),
+        "{}",
+        hello
+    );
+}
+
+#[tokio::test]
+async fn a_classical_client_shows_up_as_such() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server)
+        .kx_preference(KxPreference::ClassicalOnly)
+        .build()
+        .expect("build client");
+    let traced = client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(traced.metadata.group.as_deref(), Some("X25519"));
+
+    let hello = &server.observed()[0];
+    assert!(
+        !hello.offered_groups.contains(&NamedGroup::X25519MLKEM768),
+        "{:?}",
+        hello.offered_groups
+    );
+    assert_eq!(hello.selected_group, Some(NamedGroup::X25519));
+}
+
+#[tokio::test]
+async fn forced_hello_retries_pick_a_group_without_a_key_share() {
+    let server = common::start(
+        TestTlsServer::builder()
+            .groups([
+                NamedGroup::X25519MLKEM768,
+                NamedGroup::X25519,
+                NamedGroup::secp256r1,
+            ])
+            .force_hello_retry(true),
+    )
+    .await;
+    let client = common::client_for(&server).build().expect("build client");
+    let traced = client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request");
+
+    let hello = &server.observed()[0];
+    assert!(hello.hello_retry);
+    assert!(!hello.key_shares.contains(&NamedGroup::secp256r1));
+    assert_eq!(hello.selected_group, Some(NamedGroup::secp256r1));
+    assert_eq!(traced.metadata.group.as_deref(), Some("secp256r1"));
+    assert!(
+        hello.to_string().contains("(HelloRetryRequest)"),
+        "{}",
+        hello
+    );
+}
+
+#[tokio::test]
+async fn tls12_only_servers_negotiate_tls12() {
+    let server = common::start(TestTlsServer::builder().tls12_only(true)).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let traced = client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request");
+    // rustls keeps no TLS 1.2 session data for requests to report from.
+    assert_eq!(traced.metadata.capture_status, CaptureStatus::Tls12NoData);
+    assert!(!traced.metadata.peer_certificates.is_empty());
+
+    let hello = &server.observed()[0];
+    assert_eq!(hello.version, Some(ProtocolVersion::TLSv1_2));
+    // No hybrid group exists for TLS 1.2.
+    assert!(
+        hello.offered_groups.contains(&NamedGroup::X25519MLKEM768),
+        "{:?}",
+        hello.offered_groups
+    );
+    assert_ne!(hello.selected_group, Some(NamedGroup::X25519MLKEM768));
+}
+
+#[tokio::test]
+async fn failed_handshakes_are_logged_too() {
+    let handshakes = Arc::new(Mutex::new(Vec::new()));
+    let seen = handshakes.clone();
+    let server = common::start(
+        TestTlsServer::builder().on_handshake(move |h| seen.lock().unwrap().push(h.selected_group)),
+    )
+    .await;
+    // The bundled roots do not trust the self-signed certificate.
+    let client = common::client_builder().build().expect("build client");
+    let sent = client.get(server.url().as_str()).send().await;
+    assert!(sent.is_err(), "the certificate is not trusted");
+
+    // The server notes the failure once it has seen the client's alert,
+    // which may be after the client gave up.
+    let mut observed = server.observed();
+    for _ in 0..100 {
+        if !observed.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        observed = server.observed();
+    }
+    assert_eq!(observed.len(), 1);
+    assert!(observed[0].error.is_some(), "{}", observed[0]);
+    assert!(
+        observed[0]
+            .offered_groups
+            .contains(&NamedGroup::X25519MLKEM768),
+        "{:?}",
+        observed[0].offered_groups
+    );
+    assert_eq!(handshakes.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn groups_are_looked_up_by_name() {
+    assert_eq!(
+        kx_group_by_name("x25519mlkem768"),
+        Some(NamedGroup::X25519MLKEM768)
+    );
+    assert_eq!(kx_group_by_name(" SECP256R1 "), Some(NamedGroup::secp256r1));
+    assert_eq!(kx_group_by_name("x448"), None);
+}
@@ -0,0 +1,104 @@
+//! The ServerHello extensions `deep_inspect` reports for the handshakes the
+//! tracer drives itself, and their absence everywhere else.
+
+mod common;
+
+use pqctracer::scan::{ScanOptions, ScanTarget};
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{ExtensionInfo, TlsAwareClient, TlsMetadata};
+use rustls::NamedGroup;
+
+fn deep_client(server: &TestTlsServer) -> TlsAwareClient {
+    common::client_for(server)
+        .deep_inspect(true)
+        .build()
+        .expect("build client")
+}
+
+/// A scan of `server`'s address, a handshake the tracer drives itself.
+async fn handshake(client: &TlsAwareClient, server: &TestTlsServer) -> TlsMetadata {
+    let target = ScanTarget::Address {
+        addr: server.local_addr(),
+        server_name: "localhost".to_string(),
+    };
+    let mut results = client.scan([target], &ScanOptions::default()).await;
+    let result = results.remove(0);
+    assert!(result.error.is_none(), "{:?}", result.error);
+    result.metadata
+}
+
+fn extension<'a>(extensions: &'a [ExtensionInfo], name: &str) -> Option<&'a ExtensionInfo> {
+    extensions.iter().find(|extension| extension.name == name)
+}
+
+#[tokio::test]
+async fn a_tls13_server_hello_has_key_share_and_supported_versions() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let metadata = handshake(&deep_client(&server), &server).await;
+    let extensions = metadata.server_extensions.expect("deep inspection");
+
+    let key_share = extension(&extensions, "key_share").expect("key_share");
+    assert_eq!(key_share.id, 51);
+    assert_eq!(key_share.detail.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(key_share.to_string(), "key_share (X25519MLKEM768)");
+    let versions = extension(&extensions, "supported_versions").expect("supported_versions");
+    assert_eq!(versions.id, 43);
+    assert_eq!(versions.detail.as_deref(), Some("TLS1.3"));
+    // The rest of what the server sends is encrypted.
+    assert!(extension(&extensions, "application_layer_protocol_negotiation").is_none());
+    assert!(extension(&extensions, "early_data").is_none());
+}
+
+#[tokio::test]
+async fn the_key_share_names_the_selected_group() {
+    let server = common::start(TestTlsServer::builder().groups([NamedGroup::X25519])).await;
+    let metadata = handshake(&deep_client(&server), &server).await;
+    let extensions = metadata.server_extensions.expect("deep inspection");
+    let key_share = extension(&extensions, "key_share").expect("key_share");
+    assert_eq!(key_share.detail.as_deref(), Some("X25519"));
+    assert_eq!(metadata.group.as_deref(), Some("X25519"));
+}
+
+#[tokio::test]
+async fn a_hello_retry_is_replaced_by_the_server_hello() {
+    let server = common::start(
+        TestTlsServer::builder()
+            .groups([NamedGroup::secp256r1])
+            .force_hello_retry(true),
+    )
+    .await;
+    let metadata = handshake(&deep_client(&server), &server).await;
+    let extensions = metadata.server_extensions.expect("deep inspection");
+    let key_share = extension(&extensions, "key_share").expect("key_share");
+    assert_eq!(key_share.detail.as_deref(), Some("secp256r1"));
+}
+
+#[tokio::test]
+async fn a_tls12_server_hello_has_neither() {
+    let server = common::start(TestTlsServer::builder().tls12_only(true)).await;
+    let metadata = handshake(&deep_client(&server), &server).await;
+    let extensions = metadata.server_extensions.expect("deep inspection");
+    assert!(extension(&extensions, "key_share").is_none());
+    assert!(extension(&extensions, "supported_versions").is_none());
+    assert!(!extensions.is_empty());
+}
+
+#[tokio::test]
+async fn off_by_default() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    assert_eq!(handshake(&client, &server).await.server_extensions, None);
+}
+
+#[tokio::test]
+async fn requests_do_not_see_extensions() {
+    // reqwest reads the connection, so the ServerHello goes unparsed.
+    let server = common::start(TestTlsServer::builder()).await;
+    let response = deep_client(&server)
+        .get(server.url())
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(response.metadata.server_extensions, None);
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519MLKEM768"));
+}
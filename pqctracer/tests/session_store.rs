@@ -0,0 +1,210 @@
+//! A session store of the application's own behind the tracer's: every
+//! callback reaches it, and the capture is the same as without it.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{CaptureStatus, HandshakeOrigin, TlsAwareClient, TlsMetadata};
+use rustls::NamedGroup;
+use rustls::client::{
+    ClientSessionMemoryCache, ClientSessionStore, Tls12ClientSessionValue, Tls13ClientSessionValue,
+};
+use rustls_pki_types::ServerName;
+
+/// A memory cache that records each call, and whether lookups found
+/// anything.
+#[derive(Debug)]
+struct Recording {
+    inner: ClientSessionMemoryCache,
+    calls: Mutex<Vec<String>>,
+}
+
+impl Recording {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: ClientSessionMemoryCache::new(32),
+            calls: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn record(&self, call: &str) {
+        self.calls.lock().unwrap().push(call.to_string());
+    }
+
+    fn lookup<T>(&self, call: &str, found: Option<T>) -> Option<T> {
+        let outcome = if found.is_some() { "found" } else { "none" };
+        self.record(&format!("{} {}", call, outcome));
+        found
+    }
+
+    /// The calls so far, emptying the record.
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.calls.lock().unwrap())
+    }
+}
+
+impl ClientSessionStore for Recording {
+    fn set_kx_hint(&self, server_name: ServerName<'static>, group: NamedGroup) {
+        self.record(&format!("set_kx_hint {:?}", group));
+        self.inner.set_kx_hint(server_name, group);
+    }
+
+    fn kx_hint(&self, server_name: &ServerName<'_>) -> Option<NamedGroup> {
+        self.lookup("kx_hint", self.inner.kx_hint(server_name))
+    }
+
+    fn set_tls12_session(&self, server_name: ServerName<'static>, value: Tls12ClientSessionValue) {
+        self.record("set_tls12_session");
+        self.inner.set_tls12_session(server_name, value);
+    }
+
+    fn tls12_session(&self, server_name: &ServerName<'_>) -> Option<Tls12ClientSessionValue> {
+        self.lookup("tls12_session", self.inner.tls12_session(server_name))
+    }
+
+    fn remove_tls12_session(&self, server_name: &ServerName<'static>) {
+        self.record("remove_tls12_session");
+        self.inner.remove_tls12_session(server_name);
+    }
+
+    fn insert_tls13_ticket(
+        &self,
+        server_name: ServerName<'static>,
+        value: Tls13ClientSessionValue,
+    ) {
+        self.record("insert_tls13_ticket");
+        self.inner.insert_tls13_ticket(server_name, value);
+    }
+
+    fn take_tls13_ticket(
+        &self,
+        server_name: &ServerName<'static>,
+    ) -> Option<Tls13ClientSessionValue> {
+        self.lookup(
+            "take_tls13_ticket",
+            self.inner.take_tls13_ticket(server_name),
+        )
+    }
+}
+
+/// A client for `server` with `store` behind the tracer's if given.
+fn client(server: &TestTlsServer, store: Option<Arc<Recording>>) -> TlsAwareClient {
+    let mut builder = common::client_for(server);
+    if let Some(store) = store {
+        builder = builder.session_store(store);
+    }
+    builder.build().expect("build client")
+}
+
+/// The metadata of a request to `server`, which closes every connection
+/// after its response.
+async fn request(client: &TlsAwareClient, server: &TestTlsServer) -> TlsMetadata {
+    client
+        .get(server.url().as_str())
+        .send()
+        .await
+        .expect("request")
+        .metadata
+}
+
+/// What the capture of `metadata` is made of.
+fn captured(metadata: &TlsMetadata) -> (Option<String>, Option<String>, CaptureStatus, usize) {
+    (
+        metadata.group.clone(),
+        metadata.cipher.clone(),
+        metadata.capture_status,
+        metadata.peer_certificates.len(),
+    )
+}
+
+#[tokio::test]
+async fn tls13_callbacks_reach_the_inner_store() {
+    let server = common::start(TestTlsServer::builder().tls13_tickets(1)).await;
+    let store = Recording::new();
+    let client = client(&server, Some(store.clone()));
+
+    let first = request(&client, &server).await;
+    assert_eq!(first.origin, Some(HandshakeOrigin::Fresh));
+    assert_eq!(
+        common::observed(&server, 1).await[0].selected_group,
+        Some(NamedGroup::X25519MLKEM768)
+    );
+    let calls = store.take();
+    for call in [
+        "take_tls13_ticket none",
+        "tls12_session none",
+        "kx_hint none",
+        "set_kx_hint X25519MLKEM768",
+        "remove_tls12_session",
+        "insert_tls13_ticket",
+    ] {
+        assert!(
+            calls.iter().any(|c| c == call),
+            "{} not in {:?}",
+            call,
+            calls
+        );
+    }
+    assert_eq!(
+        captured(&first),
+        captured(&request(&self::client(&server, None), &server).await)
+    );
+
+    // The next connection resumes with the ticket the inner store kept.
+    let second = request(&client, &server).await;
+    assert_eq!(second.origin, Some(HandshakeOrigin::Resumed));
+    let calls = store.take();
+    assert!(
+        calls.iter().any(|c| c == "take_tls13_ticket found"),
+        "{:?}",
+        calls
+    );
+    assert!(calls.iter().any(|c| c == "kx_hint found"), "{:?}", calls);
+    assert_eq!(second.group.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(second.cipher, first.cipher);
+}
+
+#[tokio::test]
+async fn tls12_callbacks_reach_the_inner_store() {
+    let server = common::start(TestTlsServer::builder().tls12_only(true)).await;
+    let store = Recording::new();
+    let client = client(&server, Some(store.clone()));
+
+    let first = request(&client, &server).await;
+    let calls = store.take();
+    assert!(
+        calls.iter().any(|c| c == "set_tls12_session"),
+        "{:?}",
+        calls
+    );
+    assert_eq!(first.capture_status, CaptureStatus::Tls12NoData);
+    assert_eq!(
+        captured(&first),
+        captured(&request(&self::client(&server, None), &server).await)
+    );
+
+    // Resuming goes without a certificate as well as without a group.
+    let second = request(&client, &server).await;
+    assert_eq!(second.origin, Some(HandshakeOrigin::Resumed));
+    assert_eq!(second.capture_status, CaptureStatus::Tls12NoData);
+    assert!(second.peer_certificates.is_empty());
+    let calls = store.take();
+    assert!(
+        calls.iter().any(|c| c == "tls12_session found"),
+        "{:?}",
+        calls
+    );
+}
+
+#[tokio::test]
+async fn without_an_inner_store_nothing_resumes() {
+    let server = common::start(TestTlsServer::builder().tls13_tickets(1)).await;
+    let client = client(&server, None);
+    for _ in 0..2 {
+        let metadata = request(&client, &server).await;
+        assert_eq!(metadata.origin, Some(HandshakeOrigin::Fresh));
+        assert_eq!(metadata.capture_status, CaptureStatus::Captured);
+    }
+}
@@ -0,0 +1,108 @@
+//! Requests over a keep-alive connection that an earlier request set up
+//! report that connection's handshake, marked as shared.
+
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures_util::future::join_all;
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{HandshakeOrigin, TlsMetadata};
+
+/// What a shared connection's requests must agree on.
+fn handshake(metadata: &TlsMetadata) -> (Option<String>, Option<String>, usize) {
+    (
+        metadata.group.clone(),
+        metadata.cipher.clone(),
+        metadata.peer_certificates.len(),
+    )
+}
+
+#[tokio::test]
+async fn requests_on_one_connection_report_its_handshake() {
+    let server = common::start(TestTlsServer::builder().keep_alive(true)).await;
+    let client = common::client_for(&server).build().expect("build client");
+
+    let mut responses = Vec::new();
+    for i in 0..20 {
+        let url = format!("{}/{}", server.url(), i);
+        responses.push(
+            client
+                .get(url.as_str())
+                .send()
+                .await
+                .expect("request")
+                .metadata,
+        );
+    }
+    assert_eq!(common::observed(&server, 1).await.len(), 1);
+
+    let first = &responses[0];
+    assert_eq!(first.origin, Some(HandshakeOrigin::Fresh));
+    assert_eq!(first.group.as_deref(), Some("X25519MLKEM768"));
+    assert_eq!(first.peer_certificates.first(), Some(server.cert_der()));
+    for metadata in &responses[1..] {
+        assert_eq!(metadata.origin, Some(HandshakeOrigin::SharedConnection));
+        assert_eq!(handshake(metadata), handshake(first));
+        assert_eq!(metadata.remote_addr, first.remote_addr);
+        assert_eq!(metadata.local_addr, first.local_addr);
+        assert_eq!(metadata.handshake_duration, None);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_requests_report_the_handshake_of_their_connection() {
+    let server = common::start(TestTlsServer::builder().keep_alive(true)).await;
+    let client = Arc::new(common::client_for(&server).build().expect("build client"));
+
+    // HTTP/1.1 runs one request per connection at a time, so the first
+    // round opens a connection per request and the next ones share them.
+    let mut responses = Vec::new();
+    for _ in 0..3 {
+        let round = (0..20).map(|i| {
+            let client = client.clone();
+            let url = format!("{}/{}", server.url(), i);
+            async move {
+                client
+                    .get(url.as_str())
+                    .send()
+                    .await
+                    .expect("request")
+                    .metadata
+            }
+        });
+        responses.extend(join_all(round).await);
+    }
+
+    // Each connection reports its handshake as fresh exactly once, to the
+    // first request served over it, and as shared to every later one.
+    let mut connections: HashMap<_, Vec<&TlsMetadata>> = HashMap::new();
+    for metadata in &responses {
+        let local_addr = metadata.local_addr.expect("local address");
+        connections.entry(local_addr).or_default().push(metadata);
+    }
+    for served in connections.values() {
+        let fresh: Vec<_> = served
+            .iter()
+            .filter(|m| m.origin == Some(HandshakeOrigin::Fresh))
+            .collect();
+        assert_eq!(fresh.len(), 1, "{:?}", served);
+        for metadata in served {
+            assert_eq!(handshake(metadata), handshake(fresh[0]));
+            assert_eq!(metadata.remote_addr, fresh[0].remote_addr);
+            if metadata.origin != Some(HandshakeOrigin::Fresh) {
+                assert_eq!(metadata.origin, Some(HandshakeOrigin::SharedConnection));
+            }
+        }
+    }
+    assert!(connections.len() < 60, "no connection was shared");
+    // hyper may also have opened connections no request ended up on.
+    let peers: Vec<_> = server.observed().iter().map(|h| h.peer).collect();
+    for local_addr in connections.keys() {
+        assert!(peers.contains(local_addr), "{} not observed", local_addr);
+    }
+    for metadata in &responses {
+        assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+    }
+}
@@ -0,0 +1,184 @@
+//! STARTTLS probes against in-process mail servers: the test server's
+//! preamble for upgrades that work, plaintext stubs for the ones that don't.
+
+mod common;
+
+use pqctracer::TraceError;
+use pqctracer::starttls::Protocol;
+use pqctracer::testutil::TestTlsServer;
+use rustls::NamedGroup;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+#[tokio::test]
+async fn every_protocol_upgrades_and_reports_the_handshake() {
+    for protocol in [Protocol::Smtp, Protocol::Imap, Protocol::Pop3] {
+        let server = common::start(
+            TestTlsServer::builder()
+                .starttls(protocol)
+                .groups([NamedGroup::X25519MLKEM768]),
+        )
+        .await;
+        let client = common::client_for(&server).build().expect("build client");
+        let metadata = client
+            .probe_starttls("localhost", server.local_addr().port(), protocol)
+            .await
+            .unwrap_or_else(|e| panic!("{}: {}", protocol, e));
+        assert_eq!(
+            metadata.group.as_deref(),
+            Some("X25519MLKEM768"),
+            "{}",
+            protocol
+        );
+        assert_eq!(metadata.protocol_version(), Some("TLS1.3"), "{}", protocol);
+        assert!(metadata.handshake_bytes_sent.is_some_and(|n| n > 0));
+        assert_eq!(metadata.cert_validation, Some(Ok(())));
+        assert_eq!(metadata.peer_certificates.first(), Some(server.cert_der()));
+
+        let observed = common::observed(&server, 1).await;
+        assert_eq!(observed[0].selected_group, Some(NamedGroup::X25519MLKEM768));
+    }
+}
+
+/// A plaintext server that sends `greeting`, answers every line it reads
+/// with `reply` and hangs up when that has nothing to say; returns its port.
+async fn stub(greeting: &'static str, reply: fn(&str) -> Option<&'static str>) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind stub");
+    let port = listener.local_addr().expect("stub address").port();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.expect("accept");
+        let mut stream = BufReader::new(stream);
+        stream.get_mut().write_all(greeting.as_bytes()).await.ok();
+        let mut line = String::new();
+        while stream.read_line(&mut line).await.is_ok_and(|n| n > 0) {
+            let Some(answer) = reply(line.trim_end()) else {
+                break;
+            };
+            stream.get_mut().write_all(answer.as_bytes()).await.ok();
+            line.clear();
+        }
+    });
+    port
+}
+
+async fn probe(port: u16, protocol: Protocol) -> Result<pqctracer::TlsMetadata, TraceError> {
+    common::insecure_client()
+        .probe_starttls("127.0.0.1", port, protocol)
+        .await
+}
+
+#[tokio::test]
+async fn servers_that_do_not_advertise_starttls_are_told() {
+    let smtp = stub("220 mx ESMTP\r\n", |line| match line {
+        "EHLO pqctracer.invalid" => Some("250-mx\r\n250-STARTTLSX\r\n250 SIZE 1000\r\n"),
+        _ => None,
+    })
+    .await;
+    let imap = stub("* OK ready\r\n", |line| match line {
+        "a1 CAPABILITY" => Some("* CAPABILITY IMAP4rev1 LOGINDISABLED\r\na1 OK done\r\n"),
+        _ => None,
+    })
+    .await;
+    // Without CAPA there is no way to advertise STLS either.
+    let pop3 = stub("+OK ready\r\n", |_| Some("-ERR unknown command\r\n")).await;
+
+    for (port, protocol) in [
+        (smtp, Protocol::Smtp),
+        (imap, Protocol::Imap),
+        (pop3, Protocol::Pop3),
+    ] {
+        match probe(port, protocol).await {
+            Err(TraceError::StartTlsNotSupported { protocol: p }) => assert_eq!(p, protocol),
+            other => panic!("{}: {:?}", protocol, other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn unexpected_replies_are_rejected_with_the_reply() {
+    let refused = stub("220 mx ESMTP\r\n", |line| match line {
+        "EHLO pqctracer.invalid" => Some("250-mx\r\n250 STARTTLS\r\n"),
+        "STARTTLS" => Some("454 4.7.0 TLS not available due to temporary reason\r\n"),
+        _ => None,
+    })
+    .await;
+    let busy = stub("421 mx too busy\r\n", |_| None).await;
+    let imap = stub("* OK ready\r\n", |line| match line {
+        "a1 CAPABILITY" => Some("* CAPABILITY IMAP4rev1 STARTTLS\r\na1 OK done\r\n"),
+        "a2 STARTTLS" => Some("a2 NO not now\r\n"),
+        _ => None,
+    })
+    .await;
+    let closed = stub("+OK ready\r\n", |_| None).await;
+
+    for (port, protocol, expected) in [
+        (
+            refused,
+            Protocol::Smtp,
+            "454 4.7.0 TLS not available due to temporary reason",
+        ),
+        (busy, Protocol::Smtp, "421 mx too busy"),
+        (imap, Protocol::Imap, "a2 NO not now"),
+        (closed, Protocol::Pop3, "connection closed"),
+    ] {
+        match probe(port, protocol).await {
+            Err(TraceError::StartTlsRejected { protocol: p, reply }) => {
+                assert_eq!((p, reply.as_str()), (protocol, expected))
+            }
+            other => panic!("{}: {:?}", protocol, other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn data_after_the_upgrade_reply_is_refused() {
+    let port = stub("220 mx ESMTP\r\n", |line| match line {
+        "EHLO pqctracer.invalid" => Some("250-mx\r\n250 STARTTLS\r\n"),
+        // An injected reply riding along with the real one, which would
+        // otherwise be read as the start of the handshake.
+        "STARTTLS" => Some("220 go ahead\r\n250 injected\r\n"),
+        _ => None,
+    })
+    .await;
+    match probe(port, Protocol::Smtp).await {
+        Err(TraceError::StartTlsRejected { reply, .. }) => {
+            assert_eq!(reply, "unexpected data after STARTTLS response")
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn silent_servers_time_out() {
+    // Greets, then never answers EHLO; the paused clock skips the wait.
+    let port = stub("220 mx ESMTP\r\n", |_| Some("")).await;
+    match probe(port, Protocol::Smtp).await {
+        Err(TraceError::Timeout) => {}
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn protocols_parse_and_have_default_ports() {
+    let parsed: Vec<Protocol> = ["smtp", "IMAP", "pop", "pop3"]
+        .into_iter()
+        .map(|name| name.parse().expect("protocol"))
+        .collect();
+    assert_eq!(
+        parsed,
+        [
+            Protocol::Smtp,
+            Protocol::Imap,
+            Protocol::Pop3,
+            Protocol::Pop3
+        ]
+    );
+    assert_eq!(
+        "ftp".parse::<Protocol>().unwrap_err(),
+        "unknown STARTTLS protocol: ftp"
+    );
+    assert_eq!(
+        [Protocol::Smtp, Protocol::Imap, Protocol::Pop3].map(Protocol::default_port),
+        [25, 143, 110]
+    );
+}
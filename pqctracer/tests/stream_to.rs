@@ -0,0 +1,112 @@
+//! `TlsResponse::stream_to` with a multi-megabyte body from the test
+//! server, checked by checksum, and with a body cut short of its
+//! `Content-Length`.
+
+mod common;
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use pqctracer::testutil::TestTlsServer;
+use rustls::crypto::aws_lc_rs::cipher_suite::TLS13_AES_128_GCM_SHA256;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+/// Four megabytes of pseudo-random letters, the same every time.
+fn large_body() -> String {
+    let mut state: u32 = 0x2545_f491;
+    (0..4 << 20)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            char::from(b'a' + (state >> 24) as u8 % 26)
+        })
+        .collect()
+}
+
+fn sha256(bytes: &[u8]) -> Vec<u8> {
+    let sha256 = TLS13_AES_128_GCM_SHA256
+        .tls13()
+        .unwrap()
+        .common
+        .hash_provider;
+    sha256.hash(bytes).as_ref().to_vec()
+}
+
+#[tokio::test]
+async fn a_multi_megabyte_body_arrives_intact() {
+    let body = large_body();
+    let checksum = sha256(body.as_bytes());
+    let served = body.clone();
+    let server = common::start(
+        TestTlsServer::builder().http_handler(move |_| format!("200 OK\r\n\r\n{}", served)),
+    )
+    .await;
+    let client = common::client_for(&server).build().expect("build client");
+    let response = client.get(server.url()).send().await.expect("request");
+    assert_eq!(response.response.content_length(), Some(body.len() as u64));
+
+    let mut file = Vec::new();
+    let (written, metadata) = response.stream_to(&mut file).await.expect("streamed");
+    assert_eq!(written, body.len() as u64);
+    assert_eq!(file.len(), body.len());
+    assert_eq!(sha256(&file), checksum);
+    // The handshake facts come along with the body.
+    assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert!(metadata.cipher.is_some());
+    common::observed(&server, 1).await;
+}
+
+/// A server announcing `length` bytes that sends only `sent` of them and
+/// closes the connection.
+async fn truncating_server(length: usize, sent: usize) -> (SocketAddr, Vec<u8>) {
+    let (config, cert) = common::server_config(&[]);
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let mut tls = acceptor.accept(stream).await?;
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = tls.read(&mut buf).await?;
+                    if read == 0 {
+                        return Ok(());
+                    }
+                    request.extend_from_slice(&buf[..read]);
+                }
+                let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", length);
+                tls.write_all(head.as_bytes()).await?;
+                tls.write_all(&vec![b'x'; sent]).await?;
+                tls.shutdown().await?;
+                Ok::<_, io::Error>(())
+            });
+        }
+    });
+    (addr, cert.as_ref().to_vec())
+}
+
+#[tokio::test]
+async fn a_body_cut_short_is_an_error() {
+    let (addr, cert) = truncating_server(2 << 20, 1 << 20).await;
+    let client = common::client_builder()
+        .add_root_certificate(cert.into())
+        .build()
+        .expect("build client");
+    let url = format!("https://localhost:{}/", addr.port());
+    let response = client.get(url).send().await.expect("request");
+
+    let mut file = Vec::new();
+    let Err(error) = response.stream_to(&mut file).await else {
+        panic!("the body was cut short");
+    };
+    // What arrived stays written, and is counted.
+    assert!(error.written > 0);
+    assert!(error.written < 2 << 20, "{}", error.written);
+    assert_eq!(file.len() as u64, error.written);
+    assert_eq!(error.metadata.group.as_deref(), Some("X25519MLKEM768"));
+}
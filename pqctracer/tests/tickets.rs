@@ -0,0 +1,81 @@
+//! `tickets_issued` of servers that send no session tickets and of servers
+//! that send rustls' default, over TLS 1.3 and TLS 1.2.
+
+mod common;
+
+use std::sync::Arc;
+
+use pqctracer::TlsMetadata;
+use pqctracer::testutil::{TestTlsServer, capturing_provider, handshake_in_memory};
+use rustls::crypto::aws_lc_rs;
+use rustls::server::NoServerSessionStorage;
+use rustls::version::{TLS12, TLS13};
+use rustls::{ClientConfig, RootCertStore, ServerConfig, SupportedProtocolVersion};
+
+/// A handshake with `version` against a server that `configure` had its
+/// say on, read up to the server's close_notify.
+async fn handshake(
+    version: &'static SupportedProtocolVersion,
+    configure: impl FnOnce(&mut ServerConfig),
+) -> TlsMetadata {
+    let (mut server_config, cert) = common::server_config(&[]);
+    configure(&mut server_config);
+    let mut roots = RootCertStore::empty();
+    roots.add(cert).expect("root");
+    let client_config =
+        ClientConfig::builder_with_provider(capturing_provider(&aws_lc_rs::default_provider()))
+            .with_protocol_versions(&[version])
+            .expect("protocol versions")
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    let (metadata, _) = handshake_in_memory(client_config, Arc::new(server_config))
+        .await
+        .expect("handshake");
+    metadata
+}
+
+#[tokio::test]
+async fn tls13_servers_send_the_default_or_none() {
+    let metadata = handshake(&TLS13, |_| {}).await;
+    let expected = common::server_config(&[]).0.send_tls13_tickets;
+    assert!(expected > 0);
+    assert_eq!(metadata.tickets_issued, Some(expected as u32));
+
+    let metadata = handshake(&TLS13, |config| config.send_tls13_tickets = 0).await;
+    assert_eq!(metadata.tickets_issued, Some(0));
+    // Completing the handshake is what tells zero from unknown.
+    assert!(metadata.group.is_some());
+}
+
+#[tokio::test]
+async fn tls12_servers_make_the_session_resumable_or_not() {
+    let metadata = handshake(&TLS12, |_| {}).await;
+    assert_eq!(metadata.tickets_issued, Some(1));
+
+    let metadata = handshake(&TLS12, |config| {
+        config.session_storage = Arc::new(NoServerSessionStorage {});
+    })
+    .await;
+    assert_eq!(metadata.tickets_issued, Some(0));
+}
+
+#[tokio::test]
+async fn requests_count_tls12_sessions_only() {
+    let server = common::start(TestTlsServer::builder().tls12_only(true)).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let response = client.get(server.url()).send().await.expect("request");
+    assert_eq!(response.metadata.tickets_issued, Some(1));
+
+    // hyper reads TLS 1.3 tickets on its connection task, where the
+    // request does not see them, whether there are any or not.
+    for builder in [
+        TestTlsServer::builder(),
+        TestTlsServer::builder().tls13_tickets(0),
+    ] {
+        let server = common::start(builder).await;
+        let client = common::client_for(&server).build().expect("build client");
+        let response = client.get(server.url()).send().await.expect("request");
+        assert_eq!(response.metadata.tickets_issued, None);
+        assert!(response.metadata.cipher.is_some());
+    }
+}
@@ -0,0 +1,170 @@
+//! The phases `collect_timings` reports, in order and of plausible length,
+//! against servers and proxies that hold up one phase at a time.
+
+mod common;
+
+use std::time::Duration;
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{Timings, TlsAwareClient};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const DELAY: Duration = Duration::from_millis(100);
+
+fn timed_client(server: &TestTlsServer) -> TlsAwareClient {
+    common::client_for(server)
+        .collect_timings(true)
+        .build()
+        .expect("build client")
+}
+
+/// Forwards connections to `upstream` once they have waited `DELAY`, which
+/// holds up the server's answer to the ClientHello.
+async fn slow_proxy(upstream: std::net::SocketAddr) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind proxy");
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                tokio::time::sleep(DELAY).await;
+                if let Ok(mut server) = TcpStream::connect(upstream).await {
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                }
+            });
+        }
+    });
+    port
+}
+
+/// The phases of a new TLS connection follow each other within the time
+/// to the first byte, which the total includes.
+fn assert_ordered(timings: &Timings) {
+    let connection =
+        timings.dns.unwrap_or_default() + timings.connect.unwrap() + timings.tls.unwrap();
+    assert!(connection <= timings.ttfb, "{}", timings);
+    if let Some(total) = timings.total {
+        assert!(timings.ttfb <= total, "{}", timings);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_slow_response_shows_in_the_time_to_first_byte() {
+    let server = common::start(TestTlsServer::builder().http_handler(|_| {
+        std::thread::sleep(DELAY);
+        "200 OK".to_string()
+    }))
+    .await;
+    let response = timed_client(&server)
+        .get(server.url())
+        .send()
+        .await
+        .expect("request");
+    let timings = response.timings().expect("timings");
+    assert!(timings.dns.is_some(), "{}", timings);
+    assert!(timings.new_connection());
+    assert_ordered(&timings);
+    let connection = timings.dns.unwrap() + timings.connect.unwrap() + timings.tls.unwrap();
+    assert!(timings.ttfb >= connection + DELAY, "{}", timings);
+    assert!(timings.tls.unwrap() < DELAY, "{}", timings);
+    assert_eq!(timings.total, None);
+
+    let (_, metadata) = response.text().await.expect("body");
+    let timings = metadata.timings.expect("timings");
+    assert_ordered(&timings);
+    assert!(timings.total.is_some());
+}
+
+#[tokio::test]
+async fn a_slow_handshake_shows_in_the_tls_phase() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let port = slow_proxy(server.local_addr()).await;
+    let response = timed_client(&server)
+        .get(format!("https://localhost:{}/", port))
+        .send()
+        .await
+        .expect("request");
+    let timings = response.timings().expect("timings");
+    assert_ordered(&timings);
+    assert!(timings.tls.unwrap() >= DELAY, "{}", timings);
+    assert!(timings.connect.unwrap() < DELAY, "{}", timings);
+    assert!(timings.dns.unwrap() < DELAY, "{}", timings);
+}
+
+#[tokio::test]
+async fn pooled_connections_have_no_connection_phases() {
+    let server = common::start(TestTlsServer::builder().keep_alive(true)).await;
+    let client = timed_client(&server);
+    let first = client.get(server.url()).send().await.expect("request");
+    assert_ordered(&first.timings().expect("timings"));
+    first.text().await.expect("body");
+
+    let second = client.get(server.url()).send().await.expect("request");
+    let timings = second.timings().expect("timings");
+    assert_eq!(
+        (timings.dns, timings.connect, timings.tls),
+        (None, None, None)
+    );
+    assert!(!timings.new_connection());
+    assert!(timings.ttfb > Duration::ZERO);
+    assert_eq!(common::observed(&server, 1).await.len(), 1);
+}
+
+#[tokio::test]
+async fn ip_hosts_need_no_lookup() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let untimed = common::insecure_client();
+    let timed = common::client_builder()
+        .danger_accept_invalid_certs(true)
+        .collect_timings(true)
+        .build()
+        .expect("build client");
+    let url = format!("https://127.0.0.1:{}/", server.local_addr().port());
+    let timings = timed
+        .get(&url)
+        .send()
+        .await
+        .expect("request")
+        .timings()
+        .expect("timings");
+    assert_eq!(timings.dns, None);
+    assert_ordered(&timings);
+
+    // Without collect_timings there are none.
+    let response = untimed.get(&url).send().await.expect("request");
+    assert!(response.timings().is_none());
+}
+
+#[tokio::test]
+async fn plain_http_has_no_tls_phase() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = vec![0; 4096];
+            let _ = stream.read(&mut request).await;
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .await;
+        }
+    });
+    let client = common::client_builder()
+        .collect_timings(true)
+        .build()
+        .expect("build client");
+    let url = format!("http://localhost:{}/", port);
+    let timings = client
+        .get(&url)
+        .send()
+        .await
+        .expect("request")
+        .timings()
+        .expect("timings");
+    assert_eq!(timings.tls, None);
+    assert!(
+        timings.dns.is_some() && timings.connect.is_some(),
+        "{}",
+        timings
+    );
+    assert!(timings.dns.unwrap() + timings.connect.unwrap() <= timings.ttfb);
+}
@@ -0,0 +1,114 @@
+//! `trace_url` and `trace_url_with` against the test server. No test here
+//! installs a crypto provider, so the first trace installs its own; see
+//! `trace_url_provider.rs` for a provider installed by the caller.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{CaptureStatus, HandshakeOrigin, KxPreference, TraceError, TraceOptions};
+use pqctracer::{trace_url, trace_url_with};
+use rustls::NamedGroup;
+use rustls::crypto::CryptoProvider;
+
+async fn start(builder: pqctracer::testutil::TestTlsServerBuilder) -> TestTlsServer {
+    builder.bind("127.0.0.1:0").await.expect("bind test server")
+}
+
+fn trusting(server: &TestTlsServer) -> TraceOptions {
+    TraceOptions::default().add_root_certificate(server.cert_der().clone())
+}
+
+#[tokio::test]
+async fn a_get_returns_the_handshake() {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = requests.clone();
+    let server = start(TestTlsServer::builder().http_handler(move |request| {
+        seen.lock()
+            .unwrap()
+            .push(request.lines().next().unwrap_or_default().to_string());
+        "200 OK".to_string()
+    }))
+    .await;
+    let metadata = trace_url_with(&server.url(), trusting(&server))
+        .await
+        .expect("trace");
+
+    assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert!(metadata.cipher.is_some());
+    assert_eq!(metadata.origin, Some(HandshakeOrigin::Fresh));
+    assert_eq!(metadata.capture_status, CaptureStatus::Captured);
+    assert_eq!(*requests.lock().unwrap(), ["GET / HTTP/1.1"]);
+    // The first trace installed a provider, aws-lc-rs with its ML-KEM group.
+    assert!(CryptoProvider::get_default().is_some());
+}
+
+#[tokio::test]
+async fn head_sends_a_head_request() {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = requests.clone();
+    let server = start(TestTlsServer::builder().http_handler(move |request| {
+        seen.lock()
+            .unwrap()
+            .push(request.lines().next().unwrap_or_default().to_string());
+        "200 OK".to_string()
+    }))
+    .await;
+    let url = format!("{}/status", server.url());
+    let metadata = trace_url_with(&url, trusting(&server).head(true))
+        .await
+        .expect("trace");
+    assert!(metadata.group.is_some());
+    assert_eq!(*requests.lock().unwrap(), ["HEAD /status HTTP/1.1"]);
+}
+
+#[tokio::test]
+async fn classical_only_offers_no_ml_kem() {
+    let server = start(TestTlsServer::builder()).await;
+    let options = trusting(&server).kx_preference(KxPreference::ClassicalOnly);
+    let metadata = trace_url_with(&server.url(), options).await.expect("trace");
+    assert_eq!(metadata.group.as_deref(), Some("X25519"));
+    let observed = server.observed();
+    assert!(
+        !observed[0]
+            .offered_groups
+            .contains(&NamedGroup::X25519MLKEM768)
+    );
+}
+
+#[tokio::test]
+async fn certificates_are_verified_unless_told_otherwise() {
+    let server = start(TestTlsServer::builder()).await;
+    let error = trace_url(&server.url()).await.expect_err("self-signed");
+    assert!(matches!(error, TraceError::Http(_)), "{:?}", error);
+
+    let options = TraceOptions::default().danger_accept_invalid_certs(true);
+    let metadata = trace_url_with(&server.url(), options).await.expect("trace");
+    assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+}
+
+#[tokio::test]
+async fn the_timeout_applies() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind");
+    let url = format!(
+        "https://localhost:{}/",
+        listener.local_addr().unwrap().port()
+    );
+    let options = TraceOptions::default().timeout(Duration::from_millis(200));
+    let error = trace_url_with(&url, options).await.expect_err("no answer");
+    let TraceError::Http(error) = error else {
+        panic!("{:?}", error);
+    };
+    assert!(error.is_timeout(), "{:?}", error);
+    drop(listener);
+}
+
+#[tokio::test]
+async fn invalid_urls_are_rejected() {
+    let error = trace_url("https://exa mple.com/")
+        .await
+        .expect_err("invalid");
+    assert!(matches!(error, TraceError::InvalidUrl(_)), "{:?}", error);
+}
@@ -0,0 +1,29 @@
+//! `trace_url` leaves a crypto provider the caller installed in place: a
+//! process of its own, since the provider is process-wide.
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::{TraceOptions, trace_url_with};
+use rustls::crypto::{CryptoProvider, ring};
+
+#[tokio::test]
+async fn the_callers_provider_is_used() {
+    ring::default_provider()
+        .install_default()
+        .expect("first provider");
+    let server = TestTlsServer::builder()
+        .bind("127.0.0.1:0")
+        .await
+        .expect("bind test server");
+    let options = TraceOptions::default().add_root_certificate(server.cert_der().clone());
+    let metadata = trace_url_with(&server.url(), options).await.expect("trace");
+
+    // ring has no ML-KEM, so the hybrid server settles for X25519.
+    assert_eq!(metadata.group.as_deref(), Some("X25519"));
+    let installed = CryptoProvider::get_default().expect("provider");
+    assert!(
+        installed
+            .kx_groups
+            .iter()
+            .all(|group| group.name() != rustls::NamedGroup::X25519MLKEM768)
+    );
+}
@@ -0,0 +1,142 @@
+//! Streamed request bodies against servers that count what arrives: large
+//! generated bodies of known and unknown length, a slow body that leaves
+//! the handshake alone, and a reset mid-upload.
+
+mod common;
+
+use std::io;
+use std::time::Duration;
+
+use pqctracer::testutil::{TestTlsServer, TestTlsServerBuilder};
+use pqctracer::{TlsAwareClient, TraceError};
+use reqwest::Method;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+/// A server answering each request with the size of its body, followed by
+/// `chunked` if it was sent so.
+fn counting_server() -> TestTlsServerBuilder {
+    TestTlsServer::builder().http_handler(|request| {
+        let (head, body) = request.split_once("\r\n\r\n").unwrap_or((request, ""));
+        let chunked = head
+            .to_ascii_lowercase()
+            .contains("transfer-encoding: chunked");
+        let encoding = if chunked { " chunked" } else { "" };
+        format!("200 OK\r\n\r\n{}{}", body.len(), encoding)
+    })
+}
+
+/// `size` bytes of letters, read as they are sent.
+fn generated(size: u64) -> impl tokio::io::AsyncRead + Unpin + Send + Sync + 'static {
+    tokio::io::repeat(b'q').take(size)
+}
+
+async fn counted(client: &TlsAwareClient, server: &TestTlsServer, body_size: u64, known: bool) {
+    let length = known.then_some(body_size);
+    let response = client
+        .request(Method::PUT, server.url())
+        .body_reader(generated(body_size), length)
+        .send()
+        .await
+        .expect("upload");
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519MLKEM768"));
+    let (text, _) = response.text().await.expect("body");
+    let expected = if known {
+        body_size.to_string()
+    } else {
+        format!("{} chunked", body_size)
+    };
+    assert_eq!(text, expected);
+}
+
+#[tokio::test]
+async fn a_large_body_of_known_length_arrives_whole() {
+    let server = common::start(counting_server()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    counted(&client, &server, 32 << 20, true).await;
+    common::observed(&server, 1).await;
+}
+
+#[tokio::test]
+async fn a_large_body_of_unknown_length_is_sent_chunked() {
+    let server = common::start(counting_server()).await;
+    let client = common::client_for(&server).build().expect("build client");
+    counted(&client, &server, 32 << 20, false).await;
+}
+
+#[tokio::test]
+async fn a_slow_body_does_not_slow_the_handshake() {
+    let server = common::start(counting_server()).await;
+    let client = common::client_for(&server)
+        .collect_timings(true)
+        .build()
+        .expect("build client");
+    let (mut sender, body) = tokio::io::duplex(64 << 10);
+    tokio::spawn(async move {
+        for _ in 0..10 {
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            sender.write_all(&[b's'; 64 << 10]).await?;
+        }
+        Ok::<_, io::Error>(())
+    });
+
+    let response = client
+        .request(Method::PUT, server.url())
+        .body_reader(body, None)
+        .send()
+        .await
+        .expect("upload");
+    let timings = response.timings().expect("timings");
+    assert!(timings.ttfb >= Duration::from_millis(400), "{:?}", timings);
+    // The handshake was over before the body started.
+    let tls = timings.tls.expect("handshake");
+    assert!(tls < Duration::from_millis(300), "{:?}", timings);
+    assert_eq!(response.metadata.group.as_deref(), Some("X25519MLKEM768"));
+    let (text, _) = response.text().await.expect("body");
+    assert_eq!(text, format!("{} chunked", 10 * (64 << 10)));
+}
+
+#[tokio::test]
+async fn a_reset_mid_upload_keeps_the_handshake() {
+    // A server that completes the handshake, reads a little of the request
+    // and closes the connection with the rest of it unread.
+    let (config, cert) = common::server_config(&[]);
+    let acceptor = TlsAcceptor::from(std::sync::Arc::new(config));
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let Ok(mut tls) = acceptor.accept(stream).await else {
+                continue;
+            };
+            let _ = tls.read(&mut [0; 4096]).await;
+        }
+    });
+
+    let client = common::client_builder()
+        .add_root_certificate(cert)
+        .build()
+        .expect("build client");
+    let Err(error) = client
+        .request(Method::PUT, format!("https://localhost:{}/", port))
+        .body_reader(generated(64 << 20), Some(64 << 20))
+        .send()
+        .await
+    else {
+        panic!("the server closed the connection mid-upload");
+    };
+    let TraceError::RequestFailed { metadata, .. } = &error else {
+        panic!("{:?}", error);
+    };
+    assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert!(metadata.cipher.is_some());
+    assert_eq!(metadata.peer_certificates.len(), 1);
+    assert!(
+        error
+            .to_string()
+            .starts_with("HTTP request failed after the handshake: "),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,114 @@
+//! `validation_report` of chains issued through intermediates: the anchor
+//! and the path to it, and what a client that accepts invalid
+//! certificates learns about why validation would have failed.
+
+mod common;
+
+use std::sync::Arc;
+
+use pqctracer::HandshakeOrigin;
+use pqctracer::testutil::TestTlsServer;
+use rustls::client::ClientSessionMemoryCache;
+
+async fn two_intermediates() -> TestTlsServer {
+    common::start(TestTlsServer::builder().intermediates(2)).await
+}
+
+#[tokio::test]
+async fn the_root_anchors_two_intermediates() {
+    let server = two_intermediates().await;
+    let client = common::client_for(&server).build().expect("build client");
+    let response = client.get(server.url()).send().await.expect("request");
+    assert_eq!(response.metadata.peer_certificates.len(), 3);
+
+    let report = response.validation_report().expect("a fresh handshake");
+    assert!(report.is_valid(), "{}", report);
+    assert_eq!(
+        report.trust_anchor.as_deref(),
+        Some("CN=pqctracer test root")
+    );
+    assert_eq!(report.chain_length, 3);
+    assert_eq!(report.path_intermediates, Some(2));
+    assert!(!report.cross_signed);
+    let printed = report.to_string();
+    assert!(
+        printed.starts_with("anchored at CN=pqctracer test root via 2 of 2 intermediates ("),
+        "{}",
+        printed
+    );
+}
+
+#[tokio::test]
+async fn a_trusted_intermediate_shortens_the_path() {
+    let server = two_intermediates().await;
+    let client = common::client_for(&server).build().expect("build client");
+    let response = client.get(server.url()).send().await.expect("request");
+    // End-entity first, so the last certificate is the first intermediate.
+    let first_intermediate = response.metadata.peer_certificates[2].clone();
+
+    let client = common::client_builder()
+        .add_root_certificate(first_intermediate)
+        .build()
+        .expect("build client");
+    let response = client.get(server.url()).send().await.expect("request");
+    let report = response.validation_report().expect("a fresh handshake");
+    assert!(report.is_valid(), "{}", report);
+    assert_eq!(
+        report.trust_anchor.as_deref(),
+        Some("CN=pqctracer test intermediate 1")
+    );
+    assert_eq!(report.chain_length, 3);
+    assert_eq!(report.path_intermediates, Some(1));
+    assert!(report.to_string().contains(" via 1 of 2 intermediates "));
+}
+
+#[tokio::test]
+async fn an_untrusted_root_is_an_unknown_issuer() {
+    let server = two_intermediates().await;
+    let response = common::insecure_client()
+        .get(server.url())
+        .send()
+        .await
+        .expect("invalid certificates accepted");
+    let report = response.validation_report().expect("a fresh handshake");
+    assert!(!report.is_valid());
+    assert_eq!(report.error.as_deref(), Some("UnknownIssuer"));
+    assert_eq!(report.trust_anchor, None);
+    assert_eq!(report.path_intermediates, None);
+    assert_eq!(report.chain_length, 3);
+    assert!(report.to_string().starts_with("failed: UnknownIssuer ("));
+}
+
+#[tokio::test]
+async fn pooled_requests_repeat_the_connections_report() {
+    let server = common::start(TestTlsServer::builder().keep_alive(true)).await;
+    let client = common::client_for(&server).build().expect("build client");
+    let first = client.get(server.url()).send().await.expect("request");
+    let report = first
+        .validation_report()
+        .cloned()
+        .expect("a fresh handshake");
+    let _ = first.text().await.expect("body");
+    let second = client.get(server.url()).send().await.expect("request");
+    assert_eq!(
+        second.metadata.origin,
+        Some(HandshakeOrigin::SharedConnection)
+    );
+    assert_eq!(second.validation_report(), Some(&report));
+}
+
+#[tokio::test]
+async fn resumed_handshakes_have_no_report() {
+    let server = common::start(TestTlsServer::builder().tls13_tickets(1)).await;
+    let client = common::client_for(&server)
+        .session_store(Arc::new(ClientSessionMemoryCache::new(32)))
+        .build()
+        .expect("build client");
+    let first = client.get(server.url()).send().await.expect("request");
+    assert!(first.validation_report().is_some());
+    drop(first);
+    // Nothing is verified when the ticket vouches for the server.
+    let second = client.get(server.url()).send().await.expect("request");
+    assert_eq!(second.metadata.origin, Some(HandshakeOrigin::Resumed));
+    assert_eq!(second.validation_report(), None);
+}
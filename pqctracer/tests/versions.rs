@@ -0,0 +1,132 @@
+//! `probe_tls_versions` against servers pinned to TLS 1.2, to TLS 1.3 and
+//! to neither, and against ones it cannot tell anything about.
+
+mod common;
+
+use pqctracer::testutil::TestTlsServer;
+use pqctracer::versions::{VersionSupport, VersionSupportReport};
+use pqctracer::{CertificateProblem, ErrorCategory, TlsAwareClient};
+use rustls::ProtocolVersion;
+
+async fn probe(client: &TlsAwareClient, server: &TestTlsServer) -> VersionSupportReport {
+    client
+        .probe_tls_versions("localhost", server.local_addr().port())
+        .await
+}
+
+async fn probe_pinned(versions: &[ProtocolVersion]) -> VersionSupportReport {
+    let server = common::start(TestTlsServer::builder().protocol_versions(versions.to_vec())).await;
+    let client = common::client_for(&server).build().expect("build client");
+    probe(&client, &server).await
+}
+
+fn accepted(support: &VersionSupport) -> (Option<String>, Option<String>) {
+    match support {
+        VersionSupport::Accepted { metadata } => (metadata.group.clone(), metadata.cipher.clone()),
+        other => panic!("not accepted: {}", other),
+    }
+}
+
+fn rejected(support: &VersionSupport) -> &str {
+    match support {
+        VersionSupport::Rejected { reason } => reason,
+        other => panic!("not rejected: {}", other),
+    }
+}
+
+#[tokio::test]
+async fn a_tls12_only_server_rejects_tls13() {
+    let report = probe_pinned(&[ProtocolVersion::TLSv1_2]).await;
+    let reason = rejected(&report.tls13);
+    assert!(reason.contains("ProtocolVersion"), "{}", reason);
+    let (group, cipher) = accepted(&report.tls12);
+    // TLS 1.2 has no hybrid groups.
+    assert_eq!(group.as_deref(), Some("X25519"));
+    assert!(cipher.unwrap().starts_with("TLS_ECDHE_"));
+}
+
+#[tokio::test]
+async fn a_tls13_only_server_rejects_tls12() {
+    let report = probe_pinned(&[ProtocolVersion::TLSv1_3]).await;
+    let (group, cipher) = accepted(&report.tls13);
+    assert_eq!(group.as_deref(), Some("X25519MLKEM768"));
+    assert!(cipher.unwrap().starts_with("TLS13_"));
+    let reason = rejected(&report.tls12);
+    assert!(reason.contains("ProtocolVersion"), "{}", reason);
+    assert!(report.tls12.to_string().starts_with("rejected ("));
+}
+
+#[tokio::test]
+async fn a_server_with_both_accepts_both() {
+    let report = probe_pinned(&[ProtocolVersion::TLSv1_2, ProtocolVersion::TLSv1_3]).await;
+    assert!(report.tls13.is_accepted());
+    assert!(report.tls12.is_accepted());
+    assert_eq!(report.tls13.to_string(), "accepted (X25519MLKEM768)");
+    assert_eq!(report.tls12.to_string(), "accepted (X25519)");
+}
+
+#[tokio::test]
+async fn network_errors_are_failures_not_rejections() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+    let report = common::client_builder()
+        .build()
+        .expect("build client")
+        .probe_tls_versions("127.0.0.1", port)
+        .await;
+    for support in [&report.tls13, &report.tls12] {
+        assert!(
+            matches!(
+                support,
+                VersionSupport::Failed {
+                    error: ErrorCategory::ConnectRefused
+                }
+            ),
+            "{}",
+            support
+        );
+    }
+}
+
+#[tokio::test]
+async fn certificate_errors_are_failures_not_rejections() {
+    let server = common::start(TestTlsServer::builder()).await;
+    let client = common::client_builder().build().expect("build client");
+    let report = probe(&client, &server).await;
+    for support in [&report.tls13, &report.tls12] {
+        assert!(
+            matches!(
+                support,
+                VersionSupport::Failed {
+                    error: ErrorCategory::CertificateInvalid {
+                        reason: CertificateProblem::Untrusted
+                    }
+                }
+            ),
+            "{}",
+            support
+        );
+    }
+}
+
+#[tokio::test]
+async fn unusable_hosts_fail_both_probes() {
+    let client = common::client_builder().build().expect("build client");
+    let report = client.probe_tls_versions("exa mple", 443).await;
+    assert!(matches!(
+        report.tls13,
+        VersionSupport::Failed {
+            error: ErrorCategory::InvalidUrl
+        }
+    ));
+    let report = client
+        .probe_tls_versions("does-not-exist.invalid", 443)
+        .await;
+    assert!(matches!(
+        report.tls12,
+        VersionSupport::Failed {
+            error: ErrorCategory::DnsFailure
+        }
+    ));
+}
@@ -0,0 +1,143 @@
+//! `TlsAwareClient::websocket` against a loopback server that answers the
+//! upgrade request as the test asks.
+
+#![cfg(feature = "websocket")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use aws_lc_rs::digest;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use pqctracer::TraceError;
+use pqctracer::testutil::TestTlsServer;
+use tokio::io::AsyncReadExt;
+
+/// The `Sec-WebSocket-Accept` a server answers `key` with (RFC 6455, 4.2.2).
+fn accept(key: &str) -> String {
+    let mut context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+    context.update(key.as_bytes());
+    context.update(b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11");
+    BASE64.encode(context.finish())
+}
+
+fn key_of(request: &str) -> &str {
+    request
+        .lines()
+        .find_map(|line| line.strip_prefix("Sec-WebSocket-Key: "))
+        .expect("Sec-WebSocket-Key")
+        .trim()
+}
+
+async fn server(respond: impl Fn(&str) -> String + Send + Sync + 'static) -> TestTlsServer {
+    common::start(TestTlsServer::builder().http_handler(respond)).await
+}
+
+fn wss_url(server: &TestTlsServer, path: &str) -> String {
+    format!("wss://localhost:{}{}", server.local_addr().port(), path)
+}
+
+#[tokio::test]
+async fn an_upgrade_reports_the_handshake() {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = requests.clone();
+    let server = server(move |request| {
+        seen.lock().unwrap().push(request.to_string());
+        format!(
+            "101 Switching Protocols\r\nUpgrade: websocket\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept(key_of(request))
+        )
+    })
+    .await;
+    let client = common::client_for(&server).build().expect("client");
+    let url = wss_url(&server, "/events?since=5");
+    let (mut stream, metadata) = client.websocket(&url).await.expect("upgrade");
+
+    assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+    assert!(metadata.cipher.is_some());
+    assert_eq!(metadata.protocol_version(), Some("TLS1.3"));
+    assert_eq!(metadata.remote_addr, Some(server.local_addr()));
+    assert!(metadata.handshake_duration.is_some());
+    assert_eq!(metadata.peer_certificates.first(), Some(server.cert_der()));
+
+    let observed = common::observed(&server, 1).await;
+    assert_eq!(
+        observed[0].selected_group,
+        Some(rustls::NamedGroup::X25519MLKEM768)
+    );
+
+    let request = requests.lock().unwrap()[0].clone();
+    assert!(
+        request.starts_with("GET /events?since=5 HTTP/1.1\r\n"),
+        "{}",
+        request
+    );
+    let host = format!("Host: localhost:{}\r\n", server.local_addr().port());
+    assert!(request.contains(&host), "{}", request);
+    assert!(request.contains("Upgrade: websocket\r\n"), "{}", request);
+    assert!(
+        request.contains("Sec-WebSocket-Version: 13\r\n"),
+        "{}",
+        request
+    );
+
+    // The stream starts after the response head: the server sent nothing
+    // past its empty body before closing.
+    let mut rest = Vec::new();
+    let _ = stream.read_to_end(&mut rest).await;
+    assert!(rest.is_empty(), "{:?}", rest);
+}
+
+#[tokio::test]
+async fn redirects_are_reported_with_their_location() {
+    let server = server(|_| "301 Moved Permanently\r\nLocation: wss://example.com/".into()).await;
+    let client = common::client_for(&server).build().expect("client");
+    let e = client.websocket(&wss_url(&server, "/")).await.err();
+    match e {
+        Some(TraceError::WebSocketRedirected { status, location }) => {
+            assert_eq!(status, 301);
+            assert_eq!(location.as_deref(), Some("wss://example.com/"));
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+    // Not followed: one connection, to the first server.
+    common::observed(&server, 1).await;
+}
+
+#[tokio::test]
+async fn other_statuses_are_rejections() {
+    let server = server(|_| "403 Forbidden".into()).await;
+    let client = common::client_for(&server).build().expect("client");
+    let e = client.websocket(&wss_url(&server, "/")).await.err();
+    match e {
+        Some(TraceError::WebSocketRejected { status, reason }) => {
+            assert_eq!(status, 403);
+            assert_eq!(reason, "Forbidden");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn a_wrong_accept_is_a_protocol_error() {
+    let server = server(|_| {
+        "101 Switching Protocols\r\nUpgrade: websocket\r\nSec-WebSocket-Accept: bm90IHRoZSBrZXk=\r\n\r\n"
+            .into()
+    })
+    .await;
+    let client = common::client_for(&server).build().expect("client");
+    let e = client.websocket(&wss_url(&server, "/")).await.err();
+    assert!(
+        matches!(&e, Some(TraceError::WebSocketProtocol(message)) if message.contains("Sec-WebSocket-Accept")),
+        "{:?}",
+        e
+    );
+}
+
+#[tokio::test]
+async fn only_wss_urls_are_opened() {
+    let client = common::insecure_client();
+    let e = client.websocket("https://localhost/").await.err();
+    assert!(matches!(e, Some(TraceError::InvalidUrl(_))), "{:?}", e);
+}
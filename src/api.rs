@@ -0,0 +1,755 @@
+//! `serve-api`: tracing over HTTP, for teams that would rather `curl` than
+//! install Rust.
+//!
+//! Deliberately minimal: one request per connection, `GET` only, answered
+//! by hand like the self-test server does.
+//!
+//! Every trace gets a client of its own that connects only to the addresses
+//! its host was checked against, through no proxy, and does not follow
+//! redirects, so neither a second DNS answer, a proxy nor a redirect can
+//! point it somewhere the checks would have refused.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::Arc;
+#[cfg(feature = "metrics")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use clap::Args;
+#[cfg(feature = "metrics")]
+use pqctracer::KeyExchangeKind;
+use pqctracer::idn;
+#[cfg(feature = "metrics")]
+use pqctracer::scan::ScanResult;
+use pqctracer::scan::{ScanOptions, target_url};
+use reqwest::Url;
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+
+use crate::ClientArgs;
+use crate::cidr::Cidr;
+use crate::config::{ClientSettings, Config};
+
+/// Largest request head accepted, request line and headers included.
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+/// How long a client may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Upper bound for one trace when the client has no timeout configured.
+const DEFAULT_TRACE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Args)]
+pub struct ServeApiArgs {
+    /// Address to listen on.
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+    pub listen: SocketAddr,
+
+    #[command(flatten)]
+    pub client: ClientArgs,
+
+    /// Only trace hosts matching this pattern: `example.com`,
+    /// `*.example.com` or a CIDR range; repeatable.
+    #[arg(long = "allow-host", value_name = "PATTERN")]
+    pub allow_hosts: Vec<HostPattern>,
+
+    /// Never trace hosts matching this pattern; repeatable, and wins over
+    /// --allow-host.
+    #[arg(long = "deny-host", value_name = "PATTERN")]
+    pub deny_hosts: Vec<HostPattern>,
+
+    /// Allow hosts that resolve to loopback, private, link-local, multicast
+    /// or other internal addresses.
+    #[arg(long)]
+    pub allow_private: bool,
+
+    /// Honour `insecure=true` in /trace requests.
+    #[arg(long)]
+    pub allow_insecure: bool,
+
+    /// Traces running at the same time; further requests get a 503.
+    #[arg(long, value_name = "N", default_value_t = 16)]
+    pub max_concurrent: usize,
+}
+
+/// A host name, a `*.` wildcard covering its subdomains, or a network.
+#[derive(Debug, Clone)]
+pub enum HostPattern {
+    Exact(String),
+    Subdomains(String),
+    Network(Cidr),
+}
+
+impl HostPattern {
+    /// Whether `host`, or for networks every one of its `addrs`, matches.
+    fn matches(&self, host: &str, addrs: &[IpAddr]) -> bool {
+        match self {
+            HostPattern::Exact(name) => host == name,
+            HostPattern::Subdomains(domain) => host
+                .strip_suffix(domain.as_str())
+                .is_some_and(|prefix| prefix.ends_with('.')),
+            HostPattern::Network(network) => {
+                !addrs.is_empty() && addrs.iter().all(|ip| network.contains(*ip))
+            }
+        }
+    }
+
+    /// Whether `host`, or for networks any one of its `addrs`, matches.
+    fn matches_any(&self, host: &str, addrs: &[IpAddr]) -> bool {
+        match self {
+            HostPattern::Network(network) => addrs.iter().any(|ip| network.contains(*ip)),
+            pattern => pattern.matches(host, addrs),
+        }
+    }
+}
+
+impl FromStr for HostPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        if let Some(domain) = s.strip_prefix("*.") {
+            return Ok(HostPattern::Subdomains(domain.to_string()));
+        }
+        if s.contains('/') || s.parse::<IpAddr>().is_ok() {
+            return s.parse().map(HostPattern::Network);
+        }
+        if s.is_empty() {
+            return Err("empty host pattern".to_string());
+        }
+        Ok(HostPattern::Exact(s))
+    }
+}
+
+struct Api {
+    client: ClientSettings,
+    /// Present with --allow-insecure.
+    insecure_client: Option<ClientSettings>,
+    allow_hosts: Vec<HostPattern>,
+    deny_hosts: Vec<HostPattern>,
+    allow_private: bool,
+    max_timeout: Duration,
+    #[cfg(feature = "metrics")]
+    max_concurrent: usize,
+    slots: Semaphore,
+    #[cfg(feature = "metrics")]
+    metrics: Metrics,
+}
+
+pub async fn serve_api(args: ServeApiArgs) -> ExitCode {
+    let config = match args.client.resolve(Config::default()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let insecure = ClientSettings {
+        insecure: Some(true),
+        ..config.client.clone()
+    };
+    let insecure_client = args.allow_insecure.then_some(insecure);
+    // Traces build their own clients; this one reports invalid settings
+    // before the server starts listening.
+    let built = config
+        .client
+        .build_client()
+        .and_then(|_| match &insecure_client {
+            Some(insecure) => Ok(insecure.client_builder()?.build().map(drop)?),
+            None => Ok(()),
+        });
+    if let Err(e) = built {
+        eprintln!("Failed to build client: {}", e);
+        return ExitCode::FAILURE;
+    }
+    let max_timeout = match config.client.timeout.map(Duration::try_from_secs_f64) {
+        Some(Ok(timeout)) => timeout,
+        _ => DEFAULT_TRACE_TIMEOUT,
+    };
+
+    let max_concurrent = args.max_concurrent.max(1);
+
+    let listener = match TcpListener::bind(args.listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to listen on {}: {}", args.listen, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let api = Arc::new(Api {
+        client: config.client,
+        insecure_client,
+        allow_hosts: args.allow_hosts,
+        deny_hosts: args.deny_hosts,
+        allow_private: args.allow_private,
+        max_timeout,
+        #[cfg(feature = "metrics")]
+        max_concurrent,
+        slots: Semaphore::new(max_concurrent),
+        #[cfg(feature = "metrics")]
+        metrics: Metrics::default(),
+    });
+
+    let addr = listener.local_addr().unwrap_or(args.listen);
+    println!("Serving the trace API on http://{}", addr);
+    println!(
+        "Try: curl 'http://{}/trace?url=https://www.google.com'",
+        addr
+    );
+
+    let accept_loop = async {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(api.clone().handle(stream));
+                }
+                Err(e) => eprintln!("Failed to accept a connection: {}", e),
+            }
+        }
+    };
+    tokio::select! {
+        _ = accept_loop => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    ExitCode::SUCCESS
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: &impl Serialize) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_string(body).expect("response serializes") + "\n",
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        #[derive(Serialize)]
+        struct Error {
+            error: String,
+        }
+        Self::json(
+            status,
+            &Error {
+                error: message.into(),
+            },
+        )
+    }
+
+    fn text(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into(),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            431 => "Request Header Fields Too Large",
+            500 => "Internal Server Error",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            _ => "",
+        };
+        format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            reason,
+            self.content_type,
+            self.body.len(),
+            self.body
+        )
+        .into_bytes()
+    }
+}
+
+impl Api {
+    async fn handle(self: Arc<Self>, mut stream: TcpStream) {
+        let response = match read_request_target(&mut stream).await {
+            Ok(target) => self.route(&target).await,
+            Err(response) => response,
+        };
+        let _ = stream.write_all(&response.to_bytes()).await;
+        let _ = stream.shutdown().await;
+    }
+
+    async fn route(&self, target: &str) -> Response {
+        let Ok(url) = Url::parse(&format!("http://api.invalid{}", target)) else {
+            return Response::error(400, "invalid request target");
+        };
+        match url.path() {
+            "/trace" => self.trace(&url).await,
+            "/healthz" => Response::text(200, "ok\n"),
+            #[cfg(feature = "metrics")]
+            "/metrics" => {
+                let in_flight = self.max_concurrent - self.slots.available_permits();
+                Response::text(200, self.metrics.render(in_flight))
+            }
+            _ => Response::error(404, "not found"),
+        }
+    }
+
+    async fn trace(&self, request: &Url) -> Response {
+        let mut target = None;
+        let mut timeout = self.max_timeout;
+        let mut insecure = false;
+        for (key, value) in request.query_pairs() {
+            match &*key {
+                "url" => target = Some(value.into_owned()),
+                "timeout" => match value.parse().map(Duration::try_from_secs_f64) {
+                    Ok(Ok(requested)) if !requested.is_zero() => {
+                        timeout = requested.min(self.max_timeout)
+                    }
+                    _ => return Response::error(400, format!("invalid timeout: {}", value)),
+                },
+                "insecure" => match &*value {
+                    "true" | "1" => insecure = true,
+                    "false" | "0" => insecure = false,
+                    _ => return Response::error(400, format!("invalid insecure flag: {}", value)),
+                },
+                _ => {}
+            }
+        }
+        let Some(target) = target else {
+            return Response::error(400, "missing url parameter");
+        };
+        let url = idn::normalize_url(&target_url(&target)).map_err(|e| e.to_string());
+        let url = match url.and_then(|url| Url::parse(&url).map_err(|e| e.to_string())) {
+            Ok(url) if url.scheme() == "https" => url,
+            Ok(_) => return Response::error(400, "only https URLs can be traced"),
+            Err(e) => return Response::error(400, format!("invalid url: {}", e)),
+        };
+        let settings = match (insecure, &self.insecure_client) {
+            (false, _) => &self.client,
+            (true, Some(settings)) => settings,
+            (true, None) => {
+                return self.reject(403, "insecure traces are disabled on this server");
+            }
+        };
+        let (host, addrs) = match self.check_host(&url).await {
+            Ok(checked) => checked,
+            Err(reason) => return self.reject(403, reason),
+        };
+        if addrs.is_empty() {
+            return Response::error(502, format!("{} does not resolve", host));
+        }
+        let Ok(_slot) = self.slots.try_acquire() else {
+            return self.reject(503, "too many traces in progress, try again later");
+        };
+        let built = settings.client_builder().and_then(|builder| {
+            // A proxy would connect wherever it resolves the host to.
+            let builder = builder.follow_redirects(false).no_proxy();
+            let builder = match host.parse::<IpAddr>() {
+                Ok(_) => builder,
+                Err(_) => builder.resolve(&host, &addrs),
+            };
+            Ok(builder.build()?)
+        });
+        let built = built.map_err(|e: Box<dyn std::error::Error>| e.to_string());
+        let client = match built {
+            Ok(client) => client,
+            Err(e) => return Response::error(500, format!("failed to build client: {}", e)),
+        };
+
+        let options = ScanOptions {
+            concurrency: 1,
+            retries: 0,
+            ..Default::default()
+        };
+        let traced = tokio::time::timeout(timeout, client.scan([url.to_string()], &options)).await;
+        match traced.map(|mut results| results.pop()) {
+            Ok(Some(result)) => {
+                #[cfg(feature = "metrics")]
+                self.metrics.record(&result);
+                Response::json(200, &result)
+            }
+            Ok(None) => unreachable!("a scan of one target yields one result"),
+            Err(_) => {
+                #[cfg(feature = "metrics")]
+                self.metrics.timeouts.fetch_add(1, Ordering::Relaxed);
+                Response::error(504, format!("trace timed out after {:?}", timeout))
+            }
+        }
+    }
+
+    /// Apply the deny list, the allow list and the internal-address guard
+    /// to `url`'s host and the addresses it resolves to, and return both.
+    async fn check_host(&self, url: &Url) -> Result<(String, Vec<IpAddr>), String> {
+        let host = url
+            .host_str()
+            .unwrap_or_default()
+            .trim_matches(['[', ']'])
+            .to_ascii_lowercase();
+        let addrs: Vec<IpAddr> = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![ip],
+            // Names that do not resolve are left for the lists to judge.
+            Err(_) => {
+                let port = url.port_or_known_default().unwrap_or(443);
+                match tokio::net::lookup_host((host.as_str(), port)).await {
+                    Ok(addrs) => addrs.map(|addr| addr.ip()).collect(),
+                    Err(_) => Vec::new(),
+                }
+            }
+        };
+
+        if self.deny_hosts.iter().any(|p| p.matches_any(&host, &addrs)) {
+            return Err(format!("{} is denied on this server", host));
+        }
+        if !self.allow_hosts.is_empty()
+            && !self.allow_hosts.iter().any(|p| p.matches(&host, &addrs))
+        {
+            return Err(format!("{} is not allowed on this server", host));
+        }
+        if !self.allow_private && addrs.iter().any(|ip| is_internal(*ip)) {
+            return Err(format!("{} resolves to an internal address", host));
+        }
+        Ok((host, addrs))
+    }
+
+    fn reject(&self, status: u16, message: impl Into<String>) -> Response {
+        #[cfg(feature = "metrics")]
+        self.metrics.rejected.fetch_add(1, Ordering::Relaxed);
+        Response::error(status, message)
+    }
+}
+
+/// Read the request head from `stream` and return its request target, or
+/// the error response to send instead.
+async fn read_request_target(stream: &mut TcpStream) -> Result<String, Response> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    let read = async {
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+            if head.len() > MAX_REQUEST_HEAD {
+                return Err(Response::error(431, "request head too large"));
+            }
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => return Err(Response::error(400, "incomplete request")),
+                Ok(n) => head.extend_from_slice(&buf[..n]),
+            }
+        }
+        Ok(())
+    };
+    match tokio::time::timeout(REQUEST_TIMEOUT, read).await {
+        Ok(Ok(())) => {}
+        Ok(Err(response)) => return Err(response),
+        Err(_) => return Err(Response::error(408, "request timed out")),
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let request_line = head.lines().next().unwrap_or_default();
+    let mut parts = request_line.split(' ');
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) if target.starts_with('/') => Ok(target.to_string()),
+        (Some("GET"), _) => Err(Response::error(400, "invalid request line")),
+        _ => Err(Response::error(405, "only GET is supported")),
+    }
+}
+
+/// Loopback, private, link-local, carrier-grade NAT, multicast and
+/// unspecified addresses: the ones an API reachable by others must not be
+/// pointed at. IPv6 addresses embedding an IPv4 one – mapped, compatible,
+/// NAT64 and 6to4 – are judged by the address they embed.
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || a == 0
+                || (a == 100 && b & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(embedded) => is_internal(IpAddr::V4(embedded)),
+            None => {
+                let first = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80
+            }
+        },
+    }
+}
+
+/// The IPv4 address an IPv4-mapped (`::ffff:a.b.c.d`), IPv4-compatible
+/// (`::a.b.c.d`), NAT64 (`64:ff9b::a.b.c.d`) or 6to4 (`2002:aabb:ccdd::`)
+/// address leads to.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let octets = ip.octets();
+    let low = Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15]);
+    match segments {
+        [0, 0, 0, 0, 0, 0xffff, ..] => Some(low),
+        // `::` and `::1` are IPv6's own unspecified and loopback addresses.
+        [0, 0, 0, 0, 0, 0, ..] if !ip.is_unspecified() && !ip.is_loopback() => Some(low),
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(low),
+        [0x2002, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+        _ => None,
+    }
+}
+
+/// Counters for `/metrics`, in the Prometheus text format.
+#[cfg(feature = "metrics")]
+#[derive(Default)]
+struct Metrics {
+    traces_reached: AtomicU64,
+    traces_failed: AtomicU64,
+    timeouts: AtomicU64,
+    rejected: AtomicU64,
+    hybrid: AtomicU64,
+    post_quantum: AtomicU64,
+    classical: AtomicU64,
+}
+
+#[cfg(feature = "metrics")]
+impl Metrics {
+    fn record(&self, result: &ScanResult) {
+        let outcome = if result.status.is_some() {
+            &self.traces_reached
+        } else {
+            &self.traces_failed
+        };
+        outcome.fetch_add(1, Ordering::Relaxed);
+        let kind = match result.metadata.key_exchange_kind() {
+            Some(KeyExchangeKind::Hybrid) => &self.hybrid,
+            Some(KeyExchangeKind::PostQuantum) => &self.post_quantum,
+            Some(KeyExchangeKind::Classical) => &self.classical,
+            None => return,
+        };
+        kind.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn render(&self, in_flight: usize) -> String {
+        let get = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        format!(
+            "# HELP pqctracer_api_traces_total Traces finished, by outcome.\n\
+             # TYPE pqctracer_api_traces_total counter\n\
+             pqctracer_api_traces_total{{outcome=\"reached\"}} {}\n\
+             pqctracer_api_traces_total{{outcome=\"failed\"}} {}\n\
+             pqctracer_api_traces_total{{outcome=\"timeout\"}} {}\n\
+             # HELP pqctracer_api_rejected_total Trace requests refused by the host or concurrency guards.\n\
+             # TYPE pqctracer_api_rejected_total counter\n\
+             pqctracer_api_rejected_total {}\n\
+             # HELP pqctracer_api_key_exchange_total Traces by kind of negotiated key exchange.\n\
+             # TYPE pqctracer_api_key_exchange_total counter\n\
+             pqctracer_api_key_exchange_total{{kind=\"hybrid\"}} {}\n\
+             pqctracer_api_key_exchange_total{{kind=\"post-quantum\"}} {}\n\
+             pqctracer_api_key_exchange_total{{kind=\"classical\"}} {}\n\
+             # HELP pqctracer_api_traces_in_flight Traces currently running.\n\
+             # TYPE pqctracer_api_traces_in_flight gauge\n\
+             pqctracer_api_traces_in_flight {}\n",
+            get(&self.traces_reached),
+            get(&self.traces_failed),
+            get(&self.timeouts),
+            get(&self.rejected),
+            get(&self.hybrid),
+            get(&self.post_quantum),
+            get(&self.classical),
+            in_flight,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pqctracer::testutil::TestTlsServer;
+    use serde_json::Value;
+
+    use super::*;
+
+    fn api_with(allow_hosts: &[&str], deny_hosts: &[&str], allow_private: bool) -> Api {
+        let patterns = |patterns: &[&str]| {
+            patterns
+                .iter()
+                .map(|p| p.parse().expect("host pattern"))
+                .collect()
+        };
+        Api {
+            client: ClientSettings {
+                insecure: Some(true),
+                ..ClientSettings::default()
+            },
+            insecure_client: None,
+            allow_hosts: patterns(allow_hosts),
+            deny_hosts: patterns(deny_hosts),
+            allow_private,
+            max_timeout: Duration::from_secs(10),
+            #[cfg(feature = "metrics")]
+            max_concurrent: 4,
+            slots: Semaphore::new(4),
+            #[cfg(feature = "metrics")]
+            metrics: Metrics::default(),
+        }
+    }
+
+    async fn trace(api: &Api, url: &str) -> (u16, Value) {
+        let response = api.route(&format!("/trace?url={}", url)).await;
+        let body = serde_json::from_str(&response.body).expect("JSON body");
+        (response.status, body)
+    }
+
+    async fn server() -> TestTlsServer {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        TestTlsServer::builder()
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server")
+    }
+
+    #[test]
+    fn internal_addresses_are_recognised() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "0.1.2.3",
+            "224.0.0.1",
+            "239.255.255.250",
+            "255.255.255.255",
+            "::",
+            "::1",
+            "fc00::1",
+            "fe80::1",
+            "ff02::1",
+            "ff0e::1",
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+            "::127.0.0.1",
+            "::169.254.169.254",
+            "64:ff9b::7f00:1",
+            "64:ff9b::a9fe:a9fe",
+            "2002:7f00:1::",
+            "2002:c0a8:101::1",
+        ] {
+            assert!(is_internal(ip.parse().unwrap()), "{} is internal", ip);
+        }
+        for ip in [
+            "8.8.8.8",
+            "1.1.1.1",
+            "100.128.0.1",
+            "2606:4700:4700::1111",
+            "::ffff:8.8.8.8",
+            "::8.8.8.8",
+            "64:ff9b::808:808",
+            "2002:808:808::",
+        ] {
+            assert!(!is_internal(ip.parse().unwrap()), "{} is external", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn internal_addresses_are_refused() {
+        let server = server().await;
+        let api = api_with(&[], &[], false);
+        let port = server.local_addr().port();
+        for host in [
+            "127.0.0.1".to_string(),
+            "localhost".to_string(),
+            "[::ffff:127.0.0.1]".to_string(),
+            "[64:ff9b::7f00:1]".to_string(),
+            "[2002:7f00:1::]".to_string(),
+        ] {
+            let (status, body) = trace(&api, &format!("https://{}:{}/", host, port)).await;
+            assert_eq!(status, 403, "{}: {}", host, body);
+            let error = body["error"].as_str().expect("error");
+            assert!(
+                error.ends_with("resolves to an internal address"),
+                "{}",
+                error
+            );
+        }
+        assert!(server.observed().is_empty());
+    }
+
+    #[tokio::test]
+    async fn deny_list_wins_over_everything() {
+        let server = server().await;
+        let url = format!("https://localhost:{}/", server.local_addr().port());
+        for deny in ["localhost", "127.0.0.0/8"] {
+            let api = api_with(&["localhost"], &[deny], true);
+            let (status, body) = trace(&api, &url).await;
+            assert_eq!(status, 403, "{}", body);
+            assert_eq!(body["error"], "localhost is denied on this server");
+        }
+        assert!(server.observed().is_empty());
+    }
+
+    #[tokio::test]
+    async fn allow_list_admits_matching_hosts_only() {
+        let server = server().await;
+        let port = server.local_addr().port();
+
+        let api = api_with(&["*.example.com"], &[], true);
+        let (status, body) = trace(&api, &format!("https://localhost:{}/", port)).await;
+        assert_eq!(status, 403, "{}", body);
+        assert_eq!(body["error"], "localhost is not allowed on this server");
+        assert!(server.observed().is_empty());
+
+        let api = api_with(&["127.0.0.0/8"], &[], true);
+        let (status, body) = trace(&api, &format!("https://127.0.0.1:{}/", port)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["status"], 200);
+        assert_eq!(body["metadata"]["group"], "X25519MLKEM768");
+    }
+
+    #[tokio::test]
+    async fn redirects_are_not_followed() {
+        let elsewhere = server().await;
+        let location = format!("https://localhost:{}/", elsewhere.local_addr().port());
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let server = TestTlsServer::builder()
+            .http_handler(move |_| format!("302 Found\r\nLocation: {}", location))
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+
+        let api = api_with(&[], &[], true);
+        let url = format!("https://localhost:{}/", server.local_addr().port());
+        let (status, body) = trace(&api, &url).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["status"], 302);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(elsewhere.observed().is_empty());
+    }
+
+    #[tokio::test]
+    async fn configured_proxies_are_bypassed() {
+        let server = server().await;
+        let mut api = api_with(&[], &[], true);
+        // Nothing listens there; a request through it would fail.
+        let unused = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        api.client.proxy = Some(format!("http://{}", unused.local_addr().unwrap()));
+        drop(unused);
+
+        let url = format!("https://localhost:{}/", server.local_addr().port());
+        let (status, body) = trace(&api, &url).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["status"], 200);
+        assert_eq!(server.observed().len(), 1);
+    }
+}
@@ -0,0 +1,96 @@
+//! `--basic` and `--bearer`: credentials for targets that answer `401`
+//! without them.
+
+use std::fmt;
+use std::io::{self, IsTerminal, Write as _};
+use std::process::Command;
+
+use clap::Args;
+
+/// Credentials sent with traced requests.
+#[derive(Args)]
+pub struct AuthArgs {
+    /// Send HTTP Basic credentials; prompts for the password when it is
+    /// left out.
+    #[arg(long, value_name = "USER[:PASSWORD]", conflicts_with = "bearer")]
+    pub basic: Option<String>,
+
+    /// Send this token in an `Authorization: Bearer` header.
+    #[arg(long, value_name = "TOKEN")]
+    pub bearer: Option<String>,
+}
+
+impl AuthArgs {
+    /// The credentials to send, prompting for a Basic password if needed.
+    pub fn credentials(&self) -> io::Result<Option<Credentials>> {
+        if let Some(token) = &self.bearer {
+            return Ok(Some(Credentials::Bearer(token.clone())));
+        }
+        let Some(basic) = &self.basic else {
+            return Ok(None);
+        };
+        let (user, password) = match basic.split_once(':') {
+            Some((user, password)) => (user.to_string(), password.to_string()),
+            None => (basic.clone(), prompt_password(basic)?),
+        };
+        Ok(Some(Credentials::Basic { user, password }))
+    }
+}
+
+/// Credentials for the `Authorization` header. `Debug` leaves the secrets
+/// out.
+pub enum Credentials {
+    Basic { user: String, password: String },
+    Bearer(String),
+}
+
+impl Credentials {
+    /// Add the credentials to `request`.
+    pub fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self {
+            Credentials::Basic { user, password } => request.basic_auth(user, Some(password)),
+            Credentials::Bearer(token) => request.bearer_auth(token),
+        }
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Basic { user, .. } => f
+                .debug_struct("Basic")
+                .field("user", user)
+                .field("password", &"<redacted>")
+                .finish(),
+            Credentials::Bearer(_) => f.debug_tuple("Bearer").field(&"<redacted>").finish(),
+        }
+    }
+}
+
+/// Read `user`'s password from standard input, with echo turned off when
+/// it is a terminal.
+fn prompt_password(user: &str) -> io::Result<String> {
+    eprint!("Password for {}: ", user);
+    io::stderr().flush()?;
+    let terminal = io::stdin().is_terminal();
+    let silenced = terminal && stty("-echo");
+    let mut line = String::new();
+    let read = io::stdin().read_line(&mut line);
+    if silenced {
+        stty("echo");
+    }
+    // Neither a silenced terminal nor a pipe ends the prompt's line.
+    if silenced || !terminal {
+        eprintln!();
+    }
+    read?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Change a setting of the terminal on standard input.
+fn stty(setting: &str) -> bool {
+    Command::new("stty")
+        .arg(setting)
+        .status()
+        .is_ok_and(|status| status.success())
+}
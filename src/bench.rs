@@ -0,0 +1,321 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::Args;
+use pqctracer::bench::{BenchOptions, BenchReport};
+use pqctracer::{ClientCapabilities, ReportMeta};
+use serde::Serialize;
+
+use crate::config::{Config, OutputSettings};
+use crate::{ClientArgs, OutputFormat, csv_field, host_and_port, open_output};
+
+#[derive(Args)]
+#[command(after_help = crate::config::ENV_VARS)]
+pub struct BenchArgs {
+    /// Host name, host:port or https:// URL to benchmark.
+    pub target: String,
+
+    #[command(flatten)]
+    pub client: ClientArgs,
+
+    /// Measured handshakes per configuration.
+    #[arg(short = 'n', long, default_value_t = 10)]
+    pub iterations: usize,
+
+    /// Unmeasured handshakes per configuration before sampling.
+    #[arg(long, default_value_t = 1)]
+    pub warmup: usize,
+
+    /// Pause between handshakes, in milliseconds.
+    #[arg(long, default_value_t = 0)]
+    pub delay_ms: u64,
+
+    /// Report format [default: table].
+    #[arg(long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Write the report to this file instead of standard output.
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+pub async fn bench(args: BenchArgs) -> ExitCode {
+    let (host, port) = match host_and_port(&args.target, 443) {
+        Ok(target) => target,
+        Err(e) => {
+            eprintln!("Invalid target {}: {}", args.target, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let options = BenchOptions {
+        iterations: args.iterations,
+        warmup: args.warmup,
+        delay: Duration::from_millis(args.delay_ms),
+    };
+
+    let config = match args.client.resolve(Config {
+        output: OutputSettings {
+            format: args.format,
+            path: args.output,
+        },
+        ..Default::default()
+    }) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let report = match config.client.build_client() {
+        Ok(client) => client
+            .bench(&host, port, &options)
+            .await
+            .map(|report| (report, client.client_capabilities(), client.report_meta()))
+            .map_err(Into::into),
+        Err(e) => Err(e),
+    };
+    let (report, capabilities, meta) = match report {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Benchmark failed: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let format = config.output.format.unwrap_or(OutputFormat::Table);
+    let written = open_output(config.output.path.as_deref())
+        .and_then(|mut out| write_report(&mut *out, format, &report, &capabilities, &meta));
+    if let Err(e) = written {
+        eprintln!("Failed to write report: {}", e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// The JSON form of a report: the meta block, the report's own fields and
+/// the client's.
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    meta: &'a ReportMeta,
+    #[serde(flatten)]
+    report: &'a BenchReport,
+    client: &'a ClientCapabilities,
+}
+
+fn write_report(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    report: &BenchReport,
+    client: &ClientCapabilities,
+    meta: &ReportMeta,
+) -> io::Result<()> {
+    let json = JsonReport {
+        meta,
+        report,
+        client,
+    };
+    match format {
+        OutputFormat::Table => write_bench_table(out, report),
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&json).expect("report serializes");
+            writeln!(out, "{}", json)
+        }
+        OutputFormat::Jsonl => {
+            let json = serde_json::to_string(&json).expect("report serializes");
+            writeln!(out, "{}", json)
+        }
+        OutputFormat::Csv => write_bench_csv(out, report),
+    }
+}
+
+fn write_bench_csv(out: &mut dyn Write, report: &BenchReport) -> io::Result<()> {
+    let ms = |d: Duration| format!("{:.3}", d.as_secs_f64() * 1000.0);
+
+    writeln!(
+        out,
+        "host,port,configuration,negotiated,iterations,min_ms,median_ms,p95_ms,max_ms,bytes_sent,bytes_received,hello_retries,hrr_penalty_median_ms"
+    )?;
+    for run in &report.runs {
+        let negotiated = run
+            .samples
+            .first()
+            .and_then(|s| s.metadata.group.as_deref())
+            .unwrap_or_default();
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&report.host),
+            report.port,
+            run.preference,
+            negotiated,
+            run.durations.count,
+            ms(run.durations.min),
+            ms(run.durations.median),
+            ms(run.durations.p95),
+            ms(run.durations.max),
+            run.bytes_sent,
+            run.bytes_received,
+            run.hello_retries,
+            run.hrr_penalties.map(|p| ms(p.median)).unwrap_or_default()
+        )?;
+    }
+    Ok(())
+}
+
+fn write_bench_table(out: &mut dyn Write, report: &BenchReport) -> io::Result<()> {
+    let ms = |d: Duration| format!("{:.2}", d.as_secs_f64() * 1000.0);
+
+    writeln!(out, "Handshake benchmark: {}:{}", report.host, report.port)?;
+    writeln!(
+        out,
+        "{:<18} {:<16} {:>9} {:>9} {:>9} {:>9} {:>8} {:>8}",
+        "configuration",
+        "negotiated",
+        "min ms",
+        "median ms",
+        "p95 ms",
+        "max ms",
+        "sent B",
+        "recv B"
+    )?;
+    for run in &report.runs {
+        let negotiated = run
+            .samples
+            .first()
+            .and_then(|s| s.metadata.group.as_deref())
+            .unwrap_or("n/a");
+        writeln!(
+            out,
+            "{:<18} {:<16} {:>9} {:>9} {:>9} {:>9} {:>8} {:>8}",
+            run.preference.to_string(),
+            negotiated,
+            ms(run.durations.min),
+            ms(run.durations.median),
+            ms(run.durations.p95),
+            ms(run.durations.max),
+            run.bytes_sent,
+            run.bytes_received
+        )?;
+    }
+    for run in &report.runs {
+        writeln!(
+            out,
+            "{} offered: {}",
+            run.preference,
+            run.offered_groups.join(", ")
+        )?;
+    }
+    for run in &report.runs {
+        if let Some(penalties) = &run.hrr_penalties {
+            writeln!(
+                out,
+                "{}: {} of {} handshakes needed a HelloRetryRequest, median {} ms added",
+                run.preference,
+                run.hello_retries,
+                run.durations.count,
+                ms(penalties.median)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pqctracer::TlsAwareClient;
+    use pqctracer::testutil::TestTlsServer;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::config::ClientSettings;
+
+    /// A two-sample benchmark of a loopback server, and the client that ran
+    /// it.
+    async fn report() -> (BenchReport, TlsAwareClient) {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let server = TestTlsServer::builder()
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let settings = ClientSettings {
+            insecure: Some(true),
+            ..ClientSettings::default()
+        };
+        let client = settings.build_client().expect("client");
+        let options = BenchOptions {
+            iterations: 2,
+            warmup: 0,
+            delay: Duration::ZERO,
+        };
+        let report = client
+            .bench("localhost", server.local_addr().port(), &options)
+            .await
+            .expect("bench");
+        (report, client)
+    }
+
+    fn written(format: OutputFormat, report: &BenchReport, client: &TlsAwareClient) -> String {
+        let mut out = Vec::new();
+        write_report(
+            &mut out,
+            format,
+            report,
+            &client.client_capabilities(),
+            &client.report_meta(),
+        )
+        .expect("write");
+        String::from_utf8(out).expect("UTF-8")
+    }
+
+    #[tokio::test]
+    async fn the_table_compares_both_configurations() {
+        let (report, client) = report().await;
+        let table = written(OutputFormat::Table, &report, &client);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(
+            lines[0],
+            format!("Handshake benchmark: localhost:{}", report.port)
+        );
+        assert!(lines[1].starts_with("configuration"), "{}", table);
+        let hybrid: Vec<&str> = lines[2].split_whitespace().collect();
+        let classical: Vec<&str> = lines[3].split_whitespace().collect();
+        assert_eq!(hybrid[..2], ["hybrid-preferred", "X25519MLKEM768"]);
+        assert_eq!(classical[..2], ["classical-only", "X25519"]);
+        // The byte columns are the runs' medians.
+        let run = |i: usize| &report.runs[i];
+        assert_eq!(hybrid[6], run(0).bytes_sent.to_string());
+        assert_eq!(classical[7], run(1).bytes_received.to_string());
+        assert!(lines[4].starts_with("hybrid-preferred offered: X25519MLKEM768, "));
+        assert!(lines[5].starts_with("classical-only offered: "));
+        assert!(!lines[5].contains("MLKEM"), "{}", table);
+        // No HelloRetryRequests, so no penalty lines.
+        assert_eq!(lines.len(), 6, "{}", table);
+    }
+
+    #[tokio::test]
+    async fn csv_and_json_carry_both_runs() {
+        let (report, client) = report().await;
+        let csv = written(OutputFormat::Csv, &report, &client);
+        let rows: Vec<Vec<&str>> = csv.lines().map(|l| l.split(',').collect()).collect();
+        assert_eq!(rows.len(), 3, "{}", csv);
+        assert_eq!(rows[0][2..5], ["configuration", "negotiated", "iterations"]);
+        assert_eq!(rows[1][2..5], ["hybrid-preferred", "X25519MLKEM768", "2"]);
+        assert_eq!(rows[2][2..5], ["classical-only", "X25519", "2"]);
+
+        let json: Value =
+            serde_json::from_str(&written(OutputFormat::Json, &report, &client)).expect("JSON");
+        assert!(json["meta"].is_object());
+        assert!(json["client"]["kx_groups"].is_array());
+        let runs = json["runs"].as_array().expect("runs");
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0]["preference"], "hybrid-preferred");
+        assert_eq!(runs[1]["samples"].as_array().map(Vec::len), Some(2));
+        assert_eq!(
+            runs[1]["samples"][0]["metadata"]["group"],
+            Value::from("X25519")
+        );
+    }
+}
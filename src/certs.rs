@@ -0,0 +1,164 @@
+//! `--show-certs` and `--certs-out`: the certificate chain a server
+//! presented, as PEM.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use pqctracer::cert::{self, CertificateSummary};
+use rustls::pki_types::CertificateDer;
+
+/// Options for looking at the chains servers present.
+#[derive(Args)]
+pub struct CertArgs {
+    /// Print the certificate chain each server presented, as PEM with a
+    /// summary line per certificate.
+    #[arg(long)]
+    pub show_certs: bool,
+
+    /// Write the certificate chain each server presented to this directory,
+    /// one subdirectory per target and one PEM file per certificate.
+    #[arg(long, value_name = "DIR")]
+    pub certs_out: Option<PathBuf>,
+}
+
+/// Print `chain` as PEM, each certificate after a summary line with its
+/// position in the chain.
+pub fn print_chain(out: &mut dyn Write, chain: &[CertificateDer<'_>]) -> io::Result<()> {
+    for (index, der) in chain.iter().enumerate() {
+        match CertificateSummary::of(der) {
+            Some(summary) => writeln!(out, "Certificate {}: {}", index, summary)?,
+            None => writeln!(out, "Certificate {}: unparsable", index)?,
+        }
+        write!(out, "{}", cert::to_pem(der))?;
+    }
+    Ok(())
+}
+
+/// Write `chain` to `dir/<target>/<index>-<sha256>.pem` and return the
+/// paths written.
+pub fn write_chain(
+    dir: &Path,
+    target: &str,
+    chain: &[CertificateDer<'_>],
+) -> io::Result<Vec<PathBuf>> {
+    let dir = dir.join(file_name(target));
+    fs::create_dir_all(&dir)?;
+    let mut paths = Vec::with_capacity(chain.len());
+    for (index, der) in chain.iter().enumerate() {
+        let name = match cert::fingerprint(der) {
+            Some(fingerprint) => format!("{}-{}.pem", index, fingerprint.to_hex()),
+            None => format!("{}.pem", index),
+        };
+        let path = dir.join(name);
+        fs::write(&path, cert::to_pem(der))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// `target` with everything but letters, digits, `.`, `-` and `_`
+/// replaced, e.g. `example.com_8443` for `https://example.com:8443`.
+fn file_name(target: &str) -> String {
+    let target = target.split_once("://").map_or(target, |(_, rest)| rest);
+    target
+        .trim_end_matches('/')
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pqctracer::TlsAwareClient;
+    use pqctracer::testutil::TestTlsServer;
+    use rustls::pki_types::pem::PemObject;
+
+    use super::*;
+
+    /// A server sending a chain through two intermediates, and the chain a
+    /// client trusting its root captured.
+    async fn chain() -> (TestTlsServer, Vec<CertificateDer<'static>>) {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let server = TestTlsServer::builder()
+            .intermediates(2)
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let client = TlsAwareClient::builder()
+            .add_root_certificate(server.cert_der().clone())
+            .build()
+            .expect("client");
+        let response = client.get(server.url()).send().await.expect("request");
+        let chain = response.metadata.peer_certificates.clone();
+        (server, chain)
+    }
+
+    fn parse(pem: &[u8]) -> Vec<CertificateDer<'static>> {
+        CertificateDer::pem_slice_iter(pem)
+            .collect::<Result<_, _>>()
+            .expect("PEM certificates")
+    }
+
+    /// The subject and issuer of `der`.
+    fn names(der: &CertificateDer<'_>) -> (String, String) {
+        let summary = CertificateSummary::of(der).expect("parsable");
+        (summary.subject, summary.issuer)
+    }
+
+    #[tokio::test]
+    async fn printed_chains_parse_back_into_the_servers_chain() {
+        let (server, chain) = chain().await;
+        assert_eq!(chain.len(), 3);
+        let mut out = Vec::new();
+        print_chain(&mut out, &chain).expect("print");
+        assert_eq!(parse(&out), chain);
+
+        // End-entity first, each certificate issued by the next, the last
+        // by the root the server was configured with.
+        assert_eq!(names(&chain[0]).1, names(&chain[1]).0);
+        assert_eq!(names(&chain[1]).1, names(&chain[2]).0);
+        let root = names(server.cert_der()).0;
+        assert_eq!(root, "CN=pqctracer test root");
+        assert_eq!(names(&chain[2]).1, root);
+
+        let printed = String::from_utf8(out).expect("UTF-8");
+        let summaries: Vec<&str> = printed
+            .lines()
+            .filter(|line| line.starts_with("Certificate "))
+            .collect();
+        assert_eq!(summaries.len(), 3);
+        assert!(
+            summaries[2].starts_with(
+                "Certificate 2: subject=CN=pqctracer test intermediate 1 \
+                 issuer=CN=pqctracer test root key=ECDSA P-256 not_after="
+            ),
+            "{}",
+            summaries[2]
+        );
+    }
+
+    #[tokio::test]
+    async fn written_chains_are_named_by_index_and_fingerprint() {
+        let (server, chain) = chain().await;
+        let dir = std::env::temp_dir().join(format!("pqctracer-certs-{}", std::process::id()));
+        let target = format!("https://localhost:{}/", server.local_addr().port());
+        let paths = write_chain(&dir, &target, &chain).expect("write");
+
+        let expected_dir = dir.join(format!("localhost_{}", server.local_addr().port()));
+        assert_eq!(paths.len(), 3);
+        for (index, (path, der)) in paths.iter().zip(&chain).enumerate() {
+            let name = format!("{}-{}.pem", index, cert::fingerprint(der).unwrap().to_hex());
+            assert_eq!(*path, expected_dir.join(name));
+            assert_eq!(
+                parse(&fs::read(path).expect("read")),
+                std::slice::from_ref(der)
+            );
+        }
+        fs::remove_dir_all(&dir).expect("clean up");
+    }
+}
@@ -0,0 +1,234 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use pqctracer::scan::ScanResult;
+
+/// An append-only JSONL file of finished scan results.
+///
+/// Every line is a complete `--format jsonl` record, so the checkpoint is
+/// also usable as partial output of an interrupted scan.
+pub struct Checkpoint {
+    file: File,
+    failed: bool,
+}
+
+impl Checkpoint {
+    /// Open (or create) the checkpoint at `path` and return the results it
+    /// already holds.
+    ///
+    /// Lines that do not parse – typically a record cut short by a crash –
+    /// are skipped with a warning.
+    pub fn open(path: &Path) -> io::Result<(Self, Vec<ScanResult>)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        let mut results = Vec::new();
+        let mut skipped = 0;
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(result) => results.push(result),
+                Err(_) => skipped += 1,
+            }
+        }
+        if skipped > 0 {
+            eprintln!(
+                "Warning: skipped {} unreadable line(s) in checkpoint {}",
+                skipped,
+                path.display()
+            );
+        }
+
+        // Terminate a partially written last line so the next record starts
+        // on a line of its own.
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            file.write_all(b"\n")?;
+        }
+
+        Ok((
+            Self {
+                file,
+                failed: false,
+            },
+            results,
+        ))
+    }
+
+    /// Append one result. Write errors are reported once and otherwise
+    /// ignored, so a full disk does not abort the scan itself.
+    pub fn record(&mut self, result: &ScanResult) {
+        let mut line = serde_json::to_string(result).expect("result serializes");
+        line.push('\n');
+        if let Err(e) = self.file.write_all(line.as_bytes()) {
+            if !self.failed {
+                eprintln!("Warning: failed to write checkpoint: {}", e);
+            }
+            self.failed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use clap::Parser;
+    use pqctracer::testutil::TestTlsServer;
+
+    use super::*;
+    use crate::{Cli, Command};
+
+    /// A scratch file unique to `name` and this process, removed if left
+    /// over from an earlier run.
+    fn scratch(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "pqctracer-checkpoint-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    /// `scan` with `flags` after the targets, run as from the command line.
+    async fn scan(targets: &[String], flags: &[&str]) -> std::process::ExitCode {
+        let args = ["untitled", "scan", "--insecure", "--concurrency", "1"]
+            .into_iter()
+            .map(str::to_string)
+            .chain(targets.iter().cloned())
+            .chain(flags.iter().map(|flag| flag.to_string()));
+        match Cli::try_parse_from(args).expect("arguments").command {
+            Some(Command::Scan(args)) => crate::scan::scan(args).await,
+            _ => unreachable!(),
+        }
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap_or_default()
+    }
+
+    fn records(path: &Path) -> Vec<ScanResult> {
+        read(path)
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn an_interrupted_scan_resumes_with_exactly_the_remaining_targets() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let mut servers = Vec::new();
+        for _ in 0..8 {
+            let server = TestTlsServer::builder()
+                .bind("127.0.0.1:0")
+                .await
+                .expect("bind test server");
+            servers.push(server);
+        }
+        let targets: Vec<String> = servers.iter().map(TestTlsServer::url).collect();
+        let checkpoint = scratch("interrupted.jsonl");
+        let report = scratch("report.jsonl");
+        let checkpoint_flag = checkpoint.to_str().expect("UTF-8 path");
+        let report_flag = report.to_str().expect("UTF-8 path");
+        // One request every 50 ms, so that the scan is caught mid-way.
+        let first_run = ["--rate", "20", "--checkpoint", checkpoint_flag];
+
+        let mut run = Box::pin(scan(&targets, &first_run));
+        loop {
+            tokio::select! {
+                _ = &mut run => panic!("the scan finished before it was interrupted"),
+                _ = tokio::time::sleep(Duration::from_millis(2)) => {}
+            }
+            if read(&checkpoint).lines().count() >= 3 {
+                break;
+            }
+        }
+        drop(run);
+        let done: Vec<String> = records(&checkpoint).into_iter().map(|r| r.target).collect();
+        assert!(
+            (3..targets.len()).contains(&done.len()),
+            "{} done",
+            done.len()
+        );
+        assert_eq!(done, targets[..done.len()]);
+        // A crash in the middle of writing the next record.
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&checkpoint)
+            .expect("open checkpoint");
+        file.write_all(br#"{"target":"https://localhost:"#)
+            .expect("write");
+        // Let the server finish with a request dropped mid-way.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let before: Vec<usize> = servers.iter().map(|s| s.observed().len()).collect();
+
+        let resume = [
+            "--checkpoint",
+            checkpoint_flag,
+            "--format",
+            "jsonl",
+            "--output",
+            report_flag,
+        ];
+        scan(&targets, &resume).await;
+        let scanned: Vec<usize> = servers
+            .iter()
+            .zip(before)
+            .map(|(s, before)| s.observed().len() - before)
+            .collect();
+        let expected: Vec<usize> = (0..targets.len())
+            .map(|i| usize::from(i >= done.len()))
+            .collect();
+        assert_eq!(scanned, expected);
+
+        // The report has every target once, in input order, and the
+        // checkpoint every record.
+        let reported: Vec<String> = records(&report).into_iter().map(|r| r.target).collect();
+        assert_eq!(reported, targets);
+        let recorded = records(&checkpoint);
+        assert_eq!(recorded.len(), targets.len());
+        assert!(recorded.iter().all(|r| r.error.is_none()));
+
+        // A third run has nothing left to do.
+        let before: Vec<usize> = servers.iter().map(|s| s.observed().len()).collect();
+        scan(&targets, &resume).await;
+        let after: Vec<usize> = servers.iter().map(|s| s.observed().len()).collect();
+        assert_eq!(after, before);
+        assert_eq!(records(&report).len(), targets.len());
+
+        std::fs::remove_file(&checkpoint).expect("remove checkpoint");
+        std::fs::remove_file(&report).expect("remove report");
+    }
+
+    #[test]
+    fn unreadable_lines_are_skipped_and_the_last_one_terminated() {
+        let path = scratch("truncated.jsonl");
+        let (mut checkpoint, done) = Checkpoint::open(&path).expect("create");
+        assert!(done.is_empty());
+        let record = r#"{"target": "https://example.com", "url": "https://example.com/",
+            "metadata": {}, "attempts": 1, "duration_ms": 5}"#;
+        let result: ScanResult = serde_json::from_str(record).expect("result");
+        checkpoint.record(&result);
+        drop(checkpoint);
+        let mut file = OpenOptions::new().append(true).open(&path).expect("open");
+        file.write_all(b"not json\n{\"target\": \"https://exa")
+            .expect("write");
+        drop(file);
+
+        let (mut checkpoint, done) = Checkpoint::open(&path).expect("reopen");
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].target, "https://example.com");
+        checkpoint.record(&result);
+        drop(checkpoint);
+        // The record after the cut-off one starts on a line of its own.
+        let (_, done) = Checkpoint::open(&path).expect("reopen");
+        assert_eq!(done.len(), 2);
+        std::fs::remove_file(&path).expect("remove checkpoint");
+    }
+}
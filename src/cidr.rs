@@ -0,0 +1,193 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// An IPv4 or IPv6 network in CIDR notation, e.g. `203.0.113.0/28` or
+/// `2001:db8::/120`. A bare address is a network of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Number of addresses in the network, saturating for IPv6 networks too
+    /// large to count.
+    pub fn size(&self) -> u128 {
+        1u128
+            .checked_shl(u32::from(self.bits() - self.prefix))
+            .unwrap_or(u128::MAX)
+    }
+
+    /// Every address in the network, network and broadcast addresses
+    /// included: edge fleets often answer on all of them.
+    pub fn addresses(&self) -> impl Iterator<Item = IpAddr> + use<> {
+        let network = self.network;
+        let first = match network {
+            IpAddr::V4(ip) => u128::from(u32::from(ip)),
+            IpAddr::V6(ip) => u128::from(ip),
+        };
+        (0..self.size()).map(move |offset| match network {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::from((first + offset) as u32)),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::from(first + offset)),
+        })
+    }
+
+    /// Whether `ip` lies in the network. IPv4 addresses never match IPv6
+    /// networks and vice versa.
+    #[cfg(feature = "serve-api")]
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let host_bits = u32::from(self.bits() - self.prefix);
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                u32::from(ip).checked_shr(host_bits).unwrap_or(0)
+                    == u32::from(network).checked_shr(host_bits).unwrap_or(0)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                u128::from(ip).checked_shr(host_bits).unwrap_or(0)
+                    == u128::from(network).checked_shr(host_bits).unwrap_or(0)
+            }
+            _ => false,
+        }
+    }
+
+    fn bits(&self) -> u8 {
+        match self.network {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, prefix) = match s.split_once('/') {
+            Some((ip, prefix)) => (ip, Some(prefix)),
+            None => (s, None),
+        };
+        let ip: IpAddr = ip
+            .trim_matches(['[', ']'])
+            .parse()
+            .map_err(|_| format!("invalid network address: {}", s))?;
+        let bits = if ip.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= bits)
+                .ok_or_else(|| format!("invalid prefix length in {}", s))?,
+            None => bits,
+        };
+
+        // Clear the host bits so that 203.0.113.7/28 means 203.0.113.0/28.
+        let network = match ip {
+            IpAddr::V4(ip) => {
+                let mask = u32::MAX.checked_shl(u32::from(32 - prefix)).unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(u32::from(ip) & mask))
+            }
+            IpAddr::V6(ip) => {
+                let mask = u128::MAX.checked_shl(u32::from(128 - prefix)).unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(ip) & mask))
+            }
+        };
+        Ok(Self { network, prefix })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(s: &str) -> Cidr {
+        s.parse().unwrap_or_else(|e| panic!("{}: {}", s, e))
+    }
+
+    fn addresses(s: &str) -> Vec<String> {
+        cidr(s).addresses().map(|ip| ip.to_string()).collect()
+    }
+
+    #[test]
+    fn malformed_networks_are_rejected() {
+        for s in ["", "example.com", "203.0.113/24", "::g/64"] {
+            assert!(
+                s.parse::<Cidr>()
+                    .unwrap_err()
+                    .starts_with("invalid network address: "),
+                "{}",
+                s
+            );
+        }
+        for s in [
+            "203.0.113.0/33",
+            "2001:db8::/129",
+            "203.0.113.0/-1",
+            "203.0.113.0/x",
+            "203.0.113.0/",
+        ] {
+            assert_eq!(
+                s.parse::<Cidr>().unwrap_err(),
+                format!("invalid prefix length in {}", s)
+            );
+        }
+    }
+
+    #[test]
+    fn host_bits_are_cleared() {
+        assert_eq!(cidr("203.0.113.7/28"), cidr("203.0.113.0/28"));
+        assert_eq!(cidr("[2001:db8::1]/120"), cidr("2001:db8::/120"));
+        assert_eq!(addresses("203.0.113.7/28")[0], "203.0.113.0");
+    }
+
+    #[test]
+    fn single_addresses_are_networks_of_one() {
+        for s in ["203.0.113.7/32", "203.0.113.7"] {
+            assert_eq!(cidr(s).size(), 1);
+            assert_eq!(addresses(s), ["203.0.113.7"]);
+        }
+        for s in ["2001:db8::7/128", "2001:db8::7", "[2001:db8::7]"] {
+            assert_eq!(cidr(s).size(), 1);
+            assert_eq!(addresses(s), ["2001:db8::7"]);
+        }
+    }
+
+    #[test]
+    fn whole_address_spaces_are_counted() {
+        assert_eq!(cidr("0.0.0.0/0").size(), 1 << 32);
+        assert_eq!(cidr("::/1").size(), 1 << 127);
+        // 2^128 does not fit.
+        assert_eq!(cidr("::/0").size(), u128::MAX);
+    }
+
+    #[test]
+    fn addresses_include_the_network_and_broadcast_address() {
+        assert_eq!(
+            addresses("203.0.113.4/30"),
+            ["203.0.113.4", "203.0.113.5", "203.0.113.6", "203.0.113.7"]
+        );
+        assert_eq!(addresses("2001:db8::/127"), ["2001:db8::", "2001:db8::1"]);
+        assert_eq!(cidr("203.0.113.0/24").addresses().count(), 256);
+    }
+
+    #[cfg(feature = "serve-api")]
+    #[test]
+    fn contains_matches_the_network_bits() {
+        let v4 = cidr("203.0.113.0/28");
+        assert!(v4.contains("203.0.113.0".parse().unwrap()));
+        assert!(v4.contains("203.0.113.15".parse().unwrap()));
+        assert!(!v4.contains("203.0.113.16".parse().unwrap()));
+        assert!(!v4.contains("::ffff:203.0.113.1".parse().unwrap()));
+
+        let v6 = cidr("2001:db8::/120");
+        assert!(v6.contains("2001:db8::ff".parse().unwrap()));
+        assert!(!v6.contains("2001:db8::100".parse().unwrap()));
+        assert!(!v6.contains("203.0.113.1".parse().unwrap()));
+
+        assert!(cidr("0.0.0.0/0").contains("198.51.100.1".parse().unwrap()));
+        assert!(cidr("::/0").contains("2001:db8::1".parse().unwrap()));
+        assert!(cidr("203.0.113.7/32").contains("203.0.113.7".parse().unwrap()));
+        assert!(!cidr("203.0.113.7/32").contains("203.0.113.6".parse().unwrap()));
+        assert!(cidr("2001:db8::7/128").contains("2001:db8::7".parse().unwrap()));
+        assert!(!cidr("2001:db8::7/128").contains("2001:db8::6".parse().unwrap()));
+    }
+}
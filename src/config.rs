@@ -0,0 +1,1141 @@
+use std::env::VarError;
+use std::error::Error;
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::{Args, Subcommand, ValueEnum};
+use pqctracer::dns::SystemResolver;
+use pqctracer::readiness::ReadinessWeights;
+use pqctracer::scan::ScanMethod;
+use pqctracer::{
+    CertPolicy, CookieJar, KxPreference, PinPolicy, Sha256Digest, TlsAwareClient,
+    TlsAwareClientBuilder,
+};
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::pem::PemObject;
+use serde::Deserialize;
+
+use crate::OutputFormat;
+
+/// Settings that can come from the built-in defaults, a `--config` file or
+/// command-line flags.
+///
+/// Every value is optional so that each source can be read as a layer; the
+/// layers are combined in [`Config::layered`], the one place that decides
+/// which source wins.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub client: ClientSettings,
+    pub scan: ScanSettings,
+    pub targets: TargetSettings,
+    pub output: OutputSettings,
+    pub readiness: ReadinessSettings,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ClientSettings {
+    /// Total timeout for each HTTP request, in seconds.
+    pub timeout: Option<f64>,
+    /// Timeout for establishing each TCP connection, in seconds.
+    pub connect_timeout: Option<f64>,
+    /// PEM file with additional root certificates.
+    pub cacert: Option<PathBuf>,
+    /// Netscape cookie file to load cookies from and save them to.
+    pub cookie_jar: Option<PathBuf>,
+    /// Proxy URL for HTTP requests.
+    pub proxy: Option<String>,
+    /// Local address to connect from.
+    pub local_addr: Option<IpAddr>,
+    /// Network interface to connect through.
+    pub interface: Option<String>,
+    pub insecure: Option<bool>,
+    /// Whether a certificate that fails verification fails the request.
+    pub cert_policy: Option<CertPolicy>,
+    /// Public-key pins, `host=base64digest`.
+    pub pins: Option<Vec<String>>,
+    /// Whether a key that matches none of its host's pins fails the request.
+    pub pin_policy: Option<PinPolicy>,
+    /// Application protocols to offer by ALPN instead of none.
+    pub alpn: Option<Vec<String>>,
+    pub kx: Option<KxPreference>,
+    /// Fail when a target negotiates a classical key exchange, or when the
+    /// crypto provider cannot offer anything else.
+    pub require_pqc: Option<bool>,
+    /// Retry failed handshakes with classical key exchange only.
+    pub fallback: Option<bool>,
+    /// Look up HTTPS DNS records before each request.
+    pub dns: Option<bool>,
+    /// Use ECH configurations found in HTTPS records.
+    pub ech: Option<bool>,
+    /// Check the certificate chain against the host's TLSA records.
+    pub dane: Option<bool>,
+    /// Measure how long each phase of a request takes.
+    pub timings: Option<bool>,
+    /// Report the ServerHello's extensions of handshakes the tracer drives
+    /// itself.
+    pub deep_inspect: Option<bool>,
+    /// Suppress notices, such as the one about a crypto provider without
+    /// post-quantum groups.
+    pub quiet: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ScanSettings {
+    pub concurrency: Option<usize>,
+    /// Targets of the same host traced at the same time; 0 for no cap.
+    pub max_per_host: Option<usize>,
+    pub retries: Option<u32>,
+    /// Requests per second across all workers.
+    pub rate: Option<f64>,
+    pub per_host_interval_ms: Option<u64>,
+    /// HEAD or GET.
+    pub method: Option<ScanMethod>,
+    /// Repeat HEAD requests the server rejects as GET requests.
+    pub fallback_get: Option<bool>,
+    pub checkpoint: Option<PathBuf>,
+    /// Warn when a certificate expires within this many days.
+    pub warn_expiry_days: Option<i64>,
+    /// Also probe which TLS versions every target accepts.
+    pub probe_versions: Option<bool>,
+    /// Also tell whether every target copes with a large ClientHello.
+    pub diagnose: Option<bool>,
+    /// Also compare the handshakes for HTTP/1.1 and HTTP/2 of every target.
+    pub compare_http_versions: Option<bool>,
+    /// Also resume a session with every target and check its key exchange.
+    pub check_resumption: Option<bool>,
+    /// Normalize the targets and scan each unique one once.
+    pub normalize: Option<bool>,
+    /// Tell targets with different paths apart when normalizing.
+    pub keep_paths: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TargetSettings {
+    pub list: Option<Vec<String>>,
+    /// File with one target per line.
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct OutputSettings {
+    pub format: Option<OutputFormat>,
+    /// Write the report to this file instead of standard output.
+    pub path: Option<PathBuf>,
+}
+
+/// How much each factor counts towards the quantum-readiness grade.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ReadinessSettings {
+    pub key_exchange: Option<u32>,
+    pub certificate: Option<u32>,
+    pub cipher: Option<u32>,
+    pub version: Option<u32>,
+}
+
+impl ReadinessSettings {
+    /// The weights, the built-in ones for factors not given.
+    pub fn weights(&self) -> ReadinessWeights {
+        let defaults = ReadinessWeights::default();
+        ReadinessWeights {
+            key_exchange: self.key_exchange.unwrap_or(defaults.key_exchange),
+            certificate: self.certificate.unwrap_or(defaults.certificate),
+            cipher: self.cipher.unwrap_or(defaults.cipher),
+            version: self.version.unwrap_or(defaults.version),
+        }
+    }
+}
+
+/// Combine two layers of settings, values set in `over` winning.
+trait Layer {
+    fn overlay(self, over: Self) -> Self;
+}
+
+macro_rules! layer {
+    ($ty:ident { $($field:ident),* $(,)? }) => {
+        impl Layer for $ty {
+            fn overlay(self, over: Self) -> Self {
+                Self { $($field: over.$field.or(self.$field)),* }
+            }
+        }
+    };
+}
+
+layer!(ClientSettings {
+    timeout,
+    connect_timeout,
+    cacert,
+    cookie_jar,
+    proxy,
+    local_addr,
+    interface,
+    insecure,
+    cert_policy,
+    pins,
+    pin_policy,
+    alpn,
+    kx,
+    require_pqc,
+    fallback,
+    dns,
+    ech,
+    dane,
+    timings,
+    deep_inspect,
+    quiet
+});
+layer!(ScanSettings {
+    concurrency,
+    max_per_host,
+    retries,
+    rate,
+    per_host_interval_ms,
+    method,
+    fallback_get,
+    checkpoint,
+    warn_expiry_days,
+    probe_versions,
+    diagnose,
+    compare_http_versions,
+    check_resumption,
+    normalize,
+    keep_paths
+});
+layer!(OutputSettings { format, path });
+layer!(ReadinessSettings {
+    key_exchange,
+    certificate,
+    cipher,
+    version
+});
+
+impl Layer for TargetSettings {
+    // Targets are replaced as a whole: listing targets on the command line
+    // should not also scan the file named in the config.
+    fn overlay(self, over: Self) -> Self {
+        if over.list.is_some() || over.file.is_some() {
+            over
+        } else {
+            self
+        }
+    }
+}
+
+impl Layer for Config {
+    fn overlay(self, over: Self) -> Self {
+        Self {
+            client: self.client.overlay(over.client),
+            scan: self.scan.overlay(over.scan),
+            targets: self.targets.overlay(over.targets),
+            output: self.output.overlay(over.output),
+            readiness: self.readiness.overlay(over.readiness),
+        }
+    }
+}
+
+impl Config {
+    fn defaults() -> Self {
+        Self {
+            client: ClientSettings {
+                insecure: Some(false),
+                cert_policy: Some(CertPolicy::Enforce),
+                pin_policy: Some(PinPolicy::Enforce),
+                kx: Some(KxPreference::HybridPreferred),
+                require_pqc: Some(false),
+                fallback: Some(false),
+                ..Default::default()
+            },
+            scan: ScanSettings {
+                concurrency: Some(8),
+                max_per_host: Some(2),
+                retries: Some(1),
+                method: Some(ScanMethod::Head),
+                fallback_get: Some(false),
+                probe_versions: Some(false),
+                diagnose: Some(false),
+                compare_http_versions: Some(false),
+                check_resumption: Some(false),
+                normalize: Some(true),
+                keep_paths: Some(false),
+                ..Default::default()
+            },
+            targets: TargetSettings::default(),
+            output: OutputSettings {
+                format: Some(OutputFormat::Table),
+                ..Default::default()
+            },
+            readiness: ReadinessSettings::default(),
+        }
+    }
+
+    /// The effective settings, each source overriding the previous one:
+    /// built-in defaults, the config file at `path` (or `PQC_TRACER_CONFIG`),
+    /// `PQC_TRACER_*` environment variables and the command-line flags in
+    /// `cli`.
+    pub fn layered(path: Option<&Path>, cli: Config) -> Result<Self, Box<dyn Error>> {
+        Self::layered_with(path, cli, &|name| std::env::var(name))
+    }
+
+    /// [`layered`](Self::layered), with the environment variables read
+    /// from `env`.
+    fn layered_with(path: Option<&Path>, cli: Config, env: Env) -> Result<Self, Box<dyn Error>> {
+        let path = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => env_var(env, "PQC_TRACER_CONFIG", |v| {
+                Ok::<_, String>(PathBuf::from(v))
+            })?,
+        };
+        let file = match path {
+            Some(path) => Self::load(&path)?,
+            None => Self::default(),
+        };
+        Ok(Self::defaults()
+            .overlay(file)
+            .overlay(Self::from_env(env)?)
+            .overlay(cli))
+    }
+
+    /// The settings given as environment variables; see [`ENV_VARS`].
+    fn from_env(env: Env) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            client: ClientSettings {
+                timeout: env_var(env, "PQC_TRACER_TIMEOUT", str::parse)?,
+                connect_timeout: env_var(env, "PQC_TRACER_CONNECT_TIMEOUT", str::parse)?,
+                cacert: env_var(env, "PQC_TRACER_CACERT", |v| Ok::<_, String>(v.into()))?,
+                cookie_jar: env_var(env, "PQC_TRACER_COOKIE_JAR", |v| {
+                    Ok::<_, String>(v.into())
+                })?,
+                proxy: env_var(env, "PQC_TRACER_PROXY", |v| Ok::<_, String>(v.to_string()))?,
+                local_addr: env_var(env, "PQC_TRACER_LOCAL_ADDR", str::parse)?,
+                interface: env_var(env, "PQC_TRACER_INTERFACE", |v| {
+                    Ok::<_, String>(v.to_string())
+                })?,
+                insecure: env_var(env, "PQC_TRACER_INSECURE", parse_bool)?,
+                cert_policy: env_var(env, "PQC_TRACER_CERT_POLICY", str::parse)?,
+                pins: env_var(env, "PQC_TRACER_PINS", |v| {
+                    Ok::<_, String>(v.split(',').map(|pin| pin.trim().to_string()).collect())
+                })?,
+                pin_policy: env_var(env, "PQC_TRACER_PIN_POLICY", str::parse)?,
+                alpn: env_var(env, "PQC_TRACER_ALPN", |v| {
+                    Ok::<_, String>(v.split(',').map(|p| p.trim().to_string()).collect())
+                })?,
+                kx: env_var(env, "PQC_TRACER_KX", str::parse)?,
+                require_pqc: env_var(env, "PQC_TRACER_REQUIRE_PQC", parse_bool)?,
+                fallback: env_var(env, "PQC_TRACER_FALLBACK", parse_bool)?,
+                dns: env_var(env, "PQC_TRACER_DNS", parse_bool)?,
+                ech: env_var(env, "PQC_TRACER_ECH", parse_bool)?,
+                dane: env_var(env, "PQC_TRACER_DANE", parse_bool)?,
+                timings: env_var(env, "PQC_TRACER_TIMINGS", parse_bool)?,
+                deep_inspect: env_var(env, "PQC_TRACER_DEEP_INSPECT", parse_bool)?,
+                quiet: env_var(env, "PQC_TRACER_QUIET", parse_bool)?,
+            },
+            scan: ScanSettings {
+                concurrency: env_var(env, "PQC_TRACER_CONCURRENCY", str::parse)?,
+                max_per_host: env_var(env, "PQC_TRACER_MAX_PER_HOST", str::parse)?,
+                retries: env_var(env, "PQC_TRACER_RETRIES", str::parse)?,
+                rate: env_var(env, "PQC_TRACER_RATE", str::parse)?,
+                per_host_interval_ms: env_var(env, "PQC_TRACER_PER_HOST_INTERVAL_MS", str::parse)?,
+                method: env_var(env, "PQC_TRACER_SCAN_METHOD", str::parse)?,
+                fallback_get: env_var(env, "PQC_TRACER_FALLBACK_GET", parse_bool)?,
+                checkpoint: env_var(env, "PQC_TRACER_CHECKPOINT", |v| Ok::<_, String>(v.into()))?,
+                warn_expiry_days: env_var(env, "PQC_TRACER_WARN_EXPIRY", str::parse)?,
+                probe_versions: env_var(env, "PQC_TRACER_PROBE_VERSIONS", parse_bool)?,
+                diagnose: env_var(env, "PQC_TRACER_DIAGNOSE", parse_bool)?,
+                compare_http_versions: env_var(env, "PQC_TRACER_HTTP_VERSIONS", parse_bool)?,
+                check_resumption: env_var(env, "PQC_TRACER_CHECK_RESUMPTION", parse_bool)?,
+                normalize: env_var(env, "PQC_TRACER_NORMALIZE", parse_bool)?,
+                keep_paths: env_var(env, "PQC_TRACER_KEEP_PATHS", parse_bool)?,
+            },
+            targets: TargetSettings::default(),
+            output: OutputSettings {
+                format: env_var(env, "PQC_TRACER_FORMAT", |v| {
+                    OutputFormat::from_str(v, true)
+                })?,
+                path: env_var(env, "PQC_TRACER_OUTPUT", |v| Ok::<_, String>(v.into()))?,
+            },
+            readiness: ReadinessSettings::default(),
+        })
+    }
+
+    /// Parse the TOML file at `path`, warning about keys it does not know.
+    ///
+    /// Relative paths inside the file are taken relative to the file itself.
+    fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let deserializer = toml::Deserializer::parse(&contents)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut config: Config = serde_ignored::deserialize(deserializer, |key| {
+            eprintln!("Warning: {}: unknown key `{}`", path.display(), key)
+        })
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for file in [
+            &mut config.client.cacert,
+            &mut config.client.cookie_jar,
+            &mut config.scan.checkpoint,
+            &mut config.targets.file,
+            &mut config.output.path,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *file = base.join(&*file);
+        }
+        Ok(config)
+    }
+}
+
+impl TargetSettings {
+    /// The inline targets followed by the ones in the target file.
+    pub fn collect(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut targets = self.list.clone().unwrap_or_default();
+        if let Some(path) = &self.file {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            targets.extend(
+                contents
+                    .lines()
+                    .map(|line| strip_comment(line).trim())
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string),
+            );
+        }
+        Ok(targets)
+    }
+}
+
+/// `line` up to its comment, which starts with a `#` at the start of the
+/// line or after whitespace; other `#`s, like URL fragments, are kept.
+fn strip_comment(line: &str) -> &str {
+    let comment = line
+        .match_indices('#')
+        .find(|&(at, _)| line[..at].is_empty() || line[..at].ends_with(char::is_whitespace));
+    match comment {
+        Some((at, _)) => &line[..at],
+        None => line,
+    }
+}
+
+impl ClientSettings {
+    pub fn build_client(&self) -> Result<TlsAwareClient, Box<dyn Error>> {
+        let client = self.client_builder()?.build()?;
+        let capabilities = client.client_capabilities();
+        if !capabilities.pqc_capable && !self.quiet.unwrap_or_default() {
+            eprintln!(
+                "WARNING: the {} crypto provider has no post-quantum key-exchange group, so every \
+                 handshake will be classical whatever the server supports (--quiet hides this)",
+                capabilities.provider
+            );
+        }
+        Ok(client)
+    }
+
+    /// Save the cookies `client` kept to the `cookie_jar` file, if one is set.
+    pub fn save_cookies(&self, client: &TlsAwareClient) -> Result<(), Box<dyn Error>> {
+        if let (Some(path), Some(jar)) = (&self.cookie_jar, client.cookie_jar()) {
+            jar.save(path)
+                .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// The builder [`build_client`](Self::build_client) builds, for clients
+    /// that need more than the settings.
+    pub fn client_builder(&self) -> Result<TlsAwareClientBuilder, Box<dyn Error>> {
+        let mut builder = TlsAwareClient::builder()
+            .danger_accept_invalid_certs(self.insecure.unwrap_or_default())
+            .certificate_policy(self.cert_policy.unwrap_or_default())
+            .pin_policy(self.pin_policy.unwrap_or_default())
+            .kx_preference(self.kx.unwrap_or_default())
+            .enable_ech(self.ech.unwrap_or_default())
+            .dane(self.dane.unwrap_or_default())
+            .collect_timings(self.timings.unwrap_or_default())
+            .deep_inspect(self.deep_inspect.unwrap_or_default())
+            .require_pqc_capability(self.require_pqc.unwrap_or_default())
+            .fallback_to_classical(self.fallback.unwrap_or_default());
+        if self.dns.unwrap_or_default()
+            || self.ech.unwrap_or_default()
+            || self.dane.unwrap_or_default()
+        {
+            builder = builder.https_resolver(SystemResolver::new());
+        }
+        if let Some(timeout) = self.timeout {
+            let timeout = Duration::try_from_secs_f64(timeout)
+                .map_err(|e| format!("invalid timeout {}: {}", timeout, e))?;
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            let timeout = Duration::try_from_secs_f64(timeout)
+                .map_err(|e| format!("invalid connect timeout {}: {}", timeout, e))?;
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(path) = &self.cacert {
+            let certs = CertificateDer::pem_file_iter(path)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            if certs.is_empty() {
+                return Err(format!("no certificates found in {}", path.display()).into());
+            }
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        if let Some(path) = &self.cookie_jar {
+            let jar = CookieJar::load(path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            builder = builder.cookie_jar(Arc::new(jar));
+        }
+        if let Some(proxy) = &self.proxy {
+            let proxy =
+                reqwest::Proxy::all(proxy).map_err(|_| format!("invalid proxy URL: {}", proxy))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(address) = self.local_addr {
+            builder = builder.local_address(address);
+        }
+        if let Some(interface) = &self.interface {
+            #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+            {
+                builder = builder.interface(interface);
+            }
+            #[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+            return Err(format!(
+                "cannot connect through {}: only supported on Linux",
+                interface
+            )
+            .into());
+        }
+        for pin in self.pins.iter().flatten() {
+            let (host, digest) = pin
+                .split_once('=')
+                .ok_or_else(|| format!("invalid pin {}: expected HOST=DIGEST", pin))?;
+            let digest: Sha256Digest = digest.parse()?;
+            builder = builder.pin_spki(host, &[digest]);
+        }
+        if let Some(protocols) = &self.alpn {
+            let protocols: Vec<&str> = protocols.iter().map(String::as_str).collect();
+            builder = builder.alpn_protocols(&protocols);
+        }
+        Ok(builder)
+    }
+}
+
+/// The environment variables read by [`Config::layered`], for `--help`.
+pub const ENV_VARS: &str = "\
+Environment variables (overridden by flags, override the config file):
+  PQC_TRACER_CONFIG                Config file used when --config is not given
+  PQC_TRACER_TIMEOUT               Request timeout in seconds
+  PQC_TRACER_CONNECT_TIMEOUT       Connect timeout in seconds
+  PQC_TRACER_CACERT                PEM file with additional root certificates
+  PQC_TRACER_COOKIE_JAR            Netscape cookie file to load and save
+  PQC_TRACER_PROXY                 Proxy URL for HTTP requests
+  PQC_TRACER_LOCAL_ADDR            Local address to connect from
+  PQC_TRACER_INTERFACE             Network interface to connect through (Linux)
+  PQC_TRACER_INSECURE              Accept invalid certificates (true/false)
+  PQC_TRACER_CERT_POLICY           enforce or report-only
+  PQC_TRACER_PINS                  Comma-separated public-key pins, host=digest
+  PQC_TRACER_PIN_POLICY            enforce or report
+  PQC_TRACER_ALPN                  Comma-separated ALPN protocols to offer
+  PQC_TRACER_KX                    hybrid-preferred or classical-only
+  PQC_TRACER_REQUIRE_PQC           Fail on classical key exchange (true/false)
+  PQC_TRACER_FALLBACK              Retry failed handshakes classical-only (true/false)
+  PQC_TRACER_DNS                   Look up HTTPS DNS records (true/false)
+  PQC_TRACER_ECH                   Use ECH configs from DNS (true/false)
+  PQC_TRACER_DANE                  Check certificates against TLSA records (true/false)
+  PQC_TRACER_TIMINGS               Measure DNS, connect, TLS and TTFB (true/false)
+  PQC_TRACER_DEEP_INSPECT          Report ServerHello extensions (true/false)
+  PQC_TRACER_QUIET                 Suppress notices (true/false)
+  PQC_TRACER_CONCURRENCY           Scan concurrency
+  PQC_TRACER_MAX_PER_HOST          Scan concurrency per host (0 for no cap)
+  PQC_TRACER_RETRIES               Scan retries per target
+  PQC_TRACER_RATE                  Scan requests per second
+  PQC_TRACER_PER_HOST_INTERVAL_MS  Minimum scan interval per host
+  PQC_TRACER_SCAN_METHOD           HEAD or GET
+  PQC_TRACER_FALLBACK_GET          Repeat rejected HEAD requests as GET (true/false)
+  PQC_TRACER_CHECKPOINT            Scan checkpoint file
+  PQC_TRACER_WARN_EXPIRY           Warn when a certificate expires within DAYS
+  PQC_TRACER_PROBE_VERSIONS        Probe accepted TLS versions in scans (true/false)
+  PQC_TRACER_DIAGNOSE              Probe ClientHello size tolerance in scans (true/false)
+  PQC_TRACER_HTTP_VERSIONS         Compare HTTP/1.1 and HTTP/2 handshakes in scans (true/false)
+  PQC_TRACER_CHECK_RESUMPTION      Check the key exchange of resumed handshakes in scans (true/false)
+  PQC_TRACER_NORMALIZE             Normalize and deduplicate scan targets (true/false)
+  PQC_TRACER_KEEP_PATHS            Keep paths when normalizing scan targets (true/false)
+  PQC_TRACER_FORMAT                table, json, jsonl or csv
+  PQC_TRACER_OUTPUT                Report file";
+
+/// Where environment variables are read from: the process environment, or
+/// a fixed set of variables in tests.
+type Env<'a> = &'a dyn Fn(&str) -> Result<String, VarError>;
+
+/// Read and parse the environment variable `name`; unset or empty means
+/// "not configured". Errors name the variable.
+fn env_var<T, E: Display>(
+    env: Env,
+    name: &str,
+    parse: impl FnOnce(&str) -> Result<T, E>,
+) -> Result<Option<T>, String> {
+    match env(name) {
+        Ok(value) if value.is_empty() => Ok(None),
+        Ok(value) => parse(&value)
+            .map(Some)
+            .map_err(|e| format!("{}={:?}: {}", name, value, e)),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(_)) => Err(format!("{} is not valid UTF-8", name)),
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err("expected true or false".to_string()),
+    }
+}
+
+#[derive(Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Write a commented example configuration file.
+    Init {
+        /// Where to write the file.
+        #[arg(default_value = "pqctracer.toml")]
+        path: PathBuf,
+
+        /// Overwrite the file if it already exists.
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+const EXAMPLE: &str = r#"# pqctracer configuration. Environment variables (PQC_TRACER_*) override the
+# values here and flags given on the command line override both; every key
+# is optional.
+
+[client]
+# Total timeout for each HTTP request, in seconds.
+# timeout = 10
+# Timeout for establishing each TCP connection, in seconds.
+# connect_timeout = 3
+# PEM file with additional root certificates, relative to this file.
+# cacert = "internal-ca.pem"
+# Netscape cookie file, relative to this file: loaded before the run and
+# saved after it. It holds session cookies in plain text; keep it private.
+# cookie_jar = "cookies.txt"
+# Proxy for HTTP requests.
+# proxy = "http://proxy.example.com:3128"
+# Local address to connect from; targets are reached over addresses of its
+# family only.
+# local_addr = "192.0.2.10"
+# Network interface to connect through (Linux only).
+# interface = "eth1"
+# Accept invalid or self-signed certificates.
+# insecure = false
+# What a certificate that fails verification does: "enforce" fails the
+# request, "report-only" records the failure and carries on.
+# cert_policy = "enforce"
+# SHA-256 digests of the public keys expected from some hosts, in base64;
+# give several for the same host during a key rotation.
+# pins = ["example.com=47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="]
+# What a key that matches none of the pins does: "enforce" fails the request,
+# "report" records the mismatch and carries on.
+# pin_policy = "enforce"
+# Application protocols to offer by ALPN; none are offered by default.
+# alpn = ["http/1.1"]
+# Key-exchange groups to offer: "hybrid-preferred" or "classical-only".
+# kx = "hybrid-preferred"
+# Fail when a target negotiates a classical key exchange, or when the crypto
+# provider cannot offer anything else.
+# require_pqc = false
+# Retry a handshake that failed while offering hybrid groups with classical
+# groups only, and report whether that got through.
+# fallback = false
+# Look up HTTPS (type 65) DNS records and report their ALPN, ECH and hints.
+# dns = false
+# Encrypt the ClientHello when the HTTPS record has an ECH config; implies dns.
+# ech = false
+# Check the certificate chain against the host's TLSA records (DANE); implies
+# dns. DNSSEC is left to the resolver.
+# dane = false
+# Measure DNS resolution, TCP connect, TLS handshake and time to first byte.
+# timings = false
+# Report the extensions of the ServerHello in handshakes the tracer drives
+# itself, such as address scans and STARTTLS probes.
+# deep_inspect = false
+# Suppress notices, e.g. about a crypto provider without post-quantum groups.
+# quiet = false
+
+[scan]
+# concurrency = 8
+# Targets of the same host traced at the same time; 0 for no cap.
+# max_per_host = 2
+# retries = 1
+# Maximum requests per second across all workers, retries included.
+# rate = 10
+# Minimum time between two requests to the same host, in milliseconds.
+# per_host_interval_ms = 500
+# The method of every request; HEAD transfers little beyond the handshake.
+# method = "HEAD"
+# Repeat HEAD requests answered with 405 or 501 as GET requests.
+# fallback_get = false
+# Resume file for long scans, see `scan --checkpoint`.
+# checkpoint = "scan.checkpoint.jsonl"
+# Warn, and exit with an error, when a certificate expires within this many days.
+# warn_expiry_days = 14
+# Also probe which TLS versions every target accepts, see `scan --probe-versions`.
+# probe_versions = false
+# Also tell whether every target copes with a large ClientHello, see `scan --diagnose`.
+# diagnose = false
+# Also compare the handshakes for HTTP/1.1 and HTTP/2, see `scan --compare-http-versions`.
+# compare_http_versions = false
+# Also resume a session and flag PSK-only resumption, see `scan --check-resumption`.
+# check_resumption = false
+# Lowercase hosts, drop default ports and paths, and scan every unique target once.
+# normalize = true
+# Tell targets apart by path when normalizing, for sites that redirect by path.
+# keep_paths = false
+
+[targets]
+# list = ["www.google.com", "www.bing.com"]
+# One target per line; `#` at the start of a line or after a space starts a
+# comment, so URL fragments are kept.
+# file = "domains.txt"
+
+[readiness]
+# How much each factor counts towards the quantum-readiness grade; only the
+# ratios matter, and 0 leaves a factor out.
+# key_exchange = 50
+# certificate = 20
+# cipher = 15
+# version = 15
+
+[output]
+# "table", "json", "jsonl" or "csv".
+# format = "table"
+# Write the report to a file instead of standard output.
+# path = "report.json"
+"#;
+
+pub fn config(args: ConfigArgs) -> ExitCode {
+    match args.command {
+        ConfigCommand::Init { path, force } => {
+            if path.exists() && !force {
+                eprintln!(
+                    "{} already exists; pass --force to overwrite it",
+                    path.display()
+                );
+                return ExitCode::FAILURE;
+            }
+            if let Err(e) = std::fs::write(&path, EXAMPLE) {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+            println!("Wrote {}", path.display());
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// A config file with `contents`, unique to `name` and this process.
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pqctracer-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create config directory");
+        let path = dir.join(format!("{}.toml", name));
+        std::fs::write(&path, contents).expect("write config file");
+        path
+    }
+
+    fn layered(
+        file: Option<&Path>,
+        env: &[(&str, &str)],
+        cli: Config,
+    ) -> Result<Config, Box<dyn Error>> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Config::layered_with(file, cli, &|name| {
+            env.get(name).cloned().ok_or(VarError::NotPresent)
+        })
+    }
+
+    fn cli_scan(scan: ScanSettings) -> Config {
+        Config {
+            scan,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn defaults_apply_without_any_other_layer() {
+        let config = layered(None, &[], Config::default()).expect("layered");
+        assert_eq!(config.scan.concurrency, Some(8));
+        assert_eq!(config.scan.max_per_host, Some(2));
+        assert_eq!(config.scan.retries, Some(1));
+        assert_eq!(config.scan.method, Some(ScanMethod::Head));
+        assert_eq!(config.scan.normalize, Some(true));
+        assert_eq!(config.client.insecure, Some(false));
+        assert_eq!(config.client.kx, Some(KxPreference::HybridPreferred));
+        assert_eq!(config.client.cert_policy, Some(CertPolicy::Enforce));
+        assert_eq!(config.client.timeout, None);
+        assert!(matches!(config.output.format, Some(OutputFormat::Table)));
+        assert_eq!(config.output.path, None);
+        assert_eq!(config.targets.list, None);
+    }
+
+    /// Every combination of layers setting `scan.concurrency`, and the one
+    /// that must win.
+    #[test]
+    fn each_layer_overrides_the_ones_below() {
+        let file = config_file("concurrency", "[scan]\nconcurrency = 2\n");
+        for set in 0..8 {
+            let (in_file, in_env, in_cli) = (set & 1 != 0, set & 2 != 0, set & 4 != 0);
+            let env: &[(&str, &str)] = match in_env {
+                true => &[("PQC_TRACER_CONCURRENCY", "3")],
+                false => &[],
+            };
+            let cli = cli_scan(ScanSettings {
+                concurrency: in_cli.then_some(4),
+                ..Default::default()
+            });
+            let config = layered(in_file.then_some(file.as_path()), env, cli).expect("layered");
+            let expected = match (in_file, in_env, in_cli) {
+                (_, _, true) => 4,
+                (_, true, false) => 3,
+                (true, false, false) => 2,
+                (false, false, false) => 8,
+            };
+            assert_eq!(
+                config.scan.concurrency,
+                Some(expected),
+                "layers {:03b}",
+                set
+            );
+        }
+    }
+
+    #[test]
+    fn layers_override_field_by_field() {
+        let file = config_file(
+            "fields",
+            "[client]\ntimeout = 5.0\ninsecure = true\nkx = \"classical-only\"\n\
+             [scan]\nretries = 4\nrate = 2.5\n\
+             [output]\nformat = \"csv\"\n",
+        );
+        let env = [
+            ("PQC_TRACER_TIMEOUT", "6"),
+            ("PQC_TRACER_RATE", "7.5"),
+            ("PQC_TRACER_FORMAT", "json"),
+        ];
+        let cli = Config {
+            client: ClientSettings {
+                insecure: Some(false),
+                ..Default::default()
+            },
+            output: OutputSettings {
+                format: Some(OutputFormat::Jsonl),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let config = layered(Some(&file), &env, cli).expect("layered");
+        // The file alone, over the defaults.
+        assert_eq!(config.client.kx, Some(KxPreference::ClassicalOnly));
+        assert_eq!(config.scan.retries, Some(4));
+        // The environment over the file.
+        assert_eq!(config.client.timeout, Some(6.0));
+        assert_eq!(config.scan.rate, Some(7.5));
+        // The command line over both.
+        assert_eq!(config.client.insecure, Some(false));
+        assert!(matches!(config.output.format, Some(OutputFormat::Jsonl)));
+        // Nobody's, so the default.
+        assert_eq!(config.scan.concurrency, Some(8));
+    }
+
+    #[test]
+    fn readiness_weights_come_from_the_file_and_the_command_line() {
+        let file = config_file("readiness", "[readiness]\nkey_exchange = 70\ncipher = 5\n");
+        let cli = Config {
+            readiness: ReadinessSettings {
+                cipher: Some(10),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let weights = layered(Some(&file), &[], cli)
+            .expect("layered")
+            .readiness
+            .weights();
+        let defaults = ReadinessWeights::default();
+        assert_eq!(weights.key_exchange, 70);
+        assert_eq!(weights.cipher, 10);
+        assert_eq!(weights.certificate, defaults.certificate);
+        assert_eq!(weights.version, defaults.version);
+    }
+
+    #[test]
+    fn targets_are_replaced_as_a_whole() {
+        let file = config_file(
+            "targets",
+            "[targets]\nlist = [\"a.example\"]\nfile = \"targets.txt\"\n",
+        );
+        let config = layered(Some(&file), &[], Config::default()).expect("layered");
+        assert_eq!(config.targets.list, Some(vec!["a.example".to_string()]));
+        assert_eq!(
+            config.targets.file,
+            Some(file.parent().expect("directory").join("targets.txt"))
+        );
+
+        let cli = Config {
+            targets: TargetSettings {
+                list: Some(vec!["b.example".to_string()]),
+                file: None,
+            },
+            ..Default::default()
+        };
+        let config = layered(Some(&file), &[], cli).expect("layered");
+        assert_eq!(config.targets.list, Some(vec!["b.example".to_string()]));
+        assert_eq!(config.targets.file, None);
+    }
+
+    #[test]
+    fn the_config_file_can_be_named_in_the_environment() {
+        let named = config_file("named", "[scan]\nconcurrency = 11\n");
+        let given = config_file("given", "[scan]\nconcurrency = 12\n");
+        let env = [("PQC_TRACER_CONFIG", named.to_str().expect("UTF-8 path"))];
+        let config = layered(None, &env, Config::default()).expect("layered");
+        assert_eq!(config.scan.concurrency, Some(11));
+        // --config wins over PQC_TRACER_CONFIG.
+        let config = layered(Some(&given), &env, Config::default()).expect("layered");
+        assert_eq!(config.scan.concurrency, Some(12));
+    }
+
+    #[test]
+    fn file_paths_are_relative_to_the_file() {
+        let file = config_file(
+            "paths",
+            "[client]\ncacert = \"ca.pem\"\ncookie_jar = \"jar.txt\"\n[scan]\ncheckpoint = \"/var/lib/scan.jsonl\"\n\
+             [output]\npath = \"out/report.json\"\n",
+        );
+        let dir = file.parent().expect("directory");
+        let config = layered(Some(&file), &[], Config::default()).expect("layered");
+        assert_eq!(config.client.cacert, Some(dir.join("ca.pem")));
+        assert_eq!(config.client.cookie_jar, Some(dir.join("jar.txt")));
+        assert_eq!(
+            config.scan.checkpoint,
+            Some(PathBuf::from("/var/lib/scan.jsonl"))
+        );
+        assert_eq!(config.output.path, Some(dir.join("out/report.json")));
+        // Paths from the environment are left as they are.
+        let env = [("PQC_TRACER_OUTPUT", "report.json")];
+        let config = layered(Some(&file), &env, Config::default()).expect("layered");
+        assert_eq!(config.output.path, Some(PathBuf::from("report.json")));
+    }
+
+    #[test]
+    fn every_environment_variable_sets_its_setting() {
+        let env = [
+            ("PQC_TRACER_TIMEOUT", "1.5"),
+            ("PQC_TRACER_CONNECT_TIMEOUT", "0.5"),
+            ("PQC_TRACER_CACERT", "ca.pem"),
+            ("PQC_TRACER_COOKIE_JAR", "cookies.txt"),
+            ("PQC_TRACER_PROXY", "http://proxy.example:3128"),
+            ("PQC_TRACER_LOCAL_ADDR", "192.0.2.10"),
+            ("PQC_TRACER_INTERFACE", "eth1"),
+            ("PQC_TRACER_INSECURE", "yes"),
+            ("PQC_TRACER_CERT_POLICY", "report-only"),
+            ("PQC_TRACER_PINS", "a.example=AAAA, b.example=BBBB"),
+            ("PQC_TRACER_PIN_POLICY", "report"),
+            ("PQC_TRACER_ALPN", "http/1.1, acme-tls/1"),
+            ("PQC_TRACER_KX", "classical-only"),
+            ("PQC_TRACER_REQUIRE_PQC", "1"),
+            ("PQC_TRACER_FALLBACK", "on"),
+            ("PQC_TRACER_DNS", "true"),
+            ("PQC_TRACER_ECH", "TRUE"),
+            ("PQC_TRACER_DANE", "true"),
+            ("PQC_TRACER_TIMINGS", "true"),
+            ("PQC_TRACER_DEEP_INSPECT", "true"),
+            ("PQC_TRACER_QUIET", "true"),
+            ("PQC_TRACER_CONCURRENCY", "16"),
+            ("PQC_TRACER_MAX_PER_HOST", "0"),
+            ("PQC_TRACER_RETRIES", "3"),
+            ("PQC_TRACER_RATE", "20"),
+            ("PQC_TRACER_PER_HOST_INTERVAL_MS", "250"),
+            ("PQC_TRACER_SCAN_METHOD", "get"),
+            ("PQC_TRACER_FALLBACK_GET", "true"),
+            ("PQC_TRACER_CHECKPOINT", "scan.jsonl"),
+            ("PQC_TRACER_WARN_EXPIRY", "30"),
+            ("PQC_TRACER_PROBE_VERSIONS", "true"),
+            ("PQC_TRACER_DIAGNOSE", "true"),
+            ("PQC_TRACER_HTTP_VERSIONS", "true"),
+            ("PQC_TRACER_CHECK_RESUMPTION", "true"),
+            ("PQC_TRACER_NORMALIZE", "false"),
+            ("PQC_TRACER_KEEP_PATHS", "true"),
+            ("PQC_TRACER_FORMAT", "CSV"),
+            ("PQC_TRACER_OUTPUT", "report.csv"),
+        ];
+        let Config {
+            client,
+            scan,
+            output,
+            ..
+        } = layered(None, &env, Config::default()).expect("layered");
+        assert_eq!(client.timeout, Some(1.5));
+        assert_eq!(client.connect_timeout, Some(0.5));
+        assert_eq!(client.cacert, Some(PathBuf::from("ca.pem")));
+        assert_eq!(client.cookie_jar, Some(PathBuf::from("cookies.txt")));
+        assert_eq!(client.proxy.as_deref(), Some("http://proxy.example:3128"));
+        assert_eq!(client.local_addr, "192.0.2.10".parse().ok());
+        assert_eq!(client.interface.as_deref(), Some("eth1"));
+        assert_eq!(client.insecure, Some(true));
+        assert_eq!(client.cert_policy, Some(CertPolicy::ReportOnly));
+        assert_eq!(
+            client.pins,
+            Some(vec![
+                "a.example=AAAA".to_string(),
+                "b.example=BBBB".to_string()
+            ])
+        );
+        assert_eq!(client.pin_policy, Some(PinPolicy::Report));
+        assert_eq!(
+            client.alpn,
+            Some(vec!["http/1.1".to_string(), "acme-tls/1".to_string()])
+        );
+        assert_eq!(client.kx, Some(KxPreference::ClassicalOnly));
+        for flag in [
+            client.require_pqc,
+            client.fallback,
+            client.dns,
+            client.ech,
+            client.dane,
+            client.timings,
+            client.deep_inspect,
+            client.quiet,
+            scan.fallback_get,
+            scan.probe_versions,
+            scan.diagnose,
+            scan.compare_http_versions,
+            scan.check_resumption,
+            scan.keep_paths,
+        ] {
+            assert_eq!(flag, Some(true));
+        }
+        assert_eq!(scan.concurrency, Some(16));
+        assert_eq!(scan.max_per_host, Some(0));
+        assert_eq!(scan.retries, Some(3));
+        assert_eq!(scan.rate, Some(20.0));
+        assert_eq!(scan.per_host_interval_ms, Some(250));
+        assert_eq!(scan.method, Some(ScanMethod::Get));
+        assert_eq!(scan.checkpoint, Some(PathBuf::from("scan.jsonl")));
+        assert_eq!(scan.warn_expiry_days, Some(30));
+        assert_eq!(scan.normalize, Some(false));
+        assert!(matches!(output.format, Some(OutputFormat::Csv)));
+        assert_eq!(output.path, Some(PathBuf::from("report.csv")));
+    }
+
+    #[test]
+    fn empty_environment_variables_are_not_configured() {
+        let file = config_file("empty-env", "[scan]\nconcurrency = 2\n");
+        let env = [("PQC_TRACER_CONCURRENCY", ""), ("PQC_TRACER_INSECURE", "")];
+        let config = layered(Some(&file), &env, Config::default()).expect("layered");
+        assert_eq!(config.scan.concurrency, Some(2));
+        assert_eq!(config.client.insecure, Some(false));
+    }
+
+    #[test]
+    fn invalid_environment_variables_are_reported_by_name() {
+        let cases = [
+            (
+                "PQC_TRACER_TIMEOUT",
+                "soon",
+                "PQC_TRACER_TIMEOUT=\"soon\": ",
+            ),
+            (
+                "PQC_TRACER_INSECURE",
+                "maybe",
+                "PQC_TRACER_INSECURE=\"maybe\": expected true or false",
+            ),
+            (
+                "PQC_TRACER_CONCURRENCY",
+                "-1",
+                "PQC_TRACER_CONCURRENCY=\"-1\": ",
+            ),
+            (
+                "PQC_TRACER_LOCAL_ADDR",
+                "localhost",
+                "PQC_TRACER_LOCAL_ADDR=\"localhost\": ",
+            ),
+            ("PQC_TRACER_KX", "quantum", "PQC_TRACER_KX=\"quantum\": "),
+            (
+                "PQC_TRACER_SCAN_METHOD",
+                "POST",
+                "PQC_TRACER_SCAN_METHOD=\"POST\": ",
+            ),
+            ("PQC_TRACER_FORMAT", "xml", "PQC_TRACER_FORMAT=\"xml\": "),
+        ];
+        for (name, value, message) in cases {
+            let error = layered(None, &[(name, value)], Config::default())
+                .expect_err(name)
+                .to_string();
+            assert!(error.starts_with(message), "{}: {}", name, error);
+        }
+    }
+
+    #[test]
+    fn environment_variables_that_are_not_unicode_are_reported() {
+        let error = Config::layered_with(None, Config::default(), &|name| match name {
+            "PQC_TRACER_PROXY" => Err(VarError::NotUnicode("\u{fffd}".into())),
+            _ => Err(VarError::NotPresent),
+        })
+        .expect_err("not unicode")
+        .to_string();
+        assert_eq!(error, "PQC_TRACER_PROXY is not valid UTF-8");
+    }
+
+    #[test]
+    fn target_files_keep_url_fragments() {
+        let path = config_file(
+            "fragments",
+            "# production hosts\n\
+             https://a.example/#section\n\
+             b.example # the old one\n\
+             \t# indented comment\n\
+             https://c.example/page#top#again\t#trailing\n\
+             \n",
+        );
+        let targets = TargetSettings {
+            list: Some(vec!["inline.example".to_string()]),
+            file: Some(path),
+        };
+        assert_eq!(
+            targets.collect().expect("collect"),
+            [
+                "inline.example",
+                "https://a.example/#section",
+                "b.example",
+                "https://c.example/page#top#again",
+            ]
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! `--download`: saving the body of a traced response to a file, with the
+//! handshake reported as usual.
+
+use std::error::Error;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use pqctracer::{TlsMetadata, TlsResponse};
+use tokio::fs::{self, File};
+use tokio::io::AsyncWrite;
+
+/// How often the progress line is redrawn.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Options for saving the response body.
+#[derive(Args)]
+pub struct DownloadArgs {
+    /// Save the response body to this file, streaming it to disk with a
+    /// progress line on standard error. Takes a single target.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["repeat", "starttls"])]
+    pub download: Option<PathBuf>,
+
+    /// Keep what arrived of an interrupted or failed download as
+    /// <PATH>.partial instead of removing it.
+    #[arg(long, requires = "download")]
+    pub keep_partial: bool,
+}
+
+/// Stream the body of `response` to `path` and return the metadata.
+///
+/// The body goes to `<path>.partial` first and is only renamed to `path`
+/// once it is complete, so that a file at `path` is never cut short. On
+/// failure or Ctrl-C the partial file is removed, unless `keep_partial`.
+pub async fn download(
+    response: TlsResponse,
+    path: &Path,
+    keep_partial: bool,
+) -> Result<TlsMetadata, Box<dyn Error>> {
+    let partial = partial_path(path);
+    let expected = response.response.content_length();
+    let mut writer = Progress::new(File::create(&partial).await?, expected);
+    let start = Instant::now();
+
+    let outcome = tokio::select! {
+        streamed = response.stream_to(&mut writer) => {
+            writer.finish();
+            streamed.map_err(|e| Box::new(e) as Box<dyn Error>)
+        }
+        _ = tokio::signal::ctrl_c() => {
+            writer.finish();
+            Err("interrupted".into())
+        }
+    };
+    drop(writer);
+
+    match outcome {
+        Ok((written, metadata)) => {
+            fs::rename(&partial, path).await?;
+            println!(
+                "Downloaded: {} bytes to {} ({})",
+                written,
+                path.display(),
+                rate(written, start.elapsed())
+            );
+            Ok(metadata)
+        }
+        Err(e) if keep_partial => {
+            Err(format!("download failed: {}; kept {}", e, partial.display()).into())
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&partial).await;
+            Err(format!("download failed: {}", e).into())
+        }
+    }
+}
+
+/// `path` with `.partial` appended to its file name.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    path.with_file_name(name)
+}
+
+/// `12.3 MB/s`, in decimal megabytes.
+fn rate(bytes: u64, elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+    format!("{:.1} MB/s", bytes as f64 / seconds / 1e6)
+}
+
+/// Writes through to a file and redraws a progress line on standard error
+/// as the body arrives.
+struct Progress {
+    file: File,
+    expected: Option<u64>,
+    written: u64,
+    start: Instant,
+    drawn: Option<Instant>,
+}
+
+impl Progress {
+    fn new(file: File, expected: Option<u64>) -> Self {
+        Self {
+            file,
+            expected,
+            written: 0,
+            start: Instant::now(),
+            drawn: None,
+        }
+    }
+
+    fn draw(&mut self) {
+        let mut line = format!("\r{} bytes", self.written);
+        if let Some(expected) = self.expected.filter(|&expected| expected > 0) {
+            let percent = self.written as f64 / expected as f64 * 100.0;
+            line.push_str(&format!(" of {} ({:.0}%)", expected, percent));
+        }
+        line.push_str(&format!(", {}", rate(self.written, self.start.elapsed())));
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "{}", line);
+        let _ = stderr.flush();
+        self.drawn = Some(Instant::now());
+    }
+
+    /// Draw the final state and end the progress line, if one was started.
+    fn finish(&mut self) {
+        if self.drawn.is_some() {
+            self.draw();
+            eprintln!();
+        }
+    }
+}
+
+impl AsyncWrite for Progress {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.file).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = poll {
+            self.written += n as u64;
+            if self
+                .drawn
+                .is_none_or(|drawn| drawn.elapsed() >= PROGRESS_INTERVAL)
+            {
+                self.draw();
+            }
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.file).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.file).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pqctracer::TlsAwareClient;
+    use pqctracer::testutil::TestTlsServer;
+    use rustls::crypto::aws_lc_rs::cipher_suite::TLS13_AES_128_GCM_SHA256;
+
+    use super::*;
+
+    fn sha256(bytes: &[u8]) -> Vec<u8> {
+        let sha256 = TLS13_AES_128_GCM_SHA256
+            .tls13()
+            .unwrap()
+            .common
+            .hash_provider;
+        sha256.hash(bytes).as_ref().to_vec()
+    }
+
+    #[tokio::test]
+    async fn a_multi_megabyte_download_matches_its_checksum() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        // Three megabytes of pseudo-random letters.
+        let mut state: u32 = 7;
+        let body: String = (0..3 << 20)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                char::from(b'a' + (state >> 24) as u8 % 26)
+            })
+            .collect();
+        let checksum = sha256(body.as_bytes());
+        let served = body.clone();
+        let server = TestTlsServer::builder()
+            .http_handler(move |_| format!("200 OK\r\n\r\n{}", served))
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let client = TlsAwareClient::builder()
+            .add_root_certificate(server.cert_der().clone())
+            .build()
+            .expect("build client");
+        let path =
+            std::env::temp_dir().join(format!("pqctracer-download-{}.bin", std::process::id()));
+
+        let response = client.get(server.url()).send().await.expect("request");
+        let metadata = download(response, &path, false).await.expect("download");
+        let saved = std::fs::read(&path).expect("downloaded file");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(saved.len(), body.len());
+        assert_eq!(sha256(&saved), checksum);
+        assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+        // The partial file was renamed, not left behind.
+        assert!(!partial_path(&path).exists());
+    }
+
+    #[test]
+    fn partial_files_are_marked_as_such() {
+        assert_eq!(
+            partial_path(Path::new("mirror/artifact.tar.gz")),
+            Path::new("mirror/artifact.tar.gz.partial")
+        );
+    }
+}
@@ -0,0 +1,755 @@
+//! `--db` and `history`: every traced result kept in a SQLite database, to
+//! be queried with SQL months later.
+//!
+//! The database is written through the `sqlite3` command-line shell rather
+//! than a linked-in SQLite: one shell per database, fed statements on
+//! standard input and answering queries in its JSON output mode. Each batch
+//! of statements is followed by a marker query, so that the answer to the
+//! batch is everything printed before the marker's row; with `-bail` the
+//! shell exits on the first error, which is then read from its standard
+//! error.
+//!
+//! The shell is driven by a writer thread of its own, so that recording a
+//! result from an async scan callback only queues the row, however long
+//! the database takes. A shell that exited on an error is started again,
+//! with the schema migrated, before the next statement; the rows that were
+//! lost on the way are counted and reported by [`HistorySink::finish`].
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitCode, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use clap::Args;
+use pqctracer::cert::{fingerprint, rfc3339};
+use pqctracer::scan::ScanResult;
+use pqctracer::{ErrorCategory, TlsMetadata};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{OutputFormat, csv_field};
+
+/// The shell's answer to the marker query, on a line of its own.
+const END_MARKER: &str = r#"[{"pqctracer_end":1}]"#;
+
+/// The schema, one migration per version; `PRAGMA user_version` counts the
+/// ones a database has had.
+const MIGRATIONS: &[&str] = &["CREATE TABLE results (
+    id INTEGER PRIMARY KEY,
+    command TEXT NOT NULL,
+    target TEXT NOT NULL,
+    timestamp TEXT NOT NULL,
+    group_name TEXT,
+    cipher TEXT,
+    version TEXT,
+    cert_sha256 TEXT,
+    duration_ms REAL,
+    error_category TEXT,
+    error TEXT,
+    labels TEXT NOT NULL DEFAULT '{}'
+);
+CREATE INDEX results_target_timestamp ON results (target, timestamp);"];
+
+/// One open `sqlite3` shell.
+struct Shell {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Shell {
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut child = Command::new("sqlite3")
+            .args(["-batch", "-bail", "-json", "-cmd", ".timeout 5000"])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("failed to run sqlite3: {}", e)))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    /// Run `sql` and return the rows its queries printed.
+    fn run(&mut self, sql: &str) -> io::Result<Vec<Map<String, Value>>> {
+        let sent = writeln!(self.stdin, "{}\nSELECT 1 AS pqctracer_end;", sql)
+            .and_then(|()| self.stdin.flush());
+        if sent.is_err() {
+            return Err(self.failure());
+        }
+        let mut output = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line)? == 0 {
+                return Err(self.failure());
+            }
+            if line.trim_end() == END_MARKER {
+                break;
+            }
+            output.push_str(&line);
+        }
+        let mut rows = Vec::new();
+        for batch in serde_json::Deserializer::from_str(&output).into_iter::<Vec<_>>() {
+            rows.extend(batch.map_err(io::Error::other)?);
+        }
+        Ok(rows)
+    }
+
+    /// Whether the shell has exited, as it does after an error.
+    fn exited(&mut self) -> bool {
+        !matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Why the shell stopped: its last words on standard error.
+    fn failure(&mut self) -> io::Error {
+        let mut stderr = String::new();
+        if let Some(mut pipe) = self.child.stderr.take() {
+            let _ = pipe.read_to_string(&mut stderr);
+        }
+        let _ = self.child.wait();
+        match stderr.trim() {
+            "" => io::Error::other("sqlite3 exited unexpectedly"),
+            message => io::Error::other(format!("sqlite3: {}", message)),
+        }
+    }
+}
+
+impl Drop for Shell {
+    fn drop(&mut self) {
+        // Every batch has been answered by now; killing the shell loses
+        // nothing, and a hung one cannot keep the process waiting.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// One traced result as the database keeps it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryRow {
+    /// `trace`, `scan` or `watch`.
+    pub command: String,
+    pub target: String,
+    /// When the result was recorded, RFC 3339 in UTC.
+    pub timestamp: String,
+    pub group: Option<String>,
+    pub cipher: Option<String>,
+    pub version: Option<String>,
+    /// SHA-256 of the end-entity certificate, in hex.
+    pub cert_sha256: Option<String>,
+    pub duration_ms: Option<f64>,
+    /// The `kind` of the [`ErrorCategory`], e.g. `dns-failure`.
+    pub error_category: Option<String>,
+    pub error: Option<String>,
+    pub labels: BTreeMap<String, String>,
+}
+
+impl HistoryRow {
+    fn new(
+        command: &str,
+        target: &str,
+        metadata: &TlsMetadata,
+        duration: Option<Duration>,
+    ) -> Self {
+        Self {
+            command: command.to_string(),
+            target: target.to_string(),
+            timestamp: rfc3339(SystemTime::now()),
+            group: metadata.group.clone(),
+            cipher: metadata.cipher.clone(),
+            version: metadata.protocol_version().map(str::to_string),
+            cert_sha256: metadata
+                .peer_certificates
+                .first()
+                .and_then(fingerprint)
+                .map(|digest| digest.to_hex()),
+            duration_ms: duration.map(|d| d.as_secs_f64() * 1000.0),
+            error_category: None,
+            error: None,
+            labels: metadata.labels.clone(),
+        }
+    }
+
+    /// The row for a scan result of `command`.
+    pub fn of_scan(command: &str, result: &ScanResult) -> Self {
+        let mut row = Self::new(
+            command,
+            &result.target,
+            &result.metadata,
+            Some(result.duration),
+        );
+        if let Some(error) = &result.error {
+            row.error_category = Some(category_kind(&error.category));
+            row.error = Some(error.message.clone());
+        }
+        row
+    }
+
+    /// The row for a trace of `target` that took `duration`.
+    pub fn of_trace(
+        target: &str,
+        result: &Result<TlsMetadata, Box<dyn Error>>,
+        duration: Duration,
+    ) -> Self {
+        match result {
+            Ok(metadata) => Self::new("trace", target, metadata, Some(duration)),
+            Err(e) => {
+                let category = match e.downcast_ref::<reqwest::Error>() {
+                    Some(e) => ErrorCategory::of(e),
+                    None => ErrorCategory::Other,
+                };
+                Self {
+                    error_category: Some(category_kind(&category)),
+                    error: Some(e.to_string()),
+                    ..Self::new("trace", target, &TlsMetadata::default(), Some(duration))
+                }
+            }
+        }
+    }
+
+    fn from_row(row: &Map<String, Value>) -> io::Result<Self> {
+        let text = |column: &str| row.get(column).and_then(Value::as_str).map(str::to_string);
+        let required = |column: &str| {
+            text(column).ok_or_else(|| io::Error::other(format!("{} is missing", column)))
+        };
+        let labels = text("labels").unwrap_or_default();
+        Ok(Self {
+            command: required("command")?,
+            target: required("target")?,
+            timestamp: required("timestamp")?,
+            group: text("group_name"),
+            cipher: text("cipher"),
+            version: text("version"),
+            cert_sha256: text("cert_sha256"),
+            duration_ms: row.get("duration_ms").and_then(Value::as_f64),
+            error_category: text("error_category"),
+            error: text("error"),
+            labels: serde_json::from_str(&labels).unwrap_or_default(),
+        })
+    }
+}
+
+/// The kebab-case `kind` an [`ErrorCategory`] serializes with.
+fn category_kind(category: &ErrorCategory) -> String {
+    match serde_json::to_value(category) {
+        Ok(Value::Object(mut fields)) => match fields.remove("kind") {
+            Some(Value::String(kind)) => kind,
+            _ => "other".to_string(),
+        },
+        _ => "other".to_string(),
+    }
+}
+
+/// `value` as an SQL literal. Line breaks are spelled out with `char()`,
+/// so that no line of a statement can be mistaken for a shell command.
+fn literal(value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return "NULL".to_string();
+    };
+    let quoted: Vec<String> = value
+        .replace('\0', "")
+        .split('\n')
+        .map(|line| {
+            format!(
+                "'{}'",
+                line.replace('\'', "''").replace('\r', "' || char(13) || '")
+            )
+        })
+        .collect();
+    quoted.join(" || char(10) || ")
+}
+
+/// Open the shell of `path` and bring its schema up to date.
+fn connect(path: &Path) -> io::Result<Shell> {
+    let mut shell = Shell::open(path)?;
+    let version = shell.run("PRAGMA user_version;")?;
+    let version = version
+        .first()
+        .and_then(|row| row.get("user_version"))
+        .and_then(Value::as_u64)
+        .unwrap_or_default() as usize;
+    if version > MIGRATIONS.len() {
+        return Err(io::Error::other(format!(
+            "{} has schema version {}, newer than this build's {}",
+            path.display(),
+            version,
+            MIGRATIONS.len()
+        )));
+    }
+    for (applied, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        shell.run(&format!(
+            "BEGIN;\n{}\nPRAGMA user_version = {};\nCOMMIT;",
+            migration,
+            applied + 1
+        ))?;
+    }
+    Ok(shell)
+}
+
+/// Statements for the writer thread, in the order they are to run.
+enum Job {
+    /// A row to store, whose failure is only counted.
+    Record(String),
+    /// A query whose rows, or error, are sent back.
+    Query(String, Sender<io::Result<Vec<Map<String, Value>>>>),
+}
+
+/// The rows the writer thread failed to store.
+#[derive(Default)]
+struct Lost {
+    rows: usize,
+    first: Option<io::Error>,
+}
+
+/// The writer thread: runs every job on the shell, reopening it whenever
+/// an error made it exit.
+fn write(path: PathBuf, shell: Shell, jobs: Receiver<Job>) -> Lost {
+    let mut shell = Some(shell);
+    let mut lost = Lost::default();
+    for job in jobs {
+        let (sql, reply) = match job {
+            Job::Record(sql) => (sql, None),
+            Job::Query(sql, reply) => (sql, Some(reply)),
+        };
+        let done = match &mut shell {
+            Some(open) => Ok(open),
+            None => connect(&path).map(|open| shell.insert(open)),
+        }
+        .and_then(|open| open.run(&sql));
+        if shell.as_mut().is_some_and(Shell::exited) {
+            shell = None;
+        }
+        match (done, reply) {
+            (done, Some(reply)) => {
+                let _ = reply.send(done);
+            }
+            (Ok(_), None) => {}
+            (Err(e), None) => {
+                if lost.first.is_none() {
+                    eprintln!("Warning: failed to write to {}: {}", path.display(), e);
+                    lost.first = Some(e);
+                }
+                lost.rows += 1;
+            }
+        }
+    }
+    lost
+}
+
+/// A SQLite database of traced results, shared by every scan worker.
+pub struct HistorySink {
+    path: PathBuf,
+    jobs: Option<Sender<Job>>,
+    writer: Option<JoinHandle<Lost>>,
+}
+
+impl HistorySink {
+    /// Open (or create) the database at `path`, `:memory:` for one that
+    /// lives as long as its shell, and bring its schema up to date.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let shell = connect(path)?;
+        let (jobs, queue) = mpsc::channel();
+        let writer = {
+            let path = path.to_path_buf();
+            std::thread::Builder::new()
+                .name("history".to_string())
+                .spawn(move || write(path, shell, queue))?
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            jobs: Some(jobs),
+            writer: Some(writer),
+        })
+    }
+
+    /// Run `sql` after every row recorded so far.
+    fn run(&self, sql: &str) -> io::Result<Vec<Map<String, Value>>> {
+        let (reply, answer) = mpsc::channel();
+        self.send(Job::Query(sql.to_string(), reply));
+        answer
+            .recv()
+            .unwrap_or_else(|_| Err(io::Error::other("the history writer stopped")))
+    }
+
+    fn send(&self, job: Job) {
+        // The writer only stops once the sink is finished.
+        if let Some(jobs) = &self.jobs {
+            let _ = jobs.send(job);
+        }
+    }
+
+    /// Queue `row` to be stored. This never blocks, so that it can be
+    /// called from async code; a failed write is reported once as it
+    /// happens, and with the count of lost rows by [`finish`](Self::finish),
+    /// but does not abort the scan itself.
+    pub fn record(&self, row: &HistoryRow) {
+        let labels = serde_json::to_string(&row.labels).expect("labels serialize");
+        let duration = row
+            .duration_ms
+            .filter(|ms| ms.is_finite())
+            .map_or("NULL".to_string(), |ms| ms.to_string());
+        let sql = format!(
+            "INSERT INTO results (command, target, timestamp, group_name, cipher, version, \
+             cert_sha256, duration_ms, error_category, error, labels) \
+             VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {});",
+            literal(Some(&row.command)),
+            literal(Some(&row.target)),
+            literal(Some(&row.timestamp)),
+            literal(row.group.as_deref()),
+            literal(row.cipher.as_deref()),
+            literal(row.version.as_deref()),
+            literal(row.cert_sha256.as_deref()),
+            duration,
+            literal(row.error_category.as_deref()),
+            literal(row.error.as_deref()),
+            literal(Some(&labels)),
+        );
+        self.send(Job::Record(sql));
+    }
+
+    /// Wait for every queued row to be written, and fail if any of them
+    /// could not be.
+    pub fn finish(mut self) -> io::Result<()> {
+        let lost = self.stop();
+        match lost.first {
+            None => Ok(()),
+            Some(e) => Err(io::Error::other(format!(
+                "{} {} not stored in {}: {}",
+                lost.rows,
+                if lost.rows == 1 {
+                    "result was"
+                } else {
+                    "results were"
+                },
+                self.path.display(),
+                e
+            ))),
+        }
+    }
+
+    fn stop(&mut self) -> Lost {
+        drop(self.jobs.take());
+        match self.writer.take().map(JoinHandle::join) {
+            Some(Ok(lost)) => lost,
+            Some(Err(_)) => Lost {
+                rows: 0,
+                first: Some(io::Error::other("the history writer panicked")),
+            },
+            None => Lost::default(),
+        }
+    }
+
+    /// The latest `limit` rows of `target`, newest first.
+    pub fn recent(&self, target: &str, limit: usize) -> io::Result<Vec<HistoryRow>> {
+        let rows = self.run(&format!(
+            "SELECT * FROM results WHERE target = {} \
+             ORDER BY timestamp DESC, id DESC LIMIT {};",
+            literal(Some(target)),
+            limit
+        ))?;
+        rows.iter().map(HistoryRow::from_row).collect()
+    }
+}
+
+impl Drop for HistorySink {
+    /// Rows still queued are written before the sink goes.
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[derive(Args)]
+pub struct HistoryArgs {
+    /// The target, as it was given to trace, scan or watch.
+    pub target: String,
+
+    /// The database the results were recorded in with --db.
+    #[arg(long, value_name = "FILE")]
+    pub db: PathBuf,
+
+    /// Number of rows to print, newest first.
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub limit: usize,
+
+    /// Report format.
+    #[arg(long, value_enum, default_value = "table")]
+    pub format: OutputFormat,
+}
+
+pub fn history(args: HistoryArgs) -> ExitCode {
+    if !args.db.exists() {
+        eprintln!("No history database at {}", args.db.display());
+        return ExitCode::FAILURE;
+    }
+    let rows = match HistorySink::open(&args.db).and_then(|db| db.recent(&args.target, args.limit))
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", args.db.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut out = io::stdout().lock();
+    let written = match args.format {
+        OutputFormat::Table => write_table(&mut out, &rows),
+        OutputFormat::Json => serde_json::to_writer_pretty(&mut out, &rows)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(out)),
+        OutputFormat::Jsonl => rows.iter().try_for_each(|row| {
+            serde_json::to_writer(&mut out, row)?;
+            writeln!(out)
+        }),
+        OutputFormat::Csv => write_csv(&mut out, &rows),
+    };
+    match written {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Failed to write history: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn write_table(out: &mut dyn Write, rows: &[HistoryRow]) -> io::Result<()> {
+    if rows.is_empty() {
+        return writeln!(out, "No recorded results");
+    }
+    writeln!(
+        out,
+        "{:<20} {:<7} {:<22} {:<28} {:<7} {:>10}  error",
+        "timestamp", "command", "group", "cipher", "version", "ms"
+    )?;
+    for row in rows {
+        writeln!(
+            out,
+            "{:<20} {:<7} {:<22} {:<28} {:<7} {:>10}  {}",
+            row.timestamp,
+            row.command,
+            row.group.as_deref().unwrap_or("-"),
+            row.cipher.as_deref().unwrap_or("-"),
+            row.version.as_deref().unwrap_or("-"),
+            row.duration_ms
+                .map_or("-".to_string(), |ms| format!("{:.1}", ms)),
+            row.error.as_deref().unwrap_or(""),
+        )?;
+    }
+    Ok(())
+}
+
+fn write_csv(out: &mut dyn Write, rows: &[HistoryRow]) -> io::Result<()> {
+    writeln!(
+        out,
+        "timestamp,command,target,group,cipher,version,cert_sha256,duration_ms,error_category,error"
+    )?;
+    for row in rows {
+        let fields = [
+            Some(row.timestamp.clone()),
+            Some(row.command.clone()),
+            Some(row.target.clone()),
+            row.group.clone(),
+            row.cipher.clone(),
+            row.version.clone(),
+            row.cert_sha256.clone(),
+            row.duration_ms.map(|ms| format!("{:.3}", ms)),
+            row.error_category.clone(),
+            row.error.clone(),
+        ];
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|field| csv_field(field.as_deref().unwrap_or_default()))
+            .collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(target: &str, timestamp: &str) -> HistoryRow {
+        HistoryRow {
+            command: "scan".to_string(),
+            target: target.to_string(),
+            timestamp: timestamp.to_string(),
+            group: Some("X25519MLKEM768".to_string()),
+            cipher: Some("TLS13_AES_128_GCM_SHA256".to_string()),
+            version: Some("TLS1.3".to_string()),
+            cert_sha256: Some("ab".repeat(32)),
+            duration_ms: Some(12.5),
+            error_category: None,
+            error: None,
+            labels: BTreeMap::new(),
+        }
+    }
+
+    fn temp_db(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("pqctracer-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn rows_round_trip() {
+        let db = HistorySink::open(Path::new(":memory:")).unwrap();
+        let mut failed = row("https://example.com", "2026-03-01T12:00:00Z");
+        failed.group = None;
+        failed.cipher = None;
+        failed.version = None;
+        failed.cert_sha256 = None;
+        failed.error_category = Some("dns-failure".to_string());
+        failed.error = Some("no such host".to_string());
+        failed.labels.insert("env".to_string(), "prod".to_string());
+        db.record(&failed);
+        assert_eq!(db.recent("https://example.com", 10).unwrap(), [failed]);
+        assert!(db.recent("https://example.org", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn awkward_text_is_stored_as_given() {
+        let db = HistorySink::open(Path::new(":memory:")).unwrap();
+        let mut awkward = row("https://example.com/it's", "2026-03-01T12:00:00Z");
+        awkward.error = Some("line one\n.quit\r\n'); DROP TABLE results; --\0".to_string());
+        awkward
+            .labels
+            .insert("team".to_string(), "o'brien \"ops\"\n".to_string());
+        db.record(&awkward);
+        let stored = db.recent("https://example.com/it's", 1).unwrap();
+        assert_eq!(
+            stored[0].error.as_deref(),
+            Some("line one\n.quit\r\n'); DROP TABLE results; --")
+        );
+        assert_eq!(stored[0].labels, awkward.labels);
+    }
+
+    #[test]
+    fn recent_rows_come_newest_first() {
+        let db = HistorySink::open(Path::new(":memory:")).unwrap();
+        for timestamp in [
+            "2026-03-02T00:00:00Z",
+            "2026-03-01T00:00:00Z",
+            "2026-03-03T00:00:00Z",
+        ] {
+            db.record(&row("https://example.com", timestamp));
+        }
+        let mut later = row("https://example.com", "2026-03-03T00:00:00Z");
+        later.group = Some("X25519".to_string());
+        db.record(&later);
+
+        let recent = db.recent("https://example.com", 3).unwrap();
+        let order: Vec<(&str, Option<&str>)> = recent
+            .iter()
+            .map(|row| (row.timestamp.as_str(), row.group.as_deref()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("2026-03-03T00:00:00Z", Some("X25519")),
+                ("2026-03-03T00:00:00Z", Some("X25519MLKEM768")),
+                ("2026-03-02T00:00:00Z", Some("X25519MLKEM768")),
+            ]
+        );
+    }
+
+    #[test]
+    fn reopening_keeps_rows_and_schema() {
+        let path = temp_db("reopen");
+        HistorySink::open(&path)
+            .unwrap()
+            .record(&row("https://example.com", "2026-03-01T00:00:00Z"));
+        let db = HistorySink::open(&path).unwrap();
+        db.record(&row("https://example.com", "2026-03-02T00:00:00Z"));
+        assert_eq!(db.recent("https://example.com", 10).unwrap().len(), 2);
+        let version = db.run("PRAGMA user_version;").unwrap();
+        assert_eq!(version[0]["user_version"], MIGRATIONS.len());
+        drop(db);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn newer_schemas_are_refused() {
+        let path = temp_db("newer");
+        let db = HistorySink::open(&path).unwrap();
+        db.run(&format!("PRAGMA user_version = {};", MIGRATIONS.len() + 1))
+            .unwrap();
+        drop(db);
+        let e = HistorySink::open(&path).err().unwrap();
+        assert!(e.to_string().contains("newer than this build's"), "{}", e);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sql_errors_are_reported() {
+        let db = HistorySink::open(Path::new(":memory:")).unwrap();
+        let e = db.run("SELECT * FROM missing;").unwrap_err();
+        assert!(e.to_string().contains("no such table: missing"), "{}", e);
+    }
+
+    #[test]
+    fn a_failed_insert_loses_only_its_row() {
+        let path = temp_db("bail");
+        let db = HistorySink::open(&path).unwrap();
+        db.run(
+            "CREATE TRIGGER refuse BEFORE INSERT ON results WHEN NEW.target = 'refused' \
+             BEGIN SELECT RAISE(ABORT, 'refused by trigger'); END;",
+        )
+        .unwrap();
+        db.record(&row("https://example.com", "2026-03-01T00:00:00Z"));
+        db.record(&row("refused", "2026-03-01T00:00:01Z"));
+        // With -bail the shell exited on the trigger; these go to a new one.
+        db.record(&row("https://example.com", "2026-03-01T00:00:02Z"));
+        db.record(&row("refused", "2026-03-01T00:00:03Z"));
+        db.record(&row("https://example.com", "2026-03-01T00:00:04Z"));
+        assert_eq!(db.recent("https://example.com", 10).unwrap().len(), 3);
+        let e = db.finish().unwrap_err().to_string();
+        assert!(e.starts_with("2 results were not stored in "), "{}", e);
+        assert!(e.contains("refused by trigger"), "{}", e);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_query_error_does_not_lose_later_rows() {
+        let db = HistorySink::open(Path::new(":memory:")).unwrap();
+        db.run("SELECT * FROM missing;").unwrap_err();
+        db.record(&row("https://example.com", "2026-03-01T00:00:00Z"));
+        assert_eq!(db.recent("https://example.com", 10).unwrap().len(), 1);
+        db.finish().unwrap();
+    }
+
+    #[test]
+    fn recording_does_not_wait_for_a_locked_database() {
+        let path = temp_db("locked");
+        let db = HistorySink::open(&path).unwrap();
+        let holder = HistorySink::open(&path).unwrap();
+        holder.run("BEGIN EXCLUSIVE;").unwrap();
+        let start = std::time::Instant::now();
+        db.record(&row("https://example.com", "2026-03-01T00:00:00Z"));
+        // The writer waits for the lock up to .timeout; record does not.
+        assert!(start.elapsed() < Duration::from_millis(500));
+        std::thread::sleep(Duration::from_millis(200));
+        holder.run("COMMIT;").unwrap();
+        assert_eq!(db.recent("https://example.com", 10).unwrap().len(), 1);
+        db.finish().unwrap();
+        drop(holder);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn categories_are_stored_by_kind() {
+        assert_eq!(category_kind(&ErrorCategory::DnsFailure), "dns-failure");
+        assert_eq!(
+            category_kind(&ErrorCategory::HttpError { status: 503 }),
+            "http-error"
+        );
+    }
+}
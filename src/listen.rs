@@ -0,0 +1,154 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Args;
+use pqctracer::server::TracingAcceptor;
+use pqctracer::testutil::kx_group_by_name;
+use rustls::crypto::{CryptoProvider, aws_lc_rs};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::ServerConfig;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// How long a client may take to send its HTTP request after the handshake.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Args)]
+pub struct ListenArgs {
+    /// Address to listen on: `:PORT` (all interfaces), `HOST:PORT` or `PORT`.
+    pub address: String,
+
+    /// PEM file with the certificate chain, end-entity first.
+    #[arg(long, value_name = "FILE")]
+    pub cert: PathBuf,
+
+    /// PEM file with the private key.
+    #[arg(long, value_name = "FILE")]
+    pub key: PathBuf,
+
+    /// Comma-separated key-exchange groups to accept, in preference order
+    /// [default: every supported group, hybrid first].
+    #[arg(long, value_delimiter = ',')]
+    pub groups: Vec<String>,
+}
+
+pub async fn listen(args: ListenArgs) -> ExitCode {
+    let config = match server_config(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid server configuration: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let address = match args.address.strip_prefix(':') {
+        Some(port) => format!("0.0.0.0:{}", port),
+        None if args.address.bytes().all(|b| b.is_ascii_digit()) => {
+            format!("0.0.0.0:{}", args.address)
+        }
+        None => args.address.clone(),
+    };
+    let listener = match TcpListener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to listen on {}: {}", address, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match listener.local_addr() {
+        Ok(addr) => println!("Listening on {}", addr),
+        Err(_) => println!("Listening on {}", address),
+    }
+    println!("Press Ctrl-C to stop.");
+
+    let acceptor = TracingAcceptor::new(config).on_handshake(|info| println!("{}", info));
+    let accept_loop = async {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("Failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let Ok((mut tls, info)) = acceptor.accept_tcp(stream).await else {
+                    return;
+                };
+                // Answer one HTTP/1.1 request with the recorded line, so that
+                // browsers and curl show what they negotiated.
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                let read_request = async {
+                    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match tls.read(&mut buf).await {
+                            Ok(0) | Err(_) => return false,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    true
+                };
+                if !matches!(
+                    tokio::time::timeout(REQUEST_TIMEOUT, read_request).await,
+                    Ok(true)
+                ) {
+                    return;
+                }
+                let body = format!("{}\n", info);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = tls.write_all(response.as_bytes()).await;
+                let _ = tls.shutdown().await;
+            });
+        }
+    };
+    tokio::select! {
+        _ = accept_loop => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    ExitCode::SUCCESS
+}
+
+fn server_config(args: &ListenArgs) -> Result<Arc<ServerConfig>, Box<dyn Error>> {
+    let cert_chain = CertificateDer::pem_file_iter(&args.cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("failed to read {}: {}", args.cert.display(), e))?;
+    if cert_chain.is_empty() {
+        return Err(format!("no certificates found in {}", args.cert.display()).into());
+    }
+    let key = PrivateKeyDer::from_pem_file(&args.key)
+        .map_err(|e| format!("failed to read {}: {}", args.key.display(), e))?;
+
+    let mut provider = aws_lc_rs::default_provider();
+    if !args.groups.is_empty() {
+        let mut kx_groups = Vec::new();
+        for name in &args.groups {
+            let group = kx_group_by_name(name)
+                .ok_or_else(|| format!("unknown key-exchange group: {}", name))?;
+            kx_groups.extend(
+                aws_lc_rs::ALL_KX_GROUPS
+                    .iter()
+                    .copied()
+                    .filter(|g| g.name() == group),
+            );
+        }
+        provider = CryptoProvider {
+            kx_groups,
+            ..provider
+        };
+    }
+
+    let mut config = ServerConfig::builder_with_provider(Arc::new(provider))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(cert_chain, key)?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
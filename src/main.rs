@@ -1,36 +1,734 @@
-use pqctracer::TlsAwareClient;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-async fn trace_host(tls_client: &TlsAwareClient, host: &str) {
-    let url = format!("https://{}", host);
-    println!("Requesting: {}", url);
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use pqctracer::idn;
+use pqctracer::starttls::Protocol;
+use pqctracer::{
+    CaptureStatus, CertPolicy, HandshakeFailureKind, KxPreference, NameMismatch, PinPolicy,
+    TlsAwareClient, TlsMetadata,
+};
+use rustls::pki_types::CertificateDer;
+use serde::Deserialize;
+
+#[cfg(feature = "serve-api")]
+use crate::api::ServeApiArgs;
+use crate::auth::{AuthArgs, Credentials};
+use crate::bench::BenchArgs;
+use crate::certs::CertArgs;
+use crate::config::{ClientSettings, Config, ConfigArgs, TargetSettings};
+use crate::download::DownloadArgs;
+use crate::listen::ListenArgs;
+use crate::repeat::RepeatArgs;
+use crate::scan::ScanArgs;
+use crate::schema::SchemaArgs;
+use crate::serve_test::ServeTestArgs;
+use crate::upload::UploadArgs;
+use crate::watch::WatchArgs;
+
+#[cfg(feature = "serve-api")]
+mod api;
+mod auth;
+mod bench;
+mod certs;
+mod checkpoint;
+mod cidr;
+mod config;
+mod download;
+mod listen;
+#[cfg(feature = "sqlite")]
+mod history;
+#[cfg(feature = "compare-providers")]
+mod providers;
+mod repeat;
+mod scan;
+mod schema;
+mod serve_test;
+mod upload;
+mod watch;
+
+/// Trace the TLS key exchange negotiated with HTTPS endpoints.
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, after_help = config::ENV_VARS)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// URLs or host names to trace. Defaults to the configured targets, or
+    /// www.google.com and www.bing.com.
+    targets: Vec<String>,
+
+    #[command(flatten)]
+    client: ClientArgs,
+
+    #[command(flatten)]
+    certs: CertArgs,
+
+    #[command(flatten)]
+    repeat: RepeatArgs,
+
+    #[command(flatten)]
+    download: DownloadArgs,
+
+    #[command(flatten)]
+    upload: UploadArgs,
+
+    #[command(flatten)]
+    auth: AuthArgs,
+
+    /// Probe mail servers (host[:port]) that upgrade to TLS in-band: smtp, imap or pop3.
+    #[arg(long, value_name = "PROTOCOL")]
+    starttls: Option<Protocol>,
+
+    /// Print the provider, key-exchange groups, cipher suites and TLS
+    /// versions this client offers, then exit.
+    #[arg(long)]
+    capabilities: bool,
+
+    /// Print the JA3N and JA4 fingerprints of this client's ClientHello,
+    /// then exit.
+    #[arg(long)]
+    fingerprint: bool,
+
+    /// Trace every target with an aws-lc-rs and a ring client, each with
+    /// its provider's own groups and cipher suites, and compare the two.
+    #[cfg(feature = "compare-providers")]
+    #[arg(long, conflicts_with_all = ["repeat", "starttls", "download", "data_file", "form"])]
+    compare_providers: bool,
+
+    /// Also record every result in this SQLite database, created if
+    /// missing; see the history subcommand.
+    #[cfg(feature = "sqlite")]
+    #[arg(long, value_name = "FILE", conflicts_with = "repeat")]
+    db: Option<PathBuf>,
+
+    /// Print the version, then exit.
+    #[arg(short = 'V', long)]
+    version: bool,
+
+    /// With --version, also print the build, crypto provider, offered
+    /// groups and platform, as in the meta block of JSON reports.
+    #[arg(long, requires = "version")]
+    verbose: bool,
+}
 
-    let req = reqwest::Client::new()
-        .get(&url)
-        .build()
-        .expect("failed to build request");
+/// Options shared by every command that connects to servers.
+#[derive(Args)]
+struct ClientArgs {
+    /// Read settings from a TOML file; flags given here override its values.
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
 
-    let result = tls_client.execute(req).await.expect("request failed");
+    /// Accept invalid or self-signed server certificates.
+    #[arg(long)]
+    insecure: bool,
 
-    let group = result.group.as_deref().unwrap_or("Not available");
-    let cipher = result.cipher.as_deref().unwrap_or("Not available");
+    /// What a certificate that fails verification does: enforce fails the
+    /// request, report-only records the failure and carries on.
+    #[arg(long, value_name = "POLICY")]
+    cert_policy: Option<CertPolicy>,
+
+    /// Expect this host's public key to have this SHA-256 digest, in
+    /// base64; repeatable, also for several keys of one host.
+    #[arg(long = "pin", value_name = "HOST=DIGEST")]
+    pins: Vec<String>,
+
+    /// What a key that matches none of its host's pins does: enforce fails
+    /// the request, report records the mismatch and carries on.
+    #[arg(long, value_name = "POLICY")]
+    pin_policy: Option<PinPolicy>,
+
+    /// Comma-separated application protocols to offer by ALPN, e.g.
+    /// http/1.1; none are offered by default.
+    #[arg(long, value_name = "PROTOCOLS", value_delimiter = ',')]
+    alpn: Vec<String>,
+
+    /// Total timeout for each HTTP request, in seconds.
+    #[arg(long, value_name = "SECS")]
+    timeout: Option<f64>,
+
+    /// Timeout for establishing each TCP connection, in seconds.
+    #[arg(long, value_name = "SECS")]
+    connect_timeout: Option<f64>,
+
+    /// PEM file with root certificates to trust in addition to the web PKI.
+    #[arg(long, value_name = "FILE")]
+    cacert: Option<PathBuf>,
+
+    /// Keep the cookies servers set, follow redirects hop by hop with them
+    /// and load and save them in this Netscape cookie file. It holds session
+    /// cookies in plain text and is shared by every target of the run.
+    #[arg(long, value_name = "FILE")]
+    cookie_jar: Option<PathBuf>,
+
+    /// Send HTTP requests through this proxy URL.
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// Connect from this local address; targets are reached over addresses
+    /// of its family only.
+    #[arg(long, value_name = "IP")]
+    local_addr: Option<IpAddr>,
+
+    /// Connect through this network interface, e.g. eth1 (Linux only,
+    /// usually needs CAP_NET_RAW).
+    #[arg(long, value_name = "NAME")]
+    interface: Option<String>,
+
+    /// Key-exchange groups to offer: hybrid-preferred or classical-only.
+    #[arg(long, value_name = "PREFERENCE")]
+    kx: Option<KxPreference>,
+
+    /// Fail when a target negotiates a classical key exchange, or when the
+    /// crypto provider cannot offer anything else.
+    #[arg(long)]
+    require_pqc: bool,
+
+    /// Retry a handshake that failed while offering hybrid groups with
+    /// classical groups only, on a new connection, and report the outcome.
+    #[arg(long)]
+    fallback: bool,
+
+    /// Look up each target's HTTPS DNS record and report its ALPN, ECH and
+    /// address hints.
+    #[arg(long)]
+    dns: bool,
+
+    /// Encrypt the ClientHello when the target's HTTPS record has an ECH
+    /// configuration; implies --dns.
+    #[arg(long)]
+    ech: bool,
+
+    /// Look up each target's TLSA records and check the certificate chain
+    /// against them (DANE); implies --dns.
+    #[arg(long)]
+    dane: bool,
+
+    /// Measure DNS resolution, TCP connect, TLS handshake and time to the
+    /// response head of every request.
+    #[arg(long)]
+    timings: bool,
+
+    /// Report the extensions of the ServerHello in handshakes the tracer
+    /// drives itself: address scans, STARTTLS probes and version probes.
+    #[arg(long)]
+    deep_inspect: bool,
+
+    /// Suppress notices, e.g. that the crypto provider cannot offer
+    /// post-quantum key exchange.
+    #[arg(long)]
+    quiet: bool,
+}
+
+impl ClientArgs {
+    /// Combine the defaults, the `--config` file, the environment and the
+    /// command's flags: these client flags plus the command-specific ones in
+    /// `command`.
+    fn resolve(&self, command: Config) -> Result<Config, Box<dyn Error>> {
+        let client = ClientSettings {
+            timeout: self.timeout,
+            connect_timeout: self.connect_timeout,
+            cacert: self.cacert.clone(),
+            cookie_jar: self.cookie_jar.clone(),
+            proxy: self.proxy.clone(),
+            local_addr: self.local_addr,
+            interface: self.interface.clone(),
+            insecure: self.insecure.then_some(true),
+            cert_policy: self.cert_policy,
+            pins: (!self.pins.is_empty()).then(|| self.pins.clone()),
+            pin_policy: self.pin_policy,
+            alpn: (!self.alpn.is_empty()).then(|| self.alpn.clone()),
+            kx: self.kx,
+            require_pqc: self.require_pqc.then_some(true),
+            fallback: self.fallback.then_some(true),
+            dns: self.dns.then_some(true),
+            ech: self.ech.then_some(true),
+            dane: self.dane.then_some(true),
+            timings: self.timings.then_some(true),
+            deep_inspect: self.deep_inspect.then_some(true),
+            quiet: self.quiet.then_some(true),
+        };
+        Config::layered(self.config.as_deref(), Config { client, ..command })
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum OutputFormat {
+    Table,
+    Json,
+    /// One compact JSON document per line.
+    Jsonl,
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a local TLS server that logs the ClientHello of every connection.
+    ServeTest(ServeTestArgs),
+    /// Compare handshake latency with hybrid-preferred and classical-only key exchange.
+    Bench(BenchArgs),
+    /// Trace many targets concurrently, optionally rate limited.
+    Scan(ScanArgs),
+    /// Trace targets round after round and report, optionally to webhooks, when they change.
+    Watch(WatchArgs),
+    /// Manage configuration files.
+    Config(ConfigArgs),
+    /// Accept TLS connections with your certificate and log what every client offers and negotiates.
+    Listen(ListenArgs),
+    /// Serve traces over HTTP: GET /trace?url=..., /healthz and, with the metrics feature, /metrics.
+    #[cfg(feature = "serve-api")]
+    ServeApi(ServeApiArgs),
+    /// Print the results recorded for a target with --db, newest first.
+    #[cfg(feature = "sqlite")]
+    History(history::HistoryArgs),
+    /// Print the JSON Schema of the machine-readable output, or check a file against it.
+    Schema(SchemaArgs),
+}
+
+async fn trace_host(
+    tls_client: &TlsAwareClient,
+    target: &str,
+    download: &DownloadArgs,
+    upload: &UploadArgs,
+    credentials: Option<&Credentials>,
+) -> Result<TlsMetadata, Box<dyn Error>> {
+    let url = if target.contains("://") {
+        target.to_string()
+    } else {
+        format!("https://{}", target)
+    };
+    println!("Requesting: {}", url);
+    let url = idn::normalize_url(&url)?;
+    tls_client.check_local_address(&reqwest::Url::parse(&url)?)?;
+
+    let result = if upload.is_upload() {
+        upload::upload(tls_client, &url, upload, credentials).await?
+    } else {
+        let mut req = reqwest::Client::new().get(&url);
+        if let Some(credentials) = credentials {
+            req = credentials.authorize(req);
+        }
+        let req = req.build()?;
+        let (result, fallback) = tls_client.execute_with_fallback(req).await;
+        if let Some(fallback) = fallback {
+            println!("Fallback: {}", fallback);
+        }
+        result?
+    };
 
     println!("Status code: {}", result.response.status());
-    println!("Negotiated group: {}", group);
-    println!("Cipher suite: {}", cipher);
+    println!("{}", result.summary());
+    if result.metadata.capture_status != CaptureStatus::Captured {
+        println!("Capture: {}", result.metadata.capture_status);
+    }
+    if let (Some(local), Some(remote)) = (result.metadata.local_addr, result.metadata.remote_addr) {
+        println!("Connection: {} -> {}", local, remote);
+    }
+    if let Some(idn) = &result.metadata.idn {
+        println!("Server name: {}", idn);
+    }
+    if let Some(timings) = result.timings() {
+        println!("Timings: {}", timings);
+    }
+    if let Some(tickets) = result.metadata.tickets_issued {
+        println!("Session tickets: {}", tickets);
+    }
+    if let Some(penalty) = result.metadata.hrr_penalty {
+        println!(
+            "HelloRetryRequest: {:.2} ms added",
+            penalty.as_secs_f64() * 1000.0
+        );
+    }
+    if let Some(report) = result.validation_report() {
+        println!("Validation: {}", report);
+    }
+    if let Some(Err(e)) = &result.metadata.cert_validation {
+        println!("Certificate: NOT VERIFIED, {}", e);
+        if let Some(mismatch) = &e.name_mismatch {
+            println!("Certificate names: {}", mismatch);
+        }
+    }
+    if result.metadata.pin_matched == Some(false) {
+        println!("Pin: MISMATCH, the server's key matches none of its pins");
+    }
+    if result.metadata.client_cert_requested == Some(true) {
+        match result.metadata.client_cert_authorities.as_slice() {
+            [] => println!("Client certificate: requested, none presented"),
+            authorities => println!(
+                "Client certificate: requested from {}, none presented",
+                authorities.join("; ")
+            ),
+        }
+    }
+    print_dns(&result.metadata);
+    match &download.download {
+        Some(path) => download::download(result, path, download.keep_partial).await,
+        None => Ok(result.metadata),
+    }
+}
+
+async fn probe_mail_host(
+    tls_client: &TlsAwareClient,
+    target: &str,
+    protocol: Protocol,
+) -> Result<TlsMetadata, Box<dyn Error>> {
+    let (host, port) = host_and_port(target, protocol.default_port())?;
+    println!("Probing: {} STARTTLS on {}:{}", protocol, host, port);
+
+    let metadata = tls_client.probe_starttls(&host, port, protocol).await?;
+
+    println!("{}: {}", host, metadata);
+    if let (Some(sent), Some(received)) = (
+        metadata.handshake_bytes_sent,
+        metadata.handshake_bytes_received,
+    ) {
+        println!("Handshake bytes: {} sent, {} received", sent, received);
+    }
+    for alert in &metadata.alerts_received {
+        println!("Alert received: {}", alert);
+    }
+    if let Some(extensions) = &metadata.server_extensions {
+        let extensions: Vec<String> = extensions.iter().map(ToString::to_string).collect();
+        println!("Server extensions: {}", extensions.join(", "));
+    }
+    print_dns(&metadata);
+    Ok(metadata)
+}
+
+fn print_dns(metadata: &TlsMetadata) {
+    for record in &metadata.https_records {
+        println!("HTTPS record: {}", record);
+    }
+    if metadata.ech_offered {
+        println!("ECH: offered");
+    }
+    if let Some(dane) = &metadata.dane {
+        println!("DANE: {}", dane);
+        for detail in &dane.details {
+            println!("  TLSA {}", detail);
+        }
+    }
+}
+
+async fn trace(cli: Cli) -> ExitCode {
+    let Cli {
+        targets,
+        client,
+        certs,
+        repeat,
+        download,
+        upload,
+        auth,
+        starttls,
+        capabilities,
+        fingerprint,
+        #[cfg(feature = "compare-providers")]
+        compare_providers,
+        #[cfg(feature = "sqlite")]
+        db,
+        version,
+        verbose,
+        ..
+    } = cli;
+    if version && !verbose {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        return ExitCode::SUCCESS;
+    }
+    let config = match client.resolve(Config {
+        targets: TargetSettings {
+            list: (!targets.is_empty()).then_some(targets),
+            file: None,
+        },
+        ..Default::default()
+    }) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let targets = match config.targets.collect() {
+        Ok(targets) if targets.is_empty() => {
+            vec!["www.google.com".to_string(), "www.bing.com".to_string()]
+        }
+        Ok(targets) => targets,
+        Err(e) => {
+            eprintln!("Failed to read targets: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if download.download.is_some() && targets.len() != 1 {
+        eprintln!("--download takes a single target");
+        return ExitCode::FAILURE;
+    }
+    if upload.is_upload() && targets.len() != 1 {
+        eprintln!("--data-file and --form take a single target");
+        return ExitCode::FAILURE;
+    }
+    let credentials = match auth.credentials() {
+        Ok(credentials) => credentials,
+        Err(e) => {
+            eprintln!("Failed to read the password: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // Build the reusable TLS-aware client once.
+    let tls_client = match config.client.build_client() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Failed to build client: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if version {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        println!("{}", tls_client.report_meta());
+        return ExitCode::SUCCESS;
+    }
+    if capabilities {
+        println!("{}", tls_client.client_capabilities());
+        return ExitCode::SUCCESS;
+    }
+    if fingerprint {
+        let capabilities = tls_client.client_capabilities();
+        println!("JA3N: {}", capabilities.ja3n);
+        println!("JA4: {}", capabilities.ja4);
+        return ExitCode::SUCCESS;
+    }
+    #[cfg(feature = "compare-providers")]
+    if compare_providers {
+        return providers::compare_providers(&config.client, &targets).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    let db = match db.as_deref().map(history::HistorySink::open).transpose() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Failed to open the history database: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let require_pqc = config.client.require_pqc.unwrap_or_default();
+    let weights = config.readiness.weights();
+    let mut status = ExitCode::SUCCESS;
+    for (i, target) in targets.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        if let Some(attempts) = repeat.repeat {
+            if repeat::repeat(&tls_client, target, attempts, &repeat).await == ExitCode::FAILURE {
+                status = ExitCode::FAILURE;
+            }
+            continue;
+        }
+        #[cfg(feature = "sqlite")]
+        let start = std::time::Instant::now();
+        let result = match starttls {
+            Some(protocol) => probe_mail_host(&tls_client, target, protocol).await,
+            None => {
+                trace_host(
+                    &tls_client,
+                    target,
+                    &download,
+                    &upload,
+                    credentials.as_ref(),
+                )
+                .await
+            }
+        };
+        #[cfg(feature = "sqlite")]
+        if let Some(db) = &db {
+            db.record(&history::HistoryRow::of_trace(target, &result, start.elapsed()));
+        }
+        if let Ok(metadata) = &result {
+            println!("Readiness: {}", metadata.readiness(&weights));
+        }
+        if let Ok(metadata) = &result
+            && let Err(e) = save_chain(&certs, target, &metadata.peer_certificates)
+        {
+            eprintln!("Failed to write certificates: {}", e);
+            status = ExitCode::FAILURE;
+        }
+        match result {
+            Ok(metadata) if require_pqc && !is_pqc(&metadata) => {
+                eprintln!("No post-quantum key exchange negotiated with {}", target);
+                status = ExitCode::FAILURE;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Request failed: {}", e);
+                if let Some(mismatch) = NameMismatch::diagnose(&*e) {
+                    eprintln!("Certificate names: {}", mismatch);
+                }
+                if let Some(diagnosis) = HandshakeFailureKind::diagnose(&*e) {
+                    eprintln!("Diagnosis: {}", diagnosis);
+                    if let Some(hint) = diagnosis.hint() {
+                        eprintln!("Hint: {}", hint);
+                    }
+                }
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Err(e) = config.client.save_cookies(&tls_client) {
+        eprintln!("Failed to save cookies: {}", e);
+        status = ExitCode::FAILURE;
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(Err(e)) = db.map(history::HistorySink::finish) {
+        eprintln!("Failed to record history: {}", e);
+        status = ExitCode::FAILURE;
+    }
+    status
+}
+
+/// Print or write the chain `target` presented, as `certs` asks.
+fn save_chain(certs: &CertArgs, target: &str, chain: &[CertificateDer<'_>]) -> io::Result<()> {
+    if certs.show_certs {
+        certs::print_chain(&mut io::stdout().lock(), chain)?;
+    }
+    if let Some(dir) = &certs.certs_out {
+        for path in certs::write_chain(dir, target, chain)? {
+            println!("Wrote {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Whether `metadata` shows a hybrid or post-quantum key exchange.
+fn is_pqc(metadata: &TlsMetadata) -> bool {
+    metadata
+        .key_exchange_kind()
+        .is_some_and(|kind| kind.is_quantum_resistant())
+}
+
+/// Split `target` (host, host:port, `[IPv6]:port`, bare IPv6 address or
+/// URL) into host and port.
+fn host_and_port(target: &str, default_port: u16) -> Result<(String, u16), Box<dyn Error>> {
+    if target.contains("://") {
+        let url = reqwest::Url::parse(&idn::normalize_url(target)?)?;
+        let host = url.host_str().ok_or("URL has no host")?;
+        let port = url.port_or_known_default().unwrap_or(default_port);
+        return Ok((host.trim_matches(['[', ']']).to_string(), port));
+    }
+    if let Some(rest) = target.strip_prefix('[') {
+        let (host, rest) = rest
+            .split_once(']')
+            .ok_or_else(|| format!("unclosed '[' in {}", target))?;
+        let ip: Ipv6Addr = host
+            .parse()
+            .map_err(|_| format!("invalid IPv6 address: {}", host))?;
+        let port = match rest.strip_prefix(':') {
+            Some(port) => port.parse()?,
+            None if rest.is_empty() => default_port,
+            None => return Err(format!("unexpected {:?} after ']' in {}", rest, target).into()),
+        };
+        return Ok((ip.to_string(), port));
+    }
+    // More than one colon is a bare IPv6 address, which has no port.
+    match target.split_once(':') {
+        Some((host, port)) if !port.contains(':') => Ok((idn::to_ascii(host)?, port.parse()?)),
+        Some(_) => {
+            let ip: Ipv6Addr = target
+                .parse()
+                .map_err(|_| format!("invalid IPv6 address: {}", target))?;
+            Ok((ip.to_string(), default_port))
+        }
+        None => Ok((idn::to_ascii(target)?, default_port)),
+    }
+}
+
+/// Quote `field` for CSV output if it contains a separator, quote or line
+/// break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Where a command writes its report: `path`, or standard output when it
+/// is absent or `-`.
+fn open_output(path: Option<&Path>) -> io::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) if path != Path::new("-") => Box::new(File::create(path)?),
+        _ => Box::new(io::stdout().lock()),
+    })
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
+    let mut cli = Cli::parse();
+
     // Install aws-lc-rs as the process-level crypto provider (required when
     // `prefer-post-quantum` is enabled, since it brings in aws-lc-rs alongside ring).
     rustls::crypto::aws_lc_rs::default_provider()
         .install_default()
         .expect("failed to install crypto provider");
 
-    // Build the reusable TLS-aware client once.
-    let tls_client = TlsAwareClient::new();
+    match cli.command.take() {
+        Some(Command::ServeTest(args)) => serve_test::serve_test(args).await,
+        Some(Command::Bench(args)) => bench::bench(args).await,
+        Some(Command::Scan(args)) => scan::scan(args).await,
+        Some(Command::Watch(args)) => watch::watch(args).await,
+        Some(Command::Config(args)) => config::config(args),
+        Some(Command::Listen(args)) => listen::listen(args).await,
+        #[cfg(feature = "serve-api")]
+        Some(Command::ServeApi(args)) => api::serve_api(args).await,
+        #[cfg(feature = "sqlite")]
+        Some(Command::History(args)) => history::history(args),
+        Some(Command::Schema(args)) => schema::schema(args),
+        None => trace(cli).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_and_port_splits_every_target_form() {
+        let cases = [
+            ("mail.example.com", ("mail.example.com", 25)),
+            ("mail.example.com:587", ("mail.example.com", 587)),
+            ("192.0.2.1:465", ("192.0.2.1", 465)),
+            ("::1", ("::1", 25)),
+            ("2001:db8::25", ("2001:db8::25", 25)),
+            ("[::1]", ("::1", 25)),
+            ("[::1]:587", ("::1", 587)),
+            ("[2001:DB8::25]:2525", ("2001:db8::25", 2525)),
+            ("smtps://[::1]:465", ("::1", 465)),
+            ("https://example.com", ("example.com", 443)),
+            ("b\u{fc}cher.example:25", ("xn--bcher-kva.example", 25)),
+        ];
+        for (target, (host, port)) in cases {
+            let split = host_and_port(target, 25).map_err(|e| e.to_string());
+            assert_eq!(split, Ok((host.to_string(), port)), "{}", target);
+        }
+    }
 
-    trace_host(&tls_client, "www.google.com").await;
-    println!();
-    trace_host(&tls_client, "www.bing.com").await;
+    #[test]
+    fn host_and_port_rejects_malformed_targets() {
+        for target in [
+            "[::1",
+            "[::1]587",
+            "[example.com]:25",
+            "example.com:smtp",
+            "1:2:3",
+            "[::1]:99999",
+        ] {
+            assert!(host_and_port(target, 25).is_err(), "{}", target);
+        }
+    }
 }
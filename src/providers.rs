@@ -0,0 +1,278 @@
+//! `--compare-providers`: tracing the same targets with an aws-lc-rs and a
+//! ring client, each with its provider's own groups and cipher suites, to
+//! see where the two lead to different handshakes.
+
+use std::error::Error;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use pqctracer::scan::target_url;
+use pqctracer::{ClientCapabilities, TlsAwareClient, TlsMetadata, idn};
+use rustls::crypto::{CryptoProvider, aws_lc_rs, ring};
+
+use crate::config::ClientSettings;
+
+/// Trace every target with both clients and print the two side by side.
+pub async fn compare_providers(settings: &ClientSettings, targets: &[String]) -> ExitCode {
+    let clients = build(settings, aws_lc_rs::default_provider())
+        .and_then(|aws_lc_rs| Ok((aws_lc_rs, build(settings, ring::default_provider())?)));
+    let (aws_lc_rs, ring) = match clients {
+        Ok(clients) => clients,
+        Err(e) => {
+            eprintln!("Failed to build client: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut status = ExitCode::SUCCESS;
+    for (i, target) in targets.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let url = match idn::normalize_url(&target_url(target)) {
+            Ok(url) => url,
+            Err(e) => {
+                eprintln!("Invalid target {}: {}", target, e);
+                status = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        println!("Comparing: {}", url);
+        // One after the other, so that neither handshake slows the other.
+        let traces = [trace(&aws_lc_rs, &url).await, trace(&ring, &url).await];
+        if traces.iter().any(Result::is_err) {
+            status = ExitCode::FAILURE;
+        }
+        let written = write_comparison(
+            &mut io::stdout().lock(),
+            [
+                &aws_lc_rs.client_capabilities(),
+                &ring.client_capabilities(),
+            ],
+            &traces,
+        );
+        if let Err(e) = written {
+            eprintln!("Failed to write comparison: {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+    status
+}
+
+fn build(
+    settings: &ClientSettings,
+    provider: CryptoProvider,
+) -> Result<TlsAwareClient, Box<dyn Error>> {
+    Ok(settings
+        .client_builder()?
+        .crypto_provider(Arc::new(provider))
+        .collect_timings(true)
+        .build()?)
+}
+
+async fn trace(client: &TlsAwareClient, url: &str) -> Result<TlsMetadata, String> {
+    match client.get(url).send().await {
+        Ok(response) => Ok(response.metadata),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// A row per property with the providers' values side by side, lists one
+/// item per line, followed by the properties they differ in.
+fn write_comparison(
+    out: &mut dyn Write,
+    capabilities: [&ClientCapabilities; 2],
+    traces: &[Result<TlsMetadata, String>; 2],
+) -> io::Result<()> {
+    let negotiated = |f: fn(&TlsMetadata) -> Option<String>| {
+        traces.each_ref().map(|trace| match trace {
+            Ok(metadata) => vec![f(metadata).unwrap_or_else(|| "-".to_string())],
+            Err(_) => vec!["failed".to_string()],
+        })
+    };
+    let rows = [
+        ("Offered groups", capabilities.map(|c| c.kx_groups.clone())),
+        (
+            "Offered suites",
+            capabilities.map(|c| c.cipher_suites.clone()),
+        ),
+        ("Negotiated group", negotiated(|m| m.group.clone())),
+        ("Cipher", negotiated(|m| m.cipher.clone())),
+        (
+            "Handshake",
+            negotiated(|m| {
+                m.timings
+                    .and_then(|t| t.tls)
+                    .map(|tls| format!("{:.1} ms", tls.as_secs_f64() * 1000.0))
+            }),
+        ),
+    ];
+
+    let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let width = rows
+        .iter()
+        .flat_map(|(_, [first, _])| first.iter().map(String::len))
+        .chain([capabilities[0].provider.len()])
+        .max()
+        .unwrap_or(0);
+    let [first, second] = capabilities.map(|c| c.provider.as_str());
+    writeln!(out, "{:label_width$}  {:width$}  {}", "", first, second)?;
+    for (label, [first, second]) in &rows {
+        for line in 0..first.len().max(second.len()) {
+            let cell = |values: &[String]| values.get(line).cloned().unwrap_or_default();
+            let label = if line == 0 { label } else { "" };
+            let line = format!(
+                "{:label_width$}  {:width$}  {}",
+                label,
+                cell(first),
+                cell(second)
+            );
+            writeln!(out, "{}", line.trim_end())?;
+        }
+    }
+    for (provider, trace) in capabilities.iter().zip(traces) {
+        if let Err(e) = trace {
+            writeln!(out, "{} failed: {}", provider.provider, e)?;
+        }
+    }
+    // Handshake durations always differ a little.
+    let differences: Vec<&str> = rows[..rows.len() - 1]
+        .iter()
+        .filter(|(_, [first, second])| first != second)
+        .map(|(label, _)| *label)
+        .collect();
+    match differences.is_empty() {
+        true => writeln!(out, "Differences: none"),
+        false => writeln!(
+            out,
+            "Differences: {}",
+            differences.join(", ").to_lowercase()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pqctracer::testutil::TestTlsServer;
+
+    use super::*;
+
+    /// Both clients traced against `url`, and the comparison they print.
+    async fn compare(
+        settings: &ClientSettings,
+        url: &str,
+    ) -> ([Result<TlsMetadata, String>; 2], String) {
+        let aws_lc_rs = build(settings, aws_lc_rs::default_provider()).expect("aws-lc-rs client");
+        let ring = build(settings, ring::default_provider()).expect("ring client");
+        let traces = [trace(&aws_lc_rs, url).await, trace(&ring, url).await];
+        let mut table = Vec::new();
+        write_comparison(
+            &mut table,
+            [
+                &aws_lc_rs.client_capabilities(),
+                &ring.client_capabilities(),
+            ],
+            &traces,
+        )
+        .expect("write");
+        (traces, String::from_utf8(table).expect("UTF-8"))
+    }
+
+    async fn compare_with(server: &TestTlsServer) -> ([Result<TlsMetadata, String>; 2], String) {
+        let settings = ClientSettings {
+            insecure: Some(true),
+            ..ClientSettings::default()
+        };
+        let url = format!("https://localhost:{}/", server.local_addr().port());
+        compare(&settings, &url).await
+    }
+
+    /// The cells of the table's second column, found by the offset of the
+    /// header's ring label.
+    fn ring_column(table: &str) -> Vec<&str> {
+        let header = table.lines().next().expect("header");
+        let at = header.rfind("ring").expect("ring column");
+        table
+            .lines()
+            .skip(1)
+            .take_while(|line| !line.starts_with("Differences:"))
+            .map(|line| line.get(at..).unwrap_or_default())
+            .collect()
+    }
+
+    fn row<'a>(table: &'a str, label: &str) -> &'a str {
+        table
+            .lines()
+            .find(|line| line.starts_with(label))
+            .unwrap_or_else(|| panic!("no {} row in\n{}", label, table))
+    }
+
+    #[tokio::test]
+    async fn only_aws_lc_rs_negotiates_ml_kem() {
+        let server = TestTlsServer::builder()
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let (traces, table) = compare_with(&server).await;
+        let [aws_lc_rs, ring] = traces.map(|trace| trace.expect("trace"));
+        assert_eq!(aws_lc_rs.group.as_deref(), Some("X25519MLKEM768"));
+        assert_eq!(ring.group.as_deref(), Some("X25519"));
+
+        let header = table.lines().next().expect("header");
+        assert!(header.trim_start().starts_with("aws-lc-rs"), "{}", table);
+        assert!(row(&table, "Offered groups").contains("X25519MLKEM768"));
+        assert!(row(&table, "Negotiated group").contains("X25519MLKEM768"));
+        let column = ring_column(&table);
+        assert!(column.contains(&"X25519"), "{}", table);
+        assert!(
+            column.iter().all(|cell| !cell.contains("MLKEM")),
+            "{}",
+            table
+        );
+        assert!(
+            table.ends_with("Differences: offered groups, negotiated group\n"),
+            "{}",
+            table
+        );
+    }
+
+    #[tokio::test]
+    async fn a_classical_server_gives_both_the_same_group() {
+        let server = TestTlsServer::builder()
+            .groups([rustls::NamedGroup::X25519])
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let (traces, table) = compare_with(&server).await;
+        for trace in traces {
+            assert_eq!(trace.expect("trace").group.as_deref(), Some("X25519"));
+        }
+        assert!(
+            ring_column(&table)
+                .iter()
+                .all(|cell| !cell.contains("MLKEM"))
+        );
+        assert!(
+            table.ends_with("Differences: offered groups\n"),
+            "{}",
+            table
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_traces_are_reported_under_the_table() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let url = format!(
+            "https://localhost:{}/",
+            listener.local_addr().unwrap().port()
+        );
+        drop(listener);
+        let (traces, table) = compare(&ClientSettings::default(), &url).await;
+        assert!(traces.iter().all(Result::is_err));
+        let negotiated = row(&table, "Negotiated group");
+        assert_eq!(negotiated.matches("failed").count(), 2, "{}", table);
+        assert!(table.contains("\naws-lc-rs failed: "), "{}", table);
+        assert!(table.contains("\nring failed: "), "{}", table);
+    }
+}
@@ -0,0 +1,124 @@
+//! `--repeat`: tracing one target several times, to tell whether the
+//! backends behind it agree on the key exchange and the certificate.
+
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::Args;
+use pqctracer::TlsAwareClient;
+use pqctracer::repeat::{RepeatOptions, RepeatReport, ValueCount};
+
+/// Options for repeated traces.
+#[derive(Args)]
+pub struct RepeatArgs {
+    /// Trace each target this many times with fresh connections and report
+    /// whether every attempt negotiated the same group and certificate.
+    #[arg(long, value_name = "N")]
+    pub repeat: Option<usize>,
+
+    /// Wait at least this many milliseconds between the starts of two
+    /// attempts.
+    #[arg(long, value_name = "MS", default_value_t = 0, requires = "repeat")]
+    pub repeat_delay: u64,
+
+    /// Run this many attempts at the same time.
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "repeat")]
+    pub repeat_concurrency: usize,
+}
+
+impl RepeatArgs {
+    fn options(&self) -> RepeatOptions {
+        RepeatOptions {
+            delay: Duration::from_millis(self.repeat_delay),
+            concurrency: self.repeat_concurrency,
+        }
+    }
+}
+
+/// Trace `target` `attempts` times and print every attempt and what they
+/// had in common; fails when the attempts disagree or any of them failed.
+pub async fn repeat(
+    tls_client: &TlsAwareClient,
+    target: &str,
+    attempts: usize,
+    args: &RepeatArgs,
+) -> ExitCode {
+    let url = if target.contains("://") {
+        target.to_string()
+    } else {
+        format!("https://{}", target)
+    };
+    println!("Requesting: {} ({} attempts)", url, attempts);
+    let report = match tls_client
+        .trace_repeated(&url, attempts, &args.options())
+        .await
+    {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Request failed: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    print_report(&report);
+    if report.inconsistent || report.failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn print_report(report: &RepeatReport) {
+    for (index, attempt) in report.attempts.iter().enumerate() {
+        let addr = attempt
+            .remote_addr
+            .map_or_else(|| "-".to_string(), |addr| addr.to_string());
+        let outcome = match &attempt.error {
+            Some(error) => format!("failed ({})", error),
+            None => format!(
+                "{} {}",
+                attempt.metadata.group.as_deref().unwrap_or("-"),
+                attempt.certificate.as_deref().map_or("-", short)
+            ),
+        };
+        println!(
+            "Attempt {}: {} {} ({:.1} ms)",
+            index + 1,
+            addr,
+            outcome,
+            attempt.duration.as_secs_f64() * 1000.0
+        );
+    }
+    print_counts("Groups", &report.groups, str::to_string);
+    print_counts("Remote addresses", &report.remote_addrs, str::to_string);
+    print_counts("Certificates", &report.certificates, |value| {
+        short(value).to_string()
+    });
+    if report.failed > 0 {
+        println!("Failed: {} of {}", report.failed, report.attempts.len());
+    }
+    println!(
+        "Consistency: {}",
+        if report.inconsistent {
+            "INCONSISTENT"
+        } else {
+            "consistent"
+        }
+    );
+}
+
+/// A heading and one line per value with how often it was observed.
+fn print_counts(heading: &str, counts: &[ValueCount], show: impl Fn(&str) -> String) {
+    if counts.is_empty() {
+        return;
+    }
+    println!("{}:", heading);
+    for count in counts {
+        println!("  {:<46} {:>6}", show(&count.value), count.count);
+    }
+}
+
+/// The first 16 hex digits of a fingerprint, enough to tell certificates
+/// apart on one line.
+fn short(fingerprint: &str) -> &str {
+    fingerprint.get(..16).unwrap_or(fingerprint)
+}
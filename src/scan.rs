@@ -0,0 +1,1685 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant, SystemTime};
+
+use clap::Args;
+use pqctracer::cert::rfc3339;
+use pqctracer::http_versions::{HttpVersionReport, HttpVersionSupport};
+use pqctracer::resumption::ResumptionReport;
+use pqctracer::scan::{
+    KindLatency, ScanMethod, ScanOptions, ScanResult, ScanSummary, ScanTarget, dedupe_targets,
+};
+use pqctracer::stats::LatencyHistogram;
+use pqctracer::versions::{VersionSupport, VersionSupportReport};
+use pqctracer::{ClientCapabilities, KeyExchangeKind, ReportMeta, ResumptionKx, TlsAwareClient};
+use serde::Serialize;
+
+use crate::certs;
+use crate::checkpoint::Checkpoint;
+use crate::cidr::Cidr;
+use crate::config::{Config, OutputSettings, ScanSettings, TargetSettings};
+#[cfg(feature = "sqlite")]
+use crate::history::{HistoryRow, HistorySink};
+use crate::{ClientArgs, OutputFormat, csv_field, is_pqc, open_output};
+
+/// `--cidr` ranges larger than this need `--yes-i-know`.
+const CIDR_CAP: u128 = 256;
+/// Upper bound for `--cidr` ranges even with `--yes-i-know`; every address
+/// is held in memory.
+const CIDR_LIMIT: u128 = 1 << 24;
+
+#[derive(Args)]
+#[command(after_help = crate::config::ENV_VARS)]
+pub struct ScanArgs {
+    /// URLs or host names to trace.
+    pub targets: Vec<String>,
+
+    /// Read additional targets from a file, one per line (`#` starts a comment).
+    #[arg(short, long, value_name = "FILE")]
+    pub input: Option<PathBuf>,
+
+    /// Hand-shake with every address of this IPv4 or IPv6 range, e.g.
+    /// 203.0.113.0/28; repeatable. Requires --sni.
+    #[arg(long, value_name = "CIDR", requires = "sni")]
+    pub cidr: Vec<Cidr>,
+
+    /// Server name to send to, and validate the certificate of, every
+    /// --cidr address.
+    #[arg(long, value_name = "NAME", requires = "cidr")]
+    pub sni: Option<String>,
+
+    /// Port to connect to on every --cidr address.
+    #[arg(long, default_value_t = 443, requires = "cidr")]
+    pub port: u16,
+
+    /// Allow --cidr ranges of more than 256 addresses in total.
+    #[arg(long)]
+    pub yes_i_know: bool,
+
+    #[command(flatten)]
+    pub client: ClientArgs,
+
+    /// Number of targets traced at the same time [default: 8].
+    #[arg(long)]
+    pub concurrency: Option<usize>,
+
+    /// Number of targets of the same host traced at the same time, 0 for
+    /// no cap [default: 2].
+    #[arg(long, value_name = "N")]
+    pub max_per_host: Option<usize>,
+
+    /// Additional attempts for a target whose request failed [default: 1].
+    #[arg(long)]
+    pub retries: Option<u32>,
+
+    /// Maximum requests per second across all workers, retries included.
+    #[arg(long, value_name = "REQUESTS_PER_SECOND")]
+    pub rate: Option<f64>,
+
+    /// Minimum time between two requests to the same host, in milliseconds.
+    #[arg(long, value_name = "MS")]
+    pub per_host_interval_ms: Option<u64>,
+
+    /// Request every target with HEAD or GET [default: HEAD].
+    #[arg(long, value_name = "METHOD")]
+    pub method: Option<ScanMethod>,
+
+    /// Repeat a HEAD request the server answers with 405 or 501 as a GET
+    /// request.
+    #[arg(long)]
+    pub fallback_get: bool,
+
+    /// Append finished results to this JSONL file and skip targets it
+    /// already contains, so an interrupted scan can be resumed.
+    #[arg(long, value_name = "FILE")]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Warn, and exit with an error, when a certificate expires within this
+    /// many days.
+    #[arg(long, value_name = "DAYS")]
+    pub warn_expiry: Option<i64>,
+
+    /// Also attempt a TLS 1.3-only and a TLS 1.2-only handshake with every
+    /// reachable target and report which versions it accepts.
+    #[arg(long)]
+    pub probe_versions: bool,
+
+    /// Also tell whether every target copes with a large ClientHello: one
+    /// handshake with the usual groups, one with classical groups padded to
+    /// the same size, and report whether the groups or the size break it.
+    #[arg(long)]
+    pub diagnose: bool,
+
+    /// Also attempt a handshake offering HTTP/1.1 and one offering HTTP/2
+    /// by ALPN with every reachable target, and flag targets whose two
+    /// negotiate different groups or present different certificates.
+    #[arg(long)]
+    pub compare_http_versions: bool,
+
+    /// Also resume a session with every reachable target and flag targets
+    /// whose resumed handshake runs no key exchange of its own (PSK only).
+    #[arg(long)]
+    pub check_resumption: bool,
+
+    /// Scan the targets as given, without lowercasing hosts, dropping
+    /// default ports and paths and merging targets that are the same.
+    #[arg(long)]
+    pub no_normalize: bool,
+
+    /// Tell targets with different paths apart when normalizing.
+    #[arg(long, conflicts_with = "no_normalize")]
+    pub keep_paths: bool,
+
+    /// Report format [default: table].
+    #[arg(long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Write the report to this file instead of standard output.
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Write the certificate chain each target presented to this directory,
+    /// one subdirectory per target; the report itself is unaffected.
+    #[arg(long, value_name = "DIR")]
+    pub certs_out: Option<PathBuf>,
+
+    /// Attach this label to every request and result, e.g. tenant=acme;
+    /// repeatable.
+    #[arg(long = "label", value_name = "KEY=VALUE", value_parser = parse_label)]
+    pub labels: Vec<(String, String)>,
+
+    /// Also record every result in this SQLite database, created if
+    /// missing; see the history subcommand.
+    #[cfg(feature = "sqlite")]
+    #[arg(long, value_name = "FILE")]
+    pub db: Option<PathBuf>,
+}
+
+impl ScanArgs {
+    /// The `--cidr` ranges expanded into address targets.
+    fn addresses(&self) -> Result<Vec<ScanTarget>, String> {
+        let Some(sni) = &self.sni else {
+            return Ok(Vec::new());
+        };
+        let total: u128 = self
+            .cidr
+            .iter()
+            .fold(0, |total, cidr| total.saturating_add(cidr.size()));
+        if total > CIDR_LIMIT {
+            return Err(format!(
+                "--cidr covers {} addresses, more than the limit of {}",
+                total, CIDR_LIMIT
+            ));
+        }
+        if total > CIDR_CAP && !self.yes_i_know {
+            return Err(format!(
+                "--cidr covers {} addresses; pass --yes-i-know to scan more than {}",
+                total, CIDR_CAP
+            ));
+        }
+        Ok(self
+            .cidr
+            .iter()
+            .flat_map(Cidr::addresses)
+            .map(|ip| ScanTarget::Address {
+                addr: SocketAddr::new(ip, self.port),
+                server_name: sni.clone(),
+            })
+            .collect())
+    }
+
+    fn resolve(self) -> Result<Config, Box<dyn Error>> {
+        // Ranges on the command line replace the configured targets just as
+        // listed targets do.
+        let listed = !self.targets.is_empty() || !self.cidr.is_empty();
+        let targets = TargetSettings {
+            list: listed.then_some(self.targets),
+            file: self.input,
+        };
+        self.client.resolve(Config {
+            scan: ScanSettings {
+                concurrency: self.concurrency,
+                max_per_host: self.max_per_host,
+                retries: self.retries,
+                rate: self.rate,
+                per_host_interval_ms: self.per_host_interval_ms,
+                method: self.method,
+                fallback_get: self.fallback_get.then_some(true),
+                checkpoint: self.checkpoint,
+                warn_expiry_days: self.warn_expiry,
+                probe_versions: self.probe_versions.then_some(true),
+                diagnose: self.diagnose.then_some(true),
+                compare_http_versions: self.compare_http_versions.then_some(true),
+                check_resumption: self.check_resumption.then_some(true),
+                normalize: self.no_normalize.then_some(false),
+                keep_paths: self.keep_paths.then_some(true),
+            },
+            targets,
+            output: OutputSettings {
+                format: self.format,
+                path: self.output,
+            },
+            ..Default::default()
+        })
+    }
+}
+
+pub async fn scan(args: ScanArgs) -> ExitCode {
+    let addresses = match args.addresses() {
+        Ok(addresses) => addresses,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let certs_out = args.certs_out.clone();
+    let labels: BTreeMap<String, String> = args.labels.iter().cloned().collect();
+    #[cfg(feature = "sqlite")]
+    let db = match args.db.as_deref().map(HistorySink::open).transpose() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Failed to open the history database: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let config = match args.resolve() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let listed = match config.targets.collect() {
+        Ok(targets) => targets,
+        Err(e) => {
+            eprintln!("Failed to read targets: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    // The inputs of every target that was normalized from something else.
+    let mut sources: HashMap<String, Vec<String>> = HashMap::new();
+    let listed = if config.scan.normalize.unwrap_or(true) {
+        let count = listed.len();
+        let unique = dedupe_targets(listed, config.scan.keep_paths.unwrap_or_default());
+        if unique.len() < count {
+            eprintln!(
+                "Normalized {} targets into {} unique ones",
+                count,
+                unique.len()
+            );
+        }
+        unique
+            .into_iter()
+            .map(|(target, inputs)| {
+                if inputs.len() > 1 || inputs[0] != target {
+                    sources.insert(target.clone(), inputs);
+                }
+                target
+            })
+            .collect()
+    } else {
+        listed
+    };
+    let targets: Vec<ScanTarget> = listed
+        .into_iter()
+        .map(ScanTarget::Url)
+        .chain(addresses)
+        .collect();
+    if targets.is_empty() {
+        eprintln!("No targets to scan");
+        return ExitCode::FAILURE;
+    }
+    let settings = config.scan;
+    if settings
+        .rate
+        .is_some_and(|rate| rate.is_nan() || rate <= 0.0)
+    {
+        eprintln!("The scan rate must be a positive number");
+        return ExitCode::FAILURE;
+    }
+
+    let client = match config.client.build_client() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Failed to build client: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let defaults = ScanOptions::default();
+    let options = ScanOptions {
+        concurrency: settings.concurrency.unwrap_or(defaults.concurrency),
+        max_per_host: match settings.max_per_host {
+            Some(0) => None,
+            Some(max) => Some(max),
+            None => defaults.max_per_host,
+        },
+        retries: settings.retries.unwrap_or(defaults.retries),
+        rate_limit: settings.rate,
+        per_host_interval: settings.per_host_interval_ms.map(Duration::from_millis),
+        method: settings.method.unwrap_or_default(),
+        fallback_get: settings.fallback_get.unwrap_or_default(),
+        probe_versions: settings.probe_versions.unwrap_or_default(),
+        diagnose_hello_size: settings.diagnose.unwrap_or_default(),
+        compare_http_versions: settings.compare_http_versions.unwrap_or_default(),
+        check_resumption: settings.check_resumption.unwrap_or_default(),
+        readiness_weights: config.readiness.weights(),
+        labels,
+    };
+
+    let (mut checkpoint, done) = match &settings.checkpoint {
+        Some(path) => match Checkpoint::open(path) {
+            Ok((checkpoint, done)) => (Some(checkpoint), done),
+            Err(e) => {
+                eprintln!("Failed to open checkpoint {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => (None, Vec::new()),
+    };
+    let done: HashMap<String, ScanResult> =
+        done.into_iter().map(|r| (r.target.clone(), r)).collect();
+    let remaining: Vec<&ScanTarget> = targets
+        .iter()
+        .filter(|target| !done.contains_key(&target.label()))
+        .collect();
+    if !done.is_empty() {
+        eprintln!(
+            "Resuming from checkpoint: {} done, {} remaining",
+            targets.len() - remaining.len(),
+            remaining.len()
+        );
+    }
+
+    let mut out = match open_output(config.output.path.as_deref()) {
+        Ok(out) => out,
+        Err(e) => {
+            eprintln!("Failed to open output: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let start = Instant::now();
+    let scanned = client
+        .scan_with(remaining.into_iter().cloned(), &options, |result| {
+            if let Some(checkpoint) = &mut checkpoint {
+                checkpoint.record(&with_sources(result.clone(), &sources));
+            }
+            #[cfg(feature = "sqlite")]
+            if let Some(db) = &db {
+                db.record(&HistoryRow::of_scan("scan", result));
+            }
+        })
+        .await;
+    let elapsed = start.elapsed();
+
+    // Report in input order, taking each target from the checkpoint when it
+    // was finished by an earlier run.
+    let mut scanned = scanned.into_iter();
+    let results: Vec<ScanResult> = targets
+        .iter()
+        .filter_map(|target| match done.get(&target.label()) {
+            Some(result) => Some(result.clone()),
+            None => scanned.next().map(|result| with_sources(result, &sources)),
+        })
+        .collect();
+
+    let format = config.output.format.unwrap_or(OutputFormat::Table);
+    let warn_expiry = settings.warn_expiry_days;
+    if let Err(e) = write_report(&mut *out, format, &results, elapsed, warn_expiry, &client) {
+        eprintln!("Failed to write report: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    let mut status = ExitCode::SUCCESS;
+    // Chains go to files only, so that they never mix with a report on
+    // standard output; results taken from a checkpoint have none.
+    if let Some(dir) = &certs_out {
+        let mut written = 0;
+        for result in &results {
+            match certs::write_chain(dir, &result.target, &result.metadata.peer_certificates) {
+                Ok(paths) => written += paths.len(),
+                Err(e) => {
+                    eprintln!("Failed to write certificates of {}: {}", result.target, e);
+                    status = ExitCode::FAILURE;
+                }
+            }
+        }
+        eprintln!("Wrote {} certificates to {}", written, dir.display());
+    }
+    if results.iter().any(|r| r.error.is_some()) {
+        status = ExitCode::FAILURE;
+    }
+    if let Err(e) = config.client.save_cookies(&client) {
+        eprintln!("Failed to save cookies: {}", e);
+        status = ExitCode::FAILURE;
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(Err(e)) = db.map(HistorySink::finish) {
+        eprintln!("Failed to record history: {}", e);
+        status = ExitCode::FAILURE;
+    }
+    if config.client.require_pqc.unwrap_or_default() {
+        let classical: Vec<&str> = results
+            .iter()
+            .filter(|r| r.reached() && !is_pqc(&r.metadata))
+            .map(|r| r.target.as_str())
+            .collect();
+        if !classical.is_empty() {
+            eprintln!(
+                "No post-quantum key exchange negotiated with: {}",
+                classical.join(", ")
+            );
+            status = ExitCode::FAILURE;
+        }
+    }
+    let expiring: Vec<&str> = results
+        .iter()
+        .filter(|r| expires_soon(r, warn_expiry))
+        .map(|r| r.target.as_str())
+        .collect();
+    if !expiring.is_empty() {
+        eprintln!(
+            "Certificate expires within {} days: {}",
+            warn_expiry.unwrap_or_default(),
+            expiring.join(", ")
+        );
+        status = ExitCode::FAILURE;
+    }
+    status
+}
+
+/// Parse a `--label`, `KEY=VALUE`.
+fn parse_label(label: &str) -> Result<(String, String), String> {
+    match label.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("invalid label {}: expected KEY=VALUE", label)),
+    }
+}
+
+/// `result` with the inputs its target was normalized from.
+fn with_sources(mut result: ScanResult, sources: &HashMap<String, Vec<String>>) -> ScanResult {
+    if let Some(inputs) = sources.get(&result.target) {
+        result.sources = inputs.clone();
+    }
+    result
+}
+
+/// Whether `result`'s certificate expires within `warn_expiry` days.
+fn expires_soon(result: &ScanResult, warn_expiry: Option<i64>) -> bool {
+    match (result.cert_days_remaining, warn_expiry) {
+        (Some(days), Some(limit)) => days <= limit,
+        _ => false,
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    meta: &'a ReportMeta,
+    results: &'a [ScanResult],
+    summary: &'a ScanSummary,
+    client: &'a ClientCapabilities,
+}
+
+/// The first record of `--format jsonl` output, ahead of the results.
+#[derive(Serialize)]
+struct JsonlMeta<'a> {
+    meta: &'a ReportMeta,
+}
+
+pub(crate) fn write_report(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    results: &[ScanResult],
+    elapsed: Duration,
+    warn_expiry: Option<i64>,
+    client: &TlsAwareClient,
+) -> io::Result<()> {
+    let summary = ScanSummary::from_results(results);
+    let meta = client.report_meta();
+    match format {
+        OutputFormat::Table => {
+            write_scan_table(out, results, warn_expiry)?;
+            writeln!(out)?;
+            write_summary(out, &summary, elapsed)
+        }
+        OutputFormat::Json => {
+            let report = JsonReport {
+                meta: &meta,
+                results,
+                summary: &summary,
+                client: &client.client_capabilities(),
+            };
+            let json = serde_json::to_string_pretty(&report).expect("report serializes");
+            writeln!(out, "{}", json)
+        }
+        // One record per target, as in the checkpoint file.
+        OutputFormat::Jsonl => {
+            let meta = serde_json::to_string(&JsonlMeta { meta: &meta }).expect("meta serializes");
+            writeln!(out, "{}", meta)?;
+            for result in results {
+                let json = serde_json::to_string(result).expect("result serializes");
+                writeln!(out, "{}", json)?;
+            }
+            Ok(())
+        }
+        OutputFormat::Csv => write_scan_csv(out, results, warn_expiry),
+    }
+}
+
+fn write_scan_csv(
+    out: &mut dyn Write,
+    results: &[ScanResult],
+    warn_expiry: Option<i64>,
+) -> io::Result<()> {
+    let time = |t: Option<SystemTime>| t.map(rfc3339).unwrap_or_default();
+    let ms = |d: Option<Duration>| {
+        d.map(|d| format!("{:.3}", d.as_secs_f64() * 1000.0))
+            .unwrap_or_default()
+    };
+    let count = |n: Option<u64>| n.map(|n| n.to_string()).unwrap_or_default();
+
+    writeln!(
+        out,
+        "target,url,sni,status,group,key_exchange,cipher,error_category,error,attempts,duration_ms,cert_not_before,cert_not_after,cert_days_remaining,expiry_warning,tls13,tls12,dns_ms,connect_ms,tls_ms,ttfb_ms,handshake_bytes_sent,handshake_bytes_received,alerts_received,cert_validation,tickets_issued,fallback,hello_size,hrr_penalty_ms,dane,readiness_score,readiness_grade,http_versions,method,sources,resumption,labels"
+    )?;
+    for result in results {
+        let metadata = &result.metadata;
+        let timings = metadata.timings;
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{},{:.3},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&result.target),
+            csv_field(&result.url),
+            result.sni.as_deref().map(csv_field).unwrap_or_default(),
+            result.status.map(|s| s.to_string()).unwrap_or_default(),
+            metadata.group.as_deref().unwrap_or_default(),
+            metadata
+                .key_exchange_kind()
+                .map(|k| k.to_string())
+                .unwrap_or_default(),
+            metadata.cipher.as_deref().unwrap_or_default(),
+            result
+                .error
+                .as_ref()
+                .map(|e| csv_field(&e.category.to_string()))
+                .unwrap_or_default(),
+            result
+                .error
+                .as_ref()
+                .map(|e| csv_field(&e.message))
+                .unwrap_or_default(),
+            result.attempts,
+            result.duration.as_secs_f64() * 1000.0,
+            time(result.cert_not_before),
+            time(result.cert_not_after),
+            result
+                .cert_days_remaining
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+            expires_soon(result, warn_expiry),
+            result
+                .versions
+                .as_ref()
+                .map(|v| csv_field(&v.tls13.to_string()))
+                .unwrap_or_default(),
+            result
+                .versions
+                .as_ref()
+                .map(|v| csv_field(&v.tls12.to_string()))
+                .unwrap_or_default(),
+            ms(timings.and_then(|t| t.dns)),
+            ms(timings.and_then(|t| t.connect)),
+            ms(timings.and_then(|t| t.tls)),
+            ms(timings.map(|t| t.ttfb)),
+            count(metadata.handshake_bytes_sent),
+            count(metadata.handshake_bytes_received),
+            csv_field(
+                &metadata
+                    .alerts_received
+                    .iter()
+                    .map(|alert| alert.to_string())
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+            match &metadata.cert_validation {
+                Some(Ok(())) => "verified".to_string(),
+                Some(Err(e)) => format!("unverified ({})", e.problem),
+                None => String::new(),
+            },
+            count(metadata.tickets_issued.map(u64::from)),
+            metadata
+                .fallback
+                .as_ref()
+                .map(|f| csv_field(&f.to_string()))
+                .unwrap_or_default(),
+            result
+                .hello_size
+                .as_ref()
+                .map(|h| csv_field(&h.to_string()))
+                .unwrap_or_default(),
+            ms(metadata.hrr_penalty),
+            metadata
+                .dane
+                .as_ref()
+                .map(|d| csv_field(&d.to_string()))
+                .unwrap_or_default(),
+            count(result.readiness.as_ref().map(|r| u64::from(r.score))),
+            result
+                .readiness
+                .as_ref()
+                .map(|r| r.grade.to_string())
+                .unwrap_or_default(),
+            result
+                .http_versions
+                .as_ref()
+                .map(|h| csv_field(&h.to_string()))
+                .unwrap_or_default(),
+            result.method.map(|m| m.to_string()).unwrap_or_default(),
+            csv_field(&result.sources.join("; ")),
+            result
+                .resumption
+                .as_ref()
+                .map(|r| csv_field(&r.to_string()))
+                .unwrap_or_default(),
+            csv_field(&label_list(&metadata.labels, "; ")),
+        )?;
+    }
+    Ok(())
+}
+
+fn write_scan_table(
+    out: &mut dyn Write,
+    results: &[ScanResult],
+    warn_expiry: Option<i64>,
+) -> io::Result<()> {
+    let or_dash = |value: Option<&str>| value.unwrap_or("-").to_string();
+    // The versions column only appears for scans with --probe-versions.
+    let probed = results.iter().any(|result| result.versions.is_some());
+    let versions = |result: &ScanResult| match (&result.versions, probed) {
+        (Some(versions), _) => format!("{:<12} ", version_marks(versions)),
+        (None, true) => format!("{:<12} ", "-"),
+        (None, false) => String::new(),
+    };
+    // And the hello-size column only for scans with --diagnose.
+    let diagnosed = results.iter().any(|result| result.hello_size.is_some());
+    let hello_size = |result: &ScanResult| match (&result.hello_size, diagnosed) {
+        (Some(report), _) => format!("{:<13} ", report.verdict),
+        (None, true) => format!("{:<13} ", "-"),
+        (None, false) => String::new(),
+    };
+    // And the HTTP versions column only for scans with
+    // --compare-http-versions.
+    let compared = results.iter().any(|result| result.http_versions.is_some());
+    let http_versions = |result: &ScanResult| match (&result.http_versions, compared) {
+        (Some(report), _) => format!("{:<13} ", http_version_marks(report)),
+        (None, true) => format!("{:<13} ", "-"),
+        (None, false) => String::new(),
+    };
+    // And the resumption column only for scans with --check-resumption.
+    let resumed = results.iter().any(|result| result.resumption.is_some());
+    let resumption = |result: &ScanResult| match (&result.resumption, resumed) {
+        (Some(report), _) => format!("{:<18} ", resumption_mark(report)),
+        (None, true) => format!("{:<18} ", "-"),
+        (None, false) => String::new(),
+    };
+    // And the labels column only for scans with --label.
+    let labelled = results
+        .iter()
+        .any(|result| !result.metadata.labels.is_empty());
+    let labels = |result: &ScanResult| match (result.metadata.labels.is_empty(), labelled) {
+        (false, _) => format!("{:<24} ", label_list(&result.metadata.labels, ",")),
+        (true, true) => format!("{:<24} ", "-"),
+        (true, false) => String::new(),
+    };
+
+    writeln!(
+        out,
+        "{:<40} {:>6} {:<18} {:<28} {:>5} {:>8} {}{}{}{}{}error",
+        "target",
+        "status",
+        "group",
+        "cipher",
+        "grade",
+        "expires",
+        if probed {
+            format!("{:<12} ", "versions")
+        } else {
+            String::new()
+        },
+        if diagnosed {
+            format!("{:<13} ", "hello-size")
+        } else {
+            String::new()
+        },
+        if compared {
+            format!("{:<13} ", "h1/h2")
+        } else {
+            String::new()
+        },
+        if resumed {
+            format!("{:<18} ", "resumption")
+        } else {
+            String::new()
+        },
+        if labelled {
+            format!("{:<24} ", "labels")
+        } else {
+            String::new()
+        }
+    )?;
+    for result in results {
+        writeln!(
+            out,
+            "{:<40} {:>6} {:<18} {:<28} {:>5} {:>8} {}{}{}{}{}{}",
+            result.target,
+            result
+                .status
+                .map_or_else(|| "-".to_string(), |s| s.to_string()),
+            or_dash(result.metadata.group.as_deref()),
+            or_dash(result.metadata.cipher.as_deref()),
+            result
+                .readiness
+                .as_ref()
+                .map_or_else(|| "-".to_string(), |r| r.grade.to_string()),
+            result.cert_days_remaining.map_or_else(
+                || "-".to_string(),
+                |days| {
+                    let mark = if expires_soon(result, warn_expiry) {
+                        "!"
+                    } else {
+                        ""
+                    };
+                    format!("{}d{}", days, mark)
+                }
+            ),
+            versions(result),
+            hello_size(result),
+            http_versions(result),
+            resumption(result),
+            labels(result),
+            match (&result.error, &result.metadata.cert_validation) {
+                (Some(e), _) => e.to_string(),
+                (None, Some(Err(e))) => format!("unverified certificate ({})", e.problem),
+                (None, _) => "-".to_string(),
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// `tenant=acme,env=prod`, the labels in key order joined by `separator`.
+fn label_list(labels: &BTreeMap<String, String>, separator: &str) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// `1.3+ 1.2-`: `+` for an accepted version, `-` for a rejected one and
+/// `?` when the probe failed.
+fn version_marks(versions: &VersionSupportReport) -> String {
+    let mark = |support: &VersionSupport| match support {
+        VersionSupport::Accepted { .. } => '+',
+        VersionSupport::Rejected { .. } => '-',
+        VersionSupport::Failed { .. } => '?',
+    };
+    format!("1.3{} 1.2{}", mark(&versions.tls13), mark(&versions.tls12))
+}
+
+/// `same`, `MISMATCH!`, `no h2` when the target has no HTTP/2, or `?` when
+/// a probe failed.
+fn http_version_marks(report: &HttpVersionReport) -> &'static str {
+    match (&report.http1, &report.h2) {
+        _ if !report.versions_consistent => "MISMATCH!",
+        (HttpVersionSupport::Negotiated { .. }, HttpVersionSupport::Negotiated { .. }) => "same",
+        (_, HttpVersionSupport::Unsupported { .. }) => "no h2",
+        _ => "?",
+    }
+}
+
+/// The group of a resumed handshake's key exchange, `PSK-ONLY!` for one
+/// without, `not resumed` or `?` when the probe failed.
+fn resumption_mark(report: &ResumptionReport) -> String {
+    match report {
+        ResumptionReport::Resumed { metadata } => match metadata.resumption_kx {
+            Some(ResumptionKx::PskWithGroup(group)) => format!("{:?}", group),
+            Some(ResumptionKx::PskOnly) => "PSK-ONLY!".to_string(),
+            None => "resumed".to_string(),
+        },
+        ResumptionReport::NotResumed { .. } => "not resumed".to_string(),
+        ResumptionReport::Failed { .. } => "?".to_string(),
+    }
+}
+
+fn write_summary(out: &mut dyn Write, summary: &ScanSummary, elapsed: Duration) -> io::Result<()> {
+    let ms = |d: Duration| format!("{:.2}", d.as_secs_f64() * 1000.0);
+
+    writeln!(
+        out,
+        "Scanned {} targets in {:.2}s: {} reachable, {} failed",
+        summary.total,
+        elapsed.as_secs_f64(),
+        summary.reachable,
+        summary.failed
+    )?;
+    if summary.reachable > 0 {
+        for kind in [
+            KeyExchangeKind::Hybrid,
+            KeyExchangeKind::PostQuantum,
+            KeyExchangeKind::Classical,
+        ] {
+            writeln!(
+                out,
+                "  {:<14} {:>6.1}%",
+                kind.to_string(),
+                summary.share(kind) * 100.0
+            )?;
+        }
+        if summary.key_exchange_unknown > 0 {
+            writeln!(
+                out,
+                "  {:<14} {:>6}",
+                "unknown", summary.key_exchange_unknown
+            )?;
+        }
+    }
+    if !summary.groups.is_empty() {
+        writeln!(out, "Top groups:")?;
+        for group in summary.groups.iter().take(5) {
+            writeln!(out, "  {:<22} {:>6}", group.group, group.count)?;
+        }
+    }
+    if !summary.errors.is_empty() {
+        writeln!(out, "Errors:")?;
+        for error in &summary.errors {
+            writeln!(out, "  {:>6}  {}", error.count, error.category)?;
+        }
+    }
+    if let Some(durations) = &summary.durations {
+        writeln!(
+            out,
+            "Duration (ms): median {}, p95 {}, max {}",
+            ms(durations.median),
+            ms(durations.p95),
+            ms(durations.max)
+        )?;
+    }
+    if !summary.latency.is_empty() {
+        writeln!(out, "Latency by key exchange (ms):")?;
+        for (kind, latency) in &summary.latency {
+            write_latency(out, *kind, latency)?;
+        }
+    }
+    if !summary.grades.is_empty() {
+        let grades: Vec<String> = summary
+            .grades
+            .iter()
+            .map(|(grade, count)| format!("{} {}", grade, count))
+            .collect();
+        writeln!(out, "Readiness grades: {}", grades.join(", "))?;
+    }
+    if let Some(penalties) = &summary.hrr_penalties {
+        writeln!(
+            out,
+            "HelloRetryRequests: {}, added (ms): median {}, max {}",
+            summary.hello_retries,
+            ms(penalties.median),
+            ms(penalties.max)
+        )?;
+    }
+    if summary.psk_only_resumptions > 0 {
+        writeln!(
+            out,
+            "PSK-only resumption: {} targets resume without a fresh key exchange",
+            summary.psk_only_resumptions
+        )?;
+    }
+    Ok(())
+}
+
+/// Longest bar of the latency histograms.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// The percentiles of one kind of key exchange and a histogram of its
+/// request durations.
+fn write_latency(
+    out: &mut dyn Write,
+    kind: KeyExchangeKind,
+    latency: &KindLatency,
+) -> io::Result<()> {
+    let ms = |d: Duration| format!("{:.2}", d.as_secs_f64() * 1000.0);
+    let percentiles = |histogram: &LatencyHistogram| {
+        format!(
+            "p50 {}, p90 {}, p95 {}, p99 {}",
+            ms(histogram.p50),
+            ms(histogram.p90),
+            ms(histogram.p95),
+            ms(histogram.p99)
+        )
+    };
+
+    let count = latency.total.as_ref().map_or(0, |total| total.count);
+    write!(out, "  {}: {} targets", kind, count)?;
+    if latency.failed > 0 {
+        write!(out, ", {} failed and left out", latency.failed)?;
+    }
+    writeln!(out)?;
+    if let Some(handshake) = &latency.handshake {
+        writeln!(out, "    handshake  {}", percentiles(handshake))?;
+    }
+    let Some(total) = &latency.total else {
+        return Ok(());
+    };
+    writeln!(out, "    total      {}", percentiles(total))?;
+
+    // The buckets from the fastest to the slowest that has any durations.
+    let first = total.buckets.iter().position(|b| b.count > 0);
+    let last = total.buckets.iter().rposition(|b| b.count > 0);
+    let (first, last) = (first.unwrap_or_default(), last.unwrap_or_default());
+    let widest = total
+        .buckets
+        .iter()
+        .map(|b| b.count)
+        .max()
+        .unwrap_or_default();
+    for (index, bucket) in total.buckets.iter().enumerate().take(last + 1).skip(first) {
+        let bound = match bucket.le {
+            Some(le) => format!("<= {:>5}", le.as_millis()),
+            None => {
+                let previous = index.checked_sub(1).and_then(|i| total.buckets[i].le);
+                format!("> {:>6}", previous.unwrap_or_default().as_millis())
+            }
+        };
+        let bar = (bucket.count * HISTOGRAM_WIDTH).div_ceil(widest.max(1));
+        writeln!(
+            out,
+            "      {} {:>6} {}",
+            bound,
+            bucket.count,
+            "#".repeat(bar)
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use std::process::ExitCode;
+    use std::time::{Duration, SystemTime};
+
+    use clap::Parser;
+    use pqctracer::http_versions::{HttpVersionReport, HttpVersionSupport};
+    use pqctracer::resumption::ResumptionReport;
+    use pqctracer::scan::{ScanMethod, ScanResult, ScanSummary};
+    use pqctracer::testutil::TestTlsServer;
+    use pqctracer::{ErrorCategory, KeyExchangeKind, ReportMeta, ResumptionKx, TlsMetadata};
+    use rustls::NamedGroup;
+
+    use super::{parse_label, write_report, write_scan_csv, write_scan_table, write_summary};
+    use crate::{Cli, Command, OutputFormat};
+
+    const DAY: Duration = Duration::from_secs(86_400);
+
+    /// A server whose certificate is valid from a day ago until `not_after`.
+    async fn server(not_after: SystemTime) -> TestTlsServer {
+        TestTlsServer::builder()
+            .cert_validity(SystemTime::now() - DAY, not_after)
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server")
+    }
+
+    /// `scan` of `server` with `flags`, the CSV report written to `path`.
+    async fn scan(server: &TestTlsServer, path: &Path, flags: &[&str]) -> ExitCode {
+        let args = [
+            "untitled",
+            "scan",
+            "--insecure",
+            "--format",
+            "csv",
+            "--output",
+        ]
+        .into_iter()
+        .map(str::to_string)
+        .chain([path.display().to_string(), server.url()])
+        .chain(flags.iter().map(|flag| flag.to_string()));
+        match Cli::try_parse_from(args).expect("arguments").command {
+            Some(Command::Scan(args)) => super::scan(args).await,
+            _ => unreachable!(),
+        }
+    }
+
+    /// The `cert_days_remaining` and `expiry_warning` of the report's row.
+    fn expiry_columns(path: &Path) -> (String, String) {
+        let report = std::fs::read_to_string(path).expect("read report");
+        let mut lines = report.lines();
+        let header: Vec<&str> = lines.next().expect("header").split(',').collect();
+        let row: Vec<&str> = lines.next().expect("row").split(',').collect();
+        let column = |name: &str| {
+            let index = header.iter().position(|h| *h == name).expect(name);
+            row[index].to_string()
+        };
+        (column("cert_days_remaining"), column("expiry_warning"))
+    }
+
+    #[tokio::test]
+    async fn warn_expiry_marks_rows_and_fails_the_scan() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let path =
+            std::env::temp_dir().join(format!("pqctracer-warn-expiry-{}.csv", std::process::id()));
+        let now = SystemTime::now();
+        let short_lived = server(now + 5 * DAY + DAY / 2).await;
+        let expired = server(now - DAY / 2).await;
+
+        let cases: [(&TestTlsServer, &[&str], ExitCode, &str, &str); 6] = [
+            (&short_lived, &[], ExitCode::SUCCESS, "5", "false"),
+            (
+                &short_lived,
+                &["--warn-expiry", "4"],
+                ExitCode::SUCCESS,
+                "5",
+                "false",
+            ),
+            (
+                &short_lived,
+                &["--warn-expiry", "5"],
+                ExitCode::FAILURE,
+                "5",
+                "true",
+            ),
+            (
+                &short_lived,
+                &["--warn-expiry", "30"],
+                ExitCode::FAILURE,
+                "5",
+                "true",
+            ),
+            // Only an insecure scan gets as far as an expired certificate.
+            (&expired, &[], ExitCode::SUCCESS, "-1", "false"),
+            (
+                &expired,
+                &["--warn-expiry", "0"],
+                ExitCode::FAILURE,
+                "-1",
+                "true",
+            ),
+        ];
+        for (server, flags, code, days, warning) in cases {
+            assert_eq!(scan(server, &path, flags).await, code, "{:?}", flags);
+            assert_eq!(
+                expiry_columns(&path),
+                (days.to_string(), warning.to_string()),
+                "{:?}",
+                flags
+            );
+        }
+        std::fs::remove_file(&path).expect("remove report");
+    }
+
+    /// A result for `target` with a synthetic duration, negotiating `group`
+    /// if given and failing with HTTP `status` if it is not 200.
+    fn result(target: &str, group: Option<&str>, duration_ms: u64, status: u16) -> ScanResult {
+        let mut result = serde_json::json!({
+            "target": target,
+            "url": target,
+            "metadata": { "group": group },
+            "attempts": 1,
+            "duration_ms": duration_ms,
+            "status": status,
+        });
+        if status != 200 {
+            result["error"] = serde_json::json!({
+                "category": { "kind": "http-error", "status": status },
+                "message": format!("HTTP {}", status),
+            });
+        }
+        serde_json::from_value(result).expect("result")
+    }
+
+    fn synthetic_results() -> Vec<ScanResult> {
+        let mut results: Vec<ScanResult> = (1..=20)
+            .map(|ms| result(&format!("h{}", ms), Some("X25519MLKEM768"), ms, 200))
+            .collect();
+        results.push(result("slow-error", Some("X25519MLKEM768"), 9_000, 503));
+        for (target, ms) in [("c1", 5), ("c2", 250), ("c3", 3_000)] {
+            results.push(result(target, Some("X25519"), ms, 200));
+        }
+        let refused = serde_json::json!({
+            "target": "refused",
+            "url": "refused",
+            "metadata": {},
+            "attempts": 1,
+            "duration_ms": 1,
+            "error": { "category": { "kind": "connect-refused" }, "message": "refused" },
+        });
+        let refused: ScanResult = serde_json::from_value(refused).expect("result");
+        results.push(refused);
+        results
+    }
+
+    #[test]
+    fn latency_percentiles_per_key_exchange() {
+        let summary = ScanSummary::from_results(&synthetic_results());
+        let ms = |ms| Some(Duration::from_millis(ms));
+        let percentiles = |kind| {
+            let total = summary.latency[&kind].total.as_ref().expect("durations");
+            (
+                total.count,
+                [total.p50, total.p90, total.p95, total.p99].map(Some),
+            )
+        };
+        assert_eq!(
+            percentiles(KeyExchangeKind::Hybrid),
+            (20, [ms(10), ms(18), ms(19), ms(20)])
+        );
+        assert_eq!(
+            percentiles(KeyExchangeKind::Classical),
+            (3, [ms(250), ms(3_000), ms(3_000), ms(3_000)])
+        );
+        // The failed target counts apart, the unreached one not at all.
+        assert_eq!(summary.latency[&KeyExchangeKind::Hybrid].failed, 1);
+        assert_eq!(summary.latency[&KeyExchangeKind::Classical].failed, 0);
+        assert_eq!(summary.latency.len(), 2);
+        assert_eq!(summary.latency[&KeyExchangeKind::Hybrid].handshake, None);
+    }
+
+    #[test]
+    fn latency_table_and_json_buckets() {
+        let results = synthetic_results();
+        let mut table = Vec::new();
+        let summary = ScanSummary::from_results(&results);
+        write_summary(&mut table, &summary, Duration::from_secs(1)).expect("write");
+        let table = String::from_utf8(table).expect("UTF-8");
+        let block: Vec<&str> = table
+            .lines()
+            .skip_while(|line| !line.starts_with("Latency by key exchange"))
+            .take_while(|line| line.starts_with("Latency") || line.starts_with(' '))
+            .collect();
+        assert_eq!(
+            block,
+            [
+                "Latency by key exchange (ms):",
+                "  classical: 3 targets",
+                "    total      p50 250.00, p90 3000.00, p95 3000.00, p99 3000.00",
+                "      <=     5      1 ########################################",
+                "      <=    10      0 ",
+                "      <=    25      0 ",
+                "      <=    50      0 ",
+                "      <=   100      0 ",
+                "      <=   250      1 ########################################",
+                "      <=   500      0 ",
+                "      <=  1000      0 ",
+                "      <=  2500      0 ",
+                "      <=  5000      1 ########################################",
+                "  hybrid: 20 targets, 1 failed and left out",
+                "    total      p50 10.00, p90 18.00, p95 19.00, p99 20.00",
+                "      <=     1      1 ####",
+                "      <=     2      1 ####",
+                "      <=     5      3 ############",
+                "      <=    10      5 ####################",
+                "      <=    25     10 ########################################",
+            ],
+            "{}",
+            table
+        );
+
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let client = pqctracer::TlsAwareClient::new();
+        let mut json = Vec::new();
+        let elapsed = Duration::from_secs(1);
+        write_report(
+            &mut json,
+            OutputFormat::Json,
+            &results,
+            elapsed,
+            None,
+            &client,
+        )
+        .expect("write");
+        let json: serde_json::Value = serde_json::from_slice(&json).expect("JSON");
+        let hybrid = &json["summary"]["latency"]["hybrid"];
+        assert_eq!(hybrid["failed"], 1);
+        assert_eq!(hybrid["total"]["p95_ms"], 19.0);
+        let buckets = hybrid["total"]["buckets"].as_array().expect("buckets");
+        assert_eq!(buckets.len(), 14);
+        assert_eq!(buckets[3], serde_json::json!({ "le_ms": 10.0, "count": 5 }));
+        assert_eq!(
+            buckets[13],
+            serde_json::json!({ "le_ms": null, "count": 0 })
+        );
+        let counted: u64 = buckets.iter().map(|b| b["count"].as_u64().unwrap()).sum();
+        assert_eq!(counted, 20);
+    }
+
+    /// The report of scanning `targets` with `flags`, in `format`.
+    async fn scan_report(targets: &[String], flags: &[&str], format: &str, name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "pqctracer-scan-{}-{}.{}",
+            name,
+            std::process::id(),
+            format
+        ));
+        let args = [
+            "untitled",
+            "scan",
+            "--insecure",
+            "--format",
+            format,
+            "--output",
+        ]
+        .into_iter()
+        .map(str::to_string)
+        .chain([path.display().to_string()])
+        .chain(targets.iter().cloned())
+        .chain(flags.iter().map(|flag| flag.to_string()));
+        let code = match Cli::try_parse_from(args).expect("arguments").command {
+            Some(Command::Scan(args)) => super::scan(args).await,
+            _ => unreachable!(),
+        };
+        assert_eq!(code, ExitCode::SUCCESS);
+        let report = std::fs::read_to_string(&path).expect("read report");
+        std::fs::remove_file(&path).expect("remove report");
+        report
+    }
+
+    /// The results of scanning `targets` with `flags`, from the JSON report.
+    async fn scan_json(targets: &[String], flags: &[&str], name: &str) -> Vec<ScanResult> {
+        let report = scan_report(targets, flags, "json", name).await;
+        let report: serde_json::Value = serde_json::from_str(&report).expect("JSON report");
+        serde_json::from_value(report["results"].clone()).expect("results")
+    }
+
+    /// How many handshakes `server` has seen, once they are all recorded.
+    async fn handshakes(server: &TestTlsServer, expected: usize) -> usize {
+        for _ in 0..100 {
+            if server.observed().len() >= expected {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        server.observed().len()
+    }
+
+    #[tokio::test]
+    async fn redundant_targets_are_scanned_once() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let server = TestTlsServer::builder()
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let url = server.url();
+        let port = server.local_addr().port();
+        let inputs = vec![
+            url.clone(),
+            format!("{}/", url),
+            url.to_uppercase(),
+            format!("localhost:{}", port),
+            format!("{}/health?full=1", url),
+        ];
+
+        let results = scan_json(&inputs, &[], "normalized").await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].target, url);
+        assert_eq!(results[0].sources, inputs);
+        assert_eq!(handshakes(&server, 1).await, 1);
+
+        let results = scan_json(&inputs, &["--keep-paths"], "paths").await;
+        let targets: Vec<&str> = results.iter().map(|r| r.target.as_str()).collect();
+        assert_eq!(targets, [url.clone(), format!("{}/health?full=1", url)]);
+        assert_eq!(results[0].sources, inputs[..4]);
+        assert_eq!(handshakes(&server, 3).await, 3);
+
+        // Without normalization every input is a target of its own, and
+        // rows name no sources.
+        let results = scan_json(&inputs, &["--no-normalize"], "raw").await;
+        assert_eq!(results.len(), inputs.len());
+        assert!(results.iter().all(|r| r.sources.is_empty()));
+        assert_eq!(handshakes(&server, 8).await, 8);
+    }
+
+    #[tokio::test]
+    async fn psk_only_resumption_is_flagged() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let tls13 = TestTlsServer::builder()
+            .tls13_tickets(1)
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let tls12 = TestTlsServer::builder()
+            .tls12_only(true)
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let targets = [tls13.url(), tls12.url()];
+        let results = scan_json(&targets, &["--check-resumption"], "resumption").await;
+        let kx: Vec<Option<ResumptionKx>> = results
+            .iter()
+            .map(|r| r.resumption.as_ref().and_then(ResumptionReport::kx))
+            .collect();
+        assert_eq!(
+            kx,
+            [
+                Some(ResumptionKx::PskWithGroup(NamedGroup::X25519MLKEM768)),
+                Some(ResumptionKx::PskOnly)
+            ]
+        );
+
+        let client = pqctracer::TlsAwareClient::new();
+        let report = |format| {
+            let mut out = Vec::new();
+            write_report(
+                &mut out,
+                format,
+                &results,
+                Duration::from_secs(1),
+                None,
+                &client,
+            )
+            .expect("write");
+            String::from_utf8(out).expect("UTF-8")
+        };
+        let table = report(OutputFormat::Table);
+        let mut lines = table.lines();
+        assert!(lines.next().expect("header").contains(" resumption "));
+        assert!(lines.next().expect("row").contains(" X25519MLKEM768 "));
+        assert!(lines.next().expect("row").contains(" PSK-ONLY! "));
+        assert!(
+            table
+                .contains("\nPSK-only resumption: 1 targets resume without a fresh key exchange\n"),
+            "{}",
+            table
+        );
+
+        let csv = report(OutputFormat::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert!(lines[0].ends_with(",resumption,labels"), "{}", csv);
+        assert!(
+            lines[1].ends_with(",\"resumed, PSK with X25519MLKEM768\","),
+            "{}",
+            csv
+        );
+        assert!(lines[2].ends_with(",\"resumed, PSK only\","), "{}", csv);
+
+        // Without --check-resumption there is neither column nor flag.
+        let results = scan_json(&targets[1..], &[], "no-resumption").await;
+        assert!(results[0].resumption.is_none());
+        let mut table = Vec::new();
+        write_report(
+            &mut table,
+            OutputFormat::Table,
+            &results,
+            Duration::from_secs(1),
+            None,
+            &client,
+        )
+        .expect("write");
+        let table = String::from_utf8(table).expect("UTF-8");
+        assert!(!table.contains("resumption"), "{}", table);
+    }
+
+    #[test]
+    fn http_version_mismatches_are_highlighted() {
+        let negotiated = |group: &str| HttpVersionSupport::Negotiated {
+            metadata: Box::new(TlsMetadata::new(Some(group.to_string()), None)),
+        };
+        let reports = [
+            (negotiated("X25519MLKEM768"), negotiated("X25519"), false),
+            (
+                negotiated("X25519MLKEM768"),
+                negotiated("X25519MLKEM768"),
+                true,
+            ),
+            (
+                negotiated("X25519MLKEM768"),
+                HttpVersionSupport::Unsupported {
+                    reason: "no protocol selected".to_string(),
+                },
+                true,
+            ),
+            (
+                HttpVersionSupport::Failed {
+                    error: ErrorCategory::ConnectRefused,
+                },
+                HttpVersionSupport::Failed {
+                    error: ErrorCategory::ConnectRefused,
+                },
+                true,
+            ),
+        ];
+        let results: Vec<ScanResult> = reports
+            .into_iter()
+            .enumerate()
+            .map(|(i, (http1, h2, versions_consistent))| {
+                let mut result = result(&format!("h{}", i), Some("X25519MLKEM768"), 1, 200);
+                result.http_versions = Some(HttpVersionReport {
+                    http1,
+                    h2,
+                    versions_consistent,
+                });
+                result
+            })
+            .collect();
+
+        let mut table = Vec::new();
+        write_scan_table(&mut table, &results, None).expect("write");
+        let table = String::from_utf8(table).expect("UTF-8");
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].contains(" h1/h2 "), "{}", table);
+        for (line, mark) in lines[1..].iter().zip(["MISMATCH!", "same", "no h2", "?"]) {
+            assert!(line.contains(&format!(" {:<13} ", mark)), "{}", table);
+        }
+
+        let mut csv = Vec::new();
+        write_scan_csv(&mut csv, &results, None).expect("write");
+        let csv = String::from_utf8(csv).expect("UTF-8");
+        assert!(
+            csv.contains(",\"http/1.1 X25519MLKEM768, h2 X25519 (INCONSISTENT)\","),
+            "{}",
+            csv
+        );
+    }
+
+    #[tokio::test]
+    async fn labels_are_reported_in_every_format() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let server = TestTlsServer::builder()
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let flags = ["--label", "tenant=acme", "--label", "env=prod=eu"];
+        let results = scan_json(&[server.url()], &flags, "labels").await;
+        let expected = BTreeMap::from([
+            ("env".to_string(), "prod=eu".to_string()),
+            ("tenant".to_string(), "acme".to_string()),
+        ]);
+        assert_eq!(results[0].metadata.labels, expected);
+
+        let client = pqctracer::TlsAwareClient::new();
+        let report = |format, results: &[ScanResult]| {
+            let mut out = Vec::new();
+            write_report(
+                &mut out,
+                format,
+                results,
+                Duration::from_secs(1),
+                None,
+                &client,
+            )
+            .expect("write");
+            String::from_utf8(out).expect("UTF-8")
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&report(OutputFormat::Json, &results)).expect("JSON");
+        assert_eq!(
+            json["results"][0]["metadata"]["labels"],
+            serde_json::json!({ "env": "prod=eu", "tenant": "acme" })
+        );
+
+        let table = report(OutputFormat::Table, &results);
+        let mut lines = table.lines();
+        assert!(
+            lines.next().expect("header").contains(" labels "),
+            "{}",
+            table
+        );
+        assert!(
+            lines
+                .next()
+                .expect("row")
+                .contains(" env=prod=eu,tenant=acme "),
+            "{}",
+            table
+        );
+
+        let csv = report(OutputFormat::Csv, &results);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert!(lines[0].ends_with(",labels"), "{}", csv);
+        assert!(lines[1].ends_with(",env=prod=eu; tenant=acme"), "{}", csv);
+
+        // Without --label there is no column, and nothing in the JSON.
+        let results = scan_json(&[server.url()], &[], "no-labels").await;
+        assert!(results[0].metadata.labels.is_empty());
+        assert!(!report(OutputFormat::Table, &results).contains("labels"));
+        let json = report(OutputFormat::Json, &results);
+        assert!(!json.contains("\"labels\""), "{}", json);
+    }
+
+    #[test]
+    fn labels_need_a_key_and_a_value() {
+        assert_eq!(
+            parse_label("tenant="),
+            Ok(("tenant".to_string(), String::new()))
+        );
+        for label in ["tenant", "=acme", ""] {
+            assert_eq!(
+                parse_label(label),
+                Err(format!("invalid label {}: expected KEY=VALUE", label))
+            );
+        }
+    }
+
+    /// The address targets of `scan --sni example.com` with `flags`.
+    fn cidr_targets(flags: &[&str]) -> Result<Vec<String>, String> {
+        let args = ["untitled", "scan", "--sni", "example.com"]
+            .into_iter()
+            .chain(flags.iter().copied());
+        match Cli::try_parse_from(args).expect("arguments").command {
+            Some(Command::Scan(args)) => Ok(args
+                .addresses()?
+                .iter()
+                .map(|target| match target {
+                    super::ScanTarget::Address { addr, .. } => addr.to_string(),
+                    other => panic!("{:?}", other),
+                })
+                .collect()),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn cidr_ranges_are_expanded_within_the_limits() {
+        let targets = cidr_targets(&["--cidr", "203.0.113.0/30", "--cidr", "2001:db8::1/128"]);
+        assert_eq!(
+            targets.unwrap(),
+            [
+                "203.0.113.0:443",
+                "203.0.113.1:443",
+                "203.0.113.2:443",
+                "203.0.113.3:443",
+                "[2001:db8::1]:443"
+            ]
+        );
+        assert_eq!(
+            cidr_targets(&["--cidr", "203.0.113.0/24"]).unwrap().len(),
+            256
+        );
+
+        // Both ranges count against the cap.
+        let over_cap = ["--cidr", "203.0.113.0/24", "--cidr", "198.51.100.1/32"];
+        assert_eq!(
+            cidr_targets(&over_cap).unwrap_err(),
+            "--cidr covers 257 addresses; pass --yes-i-know to scan more than 256"
+        );
+        let confirmed = [&over_cap[..], &["--yes-i-know"]].concat();
+        assert_eq!(cidr_targets(&confirmed).unwrap().len(), 257);
+
+        for range in ["0.0.0.0/0", "::/0", "10.0.0.0/7"] {
+            assert!(
+                cidr_targets(&["--cidr", range, "--yes-i-know"])
+                    .unwrap_err()
+                    .ends_with("addresses, more than the limit of 16777216"),
+                "{}",
+                range
+            );
+        }
+        assert_eq!(
+            cidr_targets(&["--cidr", "::/0", "--yes-i-know"]).unwrap_err(),
+            format!(
+                "--cidr covers {} addresses, more than the limit of 16777216",
+                u128::MAX
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn json_reports_open_with_the_meta_block() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let server = TestTlsServer::builder()
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let targets = [server.url()];
+        let report = scan_report(&targets, &[], "json", "meta").await;
+        let report: serde_json::Value = serde_json::from_str(&report).expect("JSON report");
+        let meta: ReportMeta = serde_json::from_value(report["meta"].clone()).expect("meta");
+
+        // The build's provider and the groups it offers, as the server saw
+        // them.
+        assert_eq!(meta.provider, "aws-lc-rs");
+        assert!(meta.prefer_post_quantum);
+        assert_eq!(
+            serde_json::json!(meta.kx_groups),
+            report["client"]["kx_groups"]
+        );
+        let offered: Vec<String> = server.observed()[0]
+            .offered_groups
+            .iter()
+            .map(|group| format!("{:?}", group))
+            .collect();
+        assert_eq!(meta.kx_groups, offered);
+        assert_eq!(meta.kx_groups[0], "X25519MLKEM768");
+        // The library's features this binary enables.
+        for feature in ["dns", "serde", "test-util"] {
+            assert!(meta.features.contains(&feature.to_string()), "{:?}", meta);
+        }
+        assert_eq!(
+            meta.pqctracer_version,
+            pqctracer::TlsAwareClient::new()
+                .report_meta()
+                .pqctracer_version
+        );
+
+        // JSONL output has it as its first record.
+        let report = scan_report(&targets, &[], "jsonl", "meta").await;
+        let first: serde_json::Value =
+            serde_json::from_str(report.lines().next().expect("meta record")).expect("JSON");
+        let first: ReportMeta = serde_json::from_value(first["meta"].clone()).expect("meta");
+        assert_eq!(first.kx_groups, meta.kx_groups);
+        assert_eq!(report.lines().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn scans_connect_from_the_local_address() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let server = TestTlsServer::builder()
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let targets = [server.url()];
+        let flags = ["--local-addr", "127.0.0.2"];
+        let results = scan_json(&targets, &flags, "local-addr").await;
+        let peer = server.observed()[0].peer;
+        assert_eq!(peer.ip().to_string(), "127.0.0.2");
+        assert_eq!(results[0].metadata.local_addr, Some(peer));
+        assert_eq!(results[0].metadata.remote_addr, Some(server.local_addr()));
+    }
+
+    #[tokio::test]
+    async fn rejected_head_requests_fall_back_to_get_when_asked() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let server = TestTlsServer::builder()
+            .http_handler(|request| match request.starts_with("HEAD ") {
+                true => "405 Method Not Allowed".to_string(),
+                false => "200 OK".to_string(),
+            })
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let targets = [server.url()];
+
+        let results = scan_json(&targets, &["--fallback-get"], "fallback-get").await;
+        assert_eq!(results[0].status, Some(200));
+        assert_eq!(results[0].method, Some(ScanMethod::Get));
+        let results = scan_json(&targets, &["--method", "get"], "method-get").await;
+        assert_eq!(results[0].attempts, 1);
+        assert_eq!(results[0].method, Some(ScanMethod::Get));
+
+        let mut csv = Vec::new();
+        write_scan_csv(&mut csv, &results, None).expect("write");
+        let csv = String::from_utf8(csv).expect("UTF-8");
+        let columns: Vec<&str> = csv.lines().next().expect("header").split(',').collect();
+        let row: Vec<&str> = csv.lines().nth(1).expect("row").split(',').collect();
+        let method = columns
+            .iter()
+            .position(|c| *c == "method")
+            .expect("method column");
+        assert_eq!(row[method], "GET");
+    }
+}
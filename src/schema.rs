@@ -0,0 +1,931 @@
+//! The JSON Schema of the machine-readable output, `schema/pqctracer-output.schema.json`,
+//! and `schema --validate`, which checks output files against it.
+//!
+//! The validator covers the keywords the schema uses – `type`, `enum`,
+//! `const`, `minimum`, `maximum`, `properties`, `required`,
+//! `additionalProperties`, `items`, `anyOf`, `oneOf` and local `$ref`s –
+//! and loading the schema fails on any other, so that a keyword added to
+//! the file cannot be ignored silently. `format` is an annotation only.
+
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Args;
+use serde_json::{Map, Value};
+
+/// The schema as published, with the crate version in its `$id`.
+pub const SCHEMA: &str = include_str!("../schema/pqctracer-output.schema.json");
+
+/// Keywords that only describe, and that the validator skips.
+const ANNOTATIONS: &[&str] = &["$schema", "$id", "$defs", "title", "description", "format"];
+
+/// Keywords the validator checks.
+const ASSERTIONS: &[&str] = &[
+    "$ref",
+    "type",
+    "enum",
+    "const",
+    "minimum",
+    "maximum",
+    "properties",
+    "required",
+    "additionalProperties",
+    "items",
+    "anyOf",
+    "oneOf",
+];
+
+#[derive(Args)]
+pub struct SchemaArgs {
+    /// Check FILE, a JSON document or JSON Lines, against the schema instead
+    /// of printing it; `-` reads standard input.
+    #[arg(long, value_name = "FILE")]
+    validate: Option<PathBuf>,
+}
+
+/// A place where a document breaks the schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// JSON pointer to the offending value, `` for the document itself.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.as_str() {
+            "" => f.write_str(&self.message),
+            path => write!(f, "{}: {}", path, self.message),
+        }
+    }
+}
+
+pub struct Schema {
+    root: Value,
+}
+
+impl Schema {
+    /// The published schema.
+    pub fn published() -> Self {
+        Self::parse(SCHEMA).expect("the published schema is valid")
+    }
+
+    fn parse(json: &str) -> Result<Self, Box<dyn Error>> {
+        let root: Value = serde_json::from_str(json)?;
+        let schema = Self { root };
+        schema.check_keywords(&schema.root, "")?;
+        Ok(schema)
+    }
+
+    /// Every violation of the schema in `document`; empty when it is valid.
+    pub fn validate(&self, document: &Value) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        self.check(&self.root, document, "", &mut violations);
+        violations
+    }
+
+    /// Refuse keywords the validator would ignore, anywhere in `schema`.
+    fn check_keywords(&self, schema: &Value, at: &str) -> Result<(), Box<dyn Error>> {
+        let Value::Object(schema) = schema else {
+            return match schema {
+                Value::Bool(_) => Ok(()),
+                _ => Err(format!("{}: a schema must be an object or a boolean", at).into()),
+            };
+        };
+        for (keyword, value) in schema {
+            let at = format!("{}/{}", at, keyword);
+            if !ANNOTATIONS.contains(&keyword.as_str()) && !ASSERTIONS.contains(&keyword.as_str()) {
+                return Err(format!("{}: unsupported keyword", at).into());
+            }
+            match keyword.as_str() {
+                "$defs" | "properties" => {
+                    for (name, schema) in value.as_object().into_iter().flatten() {
+                        self.check_keywords(schema, &format!("{}/{}", at, name))?;
+                    }
+                }
+                "items" | "additionalProperties" => self.check_keywords(value, &at)?,
+                "anyOf" | "oneOf" => {
+                    for (i, schema) in value.as_array().into_iter().flatten().enumerate() {
+                        self.check_keywords(schema, &format!("{}/{}", at, i))?;
+                    }
+                }
+                "$ref" => {
+                    self.resolve(value.as_str().unwrap_or_default())
+                        .ok_or_else(|| format!("{}: unresolved reference", at))?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The schema a local reference such as `#/$defs/scan-result` points to.
+    fn resolve(&self, reference: &str) -> Option<&Value> {
+        self.root.pointer(reference.strip_prefix('#')?)
+    }
+
+    fn check(&self, schema: &Value, value: &Value, path: &str, out: &mut Vec<Violation>) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => return out.push(violation(path, "no value is allowed here")),
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let target = self
+                .resolve(reference)
+                .expect("references are checked on load");
+            self.check(target, value, path, out);
+        }
+        if let Some(types) = schema.get("type")
+            && !matches_type(types, value)
+        {
+            return out.push(violation(
+                path,
+                format!("expected {}, got {}", type_names(types), type_of(value)),
+            ));
+        }
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+            && !allowed.contains(value)
+        {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            out.push(violation(
+                path,
+                format!("{} is not one of {}", value, allowed.join(", ")),
+            ));
+        }
+        if let Some(expected) = schema.get("const")
+            && expected != value
+        {
+            out.push(violation(
+                path,
+                format!("expected {}, got {}", expected, value),
+            ));
+        }
+        if let Some(number) = value.as_f64() {
+            if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64)
+                && number < minimum
+            {
+                out.push(violation(
+                    path,
+                    format!("{} is less than {}", value, minimum),
+                ));
+            }
+            if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64)
+                && number > maximum
+            {
+                out.push(violation(
+                    path,
+                    format!("{} is more than {}", value, maximum),
+                ));
+            }
+        }
+        if let Value::Object(object) = value {
+            self.check_object(schema, object, path, out);
+        }
+        if let (Some(items), Value::Array(array)) = (schema.get("items"), value) {
+            for (i, item) in array.iter().enumerate() {
+                self.check(items, item, &format!("{}/{}", path, i), out);
+            }
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(branches) = schema.get(keyword).and_then(Value::as_array) {
+                self.check_branches(keyword, branches, value, path, out);
+            }
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &str,
+        out: &mut Vec<Violation>,
+    ) {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for name in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let name = name.as_str().unwrap_or_default();
+            if !object.contains_key(name) {
+                out.push(violation(
+                    path,
+                    format!("missing required property `{}`", name),
+                ));
+            }
+        }
+        for (name, value) in object {
+            let at = format!("{}/{}", path, escape(name));
+            match (
+                properties.and_then(|p| p.get(name)),
+                schema.get("additionalProperties"),
+            ) {
+                (Some(property), _) => self.check(property, value, &at, out),
+                (None, Some(Value::Bool(false))) => {
+                    out.push(violation(path, format!("unexpected property `{}`", name)));
+                }
+                (None, Some(additional)) => self.check(additional, value, &at, out),
+                (None, None) => {}
+            }
+        }
+    }
+
+    /// `anyOf` and `oneOf`. When no branch matches, the violations of the
+    /// branch that came closest are reported: those of the right kind of
+    /// document rather than a list for every other kind. Closest is fewest
+    /// violations at `path` itself, then fewest in all, so that a report
+    /// whose every result lacks the same field still counts as a report.
+    fn check_branches(
+        &self,
+        keyword: &str,
+        branches: &[Value],
+        value: &Value,
+        path: &str,
+        out: &mut Vec<Violation>,
+    ) {
+        let outcomes: Vec<Vec<Violation>> = branches
+            .iter()
+            .map(|branch| {
+                let mut violations = Vec::new();
+                self.check(branch, value, path, &mut violations);
+                violations
+            })
+            .collect();
+        let matched = outcomes.iter().filter(|v| v.is_empty()).count();
+        if matched == 0 {
+            let closest = outcomes
+                .into_iter()
+                .min_by_key(|v| (v.iter().filter(|v| v.path == path).count(), v.len()))
+                .unwrap_or_default();
+            out.extend(closest);
+        } else if keyword == "oneOf" && matched > 1 {
+            out.push(violation(
+                path,
+                format!("matches {} of the oneOf schemas", matched),
+            ));
+        }
+    }
+}
+
+fn violation(path: &str, message: impl Into<String>) -> Violation {
+    Violation {
+        path: path.to_string(),
+        message: message.into(),
+    }
+}
+
+/// Escape a property name for a JSON pointer.
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+fn matches_type(types: &Value, value: &Value) -> bool {
+    let matches = |name: &str| match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        _ => false,
+    };
+    match types {
+        Value::String(name) => matches(name),
+        Value::Array(names) => names.iter().filter_map(Value::as_str).any(matches),
+        _ => false,
+    }
+}
+
+fn type_names(types: &Value) -> String {
+    match types {
+        Value::Array(names) => {
+            let names: Vec<&str> = names.iter().filter_map(Value::as_str).collect();
+            names.join(" or ")
+        }
+        other => other.as_str().unwrap_or_default().to_string(),
+    }
+}
+
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// The documents in `contents`: the one JSON document it holds, or one per
+/// non-empty line of JSON Lines, each with its line number.
+fn documents(contents: &str) -> Result<Vec<(usize, Value)>, String> {
+    if let Ok(document) = serde_json::from_str(contents) {
+        return Ok(vec![(1, document)]);
+    }
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .map(|document| (i + 1, document))
+                .map_err(|e| format!("line {}: not JSON: {}", i + 1, e))
+        })
+        .collect()
+}
+
+pub fn schema(args: SchemaArgs) -> ExitCode {
+    let Some(path) = args.validate else {
+        print!("{}", SCHEMA);
+        return ExitCode::SUCCESS;
+    };
+    let name = path.display().to_string();
+    let contents = if name == "-" {
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .map(|_| contents)
+    } else {
+        std::fs::read_to_string(&path)
+    };
+    let contents = match contents {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", name, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let documents = match documents(&contents) {
+        Ok(documents) if documents.is_empty() => {
+            eprintln!("{}: no JSON documents", name);
+            return ExitCode::FAILURE;
+        }
+        Ok(documents) => documents,
+        Err(e) => {
+            eprintln!("{}: {}", name, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let schema = Schema::published();
+    let mut invalid = 0;
+    for (line, document) in &documents {
+        let violations = schema.validate(document);
+        if !violations.is_empty() {
+            invalid += 1;
+        }
+        for violation in violations {
+            eprintln!("{}:{}: {}", name, line, violation);
+        }
+    }
+    if invalid > 0 {
+        eprintln!(
+            "{}: {} of {} documents invalid",
+            name,
+            invalid,
+            documents.len()
+        );
+        return ExitCode::FAILURE;
+    }
+    println!("{}: {} documents valid", name, documents.len());
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::{Duration, SystemTime};
+
+    use pqctracer::dane::{DaneResult, TlsaMatch, TlsaOutcome};
+    use pqctracer::dns::{HttpsRecord, TlsaRecord};
+    use pqctracer::hello_size::{HelloProbe, HelloSizeReport, HelloSizeVerdict};
+    use pqctracer::http_versions::{HttpVersionReport, HttpVersionSupport};
+    use pqctracer::idn::IdnName;
+    use pqctracer::readiness::ReadinessWeights;
+    use pqctracer::resumption::ResumptionReport;
+    use pqctracer::scan::{ScanError, ScanMethod, ScanOptions, ScanResult};
+    use pqctracer::testutil::TestTlsServer;
+    use pqctracer::versions::{VersionSupport, VersionSupportReport};
+    use pqctracer::{
+        AlertInfo, AlertLevel, CaptureStatus, CertError, CertificateProblem, ErrorCategory,
+        ExtensionInfo, FallbackOutcome, HandshakeFailureKind, HandshakeOrigin, NameMismatch,
+        ResumptionKx, Timings, TlsAwareClient, TlsMetadata, ValidationReport,
+    };
+    use rustls::NamedGroup;
+
+    use super::*;
+    use crate::OutputFormat;
+    use crate::scan::write_report;
+    use crate::watch::{Observation, Payload, Watcher};
+
+    fn install_provider() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+    }
+
+    /// Assert that every document validates, with the violations if not.
+    fn assert_valid(documents: &[Value]) {
+        let schema = Schema::published();
+        for document in documents {
+            let violations = schema.validate(document);
+            assert!(
+                violations.is_empty(),
+                "{}\n{}",
+                violations
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                serde_json::to_string_pretty(document).unwrap()
+            );
+        }
+    }
+
+    /// The scan report of `results` in `format`, as documents.
+    fn report(format: OutputFormat, results: &[ScanResult]) -> Vec<Value> {
+        install_provider();
+        let mut out = Vec::new();
+        let client = TlsAwareClient::new();
+        write_report(
+            &mut out,
+            format,
+            results,
+            Duration::from_secs(1),
+            None,
+            &client,
+        )
+        .expect("write report");
+        let out = String::from_utf8(out).expect("UTF-8");
+        documents(&out)
+            .expect("JSON")
+            .into_iter()
+            .map(|(_, d)| d)
+            .collect()
+    }
+
+    /// Metadata with every field set. `TlsMetadata` is non-exhaustive
+    /// here, so the fields are set one by one, and the document it becomes
+    /// is checked to have every property the schema describes.
+    fn filled_metadata(fallback: FallbackOutcome) -> TlsMetadata {
+        let millis = Duration::from_micros(1500);
+        let mut metadata = TlsMetadata::default();
+        metadata.group = Some("X25519MLKEM768".to_string());
+        metadata.cipher = Some("TLS13_AES_256_GCM_SHA384".to_string());
+        metadata.peer_certificates = Vec::new();
+        metadata.origin = Some(HandshakeOrigin::Resumed);
+        metadata.local_addr = "127.0.0.1:40512".parse().ok();
+        metadata.remote_addr = "[::1]:443".parse().ok();
+        metadata.idn = IdnName::of("münchen.example");
+        metadata.capture_status = CaptureStatus::Captured;
+        metadata.handshake_duration = Some(millis);
+        metadata.hrr_penalty = Some(millis);
+        metadata.handshake_bytes_sent = Some(1532);
+        metadata.handshake_bytes_received = Some(687);
+        metadata.alerts_received = vec![AlertInfo {
+            level: AlertLevel::Warning,
+            description: "UnrecognisedName".to_string(),
+        }];
+        metadata.server_extensions = Some(vec![
+            ExtensionInfo {
+                id: 51,
+                name: "key_share".to_string(),
+                detail: Some("X25519MLKEM768".to_string()),
+            },
+            ExtensionInfo {
+                id: 23,
+                name: "extended_master_secret".to_string(),
+                detail: None,
+            },
+        ]);
+        metadata.https_records = vec![HttpsRecord {
+            priority: 1,
+            target: ".".to_string(),
+            alpn: vec!["h2".to_string()],
+            no_default_alpn: true,
+            port: Some(8443),
+            ipv4_hints: vec!["192.0.2.1".parse().unwrap()],
+            ipv6_hints: vec!["2001:db8::1".parse().unwrap()],
+            ech: true,
+            ech_config_list: Some(vec![0, 1]),
+        }];
+        metadata.ech_offered = true;
+        metadata.dane = Some(DaneResult {
+            records_found: 3,
+            matched: true,
+            authenticated: false,
+            details: [
+                TlsaOutcome::Matched { certificate: 0 },
+                TlsaOutcome::PkixUnverified { certificate: 1 },
+                TlsaOutcome::NoMatch,
+                TlsaOutcome::Unusable {
+                    reason: "unknown usage 9".to_string(),
+                },
+            ]
+            .into_iter()
+            .map(|outcome| TlsaMatch {
+                record: TlsaRecord {
+                    usage: 3,
+                    selector: 1,
+                    matching_type: 1,
+                    data: vec![0xab, 0xcd],
+                },
+                outcome,
+            })
+            .collect(),
+            error: Some("lookup timed out".to_string()),
+        });
+        metadata.timings = Some(Timings {
+            dns: Some(millis),
+            connect: None,
+            tls: Some(millis),
+            ttfb: millis,
+            total: Some(millis),
+        });
+        metadata.validation = Some(ValidationReport {
+            trust_anchor: Some("CN=Example Root".to_string()),
+            chain_length: 2,
+            path_intermediates: Some(1),
+            cross_signed: true,
+            duration: millis,
+            error: Some("UnknownIssuer".to_string()),
+        });
+        metadata.cert_validation = Some(Err(CertError {
+            problem: CertificateProblem::NameMismatch,
+            detail: "certificate not valid for name".to_string(),
+            name_mismatch: Some(name_mismatch()),
+        }));
+        metadata.pin_matched = Some(false);
+        metadata.alpn = Some("h2".to_string());
+        metadata.tickets_issued = Some(2);
+        metadata.resumption_kx = Some(ResumptionKx::PskWithGroup(NamedGroup::X25519MLKEM768));
+        metadata.client_cert_requested = Some(true);
+        metadata.client_cert_authorities = vec!["CN=Example Client CA".to_string()];
+        metadata.fallback = Some(fallback);
+        metadata.labels = BTreeMap::from([("tenant".to_string(), "acme".to_string())]);
+        metadata
+    }
+
+    fn name_mismatch() -> NameMismatch {
+        NameMismatch {
+            expected: "example.com".to_string(),
+            presented: vec!["*.example.com".to_string()],
+            wildcard_near_miss: Some("*.example.com".to_string()),
+        }
+    }
+
+    /// A result with every field set, written out in full like
+    /// [`filled_metadata`].
+    fn filled_result(metadata: TlsMetadata, error: Option<ErrorCategory>) -> ScanResult {
+        let boxed = || Box::new(metadata.clone());
+        let failed = ErrorCategory::ConnectRefused;
+        ScanResult {
+            target: "example.com".to_string(),
+            url: "https://example.com/".to_string(),
+            sources: vec!["EXAMPLE.COM:443".to_string()],
+            sni: Some("example.com".to_string()),
+            status: Some(200),
+            method: Some(ScanMethod::Get),
+            readiness: Some(metadata.readiness(&ReadinessWeights::default())),
+            metadata: metadata.clone(),
+            error: error.map(|category| ScanError {
+                category,
+                message: "failed".to_string(),
+                name_mismatch: Some(name_mismatch()),
+            }),
+            attempts: 2,
+            duration: Duration::from_millis(12),
+            cert_not_before: Some(SystemTime::UNIX_EPOCH),
+            cert_not_after: Some(SystemTime::now()),
+            cert_days_remaining: Some(-3),
+            versions: Some(VersionSupportReport {
+                tls13: VersionSupport::Accepted { metadata: boxed() },
+                tls12: VersionSupport::Rejected {
+                    reason: "AlertReceived(ProtocolVersion)".to_string(),
+                },
+            }),
+            hello_size: Some(HelloSizeReport {
+                verdict: HelloSizeVerdict::SizeProblem,
+                hybrid_hello_len: 1456,
+                classical_hello_len: 234,
+                padded_hello_len: 1456,
+                hybrid: HelloProbe::Failed {
+                    error: failed.clone(),
+                },
+                padded: HelloProbe::Failed {
+                    error: failed.clone(),
+                },
+                classical: Some(HelloProbe::Completed { group: None }),
+            }),
+            http_versions: Some(HttpVersionReport {
+                http1: HttpVersionSupport::Negotiated { metadata: boxed() },
+                h2: HttpVersionSupport::Unsupported {
+                    reason: "no protocol selected".to_string(),
+                },
+                versions_consistent: true,
+            }),
+            resumption: Some(ResumptionReport::Resumed { metadata: boxed() }),
+        }
+    }
+
+    #[test]
+    fn the_id_carries_the_crate_version() {
+        install_provider();
+        let schema = Schema::published();
+        let version = TlsAwareClient::new().report_meta().pqctracer_version;
+        assert_eq!(
+            schema.root["$id"],
+            format!("urn:pqctracer:output:{}", version)
+        );
+        assert!(
+            schema.root["description"]
+                .as_str()
+                .unwrap()
+                .contains(&format!("pqctracer {}:", version))
+        );
+    }
+
+    #[test]
+    fn keywords_the_validator_ignores_are_refused() {
+        let e = Schema::parse(r#"{"$defs": {"name": {"type": "string", "pattern": "^a"}}}"#)
+            .err()
+            .expect("pattern is refused");
+        assert_eq!(e.to_string(), "/$defs/name/pattern: unsupported keyword");
+        let e = Schema::parse(r##"{"$ref": "#/$defs/missing"}"##)
+            .err()
+            .expect("dangling reference");
+        assert_eq!(e.to_string(), "/$ref: unresolved reference");
+    }
+
+    #[test]
+    fn every_field_and_variant_validates() {
+        let fallbacks = [
+            HandshakeFailureKind::NoCommonGroup,
+            HandshakeFailureKind::NoCommonCipherSuite,
+            HandshakeFailureKind::HandshakeRejected,
+            HandshakeFailureKind::VersionMismatch,
+            HandshakeFailureKind::Certificate {
+                problem: CertificateProblem::Expired,
+            },
+            HandshakeFailureKind::ClientCertificateRequired,
+            HandshakeFailureKind::ServerNameRejected,
+            HandshakeFailureKind::AlpnMismatch,
+            HandshakeFailureKind::NotTls,
+            HandshakeFailureKind::ConnectionClosed,
+            HandshakeFailureKind::ProtocolViolation {
+                detail: "bad".to_string(),
+            },
+            HandshakeFailureKind::Alert {
+                alert: "InternalError".to_string(),
+            },
+            HandshakeFailureKind::Other {
+                detail: "other".to_string(),
+            },
+        ]
+        .map(|original_error| FallbackOutcome::ClassicalOnlyAfterFailure { original_error });
+        let problems = [
+            CertificateProblem::Expired,
+            CertificateProblem::NotYetValid,
+            CertificateProblem::Untrusted,
+            CertificateProblem::NameMismatch,
+            CertificateProblem::Revoked,
+            CertificateProblem::PinMismatch,
+            CertificateProblem::Other,
+        ];
+        let categories = [
+            ErrorCategory::DnsFailure,
+            ErrorCategory::ConnectTimeout,
+            ErrorCategory::ConnectRefused,
+            ErrorCategory::TlsHandshakeFailure {
+                detail: "AlertReceived(HandshakeFailure)".to_string(),
+            },
+            ErrorCategory::HttpError { status: 503 },
+            ErrorCategory::BodyError,
+            ErrorCategory::Timeout,
+            ErrorCategory::InvalidUrl,
+            ErrorCategory::Other,
+        ]
+        .into_iter()
+        .chain(problems.map(|reason| ErrorCategory::CertificateInvalid { reason }));
+
+        let mut results: Vec<ScanResult> = [FallbackOutcome::PqcOk, FallbackOutcome::BothFailed]
+            .into_iter()
+            .chain(fallbacks)
+            .map(|fallback| filled_result(filled_metadata(fallback), None))
+            .chain(categories.map(|category| {
+                filled_result(filled_metadata(FallbackOutcome::PqcOk), Some(category))
+            }))
+            .collect();
+
+        // The variants a filled result leaves out.
+        let mut other = results[0].clone();
+        other.metadata.cert_validation = Some(Ok(()));
+        other.metadata.resumption_kx = Some(ResumptionKx::PskOnly);
+        other.metadata.origin = Some(HandshakeOrigin::SharedConnection);
+        let failed = ErrorCategory::Timeout;
+        other.versions = Some(VersionSupportReport {
+            tls13: VersionSupport::Failed {
+                error: failed.clone(),
+            },
+            tls12: VersionSupport::Failed {
+                error: failed.clone(),
+            },
+        });
+        other.http_versions = Some(HttpVersionReport {
+            http1: HttpVersionSupport::Failed {
+                error: failed.clone(),
+            },
+            h2: HttpVersionSupport::Failed {
+                error: failed.clone(),
+            },
+            versions_consistent: false,
+        });
+        other.resumption = Some(ResumptionReport::Failed { error: failed });
+        results.push(other.clone());
+        other.resumption = Some(ResumptionReport::NotResumed {
+            metadata: Box::new(TlsMetadata::default()),
+        });
+        results.push(other);
+        // And a result with nothing set but what is always there.
+        results.push(ScanResult {
+            target: "192.0.2.1:443".to_string(),
+            url: "https://192.0.2.1:443".to_string(),
+            sources: Vec::new(),
+            sni: None,
+            status: None,
+            method: None,
+            metadata: TlsMetadata::default(),
+            error: None,
+            attempts: 0,
+            duration: Duration::ZERO,
+            cert_not_before: None,
+            cert_not_after: None,
+            cert_days_remaining: None,
+            versions: None,
+            hello_size: None,
+            http_versions: None,
+            resumption: None,
+            readiness: None,
+        });
+
+        let documents = report(OutputFormat::Json, &results);
+        assert_eq!(documents.len(), 1);
+        assert_valid(&documents);
+        let lines = report(OutputFormat::Jsonl, &results);
+        assert_eq!(lines.len(), results.len() + 1);
+        assert_valid(&lines);
+        // The C and Python `trace` functions return the metadata alone.
+        let metadata: Vec<Value> = results
+            .iter()
+            .map(|r| serde_json::to_value(&r.metadata).unwrap())
+            .collect();
+        assert_valid(&metadata);
+    }
+
+    #[tokio::test]
+    async fn a_loopback_scan_validates() {
+        install_provider();
+        let server = TestTlsServer::builder()
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let client = TlsAwareClient::builder()
+            .danger_accept_invalid_certs(true)
+            .collect_timings(true)
+            .deep_inspect(true)
+            .build()
+            .expect("build client");
+        let options = ScanOptions {
+            retries: 0,
+            probe_versions: true,
+            diagnose_hello_size: true,
+            compare_http_versions: true,
+            check_resumption: true,
+            ..ScanOptions::default()
+        };
+        let address = server.local_addr().to_string();
+        let targets = [server.url(), "https://127.0.0.1:1/".to_string(), address];
+        let results = client.scan(targets, &options).await;
+        assert_eq!(results.iter().filter(|r| r.error.is_some()).count(), 1);
+
+        assert_valid(&report(OutputFormat::Json, &results));
+        assert_valid(&report(OutputFormat::Jsonl, &results));
+    }
+
+    #[test]
+    fn watch_notifications_validate() {
+        let observation = |group: Option<&str>, error: Option<&str>| Observation {
+            reachable: error.is_none(),
+            group: group.map(str::to_string),
+            pqc: group == Some("X25519MLKEM768"),
+            cert_algorithm: group.map(|_| "ECDSA P-256".to_string()),
+            cert_sha256: group.map(|_| "ab".repeat(32)),
+            error: error.map(str::to_string),
+            observed_at: "2026-10-14T12:00:00Z".to_string(),
+        };
+        let mut watcher = Watcher::default();
+        let rounds = [
+            observation(Some("X25519MLKEM768"), None),
+            observation(Some("X25519"), None),
+            observation(None, Some("connection refused")),
+        ];
+        let mut payloads = Vec::new();
+        for round in rounds {
+            for change in watcher.observe("example.com", round).unwrap_or_default() {
+                payloads.push(serde_json::to_value(Payload::of(&change)).unwrap());
+            }
+        }
+        assert_eq!(payloads.len(), 2);
+        assert_valid(&payloads);
+    }
+
+    #[test]
+    fn filled_metadata_has_every_described_property() {
+        let schema = Schema::published();
+        let described = schema.root["$defs"]["tls-metadata"]["properties"]
+            .as_object()
+            .expect("tls-metadata properties");
+        let document = serde_json::to_value(filled_metadata(FallbackOutcome::PqcOk)).unwrap();
+        let missing: Vec<&String> = described
+            .keys()
+            .filter(|name| document.get(name.as_str()).is_none_or(Value::is_null))
+            .collect();
+        assert!(missing.is_empty(), "not set: {:?}", missing);
+    }
+
+    #[test]
+    fn violations_name_the_offending_value() {
+        let schema = Schema::published();
+        let result = serde_json::to_value(filled_result(
+            filled_metadata(FallbackOutcome::PqcOk),
+            Some(ErrorCategory::HttpError { status: 503 }),
+        ))
+        .unwrap();
+        assert_eq!(schema.validate(&result), []);
+
+        let broken = |change: fn(&mut Value)| {
+            let mut result = result.clone();
+            change(&mut result);
+            schema.validate(&result)
+        };
+        let messages = |violations: Vec<Violation>| -> Vec<String> {
+            violations.iter().map(Violation::to_string).collect()
+        };
+        assert_eq!(
+            messages(broken(|r| r["metadata"]["tickets"] = 2.into())),
+            ["/metadata: unexpected property `tickets`"]
+        );
+        assert_eq!(
+            messages(broken(|r| r["metadata"]["group"] = 5.into())),
+            ["/metadata/group: expected string or null, got number"]
+        );
+        assert_eq!(
+            messages(broken(|r| {
+                r.as_object_mut().unwrap().remove("attempts");
+            })),
+            ["missing required property `attempts`"]
+        );
+        assert_eq!(
+            messages(broken(|r| r["error"]["category"]["status"] = (-1).into())),
+            ["/error/category/status: -1 is less than 100"]
+        );
+        assert_eq!(
+            messages(broken(
+                |r| r["versions"]["tls12"]["outcome"] = "maybe".into()
+            )),
+            ["/versions/tls12/outcome: expected \"rejected\", got \"maybe\""]
+        );
+    }
+
+    #[test]
+    fn a_report_is_told_by_its_shape_not_its_violation_count() {
+        let result = filled_result(filled_metadata(FallbackOutcome::PqcOk), None);
+        let mut document = report(OutputFormat::Json, &vec![result; 3]).remove(0);
+        for result in document["results"].as_array_mut().unwrap() {
+            result.as_object_mut().unwrap().remove("attempts");
+        }
+        let violations = Schema::published().validate(&document);
+        let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, ["/results/0", "/results/1", "/results/2"]);
+    }
+
+    #[test]
+    fn files_hold_a_document_or_json_lines() {
+        let document = documents("{\n  \"meta\": {}\n}\n").unwrap();
+        assert_eq!(document, [(1, serde_json::json!({"meta": {}}))]);
+        let lines = documents("{\"a\": 1}\n\n{\"b\": 2}\n").unwrap();
+        assert_eq!(
+            lines.iter().map(|(line, _)| *line).collect::<Vec<_>>(),
+            [1, 3]
+        );
+        let e = documents("{\"a\": 1}\n{\"b\":\n").unwrap_err();
+        assert!(e.starts_with("line 2: not JSON"), "{}", e);
+    }
+}
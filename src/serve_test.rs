@@ -0,0 +1,151 @@
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+use clap::Args;
+use pqctracer::starttls::Protocol;
+use pqctracer::testutil::{TestTlsServer, kx_group_by_name};
+use rustls::AlertDescription;
+
+const DAY: u64 = 86_400;
+
+#[derive(Args)]
+pub struct ServeTestArgs {
+    /// Address to listen on, e.g. another loopback address to run several
+    /// servers side by side.
+    #[arg(long, default_value = "127.0.0.1")]
+    pub address: IpAddr,
+
+    /// Port to listen on.
+    #[arg(long, default_value_t = 8443)]
+    pub port: u16,
+
+    /// Comma-separated key-exchange groups to accept, in preference order.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "x25519mlkem768,x25519,secp256r1"
+    )]
+    pub groups: Vec<String>,
+
+    /// Refuse the groups the client sent key shares for, forcing a HelloRetryRequest.
+    #[arg(long)]
+    pub hrr: bool,
+
+    /// Abort the handshake with a fatal alert when the client lists a group
+    /// not accepted, like servers that choke on unknown hybrid groups.
+    #[arg(long)]
+    pub reject_unknown_groups: bool,
+
+    /// Drop connections whose ClientHello takes more than this many bytes,
+    /// like middleboxes that choke on large post-quantum ClientHellos.
+    #[arg(long, value_name = "BYTES")]
+    pub max_hello_len: Option<usize>,
+
+    /// Only accept TLS 1.2 handshakes.
+    #[arg(long)]
+    pub tls12_only: bool,
+
+    /// Issue a certificate that expires this many days from now; negative
+    /// values give an already expired one.
+    #[arg(long, value_name = "DAYS", allow_negative_numbers = true)]
+    pub cert_days: Option<i64>,
+
+    /// Send an unrecognized_name warning alert ahead of every handshake.
+    #[arg(long)]
+    pub warning_alert: bool,
+
+    /// Speak this mail protocol's plaintext preamble (smtp, imap or pop3)
+    /// and start TLS once the client asks for it, for --starttls.
+    #[arg(long, value_name = "PROTOCOL")]
+    pub starttls: Option<Protocol>,
+
+    /// Issue the certificate from a generated root through this many
+    /// intermediate CAs instead of self-signing it.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub intermediates: usize,
+
+    /// Write the certificate to trust (the root with --intermediates) to
+    /// this PEM file, for use with --cacert.
+    #[arg(long, value_name = "FILE")]
+    pub ca_out: Option<PathBuf>,
+}
+
+pub async fn serve_test(args: ServeTestArgs) -> ExitCode {
+    let mut groups = Vec::new();
+    for name in &args.groups {
+        match kx_group_by_name(name) {
+            Some(group) => groups.push(group),
+            None => {
+                eprintln!("Unknown key-exchange group: {}", name);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let mut builder = TestTlsServer::builder()
+        .groups(groups.clone())
+        .force_hello_retry(args.hrr)
+        .reject_unknown_groups(args.reject_unknown_groups)
+        .tls12_only(args.tls12_only)
+        .on_handshake(|handshake| println!("{}", handshake));
+    if let Some(days) = args.cert_days {
+        let now = SystemTime::now();
+        let offset = Duration::from_secs(days.unsigned_abs() * DAY);
+        let not_after = if days >= 0 {
+            now + offset
+        } else {
+            now - offset
+        };
+        builder = builder.cert_validity(not_after.min(now) - Duration::from_secs(DAY), not_after);
+    }
+    if let Some(len) = args.max_hello_len {
+        builder = builder.max_hello_len(len);
+    }
+    if args.warning_alert {
+        builder = builder.warning_alert(AlertDescription::UnrecognisedName);
+    }
+    if let Some(protocol) = args.starttls {
+        builder = builder.starttls(protocol);
+    }
+    builder = builder.intermediates(args.intermediates);
+    let server = builder.bind((args.address, args.port)).await;
+    let server = match server {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Failed to start test server: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let url = if args.address == IpAddr::from([127, 0, 0, 1]) {
+        server.url()
+    } else {
+        format!("https://{}", server.local_addr())
+    };
+    println!("Listening on {} (accepting {:?})", url, groups);
+    // What to pass the tracer: a mail server is traced by host and port.
+    let url = match args.starttls {
+        Some(protocol) => format!(
+            "--starttls {} {}",
+            protocol.to_string().to_lowercase(),
+            url.trim_start_matches("https://")
+        ),
+        None => url,
+    };
+    match &args.ca_out {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, server.cert_pem()) {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+            println!("Trace it with: --cacert {} {}", path.display(), url);
+        }
+        None => println!("Trace it with: --insecure {}", url),
+    }
+    println!("Press Ctrl-C to stop.");
+
+    let _ = tokio::signal::ctrl_c().await;
+    ExitCode::SUCCESS
+}
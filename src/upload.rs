@@ -0,0 +1,321 @@
+//! `--data-file` and `--form`: sending a file as the body of a traced
+//! `POST`, read into memory or streamed from disk, or a
+//! `multipart/form-data` form.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use pqctracer::multipart::{Form, Part};
+use pqctracer::{TlsAwareClient, TlsResponse, TraceError, TracedRequestBuilder};
+use reqwest::Method;
+use reqwest::header::{CONTENT_TYPE, HeaderValue};
+use tokio::fs::{self, File};
+
+use crate::auth::Credentials;
+
+/// Options for sending a request body.
+#[derive(Args)]
+pub struct UploadArgs {
+    /// Send a POST request with the contents of this file as its body; a
+    /// leading @ is ignored, as in curl. Takes a single target.
+    #[arg(long, value_name = "[@]PATH", conflicts_with_all = ["repeat", "starttls", "form"])]
+    pub data_file: Option<PathBuf>,
+
+    /// Stream the file from disk as the request is sent instead of reading
+    /// it into memory first; sent chunked when its length is unknown, e.g.
+    /// for a pipe.
+    #[arg(long, requires = "data_file")]
+    pub stream: bool,
+
+    /// The Content-Type of the --data-file body; guessed from the file's
+    /// extension by default.
+    #[arg(long, value_name = "MIME", requires = "data_file")]
+    pub content_type: Option<String>,
+
+    /// Send a POST request with a multipart/form-data body; repeatable.
+    /// NAME=@PATH uploads a file, followed by ;type=MIME or ;filename=NAME
+    /// to override what is guessed from the path. Takes a single target.
+    #[arg(
+        long,
+        value_name = "NAME=VALUE|NAME=@PATH",
+        conflicts_with_all = ["repeat", "starttls"]
+    )]
+    pub form: Vec<String>,
+}
+
+impl UploadArgs {
+    /// Whether a request body is to be sent.
+    pub fn is_upload(&self) -> bool {
+        self.data_file.is_some() || !self.form.is_empty()
+    }
+}
+
+/// Send the file or form of `args` to `url` and return the response.
+///
+/// A request that fails after its handshake, e.g. on a connection reset
+/// mid-upload, still has the handshake printed before its error.
+pub async fn upload(
+    tls_client: &TlsAwareClient,
+    url: &str,
+    args: &UploadArgs,
+    credentials: Option<&Credentials>,
+) -> Result<TlsResponse, Box<dyn Error>> {
+    let request = tls_client.request(Method::POST, url);
+    let request = match credentials {
+        Some(Credentials::Basic { user, password }) => request.basic_auth(user, Some(password)),
+        Some(Credentials::Bearer(token)) => request.bearer_auth(token),
+        None => request,
+    };
+    let request = if let Some(path) = &args.data_file {
+        let path = strip_at(path);
+        let content_type = match &args.content_type {
+            Some(content_type) => content_type.as_str(),
+            None => guess_mime(path),
+        };
+        let content_type = HeaderValue::from_str(content_type)
+            .map_err(|_| format!("invalid content type {:?}", content_type))?;
+        let request = request.header(CONTENT_TYPE, content_type);
+        data_file_body(request, path, args.stream).await?
+    } else {
+        let mut form = Form::new();
+        for field in &args.form {
+            let (name, part) = form_field(field).await?;
+            form = form.part(name, part);
+        }
+        println!("Uploading: {} form fields", args.form.len());
+        request.multipart(form)
+    };
+    request.send().await.map_err(|e| {
+        if let TraceError::RequestFailed { metadata, .. } = &e {
+            println!("Handshake: {}", metadata);
+        }
+        e.into()
+    })
+}
+
+/// Set the contents of `path` as the body of `request`.
+async fn data_file_body<'a>(
+    request: TracedRequestBuilder<'a>,
+    path: &Path,
+    stream: bool,
+) -> Result<TracedRequestBuilder<'a>, Box<dyn Error>> {
+    Ok(if stream {
+        let file = File::open(path).await?;
+        let file_metadata = file.metadata().await?;
+        let length = file_metadata.is_file().then_some(file_metadata.len());
+        match length {
+            Some(length) => println!("Uploading: {} bytes from {}", length, path.display()),
+            None => println!("Uploading: {} (chunked)", path.display()),
+        }
+        request.body_reader(file, length)
+    } else {
+        let body = fs::read(path).await?;
+        println!("Uploading: {} bytes from {}", body.len(), path.display());
+        request.body(body)
+    })
+}
+
+/// Parse a `--form` field, `NAME=VALUE` or `NAME=@PATH[;type=MIME][;filename=NAME]`,
+/// reading the file of the latter.
+async fn form_field(field: &str) -> Result<(String, Part), Box<dyn Error>> {
+    let (name, value) = field
+        .split_once('=')
+        .ok_or_else(|| format!("invalid form field {}: expected NAME=VALUE", field))?;
+    let Some(file) = value.strip_prefix('@') else {
+        return Ok((name.to_string(), Part::text(value)));
+    };
+    let mut options = file.split(';');
+    let path = Path::new(options.next().unwrap_or_default());
+    let (mut mime, mut file_name) = (None, None);
+    for option in options {
+        match option.split_once('=') {
+            Some(("type", value)) => mime = Some(value),
+            Some(("filename", value)) => file_name = Some(value.to_string()),
+            _ => return Err(format!("invalid form field option {:?} in {}", option, field).into()),
+        }
+    }
+    let data = fs::read(path)
+        .await
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let file_name = file_name.unwrap_or_else(|| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    let part = Part::bytes(data)
+        .file_name(file_name)
+        .mime_str(mime.unwrap_or_else(|| guess_mime(path)))?;
+    Ok((name.to_string(), part))
+}
+
+/// `path` without the leading `@` curl users are used to.
+fn strip_at(path: &Path) -> &Path {
+    path.to_str()
+        .and_then(|path| path.strip_prefix('@'))
+        .map_or(path, Path::new)
+}
+
+/// The media type of a file with `path`'s extension, for the common ones;
+/// `application/octet-stream` otherwise.
+fn guess_mime(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "txt" | "log" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pqctracer::testutil::TestTlsServer;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn a_large_file_is_streamed_whole() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        // Answers with the size of the body and how it was framed.
+        let server = TestTlsServer::builder()
+            .http_handler(|request| {
+                let (head, body) = request.split_once("\r\n\r\n").unwrap_or((request, ""));
+                let head = head.to_ascii_lowercase();
+                let framing = if head.contains("transfer-encoding: chunked") {
+                    "chunked"
+                } else {
+                    "length"
+                };
+                format!("200 OK\r\n\r\n{} {}", body.len(), framing)
+            })
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let client = TlsAwareClient::builder()
+            .add_root_certificate(server.cert_der().clone())
+            .build()
+            .expect("build client");
+        let path =
+            std::env::temp_dir().join(format!("pqctracer-upload-{}.bin", std::process::id()));
+        std::fs::write(&path, vec![b'u'; 12 << 20]).expect("write file");
+
+        for stream in [true, false] {
+            let args = UploadArgs {
+                data_file: Some(format!("@{}", path.display()).into()),
+                stream,
+                content_type: None,
+                form: Vec::new(),
+            };
+            let response = upload(&client, &server.url(), &args, None).await;
+            let (text, metadata) = response.expect("upload").text().await.expect("body");
+            assert_eq!(text, format!("{} length", 12 << 20), "stream: {}", stream);
+            assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn types_are_guessed_from_the_extension() {
+        assert_eq!(guess_mime(Path::new("report.JSON")), "application/json");
+        assert_eq!(guess_mime(Path::new("image.tar")), "application/x-tar");
+        assert_eq!(guess_mime(Path::new("blob")), "application/octet-stream");
+        assert_eq!(strip_at(Path::new("@data.bin")), Path::new("data.bin"));
+    }
+
+    #[tokio::test]
+    async fn forms_carry_fields_and_files() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        // Answers with the request's Content-Type and body.
+        let server = TestTlsServer::builder()
+            .http_handler(|request| {
+                let (head, body) = request.split_once("\r\n\r\n").unwrap_or((request, ""));
+                let content_type = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-type: "))
+                    .unwrap_or_default();
+                format!("200 OK\r\n\r\n{}\n{}", content_type, body)
+            })
+            .bind("127.0.0.1:0")
+            .await
+            .expect("bind test server");
+        let client = TlsAwareClient::builder()
+            .add_root_certificate(server.cert_der().clone())
+            .build()
+            .expect("build client");
+        let path = std::env::temp_dir().join(format!("pqctracer-form-{}.csv", std::process::id()));
+        std::fs::write(&path, "host,group\n").expect("write file");
+
+        let args = UploadArgs {
+            data_file: None,
+            stream: false,
+            content_type: None,
+            form: vec![
+                "user=alice".to_string(),
+                format!("report=@{}", path.display()),
+                format!("renamed=@{};type=text/plain;filename=a.txt", path.display()),
+            ],
+        };
+        let response = upload(&client, &server.url(), &args, None).await;
+        let _ = std::fs::remove_file(&path);
+        let (text, metadata) = response.expect("upload").text().await.expect("body");
+        assert_eq!(metadata.group.as_deref(), Some("X25519MLKEM768"));
+
+        let (content_type, body) = text.split_once('\n').expect("content type");
+        let boundary = content_type
+            .strip_prefix("multipart/form-data; boundary=")
+            .expect("boundary");
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        let expected = [
+            format!("--{}\r\n", boundary),
+            "Content-Disposition: form-data; name=\"user\"\r\n\r\nalice\r\n".to_string(),
+            format!("--{}\r\n", boundary),
+            format!(
+                "Content-Disposition: form-data; name=\"report\"; filename=\"{}\"\r\n",
+                file_name
+            ),
+            "Content-Type: text/csv\r\n\r\nhost,group\n\r\n".to_string(),
+            format!("--{}\r\n", boundary),
+            "Content-Disposition: form-data; name=\"renamed\"; filename=\"a.txt\"\r\n".to_string(),
+            "Content-Type: text/plain\r\n\r\nhost,group\n\r\n".to_string(),
+            format!("--{}--\r\n", boundary),
+        ];
+        assert_eq!(body, expected.concat());
+    }
+
+    #[tokio::test]
+    async fn malformed_form_fields_are_refused() {
+        for (field, message) in [
+            ("user", "invalid form field user: expected NAME=VALUE"),
+            (
+                "file=@a.txt;size=3",
+                "invalid form field option \"size=3\" in file=@a.txt;size=3",
+            ),
+            (
+                "file=@/nonexistent/a.txt",
+                "failed to read /nonexistent/a.txt: ",
+            ),
+        ] {
+            let Err(error) = form_field(field).await else {
+                panic!("{} is malformed", field);
+            };
+            assert!(error.to_string().starts_with(message), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,753 @@
+//! `watch`: tracing the same targets round after round and telling, on the
+//! console and to webhooks, when one of them changes for the worse.
+//!
+//! Every round compares what each target negotiated with its previous
+//! round. A change is printed, and posted to every `--notify-webhook` whose
+//! `--notify-on` filter lets its kind through, as a JSON document with a
+//! `text` line, so Slack and Teams incoming webhooks show it as it is. The
+//! same change is posted at most once a run: a target flapping between two
+//! states notifies once for each direction.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+use clap::{Args, ValueEnum};
+use pqctracer::TlsAwareClient;
+use pqctracer::cert::{CertificateSummary, fingerprint, rfc3339};
+use pqctracer::scan::{ScanOptions, ScanResult};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{StatusCode, Url};
+use serde::Serialize;
+
+use crate::config::{Config, ScanSettings, TargetSettings};
+#[cfg(feature = "sqlite")]
+use crate::history::{HistoryRow, HistorySink};
+use crate::{ClientArgs, is_pqc};
+
+/// A change a target can go through between two rounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+    /// A post-quantum or hybrid key exchange gave way to a classical one.
+    PqcRegression,
+    /// The end-entity certificate, or its key algorithm, changed.
+    CertChange,
+    /// The target became unreachable, or reachable again.
+    Reachability,
+}
+
+#[derive(Args)]
+#[command(after_help = crate::config::ENV_VARS)]
+pub struct WatchArgs {
+    /// URLs or host names to watch.
+    pub targets: Vec<String>,
+
+    /// Read additional targets from a file, one per line (`#` starts a comment).
+    #[arg(short, long, value_name = "FILE")]
+    pub input: Option<PathBuf>,
+
+    /// Seconds between the starts of two rounds.
+    #[arg(long, value_name = "SECS", default_value_t = 300.0)]
+    pub interval: f64,
+
+    /// Stop after this many rounds instead of at Ctrl-C.
+    #[arg(long, value_name = "N")]
+    pub rounds: Option<u64>,
+
+    /// POST every change to this URL as JSON; repeatable.
+    #[arg(long = "notify-webhook", value_name = "URL")]
+    pub notify_webhooks: Vec<Url>,
+
+    /// The changes that notify, comma-separated [default: all].
+    #[arg(long, value_name = "CHANGES", value_enum, value_delimiter = ',')]
+    pub notify_on: Vec<ChangeKind>,
+
+    /// Retry a failed delivery this many times, waiting 1, 2, 4, ...
+    /// seconds in between.
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    pub notify_retries: u32,
+
+    /// Also record every result in this SQLite database, created if
+    /// missing; see the history subcommand.
+    #[cfg(feature = "sqlite")]
+    #[arg(long, value_name = "FILE")]
+    pub db: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub client: ClientArgs,
+}
+
+impl WatchArgs {
+    fn resolve(&self) -> Result<Config, Box<dyn Error>> {
+        self.client.resolve(Config {
+            targets: TargetSettings {
+                list: (!self.targets.is_empty()).then(|| self.targets.clone()),
+                file: self.input.clone(),
+            },
+            scan: ScanSettings::default(),
+            ..Default::default()
+        })
+    }
+}
+
+/// What a round saw of a target: the values a change is told by.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Observation {
+    pub reachable: bool,
+    pub group: Option<String>,
+    /// Whether `group` is a post-quantum or hybrid key exchange.
+    pub pqc: bool,
+    /// The end-entity certificate's key algorithm, e.g. `ECDSA P-256`.
+    pub cert_algorithm: Option<String>,
+    /// SHA-256 of the end-entity certificate, in hex.
+    pub cert_sha256: Option<String>,
+    pub error: Option<String>,
+    /// RFC 3339, in UTC.
+    pub observed_at: String,
+}
+
+impl Observation {
+    pub fn of(result: &ScanResult, at: SystemTime) -> Self {
+        let cert = result.metadata.peer_certificates.first();
+        Self {
+            reachable: result.reached(),
+            group: result.metadata.group.clone(),
+            pqc: is_pqc(&result.metadata),
+            cert_algorithm: cert
+                .and_then(CertificateSummary::of)
+                .map(|summary| summary.key_algorithm),
+            cert_sha256: cert.and_then(fingerprint).map(|digest| digest.to_hex()),
+            error: result.error.as_ref().map(|e| e.message.clone()),
+            observed_at: rfc3339(at),
+        }
+    }
+
+    fn certificate(&self) -> String {
+        match (&self.cert_algorithm, &self.cert_sha256) {
+            (Some(algorithm), Some(digest)) => format!("{} ({})", algorithm, &digest[..16]),
+            (algorithm, _) => algorithm.clone().unwrap_or_else(|| "unknown".to_string()),
+        }
+    }
+}
+
+/// A target's change between two rounds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub target: String,
+    pub old: Observation,
+    pub new: Observation,
+}
+
+impl Change {
+    fn new(kind: ChangeKind, target: &str, old: &Observation, new: &Observation) -> Self {
+        Self {
+            kind,
+            target: target.to_string(),
+            old: old.clone(),
+            new: new.clone(),
+        }
+    }
+
+    /// The old and the new value of what changed, as [`text`](Self::text)
+    /// shows them.
+    fn values(&self) -> (String, String) {
+        match self.kind {
+            ChangeKind::PqcRegression => {
+                let group = |o: &Observation| o.group.clone().unwrap_or_default();
+                (group(&self.old), group(&self.new))
+            }
+            ChangeKind::CertChange => (self.old.certificate(), self.new.certificate()),
+            ChangeKind::Reachability => {
+                let state = |o: &Observation| match o.reachable {
+                    true => "reachable".to_string(),
+                    false => "unreachable".to_string(),
+                };
+                (state(&self.old), state(&self.new))
+            }
+        }
+    }
+
+    /// One line for the console and the webhook's `text`.
+    pub fn text(&self) -> String {
+        let (old, new) = self.values();
+        match self.kind {
+            ChangeKind::PqcRegression => format!(
+                "{}: post-quantum key exchange lost, {} -> {}",
+                self.target, old, new
+            ),
+            ChangeKind::CertChange => {
+                format!("{}: certificate changed, {} -> {}", self.target, old, new)
+            }
+            ChangeKind::Reachability => match &self.new.error {
+                Some(error) if !self.new.reachable => {
+                    format!("{}: unreachable, {}", self.target, error)
+                }
+                _ => format!("{}: {} -> {}", self.target, old, new),
+            },
+        }
+    }
+}
+
+/// The latest observations of every target.
+#[derive(Default)]
+pub struct Watcher {
+    last: HashMap<String, Observation>,
+    /// The latest of each target it was reachable in, which the key
+    /// exchange and the certificate are compared with, so that an outage
+    /// in between does not hide their changes.
+    last_reached: HashMap<String, Observation>,
+}
+
+impl Watcher {
+    /// Note what a round saw of `target` and return its changes since the
+    /// rounds before, in [`ChangeKind`] order; `None` for its first round.
+    pub fn observe(&mut self, target: &str, new: Observation) -> Option<Vec<Change>> {
+        let mut changes = Vec::new();
+        if new.reachable
+            && let Some(old) = self.last_reached.get(target)
+        {
+            // A handshake whose group went uncaptured says nothing either way.
+            if old.pqc && !new.pqc && new.group.is_some() {
+                changes.push(Change::new(ChangeKind::PqcRegression, target, old, &new));
+            }
+            let changed =
+                |a: &Option<String>, b: &Option<String>| a.is_some() && b.is_some() && a != b;
+            if changed(&old.cert_algorithm, &new.cert_algorithm)
+                || changed(&old.cert_sha256, &new.cert_sha256)
+            {
+                changes.push(Change::new(ChangeKind::CertChange, target, old, &new));
+            }
+        }
+        let old = self.last.get(target);
+        if let Some(old) = old
+            && old.reachable != new.reachable
+        {
+            changes.push(Change::new(ChangeKind::Reachability, target, old, &new));
+        }
+        let first = old.is_none();
+        if new.reachable {
+            self.last_reached.insert(target.to_string(), new.clone());
+        }
+        self.last.insert(target.to_string(), new);
+        (!first).then_some(changes)
+    }
+}
+
+/// The document a webhook receives.
+#[derive(Debug, Serialize)]
+pub(crate) struct Payload<'a> {
+    text: String,
+    kind: ChangeKind,
+    target: &'a str,
+    old: &'a Observation,
+    new: &'a Observation,
+    /// RFC 3339, in UTC.
+    detected_at: String,
+}
+
+impl<'a> Payload<'a> {
+    /// The payload for `change`, detected now.
+    pub(crate) fn of(change: &'a Change) -> Self {
+        Self {
+            text: change.text(),
+            kind: change.kind,
+            target: &change.target,
+            old: &change.old,
+            new: &change.new,
+            detected_at: rfc3339(SystemTime::now()),
+        }
+    }
+}
+
+/// Posts changes to the webhooks.
+pub struct Notifier {
+    client: TlsAwareClient,
+    webhooks: Vec<Url>,
+    /// Empty for every kind.
+    notify_on: Vec<ChangeKind>,
+    retries: u32,
+    /// The wait before the first retry, doubled for each one after it.
+    backoff: Duration,
+    /// The changes posted so far, by target, kind and values.
+    sent: HashSet<(String, ChangeKind, String, String)>,
+}
+
+impl Notifier {
+    pub fn new(
+        webhooks: Vec<Url>,
+        notify_on: Vec<ChangeKind>,
+        retries: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let client = TlsAwareClient::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(Self {
+            client,
+            webhooks,
+            notify_on,
+            retries,
+            backoff: Duration::from_secs(1),
+            sent: HashSet::new(),
+        })
+    }
+
+    /// Post `change` to every webhook, unless its kind is filtered out or it
+    /// was posted before; returns whether it was posted.
+    pub async fn notify(&mut self, change: &Change) -> bool {
+        if self.webhooks.is_empty()
+            || !(self.notify_on.is_empty() || self.notify_on.contains(&change.kind))
+        {
+            return false;
+        }
+        let (old, new) = change.values();
+        if !self
+            .sent
+            .insert((change.target.clone(), change.kind, old, new))
+        {
+            return false;
+        }
+        let body = serde_json::to_vec(&Payload::of(change)).expect("payload serializes");
+        for webhook in &self.webhooks {
+            if let Err(e) = self.deliver(webhook, &body).await {
+                eprintln!("Failed to notify {}: {}", webhook, e);
+            }
+        }
+        true
+    }
+
+    async fn deliver(&self, webhook: &Url, body: &[u8]) -> Result<(), String> {
+        let mut attempt = 0;
+        loop {
+            let request = reqwest::Client::new()
+                .post(webhook.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_vec())
+                .build()
+                .map_err(|e| e.to_string())?;
+            let error = match self.client.execute(request).await {
+                Ok(response) if response.response.status().is_success() => return Ok(()),
+                Ok(response) => {
+                    let status = response.response.status();
+                    // Other client errors will not go away by asking again.
+                    let retry = status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+                    if !retry {
+                        return Err(format!("HTTP {}", status));
+                    }
+                    format!("HTTP {}", status)
+                }
+                Err(e) => e.to_string(),
+            };
+            if attempt == self.retries {
+                return Err(format!("{}, after {} attempts", error, attempt + 1));
+            }
+            tokio::time::sleep(self.backoff * 2u32.saturating_pow(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+pub async fn watch(args: WatchArgs) -> ExitCode {
+    if !(args.interval.is_finite() && args.interval >= 0.0) {
+        eprintln!("The interval must be a number of seconds");
+        return ExitCode::FAILURE;
+    }
+    let config = match args.resolve() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let targets = match config.targets.collect() {
+        Ok(targets) if targets.is_empty() => {
+            eprintln!("No targets to watch");
+            return ExitCode::FAILURE;
+        }
+        Ok(targets) => targets,
+        Err(e) => {
+            eprintln!("Failed to read targets: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let client = match config.client.build_client() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Failed to build client: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut notifier =
+        match Notifier::new(args.notify_webhooks, args.notify_on, args.notify_retries) {
+            Ok(notifier) => notifier,
+            Err(e) => {
+                eprintln!("Failed to build the webhook client: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+    #[cfg(feature = "sqlite")]
+    let db = match args.db.as_deref().map(HistorySink::open).transpose() {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Failed to open the history database: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let defaults = ScanOptions::default();
+    let options = ScanOptions {
+        concurrency: config.scan.concurrency.unwrap_or(defaults.concurrency),
+        retries: config.scan.retries.unwrap_or(defaults.retries),
+        method: config.scan.method.unwrap_or_default(),
+        ..defaults
+    };
+    let interval = Duration::from_secs_f64(args.interval);
+    eprintln!("Watching {} targets every {:?}", targets.len(), interval);
+    let mut watcher = Watcher::default();
+    let mut round = 0;
+    loop {
+        let started = tokio::time::Instant::now();
+        for result in client.scan(targets.iter().cloned(), &options).await {
+            #[cfg(feature = "sqlite")]
+            if let Some(db) = &db {
+                db.record(&HistoryRow::of_scan("watch", &result));
+            }
+            let observation = Observation::of(&result, SystemTime::now());
+            let at = observation.observed_at.clone();
+            let line = state(&observation);
+            match watcher.observe(&result.target, observation) {
+                None => println!("{} {}: {}", at, result.target, line),
+                Some(changes) => {
+                    for change in changes {
+                        println!("{} {}", at, change.text());
+                        notifier.notify(&change).await;
+                    }
+                }
+            }
+        }
+        // Saved every round, so an interrupted watch keeps its sessions.
+        if let Err(e) = config.client.save_cookies(&client) {
+            eprintln!("Failed to save cookies: {}", e);
+        }
+        round += 1;
+        if args.rounds.is_some_and(|rounds| round >= rounds) {
+            break;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep_until(started + interval) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(Err(e)) = db.map(HistorySink::finish) {
+        eprintln!("Failed to record history: {}", e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// A target's first observation, as the first round prints it.
+fn state(observation: &Observation) -> String {
+    match (&observation.error, observation.reachable) {
+        (Some(error), false) => format!("unreachable, {}", error),
+        _ => format!(
+            "{}, certificate {}",
+            observation.group.as_deref().unwrap_or("group unknown"),
+            observation.certificate()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::Value;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn seen(group: &str, pqc: bool, cert: &str) -> Observation {
+        Observation {
+            reachable: true,
+            group: Some(group.to_string()),
+            pqc,
+            cert_algorithm: Some("ECDSA P-256".to_string()),
+            cert_sha256: Some(cert.repeat(64)),
+            error: None,
+            observed_at: "2026-03-01T12:00:00Z".to_string(),
+        }
+    }
+
+    fn hybrid() -> Observation {
+        seen("X25519MLKEM768", true, "a")
+    }
+
+    fn classical() -> Observation {
+        seen("X25519", false, "a")
+    }
+
+    fn unreachable() -> Observation {
+        Observation {
+            reachable: false,
+            group: None,
+            pqc: false,
+            cert_algorithm: None,
+            cert_sha256: None,
+            error: Some("connection refused".to_string()),
+            observed_at: "2026-03-01T12:05:00Z".to_string(),
+        }
+    }
+
+    /// The kinds of change each round after the first reports.
+    fn kinds(rounds: &[Observation]) -> Vec<Vec<ChangeKind>> {
+        let mut watcher = Watcher::default();
+        assert_eq!(watcher.observe("t", rounds[0].clone()), None);
+        rounds[1..]
+            .iter()
+            .map(|round| {
+                let changes = watcher.observe("t", round.clone()).unwrap();
+                changes.iter().map(|change| change.kind).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn changes_are_detected() {
+        use ChangeKind::*;
+        let mut algorithm = hybrid();
+        algorithm.cert_algorithm = Some("ML-DSA-65".to_string());
+        let mut uncaptured = classical();
+        uncaptured.group = None;
+        // The rounds of a target, and the changes of each after the first.
+        type Case = (&'static str, Vec<Observation>, Vec<Vec<ChangeKind>>);
+        let cases: Vec<Case> = vec![
+            ("steady", vec![hybrid(), hybrid()], vec![vec![]]),
+            (
+                "regression",
+                vec![hybrid(), classical()],
+                vec![vec![PqcRegression]],
+            ),
+            ("upgrade", vec![classical(), hybrid()], vec![vec![]]),
+            ("uncaptured group", vec![hybrid(), uncaptured], vec![vec![]]),
+            (
+                "new certificate",
+                vec![hybrid(), seen("X25519MLKEM768", true, "b")],
+                vec![vec![CertChange]],
+            ),
+            (
+                "new key algorithm",
+                vec![hybrid(), algorithm],
+                vec![vec![CertChange]],
+            ),
+            (
+                "both",
+                vec![hybrid(), seen("X25519", false, "b")],
+                vec![vec![PqcRegression, CertChange]],
+            ),
+            (
+                "outage",
+                vec![hybrid(), unreachable(), unreachable(), hybrid()],
+                vec![vec![Reachability], vec![], vec![Reachability]],
+            ),
+            (
+                "regression during an outage",
+                vec![hybrid(), unreachable(), classical()],
+                vec![vec![Reachability], vec![PqcRegression, Reachability]],
+            ),
+        ];
+        for (name, rounds, expected) in cases {
+            assert_eq!(kinds(&rounds), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn changes_read_as_one_line() {
+        let mut watcher = Watcher::default();
+        watcher.observe("example.com", hybrid());
+        let changes = watcher.observe("example.com", unreachable()).unwrap();
+        assert_eq!(
+            changes[0].text(),
+            "example.com: unreachable, connection refused"
+        );
+        let changes = watcher
+            .observe("example.com", seen("X25519", false, "b"))
+            .unwrap();
+        let texts: Vec<String> = changes.iter().map(Change::text).collect();
+        assert_eq!(
+            texts,
+            [
+                "example.com: post-quantum key exchange lost, X25519MLKEM768 -> X25519",
+                "example.com: certificate changed, ECDSA P-256 (aaaaaaaaaaaaaaaa) -> \
+                 ECDSA P-256 (bbbbbbbbbbbbbbbb)",
+                "example.com: unreachable -> reachable",
+            ]
+        );
+    }
+
+    /// An HTTP server that records every request body and answers with the
+    /// given statuses in turn, then 200.
+    async fn sink(statuses: &'static [u16]) -> (Url, Arc<Mutex<Vec<Value>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut statuses = statuses.iter().copied();
+        let bodies = received.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                let body = loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    assert!(n > 0, "request cut short");
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                        continue;
+                    };
+                    assert!(head.starts_with("POST /hook "), "{}", head);
+                    assert!(head.contains("content-type: application/json"), "{}", head);
+                    let length: usize = head
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length: "))
+                        .unwrap()
+                        .parse()
+                        .unwrap();
+                    if body.len() >= length {
+                        break body.to_string();
+                    }
+                };
+                bodies
+                    .lock()
+                    .unwrap()
+                    .push(serde_json::from_str(&body).unwrap());
+                let status = statuses.next().unwrap_or(200);
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url.parse().unwrap(), received)
+    }
+
+    fn notifier(webhooks: Vec<Url>, notify_on: Vec<ChangeKind>) -> Notifier {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let mut notifier = Notifier::new(webhooks, notify_on, 2).unwrap();
+        notifier.backoff = Duration::from_millis(1);
+        notifier
+    }
+
+    fn regression() -> Change {
+        Change::new(
+            ChangeKind::PqcRegression,
+            "example.com",
+            &hybrid(),
+            &classical(),
+        )
+    }
+
+    #[tokio::test]
+    async fn webhooks_receive_the_change() {
+        let (first, first_received) = sink(&[]).await;
+        let (second, second_received) = sink(&[]).await;
+        let mut notifier = notifier(vec![first, second], Vec::new());
+        assert!(notifier.notify(&regression()).await);
+
+        let payload = first_received.lock().unwrap()[0].clone();
+        assert_eq!(*second_received.lock().unwrap(), vec![payload.clone()]);
+        let Value::Object(fields) = &payload else {
+            panic!("{}", payload);
+        };
+        let mut keys: Vec<&str> = fields.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(
+            keys,
+            ["detected_at", "kind", "new", "old", "target", "text"]
+        );
+        assert_eq!(payload["kind"], "pqc-regression");
+        assert_eq!(payload["target"], "example.com");
+        assert_eq!(payload["text"], regression().text());
+        assert_eq!(payload["old"]["group"], "X25519MLKEM768");
+        assert_eq!(payload["old"]["pqc"], true);
+        assert_eq!(payload["new"]["group"], "X25519");
+        assert_eq!(payload["new"]["observed_at"], "2026-03-01T12:00:00Z");
+        assert_eq!(payload["new"]["cert_algorithm"], "ECDSA P-256");
+        assert_eq!(payload["new"]["reachable"], true);
+        assert!(payload["detected_at"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[tokio::test]
+    async fn failed_deliveries_are_retried() {
+        let (url, received) = sink(&[503, 429]).await;
+        let notifier = notifier(vec![url.clone()], Vec::new());
+        assert_eq!(notifier.deliver(&url, b"{}").await, Ok(()));
+        assert_eq!(received.lock().unwrap().len(), 3);
+
+        let (url, received) = sink(&[500, 500, 500]).await;
+        let e = notifier.deliver(&url, b"{}").await.unwrap_err();
+        assert_eq!(e, "HTTP 500 Internal Server Error, after 3 attempts");
+        assert_eq!(received.lock().unwrap().len(), 3);
+
+        // A webhook that rejects the request is not asked again.
+        let (url, received) = sink(&[404]).await;
+        let e = notifier.deliver(&url, b"{}").await.unwrap_err();
+        assert_eq!(e, "HTTP 404 Not Found");
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn flapping_notifies_once_each_way() {
+        let (url, received) = sink(&[]).await;
+        let mut notifier = notifier(vec![url], Vec::new());
+        let mut watcher = Watcher::default();
+        watcher.observe("example.com", hybrid());
+        for observation in [
+            unreachable(),
+            hybrid(),
+            unreachable(),
+            hybrid(),
+            unreachable(),
+        ] {
+            for change in watcher.observe("example.com", observation).unwrap() {
+                notifier.notify(&change).await;
+            }
+        }
+        let texts: Vec<Value> = received
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|payload| payload["text"].clone())
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "example.com: unreachable, connection refused",
+                "example.com: unreachable -> reachable",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn notify_on_filters_kinds() {
+        let (url, received) = sink(&[]).await;
+        let mut notifier = notifier(vec![url], vec![ChangeKind::Reachability]);
+        assert!(!notifier.notify(&regression()).await);
+        let outage = Change::new(
+            ChangeKind::Reachability,
+            "example.com",
+            &hybrid(),
+            &unreachable(),
+        );
+        assert!(notifier.notify(&outage).await);
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0]["kind"], "reachability");
+    }
+}